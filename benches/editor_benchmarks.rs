@@ -6,6 +6,7 @@ use editor_core::{Buffer, Document};
 use editor_syntax::Highlighter;
 use editor_plugin::{PluginManager, testing::MockPlugin};
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 /// Benchmarks text operations
 fn bench_text_operations(c: &mut Criterion) {
@@ -164,6 +165,7 @@ fn bench_plugin_operations(c: &mut Criterion) {
                         "test",
                         "test_command",
                         serde_json::json!({"arg": "value"}),
+                        &CancellationToken::new(),
                     ),
                 );
             },
@@ -0,0 +1,377 @@
+//! Editor-wide actions
+//!
+//! Actions are the single list of things a user can do. Keybindings, the
+//! command palette and menus all look actions up here by id, and plugins can
+//! contribute their own.
+
+use crate::{Error, Result};
+use parking_lot::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Maximum number of recently invoked actions remembered
+pub const MAX_RECENT_ACTIONS: usize = 20;
+
+/// Handler invoked with the action's arguments
+pub type ActionHandler = Arc<dyn Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync>;
+
+/// Condition on a context key that gates when an action is available
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Context {
+    /// Context key, e.g. "editorHasSelection"
+    pub key: String,
+    /// True if the key must not be set
+    pub negated: bool,
+}
+
+impl Context {
+    /// Requires the key to be set
+    pub fn has(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            negated: false,
+        }
+    }
+
+    /// Requires the key not to be set
+    pub fn not(key: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            negated: true,
+        }
+    }
+
+    /// Returns true if the condition holds for the given keys
+    pub fn holds(&self, keys: &HashSet<String>) -> bool {
+        keys.contains(&self.key) != self.negated
+    }
+}
+
+/// An action that can be invoked by id
+#[derive(Clone)]
+pub struct Action {
+    /// Unique id, e.g. "editor.sortLines"
+    pub id: String,
+    /// Title shown in the palette and menus
+    pub title: String,
+    /// Category used to group actions, e.g. "Edit"
+    pub category: String,
+    /// Context required for the action to be available
+    pub when: Option<Context>,
+    /// Runs the action
+    handler: ActionHandler,
+}
+
+impl Action {
+    /// Creates a new action that is always available
+    pub fn new<F>(id: impl Into<String>, title: impl Into<String>, category: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(serde_json::Value) -> Result<serde_json::Value> + Send + Sync + 'static,
+    {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            category: category.into(),
+            when: None,
+            handler: Arc::new(handler),
+        }
+    }
+
+    /// Makes the action available only when `context` holds
+    pub fn with_when(mut self, context: Context) -> Self {
+        self.when = Some(context);
+        self
+    }
+
+    /// Returns the label shown in the command palette, e.g. "Edit: Sort Lines"
+    pub fn label(&self) -> String {
+        format!("{}: {}", self.category, self.title)
+    }
+
+    /// Returns true if the action is available for the given context keys
+    pub fn is_available(&self, keys: &HashSet<String>) -> bool {
+        self.when.as_ref().is_none_or(|when| when.holds(keys))
+    }
+}
+
+impl fmt::Debug for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Action")
+            .field("id", &self.id)
+            .field("title", &self.title)
+            .field("category", &self.category)
+            .field("when", &self.when)
+            .finish()
+    }
+}
+
+/// Registry of all editor actions
+#[derive(Default)]
+pub struct ActionRegistry {
+    /// Actions by id
+    actions: RwLock<HashMap<String, Action>>,
+    /// Ids of actions contributed by each plugin
+    contributions: RwLock<HashMap<String, Vec<String>>>,
+    /// Context keys that are currently set
+    context: RwLock<HashSet<String>>,
+    /// Ids of recently invoked actions, most recent first
+    recent: RwLock<Vec<String>>,
+}
+
+impl ActionRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers an action, replacing any action with the same id
+    pub fn register(&self, action: Action) {
+        self.actions.write().insert(action.id.clone(), action);
+    }
+
+    /// Registers an action contributed by a plugin
+    pub fn contribute(&self, plugin: &str, action: Action) {
+        self.contributions.write()
+            .entry(plugin.to_string())
+            .or_default()
+            .push(action.id.clone());
+        self.register(action);
+    }
+
+    /// Removes all actions contributed by a plugin
+    pub fn remove_contributions(&self, plugin: &str) {
+        if let Some(ids) = self.contributions.write().remove(plugin) {
+            let mut actions = self.actions.write();
+            for id in ids {
+                actions.remove(&id);
+            }
+        }
+    }
+
+    /// Removes an action
+    pub fn unregister(&self, id: &str) {
+        self.actions.write().remove(id);
+    }
+
+    /// Returns an action by id
+    pub fn get(&self, id: &str) -> Option<Action> {
+        self.actions.read().get(id).cloned()
+    }
+
+    /// Sets or clears a context key
+    pub fn set_context(&self, key: &str, value: bool) {
+        let mut context = self.context.write();
+        if value {
+            context.insert(key.to_string());
+        } else {
+            context.remove(key);
+        }
+    }
+
+    /// Returns true if the action exists and its context holds
+    pub fn is_available(&self, id: &str) -> bool {
+        let context = self.context.read();
+        self.actions.read().get(id).is_some_and(|action| action.is_available(&context))
+    }
+
+    /// Returns the available actions sorted by category and title, for the command palette
+    pub fn palette(&self) -> Vec<Action> {
+        let context = self.context.read();
+        let mut actions: Vec<Action> = self.actions.read()
+            .values()
+            .filter(|action| action.is_available(&context))
+            .cloned()
+            .collect();
+        actions.sort_by(|a, b| (&a.category, &a.title).cmp(&(&b.category, &b.title)));
+        actions
+    }
+
+    /// Returns the available actions matching `query`, best first, for the
+    /// filtered command palette
+    ///
+    /// With an empty query, recently invoked actions come first, most recent
+    /// first, followed by the rest in palette order. Otherwise actions whose
+    /// "Category: Title" label contains the query's characters in order,
+    /// ignoring case, are ranked by how well they match plus a bonus for
+    /// recent use.
+    pub fn palette_matching(&self, query: &str) -> Vec<Action> {
+        let recent = self.recent.read();
+        let recency = |action: &Action| recent.iter().position(|id| *id == action.id);
+
+        if query.is_empty() {
+            let mut actions = self.palette();
+            actions.sort_by_key(|action| recency(action).unwrap_or(usize::MAX));
+            return actions;
+        }
+
+        let mut scored: Vec<(usize, Action)> = self.palette()
+            .into_iter()
+            .filter_map(|action| {
+                let score = fuzzy_score(&action.label(), query)?;
+                let bonus = recency(&action).map_or(0, |index| (MAX_RECENT_ACTIONS - index) / 4);
+                Some((score + bonus, action))
+            })
+            .collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+
+    /// Returns the ids of recently invoked actions, most recent first
+    pub fn recent(&self) -> Vec<String> {
+        self.recent.read().clone()
+    }
+
+    /// Replaces the recently invoked actions, e.g. with ones from a saved session
+    pub fn set_recent(&self, ids: Vec<String>) {
+        let mut recent = self.recent.write();
+        *recent = ids;
+        recent.truncate(MAX_RECENT_ACTIONS);
+    }
+
+    /// Moves an action to the front of the recent actions
+    fn touch_recent(&self, id: &str) {
+        let mut recent = self.recent.write();
+        recent.retain(|recent_id| recent_id != id);
+        recent.insert(0, id.to_string());
+        recent.truncate(MAX_RECENT_ACTIONS);
+    }
+
+    /// Returns the available actions of a category sorted by title, for menus
+    pub fn menu(&self, category: &str) -> Vec<Action> {
+        self.palette()
+            .into_iter()
+            .filter(|action| action.category == category)
+            .collect()
+    }
+
+    /// Invokes an action by id
+    ///
+    /// Fails if the action does not exist or its context does not hold.
+    pub fn invoke(&self, id: &str, args: serde_json::Value) -> Result<serde_json::Value> {
+        let action = self.get(id)
+            .ok_or_else(|| Error::Action(format!("Unknown action: {}", id)))?;
+
+        if !action.is_available(&self.context.read()) {
+            return Err(Error::Action(format!("Action {} is not available", id)));
+        }
+
+        self.touch_recent(id);
+        (action.handler)(args)
+    }
+}
+
+/// Scores how well `query` fuzzily matches `label`, ignoring case
+///
+/// Returns `None` unless the query's characters appear in order in the label.
+/// Each matched character scores, with extra points when it follows the
+/// previous match or starts a word.
+pub(crate) fn fuzzy_score(label: &str, query: &str) -> Option<usize> {
+    let label: Vec<char> = label.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut next = 0;
+    let mut previous = None;
+
+    for q in query.to_lowercase().chars() {
+        let index = next + label[next..].iter().position(|&c| c == q)?;
+        score += 1;
+        if previous.is_some_and(|previous| previous + 1 == index) {
+            score += 2;
+        }
+        if index == 0 || matches!(label[index - 1], ' ' | ':' | '.' | '_' | '-' | '/') {
+            score += 3;
+        }
+        previous = Some(index);
+        next = index + 1;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(actions: &[Action]) -> Vec<&str> {
+        actions.iter().map(|action| action.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_contributed_action_when_context() {
+        let registry = ActionRegistry::new();
+        registry.register(Action::new("file.save", "Save", "File", |_| Ok(serde_json::Value::Null)));
+        registry.contribute(
+            "case-tools",
+            Action::new("caseTools.upper", "Uppercase Selection", "Edit", |args| {
+                Ok(serde_json::json!(args["text"].as_str().unwrap_or_default().to_uppercase()))
+            })
+            .with_when(Context::has("editorHasSelection")),
+        );
+
+        // Hidden from the palette and menus until there is a selection
+        assert_eq!(ids(&registry.palette()), vec!["file.save"]);
+        assert!(registry.menu("Edit").is_empty());
+        assert!(registry.invoke("caseTools.upper", serde_json::json!({})).is_err());
+
+        registry.set_context("editorHasSelection", true);
+        assert_eq!(ids(&registry.palette()), vec!["caseTools.upper", "file.save"]);
+        assert_eq!(ids(&registry.menu("Edit")), vec!["caseTools.upper"]);
+        assert_eq!(ids(&registry.palette_matching("edit: upper")), vec!["caseTools.upper"]);
+        assert_eq!(ids(&registry.palette_matching("FILE")), vec!["file.save"]);
+        let result = registry.invoke("caseTools.upper", serde_json::json!({"text": "abc"})).unwrap();
+        assert_eq!(result, serde_json::json!("ABC"));
+
+        registry.set_context("editorHasSelection", false);
+        assert!(!registry.is_available("caseTools.upper"));
+
+        // Unloading the plugin removes its actions
+        registry.remove_contributions("case-tools");
+        assert!(registry.get("caseTools.upper").is_none());
+        assert!(registry.get("file.save").is_some());
+    }
+
+    #[test]
+    fn test_recent_actions_first() {
+        let registry = ActionRegistry::new();
+        for (id, title) in [("file.open", "Open"), ("file.save", "Save"), ("view.zoom", "Zoom In")] {
+            registry.register(Action::new(id, title, "File", |_| Ok(serde_json::Value::Null)));
+        }
+        assert_eq!(ids(&registry.palette_matching("")), vec!["file.open", "file.save", "view.zoom"]);
+
+        registry.invoke("view.zoom", serde_json::Value::Null).unwrap();
+        registry.invoke("file.save", serde_json::Value::Null).unwrap();
+        assert_eq!(ids(&registry.palette_matching("")), vec!["file.save", "view.zoom", "file.open"]);
+        assert_eq!(registry.recent(), vec!["file.save", "view.zoom"]);
+
+        // The menu order is unaffected
+        assert_eq!(ids(&registry.menu("File")), vec!["file.open", "file.save", "view.zoom"]);
+
+        // The list is capped
+        registry.set_recent((0..MAX_RECENT_ACTIONS + 5).map(|i| i.to_string()).collect());
+        assert_eq!(registry.recent().len(), MAX_RECENT_ACTIONS);
+    }
+
+    #[test]
+    fn test_recency_in_ranking() {
+        let registry = ActionRegistry::new();
+        registry.register(Action::new("file.save", "Save", "File", |_| Ok(serde_json::Value::Null)));
+        registry.register(Action::new("file.saveAs", "Save As", "File", |_| Ok(serde_json::Value::Null)));
+        registry.register(Action::new("search.replace", "Replace", "Search", |_| Ok(serde_json::Value::Null)));
+
+        assert_eq!(ids(&registry.palette_matching("sav")), vec!["file.save", "file.saveAs"]);
+        assert_eq!(ids(&registry.palette_matching("fsa")), vec!["file.save", "file.saveAs"]);
+
+        registry.invoke("file.saveAs", serde_json::Value::Null).unwrap();
+        assert_eq!(ids(&registry.palette_matching("sav")), vec!["file.saveAs", "file.save"]);
+
+        // Recent actions that do not match are still left out
+        registry.invoke("search.replace", serde_json::Value::Null).unwrap();
+        assert_eq!(ids(&registry.palette_matching("sav")), vec!["file.saveAs", "file.save"]);
+    }
+
+    #[test]
+    fn test_negated_context() {
+        let keys: HashSet<String> = ["readOnly".to_string()].into_iter().collect();
+        assert!(!Context::not("readOnly").holds(&keys));
+        assert!(Context::not("editorHasSelection").holds(&keys));
+    }
+}
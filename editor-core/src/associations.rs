@@ -0,0 +1,149 @@
+//! User-defined mappings from file names to languages
+//!
+//! Associations are consulted before a file's extension when detecting its
+//! language, so files such as `Dockerfile` or project-specific extensions can
+//! be mapped to a language.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Map of file name or glob patterns to language ids
+///
+/// Patterns without a `/` match the file name, e.g. `Dockerfile` or `*.conf`;
+/// patterns with a `/` match the whole path. `*` matches any run of
+/// characters and `?` a single character.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FileAssociations {
+    /// Language ids by pattern
+    patterns: BTreeMap<String, String>,
+}
+
+impl FileAssociations {
+    /// Creates an empty set of associations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an association
+    pub fn with(mut self, pattern: impl Into<String>, language: impl Into<String>) -> Self {
+        self.insert(pattern, language);
+        self
+    }
+
+    /// Adds or replaces an association
+    pub fn insert(&mut self, pattern: impl Into<String>, language: impl Into<String>) {
+        self.patterns.insert(pattern.into(), language.into());
+    }
+
+    /// Removes an association, returning its language id
+    pub fn remove(&mut self, pattern: &str) -> Option<String> {
+        self.patterns.remove(pattern)
+    }
+
+    /// Returns true if there are no associations
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Returns the associated language id for a path, if any
+    ///
+    /// Exact file names win over globs, and longer globs win over shorter ones.
+    pub fn language_for(&self, path: &Path) -> Option<&str> {
+        let name = path.file_name()?.to_str()?;
+        let full = path.to_string_lossy().replace('\\', "/");
+
+        self.patterns.iter()
+            .filter(|(pattern, _)| {
+                let target = if pattern.contains('/') { full.as_str() } else { name };
+                glob_match(pattern, target)
+            })
+            .max_by_key(|(pattern, _)| (!pattern.contains(['*', '?']), pattern.len()))
+            .map(|(_, language)| language.as_str())
+    }
+
+    /// Returns the language of a path, falling back to its extension
+    pub fn detect_language(&self, path: &Path) -> Option<String> {
+        self.language_for(path)
+            .map(String::from)
+            .or_else(|| path.extension()?.to_str().map(String::from))
+    }
+}
+
+/// Returns true if `text` matches a glob with `*` and `?` wildcards
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position of the last `*` and the text position it is matched up to
+    let mut star = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                // Let the last `*` absorb one more character and retry
+                Some((star_p, star_t)) => {
+                    star = Some((star_p, star_t + 1));
+                    p = star_p + 1;
+                    t = star_t + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*.conf", "nginx.conf"));
+        assert!(glob_match("*.conf", ".conf"));
+        assert!(!glob_match("*.conf", "nginx.config"));
+        assert!(glob_match("Dockerfile*", "Dockerfile.dev"));
+        assert!(glob_match("?akefile", "Makefile"));
+        assert!(glob_match("*/ci/*.yml", "/repo/ci/build.yml"));
+        assert!(!glob_match("a*b*c", "a-b-d"));
+    }
+
+    #[test]
+    fn test_language_for() {
+        let associations = FileAssociations::new()
+            .with("*.conf", "ini")
+            .with("*.rs", "rust")
+            .with("build.rs", "rust-build")
+            .with("Dockerfile", "dockerfile")
+            .with("*/.github/*.yml", "github-actions");
+
+        let language = |path: &str| associations.language_for(Path::new(path));
+        assert_eq!(language("/etc/nginx/nginx.conf"), Some("ini"));
+        assert_eq!(language("src/main.rs"), Some("rust"));
+        assert_eq!(language("build.rs"), Some("rust-build"));
+        assert_eq!(language("/repo/Dockerfile"), Some("dockerfile"));
+        assert_eq!(language("/repo/.github/ci.yml"), Some("github-actions"));
+        assert_eq!(language("/repo/ci.yml"), None);
+    }
+
+    #[test]
+    fn test_association_wins_over_extension() {
+        let associations = FileAssociations::new().with("Cargo.lock", "toml");
+
+        let detect = |path: &str| associations.detect_language(Path::new(path));
+        assert_eq!(detect("/repo/Cargo.lock").as_deref(), Some("toml"));
+        assert_eq!(detect("/repo/yarn.lock").as_deref(), Some("lock"));
+        assert_eq!(detect("/repo/.bashrc"), None);
+    }
+}
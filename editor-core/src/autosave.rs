@@ -0,0 +1,96 @@
+//! Scheduling of automatic saves
+
+use crate::clock::{Clock, SystemClock};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Decides when unsaved edits are due to be saved automatically
+///
+/// A save is due once `interval` has passed since the first edit after the
+/// last save, so a steady stream of typing still gets saved regularly.
+#[derive(Debug, Clone)]
+pub struct AutosaveScheduler {
+    /// Time allowed between the first unsaved edit and the save
+    interval: Duration,
+    /// Time source
+    clock: Arc<dyn Clock>,
+    /// Time of the first edit since the last save
+    first_unsaved_edit: Option<Instant>,
+}
+
+impl AutosaveScheduler {
+    /// Creates a scheduler using the system clock
+    pub fn new(interval: Duration) -> Self {
+        Self::with_clock(interval, Arc::new(SystemClock))
+    }
+
+    /// Creates a scheduler using the given clock
+    pub fn with_clock(interval: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            interval,
+            clock,
+            first_unsaved_edit: None,
+        }
+    }
+
+    /// Returns the interval
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sets the interval
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Records an edit; only the first one after a save starts the interval
+    pub fn note_edit(&mut self) {
+        if self.first_unsaved_edit.is_none() {
+            self.first_unsaved_edit = Some(self.clock.now());
+        }
+    }
+
+    /// Records a save, manual or automatic
+    pub fn note_saved(&mut self) {
+        self.first_unsaved_edit = None;
+    }
+
+    /// Returns the time left until a save is due, or `None` if nothing is unsaved
+    pub fn time_until_due(&self) -> Option<Duration> {
+        self.first_unsaved_edit
+            .map(|edit| self.interval.saturating_sub(self.clock.now().saturating_duration_since(edit)))
+    }
+
+    /// Returns true if unsaved edits should be saved now
+    pub fn is_due(&self) -> bool {
+        self.time_until_due() == Some(Duration::ZERO)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+
+    #[test]
+    fn test_autosave_due_after_interval() {
+        let clock = Arc::new(MockClock::new());
+        let mut scheduler = AutosaveScheduler::with_clock(Duration::from_secs(30), clock.clone());
+        assert!(!scheduler.is_due());
+        assert_eq!(scheduler.time_until_due(), None);
+
+        // Later edits do not push the save back
+        scheduler.note_edit();
+        clock.advance(Duration::from_secs(20));
+        scheduler.note_edit();
+        assert!(!scheduler.is_due());
+        assert_eq!(scheduler.time_until_due(), Some(Duration::from_secs(10)));
+
+        clock.advance(Duration::from_secs(10));
+        assert!(scheduler.is_due());
+
+        scheduler.note_saved();
+        clock.advance(Duration::from_secs(60));
+        assert!(!scheduler.is_due());
+    }
+}
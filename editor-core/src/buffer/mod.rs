@@ -2,10 +2,37 @@
 //!
 //! Provides efficient text buffer implementation using rope data structure
 
-use ropey::Rope;
+use ropey::{Rope, RopeSlice};
 use parking_lot::RwLock;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
-use crate::Result;
+use unicode_segmentation::{GraphemeCursor, GraphemeIncomplete};
+use crate::document::Encoding;
+use crate::syntax_edit::SyntaxPoint;
+use crate::vfs::{FileMetadata, StdFs, VirtualFs};
+use crate::{Error, Result};
+
+/// Text and lines touched by an edit
+///
+/// Ranges are in the text after the edit, except `old_line_range`, so the UI
+/// can invalidate the layout of just the affected lines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditSummary {
+    /// Byte range of the inserted text, empty for deletions
+    pub byte_range: std::ops::Range<usize>,
+    /// Lines spanned by the edited text after the edit
+    pub line_range: std::ops::Range<usize>,
+    /// Lines spanned by the edited text before the edit
+    pub old_line_range: std::ops::Range<usize>,
+}
+
+impl EditSummary {
+    /// Returns the change in the number of lines
+    pub fn line_delta(&self) -> isize {
+        self.line_range.len() as isize - self.old_line_range.len() as isize
+    }
+}
 
 /// Represents a text buffer with efficient manipulation capabilities
 pub struct Buffer {
@@ -15,6 +42,15 @@ pub struct Buffer {
     path: Option<std::path::PathBuf>,
     /// Flag indicating if the buffer has unsaved changes
     dirty: bool,
+    /// File system used to load and save the buffer
+    fs: Arc<dyn VirtualFs>,
+    /// Metadata of the file when it last held exactly the buffer's bytes,
+    /// after loading or saving
+    saved_disk: Option<FileMetadata>,
+    /// Byte range of the text covering every edit since then
+    unsaved: Option<std::ops::Range<usize>>,
+    /// Encoding the file is written in
+    encoding: Encoding,
 }
 
 impl Buffer {
@@ -24,6 +60,10 @@ impl Buffer {
             content: Arc::new(RwLock::new(Rope::new())),
             path: None,
             dirty: false,
+            fs: Arc::new(StdFs),
+            saved_disk: None,
+            unsaved: None,
+            encoding: Encoding::Utf8,
         }
     }
 
@@ -33,18 +73,63 @@ impl Buffer {
             content: Arc::new(RwLock::new(Rope::from_str(text))),
             path: None,
             dirty: false,
+            fs: Arc::new(StdFs),
+            saved_disk: None,
+            unsaved: None,
+            encoding: Encoding::Utf8,
         }
     }
 
     /// Creates a new buffer from a file
     pub fn from_file(path: impl Into<std::path::PathBuf>) -> Result<Self> {
+        Self::from_file_in(Arc::new(StdFs), path)
+    }
+
+    /// Creates a new buffer from a file in the given file system
+    pub fn from_file_in(fs: Arc<dyn VirtualFs>, path: impl Into<std::path::PathBuf>) -> Result<Self> {
         let path = path.into();
-        let text = std::fs::read_to_string(&path)?;
-        Ok(Self {
-            content: Arc::new(RwLock::new(Rope::from_str(&text))),
+        let text = fs.read_to_string(&path)?;
+        Ok(Self::from_loaded(fs, path, &text))
+    }
+
+    /// Creates a buffer for text already read from a file
+    pub(crate) fn from_loaded(fs: Arc<dyn VirtualFs>, path: std::path::PathBuf, text: &str) -> Self {
+        // Decoded text, e.g. from UTF-16, is not the file's bytes
+        let saved_disk = fs.metadata(&path).ok().filter(|disk| disk.len == text.len() as u64);
+        Self {
+            content: Arc::new(RwLock::new(Rope::from_str(text))),
             path: Some(path),
             dirty: false,
-        })
+            fs,
+            saved_disk,
+            unsaved: None,
+            encoding: Encoding::Utf8,
+        }
+    }
+
+    /// Returns the path of the file associated with this buffer, if any
+    pub fn path(&self) -> Option<&std::path::Path> {
+        self.path.as_deref()
+    }
+
+    /// Associates the buffer with a file in the given file system
+    ///
+    /// The next `save` writes to this file.
+    pub fn set_file(&mut self, fs: Arc<dyn VirtualFs>, path: impl Into<std::path::PathBuf>) {
+        self.fs = fs;
+        self.path = Some(path.into());
+        self.dirty = true;
+        self.saved_disk = None;
+    }
+
+    /// Returns the encoding the file is written in
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Sets the encoding the file is written in
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.encoding = encoding;
     }
 
     /// Returns the current content of the buffer as a string
@@ -53,19 +138,240 @@ impl Buffer {
     }
 
     /// Inserts text at the specified byte offset
-    pub fn insert(&mut self, offset: usize, text: &str) -> Result<()> {
+    pub fn insert(&mut self, offset: usize, text: &str) -> Result<EditSummary> {
         let mut content = self.content.write();
-        content.insert(offset, text);
+        let index = Self::char_index(&content, offset)?;
+        let line = content.char_to_line(index);
+        content.insert(index, text);
         self.dirty = true;
-        Ok(())
+        Self::track_edit(&mut self.unsaved, offset..offset, text.len());
+
+        let end = offset + text.len();
+        Ok(EditSummary {
+            byte_range: offset..end,
+            line_range: line..content.byte_to_line(end) + 1,
+            old_line_range: line..line + 1,
+        })
     }
 
     /// Deletes text in the specified byte range
-    pub fn delete(&mut self, start: usize, end: usize) -> Result<()> {
+    pub fn delete(&mut self, start: usize, end: usize) -> Result<EditSummary> {
         let mut content = self.content.write();
-        content.remove(start..end);
+        let range = Self::char_range(&content, start, end)?;
+        let lines = content.char_to_line(range.start)..content.char_to_line(range.end) + 1;
+        content.remove(range);
         self.dirty = true;
-        Ok(())
+        Self::track_edit(&mut self.unsaved, start..end, 0);
+
+        Ok(EditSummary {
+            byte_range: start..start,
+            line_range: lines.start..lines.start + 1,
+            old_line_range: lines,
+        })
+    }
+
+    /// Replaces the text in the specified byte range
+    ///
+    /// The deletion and insertion happen under one lock, so views sharing
+    /// the buffer see either the old or the new text, never the range deleted.
+    pub fn replace(&mut self, start: usize, end: usize, text: &str) -> Result<EditSummary> {
+        let mut content = self.content.write();
+        let range = Self::char_range(&content, start, end)?;
+        let lines = content.char_to_line(range.start)..content.char_to_line(range.end) + 1;
+        content.remove(range.clone());
+        content.insert(range.start, text);
+        self.dirty = true;
+        Self::track_edit(&mut self.unsaved, start..end, text.len());
+
+        let new_end = start + text.len();
+        Ok(EditSummary {
+            byte_range: start..new_end,
+            line_range: lines.start..content.byte_to_line(new_end) + 1,
+            old_line_range: lines,
+        })
+    }
+
+    /// Extends the unsaved range to cover `range` being replaced by `new_len` bytes
+    fn track_edit(unsaved: &mut Option<std::ops::Range<usize>>, range: std::ops::Range<usize>, new_len: usize) {
+        let shift = |offset: usize| {
+            if offset < range.start {
+                offset
+            } else if offset >= range.end {
+                offset - range.len() + new_len
+            } else {
+                range.start
+            }
+        };
+        let edited = range.start..range.start + new_len;
+        *unsaved = Some(match unsaved.take() {
+            Some(previous) => shift(previous.start).min(edited.start)..shift(previous.end).max(edited.end),
+            None => edited,
+        });
+    }
+
+    /// Returns the text in the specified byte range
+    pub fn slice(&self, start: usize, end: usize) -> Result<String> {
+        let content = self.content.read();
+        let range = Self::char_range(&content, start, end)?;
+        Ok(content.slice(range).to_string())
+    }
+
+    /// Returns the character starting at a byte offset
+    ///
+    /// Returns `None` past the end of the text, or if the offset is inside a
+    /// character.
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        let content = self.content.read();
+        if !Self::is_char_start(&content, offset) {
+            return None;
+        }
+        Some(content.char(content.byte_to_char(offset)))
+    }
+
+    /// Returns the grapheme cluster, the character as the user sees it,
+    /// starting at a byte offset
+    ///
+    /// Returns `None` past the end of the text, or if the offset is inside a
+    /// cluster, e.g. before a combining accent.
+    pub fn grapheme_at(&self, offset: usize) -> Option<String> {
+        let content = self.content.read();
+        if !Self::is_char_start(&content, offset) || !Self::is_grapheme_boundary(&content, offset) {
+            return None;
+        }
+        let end = Self::next_grapheme_boundary(&content, offset);
+        Some(content.byte_slice(offset..end).to_string())
+    }
+
+    /// Returns true if a character of the text starts at `offset`
+    fn is_char_start(content: &Rope, offset: usize) -> bool {
+        offset < content.len_bytes() && content.char_to_byte(content.byte_to_char(offset)) == offset
+    }
+
+    /// Returns true if a grapheme cluster starts or ends at `offset`, a
+    /// character boundary
+    fn is_grapheme_boundary(content: &Rope, offset: usize) -> bool {
+        let (chunk, chunk_start, _, _) = content.chunk_at_byte(offset);
+        let mut cursor = GraphemeCursor::new(offset, content.len_bytes(), true);
+        loop {
+            match cursor.is_boundary(chunk, chunk_start) {
+                Ok(boundary) => return boundary,
+                Err(GraphemeIncomplete::PreContext(end)) => {
+                    let (context, context_start, _, _) = content.chunk_at_byte(end - 1);
+                    cursor.provide_context(context, context_start);
+                }
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Returns the end of the grapheme cluster starting at `offset`
+    fn next_grapheme_boundary(content: &Rope, offset: usize) -> usize {
+        let (mut chunk, mut chunk_start, _, _) = content.chunk_at_byte(offset);
+        let mut cursor = GraphemeCursor::new(offset, content.len_bytes(), true);
+        loop {
+            match cursor.next_boundary(chunk, chunk_start) {
+                Ok(boundary) => return boundary.unwrap_or(content.len_bytes()),
+                Err(GraphemeIncomplete::NextChunk) => {
+                    chunk_start += chunk.len();
+                    chunk = content.chunk_at_byte(chunk_start).0;
+                }
+                Err(GraphemeIncomplete::PreContext(end)) => {
+                    let (context, context_start, _, _) = content.chunk_at_byte(end - 1);
+                    cursor.provide_context(context, context_start);
+                }
+                Err(_) => return content.len_bytes(),
+            }
+        }
+    }
+
+    /// Returns the number of lines
+    ///
+    /// Text ending with a line break has an empty last line, as shown in the
+    /// editor.
+    pub fn line_count(&self) -> usize {
+        self.content.read().len_lines()
+    }
+
+    /// Returns the text of a line without its line break
+    ///
+    /// Only the requested line is copied out of the rope.
+    pub fn line(&self, index: usize) -> Option<String> {
+        self.content.read().get_line(index).map(Self::line_text)
+    }
+
+    /// Returns the text of the lines in `start..end` without their line breaks
+    ///
+    /// The range is clamped to the buffer, so it can be the visible rows of a
+    /// viewport that extends past the last line.
+    pub fn lines_range(&self, start: usize, end: usize) -> Vec<String> {
+        let content = self.content.read();
+        let end = end.min(content.len_lines());
+        if start >= end {
+            return Vec::new();
+        }
+        content.lines_at(start).take(end - start).map(Self::line_text).collect()
+    }
+
+    /// Returns the indices of the lines with more than `column` characters,
+    /// not counting line breaks
+    pub fn lines_exceeding(&self, column: usize) -> Vec<usize> {
+        self.content.read()
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| line.len_chars() - Self::line_break_chars(*line) > column)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Returns the number of characters in a rope line's line break
+    fn line_break_chars(line: RopeSlice) -> usize {
+        let len = line.len_chars();
+        if len >= 2 && line.char(len - 2) == '\r' && line.char(len - 1) == '\n' {
+            2
+        } else if len >= 1 && matches!(line.char(len - 1), '\n' | '\r' | '\u{0B}' | '\u{0C}' | '\u{85}' | '\u{2028}' | '\u{2029}') {
+            1
+        } else {
+            0
+        }
+    }
+
+    /// Converts a rope line to a string, dropping its line break
+    fn line_text(line: RopeSlice) -> String {
+        let mut text = line.to_string();
+        if text.ends_with("\r\n") {
+            text.truncate(text.len() - 2);
+        } else if text.ends_with(['\n', '\r', '\u{0B}', '\u{0C}', '\u{85}', '\u{2028}', '\u{2029}']) {
+            text.pop();
+        }
+        text
+    }
+
+    /// Returns the row and byte column of a byte offset
+    ///
+    /// Rows are counted at `\n` only, matching `SyntaxPoint`.
+    pub fn point(&self, offset: usize) -> Result<SyntaxPoint> {
+        let content = self.content.read();
+        Self::char_index(&content, offset)?;
+
+        let mut point = SyntaxPoint::default();
+        for chunk in content.byte_slice(..offset).chunks() {
+            point = point.advance(chunk);
+        }
+        Ok(point)
+    }
+
+    /// Converts a byte offset to a char index
+    fn char_index(content: &Rope, offset: usize) -> Result<usize> {
+        content.try_byte_to_char(offset)
+            .map_err(|e| Error::Buffer(format!("Invalid offset {}: {}", offset, e)))
+    }
+
+    /// Converts a byte range to a char range
+    fn char_range(content: &Rope, start: usize, end: usize) -> Result<std::ops::Range<usize>> {
+        if start > end {
+            return Err(Error::Buffer(format!("Invalid range {}..{}", start, end)));
+        }
+        Ok(Self::char_index(content, start)?..Self::char_index(content, end)?)
     }
 
     /// Returns the length of the buffer in bytes
@@ -83,14 +389,111 @@ impl Buffer {
         self.dirty
     }
 
-    /// Saves the buffer content to its associated file
+    /// Returns the metadata of the associated file, if any
+    pub fn file_metadata(&self) -> Result<Option<FileMetadata>> {
+        match &self.path {
+            Some(path) => Ok(Some(self.fs.metadata(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Saves the buffer content to its associated file, in its encoding
     pub fn save(&mut self) -> Result<()> {
         if let Some(path) = &self.path {
-            std::fs::write(path, self.text())?;
-            self.dirty = false;
+            self.fs.write_bytes(path, &self.encoding.encode(&self.text()))?;
+            self.mark_saved();
         }
         Ok(())
     }
+
+    /// Saves only the bytes changed since the file was loaded or last saved,
+    /// when that is safe, and the whole buffer otherwise
+    ///
+    /// The file is patched in place with `VirtualFs::write_at` only if:
+    /// - its size and modification time are unchanged since then, and it held
+    ///   exactly the buffer's bytes, i.e. it was UTF-8 and not decoded;
+    /// - the edits only appended to the end, or left the length unchanged so
+    ///   every byte outside the edited range is still at the same offset.
+    ///
+    /// Edits that change the length before the end, and file systems without
+    /// `write_at`, rewrite the whole file. A failure part way leaves the file
+    /// partly written, as a failed full rewrite would. Returns true if the
+    /// file was patched in place.
+    pub fn save_incremental(&mut self) -> Result<bool> {
+        let (Some(path), Some(range)) = (self.path.clone(), self.incremental_range()) else {
+            self.save()?;
+            return Ok(false);
+        };
+        let text = self.slice(range.start, range.end)?;
+        match self.fs.write_at(&path, range.start as u64, &text) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => {
+                self.save()?;
+                return Ok(false);
+            }
+            Err(e) => return Err(e.into()),
+        }
+        self.mark_saved();
+        Ok(true)
+    }
+
+    /// Returns the range of the text to write at the same offset in the file
+    /// to bring it up to date, if the file can be patched in place
+    fn incremental_range(&self) -> Option<std::ops::Range<usize>> {
+        if self.encoding != Encoding::Utf8 {
+            return None;
+        }
+        let path = self.path.as_ref()?;
+        let saved = self.saved_disk.as_ref()?;
+        if self.fs.metadata(path).ok().as_ref() != Some(saved) {
+            return None;
+        }
+
+        let saved_len = saved.len as usize;
+        let len = self.len();
+        let unsaved = self.unsaved.clone().unwrap_or(len..len);
+        if len == saved_len {
+            Some(unsaved)
+        } else if len > saved_len && unsaved.start >= saved_len {
+            Some(saved_len..len)
+        } else {
+            None
+        }
+    }
+
+    /// Records that the file now holds exactly the buffer's bytes
+    pub(crate) fn mark_saved(&mut self) {
+        self.dirty = false;
+        self.unsaved = None;
+        self.saved_disk = self.file_metadata().ok().flatten();
+    }
+
+    /// Reads the bytes of the associated file, or `None` without a file
+    pub fn read_file(&self) -> Result<Option<Vec<u8>>> {
+        match &self.path {
+            Some(path) => Ok(Some(self.fs.read_bytes(path)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the associated file back and checks it matches the buffer
+    ///
+    /// Compares hashes of the file and buffer bytes. Returns true if the
+    /// buffer has no file.
+    pub fn verify_saved(&self) -> Result<bool> {
+        let Some(path) = &self.path else {
+            return Ok(true);
+        };
+        let on_disk = self.fs.read_bytes(path)?;
+        Ok(Self::content_hash(&on_disk) == Self::content_hash(&self.encoding.encode(&self.text())))
+    }
+
+    /// Hashes content for comparing saved and in-memory text
+    fn content_hash(bytes: &[u8]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        bytes.hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 impl Default for Buffer {
@@ -102,6 +505,7 @@ impl Default for Buffer {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::vfs::MemoryFs;
 
     #[test]
     fn test_buffer_operations() {
@@ -125,4 +529,125 @@ mod tests {
         // Test dirty flag
         assert!(buffer.is_dirty());
     }
+
+    #[test]
+    fn test_byte_offsets() {
+        let mut buffer = Buffer::from_text("héllo");
+
+        // "é" is two bytes, so "llo" starts at byte 3
+        assert_eq!(buffer.slice(3, 6).unwrap(), "llo");
+        buffer.insert(3, "-").unwrap();
+        assert_eq!(buffer.text(), "hé-llo");
+        buffer.delete(1, 3).unwrap();
+        assert_eq!(buffer.text(), "h-llo");
+
+        assert!(buffer.delete(3, 1).is_err());
+        assert!(buffer.insert(100, "x").is_err());
+    }
+
+    #[test]
+    fn test_edit_summary() {
+        let mut buffer = Buffer::from_text("one\ntwo\nthree");
+
+        let summary = buffer.insert(5, "wo, t").unwrap();
+        assert_eq!(summary.byte_range, 5..10);
+        assert_eq!(summary.line_range, 1..2);
+        assert_eq!(summary.old_line_range, 1..2);
+        assert_eq!(summary.line_delta(), 0);
+
+        // A paste spanning lines touches every line it creates
+        let summary = buffer.insert(3, "\na\nb").unwrap();
+        assert_eq!(buffer.text(), "one\na\nb\ntwo, two\nthree");
+        assert_eq!(summary.line_range, 0..3);
+        assert_eq!(summary.old_line_range, 0..1);
+        assert_eq!(summary.line_delta(), 2);
+
+        let summary = buffer.delete(3, 7).unwrap();
+        assert_eq!(buffer.text(), "one\ntwo, two\nthree");
+        assert_eq!(summary.byte_range, 3..3);
+        assert_eq!(summary.line_range, 0..1);
+        assert_eq!(summary.old_line_range, 0..3);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut buffer = Buffer::from_text("one\ntwo\nthree");
+
+        let summary = buffer.replace(4, 7, "2\n2.5").unwrap();
+        assert_eq!(buffer.text(), "one\n2\n2.5\nthree");
+        assert_eq!(summary.byte_range, 4..9);
+        assert_eq!(summary.line_range, 1..3);
+        assert_eq!(summary.old_line_range, 1..2);
+
+        assert!(buffer.replace(5, 4, "x").is_err());
+        assert!(buffer.replace(0, 100, "x").is_err());
+    }
+
+    #[test]
+    fn test_replace_is_atomic_for_readers() {
+        let mut buffer = Buffer::from_text("aaaa");
+        let content = buffer.content.clone();
+        let done = Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let reader = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(std::sync::atomic::Ordering::Relaxed) {
+                    let text = content.read().to_string();
+                    assert!(text == "aaaa" || text == "bbbb", "observed {:?}", text);
+                }
+            })
+        };
+        for i in 0..2000 {
+            let text = if i % 2 == 0 { "bbbb" } else { "aaaa" };
+            buffer.replace(0, 4, text).unwrap();
+        }
+        done.store(true, std::sync::atomic::Ordering::Relaxed);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_lines() {
+        // Large enough for the rope to split into several chunks
+        let text: String = (0..2000).map(|i| format!("line {}\n", i)).collect::<String>() + "last";
+        let buffer = Buffer::from_text(&text);
+        assert!(buffer.content.read().chunks().count() > 1);
+
+        assert_eq!(buffer.line_count(), 2001);
+        assert_eq!(buffer.line(1234).as_deref(), Some("line 1234"));
+        assert_eq!(buffer.line(2000).as_deref(), Some("last"));
+        assert_eq!(buffer.line(2001), None);
+
+        assert_eq!(buffer.lines_range(998, 1001), vec!["line 998", "line 999", "line 1000"]);
+        assert_eq!(buffer.lines_range(1999, 2050), vec!["line 1999", "last"]);
+        assert!(buffer.lines_range(3000, 3010).is_empty());
+    }
+
+    #[test]
+    fn test_line_breaks() {
+        let buffer = Buffer::from_text("crlf\r\nlf\n\nend\n");
+        assert_eq!(buffer.lines_range(0, 10), vec!["crlf", "lf", "", "end", ""]);
+    }
+
+    #[test]
+    fn test_memory_backed_buffer() {
+        let fs = Arc::new(MemoryFs::new().with_file("/notes.txt", "Hello"));
+
+        let mut buffer = Buffer::from_file_in(fs.clone(), "/notes.txt").unwrap();
+        assert_eq!(buffer.text(), "Hello");
+        assert!(!buffer.is_dirty());
+
+        buffer.insert(5, ", World").unwrap();
+        buffer.save().unwrap();
+        assert!(!buffer.is_dirty());
+        assert_eq!(fs.get("/notes.txt").as_deref(), Some("Hello, World"));
+
+        // Scratch buffers can be given an in-memory file later
+        let mut scratch = Buffer::from_text("scratch");
+        scratch.set_file(fs.clone(), "/untitled-1");
+        scratch.save().unwrap();
+        assert_eq!(fs.get("/untitled-1").as_deref(), Some("scratch"));
+
+        assert!(Buffer::from_file_in(fs, "/missing.txt").is_err());
+    }
 }
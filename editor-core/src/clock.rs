@@ -0,0 +1,80 @@
+//! Time sources for time-dependent behavior
+//!
+//! Code that coalesces edits, expires messages or schedules saves asks a
+//! `Clock` for the time instead of calling `Instant::now` directly, so tests
+//! can drive it with a `MockClock`.
+
+use parking_lot::Mutex;
+use std::fmt;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Source of the current time
+pub trait Clock: Send + Sync + fmt::Debug {
+    /// Returns the current monotonic time
+    fn now(&self) -> Instant;
+
+    /// Returns the current wall-clock time
+    fn system_time(&self) -> SystemTime;
+}
+
+/// Clock reading the system time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Clock that only moves when told to, for tests
+#[derive(Debug)]
+pub struct MockClock {
+    /// Monotonic time when the clock was created
+    start: Instant,
+    /// Wall-clock time when the clock was created
+    start_system: SystemTime,
+    /// Time advanced since creation
+    elapsed: Mutex<Duration>,
+}
+
+impl MockClock {
+    /// Creates a clock stopped at the current time
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            start_system: SystemTime::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        }
+    }
+
+    /// Moves the clock forward
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock() += duration;
+    }
+
+    /// Returns the time advanced since the clock was created
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock()
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system_time(&self) -> SystemTime {
+        self.start_system + self.elapsed()
+    }
+}
@@ -0,0 +1,131 @@
+//! Completion items contributed by plugins
+//!
+//! Language servers answer completion requests with their own item type;
+//! plugins, e.g. snippet or dictionary plugins, return `CompletionItem`s for
+//! a `CompletionContext`, and the two lists are merged for display.
+
+use crate::document::Document;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// Where completions were requested
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CompletionContext {
+    /// Path of the document, if it has one
+    pub path: Option<PathBuf>,
+    /// Language of the document, if known
+    pub language: Option<String>,
+    /// Byte offset of the cursor
+    pub offset: usize,
+    /// Text of the cursor's line before the cursor
+    pub line_before_cursor: String,
+    /// Word being typed before the cursor, empty after e.g. a `.`
+    pub prefix: String,
+}
+
+impl CompletionContext {
+    /// Creates the context for completions at byte `offset` of a document
+    ///
+    /// The offset is clamped to the text and moved back to a character
+    /// boundary.
+    pub fn from_document(doc: &Document, offset: usize) -> Self {
+        let text = doc.text();
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line_start = text[..offset].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_before_cursor = text[line_start..offset].to_string();
+        let prefix_start = line_before_cursor
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .last()
+            .map_or(line_before_cursor.len(), |(index, _)| index);
+
+        Self {
+            path: doc.path().map(PathBuf::from),
+            language: doc.language().map(str::to_string),
+            offset,
+            prefix: line_before_cursor[prefix_start..].to_string(),
+            line_before_cursor,
+        }
+    }
+}
+
+/// A completion offered by a plugin
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CompletionItem {
+    /// Text shown in the list, and inserted unless `insert_text` is set
+    pub label: String,
+    /// Extra information shown next to the label
+    #[serde(default)]
+    pub detail: Option<String>,
+    /// Text inserted when the item is chosen
+    #[serde(default)]
+    pub insert_text: Option<String>,
+    /// Name of the plugin that offered the item, set by the plugin manager
+    #[serde(default)]
+    pub source: Option<String>,
+}
+
+impl CompletionItem {
+    /// Creates an item inserting its label
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            detail: None,
+            insert_text: None,
+            source: None,
+        }
+    }
+
+    /// Sets the extra information shown next to the label
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    /// Sets the text inserted instead of the label
+    pub fn with_insert_text(mut self, insert_text: impl Into<String>) -> Self {
+        self.insert_text = Some(insert_text.into());
+        self
+    }
+
+    /// Returns the text inserted when the item is chosen
+    pub fn text_to_insert(&self) -> &str {
+        self.insert_text.as_deref().unwrap_or(&self.label)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::MemoryFs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_completion_context_at_cursor() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\n    let x = self.pus\n}").unwrap();
+
+        let offset = doc.text().find("pus").unwrap() + 3;
+        let context = CompletionContext::from_document(&doc, offset);
+        assert_eq!(context.line_before_cursor, "    let x = self.pus");
+        assert_eq!(context.prefix, "pus");
+        assert_eq!(context.offset, offset);
+
+        // Right after a trigger character nothing is typed yet
+        let context = CompletionContext::from_document(&doc, offset - 3);
+        assert_eq!(context.prefix, "");
+        assert_eq!(CompletionContext::from_document(&doc, 1000).offset, doc.text().len());
+
+        let fs = Arc::new(MemoryFs::new().with_file("/src/lib.rs", "us"));
+        let doc = Document::from_file_in(fs, "/src/lib.rs").unwrap();
+        let context = CompletionContext::from_document(&doc, 2);
+        assert_eq!(context.path, Some(PathBuf::from("/src/lib.rs")));
+        assert_eq!(context.language.as_deref(), Some("rs"));
+        assert_eq!(context.prefix, "us");
+        assert_eq!(CompletionItem::new("use").with_insert_text("use ").text_to_insert(), "use ");
+    }
+}
@@ -0,0 +1,424 @@
+//! Diagnostics contributed by linters, plugins and language servers
+//!
+//! Providers report problems as byte ranges; the `DiagnosticRunner` turns the
+//! merged reports into `MarkerType::Diagnostic` markers on each document.
+
+use crate::document::Document;
+use crate::markers::{DiagnosticSeverity, MarkerType};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default quiet period after an edit before diagnostics are refreshed
+pub const DEFAULT_DIAGNOSTIC_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Prefix of the names of markers created by the runner
+const MARKER_PREFIX: &str = "diagnostic:";
+
+/// A problem reported for a range of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Byte range the diagnostic applies to
+    pub range: Range<usize>,
+    /// Severity level
+    pub severity: DiagnosticSeverity,
+    /// Message shown to the user
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Creates a new diagnostic
+    pub fn new(range: Range<usize>, severity: DiagnosticSeverity, message: impl Into<String>) -> Self {
+        Self {
+            range,
+            severity,
+            message: message.into(),
+        }
+    }
+}
+
+/// Source of diagnostics for documents
+pub trait DiagnosticProvider: Send + Sync {
+    /// Returns the provider name, used as the source of its markers
+    fn name(&self) -> &str;
+
+    /// Returns the diagnostics for the current text of a document
+    fn diagnostics(&self, doc: &Document) -> Vec<Diagnostic>;
+}
+
+/// Built-in provider reporting whitespace at the end of lines
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrailingWhitespace;
+
+impl TrailingWhitespace {
+    /// Name of the provider
+    pub const NAME: &'static str = "trailing-whitespace";
+}
+
+impl DiagnosticProvider for TrailingWhitespace {
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+
+    fn diagnostics(&self, doc: &Document) -> Vec<Diagnostic> {
+        let text = doc.text();
+        let mut diagnostics = Vec::new();
+        let mut line_start = 0;
+
+        for line in text.split_inclusive('\n') {
+            let content = line.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_end_matches([' ', '\t']);
+            if trimmed.len() < content.len() {
+                diagnostics.push(Diagnostic::new(
+                    line_start + trimmed.len()..line_start + content.len(),
+                    DiagnosticSeverity::Warning,
+                    "Trailing whitespace",
+                ));
+            }
+            line_start += line.len();
+        }
+        diagnostics
+    }
+}
+
+/// A registered provider
+#[derive(Clone)]
+struct Registration {
+    /// Unique id, used in the names of the provider's markers
+    id: u64,
+    /// Plugin that registered the provider, if any
+    plugin: Option<String>,
+    /// The provider
+    provider: Arc<dyn DiagnosticProvider>,
+}
+
+impl Registration {
+    /// Returns true if this is the provider `name` registered by `plugin`
+    fn is(&self, plugin: Option<&str>, name: &str) -> bool {
+        self.plugin.as_deref() == plugin && self.provider.name() == name
+    }
+}
+
+/// Diagnostic providers keyed by language
+///
+/// Providers registered without a language apply to every document, and
+/// are identified by their name together with the plugin that registered
+/// them. The registry is shared, so plugins and language servers can add
+/// providers while the editor runs.
+#[derive(Default)]
+pub struct DiagnosticRegistry {
+    /// Providers by language, `None` for all languages
+    providers: RwLock<HashMap<Option<String>, Vec<Registration>>>,
+    /// Bumped on every change, so runners know to refresh
+    generation: AtomicU64,
+    /// Id of the next registration
+    next_id: AtomicU64,
+}
+
+impl DiagnosticRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a provider for a language, or for all languages with `None`
+    ///
+    /// Replaces an earlier provider with the same name that was not
+    /// registered by a plugin.
+    pub fn register(&self, language: Option<&str>, provider: Arc<dyn DiagnosticProvider>) {
+        self.insert(None, language, provider);
+    }
+
+    /// Registers a provider on behalf of a plugin
+    ///
+    /// Replaces an earlier provider of the plugin with the same name.
+    pub fn register_for_plugin(&self, plugin: &str, language: Option<&str>, provider: Arc<dyn DiagnosticProvider>) {
+        self.insert(Some(plugin), language, provider);
+    }
+
+    fn insert(&self, plugin: Option<&str>, language: Option<&str>, provider: Arc<dyn DiagnosticProvider>) {
+        let mut providers = self.providers.write();
+        let name = provider.name().to_string();
+        for registrations in providers.values_mut() {
+            registrations.retain(|registration| !registration.is(plugin, &name));
+        }
+        providers.retain(|_, registrations| !registrations.is_empty());
+
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        providers
+            .entry(language.map(str::to_string))
+            .or_default()
+            .push(Registration { id, plugin: plugin.map(str::to_string), provider });
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Removes the provider with the given name that was not registered by a
+    /// plugin
+    ///
+    /// Returns true if a provider was removed.
+    pub fn unregister(&self, name: &str) -> bool {
+        self.remove_where(|registration| registration.is(None, name))
+    }
+
+    /// Removes a plugin's provider with the given name
+    ///
+    /// Returns true if a provider was removed.
+    pub fn unregister_for_plugin(&self, plugin: &str, name: &str) -> bool {
+        self.remove_where(|registration| registration.is(Some(plugin), name))
+    }
+
+    /// Removes all providers registered by a plugin
+    pub fn remove_plugin(&self, plugin: &str) {
+        self.remove_where(|registration| registration.plugin.as_deref() == Some(plugin));
+    }
+
+    fn remove_where(&self, matches: impl Fn(&Registration) -> bool) -> bool {
+        let mut providers = self.providers.write();
+        let before: usize = providers.values().map(Vec::len).sum();
+        for registrations in providers.values_mut() {
+            registrations.retain(|registration| !matches(registration));
+        }
+        providers.retain(|_, registrations| !registrations.is_empty());
+
+        let removed = providers.values().map(Vec::len).sum::<usize>() < before;
+        if removed {
+            self.generation.fetch_add(1, Ordering::SeqCst);
+        }
+        removed
+    }
+
+    /// Returns the providers applying to a language
+    pub fn providers_for(&self, language: Option<&str>) -> Vec<Arc<dyn DiagnosticProvider>> {
+        self.registrations_for(language)
+            .into_iter()
+            .map(|registration| registration.provider)
+            .collect()
+    }
+
+    fn registrations_for(&self, language: Option<&str>) -> Vec<Registration> {
+        let providers = self.providers.read();
+        let global = providers.get(&None).into_iter().flatten();
+        let specific = language
+            .and_then(|language| providers.get(&Some(language.to_string())))
+            .into_iter()
+            .flatten();
+        global.chain(specific).cloned().collect()
+    }
+
+    /// Returns a counter that changes whenever providers are added or removed
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    /// Replaces the diagnostic markers of a document with the merged output of
+    /// its providers
+    pub fn apply(&self, doc: &mut Document) {
+        let registrations = self.registrations_for(doc.language());
+        let results: Vec<_> = registrations
+            .iter()
+            .map(|registration| {
+                let provider = &registration.provider;
+                (registration.id, provider.name().to_string(), provider.diagnostics(doc))
+            })
+            .collect();
+
+        let markers = doc.markers_mut();
+        let stale: Vec<String> = markers
+            .diagnostics()
+            .into_iter()
+            .filter(|marker| marker.name.starts_with(MARKER_PREFIX))
+            .map(|marker| marker.name.clone())
+            .collect();
+        for name in stale {
+            markers.remove(&name);
+        }
+
+        for (id, source, diagnostics) in results {
+            for (index, diagnostic) in diagnostics.into_iter().enumerate() {
+                markers.set_with_data(
+                    &format!("{}{}:{}", MARKER_PREFIX, id, index),
+                    diagnostic.range.start,
+                    MarkerType::Diagnostic {
+                        severity: diagnostic.severity,
+                        source: source.clone(),
+                    },
+                    serde_json::json!({
+                        "end": diagnostic.range.end,
+                        "message": diagnostic.message,
+                    }),
+                );
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DiagnosticRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let providers = self.providers.read();
+        let names: HashMap<_, Vec<_>> = providers
+            .iter()
+            .map(|(language, registrations)| {
+                (language.clone(), registrations.iter().map(|r| r.provider.name().to_string()).collect())
+            })
+            .collect();
+        f.debug_struct("DiagnosticRegistry")
+            .field("providers", &names)
+            .field("generation", &self.generation())
+            .finish()
+    }
+}
+
+/// Refreshes diagnostics once edits have been quiet for a while
+///
+/// Documents are refreshed after their version stops changing for the
+/// debounce period, and all documents are refreshed right away when the
+/// registry changes.
+#[derive(Debug, Clone)]
+pub struct DiagnosticRunner {
+    /// Quiet period required after the last edit
+    delay: Duration,
+    /// Time of the most recent edit not yet checked
+    last_edit: Option<Instant>,
+    /// Last seen version of each document, by name
+    versions: HashMap<String, u64>,
+    /// Documents edited since they were last checked
+    pending: Vec<String>,
+    /// Registry generation diagnostics were last computed with
+    generation: Option<u64>,
+}
+
+impl DiagnosticRunner {
+    /// Creates a runner with the given quiet period
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            last_edit: None,
+            versions: HashMap::new(),
+            pending: Vec::new(),
+            generation: None,
+        }
+    }
+
+    /// Returns the quiet period
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Sets the quiet period
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Notes edits and refreshes diagnostics that are due
+    ///
+    /// Returns true if any document's diagnostics were refreshed.
+    pub fn poll<'a>(
+        &mut self,
+        registry: &DiagnosticRegistry,
+        documents: impl IntoIterator<Item = &'a mut Document>,
+        now: Instant,
+    ) -> bool {
+        let mut documents: Vec<&mut Document> = documents.into_iter().collect();
+        self.versions.retain(|name, _| documents.iter().any(|doc| doc.name() == name));
+
+        for doc in &documents {
+            if self.versions.get(doc.name()) != Some(&doc.version()) {
+                self.versions.insert(doc.name().to_string(), doc.version());
+                if !self.pending.iter().any(|name| name == doc.name()) {
+                    self.pending.push(doc.name().to_string());
+                }
+                self.last_edit = Some(now);
+            }
+        }
+
+        let generation = registry.generation();
+        let refresh_all = self.generation != Some(generation);
+        let quiet = self
+            .last_edit
+            .is_some_and(|edit| now.saturating_duration_since(edit) >= self.delay);
+        if !refresh_all && !quiet {
+            return false;
+        }
+
+        let mut refreshed = false;
+        for doc in documents.iter_mut() {
+            if refresh_all || self.pending.iter().any(|name| name == doc.name()) {
+                registry.apply(doc);
+                refreshed = true;
+            }
+        }
+        self.generation = Some(generation);
+        self.pending.clear();
+        self.last_edit = None;
+        refreshed
+    }
+}
+
+impl Default for DiagnosticRunner {
+    fn default() -> Self {
+        Self::new(DEFAULT_DIAGNOSTIC_DEBOUNCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trailing_whitespace() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "fn main() {  \r\n\tok\n}\t").unwrap();
+
+        let diagnostics = TrailingWhitespace.diagnostics(&doc);
+        let ranges: Vec<_> = diagnostics.iter().map(|d| d.range.clone()).collect();
+        assert_eq!(ranges, vec![11..13, 20..21]);
+    }
+
+    #[test]
+    fn test_edits_debounced() {
+        let registry = DiagnosticRegistry::new();
+        registry.register(None, Arc::new(TrailingWhitespace));
+        let mut runner = DiagnosticRunner::new(Duration::from_millis(100));
+        let mut doc = Document::new("test.txt");
+
+        let start = Instant::now();
+        assert!(runner.poll(&registry, [&mut doc], start));
+        assert!(doc.markers().diagnostics().is_empty());
+
+        // No refresh until the edits have been quiet for the delay
+        doc.insert(0, "a ").unwrap();
+        assert!(!runner.poll(&registry, [&mut doc], start + Duration::from_millis(10)));
+        doc.insert(2, "b ").unwrap();
+        assert!(!runner.poll(&registry, [&mut doc], start + Duration::from_millis(60)));
+        assert!(doc.markers().diagnostics().is_empty());
+
+        assert!(runner.poll(&registry, [&mut doc], start + Duration::from_millis(160)));
+        assert_eq!(doc.markers().diagnostics().len(), 1);
+    }
+
+    #[test]
+    fn test_providers_keyed_by_plugin_and_name() {
+        let registry = DiagnosticRegistry::new();
+        registry.register_for_plugin("a", None, Arc::new(TrailingWhitespace));
+        registry.register_for_plugin("b", None, Arc::new(TrailingWhitespace));
+        // Replaces plugin a's provider
+        registry.register_for_plugin("a", Some("rust"), Arc::new(TrailingWhitespace));
+        assert_eq!(registry.providers_for(Some("rust")).len(), 2);
+
+        // Same-named providers get markers of their own
+        let mut doc = Document::new("test.rs");
+        doc.set_language(Some("rust".to_string()));
+        doc.insert(0, "a \n").unwrap();
+        registry.apply(&mut doc);
+        assert_eq!(doc.markers().diagnostics().len(), 2);
+
+        assert!(!registry.unregister(TrailingWhitespace::NAME));
+        assert!(registry.unregister_for_plugin("b", TrailingWhitespace::NAME));
+        registry.apply(&mut doc);
+        assert_eq!(doc.markers().diagnostics().len(), 1);
+    }
+}
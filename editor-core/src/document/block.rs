@@ -0,0 +1,90 @@
+//! Rectangular (column) selections
+
+use std::ops::RangeInclusive;
+
+/// A rectangular selection spanning the same columns on consecutive lines
+///
+/// Lines and columns are 0-based and columns count characters. The column
+/// span is `left_col..right_col`, so a block with equal columns is a column
+/// cursor on each line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockSelection {
+    /// First selected line
+    pub top_line: usize,
+    /// Last selected line, inclusive
+    pub bottom_line: usize,
+    /// First selected column
+    pub left_col: usize,
+    /// Column after the last selected one
+    pub right_col: usize,
+}
+
+/// How block edits treat lines that do not reach the block's columns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ShortLines {
+    /// Pad short lines with spaces so inserted text lines up with the block
+    #[default]
+    Pad,
+    /// Leave lines that end before the block untouched
+    Clip,
+}
+
+impl BlockSelection {
+    /// Creates a block between two corners given in any order
+    pub fn new(top_line: usize, bottom_line: usize, left_col: usize, right_col: usize) -> Self {
+        Self {
+            top_line: top_line.min(bottom_line),
+            bottom_line: top_line.max(bottom_line),
+            left_col: left_col.min(right_col),
+            right_col: left_col.max(right_col),
+        }
+    }
+
+    /// Returns the selected lines
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.top_line..=self.bottom_line
+    }
+
+    /// Returns true if the block selects no columns
+    pub fn is_empty(&self) -> bool {
+        self.left_col == self.right_col
+    }
+
+    /// Replaces the block's columns on one line with `text`
+    ///
+    /// Lines ending inside the block lose only the columns they have. Lines
+    /// ending before the block are padded or left alone according to
+    /// `short_lines`; nothing is padded when `text` is empty.
+    pub(crate) fn edit_line(&self, line: &str, text: &str, short_lines: ShortLines) -> String {
+        let len = line.chars().count();
+        if len < self.left_col {
+            return match short_lines {
+                ShortLines::Pad if !text.is_empty() => {
+                    format!("{}{}{}", line, " ".repeat(self.left_col - len), text)
+                }
+                _ => line.to_string(),
+            };
+        }
+
+        let byte = |col: usize| line.char_indices().nth(col).map_or(line.len(), |(i, _)| i);
+        let start = byte(self.left_col);
+        let end = byte(self.right_col);
+        format!("{}{}{}", &line[..start], text, &line[end..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_short_lines() {
+        let block = BlockSelection::new(0, 0, 4, 6);
+        assert_eq!(block.edit_line("abcdefgh", "X", ShortLines::Pad), "abcdXgh");
+        assert_eq!(block.edit_line("abcde", "X", ShortLines::Clip), "abcdX");
+        assert_eq!(block.edit_line("ab", "X", ShortLines::Pad), "ab  X");
+        assert_eq!(block.edit_line("ab", "X", ShortLines::Clip), "ab");
+        assert_eq!(block.edit_line("ab", "", ShortLines::Pad), "ab");
+        assert_eq!(block.edit_line("äöüßéè", "", ShortLines::Pad), "äöüß");
+    }
+}
@@ -0,0 +1,225 @@
+//! Detection of text encodings and binary content
+
+/// Encoding of a text file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8 without a byte order mark
+    Utf8,
+    /// UTF-8 with a byte order mark
+    Utf8Bom,
+    /// UTF-16, little endian, without a byte order mark
+    Utf16Le,
+    /// UTF-16, little endian, with a byte order mark
+    Utf16LeBom,
+    /// UTF-16, big endian, without a byte order mark
+    Utf16Be,
+    /// UTF-16, big endian, with a byte order mark
+    Utf16BeBom,
+}
+
+impl Encoding {
+    /// Returns the name shown in the status bar
+    pub fn label(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf8Bom => "UTF-8 with BOM",
+            Encoding::Utf16Le => "UTF-16 LE",
+            Encoding::Utf16LeBom => "UTF-16 LE with BOM",
+            Encoding::Utf16Be => "UTF-16 BE",
+            Encoding::Utf16BeBom => "UTF-16 BE with BOM",
+        }
+    }
+
+    /// Encodes text as the bytes of a file in this encoding, starting with
+    /// the byte order mark if it has one
+    pub fn encode(&self, text: &str) -> Vec<u8> {
+        match self {
+            Encoding::Utf8 => text.as_bytes().to_vec(),
+            Encoding::Utf8Bom => [b"\xEF\xBB\xBF".as_slice(), text.as_bytes()].concat(),
+            Encoding::Utf16Le | Encoding::Utf16LeBom => {
+                let bom = (*self == Encoding::Utf16LeBom).then_some(0xFEFF);
+                bom.into_iter().chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect()
+            }
+            Encoding::Utf16Be | Encoding::Utf16BeBom => {
+                let bom = (*self == Encoding::Utf16BeBom).then_some(0xFEFF);
+                bom.into_iter().chain(text.encode_utf16()).flat_map(u16::to_be_bytes).collect()
+            }
+        }
+    }
+}
+
+/// Whether a document is text, and in which encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentKind {
+    /// Text in a known encoding
+    Text {
+        /// Encoding the file was read with
+        encoding: Encoding,
+    },
+    /// Content that is not text in a supported encoding
+    Binary,
+}
+
+/// Number of leading bytes inspected for UTF-16 without a byte order mark
+const SAMPLE_LEN: usize = 8 * 1024;
+
+impl ContentKind {
+    /// Classifies file content
+    ///
+    /// Byte order marks are trusted. Without one, content with NUL bytes is
+    /// UTF-16 if nearly every other byte is NUL and binary otherwise, and
+    /// content that is not valid UTF-8 is binary.
+    pub fn detect(bytes: &[u8]) -> Self {
+        Self::decode(bytes).0
+    }
+
+    /// Classifies file content and decodes it
+    ///
+    /// Binary content decodes to an empty string. A byte order mark is not
+    /// part of the decoded text.
+    pub fn decode(bytes: &[u8]) -> (Self, String) {
+        if let Some(rest) = bytes.strip_prefix(b"\xEF\xBB\xBF") {
+            return decode_utf8(rest, Encoding::Utf8Bom);
+        }
+        if let Some(rest) = bytes.strip_prefix(b"\xFF\xFE") {
+            return decode_utf16(rest, Encoding::Utf16LeBom);
+        }
+        if let Some(rest) = bytes.strip_prefix(b"\xFE\xFF") {
+            return decode_utf16(rest, Encoding::Utf16BeBom);
+        }
+
+        if !bytes.contains(&0) {
+            return decode_utf8(bytes, Encoding::Utf8);
+        }
+        match utf16_without_bom(bytes) {
+            Some(encoding) => decode_utf16(bytes, encoding),
+            None => (ContentKind::Binary, String::new()),
+        }
+    }
+
+    /// Returns true for binary content
+    pub fn is_binary(&self) -> bool {
+        matches!(self, ContentKind::Binary)
+    }
+
+    /// Returns the name shown in the status bar
+    pub fn label(&self) -> &'static str {
+        match self {
+            ContentKind::Text { encoding } => encoding.label(),
+            ContentKind::Binary => "Binary",
+        }
+    }
+}
+
+impl Default for ContentKind {
+    fn default() -> Self {
+        ContentKind::Text { encoding: Encoding::Utf8 }
+    }
+}
+
+/// Decodes UTF-8, or reports binary content if it is invalid
+fn decode_utf8(bytes: &[u8], encoding: Encoding) -> (ContentKind, String) {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => (ContentKind::Text { encoding }, text.to_string()),
+        Err(_) => (ContentKind::Binary, String::new()),
+    }
+}
+
+/// Decodes UTF-16, or reports binary content if it is invalid
+fn decode_utf16(bytes: &[u8], encoding: Encoding) -> (ContentKind, String) {
+    if !bytes.len().is_multiple_of(2) {
+        return (ContentKind::Binary, String::new());
+    }
+
+    let units = bytes.chunks_exact(2).map(|pair| match encoding {
+        Encoding::Utf16Be | Encoding::Utf16BeBom => u16::from_be_bytes([pair[0], pair[1]]),
+        _ => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+    match char::decode_utf16(units).collect::<Result<String, _>>() {
+        Ok(text) => (ContentKind::Text { encoding }, text),
+        Err(_) => (ContentKind::Binary, String::new()),
+    }
+}
+
+/// Guesses the byte order of UTF-16 text without a byte order mark
+///
+/// Mostly-ASCII UTF-16 has a NUL in every other byte, which binary data
+/// rarely does.
+fn utf16_without_bom(bytes: &[u8]) -> Option<Encoding> {
+    let sample = &bytes[..bytes.len().min(SAMPLE_LEN)];
+    if sample.len() < 2 || !sample.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let pairs = sample.len() / 2;
+    let nul_at = |parity: usize| sample.iter().skip(parity).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (nul_at(0), nul_at(1));
+
+    if odd * 10 >= pairs * 9 && even * 10 < pairs {
+        Some(Encoding::Utf16Le)
+    } else if even * 10 >= pairs * 9 && odd * 10 < pairs {
+        Some(Encoding::Utf16Be)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn test_utf8() {
+        assert_eq!(ContentKind::detect("fn main() {}\n".as_bytes()), ContentKind::default());
+        assert_eq!(
+            ContentKind::decode("\u{FEFF}héllo".as_bytes()),
+            (ContentKind::Text { encoding: Encoding::Utf8Bom }, "héllo".to_string())
+        );
+    }
+
+    #[test]
+    fn test_utf16() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(utf16le("héllo 😀\n"));
+        assert_eq!(
+            ContentKind::decode(&bytes),
+            (ContentKind::Text { encoding: Encoding::Utf16LeBom }, "héllo 😀\n".to_string())
+        );
+
+        // Without a byte order mark
+        let big_endian: Vec<u8> = "let x = 1;\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!(
+            ContentKind::decode(&big_endian),
+            (ContentKind::Text { encoding: Encoding::Utf16Be }, "let x = 1;\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trips() {
+        let text = "fn main() { println!(\"héllo\"); }\r\n";
+        for encoding in [
+            Encoding::Utf8,
+            Encoding::Utf8Bom,
+            Encoding::Utf16Le,
+            Encoding::Utf16LeBom,
+            Encoding::Utf16Be,
+            Encoding::Utf16BeBom,
+        ] {
+            let bytes = encoding.encode(text);
+            assert_eq!(ContentKind::decode(&bytes), (ContentKind::Text { encoding }, text.to_string()));
+        }
+        assert_eq!(Encoding::Utf16LeBom.encode("a"), vec![0xFF, 0xFE, b'a', 0]);
+    }
+
+    #[test]
+    fn test_binary() {
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR\x00\x00\x01\x00";
+        assert_eq!(ContentKind::decode(png), (ContentKind::Binary, String::new()));
+        assert!(ContentKind::detect(&[0xC3, 0x28, b'a']).is_binary());
+        assert_eq!(ContentKind::Binary.label(), "Binary");
+    }
+}
@@ -0,0 +1,196 @@
+//! Undo history of a document
+
+use super::LineEnding;
+
+/// Maximum number of undo steps kept
+const MAX_UNDO_STEPS: usize = 1000;
+
+/// A primitive change to the text, or to how it is saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Change {
+    /// Text inserted at a byte offset
+    Insert { position: usize, text: String },
+    /// Text deleted from a byte offset
+    Delete { position: usize, text: String },
+    /// Text at a byte offset replaced by other text
+    Replace { position: usize, old_text: String, text: String },
+    /// Line breaks rewritten to the `new` style, each given by its offset
+    /// before the change and the style it had
+    LineBreaks { breaks: Vec<(usize, LineEnding)>, new: LineEnding },
+    /// Line ending style of the document changed
+    LineEnding { old: LineEnding, new: LineEnding },
+}
+
+/// Way to move through the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Direction {
+    /// Towards older steps
+    Undo,
+    /// Towards newer steps
+    Redo,
+}
+
+/// Changes made by one edit, undone and redone together
+#[derive(Debug, Clone)]
+struct Step {
+    /// Unique id, used to find the saved position again
+    id: u64,
+    /// Changes in the order they were applied
+    changes: Vec<Change>,
+}
+
+/// Undo and redo stacks with the position of the last save
+#[derive(Debug)]
+pub(crate) struct History {
+    /// Steps that can be undone, oldest first
+    undo: Vec<Step>,
+    /// Steps that can be redone, most recently undone last
+    redo: Vec<Step>,
+    /// Changes of the edit in progress
+    pending: Vec<Change>,
+    /// Id of the position before the oldest undo step
+    base: u64,
+    /// Id of the next step
+    next_id: u64,
+    /// Position of the last save, or `None` if it can no longer be reached
+    saved: Option<u64>,
+}
+
+impl History {
+    /// Creates an empty history for text that is saved as is
+    pub(crate) fn new() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: Vec::new(),
+            base: 0,
+            next_id: 1,
+            saved: Some(0),
+        }
+    }
+
+    /// Records a change of the edit in progress
+    pub(crate) fn record(&mut self, change: Change) {
+        self.pending.push(change);
+    }
+
+    /// Ends the edit in progress, making it one undo step
+    ///
+    /// Steps that were undone can no longer be redone afterwards.
+    pub(crate) fn commit(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.undo.push(Step {
+            id,
+            changes: std::mem::take(&mut self.pending),
+        });
+        self.redo.clear();
+
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.base = self.undo.remove(0).id;
+        }
+        // A save among the discarded redo steps can no longer be reached
+        if self.saved.is_some_and(|saved| !self.is_reachable(saved)) {
+            self.saved = None;
+        }
+    }
+
+    /// Drops changes recorded while undoing or redoing
+    pub(crate) fn discard_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Returns the position after the most recent undo step
+    fn position(&self) -> u64 {
+        self.undo.last().map_or(self.base, |step| step.id)
+    }
+
+    /// Returns true if undoing or redoing can return to a position
+    fn is_reachable(&self, id: u64) -> bool {
+        id == self.base || self.undo.iter().chain(&self.redo).any(|step| step.id == id)
+    }
+
+    /// Marks the current position as saved
+    pub(crate) fn mark_saved(&mut self) {
+        self.saved = Some(self.position());
+    }
+
+    /// Returns true if the text differs from the last save
+    pub(crate) fn is_dirty(&self) -> bool {
+        self.saved != Some(self.position())
+    }
+
+    /// Returns true if there is a step to undo
+    pub(crate) fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns true if there is a step to redo
+    pub(crate) fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Returns whether to undo or redo to get back to the last save
+    ///
+    /// Returns `None` if the text is already as saved or the save cannot be reached.
+    pub(crate) fn direction_to_saved(&self) -> Option<Direction> {
+        let saved = self.saved.filter(|&saved| saved != self.position())?;
+        if saved == self.base || self.undo.iter().any(|step| step.id == saved) {
+            Some(Direction::Undo)
+        } else if self.redo.iter().any(|step| step.id == saved) {
+            Some(Direction::Redo)
+        } else {
+            None
+        }
+    }
+
+    /// Moves the most recent step to the redo stack, returning its changes
+    pub(crate) fn undo(&mut self) -> Option<Vec<Change>> {
+        let step = self.undo.pop()?;
+        let changes = step.changes.clone();
+        self.redo.push(step);
+        Some(changes)
+    }
+
+    /// Moves the most recently undone step back, returning its changes
+    pub(crate) fn redo(&mut self) -> Option<Vec<Change>> {
+        let step = self.redo.pop()?;
+        let changes = step.changes.clone();
+        self.undo.push(step);
+        Some(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(position: usize, text: &str) -> Change {
+        Change::Insert { position, text: text.to_string() }
+    }
+
+    #[test]
+    fn test_saved_position_lost_after_branching() {
+        let mut history = History::new();
+        history.record(insert(0, "a"));
+        history.commit();
+        history.mark_saved();
+        assert!(!history.is_dirty());
+
+        history.undo();
+        assert!(history.is_dirty());
+        assert_eq!(history.direction_to_saved(), Some(Direction::Redo));
+
+        // A new edit discards the saved step
+        history.record(insert(0, "b"));
+        history.commit();
+        assert!(history.is_dirty());
+        assert_eq!(history.direction_to_saved(), None);
+        history.undo();
+        assert!(history.is_dirty());
+    }
+}
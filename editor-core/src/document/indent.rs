@@ -0,0 +1,256 @@
+//! Indentation consistency checks and reindenting
+
+/// Indentation style found in a text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndentReport {
+    /// No line is indented
+    None,
+    /// Every indented line uses tabs only
+    Tabs,
+    /// Every indented line uses spaces only
+    Spaces,
+    /// Both tabs and spaces are used
+    Mixed {
+        /// Lines (0-based) that mix tabs and spaces or use the less common style
+        lines: Vec<usize>,
+    },
+}
+
+impl IndentReport {
+    /// Scans the leading whitespace of each line
+    ///
+    /// Whitespace-only lines are ignored. When both styles are used, the less
+    /// common one is reported as offending, and spaces win a tie.
+    pub fn scan(text: &str) -> Self {
+        let mut tabs = Vec::new();
+        let mut spaces = Vec::new();
+        let mut mixed = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let content = line.trim_start_matches([' ', '\t']);
+            if content.is_empty() {
+                continue;
+            }
+
+            let indent = &line[..line.len() - content.len()];
+            match (indent.contains('\t'), indent.contains(' ')) {
+                (true, true) => mixed.push(index),
+                (true, false) => tabs.push(index),
+                (false, true) => spaces.push(index),
+                (false, false) => {}
+            }
+        }
+
+        if mixed.is_empty() {
+            match (tabs.is_empty(), spaces.is_empty()) {
+                (true, true) => return Self::None,
+                (false, true) => return Self::Tabs,
+                (true, false) => return Self::Spaces,
+                (false, false) => {}
+            }
+        }
+
+        let minority = if tabs.len() > spaces.len() { spaces } else { tabs };
+        let mut lines = [mixed, minority].concat();
+        lines.sort_unstable();
+        Self::Mixed { lines }
+    }
+
+    /// Returns true if tabs and spaces are mixed
+    pub fn is_mixed(&self) -> bool {
+        matches!(self, Self::Mixed { .. })
+    }
+}
+
+/// Indentation rules of a language
+///
+/// Lines ending with an `increase_indent` pattern indent the following lines,
+/// and lines starting with a `decrease_indent` pattern are dedented.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IndentRules {
+    /// Patterns that increase the indentation of the next line
+    pub increase_indent: Vec<String>,
+    /// Patterns that decrease the indentation of the line they start
+    pub decrease_indent: Vec<String>,
+}
+
+impl IndentRules {
+    /// Creates rules from increase and decrease patterns
+    pub fn new(increase_indent: Vec<String>, decrease_indent: Vec<String>) -> Self {
+        Self {
+            increase_indent,
+            decrease_indent,
+        }
+    }
+
+    /// Returns true if the line ends with an increase pattern
+    fn increases_after(&self, line: &str) -> bool {
+        let line = line.trim_end();
+        self.increase_indent.iter().any(|p| !p.is_empty() && line.ends_with(p.as_str()))
+    }
+
+    /// Returns true if the line content starts with a decrease pattern
+    fn decreases(&self, content: &str) -> bool {
+        self.decrease_indent.iter().any(|p| !p.is_empty() && content.starts_with(p.as_str()))
+    }
+
+    /// Reindents `lines`, which directly follow the text `before`
+    ///
+    /// The indentation level is taken from the last non-blank line of
+    /// `before`. Without decrease patterns, as for Python, blocks cannot be
+    /// told apart, so the lines are shifted as a whole and keep their relative
+    /// indentation. Blank lines lose their whitespace.
+    pub(crate) fn reindent(&self, before: &str, lines: &[&str], settings: IndentSettings) -> Vec<String> {
+        let previous = before.lines().rev().find(|line| !line.trim().is_empty());
+        let level = previous.map_or(0, |line| {
+            settings.width(line) / settings.tab_size + usize::from(self.increases_after(line))
+        });
+
+        if self.decrease_indent.is_empty() {
+            return self.shift(lines, level * settings.tab_size, settings);
+        }
+
+        let mut level = level;
+        lines.iter()
+            .map(|line| {
+                let content = line.trim_start_matches([' ', '\t']);
+                if content.trim().is_empty() {
+                    return String::new();
+                }
+                let line_level = if self.decreases(content) { level.saturating_sub(1) } else { level };
+                level = line_level + usize::from(self.increases_after(content));
+                settings.indent(line_level * settings.tab_size) + content
+            })
+            .collect()
+    }
+
+    /// Moves lines so the first non-blank one is indented by `columns`
+    fn shift(&self, lines: &[&str], columns: usize, settings: IndentSettings) -> Vec<String> {
+        let first = lines.iter().find(|line| !line.trim().is_empty());
+        let delta = columns as isize - first.map_or(0, |line| settings.width(line)) as isize;
+
+        lines.iter()
+            .map(|line| {
+                let content = line.trim_start_matches([' ', '\t']);
+                if content.trim().is_empty() {
+                    return String::new();
+                }
+                let width = settings.width(line).saturating_add_signed(delta);
+                settings.indent(width) + content
+            })
+            .collect()
+    }
+}
+
+/// How indentation is written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndentSettings {
+    /// Indent with spaces instead of tabs
+    pub use_spaces: bool,
+    /// Columns per indentation level and per tab
+    pub tab_size: usize,
+}
+
+impl IndentSettings {
+    /// Creates settings for four-space indentation
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether to indent with spaces
+    pub fn with_use_spaces(mut self, use_spaces: bool) -> Self {
+        self.use_spaces = use_spaces;
+        self
+    }
+
+    /// Sets the columns per indentation level, at least 1
+    pub fn with_tab_size(mut self, tab_size: usize) -> Self {
+        self.tab_size = tab_size.max(1);
+        self
+    }
+
+    /// Returns the width in columns of a line's leading whitespace
+    fn width(&self, line: &str) -> usize {
+        line.chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .fold(0, |width, c| match c {
+                '\t' => (width / self.tab_size + 1) * self.tab_size,
+                _ => width + 1,
+            })
+    }
+
+    /// Returns the whitespace that indents a line by `columns`
+    fn indent(&self, columns: usize) -> String {
+        if self.use_spaces {
+            " ".repeat(columns)
+        } else {
+            "\t".repeat(columns / self.tab_size) + &" ".repeat(columns % self.tab_size)
+        }
+    }
+}
+
+impl Default for IndentSettings {
+    fn default() -> Self {
+        Self {
+            use_spaces: true,
+            tab_size: 4,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_tabs() {
+        let report = IndentReport::scan("fn main() {\n\tif x {\n\t\ty();\n\t}\n    \n}\n");
+        assert_eq!(report, IndentReport::Tabs);
+    }
+
+    #[test]
+    fn test_clean_spaces() {
+        let report = IndentReport::scan("fn main() {\r\n    if x {\r\n        y();\r\n\t\r\n    }\r\n}\r\n");
+        assert_eq!(report, IndentReport::Spaces);
+        assert_eq!(IndentReport::scan("no indentation\n"), IndentReport::None);
+    }
+
+    #[test]
+    fn test_mixed() {
+        let text = "fn main() {\n    a();\n\tb();\n    c();\n \tif x {\n        d();\n    }\n}\n";
+        let report = IndentReport::scan(text);
+        assert!(report.is_mixed());
+        assert_eq!(report, IndentReport::Mixed { lines: vec![2, 4] });
+    }
+
+    fn brace_rules() -> IndentRules {
+        IndentRules::new(
+            vec!["{".to_string(), "(".to_string(), "[".to_string()],
+            vec!["}".to_string(), ")".to_string(), "]".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_reindent_by_nesting() {
+        let lines = ["if y {", "z();", "  } else {", "w(", "1,", ");", "}"];
+        let reindented = brace_rules().reindent("fn main() {\n", &lines, IndentSettings::new());
+        assert_eq!(reindented, vec![
+            "    if y {",
+            "        z();",
+            "    } else {",
+            "        w(",
+            "            1,",
+            "        );",
+            "    }",
+        ]);
+    }
+
+    #[test]
+    fn test_reindent_keeps_relative_indent_without_decrease_rules() {
+        let rules = IndentRules::new(vec![":".to_string()], Vec::new());
+        let settings = IndentSettings::new().with_use_spaces(false);
+        let lines = ["if y:", "    z()", "", "w()"];
+        let reindented = rules.reindent("def f():\n", &lines, settings);
+        assert_eq!(reindented, vec!["\tif y:", "\t\tz()", "", "\tw()"]);
+    }
+}
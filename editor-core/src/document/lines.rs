@@ -0,0 +1,125 @@
+//! Line-based editing helpers
+
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// Options for sorting lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortOptions {
+    /// Compare lines ignoring case
+    pub case_insensitive: bool,
+    /// Compare lines by their leading number, so "10" sorts after "9"
+    pub numeric: bool,
+    /// Sort in descending order
+    pub descending: bool,
+}
+
+impl SortOptions {
+    /// Creates options for a case-sensitive ascending sort
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets case-insensitive comparison
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Sets numeric comparison
+    pub fn with_numeric(mut self, numeric: bool) -> Self {
+        self.numeric = numeric;
+        self
+    }
+
+    /// Sets descending order
+    pub fn with_descending(mut self, descending: bool) -> Self {
+        self.descending = descending;
+        self
+    }
+
+    /// Compares two lines according to these options
+    pub(crate) fn compare(&self, a: &str, b: &str) -> Ordering {
+        let numeric = if self.numeric {
+            match (leading_number(a), leading_number(b)) {
+                (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                // Lines with a number sort before lines without one
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            }
+        } else {
+            Ordering::Equal
+        };
+
+        let ordering = numeric.then_with(|| {
+            if self.case_insensitive {
+                a.to_lowercase().cmp(&b.to_lowercase())
+            } else {
+                a.cmp(b)
+            }
+        });
+
+        if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+}
+
+/// Parses the number at the start of a line, ignoring leading whitespace
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let mut end = 0;
+    let mut seen_dot = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            '-' | '+' if i == 0 => {}
+            '.' if !seen_dot => seen_dot = true,
+            c if c.is_ascii_digit() => {}
+            _ => break,
+        }
+        end = i + c.len_utf8();
+    }
+    line[..end].parse().ok()
+}
+
+/// Expands a byte range to the full lines it touches
+///
+/// `newline` is the last character of the line separator. A range ending at
+/// the start of a line does not include that line.
+pub(crate) fn full_line_range(text: &str, range: Range<usize>, newline: char) -> Range<usize> {
+    let start = range.start.min(text.len());
+    let end = range.end.clamp(start, text.len());
+
+    let line_start = text[..start].rfind(newline).map_or(0, |i| i + 1);
+    let line_end = if end > line_start && text[..end].ends_with(newline) {
+        end
+    } else {
+        text[end..].find(newline).map_or(text.len(), |i| end + i + 1)
+    };
+
+    line_start..line_end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_full_line_range() {
+        let text = "one\ntwo\nthree";
+        assert_eq!(full_line_range(text, 5..5, '\n'), 4..8);
+        assert_eq!(full_line_range(text, 1..6, '\n'), 0..8);
+        assert_eq!(full_line_range(text, 0..8, '\n'), 0..8);
+        assert_eq!(full_line_range(text, 9..13, '\n'), 8..13);
+    }
+
+    #[test]
+    fn test_leading_number() {
+        assert_eq!(leading_number("10 apples"), Some(10.0));
+        assert_eq!(leading_number("  -2.5"), Some(-2.5));
+        assert_eq!(leading_number("apples"), None);
+    }
+}
@@ -2,11 +2,38 @@
 //!
 //! Provides document abstraction that manages buffers and maintains document metadata
 
-use crate::buffer::Buffer;
-use crate::Result;
+mod block;
+mod content;
+mod history;
+mod indent;
+mod lines;
+mod search;
+mod selection;
+
+pub use block::{BlockSelection, ShortLines};
+pub use content::{ContentKind, Encoding};
+pub use indent::{IndentReport, IndentRules, IndentSettings};
+pub use lines::SortOptions;
+pub use search::{SearchMatch, SearchOptions};
+pub use selection::Selection;
+
+use crate::associations::FileAssociations;
+use crate::buffer::{Buffer, EditSummary};
+use crate::event::{BufferEvent, DocumentEvent, Event, EventDispatcher};
+use crate::markers::{Bookmark, DiagnosticSeverity, LineIndex, Marker, MarkerSet, MarkerType};
+use history::{Change, Direction, History};
+use crate::snippet::{Snippet, TabStop};
+use crate::source::{DocumentSource, SourceFs};
+use crate::syntax_edit::SyntaxEdit;
+use crate::vfs::{FileMetadata, StdFs, VirtualFs};
+use crate::{Error, Result};
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
 use std::sync::Arc;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tokio::sync::broadcast;
 
 /// Represents metadata about a document
 #[derive(Debug, Clone)]
@@ -19,10 +46,16 @@ pub struct DocumentMetadata {
     pub line_ending: LineEnding,
     /// The document's language/file type
     pub language: Option<String>,
+    /// Size of the file on disk when last loaded or saved
+    pub size_on_disk: Option<u64>,
+    /// Modification time of the file when last loaded or saved
+    pub modified: Option<SystemTime>,
+    /// True if the file on disk is not writable
+    pub read_only_on_disk: bool,
 }
 
 /// Represents different line ending styles
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LineEnding {
     Unix,    // \n
     Windows, // \r\n
@@ -30,21 +63,35 @@ pub enum LineEnding {
 }
 
 impl LineEnding {
+    /// All supported line ending styles
+    pub const ALL: [LineEnding; 3] = [LineEnding::Unix, LineEnding::Windows, LineEnding::Mac];
+
     /// Detects the line ending used in a string
+    ///
+    /// The most common style wins, preferring CRLF on ties; text without line
+    /// endings is Unix.
     pub fn detect(text: &str) -> Self {
-        // Quick check for common patterns
-        if text.contains("\r\n") {
-            return LineEnding::Windows;
-        }
-        
-        if text.contains('\r') && !text.contains('\n') {
-            return LineEnding::Mac;
-        }
-        
-        // Default to Unix line endings
-        LineEnding::Unix
+        LineEndingCounts::count(text).majority()
+    }
+
+    /// Returns the style to normalize to if `text` mixes line endings
+    ///
+    /// That is the detected majority, or `None` if the text uses a single
+    /// style throughout.
+    pub fn detect_mixed(text: &str) -> Option<Self> {
+        let counts = LineEndingCounts::count(text);
+        counts.is_mixed().then(|| counts.majority())
     }
     
+    /// Returns the style of a single line break
+    fn of_break(line_break: &str) -> Self {
+        match line_break {
+            "\r\n" => LineEnding::Windows,
+            "\r" => LineEnding::Mac,
+            _ => LineEnding::Unix,
+        }
+    }
+
     /// Converts the line ending to its string representation
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -54,6 +101,15 @@ impl LineEnding {
         }
     }
     
+    /// Returns the short name shown in the status bar
+    pub fn label(&self) -> &'static str {
+        match self {
+            LineEnding::Unix => "LF",
+            LineEnding::Windows => "CRLF",
+            LineEnding::Mac => "CR",
+        }
+    }
+
     /// Normalizes text to use this line ending style
     pub fn normalize(&self, text: &str) -> String {
         // First normalize all line endings to Unix style
@@ -70,6 +126,106 @@ impl LineEnding {
     }
 }
 
+/// Number of line endings of each style in a text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineEndingCounts {
+    /// `\n` endings
+    pub unix: usize,
+    /// `\r\n` endings
+    pub windows: usize,
+    /// `\r` endings not followed by `\n`
+    pub mac: usize,
+}
+
+impl LineEndingCounts {
+    /// Counts the line endings in a string
+    pub fn count(text: &str) -> Self {
+        let mut counts = Self::default();
+        let mut bytes = text.bytes().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\n' => counts.unix += 1,
+                b'\r' if bytes.peek() == Some(&b'\n') => {
+                    bytes.next();
+                    counts.windows += 1;
+                }
+                b'\r' => counts.mac += 1,
+                _ => {}
+            }
+        }
+        counts
+    }
+
+    /// Returns the number of endings of a style
+    pub fn get(&self, line_ending: LineEnding) -> usize {
+        match line_ending {
+            LineEnding::Unix => self.unix,
+            LineEnding::Windows => self.windows,
+            LineEnding::Mac => self.mac,
+        }
+    }
+
+    /// Returns the most common style, preferring CRLF, then LF, on ties
+    pub fn majority(&self) -> LineEnding {
+        if self.unix + self.windows + self.mac == 0 {
+            return LineEnding::Unix;
+        }
+        // `max_by_key` returns the last of equal elements
+        [LineEnding::Mac, LineEnding::Unix, LineEnding::Windows]
+            .into_iter()
+            .max_by_key(|line_ending| self.get(*line_ending))
+            .unwrap_or(LineEnding::Unix)
+    }
+
+    /// Returns true if more than one style occurs
+    pub fn is_mixed(&self) -> bool {
+        [self.unix, self.windows, self.mac].iter().filter(|&&count| count > 0).count() > 1
+    }
+}
+
+/// Returns the byte ranges of the line breaks in a text, `\r\n` as one
+fn line_breaks(text: &str) -> Vec<Range<usize>> {
+    let bytes = text.as_bytes();
+    let mut breaks = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let len = match bytes[offset] {
+            b'\r' if bytes.get(offset + 1) == Some(&b'\n') => 2,
+            b'\r' | b'\n' => 1,
+            _ => 0,
+        };
+        if len > 0 {
+            breaks.push(offset..offset + len);
+        }
+        offset += len.max(1);
+    }
+    breaks
+}
+
+/// Returns the byte range of `old` that differs from `new`, and the part of
+/// `new` replacing it, or `None` if the texts are equal
+///
+/// The common start and end of the texts are left out of the range, on
+/// character boundaries.
+fn changed_range<'a>(old: &str, new: &'a str) -> Option<(Range<usize>, &'a str)> {
+    if old == new {
+        return None;
+    }
+    let mut start = old.bytes().zip(new.bytes()).take_while(|(a, b)| a == b).count();
+    while !old.is_char_boundary(start) || !new.is_char_boundary(start) {
+        start -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - start;
+    let mut suffix = old.bytes().rev().zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    Some((start..old.len() - suffix, &new[start..new.len() - suffix]))
+}
+
 impl Default for LineEnding {
     fn default() -> Self {
         #[cfg(windows)]
@@ -79,6 +235,9 @@ impl Default for LineEnding {
     }
 }
 
+/// Prefix of the names of bookmark markers
+const BOOKMARK_PREFIX: &str = "bookmark:";
+
 /// Represents a document in the editor
 pub struct Document {
     /// The document's buffer containing the actual text
@@ -87,16 +246,31 @@ pub struct Document {
     metadata: DocumentMetadata,
     /// Version number for change tracking
     version: u64,
+    /// Markers such as bookmarks and diagnostics
+    markers: MarkerSet,
+    /// Selections, the primary one first
+    selections: Vec<Selection>,
+    /// Rejects edits when set
+    read_only: bool,
+    /// Reads the file back after saving to check it was written intact
+    verify_on_save: bool,
+    /// Writes only the changed bytes on save when that is safe
+    incremental_save: bool,
+    /// Scratch documents are left out of the session and autosave
+    scratch: bool,
+    /// Whether the content is text, and its encoding
+    content_kind: ContentKind,
+    /// Undo and redo steps
+    history: History,
+    /// Broadcasts buffer edits to subscribers
+    events: EventDispatcher,
 }
 
 impl Document {
     /// Creates a new empty document
     pub fn new(name: impl Into<String>) -> Self {
         let name = name.into();
-        let language = Path::new(&name)
-            .extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext.to_string());
+        let language = FileAssociations::new().detect_language(Path::new(&name));
 
         Self {
             buffer: Arc::new(RwLock::new(Buffer::new())),
@@ -105,38 +279,106 @@ impl Document {
                 path: None,
                 line_ending: LineEnding::default(),
                 language,
+                size_on_disk: None,
+                modified: None,
+                read_only_on_disk: false,
             },
             version: 0,
+            markers: MarkerSet::new(),
+            selections: vec![Selection::default()],
+            read_only: false,
+            verify_on_save: false,
+            incremental_save: false,
+            scratch: false,
+            content_kind: ContentKind::default(),
+            history: History::new(),
+            events: EventDispatcher::new(),
         }
     }
 
+    /// Creates an untitled document holding `text`, with nothing to undo
+    pub fn from_text(name: impl Into<String>, text: &str) -> Self {
+        let mut doc = Self::new(name);
+        doc.buffer = Arc::new(RwLock::new(Buffer::from_text(text)));
+        doc
+    }
+
     /// Creates a new document from a file
     pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_file_in(Arc::new(StdFs), path)
+    }
+
+    /// Creates a new document from a file in the given file system
+    ///
+    /// UTF-16 files are decoded. Binary files are opened read-only and empty.
+    pub fn from_file_in(fs: Arc<dyn VirtualFs>, path: impl AsRef<Path>) -> Result<Self> {
         let path = path.as_ref();
-        let buffer = Buffer::from_file(path)?;
+        let bytes = fs.read_bytes(path)?;
+        Self::from_bytes_in(fs, path, &bytes)
+    }
+
+    /// Creates a document from the already read content of a file in the given file system
+    pub(crate) fn from_bytes_in(fs: Arc<dyn VirtualFs>, path: &Path, bytes: &[u8]) -> Result<Self> {
+        let (content_kind, text) = ContentKind::decode(bytes);
+        let buffer = Buffer::from_loaded(fs, path.to_path_buf(), &text);
+        let disk = buffer.file_metadata()?;
+        Ok(Self::from_loaded(buffer, path, disk, content_kind))
+    }
+
+    /// Creates a new document from a URI in the given source
+    ///
+    /// Saving the document writes it back to the source.
+    pub fn from_source(source: Arc<dyn DocumentSource>, uri: &str) -> Result<Self> {
+        let (text, disk) = source.read(uri)?;
+        let path = SourceFs::path(uri);
+        let (content_kind, text) = ContentKind::decode(text.as_bytes());
+        let buffer = Buffer::from_loaded(Arc::new(SourceFs(source)), path.clone(), &text);
+        Ok(Self::from_loaded(buffer, &path, Some(disk), content_kind))
+    }
+
+    /// Creates a document for a buffer loaded from `path`
+    fn from_loaded(mut buffer: Buffer, path: &Path, disk: Option<FileMetadata>, content_kind: ContentKind) -> Self {
+        if let ContentKind::Text { encoding } = content_kind {
+            buffer.set_encoding(encoding);
+        }
         let name = path.file_name()
             .and_then(|n| n.to_str())
             .unwrap_or("Untitled")
             .to_string();
         
-        let language = path.extension()
-            .and_then(|ext| ext.to_str())
-            .map(|ext| ext.to_string());
-            
+        let language = FileAssociations::new().detect_language(path);
+
         // Detect the line ending from the buffer content
         let content = buffer.text();
         let line_ending = LineEnding::detect(&content);
 
-        Ok(Self {
+        let mut doc = Self {
             buffer: Arc::new(RwLock::new(buffer)),
             metadata: DocumentMetadata {
                 name,
                 path: Some(path.to_path_buf()),
                 line_ending,
                 language,
+                size_on_disk: None,
+                modified: None,
+                read_only_on_disk: false,
             },
             version: 0,
-        })
+            markers: MarkerSet::new(),
+            selections: vec![Selection::default()],
+            read_only: false,
+            verify_on_save: false,
+            incremental_save: false,
+            scratch: false,
+            content_kind,
+            history: History::new(),
+            events: EventDispatcher::new(),
+        };
+        doc.apply_disk_metadata(disk);
+
+        // Files that cannot be written, or are not text, are opened read-only
+        doc.read_only = doc.metadata.read_only_on_disk || content_kind.is_binary();
+        doc
     }
 
     /// Returns the document's name
@@ -153,7 +395,95 @@ impl Document {
     pub fn language(&self) -> Option<&str> {
         self.metadata.language.as_deref()
     }
+
+    /// Sets the document's language, overriding the detected one
+    ///
+    /// Subscribers receive `LanguageChanged` if the language actually changes,
+    /// so the highlighter can switch to the matching configuration.
+    pub fn set_language(&mut self, language: Option<String>) {
+        if self.metadata.language == language {
+            return;
+        }
+        self.metadata.language = language.clone();
+        self.events.dispatch(Event::Document(DocumentEvent::LanguageChanged {
+            name: self.metadata.name.clone(),
+            path: self.metadata.path.clone(),
+            language,
+        }));
+    }
+
+    /// Detects the document's language from its path or name, checking
+    /// `associations` before the extension
+    pub fn apply_file_associations(&mut self, associations: &FileAssociations) {
+        let path = self.metadata.path.clone().unwrap_or_else(|| PathBuf::from(&self.metadata.name));
+        self.metadata.language = associations.detect_language(&path);
+    }
     
+    /// Returns the size of the file on disk when it was last loaded or saved
+    pub fn size_on_disk(&self) -> Option<u64> {
+        self.metadata.size_on_disk
+    }
+
+    /// Returns the modification time of the file when it was last loaded or saved
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.metadata.modified
+    }
+
+    /// Returns true if the file on disk is not writable
+    pub fn is_read_only_on_disk(&self) -> bool {
+        self.metadata.read_only_on_disk
+    }
+
+    /// Returns true if the document rejects edits
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Sets whether the document rejects edits
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Returns true if saves are read back and checked
+    pub fn verify_on_save(&self) -> bool {
+        self.verify_on_save
+    }
+
+    /// Sets whether `save` reads the file back and compares it with the
+    /// document, to catch writes that land corrupted, e.g. on network file
+    /// systems
+    ///
+    /// Off by default, since it reads the whole file after every save.
+    pub fn set_verify_on_save(&mut self, verify: bool) {
+        self.verify_on_save = verify;
+    }
+
+    /// Returns true if saves write only the changed bytes when possible
+    pub fn incremental_save(&self) -> bool {
+        self.incremental_save
+    }
+
+    /// Sets whether `save` patches the file in place instead of rewriting it,
+    /// for large files where a small region changed
+    ///
+    /// Only UTF-8 documents are patched, and only when `Buffer::save_incremental`
+    /// finds it safe: edits that append, or keep the length the same, to a
+    /// file unchanged on disk. Other saves rewrite the whole file.
+    pub fn set_incremental_save(&mut self, incremental: bool) {
+        self.incremental_save = incremental;
+    }
+
+    /// Returns true if this is a scratch document
+    pub fn is_scratch(&self) -> bool {
+        self.scratch
+    }
+
+    /// Marks the document as scratch, so it is neither restored with the
+    /// session nor saved automatically
+    pub fn set_scratch(&mut self, scratch: bool) {
+        self.scratch = scratch;
+    }
+
     /// Returns the line ending style used by this document
     pub fn line_ending(&self) -> &LineEnding {
         &self.metadata.line_ending
@@ -174,105 +504,1031 @@ impl Document {
         self.buffer.read().text()
     }
 
+    /// Returns the text in a byte range
+    pub fn slice(&self, range: Range<usize>) -> Result<String> {
+        self.buffer.read().slice(range.start, range.end)
+    }
+
+    /// Returns the character starting at a byte offset, or `None` past the
+    /// end or inside a character
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        self.buffer.read().char_at(offset)
+    }
+
+    /// Returns the grapheme cluster starting at a byte offset, or `None` past
+    /// the end or inside a cluster
+    ///
+    /// A cluster is what the user sees as one character, e.g. a letter with
+    /// combining accents, a flag or `\r\n`.
+    pub fn grapheme_at(&self, offset: usize) -> Option<String> {
+        self.buffer.read().grapheme_at(offset)
+    }
+
+    /// Reports whether the document is indented with tabs, spaces or both
+    pub fn indentation_consistency(&self) -> IndentReport {
+        IndentReport::scan(&self.text())
+    }
+
+    /// Returns whether the document is text, and its encoding, as detected on load
+    pub fn content_kind(&self) -> ContentKind {
+        self.content_kind
+    }
+
+    /// Returns the byte ranges of all non-overlapping matches of `pattern`
+    ///
+    /// Binary documents never match.
+    pub fn find_all(&self, pattern: &str, options: SearchOptions) -> Vec<Range<usize>> {
+        if self.content_kind.is_binary() {
+            return Vec::new();
+        }
+        options.find_all(&self.text(), pattern)
+    }
+
+    /// Returns the first match starting at or after `from`
+    ///
+    /// If there is none, the search wraps around to the start of the document
+    /// once and the match is flagged as `wrapped`.
+    pub fn find_next(&self, from: usize, pattern: &str, options: SearchOptions) -> Option<SearchMatch> {
+        if self.content_kind.is_binary() {
+            return None;
+        }
+        let text = self.text();
+        let from = from.min(text.len());
+        if let Some(range) = options.find_forward(&text, pattern, from, text.len()) {
+            return Some(SearchMatch { range, wrapped: false });
+        }
+        options.find_forward(&text, pattern, 0, from)
+            .map(|range| SearchMatch { range, wrapped: true })
+    }
+
+    /// Returns the last match starting before `from`
+    ///
+    /// If there is none, the search wraps around to the end of the document
+    /// once and the match is flagged as `wrapped`.
+    pub fn find_prev(&self, from: usize, pattern: &str, options: SearchOptions) -> Option<SearchMatch> {
+        if self.content_kind.is_binary() {
+            return None;
+        }
+        let text = self.text();
+        let from = from.min(text.len());
+        if let Some(range) = options.find_backward(&text, pattern, 0, from) {
+            return Some(SearchMatch { range, wrapped: false });
+        }
+        options.find_backward(&text, pattern, from, text.len())
+            .map(|range| SearchMatch { range, wrapped: true })
+    }
+
+    /// Returns the number of lines
+    pub fn line_count(&self) -> usize {
+        self.buffer.read().line_count()
+    }
+
+    /// Returns the text of a line without its line break
+    pub fn line(&self, index: usize) -> Option<String> {
+        self.buffer.read().line(index)
+    }
+
+    /// Returns the text of the lines in `start..end` without their line breaks
+    pub fn lines_range(&self, start: usize, end: usize) -> Vec<String> {
+        self.buffer.read().lines_range(start, end)
+    }
+
+    /// Returns the indices of the lines longer than `column` characters,
+    /// e.g. to flag lines running past a ruler
+    pub fn lines_exceeding(&self, column: usize) -> Vec<usize> {
+        self.buffer.read().lines_exceeding(column)
+    }
+
     /// Inserts text at the specified position
-    pub fn insert(&mut self, position: usize, text: &str) -> Result<()> {
-        self.buffer.write().insert(position, text)?;
-        self.version += 1;
-        Ok(())
+    ///
+    /// Returns the byte and line ranges the insertion touched.
+    pub fn insert(&mut self, position: usize, text: &str) -> Result<EditSummary> {
+        self.check_writable()?;
+        let summary = self.insert_text(position, text)?;
+        self.finish_edit();
+        Ok(summary)
     }
 
     /// Deletes text in the specified range
-    pub fn delete(&mut self, start: usize, end: usize) -> Result<()> {
-        self.buffer.write().delete(start, end)?;
-        self.version += 1;
+    ///
+    /// Returns the byte and line ranges the deletion touched.
+    pub fn delete(&mut self, start: usize, end: usize) -> Result<EditSummary> {
+        self.check_writable()?;
+        let summary = self.delete_text(start, end)?;
+        self.finish_edit();
+        Ok(summary)
+    }
+
+    /// Replaces the text in the specified range, as a single undo step
+    ///
+    /// Views sharing the buffer never see the range deleted but not yet
+    /// replaced. Returns the byte and line ranges the replacement touched.
+    pub fn replace(&mut self, start: usize, end: usize, text: &str) -> Result<EditSummary> {
+        self.check_writable()?;
+        let summary = self.replace_text(start, end, text)?;
+        self.finish_edit();
+        Ok(summary)
+    }
+
+    /// Replaces the whole text, as a single undo step
+    ///
+    /// Only the part between the common start and end of the old and new
+    /// text is replaced, so markers, bookmarks and selections outside it
+    /// keep their places. Returns false if the text was already `text`.
+    pub fn set_text(&mut self, text: &str) -> Result<bool> {
+        let current = self.text();
+        let Some((range, replacement)) = changed_range(&current, text) else {
+            return Ok(false);
+        };
+        self.check_writable()?;
+        self.replace_text(range.start, range.end, replacement)?;
+        self.finish_edit();
+        Ok(true)
+    }
+
+    /// Replaces several ranges at once, as a single undo step
+    ///
+    /// Ranges are byte offsets into the current text and must not overlap;
+    /// they are applied back to front so earlier offsets stay valid. Used to
+    /// apply edits computed elsewhere, such as language server quick fixes.
+    pub fn apply_edits(&mut self, edits: &[(Range<usize>, String)]) -> Result<()> {
+        self.check_writable()?;
+
+        let mut sorted: Vec<_> = edits.iter().collect();
+        sorted.sort_by_key(|(range, _)| (range.start, range.end));
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0].0.end > pair[1].0.start) {
+            return Err(Error::Document(format!(
+                "Overlapping edits at {:?} and {:?}",
+                pair[0].0, pair[1].0
+            )));
+        }
+
+        if sorted.is_empty() {
+            return Ok(());
+        }
+        for (range, text) in sorted.into_iter().rev() {
+            self.replace_text(range.start, range.end, text)?;
+        }
+        self.finish_edit();
         Ok(())
     }
 
+    /// Inserts an expanded snippet and returns its tab stops in document offsets
+    pub fn insert_snippet(&mut self, position: usize, snippet: &Snippet) -> Result<Vec<TabStop>> {
+        self.insert(position, snippet.text())?;
+        Ok(snippet.tab_stops().iter().map(|stop| stop.shifted(position)).collect())
+    }
+
+    /// Subscribes to edits of this document
+    ///
+    /// Every change to the text is reported as `BufferEvent::Inserted` or
+    /// `BufferEvent::Deleted` with byte offsets, in the order it was applied.
+    /// Each event also carries a `SyntaxEdit` that can be passed to an
+    /// incremental parser.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Inserts text into the buffer and reports it to subscribers
+    fn insert_text(&mut self, position: usize, text: &str) -> Result<EditSummary> {
+        let mut buffer = self.buffer.write();
+        let start = if self.events.has_subscribers() {
+            Some(buffer.point(position)?)
+        } else {
+            None
+        };
+        let summary = buffer.insert(position, text)?;
+        drop(buffer);
+        self.history.record(Change::Insert { position, text: text.to_string() });
+        self.markers.update_positions(position, text.len() as isize);
+        self.update_selections(position..position, text.len());
+
+        if let Some(start) = start {
+            self.events.dispatch(Event::Buffer(BufferEvent::Inserted {
+                position,
+                text: text.to_string(),
+                edit: SyntaxEdit::insertion(position, start, text),
+            }));
+        }
+        Ok(summary)
+    }
+
+    /// Deletes text from the buffer and reports it to subscribers
+    fn delete_text(&mut self, start: usize, end: usize) -> Result<EditSummary> {
+        let mut buffer = self.buffer.write();
+        let text = buffer.slice(start, end)?;
+        let point = if self.events.has_subscribers() {
+            Some(buffer.point(start)?)
+        } else {
+            None
+        };
+        let summary = buffer.delete(start, end)?;
+        drop(buffer);
+        self.history.record(Change::Delete { position: start, text: text.clone() });
+        self.markers.update_positions(start, -((end - start) as isize));
+        self.update_selections(start..end, 0);
+
+        if let Some(point) = point {
+            let edit = SyntaxEdit::deletion(start, point, &text);
+            self.events.dispatch(Event::Buffer(BufferEvent::Deleted { start, end, text, edit }));
+        }
+        Ok(summary)
+    }
+
+    /// Moves the selections to follow `range` being replaced by `new_len` bytes
+    fn update_selections(&mut self, range: Range<usize>, new_len: usize) {
+        for selection in &mut self.selections {
+            selection.follow_edit(range.clone(), new_len);
+        }
+    }
+
+    /// Ends an edit, making it one undo step
+    fn finish_edit(&mut self) {
+        self.version += 1;
+        self.history.commit();
+    }
+
+    /// Undoes the most recent edit
+    ///
+    /// Returns false if there is nothing to undo.
+    pub fn undo(&mut self) -> Result<bool> {
+        self.check_writable()?;
+        let Some(changes) = self.history.undo() else {
+            return Ok(false);
+        };
+
+        for change in changes.iter().rev() {
+            match change {
+                Change::Insert { position, text } => {
+                    self.delete_text(*position, position + text.len())?;
+                }
+                Change::Delete { position, text } => {
+                    self.insert_text(*position, text)?;
+                }
+                Change::Replace { position, old_text, text } => {
+                    self.replace_text(*position, position + text.len(), old_text)?;
+                }
+                Change::LineBreaks { breaks, new } => self.rewrite_line_breaks(breaks, *new, true)?,
+                Change::LineEnding { old, .. } => self.apply_line_ending(*old),
+            }
+        }
+        self.history.discard_pending();
+        self.version += 1;
+        Ok(true)
+    }
+
+    /// Redoes the most recently undone edit
+    ///
+    /// Returns false if there is nothing to redo.
+    pub fn redo(&mut self) -> Result<bool> {
+        self.check_writable()?;
+        let Some(changes) = self.history.redo() else {
+            return Ok(false);
+        };
+
+        for change in &changes {
+            match change {
+                Change::Insert { position, text } => {
+                    self.insert_text(*position, text)?;
+                }
+                Change::Delete { position, text } => {
+                    self.delete_text(*position, position + text.len())?;
+                }
+                Change::Replace { position, old_text, text } => {
+                    self.replace_text(*position, position + old_text.len(), text)?;
+                }
+                Change::LineBreaks { breaks, new } => self.rewrite_line_breaks(breaks, *new, false)?,
+                Change::LineEnding { new, .. } => self.apply_line_ending(*new),
+            }
+        }
+        self.history.discard_pending();
+        self.version += 1;
+        Ok(true)
+    }
+
+    /// Returns true if there is an edit to undo
+    pub fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    /// Returns true if there is an edit to redo
+    pub fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    /// Undoes or redoes edits until the text is as it was last saved
+    ///
+    /// Returns false if the saved state can no longer be reached, e.g. after
+    /// undoing past the save and making a new edit.
+    pub fn undo_to_save(&mut self) -> Result<bool> {
+        while let Some(direction) = self.history.direction_to_saved() {
+            match direction {
+                Direction::Undo => self.undo()?,
+                Direction::Redo => self.redo()?,
+            };
+        }
+        Ok(!self.is_dirty())
+    }
+
+    /// Replaces a range of the buffer as one change, reporting a deletion then
+    /// an insertion to subscribers
+    fn replace_text(&mut self, start: usize, end: usize, text: &str) -> Result<EditSummary> {
+        let mut buffer = self.buffer.write();
+        let old_text = buffer.slice(start, end)?;
+        let point = if self.events.has_subscribers() {
+            Some(buffer.point(start)?)
+        } else {
+            None
+        };
+        let summary = buffer.replace(start, end, text)?;
+        drop(buffer);
+        self.history.record(Change::Replace {
+            position: start,
+            old_text: old_text.clone(),
+            text: text.to_string(),
+        });
+        self.markers.update_positions(start, -((end - start) as isize));
+        self.markers.update_positions(start, text.len() as isize);
+        self.update_selections(start..end, text.len());
+
+        if let Some(point) = point {
+            let edit = SyntaxEdit::deletion(start, point, &old_text);
+            self.events.dispatch(Event::Buffer(BufferEvent::Deleted { start, end, text: old_text, edit }));
+            self.events.dispatch(Event::Buffer(BufferEvent::Inserted {
+                position: start,
+                text: text.to_string(),
+                edit: SyntaxEdit::insertion(start, point, text),
+            }));
+        }
+        Ok(summary)
+    }
+
     /// Saves the document to its file
+    ///
+    /// The current history position is remembered, so undoing or redoing back
+    /// to it leaves the document clean and `undo_to_save` can return to it.
     pub fn save(&mut self) -> Result<()> {
         // Before saving, normalize line endings if needed
-        if let Some(_path) = &self.metadata.path {
-            let text = self.text();
-            let normalized_text = self.metadata.line_ending.normalize(&text);
-            
-            // Only rewrite if line endings changed
-            if normalized_text != text {
-                let mut buffer = self.buffer.write();
-                buffer.delete(0, text.len())?;  // Clear existing content
-                buffer.insert(0, &normalized_text)?;  // Insert normalized content
-            }
+        if self.metadata.path.is_some() {
+            self.convert_line_endings(self.metadata.line_ending)?;
         }
-        
-        self.buffer.write().save()
+
+        let utf8 = self.content_kind == ContentKind::Text { encoding: Encoding::Utf8 };
+        if self.incremental_save && utf8 {
+            self.buffer.write().save_incremental()?;
+        } else {
+            self.buffer.write().save()?;
+        }
+        if self.verify_on_save && !self.buffer.read().verify_saved()? {
+            return Err(Error::Document("save verification failed".to_string()));
+        }
+        self.history.mark_saved();
+        let disk = self.buffer.read().file_metadata()?;
+        self.apply_disk_metadata(disk);
+        Ok(())
+    }
+
+    /// Returns true if the file changed on disk since it was loaded or saved
+    ///
+    /// Compares the file's size and modification time with those recorded.
+    /// Documents without a file, or whose file is gone, have not changed.
+    pub fn changed_on_disk(&self) -> Result<bool> {
+        if self.metadata.path.is_none() {
+            return Ok(false);
+        }
+        Ok(self.buffer.read().file_metadata()?.is_some_and(|disk| {
+            Some(disk.len) != self.metadata.size_on_disk || disk.modified != self.metadata.modified
+        }))
+    }
+
+    /// Records the file's current state on disk without reloading it, so
+    /// `changed_on_disk` reports only later changes
+    pub fn acknowledge_disk_change(&mut self) -> Result<()> {
+        let disk = self.buffer.read().file_metadata()?;
+        self.apply_disk_metadata(disk);
+        Ok(())
+    }
+
+    /// Replaces the text with the file's current content
+    ///
+    /// The reload is a single edit of the part that differs, so markers and
+    /// selections outside it keep their places, and undoing it brings back
+    /// the text as it was, unsaved edits included. Afterwards the document is
+    /// clean.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the document has no file or it cannot be read.
+    pub fn reload(&mut self) -> Result<()> {
+        let bytes = self.buffer.read().read_file()?
+            .ok_or_else(|| Error::Document(format!("{} has no file to reload", self.metadata.name)))?;
+        let (content_kind, text) = ContentKind::decode(&bytes);
+
+        let current = self.text();
+        if let Some((range, replacement)) = changed_range(&current, &text) {
+            self.replace_text(range.start, range.end, replacement)?;
+            self.finish_edit();
+        }
+        self.content_kind = content_kind;
+        if let ContentKind::Text { encoding } = content_kind {
+            self.buffer.write().set_encoding(encoding);
+        }
+        self.metadata.line_ending = LineEnding::detect(&text);
+        self.history.mark_saved();
+        self.buffer.write().mark_saved();
+        self.acknowledge_disk_change()
+    }
+
+    /// Stores the on-disk size, modification time and permissions
+    fn apply_disk_metadata(&mut self, disk: Option<FileMetadata>) {
+        if let Some(disk) = disk {
+            self.metadata.size_on_disk = Some(disk.len);
+            self.metadata.modified = disk.modified;
+            self.metadata.read_only_on_disk = disk.read_only;
+        }
+    }
+
+    /// Returns an error if the document is read-only
+    fn check_writable(&self) -> Result<()> {
+        if self.read_only {
+            return Err(Error::Document(format!("{} is read-only", self.metadata.name)));
+        }
+        Ok(())
     }
     
     /// Normalizes the document's line endings to the specified style
+    ///
+    /// Only the line breaks in another style are rewritten, so markers and
+    /// selections stay in place, and subscribers see each as an edit. The
+    /// conversion, including the change of the stored style, is a single
+    /// undo step; undoing it restores the original bytes and style.
     pub fn normalize_line_endings(&mut self, line_ending: LineEnding) -> Result<()> {
+        self.check_writable()?;
+        self.convert_line_endings(line_ending)
+    }
+
+    /// Rewrites the line breaks not in `line_ending` and stores the style,
+    /// recording both as one undo step
+    fn convert_line_endings(&mut self, line_ending: LineEnding) -> Result<()> {
         let text = self.text();
-        let normalized_text = line_ending.normalize(&text);
-        
-        // Only update if there were changes
-        if normalized_text != text {
-            let mut buffer = self.buffer.write();
-            buffer.delete(0, text.len())?;  // Clear existing content
-            buffer.insert(0, &normalized_text)?;  // Insert normalized content
-            self.metadata.line_ending = line_ending;
-            self.version += 1;
+        let target = line_ending.as_str();
+        let breaks: Vec<(usize, LineEnding)> = line_breaks(&text)
+            .into_iter()
+            .filter(|range| &text[range.clone()] != target)
+            .map(|range| (range.start, LineEnding::of_break(&text[range])))
+            .collect();
+        let mut changed = false;
+        if !breaks.is_empty() {
+            self.rewrite_line_breaks(&breaks, line_ending, false)?;
+            self.history.record(Change::LineBreaks { breaks, new: line_ending });
+            changed = true;
+        }
+
+        let old = self.metadata.line_ending;
+        if old != line_ending {
+            self.history.record(Change::LineEnding { old, new: line_ending });
+            self.apply_line_ending(line_ending);
+            changed = true;
+        }
+        if changed {
+            self.finish_edit();
         }
-        
         Ok(())
     }
 
-    /// Returns true if the document has unsaved changes
-    pub fn is_dirty(&self) -> bool {
-        self.buffer.read().is_dirty()
+    /// Rewrites line breaks to the `new` style, or back with `undo`
+    ///
+    /// `breaks` are the offsets the breaks had before the rewrite and their
+    /// style then. The buffer is locked and markers are moved once for all
+    /// breaks, while subscribers still see each rewritten break as an edit.
+    /// Nothing is recorded in the history.
+    fn rewrite_line_breaks(&mut self, breaks: &[(usize, LineEnding)], new: LineEnding, undo: bool) -> Result<()> {
+        // Ranges in the current text with their replacement, front to back
+        let mut shift = 0isize;
+        let edits: Vec<(Range<usize>, &str)> = breaks.iter()
+            .map(|&(position, old)| {
+                let (from, to) = if undo { (new.as_str(), old.as_str()) } else { (old.as_str(), new.as_str()) };
+                let start = position.saturating_add_signed(shift);
+                if undo {
+                    shift += from.len() as isize - to.len() as isize;
+                }
+                (start..start + from.len(), to)
+            })
+            .collect();
+
+        // Back to front, so earlier offsets stay valid
+        let mut buffer = self.buffer.write();
+        let mut events = Vec::new();
+        for (range, text) in edits.iter().rev() {
+            let old_text = buffer.slice(range.start, range.end)?;
+            if self.events.has_subscribers() {
+                let point = buffer.point(range.start)?;
+                events.push(BufferEvent::Deleted {
+                    start: range.start,
+                    end: range.end,
+                    edit: SyntaxEdit::deletion(range.start, point, &old_text),
+                    text: old_text,
+                });
+                events.push(BufferEvent::Inserted {
+                    position: range.start,
+                    text: text.to_string(),
+                    edit: SyntaxEdit::insertion(range.start, point, text),
+                });
+            }
+            buffer.replace(range.start, range.end, text)?;
+        }
+        drop(buffer);
+
+        // A position inside or at the start of a break ends up after the new
+        // break, as with any replacement
+        let mut deltas = Vec::with_capacity(edits.len());
+        let mut total = 0isize;
+        for (range, text) in &edits {
+            total += text.len() as isize - range.len() as isize;
+            deltas.push(total);
+        }
+        self.markers.map_positions(|position| {
+            let before = edits.partition_point(|(range, _)| range.start <= position);
+            match before.checked_sub(1) {
+                Some(last) => position.max(edits[last].0.end).saturating_add_signed(deltas[last]),
+                None => position,
+            }
+        });
+        for (range, text) in edits.iter().rev() {
+            self.update_selections(range.clone(), text.len());
+        }
+
+        for event in events {
+            self.events.dispatch(Event::Buffer(event));
+        }
+        Ok(())
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Stores the line ending style and reports it to subscribers
+    fn apply_line_ending(&mut self, line_ending: LineEnding) {
+        self.metadata.line_ending = line_ending;
+        self.events.dispatch(Event::Document(DocumentEvent::LineEndingChanged {
+            name: self.metadata.name.clone(),
+            path: self.metadata.path.clone(),
+            line_ending,
+        }));
+    }
 
-    #[test]
-    fn test_document_operations() {
-        let mut doc = Document::new("test.txt");
-        
-        // Test insert
-        doc.insert(0, "Hello").unwrap();
-        assert_eq!(doc.text(), "Hello");
-        
-        // Test version increment
-        assert_eq!(doc.version(), 1);
-        
-        // Test delete
-        doc.delete(0, 1).unwrap();
-        assert_eq!(doc.text(), "ello");
-        assert_eq!(doc.version(), 2);
-        
-        // Test metadata
-        assert_eq!(doc.name(), "test.txt");
-        assert!(doc.is_dirty());
+    /// Sorts the full lines touched by `range`
+    ///
+    /// The lines are replaced in a single edit, so the sort counts as one
+    /// change. Whether the last line ends with a newline is preserved.
+    pub fn sort_lines(&mut self, range: Range<usize>, options: SortOptions) -> Result<()> {
+        self.edit_lines(range, |lines| lines.sort_by(|a, b| options.compare(a, b)))
     }
-    
-    #[test]
-    fn test_line_ending_detection() {
-        assert_eq!(LineEnding::detect("hello\nworld"), LineEnding::Unix);
-        assert_eq!(LineEnding::detect("hello\r\nworld"), LineEnding::Windows);
-        assert_eq!(LineEnding::detect("hello\rworld"), LineEnding::Mac);
-        assert_eq!(LineEnding::detect("no newlines"), LineEnding::Unix);
-        
-        // Mixed line endings should prioritize Windows (CRLF) as it's most specific
-        assert_eq!(LineEnding::detect("hello\r\nworld\ntest"), LineEnding::Windows);
+
+    /// Removes adjacent duplicate lines among the full lines touched by `range`
+    pub fn dedup_lines(&mut self, range: Range<usize>) -> Result<()> {
+        self.edit_lines(range, |lines| lines.dedup())
     }
-    
-    #[test]
-    fn test_line_ending_normalization() {
-        let unix_text = "line1\nline2\nline3";
-        let windows_text = "line1\r\nline2\r\nline3";
-        let mac_text = "line1\rline2\rline3";
-        
-        // Test normalization to different line ending styles
-        assert_eq!(LineEnding::Unix.normalize(windows_text), unix_text);
+
+    /// Sorts the full lines touched by the primary selection
+    pub fn sort_selected_lines(&mut self, options: SortOptions) -> Result<()> {
+        self.sort_lines(self.primary_selection().range(), options)
+    }
+
+    /// Removes adjacent duplicate lines among the full lines touched by the
+    /// primary selection
+    pub fn dedup_selected_lines(&mut self) -> Result<()> {
+        self.dedup_lines(self.primary_selection().range())
+    }
+
+    /// Rewrites the full lines touched by `range` as one edit
+    fn edit_lines(&mut self, range: Range<usize>, edit: impl FnOnce(&mut Vec<&str>)) -> Result<()> {
+        self.check_writable()?;
+
+        let text = self.text();
+        let separator = self.metadata.line_ending.as_str();
+        let newline = separator.chars().last().unwrap_or('\n');
+        let span = lines::full_line_range(&text, range, newline);
+        let region = &text[span.clone()];
+
+        let (body, trailing) = match region.strip_suffix(separator) {
+            Some(body) => (body, separator),
+            None => (region, ""),
+        };
+        let mut lines: Vec<&str> = body.split(separator).collect();
+        edit(&mut lines);
+
+        let replacement = lines.join(separator) + trailing;
+        if replacement != region {
+            self.replace_text(span.start, span.end, &replacement)?;
+            self.finish_edit();
+        }
+        Ok(())
+    }
+
+    /// Recomputes the indentation of the full lines touched by `range`
+    ///
+    /// Each line is indented according to `rules`, starting from the line
+    /// before the range. The lines are replaced in a single edit.
+    pub fn reindent_range(&mut self, range: Range<usize>, rules: &IndentRules, settings: IndentSettings) -> Result<()> {
+        self.check_writable()?;
+
+        let text = self.text();
+        let span = self.full_line_range(&text, range);
+        let replacement = self.reindent_region(&text, span.clone(), rules, settings);
+        if replacement != text[span.clone()] {
+            self.replace_text(span.start, span.end, &replacement)?;
+            self.finish_edit();
+        }
+        Ok(())
+    }
+
+    /// Recomputes the indentation of the full lines touched by the primary
+    /// selection
+    pub fn reindent_selection(&mut self, rules: &IndentRules, settings: IndentSettings) -> Result<()> {
+        self.reindent_range(self.primary_selection().range(), rules, settings)
+    }
+
+    /// Pastes text at `position`, reindenting its lines to fit the surrounding code
+    ///
+    /// The paste and the reindent are a single edit.
+    pub fn paste_reindented(
+        &mut self,
+        position: usize,
+        pasted: &str,
+        rules: &IndentRules,
+        settings: IndentSettings,
+    ) -> Result<()> {
+        self.check_writable()?;
+
+        let text = self.text();
+        if position > text.len() || !text.is_char_boundary(position) {
+            return Err(Error::Document(format!("Invalid paste position {}", position)));
+        }
+        let combined = format!("{}{}{}", &text[..position], pasted, &text[position..]);
+        let span = self.full_line_range(&combined, position..position + pasted.len());
+        let replacement = self.reindent_region(&combined, span.clone(), rules, settings);
+
+        // The same lines without the pasted text
+        self.replace_text(span.start, span.end - pasted.len(), &replacement)?;
+        self.finish_edit();
+        Ok(())
+    }
+
+    /// Expands a byte range to the full lines it touches
+    fn full_line_range(&self, text: &str, range: Range<usize>) -> Range<usize> {
+        let newline = self.metadata.line_ending.as_str().chars().last().unwrap_or('\n');
+        lines::full_line_range(text, range, newline)
+    }
+
+    /// Returns the reindented text of a span of full lines
+    fn reindent_region(&self, text: &str, span: Range<usize>, rules: &IndentRules, settings: IndentSettings) -> String {
+        let separator = self.metadata.line_ending.as_str();
+        let region = &text[span.clone()];
+        let (body, trailing) = match region.strip_suffix(separator) {
+            Some(body) => (body, separator),
+            None => (region, ""),
+        };
+
+        let lines: Vec<&str> = body.split(separator).collect();
+        rules.reindent(&text[..span.start], &lines, settings).join(separator) + trailing
+    }
+
+    /// Replaces the columns of a block selection with `text` on every line
+    ///
+    /// With an empty block this inserts `text` at the block's column. Lines
+    /// past the end of the document are ignored. All lines are rewritten in a
+    /// single edit, so the block edit counts as one change.
+    pub fn insert_block(&mut self, block: BlockSelection, text: &str, short_lines: ShortLines) -> Result<()> {
+        self.edit_block(block, |line| block.edit_line(line, text, short_lines))
+    }
+
+    /// Deletes the columns of a block selection on every line
+    pub fn delete_block(&mut self, block: BlockSelection) -> Result<()> {
+        self.edit_block(block, |line| block.edit_line(line, "", ShortLines::Clip))
+    }
+
+    /// Rewrites each line of a block selection as one edit
+    fn edit_block(&mut self, block: BlockSelection, edit: impl Fn(&str) -> String) -> Result<()> {
+        self.check_writable()?;
+
+        let text = self.text();
+        let separator = self.metadata.line_ending.as_str();
+        let lines: Vec<&str> = text.split(separator).collect();
+        if block.top_line >= lines.len() {
+            return Err(Error::Document(format!(
+                "Block starts at line {} but the document has {} lines",
+                block.top_line + 1,
+                lines.len()
+            )));
+        }
+
+        let selected = &lines[block.top_line..=block.bottom_line.min(lines.len() - 1)];
+        let start: usize = lines[..block.top_line].iter().map(|line| line.len() + separator.len()).sum();
+        let end = start + selected.join(separator).len();
+
+        let replacement = selected.iter().map(|line| edit(line)).collect::<Vec<_>>().join(separator);
+        if replacement != text[start..end] {
+            self.replace_text(start, end, &replacement)?;
+            self.finish_edit();
+        }
+        Ok(())
+    }
+
+    /// Returns the selections, the primary one first
+    pub fn selections(&self) -> &[Selection] {
+        &self.selections
+    }
+
+    /// Returns the primary selection, which commands acting on a single range
+    /// use by default
+    pub fn primary_selection(&self) -> Selection {
+        self.selections[0]
+    }
+
+    /// Replaces the selections, e.g. as the cursor moves in a view
+    ///
+    /// Selections are clamped to the text, and an empty list leaves a cursor
+    /// at the start. Subscribers receive `SelectionChanged` if the selections
+    /// actually change.
+    pub fn set_selections(&mut self, selections: Vec<Selection>) {
+        let len = self.buffer.read().len();
+        let mut selections: Vec<_> = selections.into_iter().map(|selection| selection.clamped(len)).collect();
+        if selections.is_empty() {
+            selections.push(Selection::default());
+        }
+        if selections == self.selections {
+            return;
+        }
+        self.selections = selections;
+        self.events.dispatch(Event::Document(DocumentEvent::SelectionChanged {
+            name: self.metadata.name.clone(),
+            path: self.metadata.path.clone(),
+            selections: self.selections.clone(),
+        }));
+    }
+
+    /// Replaces the selections with ones made in `text`, an edited copy of
+    /// the document's text such as a view's, mapping them to the document
+    ///
+    /// Ends within the edited part move to the end of the text it replaced.
+    pub fn set_selections_in(&mut self, text: &str, mut selections: Vec<Selection>) {
+        if let Some((range, replacement)) = changed_range(&self.text(), text) {
+            let edited = range.start..range.start + replacement.len();
+            for selection in &mut selections {
+                selection.follow_edit(edited.clone(), range.len());
+            }
+        }
+        self.set_selections(selections);
+    }
+
+    /// Returns the document's markers
+    pub fn markers(&self) -> &MarkerSet {
+        &self.markers
+    }
+
+    /// Returns an index of the current line starts, for looking up markers
+    /// by line
+    pub fn line_index(&self) -> LineIndex {
+        LineIndex::new(&self.text())
+    }
+
+    /// Converts a byte range to an LSP range, with UTF-16 columns
+    ///
+    /// Offsets are clamped to the text.
+    #[cfg(feature = "lsp")]
+    pub fn byte_range_to_lsp(&self, range: Range<usize>) -> lsp_types::Range {
+        let text = self.text();
+        let index = LineIndex::for_lsp(&text);
+        lsp_types::Range::new(index.lsp_position(&text, range.start), index.lsp_position(&text, range.end))
+    }
+
+    /// Converts an LSP range, with UTF-16 columns, to a byte range
+    ///
+    /// Positions past the end of the text are clamped to it. Returns `None`
+    /// if either end is inside a character, or if the range ends before it
+    /// starts.
+    #[cfg(feature = "lsp")]
+    pub fn lsp_range_to_byte(&self, range: lsp_types::Range) -> Option<Range<usize>> {
+        let text = self.text();
+        let index = LineIndex::for_lsp(&text);
+        let offset = |position: lsp_types::Position| {
+            index.utf16_offset(&text, position.line as usize, position.character as usize)
+        };
+        let (start, end) = (offset(range.start)?, offset(range.end)?);
+        (start <= end).then_some(start..end)
+    }
+
+    /// Returns the document's markers for modification
+    pub fn markers_mut(&mut self) -> &mut MarkerSet {
+        &mut self.markers
+    }
+
+    /// Adds a bookmark at the start of a line, or removes the bookmarks on it
+    ///
+    /// Returns true if a bookmark was added.
+    pub fn toggle_bookmark(&mut self, line: usize, label: Option<String>) -> Result<bool> {
+        let index = self.line_index();
+        let range = index
+            .line_range(line)
+            .ok_or_else(|| Error::Document(format!("Line {} does not exist", line + 1)))?;
+
+        let existing: Vec<String> = self.markers
+            .markers_on_line(&index, line)
+            .into_iter()
+            .filter(|marker| marker.marker_type == MarkerType::Bookmark)
+            .map(|marker| marker.name.clone())
+            .collect();
+        if existing.is_empty() {
+            self.add_bookmark(range.start, label);
+            return Ok(true);
+        }
+        for name in existing {
+            self.markers.remove(&name);
+        }
+        Ok(false)
+    }
+
+    /// Adds a bookmark marker under an unused name
+    fn add_bookmark(&mut self, position: usize, label: Option<String>) {
+        let name = (0..)
+            .map(|n| format!("{}{}", BOOKMARK_PREFIX, n))
+            .find(|name| self.markers.get(name).is_none())
+            .expect("unbounded range always yields an unused name");
+        match label {
+            Some(label) => self.markers.set_with_data(
+                &name,
+                position,
+                MarkerType::Bookmark,
+                serde_json::json!({ "label": label }),
+            ),
+            None => self.markers.set_with_type(&name, position, MarkerType::Bookmark),
+        }
+    }
+
+    /// Returns the bookmarked lines, in order
+    pub fn bookmarks(&self) -> Vec<Bookmark> {
+        let index = self.line_index();
+        let mut bookmarks: Vec<Bookmark> = self.markers
+            .get_by_type(&MarkerType::Bookmark)
+            .into_iter()
+            .map(|marker| Bookmark {
+                line: index.line_of(marker.position),
+                label: marker.data.as_ref().and_then(|data| data["label"].as_str()).map(str::to_string),
+            })
+            .collect();
+        bookmarks.sort_by_key(|bookmark| bookmark.line);
+        bookmarks.dedup_by_key(|bookmark| bookmark.line);
+        bookmarks
+    }
+
+    /// Replaces the bookmarks, e.g. with ones saved in a session
+    ///
+    /// Bookmarks past the last line, left behind when the file was shortened
+    /// outside the editor, are dropped. Returns the number restored.
+    pub fn restore_bookmarks(&mut self, bookmarks: &[Bookmark]) -> usize {
+        let existing: Vec<String> = self.markers
+            .get_by_type(&MarkerType::Bookmark)
+            .into_iter()
+            .map(|marker| marker.name.clone())
+            .collect();
+        for name in existing {
+            self.markers.remove(&name);
+        }
+
+        let index = self.line_index();
+        let mut restored = 0;
+        for bookmark in bookmarks {
+            if let Some(range) = index.line_range(bookmark.line) {
+                self.add_bookmark(range.start, bookmark.label.clone());
+                restored += 1;
+            }
+        }
+        restored
+    }
+
+    /// Returns the start of the next bookmarked line after the one holding
+    /// `offset`, wrapping around to the first
+    pub fn next_bookmark(&self, offset: usize) -> Option<usize> {
+        let index = self.line_index();
+        let line = index.line_of(offset);
+        let lines: Vec<usize> = self.bookmarks().into_iter().map(|bookmark| bookmark.line).collect();
+        let next = lines.iter().find(|&&l| l > line).or(lines.first())?;
+        index.line_range(*next).map(|range| range.start)
+    }
+
+    /// Returns the start of the previous bookmarked line before the one
+    /// holding `offset`, wrapping around to the last
+    pub fn prev_bookmark(&self, offset: usize) -> Option<usize> {
+        let index = self.line_index();
+        let line = index.line_of(offset);
+        let lines: Vec<usize> = self.bookmarks().into_iter().map(|bookmark| bookmark.line).collect();
+        let prev = lines.iter().rev().find(|&&l| l < line).or(lines.last())?;
+        index.line_range(*prev).map(|range| range.start)
+    }
+
+    /// Returns the diagnostic markers at least as severe as `min_severity`,
+    /// or all of them without one, ordered by position
+    fn diagnostics_in_order(&self, min_severity: Option<DiagnosticSeverity>) -> Vec<&Marker> {
+        let mut diagnostics: Vec<&Marker> = self.markers
+            .diagnostics()
+            .into_iter()
+            .filter(|marker| match &marker.marker_type {
+                MarkerType::Diagnostic { severity, .. } => min_severity.is_none_or(|min| *severity <= min),
+                _ => false,
+            })
+            .collect();
+        diagnostics.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        diagnostics
+    }
+
+    /// Returns the first diagnostic after `from_offset`, wrapping around to
+    /// the first one
+    ///
+    /// With `min_severity`, diagnostics less severe than it are skipped, e.g.
+    /// `Some(DiagnosticSeverity::Warning)` visits only errors and warnings.
+    pub fn next_diagnostic(&self, from_offset: usize, min_severity: Option<DiagnosticSeverity>) -> Option<&Marker> {
+        let diagnostics = self.diagnostics_in_order(min_severity);
+        diagnostics.iter().find(|marker| marker.position > from_offset).or(diagnostics.first()).copied()
+    }
+
+    /// Returns the last diagnostic before `from_offset`, wrapping around to
+    /// the last one
+    ///
+    /// `min_severity` filters like in `next_diagnostic`.
+    pub fn prev_diagnostic(&self, from_offset: usize, min_severity: Option<DiagnosticSeverity>) -> Option<&Marker> {
+        let diagnostics = self.diagnostics_in_order(min_severity);
+        diagnostics.iter().rev().find(|marker| marker.position < from_offset).or(diagnostics.last()).copied()
+    }
+
+    /// Returns true if the document has unsaved changes
+    ///
+    /// Undoing or redoing back to the last save makes the document clean again.
+    pub fn is_dirty(&self) -> bool {
+        self.history.is_dirty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax_edit::SyntaxPoint;
+
+    #[test]
+    fn test_document_operations() {
+        let mut doc = Document::new("test.txt");
+        
+        // Test insert
+        doc.insert(0, "Hello").unwrap();
+        assert_eq!(doc.text(), "Hello");
+        
+        // Test version increment
+        assert_eq!(doc.version(), 1);
+        
+        // Test delete
+        doc.delete(0, 1).unwrap();
+        assert_eq!(doc.text(), "ello");
+        assert_eq!(doc.version(), 2);
+        
+        // Test metadata
+        assert_eq!(doc.name(), "test.txt");
+        assert!(doc.is_dirty());
+    }
+    
+    #[test]
+    fn test_line_ending_detection() {
+        assert_eq!(LineEnding::detect("hello\nworld"), LineEnding::Unix);
+        assert_eq!(LineEnding::detect("hello\r\nworld"), LineEnding::Windows);
+        assert_eq!(LineEnding::detect("hello\rworld"), LineEnding::Mac);
+        assert_eq!(LineEnding::detect("no newlines"), LineEnding::Unix);
+        
+        // Ties between mixed line endings prioritize Windows (CRLF) as it's most specific
+        assert_eq!(LineEnding::detect("hello\r\nworld\ntest"), LineEnding::Windows);
+    }
+
+    #[test]
+    fn test_mixed_line_endings() {
+        let counts = LineEndingCounts::count("a\r\nb\nc\n\rd\r\n\n");
+        assert_eq!(counts, LineEndingCounts { unix: 3, windows: 2, mac: 1 });
+        assert!(counts.is_mixed());
+
+        // The majority is suggested as the target
+        assert_eq!(LineEnding::detect_mixed("a\nb\r\nc\n"), Some(LineEnding::Unix));
+        assert_eq!(LineEnding::detect_mixed("a\r\nb\r\nc\n"), Some(LineEnding::Windows));
+        assert_eq!(LineEnding::detect_mixed("a\rb\rc\r\n"), Some(LineEnding::Mac));
+        assert_eq!(LineEnding::detect("a\nb\r\nc\n"), LineEnding::Unix);
+
+        // A single style, or none, is not mixed
+        assert_eq!(LineEnding::detect_mixed("a\r\nb\r\n"), None);
+        assert_eq!(LineEnding::detect_mixed("a\nb"), None);
+        assert_eq!(LineEnding::detect_mixed("abc"), None);
+    }
+    
+    #[test]
+    fn test_line_ending_normalization() {
+        let unix_text = "line1\nline2\nline3";
+        let windows_text = "line1\r\nline2\r\nline3";
+        let mac_text = "line1\rline2\rline3";
+        
+        // Test normalization to different line ending styles
+        assert_eq!(LineEnding::Unix.normalize(windows_text), unix_text);
         assert_eq!(LineEnding::Windows.normalize(unix_text), windows_text);
         assert_eq!(LineEnding::Mac.normalize(unix_text), mac_text);
         
@@ -282,4 +1538,875 @@ mod tests {
         assert_eq!(LineEnding::Windows.normalize(mixed_text), "line1\r\nline2\r\nline3\r\nline4");
         assert_eq!(LineEnding::Mac.normalize(mixed_text), "line1\rline2\rline3\rline4");
     }
+
+    #[test]
+    fn test_convert_crlf_to_lf() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "line1\r\nline2\r\n").unwrap();
+        doc.set_line_ending(LineEnding::Windows);
+        let version = doc.version();
+
+        doc.normalize_line_endings(LineEnding::Unix).unwrap();
+        assert_eq!(doc.text(), "line1\nline2\n");
+        assert_eq!(*doc.line_ending(), LineEnding::Unix);
+        assert_eq!(doc.version(), version + 1);
+
+        // The style is stored even when the text has no line breaks
+        let mut doc = Document::new("empty.txt");
+        doc.normalize_line_endings(LineEnding::Mac).unwrap();
+        assert_eq!(*doc.line_ending(), LineEnding::Mac);
+        assert_eq!(LineEnding::Mac.label(), "CR");
+    }
+
+    #[test]
+    fn test_undo_line_ending_conversion() {
+        let mut doc = Document::new("mixed.txt");
+        let original = "one\r\ntwo\nthree\rfour\r\n";
+        doc.insert(0, original).unwrap();
+        doc.set_line_ending(LineEnding::Windows);
+        doc.markers_mut().set("three", 13);
+        doc.set_selections(vec![Selection::new(1, 18)]);
+        let mut events = doc.subscribe();
+
+        doc.normalize_line_endings(LineEnding::Unix).unwrap();
+        assert_eq!(doc.text(), "one\ntwo\nthree\nfour\n");
+        assert_eq!(*doc.line_ending(), LineEnding::Unix);
+        // Only the line breaks were rewritten, so markers keep their place
+        assert_eq!(doc.markers().get("three"), Some(12));
+        assert_eq!(doc.primary_selection(), Selection::new(1, 17));
+
+        // Each rewritten break is an edit, then the style change follows
+        let mut received = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            received.push(event);
+        }
+        assert_eq!(received.iter().filter(|event| matches!(event, Event::Buffer(BufferEvent::Inserted { .. }))).count(), 3);
+        assert!(matches!(
+            received.last(),
+            Some(Event::Document(DocumentEvent::LineEndingChanged { line_ending: LineEnding::Unix, .. }))
+        ));
+
+        // One undo restores the original bytes and style
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), original);
+        assert_eq!(*doc.line_ending(), LineEnding::Windows);
+        assert_eq!(doc.markers().get("three"), Some(13));
+        assert_eq!(doc.primary_selection(), Selection::new(1, 18));
+        assert!(doc.redo().unwrap());
+        assert_eq!(doc.text(), "one\ntwo\nthree\nfour\n");
+        assert_eq!(*doc.line_ending(), LineEnding::Unix);
+
+        // Converting to the current style again changes nothing
+        let version = doc.version();
+        doc.normalize_line_endings(LineEnding::Unix).unwrap();
+        assert_eq!(doc.version(), version);
+
+        doc.set_read_only(true);
+        assert!(doc.normalize_line_endings(LineEnding::Mac).is_err());
+        assert_eq!(*doc.line_ending(), LineEnding::Unix);
+    }
+
+    #[test]
+    fn test_file_metadata() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "hello\n").unwrap();
+
+        let mut doc = Document::from_file(&path).unwrap();
+        assert_eq!(doc.size_on_disk(), Some(6));
+        assert!(doc.modified().is_some());
+        assert!(!doc.is_read_only_on_disk());
+        assert!(!doc.is_read_only());
+
+        doc.insert(6, "world\n").unwrap();
+        doc.save().unwrap();
+        assert_eq!(doc.size_on_disk(), Some(12));
+
+        // Unsaved documents have no file metadata
+        let doc = Document::new("untitled.txt");
+        assert_eq!(doc.size_on_disk(), None);
+        assert_eq!(doc.modified(), None);
+    }
+
+    #[test]
+    fn test_reload_external_change() {
+        let fs = Arc::new(crate::MemoryFs::new().with_file("/notes.txt", "one\n"));
+        let mut doc = Document::from_file_in(fs.clone(), "/notes.txt").unwrap();
+        assert!(!doc.changed_on_disk().unwrap());
+
+        VirtualFs::write(fs.as_ref(), Path::new("/notes.txt"), "one\ntwo\n").unwrap();
+        assert!(doc.changed_on_disk().unwrap());
+
+        // Unsaved edits are replaced, but can be brought back
+        doc.insert(0, "zero\n").unwrap();
+        doc.reload().unwrap();
+        assert_eq!(doc.text(), "one\ntwo\n");
+        assert!(!doc.is_dirty());
+        assert!(!doc.changed_on_disk().unwrap());
+        doc.undo().unwrap();
+        assert_eq!(doc.text(), "zero\none\n");
+
+        // Acknowledging keeps the text
+        VirtualFs::write(fs.as_ref(), Path::new("/notes.txt"), "three\n").unwrap();
+        assert!(doc.changed_on_disk().unwrap());
+        doc.acknowledge_disk_change().unwrap();
+        assert!(!doc.changed_on_disk().unwrap());
+        assert_eq!(doc.text(), "zero\none\n");
+
+        // Only the changed part is replaced, so bookmarks before it stay
+        doc.toggle_bookmark(1, None).unwrap();
+        VirtualFs::write(fs.as_ref(), Path::new("/notes.txt"), "zero\none\nfour\n").unwrap();
+        doc.reload().unwrap();
+        assert_eq!(doc.bookmarks().iter().map(|bookmark| bookmark.line).collect::<Vec<_>>(), vec![1]);
+
+        assert!(!Document::new("untitled.txt").changed_on_disk().unwrap());
+        assert!(Document::new("untitled.txt").reload().is_err());
+    }
+
+    #[test]
+    fn test_content_kind() {
+        let dir = tempfile::TempDir::new().unwrap();
+
+        let utf8 = dir.path().join("main.rs");
+        std::fs::write(&utf8, "fn main() {}\n").unwrap();
+        let doc = Document::from_file(&utf8).unwrap();
+        assert_eq!(doc.content_kind(), ContentKind::Text { encoding: Encoding::Utf8 });
+
+        let utf16 = dir.path().join("notes.txt");
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend("héllo\n".encode_utf16().flat_map(u16::to_le_bytes));
+        std::fs::write(&utf16, bytes).unwrap();
+        let mut doc = Document::from_file(&utf16).unwrap();
+        assert_eq!(doc.content_kind(), ContentKind::Text { encoding: Encoding::Utf16LeBom });
+        assert_eq!(doc.text(), "héllo\n");
+        assert_eq!(doc.find_all("llo", SearchOptions::new()), vec![3..6]);
+
+        // Saving keeps the encoding and byte order mark
+        doc.insert(0, "¡").unwrap();
+        doc.save().unwrap();
+        let mut expected = vec![0xFF, 0xFE];
+        expected.extend("¡héllo\n".encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(std::fs::read(&utf16).unwrap(), expected);
+
+        // Binary files open empty and read-only, and search skips them
+        let blob = dir.path().join("image.png");
+        std::fs::write(&blob, b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR").unwrap();
+        let doc = Document::from_file(&blob).unwrap();
+        assert_eq!(doc.content_kind(), ContentKind::Binary);
+        assert!(doc.is_read_only());
+        assert_eq!(doc.text(), "");
+        assert_eq!(doc.find_next(0, "PNG", SearchOptions::new()), None);
+    }
+
+    #[test]
+    fn test_undo_across_save() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "").unwrap();
+        let mut doc = Document::from_file(&path).unwrap();
+
+        doc.insert(0, "hello").unwrap();
+        doc.save().unwrap();
+        assert!(!doc.is_dirty());
+
+        doc.insert(5, " world").unwrap();
+        assert!(doc.is_dirty());
+
+        // Undoing back to the save is clean, undoing past it is not
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), "hello");
+        assert!(!doc.is_dirty());
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), "");
+        assert!(doc.is_dirty());
+        assert!(!doc.undo().unwrap());
+
+        // Redo returns to the saved text, then past it
+        assert!(doc.redo().unwrap());
+        assert!(!doc.is_dirty());
+        assert!(doc.redo().unwrap());
+        assert_eq!(doc.text(), "hello world");
+
+        assert!(doc.undo_to_save().unwrap());
+        assert_eq!(doc.text(), "hello");
+        assert!(doc.can_redo());
+    }
+
+    #[test]
+    fn test_undo_compound_edit() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "b\na\nc\n").unwrap();
+        doc.sort_lines(0..6, SortOptions::new()).unwrap();
+        assert_eq!(doc.text(), "a\nb\nc\n");
+
+        // The sort is undone as one step
+        doc.undo().unwrap();
+        assert_eq!(doc.text(), "b\na\nc\n");
+        doc.redo().unwrap();
+        assert_eq!(doc.text(), "a\nb\nc\n");
+
+        // A new edit after undoing drops the redo step
+        doc.undo().unwrap();
+        doc.insert(0, "x").unwrap();
+        assert!(!doc.can_redo());
+
+        // A new document counts as saved while empty
+        assert!(doc.undo_to_save().unwrap());
+        assert_eq!(doc.text(), "");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_read_only_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("locked.txt");
+        std::fs::write(&path, "locked").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o444)).unwrap();
+
+        let mut doc = Document::from_file(&path).unwrap();
+        assert!(doc.is_read_only_on_disk());
+        assert!(doc.is_read_only());
+        assert!(doc.insert(0, "x").is_err());
+        assert_eq!(doc.text(), "locked");
+
+        // The flag can be cleared to edit anyway
+        doc.set_read_only(false);
+        doc.insert(0, "un").unwrap();
+        assert_eq!(doc.text(), "unlocked");
+    }
+
+    #[test]
+    fn test_sort_lines_case_insensitive() {
+        let mut doc = Document::new("list.txt");
+        doc.set_line_ending(LineEnding::Unix);
+        doc.insert(0, "header\nbanana\nApple\ncherry\nfooter").unwrap();
+        let version = doc.version();
+
+        // Selection from the middle of "banana" to the middle of "cherry"
+        let options = SortOptions::new().with_case_insensitive(true);
+        doc.sort_lines(9..24, options).unwrap();
+        assert_eq!(doc.text(), "header\nApple\nbanana\ncherry\nfooter");
+        assert_eq!(doc.version(), version + 1);
+
+        doc.sort_lines(0..doc.text().len(), options.with_descending(true)).unwrap();
+        assert_eq!(doc.text(), "header\nfooter\ncherry\nbanana\nApple");
+    }
+
+    #[test]
+    fn test_selections() {
+        let mut doc = Document::new("list.txt");
+        doc.set_line_ending(LineEnding::Unix);
+        doc.insert(0, "header\nbanana\nApple\ncherry\nfooter").unwrap();
+        // Typing at the cursor moves it along
+        assert_eq!(doc.primary_selection(), Selection::cursor(33));
+        let mut events = doc.subscribe();
+
+        // Head before anchor, from the middle of "cherry" to the middle of "banana"
+        doc.set_selections(vec![Selection::new(24, 9), Selection::cursor(100)]);
+        doc.set_selections(vec![Selection::new(24, 9), Selection::cursor(100)]);
+        assert_eq!(doc.selections(), &[Selection::new(24, 9), Selection::cursor(33)]);
+        let changes = std::iter::from_fn(|| events.try_recv().ok())
+            .filter(|event| matches!(event, Event::Document(DocumentEvent::SelectionChanged { .. })))
+            .count();
+        assert_eq!(changes, 1);
+
+        doc.sort_selected_lines(SortOptions::new().with_case_insensitive(true)).unwrap();
+        assert_eq!(doc.text(), "header\nApple\nbanana\ncherry\nfooter");
+
+        // The sorted lines stay selected, and selections follow edits before them
+        assert_eq!(doc.primary_selection().range(), 9..24);
+        doc.insert(0, "# ").unwrap();
+        assert_eq!(doc.primary_selection().range(), 11..26);
+        doc.set_selections(Vec::new());
+        assert_eq!(doc.selections(), &[Selection::cursor(0)]);
+    }
+
+    #[test]
+    fn test_selections_in_edited_text() {
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "one two three").unwrap();
+
+        // A view typed "2 " before "two" that the document doesn't have yet
+        let edited = "one 2 two three";
+        doc.set_selections_in(edited, vec![Selection::new(6, 9), Selection::cursor(2)]);
+        assert_eq!(doc.selections(), &[Selection::new(4, 7), Selection::cursor(2)]);
+        assert_eq!(&doc.text()[4..7], "two");
+
+        // Without edits the offsets are kept
+        doc.set_selections_in("one two three", vec![Selection::new(8, 13)]);
+        assert_eq!(doc.primary_selection(), Selection::new(8, 13));
+    }
+
+    #[test]
+    fn test_sort_lines_numeric() {
+        let mut doc = Document::new("numbers.txt");
+        doc.set_line_ending(LineEnding::Unix);
+        doc.insert(0, "10\n9\n100\n1\n").unwrap();
+
+        doc.sort_lines(0..doc.text().len(), SortOptions::new()).unwrap();
+        assert_eq!(doc.text(), "1\n10\n100\n9\n");
+
+        doc.sort_lines(0..doc.text().len(), SortOptions::new().with_numeric(true)).unwrap();
+        assert_eq!(doc.text(), "1\n9\n10\n100\n");
+    }
+
+    #[test]
+    fn test_dedup_lines() {
+        let mut doc = Document::new("dups.txt");
+        doc.set_line_ending(LineEnding::Windows);
+        doc.insert(0, "a\r\na\r\nb\r\na\r\nb\r\nb").unwrap();
+
+        doc.dedup_lines(0..doc.text().len()).unwrap();
+        assert_eq!(doc.text(), "a\r\nb\r\na\r\nb");
+
+        // Nothing to remove leaves the version untouched
+        let version = doc.version();
+        doc.dedup_lines(0..doc.text().len()).unwrap();
+        assert_eq!(doc.version(), version);
+    }
+
+    #[test]
+    fn test_insert_block() {
+        let mut doc = Document::new("list.txt");
+        doc.insert(0, "alpha\nbeta\ngamma\ndelta\n").unwrap();
+
+        doc.insert_block(BlockSelection::new(1, 3, 0, 0), "- ", ShortLines::Pad).unwrap();
+        assert_eq!(doc.text(), "alpha\n- beta\n- gamma\n- delta\n");
+        assert_eq!(doc.version(), 2);
+
+        // Short lines are padded or skipped
+        doc.insert_block(BlockSelection::new(0, 1, 7, 7), "|", ShortLines::Pad).unwrap();
+        assert_eq!(doc.text(), "alpha  |\n- beta |\n- gamma\n- delta\n");
+        doc.insert_block(BlockSelection::new(2, 3, 8, 8), "|", ShortLines::Clip).unwrap();
+        assert_eq!(doc.text(), "alpha  |\n- beta |\n- gamma\n- delta\n");
+    }
+
+    #[test]
+    fn test_delete_block() {
+        let mut doc = Document::new("table.txt");
+        doc.insert(0, "id  name\r\n1   ann\r\n22\r\n").unwrap();
+        doc.normalize_line_endings(LineEnding::Windows).unwrap();
+        let version = doc.version();
+
+        // The short line only loses the columns it has
+        doc.delete_block(BlockSelection::new(0, 2, 1, 4)).unwrap();
+        assert_eq!(doc.text(), "iname\r\n1ann\r\n2\r\n");
+        assert_eq!(doc.version(), version + 1);
+
+        assert!(doc.delete_block(BlockSelection::new(9, 9, 0, 1)).is_err());
+    }
+
+    #[test]
+    fn test_edit_events() {
+        let mut doc = Document::new("events.txt");
+        let mut events = doc.subscribe();
+
+        doc.insert(0, "hello world").unwrap();
+        doc.insert(5, ",").unwrap();
+        doc.delete(6, 12).unwrap();
+        doc.insert(6, " héllo").unwrap();
+        doc.delete(8, 10).unwrap();
+        assert_eq!(doc.text(), "hello, hllo");
+
+        let received: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .map(|event| match event {
+                Event::Buffer(BufferEvent::Inserted { position, text, .. }) => format!("+{}:{}", position, text),
+                Event::Buffer(BufferEvent::Deleted { start, end, text, .. }) => format!("-{}..{}:{}", start, end, text),
+                other => panic!("unexpected event: {:?}", other),
+            })
+            .collect();
+
+        assert_eq!(received, vec![
+            "+0:hello world",
+            "+5:,",
+            "-6..12: world",
+            "+6: héllo",
+            "-8..10:é",
+        ]);
+    }
+
+    #[test]
+    fn test_set_language_event() {
+        let mut doc = Document::new("notes.txt");
+        let mut events = doc.subscribe();
+
+        doc.set_language(Some("rust".to_string()));
+        assert_eq!(doc.language(), Some("rust"));
+        // Setting the same language again is not a change
+        doc.set_language(Some("rust".to_string()));
+
+        match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::LanguageChanged { name, language, .. }) => {
+                assert_eq!(name, "notes.txt");
+                assert_eq!(language.as_deref(), Some("rust"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    /// Returns the `SyntaxEdit` of each buffer event received so far
+    fn syntax_edits(events: &mut broadcast::Receiver<Event>) -> Vec<SyntaxEdit> {
+        std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                Event::Buffer(BufferEvent::Inserted { edit, .. } | BufferEvent::Deleted { edit, .. }) => Some(edit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_syntax_edit_multiline_insert() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\n}\n").unwrap();
+        let mut events = doc.subscribe();
+
+        doc.insert(12, "    let a = 1;\n    let é = 2;\n").unwrap();
+        assert_eq!(syntax_edits(&mut events), vec![SyntaxEdit {
+            start_byte: 12,
+            old_end_byte: 12,
+            new_end_byte: 43,
+            start_position: SyntaxPoint::new(1, 0),
+            old_end_position: SyntaxPoint::new(1, 0),
+            new_end_position: SyntaxPoint::new(3, 0),
+        }]);
+
+        doc.insert(38, "x").unwrap();
+        let edit = syntax_edits(&mut events)[0];
+        assert_eq!(edit.start_position, SyntaxPoint::new(2, 11));
+        assert_eq!(edit.new_end_position, SyntaxPoint::new(2, 12));
+    }
+
+    #[test]
+    fn test_syntax_edit_delete_across_lines() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "let a = 1;\nlet bé = 2;\nlet c = 3;\n").unwrap();
+        let mut events = doc.subscribe();
+
+        // From after "let a" to after "let b" on the next line
+        doc.delete(5, 16).unwrap();
+        assert_eq!(doc.text(), "let aé = 2;\nlet c = 3;\n");
+        assert_eq!(syntax_edits(&mut events), vec![SyntaxEdit {
+            start_byte: 5,
+            old_end_byte: 16,
+            new_end_byte: 5,
+            start_position: SyntaxPoint::new(0, 5),
+            old_end_position: SyntaxPoint::new(1, 5),
+            new_end_position: SyntaxPoint::new(0, 5),
+        }]);
+    }
+
+    #[test]
+    fn test_document_in_memory_fs() {
+        let fs = Arc::new(crate::MemoryFs::new().with_file("/src/main.rs", "fn main() {}\r\n"));
+
+        let mut doc = Document::from_file_in(fs.clone(), "/src/main.rs").unwrap();
+        assert_eq!(doc.name(), "main.rs");
+        assert_eq!(doc.language(), Some("rs"));
+        assert_eq!(*doc.line_ending(), LineEnding::Windows);
+
+        doc.insert(0, "// entry\r\n").unwrap();
+        doc.save().unwrap();
+        assert_eq!(fs.get("/src/main.rs").as_deref(), Some("// entry\r\nfn main() {}\r\n"));
+    }
+
+    /// File system whose reads return content that differs from what was written
+    struct CorruptingFs(crate::MemoryFs);
+
+    impl VirtualFs for CorruptingFs {
+        fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            VirtualFs::read_to_string(&self.0, path).map(|text| text.replacen('a', "b", 1))
+        }
+
+        fn write(&self, path: &Path, contents: &str) -> std::io::Result<()> {
+            VirtualFs::write(&self.0, path, contents)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            VirtualFs::exists(&self.0, path)
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata> {
+            VirtualFs::metadata(&self.0, path)
+        }
+    }
+
+    #[test]
+    fn test_verify_on_save() {
+        let fs = Arc::new(CorruptingFs(crate::MemoryFs::new().with_file("/notes.txt", "")));
+        let mut doc = Document::from_file_in(fs, "/notes.txt").unwrap();
+        doc.insert(0, "data").unwrap();
+
+        // Corruption goes unnoticed unless verification is enabled
+        doc.save().unwrap();
+        doc.insert(4, "!").unwrap();
+        doc.set_verify_on_save(true);
+        let err = doc.save().unwrap_err();
+        assert!(err.to_string().contains("save verification failed"));
+        assert!(doc.is_dirty());
+
+        let fs = Arc::new(crate::MemoryFs::new().with_file("/notes.txt", ""));
+        let mut doc = Document::from_file_in(fs, "/notes.txt").unwrap();
+        doc.set_verify_on_save(true);
+        doc.insert(0, "data").unwrap();
+        doc.save().unwrap();
+        assert!(!doc.is_dirty());
+    }
+
+    /// File system logging the writes made to it
+    struct RecordingFs(crate::MemoryFs, parking_lot::Mutex<Vec<String>>);
+
+    impl VirtualFs for RecordingFs {
+        fn read_to_string(&self, path: &Path) -> std::io::Result<String> {
+            VirtualFs::read_to_string(&self.0, path)
+        }
+
+        fn write(&self, path: &Path, contents: &str) -> std::io::Result<()> {
+            self.1.lock().push("write".to_string());
+            VirtualFs::write(&self.0, path, contents)
+        }
+
+        fn write_at(&self, path: &Path, offset: u64, contents: &str) -> std::io::Result<()> {
+            self.1.lock().push(format!("write_at {}", offset));
+            VirtualFs::write_at(&self.0, path, offset, contents)
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            VirtualFs::exists(&self.0, path)
+        }
+
+        fn metadata(&self, path: &Path) -> std::io::Result<FileMetadata> {
+            VirtualFs::metadata(&self.0, path)
+        }
+    }
+
+    #[test]
+    fn test_incremental_save() {
+        let fs = Arc::new(RecordingFs(crate::MemoryFs::new().with_file("/log.txt", "header\n"), Default::default()));
+        let mut doc = Document::from_file_in(fs.clone(), "/log.txt").unwrap();
+        doc.set_incremental_save(true);
+        let writes = || std::mem::take(&mut *fs.1.lock());
+
+        // Appending writes only the new bytes
+        doc.insert(7, "line 2\n").unwrap();
+        doc.save().unwrap();
+        assert_eq!(writes(), vec!["write_at 7"]);
+        assert_eq!(fs.0.get("/log.txt").as_deref(), Some("header\nline 2\n"));
+
+        // So does an edit keeping the length
+        doc.replace(0, 6, "HEADER").unwrap();
+        doc.save().unwrap();
+        assert_eq!(writes(), vec!["write_at 0"]);
+        assert_eq!(fs.0.get("/log.txt").as_deref(), Some("HEADER\nline 2\n"));
+        assert!(!doc.is_dirty());
+
+        // Changing the length before the end rewrites the file
+        doc.insert(0, "# ").unwrap();
+        doc.save().unwrap();
+        assert_eq!(writes(), vec!["write"]);
+        assert_eq!(fs.0.get("/log.txt").as_deref(), Some("# HEADER\nline 2\n"));
+
+        // As does appending to a file changed by someone else
+        VirtualFs::write(&fs.0, Path::new("/log.txt"), "replaced\n").unwrap();
+        doc.insert(doc.text().len(), "end\n").unwrap();
+        doc.save().unwrap();
+        assert_eq!(writes(), vec!["write"]);
+        assert_eq!(fs.0.get("/log.txt").as_deref(), Some("# HEADER\nline 2\nend\n"));
+    }
+
+    #[test]
+    fn test_markers_follow_edits() {
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "one\ntwo\nthree").unwrap();
+        doc.markers_mut().set("two", 4);
+        doc.markers_mut().set("three", 8);
+
+        doc.insert(0, "zero\n").unwrap();
+        let index = doc.line_index();
+        let lines = doc.markers().by_line(&index);
+        assert_eq!(lines[&2][0].name, "two");
+        assert_eq!(lines[&3][0].name, "three");
+
+        // Deleting the line holding "two" moves it to the start of the deletion
+        doc.delete(9, 13).unwrap();
+        assert_eq!(doc.markers().get("two"), Some(9));
+        assert_eq!(doc.markers().get("three"), Some(9));
+        let index = doc.line_index();
+        assert_eq!(doc.markers().markers_on_line(&index, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_bookmarks() {
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "a\nb\nc\nd").unwrap();
+
+        assert!(doc.toggle_bookmark(1, None).unwrap());
+        assert!(doc.toggle_bookmark(3, Some("todo".to_string())).unwrap());
+        assert!(doc.toggle_bookmark(9, None).is_err());
+        assert_eq!(doc.bookmarks(), vec![Bookmark::new(1), Bookmark::new(3).with_label("todo")]);
+
+        assert_eq!(doc.next_bookmark(0), Some(2));
+        assert_eq!(doc.next_bookmark(2), Some(6));
+        assert_eq!(doc.next_bookmark(6), Some(2));
+        assert_eq!(doc.prev_bookmark(2), Some(6));
+
+        assert!(!doc.toggle_bookmark(1, None).unwrap());
+        assert_eq!(doc.bookmarks().len(), 1);
+
+        // Bookmarks past the end are dropped when restoring
+        assert_eq!(doc.restore_bookmarks(&[Bookmark::new(0), Bookmark::new(10)]), 1);
+        assert_eq!(doc.bookmarks(), vec![Bookmark::new(0)]);
+    }
+
+    #[test]
+    fn test_cycle_diagnostics() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "let a = 1;\nlet b = 2;\nlet c = 3;").unwrap();
+        let diagnostic = |severity| MarkerType::Diagnostic { severity, source: "lint".to_string() };
+        doc.markers_mut().set_with_type("hint", 4, diagnostic(DiagnosticSeverity::Hint));
+        doc.markers_mut().set_with_type("error", 15, diagnostic(DiagnosticSeverity::Error));
+        doc.markers_mut().set_with_type("warning", 26, diagnostic(DiagnosticSeverity::Warning));
+        doc.markers_mut().set("bookmark", 20);
+
+        let next = |offset, min| doc.next_diagnostic(offset, min).map(|marker| marker.name.as_str());
+        let prev = |offset, min| doc.prev_diagnostic(offset, min).map(|marker| marker.name.as_str());
+
+        // All severities, in position order and wrapping around
+        assert_eq!(next(0, None), Some("hint"));
+        assert_eq!(next(4, None), Some("error"));
+        assert_eq!(next(15, None), Some("warning"));
+        assert_eq!(next(26, None), Some("hint"));
+        assert_eq!(prev(26, None), Some("error"));
+        assert_eq!(prev(4, None), Some("warning"));
+
+        // Only errors and warnings
+        let min = Some(DiagnosticSeverity::Warning);
+        assert_eq!(next(0, min), Some("error"));
+        assert_eq!(next(15, min), Some("warning"));
+        assert_eq!(next(26, min), Some("error"));
+        assert_eq!(prev(15, min), Some("warning"));
+
+        // Only errors
+        let min = Some(DiagnosticSeverity::Error);
+        assert_eq!(next(15, min), Some("error"));
+        assert_eq!(prev(0, min), Some("error"));
+
+        doc.markers_mut().remove("error");
+        assert!(doc.next_diagnostic(0, min).is_none());
+        assert!(doc.prev_diagnostic(0, min).is_none());
+    }
+
+    #[test]
+    fn test_apply_edits() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "let a = 1;\nlet b = 2;").unwrap();
+
+        let edits = vec![(15..16, "y".to_string()), (4..5, "x".to_string())];
+        doc.apply_edits(&edits).unwrap();
+        assert_eq!(doc.text(), "let x = 1;\nlet y = 2;");
+
+        // All edits are undone together
+        doc.undo().unwrap();
+        assert_eq!(doc.text(), "let a = 1;\nlet b = 2;");
+
+        assert!(doc.apply_edits(&[(0..5, String::new()), (4..6, String::new())]).is_err());
+    }
+
+    #[test]
+    fn test_replace_is_one_undo() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "let value = 1;").unwrap();
+
+        let summary = doc.replace(4, 9, "total").unwrap();
+        assert_eq!(summary.byte_range, 4..9);
+        assert_eq!(doc.text(), "let total = 1;");
+
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), "let value = 1;");
+        assert!(doc.redo().unwrap());
+        assert_eq!(doc.text(), "let total = 1;");
+        assert!(doc.undo().unwrap());
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), "");
+        assert!(!doc.can_undo());
+    }
+
+    #[test]
+    fn test_set_text_keeps_markers_outside_change() {
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "a\nb\nc\nd").unwrap();
+        doc.toggle_bookmark(0, None).unwrap();
+        doc.toggle_bookmark(3, None).unwrap();
+
+        // Only the changed middle line is replaced
+        assert!(doc.set_text("a\nbee\nc\nd").unwrap());
+        assert_eq!(doc.text(), "a\nbee\nc\nd");
+        assert_eq!(doc.bookmarks(), vec![Bookmark::new(0), Bookmark::new(3)]);
+        assert!(!doc.set_text("a\nbee\nc\nd").unwrap());
+
+        // One undo step
+        assert!(doc.undo().unwrap());
+        assert_eq!(doc.text(), "a\nb\nc\nd");
+    }
+
+    #[test]
+    fn test_changed_range() {
+        assert_eq!(changed_range("abc", "abc"), None);
+        assert_eq!(changed_range("abc", "abxc"), Some((2..2, "x")));
+        assert_eq!(changed_range("abcabc", "abc"), Some((3..6, "")));
+        // Ranges stay on character boundaries
+        assert_eq!(changed_range("aé", "aè"), Some((1..3, "è")));
+        assert_eq!(changed_range("éa", "èa"), Some((0..2, "è")));
+    }
+
+    #[test]
+    fn test_insert_snippet() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "// main\n").unwrap();
+
+        let snippet = Snippet::parse("fn ${1:name}($2) {\n    $0\n}").unwrap();
+        let stops = doc.insert_snippet(8, &snippet).unwrap();
+        assert_eq!(doc.text(), "// main\nfn name() {\n    \n}");
+
+        let ranges: Vec<_> = stops.iter().map(|stop| (stop.index, stop.ranges[0].clone())).collect();
+        assert_eq!(ranges, vec![(1, 11..15), (2, 16..16), (0, 24..24)]);
+        assert_eq!(doc.slice(11..15).unwrap(), "name");
+    }
+
+    fn brace_rules() -> IndentRules {
+        IndentRules::new(
+            vec!["{".to_string(), "(".to_string(), "[".to_string()],
+            vec!["}".to_string(), ")".to_string(), "]".to_string()],
+        )
+    }
+
+    #[test]
+    fn test_paste_reindented() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\n    if x {\n\n    }\n}\n").unwrap();
+        let version = doc.version();
+
+        // Copied from a less indented context
+        let position = doc.text().find("\n\n").unwrap() + 1;
+        doc.paste_reindented(position, "let a = [\n  1,\n];\nfoo();\n", &brace_rules(), IndentSettings::new())
+            .unwrap();
+        assert_eq!(
+            doc.text(),
+            "fn main() {\n    if x {\n        let a = [\n            1,\n        ];\n        foo();\n\n    }\n}\n"
+        );
+        assert_eq!(doc.version(), version + 1);
+    }
+
+    #[test]
+    fn test_reindent_range() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\r\nif x {\r\n\ty();\r\n  }\r\n}\r\n").unwrap();
+        doc.set_line_ending(LineEnding::Windows);
+
+        let settings = IndentSettings::new().with_use_spaces(false);
+        let end = doc.text().len();
+        doc.reindent_range(13..end, &brace_rules(), settings).unwrap();
+        assert_eq!(doc.text(), "fn main() {\r\n\tif x {\r\n\t\ty();\r\n\t}\r\n}\r\n");
+    }
+
+    #[test]
+    fn test_find_next_and_prev() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "let x = 1;\nlet y = x;\nlet z = x + y;\n").unwrap();
+        let options = SearchOptions::new().with_whole_word(true);
+
+        // From the middle of the second line
+        let from = doc.text().find("y =").unwrap();
+        assert_eq!(doc.find_next(from, "x", options), Some(SearchMatch { range: 19..20, wrapped: false }));
+        assert_eq!(doc.find_prev(from, "x", options), Some(SearchMatch { range: 4..5, wrapped: false }));
+        assert_eq!(doc.find_all("x", options), vec![4..5, 19..20, 30..31]);
+    }
+
+    #[test]
+    fn test_find_wraps_around() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "alpha beta\ngamma alpha\n").unwrap();
+        let options = SearchOptions::new();
+
+        // No match after the last one, so the search starts over
+        let end = doc.text().len();
+        assert_eq!(doc.find_next(18, "alpha", options), Some(SearchMatch { range: 0..5, wrapped: true }));
+        assert_eq!(doc.find_prev(0, "alpha", options), Some(SearchMatch { range: 17..22, wrapped: true }));
+        assert_eq!(doc.find_next(end, "beta", options), Some(SearchMatch { range: 6..10, wrapped: true }));
+
+        assert_eq!(doc.find_next(0, "delta", options), None);
+        assert_eq!(doc.find_prev(end, "", options), None);
+    }
+
+    #[test]
+    fn test_char_and_grapheme_at() {
+        let mut doc = Document::new("test.txt");
+        // "é" as e plus a combining accent, a flag, and a CRLF line break
+        doc.insert(0, "ae\u{301}\u{1F1EB}\u{1F1F7}\r\nz").unwrap();
+
+        assert_eq!(doc.char_at(0), Some('a'));
+        assert_eq!(doc.char_at(1), Some('e'));
+        assert_eq!(doc.char_at(2), Some('\u{301}'));
+        assert_eq!(doc.grapheme_at(1).as_deref(), Some("e\u{301}"));
+        assert_eq!(doc.grapheme_at(4).as_deref(), Some("\u{1F1EB}\u{1F1F7}"));
+        assert_eq!(doc.grapheme_at(12).as_deref(), Some("\r\n"));
+        assert_eq!(doc.grapheme_at(14).as_deref(), Some("z"));
+
+        // Inside a character, or inside a cluster
+        assert_eq!(doc.char_at(3), None);
+        assert_eq!(doc.grapheme_at(3), None);
+        assert_eq!(doc.grapheme_at(2), None);
+        assert_eq!(doc.grapheme_at(8), None);
+        assert_eq!(doc.grapheme_at(13), None);
+
+        // At and past the end
+        assert_eq!(doc.char_at(15), None);
+        assert_eq!(doc.grapheme_at(15), None);
+        assert_eq!(doc.char_at(100), None);
+        assert_eq!(doc.grapheme_at(100), None);
+    }
+
+    #[test]
+    fn test_lines_exceeding() {
+        let mut doc = Document::new("test.txt");
+        doc.insert(0, "short\r\nten chars!\nnaïve café!\n\nthis one is too long").unwrap();
+
+        assert_eq!(doc.lines_exceeding(10), vec![2, 4]);
+        assert_eq!(doc.lines_exceeding(9), vec![1, 2, 4]);
+        // Characters count, not bytes, and line breaks do not count
+        assert_eq!(doc.lines_exceeding(12), vec![4]);
+        assert_eq!(doc.lines_exceeding(5), vec![1, 2, 4]);
+        assert_eq!(doc.lines_exceeding(4), vec![0, 1, 2, 4]);
+        assert!(doc.lines_exceeding(80).is_empty());
+    }
+
+    #[cfg(feature = "lsp")]
+    #[test]
+    fn test_lsp_range_round_trip() {
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "let s = \"héllo 😀\";\r\nfn 日本() { s.len() }\n").unwrap();
+        let text = doc.text();
+
+        let start = text.find("😀").unwrap();
+        let end = text.find("len").unwrap() + 3;
+        let range = doc.byte_range_to_lsp(start..end);
+        // Columns count UTF-16 units: one for 'é' and each of '日本', two for '😀'
+        assert_eq!(range, lsp_types::Range::new(lsp_types::Position::new(0, 15), lsp_types::Position::new(1, 15)));
+        assert_eq!(doc.lsp_range_to_byte(range), Some(start..end));
+
+        for range in [0..0, 9..12, start..start + 4, start + 4..text.len(), 0..text.len()] {
+            assert_eq!(doc.lsp_range_to_byte(doc.byte_range_to_lsp(range.clone())), Some(range));
+        }
+
+        // Past the end is clamped to it
+        let position = lsp_types::Position::new;
+        assert_eq!(doc.lsp_range_to_byte(lsp_types::Range::new(position(0, 0), position(2, 0))), Some(0..text.len()));
+        assert_eq!(doc.lsp_range_to_byte(lsp_types::Range::new(position(0, 0), position(5, 0))), Some(0..text.len()));
+
+        // Inside a surrogate pair, or reversed
+        assert_eq!(doc.lsp_range_to_byte(lsp_types::Range::new(position(0, 16), position(0, 17))), None);
+        assert_eq!(doc.lsp_range_to_byte(lsp_types::Range::new(position(1, 2), position(1, 0))), None);
+    }
 }
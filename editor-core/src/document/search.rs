@@ -0,0 +1,129 @@
+//! Plain-text search within a document
+
+use std::ops::Range;
+
+/// Options for matching a search pattern
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    /// Match letters regardless of case
+    pub case_insensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+}
+
+impl SearchOptions {
+    /// Creates options for a case-sensitive search
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets case-insensitive matching
+    pub fn with_case_insensitive(mut self, case_insensitive: bool) -> Self {
+        self.case_insensitive = case_insensitive;
+        self
+    }
+
+    /// Sets whole-word matching
+    pub fn with_whole_word(mut self, whole_word: bool) -> Self {
+        self.whole_word = whole_word;
+        self
+    }
+
+    /// Returns the end of a match of `pattern` starting at byte `start`, if any
+    fn match_at(&self, text: &str, start: usize, pattern: &str) -> Option<usize> {
+        let mut candidate = text[start..].char_indices();
+        let mut end = start;
+        for p in pattern.chars() {
+            let (offset, c) = candidate.next()?;
+            let equal = if self.case_insensitive {
+                c == p || c.to_lowercase().eq(p.to_lowercase())
+            } else {
+                c == p
+            };
+            if !equal {
+                return None;
+            }
+            end = start + offset + c.len_utf8();
+        }
+
+        if self.whole_word {
+            let before = text[..start].chars().next_back();
+            let after = text[end..].chars().next();
+            if before.is_some_and(is_word_char) || after.is_some_and(is_word_char) {
+                return None;
+            }
+        }
+
+        Some(end)
+    }
+
+    /// Returns the first match starting at or after `from` and before `to`
+    pub(crate) fn find_forward(&self, text: &str, pattern: &str, from: usize, to: usize) -> Option<Range<usize>> {
+        if pattern.is_empty() {
+            return None;
+        }
+        text.char_indices()
+            .map(|(start, _)| start)
+            .skip_while(|&start| start < from)
+            .take_while(|&start| start < to)
+            .find_map(|start| self.match_at(text, start, pattern).map(|end| start..end))
+    }
+
+    /// Returns the last match starting at or after `from` and before `to`
+    pub(crate) fn find_backward(&self, text: &str, pattern: &str, from: usize, to: usize) -> Option<Range<usize>> {
+        if pattern.is_empty() {
+            return None;
+        }
+        text.char_indices()
+            .rev()
+            .map(|(start, _)| start)
+            .skip_while(|&start| start >= to)
+            .take_while(|&start| start >= from)
+            .find_map(|start| self.match_at(text, start, pattern).map(|end| start..end))
+    }
+
+    /// Returns all non-overlapping matches in order
+    pub(crate) fn find_all(&self, text: &str, pattern: &str) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        let mut from = 0;
+        while let Some(range) = self.find_forward(text, pattern, from, text.len()) {
+            from = range.end.max(range.start + 1);
+            matches.push(range);
+        }
+        matches
+    }
+}
+
+/// A match found by `Document::find_next` or `Document::find_prev`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchMatch {
+    /// Byte range of the match
+    pub range: Range<usize>,
+    /// True if the search wrapped around the document to find it
+    pub wrapped: bool,
+}
+
+/// Returns true for characters that make up words
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options() {
+        let text = "Foo foo food Straße STRASSE";
+        let exact = SearchOptions::new();
+        assert_eq!(exact.find_all(text, "foo"), vec![4..7, 8..11]);
+
+        let words = SearchOptions::new().with_case_insensitive(true).with_whole_word(true);
+        assert_eq!(words.find_all(text, "foo"), vec![0..3, 4..7]);
+
+        // Case folding works on characters, not bytes
+        let insensitive = SearchOptions::new().with_case_insensitive(true);
+        assert_eq!(insensitive.find_all(text, "STRAßE"), vec![13..20]);
+        assert!(exact.find_all(text, "").is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+//! Selections of a document's text
+
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// A selected byte range, or a cursor if it is empty
+///
+/// The anchor stays put while the head moves with the cursor, so the head
+/// can be before the anchor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Selection {
+    /// Offset the selection was started at
+    pub anchor: usize,
+    /// Offset of the cursor
+    pub head: usize,
+}
+
+impl Selection {
+    /// Creates a selection from `anchor` to `head`
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    /// Creates an empty selection, a cursor at `offset`
+    pub fn cursor(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+
+    /// Returns the selected range, start first
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    /// Returns true if nothing is selected
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// Returns the selection with both ends at most `len`
+    pub(crate) fn clamped(self, len: usize) -> Self {
+        Self::new(self.anchor.min(len), self.head.min(len))
+    }
+
+    /// Moves the selection to follow `range` being replaced by `new_len` bytes
+    ///
+    /// Ends before the range stay, ends at or after its end shift with the
+    /// text, and ends inside it keep their offset into the replacement where
+    /// it is long enough. A cursor where text is inserted moves past it.
+    pub(crate) fn follow_edit(&mut self, range: Range<usize>, new_len: usize) {
+        for end in [&mut self.anchor, &mut self.head] {
+            if *end < range.start {
+                continue;
+            }
+            *end = if *end >= range.end {
+                *end - range.len() + new_len
+            } else {
+                range.start + (*end - range.start).min(new_len)
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_follows_edits() {
+        let mut selection = Selection::new(8, 3);
+        assert_eq!(selection.range(), 3..8);
+
+        selection.follow_edit(0..0, 2);
+        assert_eq!(selection, Selection::new(10, 5));
+        // Deleting across the head collapses it to the deletion start
+        selection.follow_edit(4..7, 0);
+        assert_eq!(selection, Selection::new(7, 4));
+        selection.follow_edit(9..9, 5);
+        assert_eq!(selection, Selection::new(7, 4));
+
+        // Replacing the selected text keeps it selected
+        selection.follow_edit(4..7, 3);
+        assert_eq!(selection, Selection::new(7, 4));
+        selection.follow_edit(4..7, 1);
+        assert_eq!(selection, Selection::new(5, 4));
+    }
+}
@@ -1,6 +1,41 @@
-use crate::{Document, Result, Error};
+use crate::diagnostics::{DiagnosticRegistry, DiagnosticRunner, TrailingWhitespace};
+use crate::event::{DocumentEvent, Event, EventDispatcher, EventHandler, HandlerId};
+use crate::view::{DocumentView, ViewId};
+use crate::{
+    ActionRegistry, Document, DocumentSource, FileAssociations, LineEnding, LocalFsSource, MarkerType, Result,
+    Error, StatusItems, StdFs,
+};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default size (in bytes) above which `open_file` refuses to eagerly load a file
+pub const DEFAULT_LARGE_FILE_THRESHOLD: u64 = 50 * 1024 * 1024;
+
+/// Reloading of open files changed by other programs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReloadPolicy {
+    /// Files are never reloaded
+    Never,
+    /// Files without unsaved edits are reloaded; for the others the user
+    /// chooses whether to reload
+    #[default]
+    IfClean,
+    /// Files are always reloaded, replacing unsaved edits
+    Always,
+}
+
+/// Open files found changed on disk by `Editor::reload_changed_files`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExternalChanges {
+    /// Files whose documents were reloaded
+    pub reloaded: Vec<PathBuf>,
+    /// Files with unsaved edits left for the user to reload or keep
+    pub conflicts: Vec<PathBuf>,
+}
 
 /// Main editor type that coordinates documents and editing operations
 pub struct Editor {
@@ -8,18 +43,116 @@ pub struct Editor {
     documents: HashMap<String, Document>,
     /// Currently active document
     active_document: Option<String>,
+    /// Files larger than this many bytes are not loaded by `open_file`
+    large_file_threshold: u64,
+    /// Broadcasts editor events to subscribers
+    events: EventDispatcher,
+    /// Actions available to keybindings, menus and the command palette
+    actions: Arc<ActionRegistry>,
+    /// Status bar items contributed by plugins
+    status_items: Arc<StatusItems>,
+    /// Backend that `open_uri` reads documents from
+    source: Arc<dyn DocumentSource>,
+    /// User mappings from file names to languages
+    file_associations: FileAssociations,
+    /// Diagnostic providers, shared with plugins and language servers
+    diagnostics: Arc<DiagnosticRegistry>,
+    /// Refreshes diagnostic markers after edits
+    diagnostic_runner: DiagnosticRunner,
+    /// Secondary views of open documents
+    views: HashMap<ViewId, DocumentView>,
+    /// Id given to the next view
+    next_view_id: u64,
 }
 
 impl Editor {
-    /// Creates a new empty editor
+    /// Creates a new empty editor that opens documents from the local disk
     pub fn new() -> Self {
+        Self::with_source(Arc::new(LocalFsSource))
+    }
+
+    /// Creates a new empty editor that opens documents from the given source
+    ///
+    /// The built-in `TrailingWhitespace` provider is registered for all
+    /// languages.
+    pub fn with_source(source: Arc<dyn DocumentSource>) -> Self {
+        let diagnostics = Arc::new(DiagnosticRegistry::new());
+        diagnostics.register(None, Arc::new(TrailingWhitespace));
+
         Self {
             documents: HashMap::new(),
             active_document: None,
+            large_file_threshold: DEFAULT_LARGE_FILE_THRESHOLD,
+            events: EventDispatcher::new(),
+            actions: Arc::new(ActionRegistry::new()),
+            status_items: Arc::new(StatusItems::new()),
+            source,
+            file_associations: FileAssociations::new(),
+            diagnostics,
+            diagnostic_runner: DiagnosticRunner::default(),
+            views: HashMap::new(),
+            next_view_id: 1,
+        }
+    }
+
+    /// Returns the size in bytes above which files are considered too large to open
+    pub fn large_file_threshold(&self) -> u64 {
+        self.large_file_threshold
+    }
+
+    /// Sets the size in bytes above which files are considered too large to open
+    pub fn set_large_file_threshold(&mut self, threshold: u64) {
+        self.large_file_threshold = threshold;
+    }
+
+    /// Returns the file associations used to detect document languages
+    pub fn file_associations(&self) -> &FileAssociations {
+        &self.file_associations
+    }
+
+    /// Sets the file associations and re-detects the language of open documents
+    pub fn set_file_associations(&mut self, associations: FileAssociations) {
+        for doc in self.documents.values_mut() {
+            doc.apply_file_associations(&associations);
         }
+        self.file_associations = associations;
     }
 
-    /// Opens a document from a file.
+    /// Adds a document and makes it active
+    fn add_document(&mut self, mut doc: Document) {
+        doc.apply_file_associations(&self.file_associations);
+        let name = doc.name().to_string();
+        self.documents.insert(name.clone(), doc);
+        self.active_document = Some(name);
+    }
+
+    /// Returns the backend that documents are opened from
+    pub fn source(&self) -> &Arc<dyn DocumentSource> {
+        &self.source
+    }
+
+    /// Opens a document from the editor's source and makes it active.
+    ///
+    /// Saving the document writes it back to the same source. Sources are
+    /// read in a single request, so the large file threshold is not applied.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use editor_core::{Editor, MemoryFs};
+    /// # use std::sync::Arc;
+    /// let source = Arc::new(MemoryFs::new().with_file("mem://notes.txt", "hello"));
+    /// let mut editor = Editor::with_source(source);
+    /// editor.open_uri("mem://notes.txt").unwrap();
+    /// assert_eq!(editor.active_document().unwrap().text(), "hello");
+    /// ```
+    pub fn open_uri(&mut self, uri: &str) -> Result<()> {
+        let doc = Document::from_source(self.source.clone(), uri)?;
+        self.add_document(doc);
+        Ok(())
+    }
+
+    /// Opens a document from a file on the local disk.
     ///
     /// This method loads a document from the specified file path and adds it to the editor.
     /// The newly opened document will be set as the active document.
@@ -32,6 +165,8 @@ impl Editor {
     /// # Returns
     ///
     /// * `Ok(())` if the file was successfully opened
+    /// * `Err(Error::LargeFile { .. })` if the file exceeds the large file threshold;
+    ///   use [`Editor::open_file_large`] to open it anyway
     /// * `Err(_)` if the file could not be opened or read
     ///
     /// # Examples
@@ -47,10 +182,69 @@ impl Editor {
     /// editor.open_file(path).unwrap();
     /// ```
     pub fn open_file(&mut self, path: impl Into<PathBuf> + AsRef<std::path::Path>) -> Result<()> {
+        let size = std::fs::metadata(path.as_ref())?.len();
+        self.check_file_size(path.as_ref(), size)?;
+        self.open_file_large(path)
+    }
+
+    /// Opens a document from a file on the local disk without blocking the async runtime.
+    ///
+    /// Behaves like [`Editor::open_file`], but the file is read with `tokio::fs`
+    /// and the document is built on the blocking thread pool.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use editor_core::Editor;
+    /// # async fn open() -> editor_core::Result<()> {
+    /// let mut editor = Editor::new();
+    /// editor.open_file_async("path/to/file.txt").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn open_file_async(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        let path = path.into();
+        let size = tokio::fs::metadata(&path).await?.len();
+        self.check_file_size(&path, size)?;
+        self.open_file_large_async(path).await
+    }
+
+    /// Opens a document from a file asynchronously without checking it against
+    /// the large file threshold.
+    pub async fn open_file_large_async(&mut self, path: impl Into<PathBuf>) -> Result<()> {
+        let path = path.into();
+        let bytes = tokio::fs::read(&path).await?;
+        let doc = tokio::task::spawn_blocking(move || Document::from_bytes_in(Arc::new(StdFs), &path, &bytes))
+            .await
+            .map_err(|e| Error::Document(format!("Failed to load document: {}", e)))??;
+        self.add_document(doc);
+        Ok(())
+    }
+
+    /// Returns `Error::LargeFile` if a file of `size` bytes exceeds the threshold
+    fn check_file_size(&self, path: &Path, size: u64) -> Result<()> {
+        if size > self.large_file_threshold {
+            log::warn!(
+                "Refusing to open {} ({} bytes exceeds threshold of {} bytes)",
+                path.display(),
+                size,
+                self.large_file_threshold
+            );
+            return Err(Error::LargeFile {
+                size,
+                threshold: self.large_file_threshold,
+            });
+        }
+        Ok(())
+    }
+
+    /// Opens a document from a file without checking it against the large file threshold.
+    ///
+    /// This is meant to be called after the user has confirmed opening a file
+    /// for which [`Editor::open_file`] returned [`Error::LargeFile`].
+    pub fn open_file_large(&mut self, path: impl Into<PathBuf> + AsRef<std::path::Path>) -> Result<()> {
         let doc = Document::from_file(path)?;
-        let name = doc.name().to_string();
-        self.documents.insert(name.clone(), doc);
-        self.active_document = Some(name);
+        self.add_document(doc);
         Ok(())
     }
 
@@ -82,9 +276,7 @@ impl Editor {
         }
         
         log::info!("Creating new document '{}'", name);
-        let doc = Document::new(name);
-        self.documents.insert(name.to_string(), doc);
-        self.active_document = Some(name.to_string());
+        self.add_document(Document::new(name));
         Ok(())
     }
 
@@ -185,6 +377,27 @@ impl Editor {
         self.documents.keys().cloned().collect()
     }
 
+    /// Returns an iterator over all open documents.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use editor_core::Editor;
+    /// # let mut editor = Editor::new();
+    /// # editor.new_document("doc1.txt").unwrap();
+    /// for doc in editor.documents() {
+    ///     println!("Open document: {}", doc.name());
+    /// }
+    /// ```
+    pub fn documents(&self) -> impl Iterator<Item = &Document> {
+        self.documents.values()
+    }
+
+    /// Returns a mutable iterator over all open documents.
+    pub fn documents_mut(&mut self) -> impl Iterator<Item = &mut Document> {
+        self.documents.values_mut()
+    }
+
     /// Checks if a document with the given name exists.
     ///
     /// # Arguments
@@ -236,13 +449,329 @@ impl Editor {
             return Err(Error::Document(format!("Cannot close document: {} not found", name)));
         }
         
-        self.documents.remove(name);
+        let path = self.documents.remove(name).and_then(|doc| doc.path().map(Path::to_path_buf));
+        self.views.retain(|_, view| view.document() != name);
         
         // If the closed document was the active one, set active to None
         if self.active_document.as_deref() == Some(name) {
             self.active_document = None;
         }
-        
+
+        self.events.dispatch(Event::Document(DocumentEvent::Closed {
+            name: name.to_string(),
+            path,
+        }));
+        Ok(())
+    }
+
+    /// Saves a document to its file
+    ///
+    /// Emits `DocumentEvent::Saved` on success.
+    pub fn save_document(&mut self, name: &str) -> Result<()> {
+        let doc = self.documents.get_mut(name)
+            .ok_or_else(|| Error::Document(format!("Cannot save document: {} not found", name)))?;
+        doc.save()?;
+        if let Some(path) = doc.path() {
+            self.events.dispatch(Event::Document(DocumentEvent::Saved { path: path.to_path_buf() }));
+        }
+        Ok(())
+    }
+
+    /// Opens a read-only view of a document with its own cursor and scroll
+    /// position
+    ///
+    /// The view shows whichever document is open under the name, so edits to
+    /// it are visible in the view, and its cursor moves with the text around
+    /// it. Views are closed along with their document.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no document has the given name.
+    pub fn open_secondary_view(&mut self, name: &str) -> Result<ViewId> {
+        let doc = self.documents.get_mut(name)
+            .ok_or_else(|| Error::Document(format!("Cannot open view: {} not found", name)))?;
+        let view = DocumentView::new(self.next_view_id, name);
+        self.next_view_id += 1;
+        doc.markers_mut().set_with_type(&view.cursor_marker(), 0, MarkerType::Cursor);
+
+        let id = view.id();
+        self.views.insert(id, view);
+        Ok(id)
+    }
+
+    /// Returns a secondary view
+    pub fn view(&self, id: ViewId) -> Option<&DocumentView> {
+        self.views.get(&id)
+    }
+
+    /// Returns a secondary view and its document
+    fn view_document(&self, id: ViewId) -> Option<(&DocumentView, &Document)> {
+        let view = self.views.get(&id)?;
+        Some((view, self.documents.get(view.document())?))
+    }
+
+    /// Returns the current text of a view's document
+    pub fn view_text(&self, id: ViewId) -> Option<String> {
+        self.view_document(id).map(|(_, doc)| doc.text())
+    }
+
+    /// Returns the number of lines in a view's document
+    pub fn view_line_count(&self, id: ViewId) -> Option<usize> {
+        self.view_document(id).map(|(_, doc)| doc.line_count())
+    }
+
+    /// Returns a view's cursor byte offset, clamped to the current text
+    pub fn view_cursor(&self, id: ViewId) -> Option<usize> {
+        let (view, doc) = self.view_document(id)?;
+        let cursor = doc.markers().get(&view.cursor_marker()).unwrap_or(0);
+        Some(cursor.min(doc.text().len()))
+    }
+
+    /// Moves a view's cursor, clamping it to the current text
+    ///
+    /// # Errors
+    ///
+    /// Fails if the view is not open.
+    pub fn set_view_cursor(&mut self, id: ViewId, offset: usize) -> Result<()> {
+        let view = self.views.get(&id)
+            .ok_or_else(|| Error::Document(format!("Cannot move cursor: {} not open", id)))?;
+        let doc = self.documents.get_mut(view.document())
+            .ok_or_else(|| Error::Document(format!("Cannot move cursor: {} not found", view.document())))?;
+        let offset = offset.min(doc.text().len());
+        doc.markers_mut().set_with_type(&view.cursor_marker(), offset, MarkerType::Cursor);
+        Ok(())
+    }
+
+    /// Returns a view's first visible line, clamped to the current text
+    pub fn view_scroll_line(&self, id: ViewId) -> Option<usize> {
+        let (view, doc) = self.view_document(id)?;
+        Some(view.scroll_line().min(doc.line_count().saturating_sub(1)))
+    }
+
+    /// Scrolls a view so that `line` is its first visible line
+    ///
+    /// # Errors
+    ///
+    /// Fails if the view is not open.
+    pub fn set_view_scroll_line(&mut self, id: ViewId, line: usize) -> Result<()> {
+        let line_count = self.view_line_count(id)
+            .ok_or_else(|| Error::Document(format!("Cannot scroll: {} not open", id)))?;
+        if let Some(view) = self.views.get_mut(&id) {
+            view.set_scroll_line(line.min(line_count.saturating_sub(1)));
+        }
+        Ok(())
+    }
+
+    /// Closes a secondary view, returning false if it was not open
+    pub fn close_view(&mut self, id: ViewId) -> bool {
+        let Some(view) = self.views.remove(&id) else {
+            return false;
+        };
+        if let Some(doc) = self.documents.get_mut(view.document()) {
+            doc.markers_mut().remove(&view.cursor_marker());
+        }
+        true
+    }
+
+    /// Writes a byte range of a document to a new file ("Save Selection As").
+    ///
+    /// The document itself is not modified. The written text uses the
+    /// document's line ending style. If `open` is true, the new file is opened
+    /// and becomes the active document.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no document has the given name, the range is not valid for
+    /// the document or the file cannot be written.
+    pub fn save_range_as(&mut self, name: &str, range: Range<usize>, path: impl AsRef<Path>, open: bool) -> Result<()> {
+        let doc = self.documents.get(name)
+            .ok_or_else(|| Error::Document(format!("Cannot save range: {} not found", name)))?;
+        let text = doc.line_ending().normalize(&doc.slice(range)?);
+
+        let path = path.as_ref();
+        std::fs::write(path, text)?;
+        log::info!("Saved part of '{}' to {}", name, path.display());
+
+        if open {
+            self.open_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Copies a document into a new untitled scratch document and makes it
+    /// active
+    ///
+    /// The copy gets its own buffer and history, so edits to either leave the
+    /// other alone. It is named after the source, e.g. `main.rs (scratch 1)`,
+    /// and keeps its language and line endings.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no document has the given name.
+    pub fn duplicate_to_scratch(&mut self, name: &str) -> Result<String> {
+        let source = self.documents.get(name)
+            .ok_or_else(|| Error::Document(format!("Cannot duplicate document: {} not found", name)))?;
+        let text = source.text();
+        let language = source.language().map(str::to_string);
+        let line_ending = *source.line_ending();
+
+        let scratch_name = (1..)
+            .map(|n| format!("{} (scratch {})", name, n))
+            .find(|candidate| !self.documents.contains_key(candidate))
+            .expect("unbounded range always yields a free name");
+        let mut doc = Document::from_text(scratch_name.clone(), &text);
+        doc.set_line_ending(line_ending);
+        doc.set_scratch(true);
+
+        log::info!("Duplicated '{}' into scratch document '{}'", name, scratch_name);
+        self.add_document(doc);
+        if let Some(doc) = self.documents.get_mut(&scratch_name) {
+            doc.set_language(language);
+        }
+        Ok(scratch_name)
+    }
+
+    /// Returns the open documents that belong in the session and autosave,
+    /// leaving out scratch documents
+    pub fn persistent_documents(&self) -> impl Iterator<Item = &Document> {
+        self.documents.values().filter(|doc| !doc.is_scratch())
+    }
+
+    /// Returns the registry of editor actions
+    ///
+    /// The same registry can be handed to the plugin manager so plugin
+    /// commands show up in the command palette.
+    pub fn actions(&self) -> &Arc<ActionRegistry> {
+        &self.actions
+    }
+
+    /// Returns the status bar items contributed by plugins
+    ///
+    /// The same set can be handed to the plugin manager so plugins update it.
+    pub fn status_items(&self) -> &Arc<StatusItems> {
+        &self.status_items
+    }
+
+    /// Returns the diagnostic providers
+    ///
+    /// The same registry can be handed to plugins and language servers so
+    /// their providers contribute markers.
+    pub fn diagnostic_providers(&self) -> &Arc<DiagnosticRegistry> {
+        &self.diagnostics
+    }
+
+    /// Sets the quiet period after an edit before diagnostics are refreshed
+    pub fn set_diagnostic_delay(&mut self, delay: Duration) {
+        self.diagnostic_runner.set_delay(delay);
+    }
+
+    /// Refreshes the diagnostic markers of documents that are due
+    ///
+    /// Call this regularly, e.g. once per frame. Edited documents are refreshed
+    /// once edits have been quiet for the diagnostic delay, and all documents
+    /// right after providers change. Returns true if any markers were refreshed.
+    pub fn poll_diagnostics(&mut self, now: Instant) -> bool {
+        self.diagnostic_runner.poll(&self.diagnostics, self.documents.values_mut(), now)
+    }
+
+    /// Applies a reload policy to the open files changed on disk
+    ///
+    /// Files without unsaved edits are reloaded unless the policy is `Never`.
+    /// Files with unsaved edits are reloaded with `Always`, and reported as
+    /// conflicts with `IfClean`. Files not reloaded are not reported again
+    /// until they change again.
+    pub fn reload_changed_files(&mut self, policy: ReloadPolicy) -> ExternalChanges {
+        let mut changes = ExternalChanges::default();
+        for doc in self.documents.values_mut() {
+            let Some(path) = doc.path().map(Path::to_path_buf) else {
+                continue;
+            };
+            if !doc.changed_on_disk().unwrap_or(false) {
+                continue;
+            }
+
+            let reload = match policy {
+                ReloadPolicy::Never => false,
+                ReloadPolicy::IfClean => !doc.is_dirty(),
+                ReloadPolicy::Always => true,
+            };
+            let result = if reload { doc.reload() } else { doc.acknowledge_disk_change() };
+            match result {
+                Err(e) => log::warn!("Failed to handle the change of {}: {}", path.display(), e),
+                Ok(()) if reload => {
+                    log::info!("Reloaded {}", path.display());
+                    changes.reloaded.push(path);
+                }
+                Ok(()) if policy == ReloadPolicy::IfClean => changes.conflicts.push(path),
+                Ok(()) => {}
+            }
+        }
+        changes
+    }
+
+    /// Reloads an open file from disk, e.g. once the user chose to resolve a
+    /// conflict that way
+    pub fn reload_file(&mut self, path: &Path) -> Result<()> {
+        let doc = self.documents.values_mut()
+            .find(|doc| doc.path() == Some(path))
+            .ok_or_else(|| Error::Document(format!("{} is not open", path.display())))?;
+        doc.reload()
+    }
+
+    /// Subscribes to editor events
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<Event> {
+        self.events.subscribe()
+    }
+
+    /// Runs a handler for every editor event, such as the plugin manager's
+    /// document event forwarder
+    pub fn add_event_handler(&self, handler: Arc<dyn EventHandler>) -> HandlerId {
+        self.events.add_handler(handler)
+    }
+
+    /// Converts the active document's line endings to the given style
+    ///
+    /// Emits `DocumentEvent::LineEndingChanged` on success.
+    pub fn change_line_ending(&mut self, line_ending: LineEnding) -> Result<()> {
+        let doc = self.active_document_mut()
+            .ok_or_else(|| Error::Document("No active document".to_string()))?;
+        doc.normalize_line_endings(line_ending)?;
+        let name = doc.name().to_string();
+        let path = doc.path().map(Path::to_path_buf);
+
+        self.events.dispatch(Event::Document(DocumentEvent::LineEndingChanged {
+            name,
+            path,
+            line_ending,
+        }));
+        Ok(())
+    }
+
+    /// Overrides the active document's language, or restores detection with `None`
+    ///
+    /// Emits `DocumentEvent::LanguageChanged` if the language changes.
+    pub fn change_language(&mut self, language: Option<String>) -> Result<()> {
+        let doc = self.active_document()
+            .ok_or_else(|| Error::Document("No active document".to_string()))?;
+        let language = language.or_else(|| {
+            let path = doc.path().map(Path::to_path_buf).unwrap_or_else(|| PathBuf::from(doc.name()));
+            self.file_associations.detect_language(&path)
+        });
+
+        let doc = self.active_document_mut()
+            .ok_or_else(|| Error::Document("No active document".to_string()))?;
+        if doc.language() == language.as_deref() {
+            return Ok(());
+        }
+        doc.set_language(language.clone());
+        let name = doc.name().to_string();
+        let path = doc.path().map(Path::to_path_buf);
+
+        self.events.dispatch(Event::Document(DocumentEvent::LanguageChanged {
+            name,
+            path,
+            language,
+        }));
         Ok(())
     }
 }
@@ -298,5 +827,440 @@ mod tests {
         assert!(!editor.has_document("doc1.txt"));
         assert!(editor.active_document().is_none());
     }
-}
 
+    #[test]
+    /// Test opening and saving a document through a non-local source
+    fn test_open_uri_remote_source() {
+        let remote = Arc::new(crate::MemoryFs::new().with_file("sftp://host/home/notes.md", "# Notes\n"));
+        let mut editor = Editor::with_source(remote.clone());
+
+        editor.open_uri("sftp://host/home/notes.md").unwrap();
+        let doc = editor.active_document_mut().unwrap();
+        assert_eq!(doc.name(), "notes.md");
+        assert_eq!(doc.language(), Some("md"));
+        assert_eq!(doc.size_on_disk(), Some(8));
+
+        doc.insert(8, "- item\n").unwrap();
+        doc.save().unwrap();
+        assert_eq!(remote.get("sftp://host/home/notes.md").as_deref(), Some("# Notes\n- item\n"));
+        assert_eq!(editor.active_document().unwrap().size_on_disk(), Some(15));
+
+        assert!(editor.open_uri("sftp://host/missing.md").is_err());
+    }
+
+    #[test]
+    /// Test each reload policy against clean and dirty documents
+    fn test_reload_changed_files() {
+        let dir = TempDir::new().unwrap();
+        let clean = dir.path().join("clean.txt");
+        let dirty = dir.path().join("dirty.txt");
+
+        let changed = |policy| {
+            std::fs::write(&clean, "one\n").unwrap();
+            std::fs::write(&dirty, "one\n").unwrap();
+            let mut editor = Editor::new();
+            editor.open_file(&clean).unwrap();
+            editor.open_file(&dirty).unwrap();
+            editor.active_document_mut().unwrap().insert(0, "edit\n").unwrap();
+            std::fs::write(&clean, "one\ntwo\n").unwrap();
+            std::fs::write(&dirty, "one\ntwo\n").unwrap();
+
+            let changes = editor.reload_changed_files(policy);
+            // Changes are handled once
+            assert_eq!(editor.reload_changed_files(policy), ExternalChanges::default());
+            let texts: Vec<String> = ["clean.txt", "dirty.txt"].iter()
+                .map(|name| editor.documents[*name].text())
+                .collect();
+            (changes, texts)
+        };
+
+        let (changes, texts) = changed(ReloadPolicy::Never);
+        assert_eq!(changes, ExternalChanges::default());
+        assert_eq!(texts, vec!["one\n", "edit\none\n"]);
+
+        let (changes, texts) = changed(ReloadPolicy::IfClean);
+        assert_eq!(changes, ExternalChanges { reloaded: vec![clean.clone()], conflicts: vec![dirty.clone()] });
+        assert_eq!(texts, vec!["one\ntwo\n", "edit\none\n"]);
+
+        let (mut changes, texts) = changed(ReloadPolicy::Always);
+        changes.reloaded.sort();
+        assert_eq!(changes, ExternalChanges { reloaded: vec![clean.clone(), dirty.clone()], conflicts: vec![] });
+        assert_eq!(texts, vec!["one\ntwo\n", "one\ntwo\n"]);
+
+        let mut editor = Editor::new();
+        editor.open_file(&dirty).unwrap();
+        std::fs::write(&dirty, "three\n").unwrap();
+        editor.reload_file(&dirty).unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "three\n");
+        assert!(editor.reload_file(&dir.path().join("missing.txt")).is_err());
+    }
+
+    #[test]
+    /// Test that file associations override extension-based language detection
+    fn test_file_associations() {
+        let mut editor = Editor::new();
+        editor.new_document("Dockerfile").unwrap();
+        editor.new_document("nginx.conf").unwrap();
+        assert_eq!(editor.active_document().unwrap().language(), Some("conf"));
+
+        editor.set_file_associations(
+            FileAssociations::new()
+                .with("Dockerfile", "dockerfile")
+                .with("*.conf", "ini"),
+        );
+        assert_eq!(editor.active_document().unwrap().language(), Some("ini"));
+
+        editor.set_active_document("Dockerfile").unwrap();
+        assert_eq!(editor.active_document().unwrap().language(), Some("dockerfile"));
+
+        editor.new_document("app.conf").unwrap();
+        assert_eq!(editor.active_document().unwrap().language(), Some("ini"));
+    }
+
+    #[test]
+    /// Test extracting part of a document to a new file
+    fn test_save_range_as() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        let doc = editor.active_document_mut().unwrap();
+        doc.insert(0, "one\ntwo\nthree\nfour\n").unwrap();
+        doc.set_line_ending(LineEnding::Windows);
+        let version = doc.version();
+
+        let path = temp_dir.path().join("middle.txt");
+        editor.save_range_as("notes.txt", 4..14, &path, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "two\r\nthree\r\n");
+
+        // The source document is untouched and still active
+        let doc = editor.active_document().unwrap();
+        assert_eq!(doc.name(), "notes.txt");
+        assert_eq!(doc.text(), "one\ntwo\nthree\nfour\n");
+        assert_eq!(doc.version(), version);
+        assert!(doc.is_dirty());
+
+        // The new file can be opened straight away
+        let path = temp_dir.path().join("last.txt");
+        editor.save_range_as("notes.txt", 14..19, &path, true).unwrap();
+        assert_eq!(editor.active_document().unwrap().name(), "last.txt");
+        assert_eq!(editor.active_document().unwrap().text(), "four\r\n");
+
+        assert!(editor.save_range_as("missing.txt", 0..1, temp_dir.path().join("x"), false).is_err());
+        assert!(editor.save_range_as("notes.txt", 10..100, temp_dir.path().join("x"), false).is_err());
+    }
+
+    #[test]
+    /// Test overriding and restoring the active document's language
+    fn test_change_language() {
+        let mut editor = Editor::new();
+        let mut events = editor.subscribe();
+
+        assert!(editor.change_language(Some("rust".to_string())).is_err());
+
+        editor.new_document("notes.txt").unwrap();
+        editor.change_language(Some("rust".to_string())).unwrap();
+        assert_eq!(editor.active_document().unwrap().language(), Some("rust"));
+        match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::LanguageChanged { name, language, .. }) => {
+                assert_eq!(name, "notes.txt");
+                assert_eq!(language.as_deref(), Some("rust"));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        // `None` goes back to the detected language
+        editor.change_language(None).unwrap();
+        assert_eq!(editor.active_document().unwrap().language(), Some("txt"));
+        assert!(events.try_recv().is_ok());
+        editor.change_language(None).unwrap();
+        assert!(events.try_recv().is_err());
+    }
+
+    #[test]
+    /// Test converting the active document's line endings
+    fn test_change_line_ending() {
+        let mut editor = Editor::new();
+        let mut events = editor.subscribe();
+
+        assert!(editor.change_line_ending(LineEnding::Unix).is_err());
+
+        editor.new_document("crlf.txt").unwrap();
+        let doc = editor.active_document_mut().unwrap();
+        doc.insert(0, "a\r\nb\r\n").unwrap();
+        doc.set_line_ending(LineEnding::Windows);
+
+        editor.change_line_ending(LineEnding::Unix).unwrap();
+        let doc = editor.active_document().unwrap();
+        assert_eq!(doc.text(), "a\nb\n");
+        assert_eq!(*doc.line_ending(), LineEnding::Unix);
+
+        match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::LineEndingChanged { name, line_ending, .. }) => {
+                assert_eq!(name, "crlf.txt");
+                assert_eq!(line_ending, LineEnding::Unix);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    /// Test that saving and closing a document are reported with its path
+    fn test_save_and_close_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = create_temp_file(&temp_dir, "notes.txt", "a");
+        let mut editor = Editor::new();
+        editor.open_file(&path).unwrap();
+        let mut events = editor.subscribe();
+
+        assert!(editor.save_document("missing.txt").is_err());
+        editor.active_document_mut().unwrap().insert(1, "b").unwrap();
+        editor.save_document("notes.txt").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "ab");
+        match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::Saved { path: saved }) => assert_eq!(saved, path),
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        editor.close_document("notes.txt").unwrap();
+        match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::Closed { name, path: closed }) => {
+                assert_eq!(name, "notes.txt");
+                assert_eq!(closed, Some(path));
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    /// Test that files over the large file threshold are not loaded
+    fn test_open_large_file() {
+        let dir = TempDir::new().unwrap();
+        let small = create_temp_file(&dir, "small.txt", "short");
+        let large = create_temp_file(&dir, "large.txt", "this content is over the threshold");
+
+        let mut editor = Editor::new();
+        editor.set_large_file_threshold(16);
+
+        match editor.open_file(&large) {
+            Err(Error::LargeFile { size, threshold }) => {
+                assert_eq!(size, 34);
+                assert_eq!(threshold, 16);
+            }
+            other => panic!("Expected LargeFile error, got {:?}", other.map(|_| ())),
+        }
+        assert!(!editor.has_document("large.txt"));
+
+        editor.open_file(&small).unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "short");
+
+        editor.open_file_large(&large).unwrap();
+        assert!(editor.has_document("large.txt"));
+    }
+
+    #[tokio::test]
+    /// Test opening files without blocking the runtime
+    async fn test_open_file_async() {
+        let dir = TempDir::new().unwrap();
+        let path = create_temp_file(&dir, "main.rs", "fn main() {\r\n}\r\n");
+        let large = create_temp_file(&dir, "large.txt", "this content is over the threshold");
+
+        let mut editor = Editor::new();
+        editor.open_file_async(&path).await.unwrap();
+        let doc = editor.active_document().unwrap();
+        assert_eq!(doc.name(), "main.rs");
+        assert_eq!(doc.path(), Some(path.as_path()));
+        assert_eq!(doc.text(), "fn main() {\r\n}\r\n");
+        assert_eq!(*doc.line_ending(), LineEnding::Windows);
+        assert_eq!(doc.language(), Some("rs"));
+        assert!(!doc.is_dirty());
+
+        editor.set_large_file_threshold(16);
+        assert!(matches!(editor.open_file_async(&large).await, Err(Error::LargeFile { .. })));
+        assert!(editor.open_file_async(dir.path().join("missing.txt")).await.is_err());
+        assert_eq!(editor.active_document().unwrap().name(), "main.rs");
+    }
+
+    /// Flags every "TODO" in a document
+    struct TodoProvider;
+
+    impl crate::DiagnosticProvider for TodoProvider {
+        fn name(&self) -> &str {
+            "todo"
+        }
+
+        fn diagnostics(&self, doc: &Document) -> Vec<crate::Diagnostic> {
+            doc.text()
+                .match_indices("TODO")
+                .map(|(start, _)| crate::Diagnostic::new(start..start + 4, crate::DiagnosticSeverity::Info, "Unfinished"))
+                .collect()
+        }
+    }
+
+    #[test]
+    /// Test registering and removing diagnostic providers
+    fn test_diagnostic_providers() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.change_language(Some("text".to_string())).unwrap();
+        editor.active_document_mut().unwrap().insert(0, "TODO: a  \nTODO: b").unwrap();
+        let now = Instant::now();
+        editor.poll_diagnostics(now);
+
+        let sources = |editor: &Editor| {
+            let mut sources: Vec<_> = editor.active_document().unwrap().markers().diagnostics()
+                .into_iter()
+                .map(|marker| match &marker.marker_type {
+                    crate::MarkerType::Diagnostic { source, .. } => (source.clone(), marker.position),
+                    _ => unreachable!(),
+                })
+                .collect();
+            sources.sort();
+            sources
+        };
+        assert_eq!(sources(&editor), vec![("trailing-whitespace".to_string(), 7)]);
+
+        // Registering a provider refreshes right away
+        editor.diagnostic_providers().register(Some("text"), Arc::new(TodoProvider));
+        assert!(editor.poll_diagnostics(now));
+        assert_eq!(
+            sources(&editor),
+            vec![("todo".to_string(), 0), ("todo".to_string(), 10), ("trailing-whitespace".to_string(), 7)]
+        );
+        let markers = editor.active_document().unwrap().markers();
+        let second = markers.diagnostics().into_iter().find(|marker| marker.position == 10).unwrap();
+        let marker = markers.get_data(&second.name).unwrap();
+        assert_eq!(marker["end"], 14);
+        assert_eq!(marker["message"], "Unfinished");
+
+        // Removing it clears its markers
+        assert!(editor.diagnostic_providers().unregister("todo"));
+        assert!(editor.poll_diagnostics(now));
+        assert_eq!(sources(&editor), vec![("trailing-whitespace".to_string(), 7)]);
+        assert!(!editor.poll_diagnostics(now));
+    }
+
+    #[test]
+    /// Test that secondary views share the document's text
+    fn test_secondary_view_sees_edits() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.active_document_mut().unwrap().insert(0, "first line\n").unwrap();
+
+        let view = editor.open_secondary_view("notes.txt").unwrap();
+        assert_eq!(editor.view_text(view).as_deref(), Some("first line\n"));
+
+        // Editing the document shows up in the view
+        editor.active_document_mut().unwrap().insert(11, "second line\n").unwrap();
+        assert_eq!(editor.view_text(view).as_deref(), Some("first line\nsecond line\n"));
+        assert_eq!(editor.view_line_count(view), Some(3));
+
+        // So does a document opened in place of the viewed one
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        std::fs::write(&path, "from disk\n").unwrap();
+        editor.open_file(&path).unwrap();
+        assert_eq!(editor.view_text(view).as_deref(), Some("from disk\n"));
+
+        assert!(editor.open_secondary_view("missing.txt").is_err());
+        editor.close_document("notes.txt").unwrap();
+        assert!(editor.view(view).is_none());
+    }
+
+    #[test]
+    /// Test that views keep their own cursor and scroll position
+    fn test_secondary_view_cursors_independent() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.active_document_mut().unwrap().insert(0, "one\ntwo\nthree\n").unwrap();
+
+        let first = editor.open_secondary_view("notes.txt").unwrap();
+        let second = editor.open_secondary_view("notes.txt").unwrap();
+        assert_ne!(first, second);
+
+        editor.set_view_cursor(first, 5).unwrap();
+        editor.set_view_scroll_line(first, 2).unwrap();
+        assert_eq!(editor.view_cursor(first), Some(5));
+        assert_eq!(editor.view_scroll_line(first), Some(2));
+        assert_eq!(editor.view_cursor(second), Some(0));
+        assert_eq!(editor.view_scroll_line(second), Some(0));
+
+        // Cursors stay within the text when the document shrinks
+        editor.set_view_cursor(second, 100).unwrap();
+        assert_eq!(editor.view_cursor(second), Some(14));
+        editor.active_document_mut().unwrap().delete(3, 14).unwrap();
+        assert_eq!(editor.view_cursor(second), Some(3));
+        assert_eq!(editor.view_cursor(first), Some(3));
+
+        assert!(editor.close_view(first));
+        assert!(!editor.close_view(first));
+        assert!(editor.set_view_cursor(first, 0).is_err());
+        assert!(editor.view(second).is_some());
+    }
+
+    #[test]
+    /// Test that view cursors follow edits before them
+    fn test_secondary_view_cursor_follows_edits() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.active_document_mut().unwrap().insert(0, "one\ntwo\n").unwrap();
+
+        let view = editor.open_secondary_view("notes.txt").unwrap();
+        editor.set_view_cursor(view, 4).unwrap();
+
+        let doc = editor.active_document_mut().unwrap();
+        doc.insert(0, "zero\n").unwrap();
+        assert_eq!(editor.view_cursor(view), Some(9));
+        assert_eq!(&editor.view_text(view).unwrap()[9..], "two\n");
+
+        editor.active_document_mut().unwrap().delete(0, 5).unwrap();
+        assert_eq!(editor.view_cursor(view), Some(4));
+
+        // Edits after the cursor leave it in place
+        editor.active_document_mut().unwrap().insert(8, "three\n").unwrap();
+        assert_eq!(editor.view_cursor(view), Some(4));
+    }
+
+    #[test]
+    /// Test that scratch copies are independent of the original
+    fn test_duplicate_to_scratch() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.active_document_mut().unwrap().insert(0, "original\n").unwrap();
+
+        let scratch = editor.duplicate_to_scratch("notes.txt").unwrap();
+        assert_eq!(scratch, "notes.txt (scratch 1)");
+        let doc = editor.active_document().unwrap();
+        assert_eq!(doc.name(), scratch);
+        assert!(doc.is_scratch());
+        assert!(doc.path().is_none());
+        assert_eq!(doc.text(), "original\n");
+
+        // Edits to either document leave the other alone
+        editor.active_document_mut().unwrap().insert(0, "changed ").unwrap();
+        editor.set_active_document("notes.txt").unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "original\n");
+        editor.active_document_mut().unwrap().insert(9, "more\n").unwrap();
+        editor.set_active_document(&scratch).unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "changed original\n");
+
+        // The copy starts with nothing to undo
+        let doc = editor.active_document_mut().unwrap();
+        doc.undo().unwrap();
+        assert!(!doc.can_undo());
+
+        assert_eq!(editor.duplicate_to_scratch("notes.txt").unwrap(), "notes.txt (scratch 2)");
+        assert!(editor.duplicate_to_scratch("missing.txt").is_err());
+    }
+
+    #[test]
+    /// Test that scratch documents are left out of the session
+    fn test_scratch_not_persisted() {
+        let mut editor = Editor::new();
+        editor.new_document("notes.txt").unwrap();
+        editor.duplicate_to_scratch("notes.txt").unwrap();
+        assert_eq!(editor.documents().count(), 2);
+
+        let persistent: Vec<&str> = editor.persistent_documents().map(|doc| doc.name()).collect();
+        assert_eq!(persistent, vec!["notes.txt"]);
+    }
+}
@@ -1,12 +1,25 @@
 //! Event system module
 //!
-//! Provides event handling and dispatching mechanisms for editor state changes
-
-use std::sync::Arc;
+//! Provides event handling and dispatching mechanisms for editor state changes.
+//! The same hierarchy covers the core library, the UI, input and plugins, so
+//! handlers can be shared between them.
+
+use crate::document::{LineEnding, Selection};
+use crate::keys::{KeyCode, Modifier};
+use crate::syntax_edit::SyntaxEdit;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
 use async_trait::async_trait;
+use parking_lot::Mutex;
+use tokio::runtime::{Handle, Runtime};
 use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 use serde::{Serialize, Deserialize};
 
+/// Id of a handler added with `EventDispatcher::add_handler`
+pub type HandlerId = u64;
+
 /// Represents different types of editor events
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Event {
@@ -16,6 +29,12 @@ pub enum Event {
     Buffer(BufferEvent),
     /// Editor state events
     Editor(EditorEvent),
+    /// UI events
+    Ui(UiEvent),
+    /// Input events
+    Input(InputEvent),
+    /// Plugin events
+    Plugin(PluginEvent),
 }
 
 /// Document-specific events
@@ -33,12 +52,26 @@ pub enum DocumentEvent {
     /// Document was closed
     Closed {
         name: String,
+        path: Option<std::path::PathBuf>,
     },
     /// Document language changed
     LanguageChanged {
         name: String,
+        path: Option<std::path::PathBuf>,
         language: Option<String>,
     },
+    /// Document line endings were converted
+    LineEndingChanged {
+        name: String,
+        path: Option<std::path::PathBuf>,
+        line_ending: LineEnding,
+    },
+    /// Document selections changed, the primary one first
+    SelectionChanged {
+        name: String,
+        path: Option<std::path::PathBuf>,
+        selections: Vec<Selection>,
+    },
 }
 
 /// Buffer-specific events
@@ -48,12 +81,16 @@ pub enum BufferEvent {
     Inserted {
         position: usize,
         text: String,
+        /// The insertion for incremental parsers
+        edit: SyntaxEdit,
     },
     /// Text was deleted
     Deleted {
         start: usize,
         end: usize,
         text: String,
+        /// The deletion for incremental parsers
+        edit: SyntaxEdit,
     },
     /// Buffer was modified
     Modified {
@@ -79,12 +116,148 @@ pub enum EditorEvent {
     },
 }
 
+/// UI-related events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum UiEvent {
+    /// Theme was changed
+    ThemeChange {
+        /// New theme name
+        theme: String,
+    },
+    /// Font was changed
+    FontChange {
+        /// New font name
+        font: String,
+        /// New font size
+        size: f32,
+    },
+    /// Layout was changed
+    LayoutChange {
+        /// New layout configuration
+        layout: String,
+    },
+    /// Status message was updated
+    StatusMessage {
+        /// Message text
+        text: String,
+        /// Message duration in seconds (None for persistent)
+        duration: Option<f32>,
+        /// Action offered with the message
+        #[serde(default)]
+        action: Option<StatusAction>,
+    },
+    /// Line endings of an open document should be converted
+    NormalizeLineEndings {
+        /// Path to the document
+        path: std::path::PathBuf,
+        /// Line ending style to convert to
+        line_ending: LineEnding,
+    },
+    /// A file changed on disk while its document has unsaved edits; the
+    /// user chooses between reloading it and keeping the edits
+    ReloadConflict {
+        /// Path to the document
+        path: std::path::PathBuf,
+    },
+    /// The user's choice for a reload conflict
+    ResolveReloadConflict {
+        /// Path to the document
+        path: std::path::PathBuf,
+        /// Reload the file if true, keep the edits otherwise
+        reload: bool,
+    },
+}
+
+/// An action offered with a status message, e.g. as a button
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatusAction {
+    /// Label of the action
+    pub title: String,
+    /// Event to handle when the action is chosen
+    pub event: Box<UiEvent>,
+}
+
+/// Input-related events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum InputEvent {
+    /// Key was pressed
+    KeyPress {
+        /// Key code
+        key: KeyCode,
+        /// Key modifiers
+        modifiers: Vec<Modifier>,
+    },
+    /// Mouse button was pressed
+    MousePress {
+        /// Button number
+        button: u8,
+        /// X coordinate
+        x: f32,
+        /// Y coordinate
+        y: f32,
+    },
+    /// Mouse was moved
+    MouseMove {
+        /// X coordinate
+        x: f32,
+        /// Y coordinate
+        y: f32,
+    },
+    /// Mouse wheel was scrolled
+    MouseScroll {
+        /// Horizontal delta
+        delta_x: f32,
+        /// Vertical delta
+        delta_y: f32,
+    },
+}
+
+/// Plugin-related events
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum PluginEvent {
+    /// Plugin was loaded
+    Load {
+        /// Plugin name
+        name: String,
+    },
+    /// Plugin was unloaded
+    Unload {
+        /// Plugin name
+        name: String,
+    },
+    /// Plugin failed
+    Error {
+        /// Plugin name
+        name: String,
+        /// Error message
+        error: String,
+    },
+    /// Plugin command was executed
+    Command {
+        /// Plugin name
+        plugin: String,
+        /// Command name
+        command: String,
+        /// Command result
+        result: CommandResult,
+    },
+}
+
+/// Result of a plugin command
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CommandResult {
+    /// Command succeeded
+    Success,
+    /// Command failed
+    Error(String),
+}
+
 /// Trait for handling editor events
 ///
 /// # Examples
 ///
 /// ```
-/// use editor_core::event::{EventDispatcher, Event, EventHandler};
+/// use editor_core::event::{DocumentEvent, EventDispatcher, Event, EventHandler, EventSubscription};
 /// use std::sync::Arc;
 ///
 /// struct MyHandler;
@@ -99,7 +272,7 @@ pub enum EditorEvent {
 /// let dispatcher = EventDispatcher::new();
 /// let handler = Arc::new(MyHandler);
 ///
-/// let subscription = dispatcher.subscribe().with_handler(handler);
+/// let subscription = EventSubscription::new(dispatcher.subscribe()).with_handler(handler);
 ///
 /// // Dispatch an event
 /// dispatcher.dispatch(Event::Document(DocumentEvent::Opened {
@@ -132,24 +305,24 @@ pub trait EventHandler: Send + Sync {
 /// }
 ///
 /// let dispatcher = EventDispatcher::new();
-/// let handler = Arc::new(MyHandler);
-///
-/// let subscription = dispatcher.subscribe()
-///     .with_handler(handler);
-///
-/// // In a real application, you would spawn this to run in the background
-/// // tokio::spawn(subscription.listen());
+/// let id = dispatcher.add_handler(Arc::new(MyHandler));
 ///
 /// // Dispatch an event
 /// dispatcher.dispatch(Event::Document(DocumentEvent::Opened {
 ///     path: Some("example.txt".into()),
 ///     name: "example.txt".to_string(),
 /// }));
+///
+/// dispatcher.unsubscribe(id);
 /// ```
 #[allow(dead_code)]
 pub struct EventDispatcher {
     /// Channel for broadcasting events
     sender: broadcast::Sender<Event>,
+    /// Listener tasks of the handlers added with `add_handler`
+    handlers: Mutex<HashMap<HandlerId, JoinHandle<()>>>,
+    /// Id given to the next handler
+    next_handler: AtomicU64,
 }
 
 #[allow(dead_code)]
@@ -157,7 +330,41 @@ impl EventDispatcher {
     /// Creates a new event dispatcher
     pub fn new() -> Self {
         let (sender, _) = broadcast::channel(100);
-        Self { sender }
+        Self {
+            sender,
+            handlers: Mutex::new(HashMap::new()),
+            next_handler: AtomicU64::new(0),
+        }
+    }
+
+    /// Subscribes a handler to the events dispatched from now on
+    ///
+    /// The handler runs on a task of the current Tokio runtime, or of a
+    /// shared background runtime when called outside of one, until it is
+    /// removed with `unsubscribe` or the dispatcher is dropped.
+    pub fn add_handler(&self, handler: Arc<dyn EventHandler>) -> HandlerId {
+        let listener = EventSubscription::new(self.subscribe()).with_handler(handler).listen();
+        let task = match Handle::try_current() {
+            Ok(runtime) => runtime.spawn(listener),
+            Err(_) => background_runtime().spawn(listener),
+        };
+
+        let id = self.next_handler.fetch_add(1, Ordering::Relaxed);
+        self.handlers.lock().insert(id, task);
+        id
+    }
+
+    /// Stops a handler added with `add_handler`
+    ///
+    /// Returns false if there is no such handler.
+    pub fn unsubscribe(&self, id: HandlerId) -> bool {
+        match self.handlers.lock().remove(&id) {
+            Some(task) => {
+                task.abort();
+                true
+            }
+            None => false,
+        }
     }
 
     /// Dispatches an event to all registered handlers
@@ -169,6 +376,11 @@ impl EventDispatcher {
     pub fn subscribe(&self) -> broadcast::Receiver<Event> {
         self.sender.subscribe()
     }
+
+    /// Returns true if anyone is subscribed
+    pub fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
 }
 
 impl Default for EventDispatcher {
@@ -177,6 +389,27 @@ impl Default for EventDispatcher {
     }
 }
 
+impl Drop for EventDispatcher {
+    fn drop(&mut self) {
+        for (_, task) in self.handlers.get_mut().drain() {
+            task.abort();
+        }
+    }
+}
+
+/// Returns the runtime running handlers added outside of any runtime
+fn background_runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("editor-events")
+            .enable_all()
+            .build()
+            .expect("failed to start the event handler runtime")
+    })
+}
+
 /// Helper struct for managing event subscriptions
 ///
 /// This struct provides a convenient way to manage event subscriptions
@@ -255,6 +488,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_event_serialization() {
+        let event = Event::Ui(UiEvent::StatusMessage {
+            text: "mixed line endings".to_string(),
+            duration: None,
+            action: Some(StatusAction {
+                title: "Normalize".to_string(),
+                event: Box::new(UiEvent::NormalizeLineEndings {
+                    path: "notes.txt".into(),
+                    line_ending: LineEnding::Unix,
+                }),
+            }),
+        });
+
+        let serialized = serde_json::to_string(&event).unwrap();
+        let deserialized: Event = serde_json::from_str(&serialized).unwrap();
+
+        match deserialized {
+            Event::Ui(UiEvent::StatusMessage { text, action: Some(action), .. }) => {
+                assert_eq!(text, "mixed line endings");
+                assert!(matches!(*action.event, UiEvent::NormalizeLineEndings { line_ending: LineEnding::Unix, .. }));
+            }
+            _ => panic!("Wrong event type after deserialization"),
+        }
+
+        let event = Event::Input(InputEvent::KeyPress {
+            key: KeyCode::Char('s'),
+            modifiers: vec![Modifier::Ctrl],
+        });
+        let serialized = serde_json::to_string(&event).unwrap();
+        assert!(matches!(
+            serde_json::from_str(&serialized).unwrap(),
+            Event::Input(InputEvent::KeyPress { key: KeyCode::Char('s'), .. })
+        ));
+    }
+
     #[tokio::test]
     async fn test_event_system() {
         let dispatcher = EventDispatcher::new();
@@ -290,6 +559,38 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_add_handler() {
+        let dispatcher = EventDispatcher::new();
+        let received = Arc::new(parking_lot::RwLock::new(Vec::new()));
+        let id = dispatcher.add_handler(Arc::new(TestHandler { received: received.clone() }));
+
+        dispatcher.dispatch(Event::Buffer(BufferEvent::Modified { dirty: true }));
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(received.read().len(), 1);
+
+        assert!(dispatcher.unsubscribe(id));
+        assert!(!dispatcher.unsubscribe(id));
+        dispatcher.dispatch(Event::Buffer(BufferEvent::Modified { dirty: false }));
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+        assert_eq!(received.read().len(), 1);
+    }
+
+    #[test]
+    fn test_add_handler_outside_runtime() {
+        let dispatcher = EventDispatcher::new();
+        let received = Arc::new(parking_lot::RwLock::new(Vec::new()));
+        let id = dispatcher.add_handler(Arc::new(TestHandler { received: received.clone() }));
+
+        dispatcher.dispatch(Event::Buffer(BufferEvent::Modified { dirty: true }));
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
+        while received.read().is_empty() && std::time::Instant::now() < deadline {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(received.read().len(), 1);
+        assert!(dispatcher.unsubscribe(id));
+    }
+
     #[tokio::test]
     async fn test_event_dispatch() {
         let dispatcher = EventDispatcher::new();
@@ -0,0 +1,257 @@
+//! Fuzzy "Go to File" over the files of a workspace
+//!
+//! `FileFinder` keeps a list of the workspace's files, skipping ignored ones
+//! like `WorkspaceSearch` does, and ranks them against a query the way the
+//! command palette ranks actions. Recently opened files rank higher.
+
+use crate::action::fuzzy_score;
+use crate::workspace_search::IgnoreRules;
+use crate::{Error, Result};
+use std::path::{Path, PathBuf};
+
+/// Maximum number of recently opened files remembered for ranking
+pub const MAX_RECENT_FILES: usize = 50;
+
+/// Finds workspace files by fuzzy matching their relative paths
+#[derive(Debug, Clone)]
+pub struct FileFinder {
+    /// Directory holding the workspace
+    root: PathBuf,
+    /// Glob patterns of files and directories to skip, besides `.gitignore`
+    ignore: Vec<String>,
+    /// Ignore globs in effect, including those of the `.gitignore` files
+    effective_ignore: IgnoreRules,
+    /// Paths of the workspace files relative to the root, sorted
+    files: Vec<PathBuf>,
+    /// Relative paths of recently opened files, most recent first
+    recent: Vec<PathBuf>,
+}
+
+impl FileFinder {
+    /// Creates a finder for the files under `root`; call `refresh` to list them
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            ignore: Vec::new(),
+            effective_ignore: IgnoreRules::default(),
+            files: Vec::new(),
+            recent: Vec::new(),
+        }
+    }
+
+    /// Skips files and directories matching a glob, like a `.gitignore` line
+    pub fn with_ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Returns the workspace directory
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Lists the workspace files again
+    ///
+    /// Patterns from the `.gitignore` files in the workspace are re-read, so
+    /// edits to them apply.
+    pub fn refresh(&mut self) -> Result<()> {
+        if !self.root.is_dir() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Not a directory: {}", self.root.display()),
+            )));
+        }
+        self.effective_ignore = IgnoreRules::new(&self.root, self.ignore.clone());
+
+        let mut files = Vec::new();
+        let mut directories = vec![self.root.clone()];
+        while let Some(directory) = directories.pop() {
+            self.effective_ignore.add_gitignore(&directory);
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if self.effective_ignore.is_ignored(&path) {
+                    continue;
+                }
+                match entry.file_type() {
+                    Ok(file_type) if file_type.is_dir() => directories.push(path),
+                    Ok(file_type) if file_type.is_file() => files.push(self.relative(&path)),
+                    _ => {}
+                }
+            }
+        }
+        files.sort();
+        self.files = files;
+        Ok(())
+    }
+
+    /// Updates the list after a file system event on `path`, e.g. a file
+    /// being created, saved, renamed or deleted
+    ///
+    /// A change to any `.gitignore`, or to a directory, e.g. one renamed or
+    /// deleted with its files, lists the workspace again.
+    pub fn note_changed(&mut self, path: &Path) -> Result<()> {
+        let path = self.root.join(path);
+        if path.file_name().is_some_and(|name| name == ".gitignore") {
+            return self.refresh();
+        }
+
+        let relative = self.relative(&path);
+        let has_files = self.files.iter().any(|file| file.starts_with(&relative) && *file != relative);
+        if path.is_dir() || has_files {
+            return self.refresh();
+        }
+        let position = self.files.binary_search(&relative);
+        let listed = path.is_file() && !self.effective_ignore.is_ignored(&path);
+        match (position, listed) {
+            (Err(index), true) => self.files.insert(index, relative),
+            (Ok(index), false) => {
+                self.files.remove(index);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Returns the listed files, relative to the root and sorted
+    pub fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    /// Returns the files matching `query`, best first, relative to the root
+    ///
+    /// With an empty query, recently opened files come first, most recent
+    /// first, followed by the rest in order. Otherwise files whose relative
+    /// path contains the query's characters in order, ignoring case, are
+    /// ranked by how well they match plus a bonus for recent use; ties go
+    /// to the shorter path.
+    pub fn matching(&self, query: &str) -> Vec<PathBuf> {
+        let recency = |path: &PathBuf| self.recent.iter().position(|recent| recent == path);
+
+        if query.is_empty() {
+            let mut files = self.files.clone();
+            files.sort_by_key(|path| recency(path).unwrap_or(usize::MAX));
+            return files;
+        }
+
+        let mut scored: Vec<(usize, &PathBuf)> = self.files.iter()
+            .filter_map(|path| {
+                let score = fuzzy_score(&path.to_string_lossy().replace('\\', "/"), query)?;
+                let bonus = recency(path).map_or(0, |index| (MAX_RECENT_FILES - index) / 10);
+                Some((score + bonus, path))
+            })
+            .collect();
+        scored.sort_by(|(a_score, a), (b_score, b)| {
+            b_score.cmp(a_score).then_with(|| a.as_os_str().len().cmp(&b.as_os_str().len()))
+        });
+        scored.into_iter().map(|(_, path)| path.clone()).collect()
+    }
+
+    /// Moves a file to the front of the recently opened files
+    ///
+    /// `path` may be absolute or relative to the root.
+    pub fn note_opened(&mut self, path: &Path) {
+        let relative = self.relative(path);
+        self.recent.retain(|recent| *recent != relative);
+        self.recent.insert(0, relative);
+        self.recent.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Returns the recently opened files relative to the root, most recent first
+    pub fn recent(&self) -> &[PathBuf] {
+        &self.recent
+    }
+
+    /// Replaces the recently opened files, e.g. with ones from a saved session
+    pub fn set_recent(&mut self, paths: Vec<PathBuf>) {
+        self.recent = paths.iter().map(|path| self.relative(path)).collect();
+        self.recent.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Returns `path` relative to the root if it is under it
+    fn relative(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.root).unwrap_or(path).to_path_buf()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace(files: &[&str]) -> (tempfile::TempDir, FileFinder) {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let mut finder = FileFinder::new(dir.path());
+        finder.refresh().unwrap();
+        (dir, finder)
+    }
+
+    fn paths(files: &[&str]) -> Vec<PathBuf> {
+        files.iter().map(PathBuf::from).collect()
+    }
+
+    #[test]
+    fn test_fuzzy_ranking_of_paths() {
+        let (dir, mut finder) = workspace(&[
+            "editor/mod.rs",
+            "editor/src/model/data.rs",
+            "docs/embedding_mode.md",
+            "README.md",
+            "target/editor/mod.rs",
+            ".gitignore",
+        ]);
+        std::fs::write(dir.path().join(".gitignore"), "/target\n").unwrap();
+        finder.note_changed(Path::new(".gitignore")).unwrap();
+
+        assert_eq!(
+            finder.matching("edmod"),
+            paths(&["editor/mod.rs", "editor/src/model/data.rs", "docs/embedding_mode.md"])
+        );
+        assert_eq!(finder.matching("readme"), paths(&["README.md"]));
+        assert!(finder.matching("xyz").is_empty());
+
+        // Saved and deleted files update the list without walking again
+        std::fs::write(dir.path().join("editor/lib.rs"), "").unwrap();
+        finder.note_changed(&dir.path().join("editor/lib.rs")).unwrap();
+        std::fs::remove_file(dir.path().join("README.md")).unwrap();
+        finder.note_changed(Path::new("README.md")).unwrap();
+        assert_eq!(finder.matching("edlib"), paths(&["editor/lib.rs"]));
+        assert!(finder.matching("readme").is_empty());
+
+        // Renaming a directory moves its files
+        std::fs::rename(dir.path().join("docs"), dir.path().join("guide")).unwrap();
+        finder.note_changed(Path::new("docs")).unwrap();
+        assert_eq!(finder.matching("embedding"), paths(&["guide/embedding_mode.md"]));
+    }
+
+    #[test]
+    fn test_anchored_and_nested_gitignore() {
+        let (dir, mut finder) = workspace(&["target/out.rs", "src/target/mod.rs", "web/dist/app.js", "web/index.js"]);
+        std::fs::write(dir.path().join(".gitignore"), "/target\n").unwrap();
+        std::fs::write(dir.path().join("web/.gitignore"), "dist\n").unwrap();
+        finder.note_changed(Path::new("web/.gitignore")).unwrap();
+
+        assert_eq!(
+            finder.files(),
+            paths(&[".gitignore", "src/target/mod.rs", "web/.gitignore", "web/index.js"]).as_slice()
+        );
+    }
+
+    #[test]
+    fn test_recent_files_rank_higher() {
+        let (dir, mut finder) = workspace(&["src/main.rs", "src/menu.rs", "src/model.rs"]);
+        assert_eq!(finder.matching("srcm")[0], PathBuf::from("src/main.rs"));
+
+        finder.note_opened(&dir.path().join("src/model.rs"));
+        finder.note_opened(Path::new("src/menu.rs"));
+        assert_eq!(finder.recent(), paths(&["src/menu.rs", "src/model.rs"]).as_slice());
+        assert_eq!(finder.matching("srcm"), paths(&["src/menu.rs", "src/model.rs", "src/main.rs"]));
+        assert_eq!(finder.matching(""), paths(&["src/menu.rs", "src/model.rs", "src/main.rs"]));
+    }
+}
@@ -0,0 +1,230 @@
+//! Deferred work run while the editor is idle
+
+use crate::clock::{Clock, SystemClock};
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default time without input or edits before the editor counts as idle
+pub const DEFAULT_IDLE_DELAY: Duration = Duration::from_millis(500);
+
+/// Identifies a callback registered with an `IdleScheduler`
+pub type IdleCallbackId = u64;
+
+/// A registered idle callback
+struct IdleCallback {
+    /// Id returned on registration
+    id: IdleCallbackId,
+    /// Callbacks with a higher priority run first
+    priority: i32,
+    /// Work to run
+    callback: Box<dyn FnMut() + Send>,
+}
+
+/// Runs registered callbacks once the editor has been idle for a while
+///
+/// Each time input and edits stop for the quiet period, every callback runs
+/// once, highest priority first. `poll` runs one callback per call, so a
+/// frame never does more than one piece of deferred work. Activity cancels
+/// the callbacks that have not run yet; they run again after the next quiet
+/// period.
+pub struct IdleScheduler {
+    /// Time without activity before callbacks run
+    quiet_period: Duration,
+    /// Time source
+    clock: Arc<dyn Clock>,
+    /// Time of the last input or edit
+    last_activity: Instant,
+    /// Registered callbacks, highest priority first
+    callbacks: Vec<IdleCallback>,
+    /// Callbacks still to run in the current idle period, in order
+    pending: Vec<IdleCallbackId>,
+    /// Set once the callbacks of the current idle period were queued
+    queued: bool,
+    /// Id of the next registered callback
+    next_id: IdleCallbackId,
+}
+
+impl IdleScheduler {
+    /// Creates a scheduler using the system clock
+    pub fn new(quiet_period: Duration) -> Self {
+        Self::with_clock(quiet_period, Arc::new(SystemClock))
+    }
+
+    /// Creates a scheduler using the given clock
+    pub fn with_clock(quiet_period: Duration, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            quiet_period,
+            last_activity: clock.now(),
+            clock,
+            callbacks: Vec::new(),
+            pending: Vec::new(),
+            queued: false,
+            next_id: 0,
+        }
+    }
+
+    /// Returns the quiet period
+    pub fn quiet_period(&self) -> Duration {
+        self.quiet_period
+    }
+
+    /// Sets the quiet period
+    pub fn set_quiet_period(&mut self, quiet_period: Duration) {
+        self.quiet_period = quiet_period;
+    }
+
+    /// Registers a callback to run whenever the editor becomes idle
+    ///
+    /// Callbacks with a higher `priority` run first, equal ones in the order
+    /// they were registered.
+    pub fn register(&mut self, priority: i32, callback: impl FnMut() + Send + 'static) -> IdleCallbackId {
+        let id = self.next_id;
+        self.next_id += 1;
+        // After the last callback of at least the same priority
+        let index = self.callbacks.partition_point(|registered| registered.priority >= priority);
+        self.callbacks.insert(index, IdleCallback { id, priority, callback: Box::new(callback) });
+        id
+    }
+
+    /// Removes a callback, returning false if there is no such callback
+    pub fn unregister(&mut self, id: IdleCallbackId) -> bool {
+        self.pending.retain(|pending| *pending != id);
+        let before = self.callbacks.len();
+        self.callbacks.retain(|registered| registered.id != id);
+        self.callbacks.len() != before
+    }
+
+    /// Records input or an edit, cancelling the callbacks that have not run
+    /// in the current idle period
+    pub fn note_activity(&mut self) {
+        self.last_activity = self.clock.now();
+        self.pending.clear();
+        self.queued = false;
+    }
+
+    /// Returns the time left until the editor counts as idle, or `None` if
+    /// every callback already ran in the current idle period
+    pub fn time_until_idle(&self) -> Option<Duration> {
+        if self.queued && self.pending.is_empty() {
+            return None;
+        }
+        let quiet = self.clock.now().saturating_duration_since(self.last_activity);
+        Some(self.quiet_period.saturating_sub(quiet))
+    }
+
+    /// Returns true if there has been no activity for the quiet period
+    pub fn is_idle(&self) -> bool {
+        self.clock.now().saturating_duration_since(self.last_activity) >= self.quiet_period
+    }
+
+    /// Runs the next callback due in the current idle period
+    ///
+    /// Returns true if a callback ran; call again, e.g. on the next frame,
+    /// to run the rest.
+    pub fn poll(&mut self) -> bool {
+        if !self.is_idle() {
+            return false;
+        }
+        if !self.queued {
+            self.pending = self.callbacks.iter().rev().map(|registered| registered.id).collect();
+            self.queued = true;
+        }
+
+        while let Some(id) = self.pending.pop() {
+            if let Some(registered) = self.callbacks.iter_mut().find(|registered| registered.id == id) {
+                (registered.callback)();
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for IdleScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_IDLE_DELAY)
+    }
+}
+
+impl fmt::Debug for IdleScheduler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IdleScheduler")
+            .field("quiet_period", &self.quiet_period)
+            .field("callbacks", &self.callbacks.len())
+            .field("pending", &self.pending)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::MockClock;
+    use parking_lot::Mutex;
+
+    /// Registers a callback recording `name` when it runs
+    fn record(scheduler: &mut IdleScheduler, runs: &Arc<Mutex<Vec<&'static str>>>, priority: i32, name: &'static str) -> IdleCallbackId {
+        let runs = runs.clone();
+        scheduler.register(priority, move || runs.lock().push(name))
+    }
+
+    #[test]
+    fn test_callbacks_run_after_idle_window() {
+        let clock = Arc::new(MockClock::new());
+        let mut scheduler = IdleScheduler::with_clock(Duration::from_millis(500), clock.clone());
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        record(&mut scheduler, &runs, 0, "index");
+        record(&mut scheduler, &runs, 10, "highlight");
+        record(&mut scheduler, &runs, 0, "spellcheck");
+
+        clock.advance(Duration::from_millis(300));
+        assert!(!scheduler.poll());
+        assert_eq!(scheduler.time_until_idle(), Some(Duration::from_millis(200)));
+
+        // One callback per poll, highest priority first
+        clock.advance(Duration::from_millis(200));
+        assert!(scheduler.poll());
+        assert_eq!(*runs.lock(), vec!["highlight"]);
+        while scheduler.poll() {}
+        assert_eq!(*runs.lock(), vec!["highlight", "index", "spellcheck"]);
+
+        // Nothing runs again until the editor was busy and idle once more
+        clock.advance(Duration::from_secs(10));
+        assert!(!scheduler.poll());
+        assert_eq!(scheduler.time_until_idle(), None);
+        scheduler.note_activity();
+        clock.advance(Duration::from_millis(500));
+        assert!(scheduler.poll());
+        assert_eq!(runs.lock().len(), 4);
+    }
+
+    #[test]
+    fn test_activity_cancels_pending_callbacks() {
+        let clock = Arc::new(MockClock::new());
+        let mut scheduler = IdleScheduler::with_clock(Duration::from_millis(500), clock.clone());
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        record(&mut scheduler, &runs, 1, "highlight");
+        let index = record(&mut scheduler, &runs, 0, "index");
+
+        // Typing before the window ends restarts it
+        clock.advance(Duration::from_millis(400));
+        scheduler.note_activity();
+        clock.advance(Duration::from_millis(400));
+        assert!(!scheduler.poll());
+        assert!(runs.lock().is_empty());
+
+        // Activity between callbacks cancels the rest of the round
+        clock.advance(Duration::from_millis(100));
+        assert!(scheduler.poll());
+        scheduler.note_activity();
+        assert!(!scheduler.poll());
+        assert_eq!(*runs.lock(), vec!["highlight"]);
+
+        assert!(scheduler.unregister(index));
+        assert!(!scheduler.unregister(index));
+        clock.advance(Duration::from_millis(500));
+        while scheduler.poll() {}
+        assert_eq!(*runs.lock(), vec!["highlight", "highlight"]);
+    }
+}
@@ -0,0 +1,33 @@
+//! Keys and modifiers of keyboard input
+
+use serde::{Deserialize, Serialize};
+
+/// Represents a key code
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyCode {
+    Char(char),
+    Function(u8),
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    Tab,
+    BackTab,
+    Delete,
+    Insert,
+    Backspace,
+    Enter,
+    Esc,
+}
+
+/// Key modifier flags
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Modifier {
+    Ctrl,
+    Alt,
+    Shift,
+}
@@ -1,14 +1,57 @@
 //! Core text editing engine for rust-editor
 
+mod action;
+mod associations;
+mod autosave;
 mod buffer;
+mod clock;
+mod completion;
+mod diagnostics;
 mod document;
 pub mod editor;
-mod event;
+pub mod event;
+mod file_finder;
+mod idle;
+mod keys;
+mod markers;
+mod snippet;
+mod source;
+mod status;
+mod syntax_edit;
+mod vfs;
+mod view;
+mod workspace_search;
 
-pub use buffer::Buffer;
-pub use document::Document;
-pub use editor::Editor;
-pub use event::{Event, EventHandler};
+pub use action::{Action, ActionHandler, ActionRegistry, Context, MAX_RECENT_ACTIONS};
+pub use associations::FileAssociations;
+pub use autosave::AutosaveScheduler;
+pub use buffer::{Buffer, EditSummary};
+pub use clock::{Clock, MockClock, SystemClock};
+pub use completion::{CompletionContext, CompletionItem};
+pub use diagnostics::{
+    Diagnostic, DiagnosticProvider, DiagnosticRegistry, DiagnosticRunner, TrailingWhitespace,
+    DEFAULT_DIAGNOSTIC_DEBOUNCE,
+};
+pub use document::{
+    BlockSelection, ContentKind, Document, Encoding, IndentReport, IndentRules, IndentSettings, LineEnding,
+    LineEndingCounts, SearchMatch, SearchOptions, Selection, ShortLines, SortOptions,
+};
+pub use editor::{Editor, ExternalChanges, ReloadPolicy};
+pub use event::{
+    BufferEvent, CommandResult, DocumentEvent, EditorEvent, Event, EventHandler, HandlerId, InputEvent,
+    PluginEvent, StatusAction, UiEvent,
+};
+pub use file_finder::{FileFinder, MAX_RECENT_FILES};
+pub use idle::{IdleCallbackId, IdleScheduler, DEFAULT_IDLE_DELAY};
+pub use keys::{KeyCode, Modifier};
+pub use markers::{Bookmark, DiagnosticSeverity, LineIndex, Marker, MarkerSet, MarkerType};
+pub use snippet::{Snippet, TabStop};
+pub use source::{DocumentSource, LocalFsSource};
+pub use status::{StatusItem, StatusItems};
+pub use syntax_edit::{SyntaxEdit, SyntaxPoint};
+pub use vfs::{FileMetadata, MemoryFs, StdFs, VirtualFs};
+pub use view::{DocumentView, ViewId};
+pub use workspace_search::{FileMatch, IgnoreRules, SearchHandle, WorkspaceSearch, DEFAULT_MAX_CONCURRENCY};
 
 /// Result type for editor operations
 pub type Result<T> = std::result::Result<T, Error>;
@@ -27,6 +70,15 @@ pub enum Error {
     
     #[error("Event error: {0}")]
     Event(String),
+
+    #[error("Action error: {0}")]
+    Action(String),
+
+    #[error("Snippet error: {0}")]
+    Snippet(String),
+
+    #[error("File is too large to open ({size} bytes, threshold is {threshold} bytes)")]
+    LargeFile { size: u64, threshold: u64 },
 }
 
 /// Creates a new buffer with the given text
@@ -0,0 +1,562 @@
+//! Text markers and annotations system
+
+use std::collections::HashMap;
+use std::ops::Range;
+use serde::{Serialize, Deserialize};
+
+/// A marker in the text buffer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    /// Marker name
+    pub name: String,
+    /// Position in the buffer
+    pub position: usize,
+    /// Marker type
+    pub marker_type: MarkerType,
+    /// Marker data
+    pub data: Option<serde_json::Value>,
+}
+
+/// Marker type
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum MarkerType {
+    /// Cursor position
+    Cursor,
+    /// Selection
+    Selection,
+    /// Bookmark
+    Bookmark,
+    /// Search result
+    SearchResult,
+    /// Error or warning
+    Diagnostic {
+        /// Severity level
+        severity: DiagnosticSeverity,
+        /// Source of the diagnostic
+        source: String,
+    },
+    /// Custom marker type
+    Custom(String),
+}
+
+/// Diagnostic severity levels, ordered from most to least severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum DiagnosticSeverity {
+    /// Error
+    Error,
+    /// Warning
+    Warning,
+    /// Information
+    Info,
+    /// Hint
+    Hint,
+}
+
+/// A bookmarked line, in the form stored between sessions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    /// Line number (0-based)
+    pub line: usize,
+    /// Optional label shown with the bookmark
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+}
+
+impl Bookmark {
+    /// Creates an unlabeled bookmark
+    pub fn new(line: usize) -> Self {
+        Self { line, label: None }
+    }
+
+    /// Sets the label
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+/// Start offsets of the lines of a text, for mapping byte offsets to lines
+///
+/// Lines are split at `\n`, matching `SyntaxPoint` rows, unless the index is
+/// built with `for_lsp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Byte offset of the start of each line
+    starts: Vec<usize>,
+    /// Length of the text in bytes
+    len: usize,
+}
+
+impl LineIndex {
+    /// Indexes the lines of a text
+    pub fn new(text: &str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { starts, len: text.len() }
+    }
+
+    /// Indexes the lines of a text as language servers count them
+    ///
+    /// The LSP specification also ends lines at a lone `\r`, so old Mac
+    /// line endings must be split too for positions to agree with the server.
+    pub fn for_lsp(text: &str) -> Self {
+        let bytes = text.as_bytes();
+        let starts = std::iter::once(0)
+            .chain(bytes.iter().enumerate().filter_map(|(i, &byte)| match byte {
+                b'\n' => Some(i + 1),
+                b'\r' if bytes.get(i + 1) != Some(&b'\n') => Some(i + 1),
+                _ => None,
+            }))
+            .collect();
+        Self { starts, len: text.len() }
+    }
+
+    /// Returns the number of lines
+    pub fn line_count(&self) -> usize {
+        self.starts.len()
+    }
+
+    /// Returns the line containing a byte offset, clamped to the last line
+    pub fn line_of(&self, offset: usize) -> usize {
+        self.starts.partition_point(|&start| start <= offset) - 1
+    }
+
+    /// Returns the byte range of a line, including its line break
+    pub fn line_range(&self, line: usize) -> Option<Range<usize>> {
+        let start = *self.starts.get(line)?;
+        let end = self.starts.get(line + 1).copied().unwrap_or(self.len);
+        Some(start..end)
+    }
+
+    /// Returns the line and UTF-16 column of a byte offset of `text`, the
+    /// text the index was built from
+    ///
+    /// Columns count UTF-16 code units, as language servers do. The offset
+    /// is clamped to the text and moved back to a character boundary.
+    pub fn utf16_position(&self, text: &str, offset: usize) -> (usize, usize) {
+        let mut offset = offset.min(text.len());
+        while !text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_of(offset);
+        (line, text[self.starts[line]..offset].encode_utf16().count())
+    }
+
+    /// Returns the LSP position of a byte offset of `text`, the text the
+    /// index was built from, as `utf16_position` computes it
+    #[cfg(feature = "lsp")]
+    pub fn lsp_position(&self, text: &str, offset: usize) -> lsp_types::Position {
+        let (line, character) = self.utf16_position(text, offset);
+        lsp_types::Position::new(line as u32, character as u32)
+    }
+
+    /// Returns the byte offset of a line and UTF-16 column of `text`, the
+    /// text the index was built from
+    ///
+    /// A column past the end of the line is clamped to it, before the line
+    /// break, as the LSP specification asks, and a line past the last one to
+    /// the end of the text. Returns `None` if the column falls inside a
+    /// surrogate pair.
+    pub fn utf16_offset(&self, text: &str, line: usize, column: usize) -> Option<usize> {
+        let Some(range) = self.line_range(line) else {
+            return Some(text.len());
+        };
+        let content = &text[range.clone()];
+        let content = content.strip_suffix('\n').unwrap_or(content);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+
+        let mut units = 0;
+        for (index, ch) in content.char_indices() {
+            if units >= column {
+                return (units == column).then_some(range.start + index);
+            }
+            units += ch.len_utf16();
+        }
+        (units <= column).then_some(range.start + content.len())
+    }
+}
+
+/// Set of markers in a buffer
+#[derive(Debug, Default)]
+pub struct MarkerSet {
+    /// Markers by name
+    markers: HashMap<String, Marker>,
+    /// Markers by type
+    markers_by_type: HashMap<MarkerType, Vec<String>>,
+}
+
+impl MarkerSet {
+    /// Creates a new marker set
+    pub fn new() -> Self {
+        Self {
+            markers: HashMap::new(),
+            markers_by_type: HashMap::new(),
+        }
+    }
+
+    /// Sets a marker
+    pub fn set(&mut self, name: &str, position: usize) {
+        self.set_with_type(name, position, MarkerType::Bookmark);
+    }
+
+    /// Sets a marker with a specific type
+    pub fn set_with_type(&mut self, name: &str, position: usize, marker_type: MarkerType) {
+        let marker = Marker {
+            name: name.to_string(),
+            position,
+            marker_type: marker_type.clone(),
+            data: None,
+        };
+
+        // Remove from old type group if exists
+        if let Some(old_marker) = self.markers.get(name) {
+            if let Some(type_markers) = self.markers_by_type.get_mut(&old_marker.marker_type) {
+                type_markers.retain(|n| n != name);
+            }
+        }
+
+        // Add to new type group
+        self.markers_by_type
+            .entry(marker_type)
+            .or_default()
+            .push(name.to_string());
+
+        self.markers.insert(name.to_string(), marker);
+    }
+
+    /// Sets a marker with data
+    pub fn set_with_data(&mut self, name: &str, position: usize, marker_type: MarkerType, data: serde_json::Value) {
+        let marker = Marker {
+            name: name.to_string(),
+            position,
+            marker_type: marker_type.clone(),
+            data: Some(data),
+        };
+
+        // Remove from old type group if exists
+        if let Some(old_marker) = self.markers.get(name) {
+            if let Some(type_markers) = self.markers_by_type.get_mut(&old_marker.marker_type) {
+                type_markers.retain(|n| n != name);
+            }
+        }
+
+        // Add to new type group
+        self.markers_by_type
+            .entry(marker_type)
+            .or_default()
+            .push(name.to_string());
+
+        self.markers.insert(name.to_string(), marker);
+    }
+
+    /// Gets a marker\'s position
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.markers.get(name).map(|m| m.position)
+    }
+
+    /// Gets a marker\'s data
+    pub fn get_data(&self, name: &str) -> Option<&serde_json::Value> {
+        self.markers.get(name).and_then(|m| m.data.as_ref())
+    }
+
+    /// Gets all markers of a specific type
+    pub fn get_by_type(&self, marker_type: &MarkerType) -> Vec<&Marker> {
+        self.markers_by_type
+            .get(marker_type)
+            .map(|names| {
+                names.iter()
+                    .filter_map(|name| self.markers.get(name))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Gets all diagnostic markers, regardless of severity or source
+    pub fn diagnostics(&self) -> Vec<&Marker> {
+        self.markers
+            .values()
+            .filter(|m| matches!(m.marker_type, MarkerType::Diagnostic { .. }))
+            .collect()
+    }
+
+    /// Removes a marker
+    pub fn remove(&mut self, name: &str) {
+        if let Some(marker) = self.markers.remove(name) {
+            if let Some(type_markers) = self.markers_by_type.get_mut(&marker.marker_type) {
+                type_markers.retain(|n| n != name);
+            }
+        }
+    }
+
+    /// Updates marker positions after text changes
+    ///
+    /// A positive `offset` is an insertion at `position`, moving markers at or
+    /// after it. A negative one is a deletion starting at `position`; markers
+    /// inside the deleted text move to its start.
+    pub fn update_positions(&mut self, position: usize, offset: isize) {
+        let deleted_end = position.saturating_add(offset.unsigned_abs());
+        for marker in self.markers.values_mut() {
+            if marker.position < position {
+                continue;
+            }
+            marker.position = if offset >= 0 {
+                marker.position + offset as usize
+            } else if marker.position < deleted_end {
+                position
+            } else {
+                marker.position - offset.unsigned_abs()
+            };
+        }
+    }
+
+    /// Moves every marker to the position `map` returns for it
+    pub(crate) fn map_positions(&mut self, map: impl Fn(usize) -> usize) {
+        for marker in self.markers.values_mut() {
+            marker.position = map(marker.position);
+        }
+    }
+
+    /// Groups markers by the line they are on, ordered by position
+    pub fn by_line(&self, index: &LineIndex) -> HashMap<usize, Vec<&Marker>> {
+        let mut lines: HashMap<usize, Vec<&Marker>> = HashMap::new();
+        for marker in self.markers.values() {
+            lines.entry(index.line_of(marker.position)).or_default().push(marker);
+        }
+        for markers in lines.values_mut() {
+            markers.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        }
+        lines
+    }
+
+    /// Returns the markers on a line, ordered by position
+    pub fn markers_on_line(&self, index: &LineIndex, line: usize) -> Vec<&Marker> {
+        let Some(range) = index.line_range(line) else {
+            return Vec::new();
+        };
+        // The last line also holds markers at the very end of the text
+        let last = line + 1 == index.line_count();
+        let mut markers: Vec<_> = self.markers
+            .values()
+            .filter(|m| range.contains(&m.position) || (last && m.position >= range.start))
+            .collect();
+        markers.sort_by(|a, b| a.position.cmp(&b.position).then_with(|| a.name.cmp(&b.name)));
+        markers
+    }
+
+    /// Returns all markers in a range
+    pub fn in_range(&self, start: usize, end: usize) -> Vec<&Marker> {
+        self.markers
+            .values()
+            .filter(|m| m.position >= start && m.position < end)
+            .collect()
+    }
+
+    /// Returns the number of markers
+    pub fn len(&self) -> usize {
+        self.markers.len()
+    }
+
+    /// Returns true if there are no markers
+    pub fn is_empty(&self) -> bool {
+        self.markers.is_empty()
+    }
+
+    /// Clears all markers
+    pub fn clear(&mut self) {
+        self.markers.clear();
+        self.markers_by_type.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_marker_operations() {
+        let mut markers = MarkerSet::new();
+        
+        // Test basic marker operations
+        markers.set("bookmark1", 10);
+        assert_eq!(markers.get("bookmark1"), Some(10));
+        
+        markers.set("bookmark2", 20);
+        assert_eq!(markers.len(), 2);
+        
+        markers.remove("bookmark1");
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers.get("bookmark1"), None);
+    }
+
+    #[test]
+    fn test_marker_types() {
+        let mut markers = MarkerSet::new();
+        
+        // Add markers of different types
+        markers.set_with_type("cursor", 5, MarkerType::Cursor);
+        markers.set_with_type("error", 10, MarkerType::Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            source: "linter".to_string(),
+        });
+        
+        // Test getting markers by type
+        let cursors = markers.get_by_type(&MarkerType::Cursor);
+        assert_eq!(cursors.len(), 1);
+        assert_eq!(cursors[0].position, 5);
+    }
+
+    #[test]
+    fn test_marker_data() {
+        let mut markers = MarkerSet::new();
+        
+        // Add marker with data
+        markers.set_with_data(
+            "diagnostic",
+            15,
+            MarkerType::Diagnostic {
+                severity: DiagnosticSeverity::Warning,
+                source: "analyzer".to_string(),
+            },
+            serde_json::json!({
+                "message": "Unused variable",
+                "code": "W001"
+            }),
+        );
+
+        // Verify data
+        let data = markers.get_data("diagnostic").unwrap();
+        assert_eq!(data["message"], "Unused variable");
+        assert_eq!(data["code"], "W001");
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        let mut markers = MarkerSet::new();
+
+        markers.set("bookmark", 5);
+        markers.set_with_type("error", 10, MarkerType::Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            source: "linter".to_string(),
+        });
+        markers.set_with_type("hint", 20, MarkerType::Diagnostic {
+            severity: DiagnosticSeverity::Hint,
+            source: "lsp".to_string(),
+        });
+
+        assert_eq!(markers.diagnostics().len(), 2);
+        assert!(DiagnosticSeverity::Error < DiagnosticSeverity::Warning);
+    }
+
+    #[test]
+    fn test_position_updates() {
+        let mut markers = MarkerSet::new();
+        
+        markers.set("m1", 10);
+        markers.set("m2", 20);
+        markers.set("m3", 30);
+
+        // Test position updates after insertion
+        markers.update_positions(15, 5);
+        assert_eq!(markers.get("m1"), Some(10)); // Before insertion point
+        assert_eq!(markers.get("m2"), Some(25)); // After insertion point
+        assert_eq!(markers.get("m3"), Some(35)); // After insertion point
+
+        // Test position updates after deletion
+        markers.update_positions(15, -5);
+        assert_eq!(markers.get("m1"), Some(10)); // Before deletion point
+        assert_eq!(markers.get("m2"), Some(20)); // After deletion point
+        assert_eq!(markers.get("m3"), Some(30)); // After deletion point
+    }
+
+    #[test]
+    fn test_deletion_clamps_markers() {
+        let mut markers = MarkerSet::new();
+        markers.set("inside", 17);
+        markers.set("after", 25);
+
+        markers.update_positions(15, -5);
+        assert_eq!(markers.get("inside"), Some(15));
+        assert_eq!(markers.get("after"), Some(20));
+    }
+
+    #[test]
+    fn test_markers_by_line() {
+        let text = "fn main() {\n    let x = 1;\n\n}";
+        let index = LineIndex::new(text);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(index.line_range(1), Some(12..27));
+
+        let mut markers = MarkerSet::new();
+        markers.set("start", 0);
+        markers.set_with_type("error", 20, MarkerType::Diagnostic {
+            severity: DiagnosticSeverity::Error,
+            source: "linter".to_string(),
+        });
+        markers.set("bookmark", 16);
+        markers.set("empty-line", 27);
+        markers.set("end", text.len());
+
+        let lines = markers.by_line(&index);
+        let names = |line: usize| -> Vec<&str> {
+            lines.get(&line).map_or(Vec::new(), |m| m.iter().map(|m| m.name.as_str()).collect())
+        };
+        assert_eq!(names(0), vec!["start"]);
+        assert_eq!(names(1), vec!["bookmark", "error"]);
+        assert_eq!(names(2), vec!["empty-line"]);
+        assert_eq!(names(3), vec!["end"]);
+
+        let on_line: Vec<_> = markers.markers_on_line(&index, 1).iter().map(|m| m.position).collect();
+        assert_eq!(on_line, vec![16, 20]);
+        assert_eq!(markers.markers_on_line(&index, 3).len(), 1);
+        assert!(markers.markers_on_line(&index, 4).is_empty());
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        let text = "aé😀b\r\nx";
+        let index = LineIndex::new(text);
+        let b = text.find('b').unwrap();
+
+        // 'é' is one UTF-16 unit, '😀' two
+        assert_eq!(index.utf16_position(text, b), (0, 4));
+        assert_eq!(index.utf16_offset(text, 0, 4), Some(b));
+        assert_eq!(index.utf16_position(text, b - 1), (0, 2));
+        assert_eq!(index.utf16_offset(text, 0, 3), None);
+
+        // Columns past the line end stop before the line break
+        assert_eq!(index.utf16_offset(text, 0, 99), Some(b + 1));
+        assert_eq!(index.utf16_position(text, text.len()), (1, 1));
+        assert_eq!(index.utf16_offset(text, 1, 1), Some(text.len()));
+
+        // Lines past the end, e.g. the line after a final line break, stop at
+        // the end of the text
+        assert_eq!(index.utf16_offset(text, 2, 0), Some(text.len()));
+        assert_eq!(index.utf16_offset(text, 5, 3), Some(text.len()));
+
+        // Language servers also end lines at a lone '\r'
+        let text = "a\rb\r\nc\nd";
+        let index = LineIndex::for_lsp(text);
+        assert_eq!(index.line_count(), 4);
+        assert_eq!(LineIndex::new(text).line_count(), 3);
+        assert_eq!(index.utf16_position(text, 2), (1, 0));
+        assert_eq!(index.utf16_offset(text, 0, 99), Some(1));
+        assert_eq!(index.utf16_offset(text, 1, 99), Some(3));
+        assert_eq!(index.utf16_position(text, text.len()), (3, 1));
+    }
+
+    #[test]
+    fn test_range_queries() {
+        let mut markers = MarkerSet::new();
+        
+        markers.set("m1", 10);
+        markers.set("m2", 20);
+        markers.set("m3", 30);
+        markers.set("m4", 40);
+
+        let range_markers = markers.in_range(15, 35);
+        assert_eq!(range_markers.len(), 2);
+        assert!(range_markers.iter().any(|m| m.position == 20));
+        assert!(range_markers.iter().any(|m| m.position == 30));
+    }
+}
@@ -0,0 +1,281 @@
+//! Expansion of LSP snippets
+//!
+//! Completion items can use the LSP snippet syntax for their insert text,
+//! e.g. `fn ${1:name}($2) {\n    $0\n}`. A `Snippet` resolves the syntax to
+//! plain text and records where its tab stops ended up, so the UI can insert
+//! the text and cycle through the stops with Tab.
+
+use crate::{Error, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::Chars;
+
+/// A tab stop of an expanded snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    /// Tab stop number, 0 is the final cursor position
+    pub index: u32,
+    /// Byte ranges of the stop in the expanded text, the first one and its mirrors
+    pub ranges: Vec<Range<usize>>,
+}
+
+impl TabStop {
+    /// Returns the stop with its ranges moved by `offset` bytes
+    pub fn shifted(&self, offset: usize) -> Self {
+        Self {
+            index: self.index,
+            ranges: self.ranges.iter()
+                .map(|range| range.start + offset..range.end + offset)
+                .collect(),
+        }
+    }
+}
+
+/// A parsed snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Text with placeholders resolved
+    text: String,
+    /// Tab stops in the order they are visited
+    tab_stops: Vec<TabStop>,
+}
+
+impl Snippet {
+    /// Parses a snippet in LSP snippet syntax
+    ///
+    /// Supports tab stops (`$1`, `${1}`), placeholders (`${1:text}`, which
+    /// may be nested), choices (`${1|a,b|}`, resolved to the first choice) and
+    /// variables (`$NAME`, `${NAME:default}`, resolved to their default).
+    /// Mirrors of a placeholder repeat its text.
+    pub fn parse(source: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: source.chars().peekable(),
+            text: String::new(),
+            stops: BTreeMap::new(),
+            placeholders: HashMap::new(),
+        };
+        parser.parse_text(false)?;
+
+        let end = parser.text.len();
+        let final_stop = parser.stops.remove(&0).unwrap_or_else(|| std::iter::once(end..end).collect());
+        let mut tab_stops: Vec<TabStop> = parser.stops
+            .into_iter()
+            .map(|(index, ranges)| TabStop { index, ranges })
+            .collect();
+        tab_stops.push(TabStop { index: 0, ranges: final_stop });
+
+        Ok(Self {
+            text: parser.text,
+            tab_stops,
+        })
+    }
+
+    /// Returns the text to insert
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the tab stops in visiting order, ending with `$0`
+    ///
+    /// If the snippet has no `$0`, the final stop is the end of the text.
+    pub fn tab_stops(&self) -> &[TabStop] {
+        &self.tab_stops
+    }
+}
+
+/// Recursive descent parser for the snippet syntax
+struct Parser<'a> {
+    /// Remaining input
+    chars: Peekable<Chars<'a>>,
+    /// Expanded text so far
+    text: String,
+    /// Ranges of each tab stop
+    stops: BTreeMap<u32, Vec<Range<usize>>>,
+    /// Text of each placeholder, for its mirrors
+    placeholders: HashMap<u32, String>,
+}
+
+impl Parser<'_> {
+    /// Parses text until the end of the input, or the closing brace if `nested`
+    fn parse_text(&mut self, nested: bool) -> Result<()> {
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => match self.chars.peek() {
+                    Some(&escaped @ ('$' | '}' | '\\')) => {
+                        self.chars.next();
+                        self.text.push(escaped);
+                    }
+                    _ => self.text.push('\\'),
+                },
+                '}' if nested => return Ok(()),
+                '$' => self.parse_dollar()?,
+                c => self.text.push(c),
+            }
+        }
+
+        if nested {
+            Err(Error::Snippet("Unterminated placeholder".to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Parses what follows a `$`
+    fn parse_dollar(&mut self) -> Result<()> {
+        match self.chars.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let index = self.number()?;
+                self.mirror(index);
+            }
+            Some(&c) if is_name_start(c) => {
+                self.skip_name();
+            }
+            Some('{') => {
+                self.chars.next();
+                match self.chars.peek() {
+                    Some(c) if c.is_ascii_digit() => {
+                        let index = self.number()?;
+                        self.parse_tab_stop(index)?;
+                    }
+                    Some(&c) if is_name_start(c) => {
+                        self.skip_name();
+                        match self.chars.next() {
+                            Some('}') => {}
+                            // The default value is used as is
+                            Some(':') => self.parse_text(true)?,
+                            _ => return Err(Error::Snippet("Invalid variable".to_string())),
+                        }
+                    }
+                    _ => return Err(Error::Snippet("Expected a tab stop or variable after ${".to_string())),
+                }
+            }
+            _ => self.text.push('$'),
+        }
+        Ok(())
+    }
+
+    /// Parses the rest of a `${n...}` tab stop
+    fn parse_tab_stop(&mut self, index: u32) -> Result<()> {
+        match self.chars.next() {
+            Some('}') => self.mirror(index),
+            Some(':') => {
+                let start = self.text.len();
+                self.parse_text(true)?;
+                self.placeholders.entry(index).or_insert_with(|| self.text[start..].to_string());
+                self.stops.entry(index).or_default().push(start..self.text.len());
+            }
+            Some('|') => {
+                let mut first = String::new();
+                let mut in_first = true;
+                loop {
+                    match self.chars.next() {
+                        Some('\\') => {
+                            if let Some(c) = self.chars.next() {
+                                if in_first {
+                                    first.push(c);
+                                }
+                            }
+                        }
+                        Some(',') => in_first = false,
+                        Some('|') => break,
+                        Some(c) => {
+                            if in_first {
+                                first.push(c);
+                            }
+                        }
+                        None => return Err(Error::Snippet("Unterminated choice".to_string())),
+                    }
+                }
+                if self.chars.next() != Some('}') {
+                    return Err(Error::Snippet("Expected } after choice".to_string()));
+                }
+
+                let start = self.text.len();
+                self.text.push_str(&first);
+                self.placeholders.entry(index).or_insert(first);
+                self.stops.entry(index).or_default().push(start..self.text.len());
+            }
+            _ => return Err(Error::Snippet(format!("Invalid tab stop ${{{}", index))),
+        }
+        Ok(())
+    }
+
+    /// Inserts a bare tab stop, repeating the placeholder text if it has one
+    fn mirror(&mut self, index: u32) {
+        let start = self.text.len();
+        if let Some(text) = self.placeholders.get(&index) {
+            self.text.push_str(text);
+        }
+        self.stops.entry(index).or_default().push(start..self.text.len());
+    }
+
+    /// Reads a tab stop number
+    fn number(&mut self) -> Result<u32> {
+        let mut digits = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            digits.push(c);
+            self.chars.next();
+        }
+        digits.parse()
+            .map_err(|_| Error::Snippet(format!("Invalid tab stop number: {}", digits)))
+    }
+
+    /// Skips a variable name, variables are not resolved
+    fn skip_name(&mut self) {
+        while self.chars.next_if(|&c| c.is_ascii_alphanumeric() || c == '_').is_some() {}
+    }
+}
+
+/// Returns true for characters that can start a variable name
+fn is_name_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops(snippet: &Snippet) -> Vec<(u32, Vec<(usize, usize)>)> {
+        snippet.tab_stops()
+            .iter()
+            .map(|stop| (stop.index, stop.ranges.iter().map(|range| (range.start, range.end)).collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_rust_fn_snippet() {
+        let snippet = Snippet::parse("fn $1($2) {\n    $0\n}").unwrap();
+        assert_eq!(snippet.text(), "fn () {\n    \n}");
+        assert_eq!(stops(&snippet), vec![
+            (1, vec![(3, 3)]),
+            (2, vec![(4, 4)]),
+            (0, vec![(12, 12)]),
+        ]);
+    }
+
+    #[test]
+    fn test_nested_placeholders_and_mirrors() {
+        let snippet = Snippet::parse("${1:foo(${2:bar})} $1 \\$x ${3|a,b|}").unwrap();
+        assert_eq!(snippet.text(), "foo(bar) foo(bar) $x a");
+        assert_eq!(stops(&snippet), vec![
+            (1, vec![(0, 8), (9, 17)]),
+            (2, vec![(4, 7)]),
+            (3, vec![(21, 22)]),
+            // Without $0 the final stop is the end of the text
+            (0, vec![(22, 22)]),
+        ]);
+    }
+
+    #[test]
+    fn test_variables_and_errors() {
+        let snippet = Snippet::parse("${TM_SELECTED_TEXT:sel} $TM_FILENAME$").unwrap();
+        assert_eq!(snippet.text(), "sel $");
+
+        assert!(Snippet::parse("${1:unterminated").is_err());
+        assert!(Snippet::parse("${1|a,b").is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! Storage backends that documents are opened from and saved to
+//!
+//! A `DocumentSource` addresses documents by URI, so the editor can work with
+//! files that are not on the local disk (SFTP, in-memory, ...).
+
+use crate::vfs::{FileMetadata, MemoryFs, StdFs, VirtualFs};
+use crate::Result;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Backend that reads and writes documents by URI
+pub trait DocumentSource: Send + Sync {
+    /// Reads a document, returning its text and metadata
+    fn read(&self, uri: &str) -> Result<(String, FileMetadata)>;
+
+    /// Returns a document's metadata without reading its text
+    fn metadata(&self, uri: &str) -> Result<FileMetadata>;
+
+    /// Writes a document, replacing any existing content
+    fn write(&self, uri: &str, contents: &str) -> Result<()>;
+}
+
+/// Source backed by the local file system
+///
+/// Accepts plain paths and `file://` URIs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFsSource;
+
+impl LocalFsSource {
+    /// Returns the local path for a URI
+    fn path(uri: &str) -> &Path {
+        Path::new(uri.strip_prefix("file://").unwrap_or(uri))
+    }
+}
+
+impl DocumentSource for LocalFsSource {
+    fn read(&self, uri: &str) -> Result<(String, FileMetadata)> {
+        let path = Self::path(uri);
+        Ok((StdFs.read_to_string(path)?, StdFs.metadata(path)?))
+    }
+
+    fn metadata(&self, uri: &str) -> Result<FileMetadata> {
+        Ok(StdFs.metadata(Self::path(uri))?)
+    }
+
+    fn write(&self, uri: &str, contents: &str) -> Result<()> {
+        Ok(StdFs.write(Self::path(uri), contents)?)
+    }
+}
+
+impl DocumentSource for MemoryFs {
+    fn read(&self, uri: &str) -> Result<(String, FileMetadata)> {
+        let path = Path::new(uri);
+        Ok((self.read_to_string(path)?, VirtualFs::metadata(self, path)?))
+    }
+
+    fn metadata(&self, uri: &str) -> Result<FileMetadata> {
+        Ok(VirtualFs::metadata(self, Path::new(uri))?)
+    }
+
+    fn write(&self, uri: &str, contents: &str) -> Result<()> {
+        Ok(VirtualFs::write(self, Path::new(uri), contents)?)
+    }
+}
+
+/// Presents a `DocumentSource` as a file system so buffers can save through it
+///
+/// Paths are the document URIs.
+pub(crate) struct SourceFs(pub(crate) Arc<dyn DocumentSource>);
+
+impl SourceFs {
+    /// Returns the URI for a path
+    fn uri(path: &Path) -> io::Result<&str> {
+        path.to_str().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a valid URI", path.display()))
+        })
+    }
+
+    /// Returns the path used for a URI
+    pub(crate) fn path(uri: &str) -> PathBuf {
+        PathBuf::from(uri)
+    }
+}
+
+impl VirtualFs for SourceFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.0.read(Self::uri(path)?).map(|(text, _)| text).map_err(into_io)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.0.write(Self::uri(path)?, contents).map_err(into_io)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        Self::uri(path).is_ok_and(|uri| self.0.metadata(uri).is_ok())
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.0.metadata(Self::uri(path)?).map_err(into_io)
+    }
+}
+
+/// Converts an editor error into an I/O error
+fn into_io(error: crate::Error) -> io::Error {
+    match error {
+        crate::Error::Io(error) => error,
+        other => io::Error::other(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Document;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Source counting how often documents are downloaded
+    struct CountingSource {
+        files: MemoryFs,
+        reads: AtomicUsize,
+    }
+
+    impl DocumentSource for CountingSource {
+        fn read(&self, uri: &str) -> Result<(String, FileMetadata)> {
+            self.reads.fetch_add(1, Ordering::SeqCst);
+            self.files.read(uri)
+        }
+
+        fn write(&self, uri: &str, contents: &str) -> Result<()> {
+            DocumentSource::write(&self.files, uri, contents)
+        }
+
+        fn metadata(&self, uri: &str) -> Result<FileMetadata> {
+            DocumentSource::metadata(&self.files, uri)
+        }
+    }
+
+    #[test]
+    fn test_save_does_not_download() {
+        let source = Arc::new(CountingSource {
+            files: MemoryFs::new().with_file("sftp://host/notes.md", "# Notes\n"),
+            reads: AtomicUsize::new(0),
+        });
+        let mut doc = Document::from_source(source.clone(), "sftp://host/notes.md").unwrap();
+        doc.insert(8, "- item\n").unwrap();
+        doc.save().unwrap();
+        doc.save().unwrap();
+
+        assert_eq!(source.reads.load(Ordering::SeqCst), 1);
+        assert_eq!(source.files.get("sftp://host/notes.md").as_deref(), Some("# Notes\n- item\n"));
+        assert_eq!(DocumentSource::metadata(source.as_ref(), "sftp://host/notes.md").unwrap().len, 15);
+    }
+}
@@ -0,0 +1,106 @@
+//! Status bar items contributed by plugins
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// An item shown in the status bar, e.g. "Git: main ↑2"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusItem {
+    /// Text shown in the status bar
+    pub text: String,
+    /// Text shown when hovering the item
+    pub tooltip: Option<String>,
+    /// Items with a higher priority are shown first
+    pub priority: i32,
+}
+
+impl StatusItem {
+    /// Creates an item with no tooltip and priority 0
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            tooltip: None,
+            priority: 0,
+        }
+    }
+
+    /// Sets the tooltip
+    pub fn with_tooltip(mut self, tooltip: impl Into<String>) -> Self {
+        self.tooltip = Some(tooltip.into());
+        self
+    }
+
+    /// Sets the priority
+    pub fn with_priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// Status bar items of all plugins
+///
+/// Item ids are scoped to the plugin that set them, so two plugins can use
+/// the same id without clashing.
+#[derive(Debug, Default)]
+pub struct StatusItems {
+    /// Items by plugin and id
+    items: RwLock<HashMap<(String, String), StatusItem>>,
+}
+
+impl StatusItems {
+    /// Creates an empty set of items
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces a plugin's item
+    pub fn set(&self, plugin: &str, id: &str, item: StatusItem) {
+        self.items.write().insert((plugin.to_string(), id.to_string()), item);
+    }
+
+    /// Removes a plugin's item
+    pub fn clear(&self, plugin: &str, id: &str) {
+        self.items.write().remove(&(plugin.to_string(), id.to_string()));
+    }
+
+    /// Removes all items of a plugin
+    pub fn remove_plugin(&self, plugin: &str) {
+        self.items.write().retain(|(owner, _), _| owner != plugin);
+    }
+
+    /// Returns the items in display order
+    ///
+    /// Items are sorted by descending priority, then by plugin and id so the
+    /// order is stable between frames.
+    pub fn items(&self) -> Vec<StatusItem> {
+        let items = self.items.read();
+        let mut sorted: Vec<_> = items.iter().collect();
+        sorted.sort_by(|(a_key, a), (b_key, b)| b.priority.cmp(&a.priority).then_with(|| a_key.cmp(b_key)));
+        sorted.into_iter().map(|(_, item)| item.clone()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(items: &StatusItems) -> Vec<String> {
+        items.items().into_iter().map(|item| item.text).collect()
+    }
+
+    #[test]
+    fn test_items_sorted_and_scoped() {
+        let items = StatusItems::new();
+        items.set("git", "branch", StatusItem::new("main ↑2").with_priority(10));
+        items.set("clock", "time", StatusItem::new("12:00"));
+        items.set("lint", "branch", StatusItem::new("3 warnings").with_priority(10));
+        assert_eq!(texts(&items), vec!["main ↑2", "3 warnings", "12:00"]);
+
+        // Ids are per plugin
+        items.clear("git", "branch");
+        assert_eq!(texts(&items), vec!["3 warnings", "12:00"]);
+
+        items.remove_plugin("clock");
+        assert_eq!(texts(&items), vec!["3 warnings"]);
+    }
+}
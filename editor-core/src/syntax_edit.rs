@@ -0,0 +1,95 @@
+//! Edit descriptions for incremental parsers
+//!
+//! A `SyntaxEdit` carries the same fields as tree-sitter's `InputEdit`, so
+//! syntax consumers can update their trees without re-deriving positions.
+
+use serde::{Deserialize, Serialize};
+
+/// A position as a row and a byte column
+///
+/// Rows are separated by `\n` only, as in tree-sitter, so a `\r\n` line ends
+/// with the `\r` in its last column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct SyntaxPoint {
+    /// Row (0-based)
+    pub row: usize,
+    /// Byte offset within the row
+    pub column: usize,
+}
+
+impl SyntaxPoint {
+    /// Creates a new point
+    pub fn new(row: usize, column: usize) -> Self {
+        Self { row, column }
+    }
+
+    /// Returns the point reached by moving past `text` from this point
+    pub fn advance(self, text: &str) -> Self {
+        match text.rfind('\n') {
+            Some(last) => Self {
+                row: self.row + text.matches('\n').count(),
+                column: text.len() - last - 1,
+            },
+            None => Self {
+                row: self.row,
+                column: self.column + text.len(),
+            },
+        }
+    }
+}
+
+/// Byte and point deltas of a single edit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SyntaxEdit {
+    /// Byte offset where the edit starts
+    pub start_byte: usize,
+    /// End of the replaced text before the edit
+    pub old_end_byte: usize,
+    /// End of the new text after the edit
+    pub new_end_byte: usize,
+    /// Position where the edit starts
+    pub start_position: SyntaxPoint,
+    /// Position of `old_end_byte` before the edit
+    pub old_end_position: SyntaxPoint,
+    /// Position of `new_end_byte` after the edit
+    pub new_end_position: SyntaxPoint,
+}
+
+impl SyntaxEdit {
+    /// Describes inserting `text` at `start`
+    pub fn insertion(start_byte: usize, start_position: SyntaxPoint, text: &str) -> Self {
+        Self {
+            start_byte,
+            old_end_byte: start_byte,
+            new_end_byte: start_byte + text.len(),
+            start_position,
+            old_end_position: start_position,
+            new_end_position: start_position.advance(text),
+        }
+    }
+
+    /// Describes deleting `text`, which started at `start`
+    pub fn deletion(start_byte: usize, start_position: SyntaxPoint, text: &str) -> Self {
+        Self {
+            start_byte,
+            old_end_byte: start_byte + text.len(),
+            new_end_byte: start_byte,
+            start_position,
+            old_end_position: start_position.advance(text),
+            new_end_position: start_position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_advance() {
+        let start = SyntaxPoint::new(2, 4);
+        assert_eq!(start.advance("abc"), SyntaxPoint::new(2, 7));
+        assert_eq!(start.advance("ab\ncd\r\néf"), SyntaxPoint::new(4, 3));
+        assert_eq!(start.advance("ab\n"), SyntaxPoint::new(3, 0));
+    }
+}
@@ -0,0 +1,206 @@
+//! File system abstraction for loading and saving buffers
+//!
+//! Buffers read and write through a `VirtualFs`, so tests and scratch buffers
+//! can be backed by memory instead of real files.
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Metadata of a file in a `VirtualFs`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    /// Size in bytes
+    pub len: u64,
+    /// Last modification time, if known
+    pub modified: Option<SystemTime>,
+    /// True if the file cannot be written
+    pub read_only: bool,
+}
+
+/// File system operations used by buffers
+pub trait VirtualFs: Send + Sync {
+    /// Reads a whole file as UTF-8 text
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Reads a whole file as bytes, for content that may not be UTF-8
+    ///
+    /// The default reads the file as text.
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.read_to_string(path).map(String::into_bytes)
+    }
+
+    /// Writes a whole file, replacing any existing content
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    /// Writes a whole file as bytes, for content that is not UTF-8
+    ///
+    /// The default writes the bytes as text, failing with
+    /// `ErrorKind::InvalidData` if they are not UTF-8.
+    fn write_bytes(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        let text = std::str::from_utf8(contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        self.write(path, text)
+    }
+
+    /// Overwrites the bytes of an existing file starting at `offset`, leaving
+    /// the rest in place and extending the file if they run past its end
+    ///
+    /// The default fails with `ErrorKind::Unsupported`, so saving rewrites
+    /// the whole file instead.
+    fn write_at(&self, path: &Path, offset: u64, contents: &str) -> io::Result<()> {
+        let _ = (path, offset, contents);
+        Err(io::Error::new(io::ErrorKind::Unsupported, "writing part of a file is not supported"))
+    }
+
+    /// Returns true if the file exists
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Returns the size, modification time and permissions of a file
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+}
+
+/// File system backed by `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdFs;
+
+impl VirtualFs for StdFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    fn read_bytes(&self, path: &Path) -> io::Result<Vec<u8>> {
+        std::fs::read(path)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn write_bytes(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &str) -> io::Result<()> {
+        use std::io::{Seek, SeekFrom, Write};
+        let mut file = std::fs::OpenOptions::new().write(true).open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(contents.as_bytes())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let metadata = std::fs::metadata(path)?;
+        Ok(FileMetadata {
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            read_only: metadata.permissions().readonly(),
+        })
+    }
+}
+
+/// In-memory file system
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    /// File contents by path
+    files: RwLock<HashMap<PathBuf, String>>,
+}
+
+impl MemoryFs {
+    /// Creates an empty in-memory file system
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file with the given contents
+    pub fn with_file(self, path: impl Into<PathBuf>, contents: impl Into<String>) -> Self {
+        self.files.write().insert(path.into(), contents.into());
+        self
+    }
+
+    /// Returns the contents of a file, if it exists
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.read().get(path.as_ref()).cloned()
+    }
+
+    /// Removes a file, returning its contents
+    pub fn remove(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.files.write().remove(path.as_ref())
+    }
+
+    /// Returns the paths of all files
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.files.read().keys().cloned().collect()
+    }
+}
+
+impl VirtualFs for MemoryFs {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.get(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display()))
+        })
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.files.write().insert(path.to_path_buf(), contents.to_string());
+        Ok(())
+    }
+
+    fn write_at(&self, path: &Path, offset: u64, contents: &str) -> io::Result<()> {
+        let mut files = self.files.write();
+        let file = files.get_mut(path).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("{} not found", path.display()))
+        })?;
+        let start = offset as usize;
+        let end = (start + contents.len()).min(file.len());
+        if start > file.len() || !file.is_char_boundary(start) || !file.is_char_boundary(end) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid offset {}", offset)));
+        }
+        file.replace_range(start..end, contents);
+        Ok(())
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files.read().contains_key(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        let len = self.read_to_string(path)?.len() as u64;
+        Ok(FileMetadata {
+            len,
+            modified: None,
+            read_only: false,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_fs() {
+        let fs = MemoryFs::new().with_file("/a.txt", "alpha");
+
+        assert!(fs.exists(Path::new("/a.txt")));
+        assert_eq!(fs.read_to_string(Path::new("/a.txt")).unwrap(), "alpha");
+
+        fs.write(Path::new("/b.txt"), "beta").unwrap();
+        assert_eq!(fs.get("/b.txt").as_deref(), Some("beta"));
+        assert_eq!(fs.paths().len(), 2);
+        assert_eq!(fs.metadata(Path::new("/b.txt")).unwrap().len, 4);
+
+        fs.write_at(Path::new("/b.txt"), 1, "ET").unwrap();
+        fs.write_at(Path::new("/b.txt"), 4, "!").unwrap();
+        assert_eq!(fs.get("/b.txt").as_deref(), Some("bETa!"));
+        assert!(fs.write_at(Path::new("/b.txt"), 9, "x").is_err());
+
+        assert_eq!(fs.remove("/a.txt").as_deref(), Some("alpha"));
+        let err = fs.read_to_string(Path::new("/a.txt")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+}
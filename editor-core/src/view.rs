@@ -0,0 +1,66 @@
+//! Secondary, read-only views of open documents
+
+use std::fmt;
+
+/// Identifies a view opened with `Editor::open_secondary_view`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ViewId(u64);
+
+impl fmt::Display for ViewId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "view-{}", self.0)
+    }
+}
+
+/// A read-only view of a document with its own cursor and scroll position
+///
+/// The view refers to its document by name, so it always shows the open
+/// document's current text. Its cursor is a marker in the document, moved
+/// by edits like bookmarks are. Use the `Editor::view_*` methods to read
+/// and move it.
+#[derive(Debug, Clone)]
+pub struct DocumentView {
+    /// View id
+    id: ViewId,
+    /// Name of the viewed document
+    document: String,
+    /// First visible line
+    scroll_line: usize,
+}
+
+impl DocumentView {
+    /// Creates a view of a document
+    pub(crate) fn new(id: u64, document: impl Into<String>) -> Self {
+        Self {
+            id: ViewId(id),
+            document: document.into(),
+            scroll_line: 0,
+        }
+    }
+
+    /// Returns the view id
+    pub fn id(&self) -> ViewId {
+        self.id
+    }
+
+    /// Returns the name of the viewed document
+    pub fn document(&self) -> &str {
+        &self.document
+    }
+
+    /// Returns the name of the document marker holding the view's cursor
+    pub(crate) fn cursor_marker(&self) -> String {
+        format!("{}:cursor", self.id)
+    }
+
+    /// Returns the first visible line as last set, which may be past the end
+    /// of the text
+    pub(crate) fn scroll_line(&self) -> usize {
+        self.scroll_line
+    }
+
+    /// Scrolls so that `line` is the first visible line
+    pub(crate) fn set_scroll_line(&mut self, line: usize) {
+        self.scroll_line = line;
+    }
+}
@@ -0,0 +1,458 @@
+//! Concurrent search across the files of a workspace
+//!
+//! `WorkspaceSearch` walks a directory tree and searches the files it finds
+//! on a bounded number of tasks, so large workspaces neither run serially nor
+//! exhaust file descriptors. Matches are streamed through a channel as they
+//! are found.
+
+use crate::associations::glob_match;
+use crate::{ContentKind, Error, Result, SearchOptions};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+
+/// Default number of files searched at the same time
+pub const DEFAULT_MAX_CONCURRENCY: usize = 16;
+
+/// Number of matches buffered before searching waits for the receiver
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Directories that are never searched
+const ALWAYS_IGNORED: &[&str] = &[".git"];
+
+/// A match found in a workspace file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatch {
+    /// Path of the file
+    pub path: PathBuf,
+    /// Zero-based line of the match start
+    pub line: usize,
+    /// Byte range of the match in the file's text
+    pub range: Range<usize>,
+    /// Text of the line containing the match start, without the line ending
+    pub line_text: String,
+}
+
+/// Search for a pattern in every file under a directory
+#[derive(Debug, Clone)]
+pub struct WorkspaceSearch {
+    /// Directory to search
+    root: PathBuf,
+    /// Text to look for
+    pattern: String,
+    /// How the pattern is matched
+    options: SearchOptions,
+    /// Maximum number of files read at the same time
+    max_concurrency: usize,
+    /// Glob patterns of files and directories to skip, besides `.gitignore`
+    ignore: Vec<String>,
+}
+
+impl WorkspaceSearch {
+    /// Creates a case-sensitive search with the default concurrency
+    pub fn new(root: impl Into<PathBuf>, pattern: impl Into<String>) -> Self {
+        Self {
+            root: root.into(),
+            pattern: pattern.into(),
+            options: SearchOptions::default(),
+            max_concurrency: DEFAULT_MAX_CONCURRENCY,
+            ignore: Vec::new(),
+        }
+    }
+
+    /// Sets how the pattern is matched
+    pub fn with_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Sets the maximum number of files read at the same time, at least 1
+    pub fn with_max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = max_concurrency.max(1);
+        self
+    }
+
+    /// Skips files and directories matching a glob, like a `.gitignore` line
+    pub fn with_ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.push(pattern.into());
+        self
+    }
+
+    /// Starts searching on the current tokio runtime
+    ///
+    /// Patterns from the `.gitignore` files in the workspace are added to the
+    /// ignored ones.
+    /// Binary and unreadable files are skipped.
+    pub fn start(self) -> Result<SearchHandle> {
+        if !self.root.is_dir() {
+            return Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("Not a directory: {}", self.root.display()),
+            )));
+        }
+
+        let ignore = IgnoreRules::new(&self.root, self.ignore.clone());
+
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let walker = Walker {
+            search: Arc::new(self),
+            ignore,
+            cancelled: Arc::clone(&cancelled),
+        };
+        tokio::spawn(walker.run(sender));
+
+        Ok(SearchHandle { receiver, cancelled })
+    }
+}
+
+/// Receives the matches of a running search
+///
+/// Dropping the handle cancels the search.
+#[derive(Debug)]
+pub struct SearchHandle {
+    /// Matches in the order they were found
+    receiver: mpsc::Receiver<FileMatch>,
+    /// Set to stop walking and searching
+    cancelled: Arc<AtomicBool>,
+}
+
+impl SearchHandle {
+    /// Returns the next match, or `None` once the search is done or cancelled
+    pub async fn next(&mut self) -> Option<FileMatch> {
+        if self.is_cancelled() {
+            return None;
+        }
+        self.receiver.recv().await
+    }
+
+    /// Waits for the search to finish and returns the remaining matches
+    pub async fn collect(mut self) -> Vec<FileMatch> {
+        let mut matches = Vec::new();
+        while let Some(found) = self.next().await {
+            matches.push(found);
+        }
+        matches
+    }
+
+    /// Stops the search; files already being searched finish quietly
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the search was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for SearchHandle {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Walks the workspace and hands files to bounded search tasks
+struct Walker {
+    /// The search being run
+    search: Arc<WorkspaceSearch>,
+    /// Ignore globs, including those of the `.gitignore` files walked so far
+    ignore: IgnoreRules,
+    /// Set when the handle cancels the search
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Walker {
+    /// Walks the tree, spawning a task per file once a permit is free
+    ///
+    /// The channel closes when the walk and every spawned task are done,
+    /// because each of them holds a sender.
+    async fn run(mut self, sender: mpsc::Sender<FileMatch>) {
+        let permits = Arc::new(Semaphore::new(self.search.max_concurrency));
+        let mut directories = vec![self.search.root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            self.ignore.add_gitignore(&directory);
+            let Ok(mut entries) = tokio::fs::read_dir(&directory).await else {
+                continue;
+            };
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if self.is_cancelled() {
+                    return;
+                }
+                let path = entry.path();
+                if self.ignore.is_ignored(&path) {
+                    continue;
+                }
+                let Ok(file_type) = entry.file_type().await else {
+                    continue;
+                };
+                if file_type.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                if !file_type.is_file() {
+                    continue;
+                }
+
+                // Waiting here bounds the number of open files and of live tasks
+                let Ok(permit) = Arc::clone(&permits).acquire_owned().await else {
+                    return;
+                };
+                let search = Arc::clone(&self.search);
+                let cancelled = Arc::clone(&self.cancelled);
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    search_file(&search, path, &cancelled, &sender).await;
+                    drop(permit);
+                });
+            }
+        }
+    }
+
+    /// Returns true if the search was cancelled or the receiver dropped
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Searches one file and sends its matches
+async fn search_file(search: &WorkspaceSearch, path: PathBuf, cancelled: &AtomicBool, sender: &mpsc::Sender<FileMatch>) {
+    if cancelled.load(Ordering::Relaxed) {
+        return;
+    }
+    let bytes = match tokio::fs::read(&path).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::debug!("Skipping {} in workspace search: {}", path.display(), e);
+            return;
+        }
+    };
+    let (kind, text) = ContentKind::decode(&bytes);
+    if kind.is_binary() {
+        return;
+    }
+
+    let mut line = 0;
+    let mut counted = 0;
+    for range in search.options.find_all(&text, &search.pattern) {
+        line += text[counted..range.start].matches('\n').count();
+        counted = range.start;
+
+        let line_start = text[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = text[range.start..].find('\n').map_or(text.len(), |i| range.start + i);
+        let found = FileMatch {
+            path: path.clone(),
+            line,
+            range,
+            line_text: text[line_start..line_end].trim_end_matches('\r').to_string(),
+        };
+        if cancelled.load(Ordering::Relaxed) || sender.send(found).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Ignore globs in effect in a workspace
+///
+/// Holds the globs given by the caller plus those of every `.gitignore` file
+/// read so far, each applying to the directory its file is in. Globs with a
+/// `/`, other than a trailing one, match the path relative to that
+/// directory, and others match the name of anything under it, as in git.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    /// Directory holding the workspace
+    root: PathBuf,
+    /// Rules in the order they were added
+    rules: Vec<IgnoreRule>,
+}
+
+/// One ignore glob and the directory it applies to
+#[derive(Debug, Clone)]
+struct IgnoreRule {
+    /// Directory the glob applies to, relative to the root
+    base: PathBuf,
+    /// Glob without a leading or trailing `/`
+    pattern: String,
+    /// True if the glob matches the path relative to `base` rather than the name
+    anchored: bool,
+}
+
+impl IgnoreRules {
+    /// Creates the rules for the files under `root`, starting with `globs`,
+    /// which apply to the whole workspace
+    ///
+    /// `.gitignore` files are added with `add_gitignore` as their
+    /// directories are walked.
+    pub fn new(root: impl Into<PathBuf>, globs: Vec<String>) -> Self {
+        let mut rules = Self { root: root.into(), rules: Vec::new() };
+        for glob in globs {
+            rules.push(PathBuf::new(), &glob);
+        }
+        rules
+    }
+
+    /// Adds the globs of the `.gitignore` in `directory`, if it has one
+    ///
+    /// Comments, blank lines and negations are skipped, and a trailing `/`
+    /// is dropped since directories and files are matched alike.
+    pub fn add_gitignore(&mut self, directory: &Path) {
+        let Ok(gitignore) = std::fs::read_to_string(directory.join(".gitignore")) else {
+            return;
+        };
+        let base = directory.strip_prefix(&self.root).unwrap_or(directory).to_path_buf();
+        for line in gitignore.lines().map(str::trim) {
+            if !line.is_empty() && !line.starts_with('#') && !line.starts_with('!') {
+                self.push(base.clone(), line);
+            }
+        }
+    }
+
+    /// Adds a glob applying to `base`, relative to the root
+    fn push(&mut self, base: PathBuf, glob: &str) {
+        let glob = glob.trim_end_matches('/');
+        // A leading `/` anchors the glob to its directory, as does one inside it
+        let (pattern, anchored) = match glob.strip_prefix('/') {
+            Some(pattern) => (pattern, true),
+            None => (glob, glob.contains('/')),
+        };
+        if !pattern.is_empty() {
+            self.rules.push(IgnoreRule { base, pattern: pattern.to_string(), anchored });
+        }
+    }
+
+    /// Returns true if a path under the root is ignored
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return true;
+        };
+        if ALWAYS_IGNORED.contains(&name) {
+            return true;
+        }
+
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        self.rules.iter().any(|rule| {
+            let Ok(within) = relative.strip_prefix(&rule.base) else {
+                return false;
+            };
+            if rule.anchored {
+                glob_match(&rule.pattern, &within.to_string_lossy().replace('\\', "/"))
+            } else {
+                glob_match(&rule.pattern, name)
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn write(dir: &TempDir, relative: &str, contents: &str) {
+        let path = dir.path().join(relative);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_search_many_files() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..60 {
+            write(&dir, &format!("src/mod{}/file{}.rs", i % 7, i), &format!("fn f{}() {{\n    todo!()\n}}\n// TODO\n", i));
+        }
+        write(&dir, "target/debug/out.rs", "todo!()\n");
+        write(&dir, ".git/HEAD", "todo\n");
+        write(&dir, ".gitignore", "# build output\n/target/\n");
+        write(&dir, "logo.png", "todo\0\u{1}\u{2}\u{3}\u{4}");
+
+        let matches = WorkspaceSearch::new(dir.path(), "todo")
+            .with_options(SearchOptions::new().with_case_insensitive(true))
+            .with_max_concurrency(3)
+            .start()
+            .unwrap()
+            .collect()
+            .await;
+
+        assert_eq!(matches.len(), 120);
+        assert!(matches.iter().all(|m| m.path.extension().is_some_and(|ext| ext == "rs")));
+        assert!(matches.iter().all(|m| !m.path.starts_with(dir.path().join("target"))));
+
+        let mut in_file: Vec<_> = matches.iter()
+            .filter(|m| m.path.ends_with("file12.rs"))
+            .map(|m| (m.line, m.line_text.as_str()))
+            .collect();
+        in_file.sort();
+        assert_eq!(in_file, vec![(1, "    todo!()"), (3, "// TODO")]);
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, ".gitignore", "/target/\n*.log\ndocs/build\n");
+        write(&dir, "app/.gitignore", "# generated\ngen\n/local.rs\n");
+        let mut rules = IgnoreRules::new(dir.path(), vec!["*.tmp".to_string()]);
+        rules.add_gitignore(dir.path());
+        rules.add_gitignore(&dir.path().join("app"));
+        let ignored = |relative: &str| rules.is_ignored(&dir.path().join(relative));
+
+        // A leading `/` anchors a glob to the directory of its `.gitignore`
+        assert!(ignored("target"));
+        assert!(!ignored("src/target"));
+        assert!(ignored("docs/build"));
+        assert!(!ignored("src/docs/build"));
+        assert!(ignored("src/debug.log"));
+        assert!(ignored("src/scratch.tmp"));
+        assert!(ignored(".git"));
+
+        // Nested `.gitignore` globs apply only under their directory
+        assert!(ignored("app/gen"));
+        assert!(ignored("app/src/gen"));
+        assert!(!ignored("gen"));
+        assert!(ignored("app/local.rs"));
+        assert!(!ignored("app/src/local.rs"));
+        assert!(!ignored("local.rs"));
+    }
+
+    #[tokio::test]
+    async fn test_nested_gitignore() {
+        let dir = TempDir::new().unwrap();
+        write(&dir, "build/out.txt", "needle\n");
+        write(&dir, "web/build/out.txt", "needle\n");
+        write(&dir, "web/.gitignore", "build/\n");
+        write(&dir, "web/src/app.txt", "needle\n");
+        write(&dir, ".gitignore", "/build\n");
+        write(&dir, "src/build/keep.txt", "needle\n");
+
+        let mut found: Vec<_> = WorkspaceSearch::new(dir.path(), "needle")
+            .start()
+            .unwrap()
+            .collect()
+            .await
+            .into_iter()
+            .map(|m| m.path.strip_prefix(dir.path()).unwrap().to_path_buf())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec![PathBuf::from("src/build/keep.txt"), PathBuf::from("web/src/app.txt")]);
+    }
+
+    #[tokio::test]
+    async fn test_cancel() {
+        let dir = TempDir::new().unwrap();
+        for i in 0..20 {
+            write(&dir, &format!("file{}.txt", i), "needle\n");
+        }
+
+        let mut handle = WorkspaceSearch::new(dir.path(), "needle")
+            .with_max_concurrency(1)
+            .start()
+            .unwrap();
+        assert!(handle.next().await.is_some());
+        handle.cancel();
+        assert!(handle.next().await.is_none());
+
+        assert!(WorkspaceSearch::new(dir.path().join("missing"), "needle").start().is_err());
+    }
+}
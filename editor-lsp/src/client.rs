@@ -1,8 +1,51 @@
-use crate::{server, LspConfig, Result};
+use crate::{apply_workspace_edit, server, spawn_did_change_feed, Error, LspConfig, LspDiagnostics, Result};
+use editor_core::Document;
 use lsp_types::*;
+use std::future::Future;
+use std::sync::atomic::{AtomicI64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
-use tower_lsp::{LspService, Server};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use futures::StreamExt;
+use tower_lsp::{jsonrpc, ClientSocket, LspService, Server};
+use tower_service::Service;
+
+/// Runs `future` until it completes or `token` is cancelled
+///
+/// On cancellation the future is dropped, aborting the in-flight work, and
+/// `Error::Cancelled` is returned so callers never apply a stale result.
+pub(crate) async fn with_cancellation<T>(
+    token: &CancellationToken,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(Error::Cancelled),
+        result = future => result,
+    }
+}
+
+/// Reads the messages a language server sends to the editor
+///
+/// Published diagnostics are stored in `diagnostics`; other messages are
+/// only logged. The task ends when the server's service is dropped.
+fn spawn_server_messages(mut socket: ClientSocket, diagnostics: Arc<LspDiagnostics>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        while let Some(message) = socket.next().await {
+            if message.method() != <notification::PublishDiagnostics as notification::Notification>::METHOD {
+                log::debug!("Language server message: {}", message.method());
+                continue;
+            }
+            let params = message.params().cloned().unwrap_or_default();
+            match serde_json::from_value::<PublishDiagnosticsParams>(params) {
+                Ok(params) => diagnostics.publish(params),
+                Err(e) => log::warn!("Invalid diagnostics from language server: {}", e),
+            }
+        }
+    })
+}
 
 /// LSP client for communicating with language servers
 #[allow(dead_code)]
@@ -15,19 +58,27 @@ pub struct LspClient {
     capabilities: Arc<Mutex<ServerCapabilities>>,
     /// Initialization status
     initialized: bool,
+    /// Id of the next request
+    next_id: AtomicI64,
+    /// Diagnostics published by the server
+    diagnostics: Arc<LspDiagnostics>,
 }
 
 impl LspClient {
     /// Creates a new LSP client
     pub async fn new(config: LspConfig) -> Result<Self> {
-        let (service, _socket) =
-            LspService::build(|client| server::LanguageServer::new(client)).finish();
+        let language_id = config.language_id.clone();
+        let (service, socket) = LspService::build(|client| {
+            server::LanguageServer::with_language(client, language_id)
+        }).finish();
 
         let service_arc = Arc::new(Mutex::new(service));
 
         // Create a new service instance for the background task
-        let (background_service, background_socket) =
-            LspService::build(|client| server::LanguageServer::new(client)).finish();
+        let language_id = config.language_id.clone();
+        let (background_service, background_socket) = LspService::build(|client| {
+            server::LanguageServer::with_language(client, language_id)
+        }).finish();
 
         // Start LSP server in background using stdio
         let stdin = tokio::io::stdin();
@@ -39,13 +90,392 @@ impl LspClient {
             log::info!("LSP server stopped");
         });
 
+        let diagnostics = Arc::new(LspDiagnostics::new(config.name.clone()));
+        spawn_server_messages(socket, diagnostics.clone());
+
         Ok(Self {
             service: service_arc,
             config,
             capabilities: Arc::new(Mutex::new(ServerCapabilities::default())),
             initialized: false,
+            next_id: AtomicI64::new(1),
+            diagnostics,
         })
     }
 
-    // ... rest of implementation stays the same...
+    /// Creates a client for an in-process language server service
+    #[cfg(test)]
+    pub(crate) fn with_service(
+        service: LspService<server::LanguageServer>,
+        socket: ClientSocket,
+        config: LspConfig,
+    ) -> Self {
+        let diagnostics = Arc::new(LspDiagnostics::new(config.name.clone()));
+        spawn_server_messages(socket, diagnostics.clone());
+        Self {
+            service: Arc::new(Mutex::new(service)),
+            config,
+            capabilities: Arc::new(Mutex::new(ServerCapabilities::default())),
+            initialized: false,
+            next_id: AtomicI64::new(1),
+            diagnostics,
+        }
+    }
+
+    /// Returns the diagnostics published by the server
+    ///
+    /// Register it with the editor's `DiagnosticRegistry` for the server's
+    /// language to show them as markers.
+    pub fn diagnostics(&self) -> &Arc<LspDiagnostics> {
+        &self.diagnostics
+    }
+
+    /// Sends a request to the language server
+    ///
+    /// Returns `Error::Cancelled` if `token` is cancelled before the response
+    /// arrives.
+    pub async fn request<R>(&self, params: R::Params, token: &CancellationToken) -> Result<R::Result>
+    where
+        R: request::Request,
+    {
+        let params = serde_json::to_value(params)
+            .map_err(|e| Error::RequestError(e.to_string()))?;
+        let request = jsonrpc::Request::build(R::METHOD)
+            .id(self.next_id.fetch_add(1, Ordering::Relaxed))
+            .params(params)
+            .finish();
+
+        with_cancellation(token, async {
+            let response = {
+                let mut service = self.service.lock().await;
+                futures::future::poll_fn(|cx| service.poll_ready(cx))
+                    .await
+                    .map_err(|e| Error::ConnectionError(e.to_string()))?;
+                service.call(request)
+            };
+
+            let response = response
+                .await
+                .map_err(|e| Error::ConnectionError(e.to_string()))?
+                .ok_or_else(|| Error::RequestError(format!("No response to {}", R::METHOD)))?;
+
+            let (_, result) = response.into_parts();
+            serde_json::from_value(result?).map_err(|e| Error::RequestError(e.to_string()))
+        }).await
+    }
+
+    /// Sends a notification to the language server
+    pub async fn notify<N>(&self, params: N::Params) -> Result<()>
+    where
+        N: notification::Notification,
+    {
+        let params = serde_json::to_value(params)
+            .map_err(|e| Error::RequestError(e.to_string()))?;
+        let notification = jsonrpc::Request::build(N::METHOD).params(params).finish();
+
+        let response = {
+            let mut service = self.service.lock().await;
+            futures::future::poll_fn(|cx| service.poll_ready(cx))
+                .await
+                .map_err(|e| Error::ConnectionError(e.to_string()))?;
+            service.call(notification)
+        };
+        response.await.map_err(|e| Error::ConnectionError(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Sends incremental changes of an open document
+    pub async fn did_change(&self, params: DidChangeTextDocumentParams) -> Result<()> {
+        self.notify::<notification::DidChangeTextDocument>(params).await
+    }
+
+    /// Opens a document on the server and keeps the server's copy in sync
+    ///
+    /// Sends `didOpen` with the document's text, then forwards its edits
+    /// through `did_change`, batched until they have been quiet for
+    /// `debounce`. The returned task ends when the document is dropped.
+    pub async fn open_document(
+        self: &Arc<Self>,
+        uri: Url,
+        doc: &Document,
+        debounce: Duration,
+    ) -> Result<JoinHandle<()>> {
+        let text = doc.text();
+        let version = doc.version() as i32;
+        self.notify::<notification::DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), self.config.language_id.clone(), version, text.clone()),
+        }).await?;
+
+        let (feed, mut changes) = spawn_did_change_feed(uri, text, version, doc.subscribe(), debounce);
+        let client = Arc::clone(self);
+        Ok(tokio::spawn(async move {
+            while let Some(params) = changes.recv().await {
+                let uri = params.text_document.uri.clone();
+                if let Err(e) = client.did_change(params).await {
+                    log::warn!("Failed to send didChange for {}: {}", uri, e);
+                }
+            }
+            let _ = feed.await;
+        }))
+    }
+
+    /// Requests completions at a position
+    pub async fn completion(
+        &self,
+        params: CompletionParams,
+        token: &CancellationToken,
+    ) -> Result<Option<CompletionResponse>> {
+        self.request::<request::Completion>(params, token).await
+    }
+
+    /// Requests completions after text was typed at a position
+    ///
+    /// Completion is only requested when `text_before_cursor` ends with one
+    /// of the language's trigger characters; otherwise `Ok(None)` is returned
+    /// without contacting the server.
+    pub async fn completion_on_type(
+        &self,
+        uri: Url,
+        position: Position,
+        text_before_cursor: &str,
+        token: &CancellationToken,
+    ) -> Result<Option<CompletionResponse>> {
+        let language_id = &self.config.language_id;
+        if !crate::should_trigger_completion(language_id, text_before_cursor) {
+            return Ok(None);
+        }
+
+        let params = CompletionParams {
+            text_document_position: TextDocumentPositionParams::new(TextDocumentIdentifier::new(uri), position),
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+            context: Some(CompletionContext {
+                trigger_kind: CompletionTriggerKind::TRIGGER_CHARACTER,
+                trigger_character: crate::completion_trigger(language_id, text_before_cursor),
+            }),
+        };
+        self.completion(params, token).await
+    }
+
+    /// Requests the code actions, such as quick fixes, for a range of a document
+    ///
+    /// Bare commands returned by the server are wrapped in actions that only
+    /// run the command.
+    pub async fn code_actions(
+        &self,
+        uri: Url,
+        range: Range,
+        context: CodeActionContext,
+        token: &CancellationToken,
+    ) -> Result<Vec<CodeAction>> {
+        let params = CodeActionParams {
+            text_document: TextDocumentIdentifier::new(uri),
+            range,
+            context,
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let response = self.request::<request::CodeActionRequest>(params, token).await?;
+
+        Ok(response
+            .unwrap_or_default()
+            .into_iter()
+            .map(|action| match action {
+                CodeActionOrCommand::CodeAction(action) => action,
+                CodeActionOrCommand::Command(command) => CodeAction {
+                    title: command.title.clone(),
+                    command: Some(command),
+                    ..CodeAction::default()
+                },
+            })
+            .collect())
+    }
+
+    /// Requests the code actions available at a byte offset of a document,
+    /// e.g. the cursor
+    pub async fn code_actions_at(
+        &self,
+        uri: Url,
+        doc: &Document,
+        offset: usize,
+        token: &CancellationToken,
+    ) -> Result<Vec<CodeAction>> {
+        let range = doc.byte_range_to_lsp(offset..offset);
+        let context = CodeActionContext {
+            trigger_kind: Some(CodeActionTriggerKind::INVOKED),
+            ..CodeActionContext::default()
+        };
+        self.code_actions(uri, range, context, token).await
+    }
+
+    /// Applies a code action to the open documents
+    ///
+    /// The action's workspace edit is applied first, then its command, if
+    /// any, is executed by the server.
+    pub async fn apply_code_action<'a>(
+        &self,
+        action: &CodeAction,
+        documents: impl IntoIterator<Item = &'a mut Document>,
+        token: &CancellationToken,
+    ) -> Result<()> {
+        if let Some(edit) = &action.edit {
+            apply_workspace_edit(edit, documents)?;
+        }
+        if let Some(command) = &action.command {
+            let params = ExecuteCommandParams {
+                command: command.command.clone(),
+                arguments: command.arguments.clone().unwrap_or_default(),
+                work_done_progress_params: Default::default(),
+            };
+            self.request::<request::ExecuteCommand>(params, token).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Creates a client for an in-process server that has been initialized
+    async fn initialized_client() -> LspClient {
+        let config = LspConfig::new("test", "rust", "test", Url::parse("file:///").unwrap());
+        let (service, socket) = LspService::build(|client| {
+            server::LanguageServer::with_language(client, "rust")
+        }).finish();
+        let client = LspClient::with_service(service, socket, config);
+        client.request::<request::Initialize>(InitializeParams::default(), &CancellationToken::new()).await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_is_not_applied() {
+        let client = initialized_client().await;
+        let uri = Url::parse("file:///main.rs").unwrap();
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {   \n}\n").unwrap();
+        client.notify::<notification::DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "rust".to_string(), 1, doc.text()),
+        }).await.unwrap();
+
+        // Keep the request waiting for the server until it is cancelled
+        let busy = client.service.lock().await;
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cancel.cancel();
+        });
+        let result = client.code_actions_at(uri.clone(), &doc, 5, &token).await;
+        assert!(matches!(result, Err(Error::Cancelled)));
+        drop(busy);
+
+        // The cancelled request left the document unchanged
+        assert_eq!(doc.text(), "fn main() {   \n}\n");
+
+        // The same request completes once it is not cancelled
+        let actions = client.code_actions_at(uri, &doc, 5, &CancellationToken::new()).await.unwrap();
+        assert_eq!(actions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_completion_on_trigger_character() {
+        crate::config::register_default_servers().unwrap();
+        let client = initialized_client().await;
+        let token = CancellationToken::new();
+        let uri = Url::parse("file:///lib.rs").unwrap();
+        client.notify::<notification::DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "rust".to_string(), 1, "foo.".to_string()),
+        }).await.unwrap();
+
+        let response = client.completion_on_type(uri.clone(), Position::new(0, 4), "foo.", &token).await.unwrap();
+        assert!(matches!(response, Some(CompletionResponse::Array(items)) if items.len() == 2));
+
+        // Typing without a trigger character doesn't request completion
+        let response = client.completion_on_type(uri, Position::new(0, 3), "foo", &token).await.unwrap();
+        assert!(response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_quick_fix_applied() {
+        let client = initialized_client().await;
+        let token = CancellationToken::new();
+
+        let dir = std::env::temp_dir().join("editor-lsp-quick-fix");
+        let path = dir.join("main.rs");
+        let uri = Url::from_file_path(&path).unwrap();
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {   \n}\n").unwrap();
+        client.notify::<notification::DidOpenTextDocument>(DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(uri.clone(), "rust".to_string(), 1, doc.text()),
+        }).await.unwrap();
+
+        let actions = client.code_actions_at(uri, &doc, 5, &token).await.unwrap();
+        assert_eq!(actions.len(), 1);
+        assert_eq!(actions[0].kind, Some(CodeActionKind::QUICKFIX));
+
+        // Edits are matched to open documents by path
+        let mut other = Document::new("other.rs");
+        assert!(client.apply_code_action(&actions[0], [&mut other], &token).await.is_err());
+
+        let fs = Arc::new(editor_core::MemoryFs::new().with_file(&path, doc.text()));
+        let mut doc = Document::from_file_in(fs, &path).unwrap();
+        client.apply_code_action(&actions[0], [&mut doc], &token).await.unwrap();
+        assert_eq!(doc.text(), "fn main() {\n}\n");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_open_document_keeps_server_in_sync() {
+        let client = Arc::new(initialized_client().await);
+        let token = CancellationToken::new();
+
+        let path = std::env::temp_dir().join("editor-lsp-sync").join("main.rs");
+        let uri = Url::from_file_path(&path).unwrap();
+        let fs = Arc::new(editor_core::MemoryFs::new().with_file(&path, "// old mac\rfn main() {}\n"));
+        let mut doc = Document::from_file_in(fs, &path).unwrap();
+        let feed = client.open_document(uri.clone(), &doc, Duration::from_millis(50)).await.unwrap();
+        assert!(client.code_actions_at(uri.clone(), &doc, 12, &token).await.unwrap().is_empty());
+
+        // The server only offers the fix once it has seen the edit, on the
+        // line after the lone '\r'
+        let end = doc.text().len() - 1;
+        doc.insert(end, "  ").unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let actions = client.code_actions_at(uri.clone(), &doc, 12, &token).await.unwrap();
+        assert_eq!(actions.len(), 1);
+        client.apply_code_action(&actions[0], [&mut doc], &token).await.unwrap();
+        assert_eq!(doc.text(), "// old mac\rfn main() {}\n");
+
+        drop(doc);
+        feed.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_published_diagnostics_reach_the_registry() {
+        let client = Arc::new(initialized_client().await);
+        let registry = editor_core::DiagnosticRegistry::new();
+        registry.register(Some("rust"), client.diagnostics().clone());
+
+        let path = std::env::temp_dir().join("editor-lsp-diagnostics").join("main.rs");
+        let uri = Url::from_file_path(&path).unwrap();
+        let fs = Arc::new(editor_core::MemoryFs::new().with_file(&path, "fn main() {}  \n"));
+        let mut doc = Document::from_file_in(fs, &path).unwrap();
+        doc.set_language(Some("rust".to_string()));
+        let feed = client.open_document(uri, &doc, Duration::from_millis(10)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        registry.apply(&mut doc);
+        let markers = doc.markers().diagnostics();
+        assert_eq!(markers.len(), 1);
+        assert_eq!(markers[0].position, 12);
+
+        // Fixing the line clears the server's diagnostic
+        doc.delete(12, 14).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        registry.apply(&mut doc);
+        assert!(doc.markers().diagnostics().is_empty());
+
+        drop(doc);
+        feed.await.unwrap();
+    }
 }
@@ -0,0 +1,185 @@
+//! Filtering and ordering of completion lists returned by language servers
+
+use lsp_types::{CompletionItem, CompletionItemLabelDetails, CompletionResponse};
+use std::collections::HashSet;
+
+/// Returns the word being typed at the end of `text_before_cursor`
+///
+/// Completions are filtered against this prefix. It is empty right after a
+/// trigger character such as `.`.
+pub fn completion_prefix(text_before_cursor: &str) -> &str {
+    let start = text_before_cursor
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+        .last()
+        .map_or(text_before_cursor.len(), |(index, _)| index);
+    &text_before_cursor[start..]
+}
+
+/// Returns the items of a completion response that match the typed prefix,
+/// in the order they should be shown
+///
+/// Items match when the prefix is a case-insensitive subsequence of their
+/// `filterText`, or of their label if they have none. Matches are sorted by
+/// `sortText`, falling back to the label, then by label; the sort is stable,
+/// so the server's order breaks remaining ties. Nothing is shown while the
+/// prefix is shorter than `min_word_length`; pass 0 when completion was
+/// triggered by a trigger character.
+pub fn filter_completions(response: CompletionResponse, prefix: &str, min_word_length: usize) -> Vec<CompletionItem> {
+    if prefix.chars().count() < min_word_length {
+        return Vec::new();
+    }
+
+    let items = match response {
+        CompletionResponse::Array(items) => items,
+        CompletionResponse::List(list) => list.items,
+    };
+    let mut items: Vec<_> = items
+        .into_iter()
+        .filter(|item| fuzzy_match(item.filter_text.as_deref().unwrap_or(&item.label), prefix))
+        .collect();
+    items.sort_by(|a, b| {
+        let key = |item: &CompletionItem| item.sort_text.clone().unwrap_or_else(|| item.label.clone());
+        key(a).cmp(&key(b)).then_with(|| a.label.cmp(&b.label))
+    });
+    items
+}
+
+/// Adds the items offered by plugins to a language server's response
+///
+/// Plugin items come after the server's, and an item whose label is already
+/// in the list is dropped, so the server's version of a completion wins. The
+/// plugin that offered an item is shown as its label description.
+pub fn merge_completions(response: CompletionResponse, plugin_items: Vec<editor_core::CompletionItem>) -> CompletionResponse {
+    let (mut items, is_incomplete) = match response {
+        CompletionResponse::Array(items) => (items, None),
+        CompletionResponse::List(list) => (list.items, Some(list.is_incomplete)),
+    };
+    let mut labels: HashSet<String> = items.iter().map(|item| item.label.clone()).collect();
+    for item in plugin_items {
+        if labels.insert(item.label.clone()) {
+            items.push(from_plugin_item(item));
+        }
+    }
+
+    match is_incomplete {
+        Some(is_incomplete) => CompletionResponse::List(lsp_types::CompletionList { is_incomplete, items }),
+        None => CompletionResponse::Array(items),
+    }
+}
+
+/// Converts an item offered by a plugin to a language server item
+fn from_plugin_item(item: editor_core::CompletionItem) -> CompletionItem {
+    CompletionItem {
+        label: item.label,
+        detail: item.detail,
+        insert_text: item.insert_text,
+        label_details: item.source.map(|source| CompletionItemLabelDetails {
+            detail: None,
+            description: Some(source),
+        }),
+        ..Default::default()
+    }
+}
+
+/// Returns true if the characters of `pattern` appear in order in `text`,
+/// ignoring case
+fn fuzzy_match(text: &str, pattern: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .all(|p| text.any(|c| c == p))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::CompletionList;
+
+    fn item(label: &str, sort_text: Option<&str>) -> CompletionItem {
+        CompletionItem {
+            label: label.to_string(),
+            sort_text: sort_text.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items.iter().map(|item| item.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_filter_by_prefix() {
+        assert_eq!(completion_prefix("let x = self.push_"), "push_");
+        assert_eq!(completion_prefix("foo."), "");
+
+        let mut filtered = item("into_iter", None);
+        filtered.filter_text = Some("iter_into".to_string());
+        let response = CompletionResponse::Array(vec![
+            item("push_str", None),
+            item("pop", None),
+            item("as_ptr", None),
+            item("extend_from_slice", None),
+            filtered,
+        ]);
+
+        let items = filter_completions(response.clone(), "pst", 2);
+        assert_eq!(labels(&items), vec!["push_str"]);
+        let items = filter_completions(response.clone(), "P", 1);
+        assert_eq!(labels(&items), vec!["as_ptr", "pop", "push_str"]);
+        let items = filter_completions(response.clone(), "iti", 2);
+        assert_eq!(labels(&items), vec!["into_iter"]);
+
+        // Too short to show, unless triggered
+        assert!(filter_completions(response.clone(), "p", 2).is_empty());
+        assert_eq!(filter_completions(response, "", 0).len(), 5);
+    }
+
+    #[test]
+    fn test_sort_text_order() {
+        let response = CompletionResponse::List(CompletionList {
+            is_incomplete: false,
+            items: vec![
+                item("len", Some("2")),
+                item("lines", Some("1")),
+                item("last", None),
+                item("leak", Some("1")),
+                item("lock", Some("2")),
+            ],
+        });
+
+        let items = filter_completions(response, "l", 1);
+        assert_eq!(labels(&items), vec!["leak", "lines", "len", "lock", "last"]);
+    }
+
+    #[test]
+    fn test_merge_plugin_completions() {
+        let response = CompletionResponse::List(CompletionList {
+            is_incomplete: true,
+            items: vec![item("println", None), item("print", None)],
+        });
+        let mut snippet = editor_core::CompletionItem::new("println").with_insert_text("println!(\"{}\");");
+        snippet.source = Some("snippets".to_string());
+        let mut word = editor_core::CompletionItem::new("printable").with_detail("dictionary word");
+        word.source = Some("dictionary".to_string());
+
+        let merged = merge_completions(response, vec![snippet, word.clone(), word]);
+        let CompletionResponse::List(list) = &merged else {
+            panic!("expected a list, got {:?}", merged);
+        };
+        assert!(list.is_incomplete);
+        assert_eq!(labels(&list.items), vec!["println", "print", "printable"]);
+        // The server's item wins over a plugin's with the same label
+        assert_eq!(list.items[0].insert_text, None);
+        assert_eq!(list.items[2].detail.as_deref(), Some("dictionary word"));
+        assert_eq!(
+            list.items[2].label_details.as_ref().and_then(|details| details.description.as_deref()),
+            Some("dictionary")
+        );
+
+        let items = filter_completions(merged, "prt", 2);
+        assert_eq!(labels(&items), vec!["print", "printable", "println"]);
+    }
+}
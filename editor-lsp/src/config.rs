@@ -9,6 +9,7 @@ use crate::Result;
 
 lazy_static::lazy_static! {
     static ref SERVERS: Arc<RwLock<HashMap<String, LspConfig>>> = Arc::new(RwLock::new(HashMap::new()));
+    static ref TRIGGER_OVERRIDES: Arc<RwLock<HashMap<String, Vec<String>>>> = Arc::new(RwLock::new(HashMap::new()));
 }
 
 /// LSP server configuration
@@ -24,6 +25,9 @@ pub struct LspConfig {
     pub root_uri: Url,
     /// Server-specific initialization options
     pub initialization_options: Option<serde_json::Value>,
+    /// Characters that trigger completion for this language
+    #[serde(default)]
+    pub trigger_characters: Vec<String>,
 }
 
 impl LspConfig {
@@ -40,6 +44,7 @@ impl LspConfig {
             command: command.into(),
             root_uri,
             initialization_options: None,
+            trigger_characters: Vec::new(),
         }
     }
 
@@ -48,6 +53,16 @@ impl LspConfig {
         self.initialization_options = Some(options);
         self
     }
+
+    /// Sets the characters that trigger completion
+    pub fn with_trigger_characters<I, S>(mut self, characters: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.trigger_characters = characters.into_iter().map(Into::into).collect();
+        self
+    }
 }
 
 /// Registers a language server configuration
@@ -61,6 +76,50 @@ pub fn get_server(language_id: &str) -> Option<LspConfig> {
     SERVERS.read().get(language_id).cloned()
 }
 
+/// Gets the language IDs with a registered server
+pub fn registered_languages() -> Vec<String> {
+    SERVERS.read().keys().cloned().collect()
+}
+
+/// Overrides the completion trigger characters for a language
+///
+/// The override replaces the characters of the language's server, and also
+/// applies to languages without a registered server.
+pub fn set_trigger_characters<I, S>(language_id: &str, characters: I)
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    let characters = characters.into_iter().map(Into::into).collect();
+    TRIGGER_OVERRIDES.write().insert(language_id.to_string(), characters);
+}
+
+/// Gets the completion trigger characters for a language
+///
+/// Returns an empty list if neither an override nor a server is registered
+/// for the language.
+pub fn trigger_characters(language_id: &str) -> Vec<String> {
+    if let Some(characters) = TRIGGER_OVERRIDES.read().get(language_id) {
+        return characters.clone();
+    }
+    SERVERS.read()
+        .get(language_id)
+        .map(|config| config.trigger_characters.clone())
+        .unwrap_or_default()
+}
+
+/// Returns the trigger character `text_before_cursor` ends with, if any
+pub fn completion_trigger(language_id: &str, text_before_cursor: &str) -> Option<String> {
+    trigger_characters(language_id)
+        .into_iter()
+        .find(|trigger| text_before_cursor.ends_with(trigger.as_str()))
+}
+
+/// Returns true if the text typed before the cursor should trigger completion
+pub fn should_trigger_completion(language_id: &str, text_before_cursor: &str) -> bool {
+    completion_trigger(language_id, text_before_cursor).is_some()
+}
+
 /// Registers default language server configurations
 pub fn register_default_servers() -> Result<()> {
     // Example root URI - in practice, this would be set per-project
@@ -76,7 +135,7 @@ pub fn register_default_servers() -> Result<()> {
         "checkOnSave": {
             "command": "clippy"
         }
-    })));
+    })).with_trigger_characters([".", "::"]));
 
     // Python
     register_server(LspConfig::new(
@@ -84,7 +143,7 @@ pub fn register_default_servers() -> Result<()> {
         "python",
         "pylsp",
         root_uri.clone(),
-    ));
+    ).with_trigger_characters(["."]));
 
     // JavaScript/TypeScript
     register_server(LspConfig::new(
@@ -92,14 +151,14 @@ pub fn register_default_servers() -> Result<()> {
         "typescript",
         "typescript-language-server --stdio",
         root_uri.clone(),
-    ));
+    ).with_trigger_characters([".", "/", "\"", "'"]));
 
     register_server(LspConfig::new(
         "typescript-language-server",
         "javascript",
         "typescript-language-server --stdio",
         root_uri.clone(),
-    ));
+    ).with_trigger_characters([".", "/", "\"", "'"]));
 
     Ok(())
 }
@@ -134,4 +193,41 @@ mod tests {
         assert!(get_server("typescript").is_some());
         assert!(get_server("javascript").is_some());
     }
+
+    #[test]
+    fn test_trigger_characters_per_language() {
+        register_default_servers().unwrap();
+        assert_eq!(trigger_characters("rust"), vec![".", "::"]);
+        assert!(should_trigger_completion("rust", "let x = foo."));
+        assert!(should_trigger_completion("rust", "std::"));
+        assert!(!should_trigger_completion("rust", "let x = foo"));
+
+        // A language without trigger characters never triggers
+        let root_uri = Url::from_file_path("/tmp").unwrap();
+        register_server(LspConfig::new("plain-server", "plaintext", "plain", root_uri));
+        assert!(trigger_characters("plaintext").is_empty());
+        assert!(!should_trigger_completion("plaintext", "foo."));
+
+        // Neither does an unknown language
+        assert!(!should_trigger_completion("unknown", "foo."));
+    }
+
+    #[test]
+    fn test_trigger_character_override() {
+        let root_uri = Url::from_file_path("/tmp").unwrap();
+        register_server(LspConfig::new("markup-server", "markup", "markup", root_uri)
+            .with_trigger_characters(["<"]));
+        assert!(registered_languages().contains(&"markup".to_string()));
+        assert_eq!(completion_trigger("markup", "<"), Some("<".to_string()));
+
+        // The override replaces the server's characters
+        set_trigger_characters("markup", ["</", "@"]);
+        assert_eq!(trigger_characters("markup"), vec!["</", "@"]);
+        assert_eq!(completion_trigger("markup", "foo</"), Some("</".to_string()));
+        assert!(!should_trigger_completion("markup", "<"));
+
+        // and applies to languages without a server
+        set_trigger_characters("notes", ["#"]);
+        assert!(should_trigger_completion("notes", "see #"));
+    }
 }
@@ -0,0 +1,135 @@
+//! Diagnostics published by language servers
+
+use editor_core::{Diagnostic, DiagnosticProvider, DiagnosticSeverity, Document};
+use lsp_types::{PublishDiagnosticsParams, Url};
+use parking_lot::RwLock;
+use std::collections::HashMap;
+
+/// Diagnostics from `textDocument/publishDiagnostics`, as a provider
+///
+/// `LspClient` keeps one fed with every notification its server publishes;
+/// register it with the editor's `DiagnosticRegistry` for the server's
+/// language.
+#[derive(Debug)]
+pub struct LspDiagnostics {
+    /// Provider name, usually the language server's
+    name: String,
+    /// Latest diagnostics by document
+    published: RwLock<HashMap<Url, Vec<lsp_types::Diagnostic>>>,
+}
+
+impl LspDiagnostics {
+    /// Creates an empty provider
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            published: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Stores diagnostics published for a document, replacing earlier ones
+    pub fn publish(&self, params: PublishDiagnosticsParams) {
+        let mut published = self.published.write();
+        if params.diagnostics.is_empty() {
+            published.remove(&params.uri);
+        } else {
+            published.insert(params.uri, params.diagnostics);
+        }
+    }
+
+    /// Forgets the diagnostics of all documents, e.g. when the server exits
+    pub fn clear(&self) {
+        self.published.write().clear();
+    }
+}
+
+impl DiagnosticProvider for LspDiagnostics {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn diagnostics(&self, doc: &Document) -> Vec<Diagnostic> {
+        let Some(uri) = doc.path().and_then(|path| Url::from_file_path(path).ok()) else {
+            return Vec::new();
+        };
+        let published = self.published.read();
+        let Some(diagnostics) = published.get(&uri) else {
+            return Vec::new();
+        };
+
+        // Diagnostics starting or ending inside a character are dropped
+        diagnostics
+            .iter()
+            .filter_map(|diagnostic| {
+                let range = doc.lsp_range_to_byte(diagnostic.range)?;
+                Some(Diagnostic::new(range, severity(diagnostic.severity), diagnostic.message.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Maps an LSP severity, treating a missing one as an error as servers do
+fn severity(severity: Option<lsp_types::DiagnosticSeverity>) -> DiagnosticSeverity {
+    match severity {
+        Some(lsp_types::DiagnosticSeverity::WARNING) => DiagnosticSeverity::Warning,
+        Some(lsp_types::DiagnosticSeverity::INFORMATION) => DiagnosticSeverity::Info,
+        Some(lsp_types::DiagnosticSeverity::HINT) => DiagnosticSeverity::Hint,
+        _ => DiagnosticSeverity::Error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::{DiagnosticRegistry, MarkerType, MemoryFs};
+    use lsp_types::{Position, Range};
+    use std::sync::Arc;
+
+    #[test]
+    fn test_published_diagnostics_become_markers() {
+        let fs = Arc::new(MemoryFs::new().with_file("/src/main.rs", "fn main() {\n    let x = 1;\n}\n"));
+        let path = std::path::Path::new("/src/main.rs");
+        let mut doc = Document::from_file_in(fs, path).unwrap();
+
+        let lsp = Arc::new(LspDiagnostics::new("rust-analyzer"));
+        let registry = DiagnosticRegistry::new();
+        registry.register(None, lsp.clone());
+
+        let uri = Url::from_file_path(path).unwrap();
+        lsp.publish(PublishDiagnosticsParams::new(
+            uri.clone(),
+            vec![
+                lsp_types::Diagnostic {
+                    range: Range::new(Position::new(1, 8), Position::new(1, 9)),
+                    severity: Some(lsp_types::DiagnosticSeverity::WARNING),
+                    message: "unused variable: `x`".to_string(),
+                    ..Default::default()
+                },
+                // Published for an older version with more lines, so clamped
+                // to the end
+                lsp_types::Diagnostic {
+                    range: Range::new(Position::new(7, 0), Position::new(7, 1)),
+                    message: "expected item".to_string(),
+                    ..Default::default()
+                },
+            ],
+            None,
+        ));
+        registry.apply(&mut doc);
+
+        let mut markers = doc.markers().diagnostics();
+        markers.sort_by_key(|marker| marker.position);
+        assert_eq!(markers.len(), 2);
+        assert_eq!(markers[0].position, 20);
+        assert_eq!(markers[1].position, doc.text().len());
+        assert_eq!(markers[0].marker_type, MarkerType::Diagnostic {
+            severity: DiagnosticSeverity::Warning,
+            source: "rust-analyzer".to_string(),
+        });
+
+        // An empty publish clears the document's diagnostics
+        lsp.publish(PublishDiagnosticsParams::new(uri, Vec::new(), None));
+        registry.apply(&mut doc);
+        assert!(doc.markers().diagnostics().is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+//! Applying text and workspace edits from language servers to documents
+
+use crate::{Error, Result};
+use editor_core::Document;
+use lsp_types::{DocumentChangeOperation, DocumentChanges, OneOf, TextEdit, Url, WorkspaceEdit};
+
+/// Applies LSP text edits to a document as a single undo step
+///
+/// Edit ranges refer to the document before any of them is applied, as the
+/// protocol requires. Nothing is applied if a range starts or ends inside a
+/// character.
+pub fn apply_text_edits(doc: &mut Document, edits: &[TextEdit]) -> Result<()> {
+    let edits = edits
+        .iter()
+        .map(|edit| {
+            let range = doc.lsp_range_to_byte(edit.range).ok_or_else(|| {
+                Error::RequestError(format!("Edit range {:?} is not in {}", edit.range, doc.name()))
+            })?;
+            Ok((range, edit.new_text.clone()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    doc.apply_edits(&edits)?;
+    Ok(())
+}
+
+/// Applies a workspace edit to the open documents it touches
+///
+/// Documents are matched to edit URIs by their file path. Returns the number
+/// of documents changed; edits for documents that are not open, and file
+/// operations such as renames, are reported as errors without applying
+/// anything.
+pub fn apply_workspace_edit<'a>(
+    edit: &WorkspaceEdit,
+    documents: impl IntoIterator<Item = &'a mut Document>,
+) -> Result<usize> {
+    let mut changes: Vec<(&Url, Vec<TextEdit>)> = Vec::new();
+    if let Some(document_changes) = &edit.document_changes {
+        let edits = match document_changes {
+            DocumentChanges::Edits(edits) => edits.iter().collect::<Vec<_>>(),
+            DocumentChanges::Operations(operations) => operations
+                .iter()
+                .map(|operation| match operation {
+                    DocumentChangeOperation::Edit(edit) => Ok(edit),
+                    DocumentChangeOperation::Op(_) => Err(Error::RequestError(
+                        "File operations in workspace edits are not supported".to_string(),
+                    )),
+                })
+                .collect::<Result<Vec<_>>>()?,
+        };
+        for edit in edits {
+            let text_edits = edit
+                .edits
+                .iter()
+                .map(|edit| match edit {
+                    OneOf::Left(edit) => edit.clone(),
+                    OneOf::Right(annotated) => annotated.text_edit.clone(),
+                })
+                .collect();
+            changes.push((&edit.text_document.uri, text_edits));
+        }
+    } else if let Some(edits) = &edit.changes {
+        changes.extend(edits.iter().map(|(uri, edits)| (uri, edits.clone())));
+    }
+
+    let mut documents: Vec<&mut Document> = documents.into_iter().collect();
+    let mut targets = Vec::with_capacity(changes.len());
+    for (uri, _) in &changes {
+        let index = documents
+            .iter()
+            .position(|doc| doc.path().and_then(|path| Url::from_file_path(path).ok()).as_ref() == Some(*uri))
+            .ok_or_else(|| Error::RequestError(format!("Edited document is not open: {}", uri)))?;
+        targets.push(index);
+    }
+
+    for ((_, edits), index) in changes.iter().zip(targets) {
+        apply_text_edits(documents[index], edits)?;
+    }
+    Ok(changes.len())
+}
@@ -3,12 +3,25 @@
 //! Provides LSP client implementation for code intelligence features
 
 mod client;
+mod completion;
 mod config;
+mod diagnostics;
+mod edits;
 mod server;
+mod sync;
 mod types;
 
 pub use client::LspClient;
-pub use config::LspConfig;
+pub use completion::{completion_prefix, filter_completions, merge_completions};
+pub use config::{
+    LspConfig, completion_trigger, registered_languages, set_trigger_characters, should_trigger_completion,
+    trigger_characters,
+};
+pub use diagnostics::LspDiagnostics;
+pub use edits::{apply_text_edits, apply_workspace_edit};
+pub use sync::{
+    ChangeBatcher, DEFAULT_DID_CHANGE_DEBOUNCE, offset_to_position, position_to_offset, spawn_did_change_feed,
+};
 pub use types::{Error, LspError, Result};
 pub use types::{
     CompletionItem,
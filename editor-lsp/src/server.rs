@@ -1,6 +1,7 @@
 //! LSP server implementation
 
 use parking_lot::RwLock;
+use editor_core::LineIndex;
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::Mutex;
@@ -16,6 +17,8 @@ pub struct LanguageServer {
     documents: Arc<RwLock<HashMap<Url, DocumentState>>>,
     /// Server state
     state: Arc<Mutex<ServerState>>,
+    /// Language of the active document, used to pick completion triggers
+    language_id: Option<String>,
 }
 
 /// Document state
@@ -42,10 +45,109 @@ impl LanguageServer {
             client,
             documents: Arc::new(RwLock::new(HashMap::new())),
             state: Arc::new(Mutex::new(ServerState { root_uri: None })),
+            language_id: None,
+        }
+    }
+
+    /// Creates a new language server for the given language
+    pub fn with_language(client: Client, language_id: impl Into<String>) -> Self {
+        Self {
+            language_id: Some(language_id.into()),
+            ..Self::new(client)
         }
     }
 }
 
+/// Applies an incremental or full content change to a document's text
+///
+/// Changes with a range outside the text are ignored.
+fn apply_content_change(content: &mut String, change: &TextDocumentContentChangeEvent) {
+    let Some(range) = change.range else {
+        *content = change.text.clone();
+        return;
+    };
+    let index = LineIndex::for_lsp(content);
+    let offset = |position: Position| {
+        index.utf16_offset(content, position.line as usize, position.character as usize)
+    };
+    match (offset(range.start), offset(range.end)) {
+        (Some(start), Some(end)) if start <= end => content.replace_range(start..end, &change.text),
+        _ => log::warn!("Ignoring change outside the document: {:?}", range),
+    }
+}
+
+/// Returns a quick fix removing trailing whitespace from the lines of `range`
+fn trailing_whitespace_fix(uri: &Url, content: &str, range: Range) -> Option<CodeAction> {
+    let index = LineIndex::for_lsp(content);
+    let mut edits = Vec::new();
+    for line in range.start.line as usize..=range.end.line as usize {
+        let Some(line_range) = index.line_range(line) else {
+            break;
+        };
+        let body = content[line_range.clone()].trim_end_matches(['\n', '\r']);
+        let trimmed = body.trim_end_matches([' ', '\t']);
+        if trimmed.len() < body.len() {
+            edits.push(TextEdit::new(
+                Range::new(
+                    index.lsp_position(content, line_range.start + trimmed.len()),
+                    index.lsp_position(content, line_range.start + body.len()),
+                ),
+                String::new(),
+            ));
+        }
+    }
+    if edits.is_empty() {
+        return None;
+    }
+
+    Some(CodeAction {
+        title: "Remove trailing whitespace".to_string(),
+        kind: Some(CodeActionKind::QUICKFIX),
+        edit: Some(WorkspaceEdit::new(HashMap::from([(uri.clone(), edits)]))),
+        ..CodeAction::default()
+    })
+}
+
+/// Returns a warning for each line of `content` ending in whitespace, which
+/// the trailing whitespace quick fix removes
+fn trailing_whitespace_diagnostics(content: &str) -> Vec<Diagnostic> {
+    let index = LineIndex::for_lsp(content);
+    (0..)
+        .map_while(|line| index.line_range(line))
+        .filter_map(|line_range| {
+            let body = content[line_range.clone()].trim_end_matches(['\n', '\r']);
+            let trimmed = body.trim_end_matches([' ', '\t']);
+            (trimmed.len() < body.len()).then(|| Diagnostic {
+                range: Range::new(
+                    index.lsp_position(content, line_range.start + trimmed.len()),
+                    index.lsp_position(content, line_range.start + body.len()),
+                ),
+                severity: Some(DiagnosticSeverity::WARNING),
+                message: "Trailing whitespace".to_string(),
+                ..Diagnostic::default()
+            })
+        })
+        .collect()
+}
+
+/// Returns the completion options advertised for a language
+///
+/// Without a language, the Rust defaults are used.
+fn completion_options(language_id: Option<&str>) -> CompletionOptions {
+    let trigger_characters = match language_id {
+        Some(language_id) => crate::config::trigger_characters(language_id),
+        None => vec![".".to_string(), "::".to_string()],
+    };
+
+    CompletionOptions {
+        resolve_provider: Some(true),
+        trigger_characters: Some(trigger_characters).filter(|chars| !chars.is_empty()),
+        work_done_progress_options: Default::default(),
+        all_commit_characters: None,
+        completion_item: None,
+    }
+}
+
 #[tower_lsp::async_trait]
 impl LspServer for LanguageServer {
     async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
@@ -70,13 +172,7 @@ impl LspServer for LanguageServer {
                 text_document_sync: Some(TextDocumentSyncCapability::Kind(
                     TextDocumentSyncKind::INCREMENTAL,
                 )),
-                completion_provider: Some(CompletionOptions {
-                    resolve_provider: Some(true),
-                    trigger_characters: Some(vec![".".to_string(), "::".to_string()]),
-                    work_done_progress_options: Default::default(),
-                    all_commit_characters: None,
-                    completion_item: None,
-                }),
+                completion_provider: Some(completion_options(self.language_id.as_deref())),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
                 signature_help_provider: Some(SignatureHelpOptions {
                     trigger_characters: Some(vec!["(".to_string(), ",".to_string()]),
@@ -117,8 +213,10 @@ impl LspServer for LanguageServer {
     }
 
     async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let diagnostics = trailing_whitespace_diagnostics(&params.text_document.text);
+        let version = params.text_document.version;
         let document = DocumentState {
-            version: params.text_document.version,
+            version,
             content: params.text_document.text,
             language_id: params.text_document.language_id,
         };
@@ -134,13 +232,14 @@ impl LspServer for LanguageServer {
         self.client
             .log_message(MessageType::INFO, format!("Document opened: {}", uri))
             .await;
+        self.client.publish_diagnostics(uri, diagnostics, Some(version)).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
         let uri = params.text_document.uri.clone();
         let version = params.text_document.version;
         let changes = params.content_changes;
-        let mut changed = false;
+        let mut diagnostics = None;
 
         // Scope the lock to drop it before await
         {
@@ -150,19 +249,22 @@ impl LspServer for LanguageServer {
                 // Update version
                 doc_state.version = version;
 
-                // Apply changes - simplified to always use the last change text
-                if let Some(last_change) = changes.last() {
-                    doc_state.content = last_change.text.clone();
-                    changed = true;
+                // Apply changes in order, each against the text left by the previous one
+                for change in &changes {
+                    apply_content_change(&mut doc_state.content, change);
+                }
+                if !changes.is_empty() {
+                    diagnostics = Some(trailing_whitespace_diagnostics(&doc_state.content));
                 }
             }
         }
 
         // Now we can await without holding the lock
-        if changed {
+        if let Some(diagnostics) = diagnostics {
             self.client
                 .log_message(MessageType::INFO, format!("Document changed: {}", uri))
                 .await;
+            self.client.publish_diagnostics(uri, diagnostics, Some(version)).await;
         } else {
             self.client
                 .log_message(
@@ -314,6 +416,20 @@ impl LspServer for LanguageServer {
         Ok(None)
     }
 
+    async fn code_action(&self, params: CodeActionParams) -> Result<Option<CodeActionResponse>> {
+        let uri = params.text_document.uri;
+        let documents = self.documents.read();
+        let Some(doc) = documents.get(&uri) else {
+            return Ok(None);
+        };
+
+        let actions = trailing_whitespace_fix(&uri, &doc.content, params.range)
+            .map(CodeActionOrCommand::CodeAction)
+            .into_iter()
+            .collect();
+        Ok(Some(actions))
+    }
+
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
@@ -346,3 +462,55 @@ impl LspServer for LanguageServer {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_completion_options_follow_language() {
+        crate::config::register_default_servers().unwrap();
+
+        let rust = completion_options(Some("rust"));
+        assert_eq!(rust.trigger_characters, Some(vec![".".to_string(), "::".to_string()]));
+
+        let unknown = completion_options(Some("unknown"));
+        assert_eq!(unknown.trigger_characters, None);
+    }
+
+    #[test]
+    fn test_incremental_changes_applied() {
+        let mut content = "a\rfn main() {}\n".to_string();
+        let change = |range: Option<Range>, text: &str| TextDocumentContentChangeEvent {
+            range,
+            range_length: None,
+            text: text.to_string(),
+        };
+
+        // Lines end at a lone '\r' too
+        let at = Position::new(1, 12);
+        apply_content_change(&mut content, &change(Some(Range::new(at, at)), "  "));
+        assert_eq!(content, "a\rfn main() {}  \n");
+
+        let range = Range::new(Position::new(0, 0), Position::new(1, 3));
+        apply_content_change(&mut content, &change(Some(range), ""));
+        assert_eq!(content, "main() {}  \n");
+
+        // Edits past the end go at the end, full changes replace the text
+        let range = Range::new(Position::new(5, 0), Position::new(5, 0));
+        apply_content_change(&mut content, &change(Some(range), "x"));
+        assert_eq!(content, "main() {}  \nx");
+        apply_content_change(&mut content, &change(None, "fn"));
+        assert_eq!(content, "fn");
+    }
+
+    #[test]
+    fn test_trailing_whitespace_diagnostics() {
+        let diagnostics = trailing_whitespace_diagnostics("fn main() {  \r\n}\t");
+        let ranges: Vec<_> = diagnostics.iter().map(|d| d.range).collect();
+        assert_eq!(ranges, vec![
+            Range::new(Position::new(0, 11), Position::new(0, 13)),
+            Range::new(Position::new(1, 1), Position::new(1, 2)),
+        ]);
+    }
+}
@@ -0,0 +1,268 @@
+//! Forwarding document edits to language servers as `textDocument/didChange`
+
+use editor_core::{BufferEvent, Event, LineIndex};
+use lsp_types::{
+    DidChangeTextDocumentParams, Position, Range, TextDocumentContentChangeEvent, Url,
+    VersionedTextDocumentIdentifier,
+};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinHandle;
+
+/// Default quiet period before batched edits are sent
+pub const DEFAULT_DID_CHANGE_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Converts a byte offset into an LSP position with a UTF-16 character offset
+pub fn offset_to_position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let character = before[line_start..].encode_utf16().count();
+
+    Position::new(line as u32, character as u32)
+}
+
+/// Converts an LSP position with a UTF-16 character offset into a byte offset
+///
+/// Positions past the end of a line or of the text are clamped to it.
+pub fn position_to_offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(end) => line_start += end + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line_end = text[line_start..].find('\n').map_or(text.len(), |end| line_start + end);
+    let mut units = 0;
+    for (index, ch) in text[line_start..line_end].char_indices() {
+        if units >= position.character as usize {
+            return line_start + index;
+        }
+        units += ch.len_utf16();
+    }
+    line_end
+}
+
+/// Collects buffer edits as incremental LSP content changes
+///
+/// Keeps a copy of the text so each change's range is computed against the
+/// document as it was when that edit was applied, as the protocol requires.
+#[derive(Debug, Clone)]
+pub struct ChangeBatcher {
+    /// Document text after all pushed edits
+    text: String,
+    /// Changes not yet sent
+    changes: Vec<TextDocumentContentChangeEvent>,
+}
+
+impl ChangeBatcher {
+    /// Creates a batcher for a document with the given text
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Returns the document text after all pushed edits
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns true if there are no pending changes
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Records a buffer edit, ignoring other events
+    pub fn push(&mut self, event: &Event) {
+        let Event::Buffer(event) = event else {
+            return;
+        };
+
+        let index = LineIndex::for_lsp(&self.text);
+        match event {
+            BufferEvent::Inserted { position, text, .. } => {
+                let at = index.lsp_position(&self.text, *position);
+                self.changes.push(TextDocumentContentChangeEvent {
+                    range: Some(Range::new(at, at)),
+                    range_length: None,
+                    text: text.clone(),
+                });
+                self.text.insert_str(*position, text);
+            }
+            BufferEvent::Deleted { start, end, .. } => {
+                let range = Range::new(
+                    index.lsp_position(&self.text, *start),
+                    index.lsp_position(&self.text, *end),
+                );
+                self.changes.push(TextDocumentContentChangeEvent {
+                    range: Some(range),
+                    range_length: None,
+                    text: String::new(),
+                });
+                self.text.replace_range(*start..*end, "");
+            }
+            BufferEvent::Modified { .. } => {}
+        }
+    }
+
+    /// Takes the pending changes
+    pub fn take(&mut self) -> Vec<TextDocumentContentChangeEvent> {
+        std::mem::take(&mut self.changes)
+    }
+}
+
+/// Spawns a task turning a document's edits into `didChange` notifications
+///
+/// `events` should come from `Document::subscribe` and `text` and `version`
+/// describe the document as last sent to the server. Edits are collected until
+/// none arrive for `debounce`, then sent as one notification with the next
+/// version. The feed stops when the document is dropped or if events were
+/// missed, since the server's copy can no longer be kept in sync.
+pub fn spawn_did_change_feed(
+    uri: Url,
+    text: impl Into<String>,
+    version: i32,
+    mut events: broadcast::Receiver<Event>,
+    debounce: Duration,
+) -> (JoinHandle<()>, mpsc::UnboundedReceiver<DidChangeTextDocumentParams>) {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut batcher = ChangeBatcher::new(text);
+
+    let handle = tokio::spawn(async move {
+        let mut version = version;
+        loop {
+            // Wait for the first edit of a batch
+            match events.recv().await {
+                Ok(event) => batcher.push(&event),
+                Err(broadcast::error::RecvError::Closed) => return,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    log::warn!("didChange feed for {} missed {} edits, stopping", uri, missed);
+                    return;
+                }
+            }
+
+            // Keep collecting until the edits have been quiet for the debounce period
+            let mut closed = false;
+            loop {
+                match tokio::time::timeout(debounce, events.recv()).await {
+                    Err(_) => break,
+                    Ok(Ok(event)) => batcher.push(&event),
+                    Ok(Err(broadcast::error::RecvError::Closed)) => {
+                        closed = true;
+                        break;
+                    }
+                    Ok(Err(broadcast::error::RecvError::Lagged(missed))) => {
+                        log::warn!("didChange feed for {} missed {} edits, stopping", uri, missed);
+                        return;
+                    }
+                }
+            }
+
+            if !batcher.is_empty() {
+                version += 1;
+                let params = DidChangeTextDocumentParams {
+                    text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+                    content_changes: batcher.take(),
+                };
+                if tx.send(params).is_err() {
+                    return;
+                }
+            }
+
+            if closed {
+                return;
+            }
+        }
+    });
+
+    (handle, rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::Document;
+
+    #[test]
+    fn test_offset_to_position() {
+        let text = "fn main() {\n    let s = \"😀x\";\n}";
+        assert_eq!(offset_to_position(text, 0), Position::new(0, 0));
+        assert_eq!(offset_to_position(text, 12), Position::new(1, 0));
+
+        // The emoji is four bytes but two UTF-16 code units
+        let x = text.find('x').unwrap();
+        assert_eq!(offset_to_position(text, x), Position::new(1, 15));
+    }
+
+    #[test]
+    fn test_position_to_offset() {
+        let text = "fn main() {\n    let s = \"😀x\";\n}";
+        let x = text.find('x').unwrap();
+        assert_eq!(position_to_offset(text, Position::new(1, 15)), x);
+        assert_eq!(position_to_offset(text, offset_to_position(text, x)), x);
+
+        // Clamped to the line and the text
+        assert_eq!(position_to_offset(text, Position::new(0, 99)), 11);
+        assert_eq!(position_to_offset(text, Position::new(9, 0)), text.len());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_of_edits() {
+        let uri = Url::parse("file:///main.rs").unwrap();
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\n}\n").unwrap();
+
+        let (_feed, mut notifications) =
+            spawn_did_change_feed(uri.clone(), doc.text(), 1, doc.subscribe(), Duration::from_millis(50));
+
+        // A burst of typing on the second line
+        doc.insert(12, "    l").unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        doc.insert(17, "et 😀 = 1;\n").unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        doc.delete(16, 17).unwrap();
+        doc.insert(16, "L").unwrap();
+
+        let params = notifications.recv().await.unwrap();
+        assert_eq!(params.text_document.uri, uri);
+        assert_eq!(params.text_document.version, 2);
+
+        let at = |line, character| Position::new(line, character);
+        let ranges: Vec<_> = params.content_changes.iter()
+            .map(|change| (change.range.unwrap(), change.text.as_str()))
+            .collect();
+        assert_eq!(ranges, vec![
+            (Range::new(at(1, 0), at(1, 0)), "    l"),
+            (Range::new(at(1, 5), at(1, 5)), "et 😀 = 1;\n"),
+            (Range::new(at(1, 4), at(1, 5)), ""),
+            (Range::new(at(1, 4), at(1, 4)), "L"),
+        ]);
+
+        // A later edit is sent on its own with the next version
+        doc.insert(0, "// main\n").unwrap();
+        let params = notifications.recv().await.unwrap();
+        assert_eq!(params.text_document.version, 3);
+        assert_eq!(params.content_changes.len(), 1);
+    }
+
+    #[test]
+    fn test_batcher_tracks_text() {
+        let mut doc = Document::new("main.rs");
+        let mut events = doc.subscribe();
+        let mut batcher = ChangeBatcher::new("");
+
+        doc.insert(0, "héllo").unwrap();
+        doc.delete(1, 3).unwrap();
+        while let Ok(event) = events.try_recv() {
+            batcher.push(&event);
+        }
+
+        assert_eq!(batcher.text(), doc.text());
+        assert_eq!(batcher.take().len(), 2);
+        assert!(batcher.is_empty());
+    }
+}
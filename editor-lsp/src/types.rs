@@ -22,6 +22,14 @@ pub enum Error {
     #[error("LSP server error: {0}")]
     ServerError(String),
     
+    /// The request was cancelled before it completed
+    #[error("LSP request cancelled")]
+    Cancelled,
+    
+    /// Errors applying edits to documents
+    #[error("Editor error: {0}")]
+    EditorError(#[from] editor_core::Error),
+    
     /// JSON-RPC errors
     #[error("JSON-RPC error: {0}")]
     JsonRpcError(#[from] jsonrpc::Error),
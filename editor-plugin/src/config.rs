@@ -0,0 +1,120 @@
+//! Plugin settings checked against the JSON Schema a plugin declares
+
+use crate::{PluginError, Result};
+use jsonschema::JSONSchema;
+use serde_json::{Map, Value};
+
+/// Returns a plugin's stored settings with the schema's defaults filled in,
+/// after checking them against the schema
+///
+/// Properties missing from `settings` take the `default` of their schema,
+/// also inside nested objects. Fails with the JSON pointer of the first
+/// invalid value, e.g. `/format/tab_size`.
+pub fn resolve_settings(plugin: &str, schema: &Value, settings: Option<&Value>) -> Result<Value> {
+    let mut settings = settings.cloned().unwrap_or_else(|| Value::Object(Map::new()));
+    apply_defaults(schema, &mut settings);
+
+    let compiled = JSONSchema::compile(schema).map_err(|e| {
+        PluginError::ManifestError(format!("Plugin {} declares an invalid settings schema: {}", plugin, e))
+    })?;
+    if let Err(mut errors) = compiled.validate(&settings) {
+        if let Some(error) = errors.next() {
+            let path = error.instance_path.to_string();
+            return Err(PluginError::InvalidConfig {
+                plugin: plugin.to_string(),
+                path: if path.is_empty() { "/".to_string() } else { path },
+                message: error.to_string(),
+            });
+        }
+    }
+    Ok(settings)
+}
+
+/// Returns the settings made of the schema's defaults alone
+pub fn default_settings(schema: &Value) -> Value {
+    let mut settings = Value::Object(Map::new());
+    apply_defaults(schema, &mut settings);
+    settings
+}
+
+/// Fills in the defaults of the properties `value` lacks
+fn apply_defaults(schema: &Value, value: &mut Value) {
+    let (Some(properties), Some(object)) = (schema.get("properties").and_then(Value::as_object), value.as_object_mut()) else {
+        return;
+    };
+    for (name, property) in properties {
+        if let Some(nested) = object.get_mut(name) {
+            apply_defaults(property, nested);
+            continue;
+        }
+        match property.get("default") {
+            Some(default) => {
+                let mut default = default.clone();
+                apply_defaults(property, &mut default);
+                object.insert(name.clone(), default);
+            }
+            None => {
+                // Objects without a default still get the defaults of their properties
+                let mut nested = Value::Object(Map::new());
+                apply_defaults(property, &mut nested);
+                if nested.as_object().is_some_and(|nested| !nested.is_empty()) {
+                    object.insert(name.clone(), nested);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "enabled": { "type": "boolean", "default": true },
+                "format": {
+                    "type": "object",
+                    "properties": {
+                        "tab_size": { "type": "integer", "minimum": 1, "default": 4 },
+                        "style": { "enum": ["compact", "expanded"] }
+                    }
+                }
+            },
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_valid_settings_get_defaults() {
+        assert_eq!(default_settings(&schema()), json!({"enabled": true, "format": {"tab_size": 4}}));
+
+        let stored = json!({"format": {"style": "compact"}});
+        let settings = resolve_settings("fmt", &schema(), Some(&stored)).unwrap();
+        assert_eq!(settings, json!({"enabled": true, "format": {"tab_size": 4, "style": "compact"}}));
+
+        let settings = resolve_settings("fmt", &schema(), None).unwrap();
+        assert_eq!(settings, default_settings(&schema()));
+    }
+
+    #[test]
+    fn test_invalid_settings_report_path() {
+        let stored = json!({"format": {"tab_size": 0}});
+        let err = resolve_settings("fmt", &schema(), Some(&stored)).unwrap_err();
+        assert!(matches!(
+            err,
+            PluginError::InvalidConfig { ref plugin, ref path, .. } if plugin == "fmt" && path == "/format/tab_size"
+        ));
+
+        let stored = json!({"enabled": "yes"});
+        let err = resolve_settings("fmt", &schema(), Some(&stored)).unwrap_err();
+        assert!(matches!(err, PluginError::InvalidConfig { ref path, .. } if path == "/enabled"));
+
+        // Unknown settings are reported against the object holding them
+        let stored = json!({"colour": "red"});
+        let err = resolve_settings("fmt", &schema(), Some(&stored)).unwrap_err();
+        assert!(matches!(err, PluginError::InvalidConfig { ref path, .. } if path == "/"));
+    }
+}
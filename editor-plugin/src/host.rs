@@ -0,0 +1,113 @@
+//! Editor services available to plugins
+
+use crate::{PluginError, Result};
+use editor_core::{DiagnosticProvider, DiagnosticRegistry, StatusItem, StatusItems};
+use editor_syntax::{Language, LanguageConfig};
+use libloading::{Library, Symbol};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Handle a plugin uses to change the editor UI
+///
+/// Everything a plugin adds through its handle is removed when the plugin is
+/// unregistered.
+#[derive(Debug, Clone)]
+pub struct HostApi {
+    /// Name of the plugin the handle belongs to
+    plugin: String,
+    /// Status bar items shared with the editor
+    status_items: Arc<StatusItems>,
+    /// Diagnostic providers shared with the editor
+    diagnostics: Arc<DiagnosticRegistry>,
+    /// Directory the plugin was loaded from, if any
+    directory: Option<PathBuf>,
+    /// Settings checked against the plugin's schema
+    settings: serde_json::Value,
+}
+
+impl HostApi {
+    /// Creates a handle for a plugin
+    pub fn new(plugin: impl Into<String>, status_items: Arc<StatusItems>, diagnostics: Arc<DiagnosticRegistry>) -> Self {
+        Self {
+            plugin: plugin.into(),
+            status_items,
+            diagnostics,
+            directory: None,
+            settings: serde_json::json!({}),
+        }
+    }
+
+    /// Sets the directory plugin resources are loaded from
+    pub fn with_directory(mut self, directory: Option<PathBuf>) -> Self {
+        self.directory = directory;
+        self
+    }
+
+    /// Sets the plugin's settings
+    pub fn with_settings(mut self, settings: serde_json::Value) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Returns the name of the plugin
+    pub fn plugin(&self) -> &str {
+        &self.plugin
+    }
+
+    /// Returns the plugin's settings, with defaults from its schema filled in
+    pub fn settings(&self) -> &serde_json::Value {
+        &self.settings
+    }
+
+    /// Shows a status bar item, replacing the plugin's item with the same id
+    pub fn set_status_item(&self, id: &str, item: StatusItem) {
+        self.status_items.set(&self.plugin, id, item);
+    }
+
+    /// Removes a status bar item
+    pub fn clear_status_item(&self, id: &str) {
+        self.status_items.clear(&self.plugin, id);
+    }
+
+    /// Adds a diagnostic provider for a language, or for all languages with `None`
+    pub fn register_diagnostic_provider(&self, language: Option<&str>, provider: Arc<dyn DiagnosticProvider>) {
+        self.diagnostics.register_for_plugin(&self.plugin, language, provider);
+    }
+
+    /// Removes the plugin's diagnostic provider with the given name
+    pub fn unregister_diagnostic_provider(&self, name: &str) -> bool {
+        self.diagnostics.unregister_for_plugin(&self.plugin, name)
+    }
+
+    /// Adds a language whose tree-sitter grammar is a shared library in the
+    /// plugin's directory
+    ///
+    /// `library` is relative to the plugin directory and `symbol` is the
+    /// grammar's C entry point, e.g. `tree_sitter_toml`. The library stays
+    /// loaded for the rest of the process, since parsers may still use the
+    /// grammar after the plugin is unregistered.
+    pub fn register_language(&self, config: LanguageConfig, library: impl AsRef<Path>, symbol: &str) -> Result<()> {
+        let directory = self.directory.as_ref().ok_or_else(|| {
+            PluginError::LoadError(format!("Plugin {} has no directory to load grammars from", self.plugin))
+        })?;
+        let path = directory.join(library);
+
+        // SAFETY: tree-sitter grammars export `const TSLanguage *symbol(void)`,
+        // which is the layout of `tree_sitter::Language`
+        let ts_language = unsafe {
+            let library = Library::new(&path)
+                .map_err(|e| PluginError::LoadError(format!("{}: {}", path.display(), e)))?;
+            let language = {
+                let entry: Symbol<unsafe extern "C" fn() -> tree_sitter::Language> = library
+                    .get(symbol.as_bytes())
+                    .map_err(|e| PluginError::LoadError(format!("{}: {}", path.display(), e)))?;
+                entry()
+            };
+            std::mem::forget(library);
+            language
+        };
+
+        editor_syntax::register_language(Language::new(config, ts_language))
+            .map_err(|e| PluginError::LoadError(e.to_string()))
+    }
+}
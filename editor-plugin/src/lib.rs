@@ -2,22 +2,31 @@
 //!
 //! Provides plugin loading and management functionality
 
+mod config;
+mod host;
 mod loader;
 mod manager;
 mod native;
 mod wasm;
 mod registry;
 mod sandbox;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
+pub use config::{default_settings, resolve_settings};
+pub use host::HostApi;
 pub use loader::{PluginLoader, LoaderError};
-pub use manager::{PluginManager, PluginEvent};
+pub use manager::{PluginManager, PluginEvent, PluginResponse, PluginState};
 pub use native::NativePlugin;
 pub use wasm::WasmPlugin;
 pub use registry::{PluginRegistry, PluginMetadata};
 pub use sandbox::{Sandbox, SandboxConfig};
 
+use editor_core::{CompletionContext, CompletionItem, DocumentEvent};
 use thiserror::Error;
+use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 /// Plugin-related errors
 #[derive(Error, Debug)]
@@ -37,6 +46,60 @@ pub enum PluginError {
     #[error("Sandbox error: {0}")]
     SandboxError(String),
 
+    #[error("Plugin {plugin} returned an error for command {command}: {message}")]
+    CommandFailed {
+        plugin: String,
+        command: String,
+        message: String,
+    },
+
+    #[error("Invalid response from plugin {plugin} for command {command}: {reason}")]
+    InvalidResponse {
+        plugin: String,
+        command: String,
+        reason: String,
+    },
+
+    #[error("Command {command} of plugin {plugin} was cancelled")]
+    Cancelled {
+        plugin: String,
+        command: String,
+    },
+
+    #[error("Invalid settings for plugin {plugin} at {path}: {message}")]
+    InvalidConfig {
+        plugin: String,
+        /// JSON pointer of the invalid value
+        path: String,
+        message: String,
+    },
+
+    #[error("Plugin {plugin} targets plugin API {version}, but the editor provides {expected}")]
+    IncompatibleApiVersion {
+        plugin: String,
+        version: ApiVersion,
+        expected: ApiVersion,
+    },
+
+    #[error("Plugin {plugin} does not match its checksum: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        plugin: String,
+        expected: String,
+        actual: String,
+    },
+
+    #[error("Plugin bundle {plugin} has no trusted checksum")]
+    UntrustedBundle {
+        plugin: String,
+    },
+
+    #[error("Native plugin {plugin} requests {permission} access, which the sandbox cannot enforce")]
+    UntrustedNativePlugin {
+        plugin: String,
+        /// Kind of permission the sandbox cannot enforce
+        permission: String,
+    },
+
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
@@ -44,12 +107,130 @@ pub enum PluginError {
 /// Result type for plugin operations
 pub type Result<T> = std::result::Result<T, PluginError>;
 
+/// Version of the `Plugin` API this crate was built with
+///
+/// The major version changes whenever the trait or the data exchanged with
+/// plugins changes incompatibly, for native and WASM plugins alike.
+pub const API_VERSION: ApiVersion = ApiVersion::new(1, 6);
+
+/// Semantic version of the plugin API
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize)]
+pub struct ApiVersion {
+    /// Incremented on incompatible changes
+    pub major: u32,
+    /// Incremented on backwards compatible additions
+    pub minor: u32,
+}
+
+impl ApiVersion {
+    /// Creates a version
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Returns true if a plugin built against this version can be loaded by `host`
+    pub fn is_compatible_with(&self, host: ApiVersion) -> bool {
+        self.major == host.major
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
 /// Plugin interface that all plugins must implement
+///
+/// `execute` takes `&self` so the manager can run commands on several plugins
+/// concurrently behind a shared read lock. Plugins that need to update state
+/// while handling a command should keep that state behind interior mutability
+/// (e.g. `std::sync::RwLock<State>`), compute the new value first and only take
+/// the write lock to store it, so that no lock is held across an `.await`.
 #[async_trait::async_trait]
 pub trait Plugin: Send + Sync {
     /// Returns the plugin metadata
     fn metadata(&self) -> &PluginMetadata;
 
+    /// Returns the plugin API version the plugin was built against
+    ///
+    /// The default is the version of the crate the plugin was compiled with.
+    fn api_version(&self) -> ApiVersion {
+        API_VERSION
+    }
+
+    /// Receives the plugin's handle to the editor when it is registered
+    ///
+    /// Plugins that want to change the editor UI, e.g. show status bar items,
+    /// should keep the handle. The default ignores it.
+    fn attach_host(&mut self, _host: HostApi) {}
+
+    /// Returns the directory the plugin was loaded from
+    ///
+    /// Resources such as grammars are loaded relative to it. The default,
+    /// for plugins built into the editor, has none.
+    fn directory(&self) -> Option<&std::path::Path> {
+        None
+    }
+
+    /// Returns a JSON Schema for the plugin's settings
+    ///
+    /// Stored settings are checked against it when the plugin is registered,
+    /// and properties they lack take the schema's defaults. The plugin reads
+    /// the result from `HostApi::settings`. The default accepts anything.
+    fn config_schema(&self) -> serde_json::Value {
+        serde_json::json!({})
+    }
+
+    /// Returns the commands the plugin adds to the command palette
+    ///
+    /// Each is run through `execute` when chosen. The default adds none.
+    fn commands(&self) -> Vec<PluginCommand> {
+        Vec::new()
+    }
+
+    /// Returns the kinds of document events the plugin wants to receive
+    ///
+    /// Only these are passed to `on_document_event`, so plugins are not woken
+    /// for every event. Plugins loaded from a manifest return its `events`.
+    /// The default receives none.
+    fn document_events(&self) -> Vec<DocumentEventKind> {
+        Vec::new()
+    }
+
+    /// Returns the permissions the plugin was granted
+    ///
+    /// Document events naming a file are only passed on if a file system
+    /// permission covers it. Plugins loaded from a manifest return its
+    /// `permissions`. The default has none.
+    fn permissions(&self) -> Vec<Permission> {
+        Vec::new()
+    }
+
+    /// Returns the features the plugin provides to the editor
+    ///
+    /// The editor only asks a plugin for what it declares here, e.g. for
+    /// completions with `PluginCapability::Completions`. Plugins loaded from
+    /// a manifest return its `capabilities`. The default provides none.
+    fn capabilities(&self) -> Vec<PluginCapability> {
+        Vec::new()
+    }
+
+    /// Returns completions for the cursor position described by `context`
+    ///
+    /// Only called if `capabilities` includes `PluginCapability::Completions`.
+    /// The items are shown with the language server's, which win when both
+    /// offer the same label. The default offers none.
+    async fn provide_completions(&self, _context: &CompletionContext) -> Vec<CompletionItem> {
+        Vec::new()
+    }
+
+    /// Reacts to a document event of a kind returned by `document_events`
+    ///
+    /// Called on the task dispatching the event, so slow work should be
+    /// spawned. The default ignores it.
+    fn on_document_event(&self, _event: &DocumentEvent) {}
+
     /// Initializes the plugin
     async fn initialize(&mut self) -> Result<()>;
 
@@ -60,6 +241,25 @@ pub trait Plugin: Send + Sync {
     async fn execute(&self, command: &str, args: serde_json::Value) -> Result<serde_json::Value>;
 }
 
+/// A plugin command shown in the command palette
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct PluginCommand {
+    /// Command passed to `Plugin::execute`
+    pub name: String,
+    /// Title shown in the palette after the plugin name
+    pub title: String,
+}
+
+impl PluginCommand {
+    /// Creates a command
+    pub fn new(name: impl Into<String>, title: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            title: title.into(),
+        }
+    }
+}
+
 /// Plugin manifest format
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct PluginManifest {
@@ -81,6 +281,12 @@ pub struct PluginManifest {
     pub dependencies: Vec<PluginDependency>,
     /// Plugin permissions
     pub permissions: Vec<Permission>,
+    /// Kinds of document events the plugin receives
+    #[serde(default)]
+    pub events: Vec<DocumentEventKind>,
+    /// Features the plugin provides to the editor
+    #[serde(default)]
+    pub capabilities: Vec<PluginCapability>,
 }
 
 /// Plugin types
@@ -125,6 +331,114 @@ pub enum Permission {
     },
 }
 
+/// Kinds of document events a plugin can subscribe to in its manifest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum DocumentEventKind {
+    /// A document was opened
+    Opened,
+    /// A document was saved
+    Saved,
+    /// A document was closed
+    Closed,
+    /// A document's language changed
+    LanguageChanged,
+    /// A document's line endings were converted
+    LineEndingChanged,
+    /// A document's selections changed
+    SelectionChanged,
+}
+
+impl DocumentEventKind {
+    /// Returns the kind of an event
+    pub fn of(event: &DocumentEvent) -> Self {
+        match event {
+            DocumentEvent::Opened { .. } => Self::Opened,
+            DocumentEvent::Saved { .. } => Self::Saved,
+            DocumentEvent::Closed { .. } => Self::Closed,
+            DocumentEvent::LanguageChanged { .. } => Self::LanguageChanged,
+            DocumentEvent::LineEndingChanged { .. } => Self::LineEndingChanged,
+            DocumentEvent::SelectionChanged { .. } => Self::SelectionChanged,
+        }
+    }
+}
+
+/// Features a plugin can declare in its manifest that it provides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum PluginCapability {
+    /// Completion items, from `Plugin::provide_completions`
+    Completions,
+}
+
+/// Asks a plugin for completions by running its `provide_completions`
+/// command with the context as arguments
+///
+/// Used by plugins loaded from a manifest, which have no other way to answer.
+/// A failed command or an invalid result is logged and gives no items.
+pub(crate) async fn completions_from_command(plugin: &dyn Plugin, context: &CompletionContext) -> Vec<CompletionItem> {
+    const COMMAND: &str = "provide_completions";
+    let name = &plugin.metadata().name;
+    let result = match serde_json::to_value(context) {
+        Ok(args) => plugin.execute(COMMAND, args).await,
+        Err(e) => Err(PluginError::ExecutionError(e.to_string())),
+    };
+    let items = result.and_then(|value| {
+        if let Some(message) = value.get("error").and_then(|e| e.as_str()) {
+            return Err(PluginError::CommandFailed {
+                plugin: name.clone(),
+                command: COMMAND.to_string(),
+                message: message.to_string(),
+            });
+        }
+        serde_json::from_value(value).map_err(|e| PluginError::InvalidResponse {
+            plugin: name.clone(),
+            command: COMMAND.to_string(),
+            reason: e.to_string(),
+        })
+    });
+    items.unwrap_or_else(|e| {
+        log::warn!("Plugin {} failed to provide completions: {}", name, e);
+        Vec::new()
+    })
+}
+
+impl Permission {
+    /// Returns true if the permission lets a plugin learn about `path`
+    pub fn covers_path(&self, path: &std::path::Path) -> bool {
+        match self {
+            Self::FileSystem { paths, .. } => paths.iter().any(|allowed| path.starts_with(allowed)),
+            Self::Network { .. } | Self::Process { .. } => false,
+        }
+    }
+}
+
+/// Receives the log messages written by a plugin
+#[derive(Clone)]
+pub struct PluginLogSink(Arc<dyn Fn(log::Level, &str) + Send + Sync>);
+
+impl PluginLogSink {
+    /// Creates a sink calling `f` for each message
+    pub fn new(f: impl Fn(log::Level, &str) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    /// Creates a sink forwarding to the `log` crate, prefixed with the plugin name
+    pub fn for_plugin(name: impl Into<String>) -> Self {
+        let name = name.into();
+        Self::new(move |level, message| log::log!(target: "plugin", level, "[{}] {}", name, message))
+    }
+
+    /// Writes a message to the sink
+    pub fn log(&self, level: log::Level, message: &str) {
+        (self.0)(level, message)
+    }
+}
+
+impl fmt::Debug for PluginLogSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginLogSink").finish_non_exhaustive()
+    }
+}
+
 /// Plugin configuration
 #[derive(Debug, Clone)]
 pub struct PluginConfig {
@@ -132,6 +446,8 @@ pub struct PluginConfig {
     pub manifest: PluginManifest,
     /// Plugin sandbox configuration
     pub sandbox: SandboxConfig,
+    /// Where the plugin's log messages go, the `log` crate if not set
+    pub log_sink: Option<PluginLogSink>,
 }
 
 impl PluginConfig {
@@ -140,6 +456,7 @@ impl PluginConfig {
         Self {
             manifest,
             sandbox: SandboxConfig::default(),
+            log_sink: None,
         }
     }
 
@@ -148,6 +465,18 @@ impl PluginConfig {
         self.sandbox = config;
         self
     }
+
+    /// Sets the sink receiving the plugin's log messages
+    pub fn with_log_sink(mut self, sink: PluginLogSink) -> Self {
+        self.log_sink = Some(sink);
+        self
+    }
+
+    /// Returns the plugin's log sink
+    pub fn log_sink(&self) -> PluginLogSink {
+        self.log_sink.clone()
+            .unwrap_or_else(|| PluginLogSink::for_plugin(&self.manifest.name))
+    }
 }
 
 #[cfg(test)]
@@ -171,10 +500,14 @@ mod tests {
                     read_only: true,
                 },
             ],
+            events: vec![DocumentEventKind::Saved],
+            capabilities: vec![PluginCapability::Completions],
         };
 
         let json = serde_json::to_string(&manifest).unwrap();
         let deserialized: PluginManifest = serde_json::from_str(&json).unwrap();
         assert_eq!(deserialized.name, "test-plugin");
+        assert_eq!(deserialized.events, vec![DocumentEventKind::Saved]);
+        assert_eq!(deserialized.capabilities, vec![PluginCapability::Completions]);
     }
 }
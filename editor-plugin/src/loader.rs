@@ -1,10 +1,17 @@
 //! Plugin loader implementation
 
-use std::path::{Path, PathBuf};
-use crate::{Plugin, PluginConfig, PluginError, Result, PluginType};
+use std::collections::HashMap;
+use std::io::{Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+use crate::{Plugin, PluginConfig, PluginError, PluginManifest, Result, PluginType, SandboxConfig};
 use crate::native::NativePlugin;
 use crate::wasm::WasmPlugin;
 
+/// Name of the manifest inside a plugin bundle
+const BUNDLE_MANIFEST: &str = "plugin.toml";
+
 /// Plugin loader error
 #[derive(thiserror::Error, Debug)]
 pub enum LoaderError {
@@ -25,6 +32,12 @@ pub enum LoaderError {
 pub struct PluginLoader {
     /// Plugin search paths
     search_paths: Vec<PathBuf>,
+    /// Directory plugin bundles are extracted to
+    cache_dir: PathBuf,
+    /// Sandbox configuration of loaded plugins
+    sandbox: SandboxConfig,
+    /// Hex SHA-256 of the trusted bundle archive of each plugin, by name
+    trusted_bundles: HashMap<String, String>,
 }
 
 impl PluginLoader {
@@ -32,6 +45,12 @@ impl PluginLoader {
     pub fn new() -> Self {
         Self {
             search_paths: Vec::new(),
+            cache_dir: dirs::cache_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("rust-editor")
+                .join("plugins"),
+            sandbox: SandboxConfig::default(),
+            trusted_bundles: HashMap::new(),
         }
     }
 
@@ -40,11 +59,52 @@ impl PluginLoader {
         self.search_paths.push(path.as_ref().to_path_buf());
     }
 
+    /// Sets the directory plugin bundles are extracted to
+    pub fn set_cache_dir(&mut self, path: impl AsRef<Path>) {
+        self.cache_dir = path.as_ref().to_path_buf();
+    }
+
+    /// Sets the sandbox configuration of plugins loaded from now on
+    ///
+    /// Native plugins requesting network or process permissions are only
+    /// loaded if it trusts native plugins.
+    pub fn set_sandbox(&mut self, config: SandboxConfig) {
+        self.sandbox = config;
+    }
+
+    /// Trusts the bundle of `plugin` whose archive has the hex SHA-256 `sha256`
+    ///
+    /// The checksum covers the whole archive, so every extracted file. Once
+    /// any bundle is trusted, bundles of other plugins are no longer loaded.
+    pub fn trust_bundle(&mut self, plugin: impl Into<String>, sha256: impl Into<String>) {
+        self.trusted_bundles.insert(plugin.into(), sha256.into());
+    }
+
     /// Loads a plugin from a path
     pub async fn load(&self, path: impl AsRef<Path>) -> Result<Box<dyn Plugin>> {
         let path = path.as_ref();
         let config = self.load_config(path)?;
+        self.load_with_config(path, config).await
+    }
+
+    /// Loads a plugin from a zip bundle holding `plugin.toml` and the binary
+    ///
+    /// If the plugin's bundle is trusted, the archive must match the trusted
+    /// checksum; if only others are, it is rejected. The bundle is extracted to a directory named after the plugin and version in the
+    /// cache directory, replacing an earlier extraction, and loaded from there.
+    /// Names or versions that are not plain path components are rejected.
+    pub async fn load_bundle(&self, path: &Path) -> Result<Box<dyn Plugin>> {
+        let bundle = path.to_path_buf();
+        let cache_dir = self.cache_dir.clone();
+        let trusted = self.trusted_bundles.clone();
+        let (directory, manifest) = tokio::task::spawn_blocking(move || unpack_bundle(&bundle, &cache_dir, &trusted))
+            .await
+            .map_err(|e| PluginError::LoadError(e.to_string()))??;
+        self.load_with_config(&directory, PluginConfig::new(manifest).with_sandbox(self.sandbox.clone())).await
+    }
 
+    /// Loads a plugin of the manifest's type from a directory
+    async fn load_with_config(&self, path: &Path, config: PluginConfig) -> Result<Box<dyn Plugin>> {
         match config.manifest.plugin_type {
             PluginType::Native => {
                 let plugin = NativePlugin::load(path, config).await?;
@@ -68,10 +128,12 @@ impl PluginLoader {
         let manifest = serde_json::from_str(&manifest_contents)
             .map_err(|e| PluginError::ManifestError(e.to_string()))?;
 
-        Ok(PluginConfig::new(manifest))
+        Ok(PluginConfig::new(manifest).with_sandbox(self.sandbox.clone()))
     }
 
     /// Discovers plugins in search paths
+    ///
+    /// Both plugin directories and `.zip` bundles are loaded.
     pub async fn discover(&self) -> Result<Vec<Box<dyn Plugin>>> {
         let mut plugins = Vec::new();
 
@@ -84,10 +146,16 @@ impl PluginLoader {
                 let entry = entry?;
                 let path = entry.path();
 
-                if path.is_dir() {
-                    if let Ok(plugin) = self.load(&path).await {
-                        plugins.push(plugin);
-                    }
+                let loaded = if path.is_dir() {
+                    self.load(&path).await
+                } else if path.extension().is_some_and(|extension| extension == "zip") {
+                    self.load_bundle(&path).await
+                } else {
+                    continue;
+                };
+                match loaded {
+                    Ok(plugin) => plugins.push(plugin),
+                    Err(e) => log::warn!("Skipping plugin {}: {}", path.display(), e),
                 }
             }
         }
@@ -96,6 +164,83 @@ impl PluginLoader {
     }
 }
 
+/// Returns the name of the plugin binary inside a bundle
+fn bundle_binary(manifest: &PluginManifest) -> String {
+    let file = match manifest.plugin_type {
+        PluginType::Native => NativePlugin::library_file(&manifest.entry_point),
+        PluginType::Wasm => Path::new(&manifest.entry_point).with_extension("wasm"),
+    };
+    file.to_string_lossy().replace('\\', "/")
+}
+
+/// Checks a bundle against its trusted checksum and extracts it into
+/// `cache_dir`, returning the directory and the manifest
+///
+/// The archive is read once, so the extracted files are the checked bytes.
+fn unpack_bundle(
+    bundle: &Path,
+    cache_dir: &Path,
+    trusted: &HashMap<String, String>,
+) -> Result<(PathBuf, PluginManifest)> {
+    let invalid = |e: zip::result::ZipError| PluginError::LoadError(format!("Invalid plugin bundle {}: {}", bundle.display(), e));
+    let bytes = std::fs::read(bundle)?;
+    let actual = hex::encode(Sha256::digest(&bytes));
+    let mut archive = ZipArchive::new(Cursor::new(bytes)).map_err(invalid)?;
+
+    let mut contents = String::new();
+    archive.by_name(BUNDLE_MANIFEST)
+        .map_err(|_| PluginError::ManifestError(format!("Missing {} in {}", BUNDLE_MANIFEST, bundle.display())))?
+        .read_to_string(&mut contents)?;
+    let manifest: PluginManifest = toml::from_str(&contents)
+        .map_err(|e| PluginError::ManifestError(e.to_string()))?;
+    match trusted.get(&manifest.name) {
+        Some(expected) if !actual.eq_ignore_ascii_case(expected.trim()) => {
+            return Err(PluginError::ChecksumMismatch { plugin: manifest.name, expected: expected.clone(), actual });
+        }
+        None if !trusted.is_empty() => return Err(PluginError::UntrustedBundle { plugin: manifest.name }),
+        _ => {}
+    }
+
+    let binary_name = bundle_binary(&manifest);
+    if archive.by_name(&binary_name).is_err() {
+        return Err(PluginError::LoadError(format!("Missing {} in {}", binary_name, bundle.display())));
+    }
+
+    let directory_name = format!("{}-{}", manifest.name, manifest.version);
+    let mut components = Path::new(&directory_name).components();
+    if !matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(name)), None) if name == directory_name.as_str()
+    ) {
+        return Err(PluginError::ManifestError(format!(
+            "Invalid plugin name or version in {}: {}",
+            bundle.display(),
+            directory_name
+        )));
+    }
+    let directory = cache_dir.join(directory_name);
+    if directory.exists() {
+        std::fs::remove_dir_all(&directory)?;
+    }
+    std::fs::create_dir_all(&directory)?;
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(invalid)?;
+        let Some(relative) = entry.enclosed_name().map(Path::to_path_buf) else {
+            return Err(PluginError::LoadError(format!("Bundle entry {} is outside the plugin", entry.name())));
+        };
+        let target = directory.join(&relative);
+        if entry.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut entry, &mut std::fs::File::create(&target)?)?;
+    }
+    Ok((directory, manifest))
+}
+
 impl Default for PluginLoader {
     fn default() -> Self {
         Self::new()
@@ -138,4 +283,106 @@ mod tests {
         let plugins = loader.discover().await.unwrap();
         assert_eq!(plugins.len(), 1);
     }
+
+    const MODULE: &str = r#"
+        (module
+            (func (export "initialize"))
+            (func (export "shutdown")))
+    "#;
+
+    /// Writes a bundle of a WASM plugin `name` with a grammar file holding
+    /// `grammar`, returning its path and hex SHA-256
+    fn write_bundle(dir: &Path, name: &str, grammar: &[u8]) -> (PathBuf, String) {
+        use std::io::Write;
+
+        let mut manifest = format!("name = \"{}\"\n", name);
+        manifest.push_str(
+            "version = \"0.2.0\"\n\
+             description = \"Bundled plugin\"\n\
+             author = \"Test Author\"\n\
+             license = \"MIT\"\n\
+             entry_point = \"plugin\"\n\
+             plugin_type = \"Wasm\"\n\
+             dependencies = []\n\
+             permissions = []\n",
+        );
+
+        let path = dir.join("bundled.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        let options = zip::write::FileOptions::default();
+        zip.start_file(BUNDLE_MANIFEST, options).unwrap();
+        zip.write_all(manifest.as_bytes()).unwrap();
+        zip.start_file("plugin.wasm", options).unwrap();
+        zip.write_all(MODULE.as_bytes()).unwrap();
+        zip.start_file("grammars/example.so", options).unwrap();
+        zip.write_all(grammar).unwrap();
+        zip.finish().unwrap();
+        let sha256 = hex::encode(Sha256::digest(fs::read(&path).unwrap()));
+        (path, sha256)
+    }
+
+    #[tokio::test]
+    async fn test_load_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let (bundle, checksum) = write_bundle(temp_dir.path(), "bundled", b"grammar");
+
+        let mut loader = PluginLoader::new();
+        loader.set_cache_dir(temp_dir.path().join("cache"));
+        loader.trust_bundle("bundled", checksum);
+        let mut plugin = loader.load_bundle(&bundle).await.unwrap();
+        plugin.initialize().await.unwrap();
+        assert_eq!(plugin.metadata().name, "bundled");
+
+        // Resources are extracted next to the binary
+        let directory = temp_dir.path().join("cache").join("bundled-0.2.0");
+        assert_eq!(plugin.directory(), Some(directory.as_path()));
+        assert_eq!(fs::read(directory.join("grammars/example.so")).unwrap(), b"grammar");
+
+        // Bundles are discovered alongside plugin directories
+        loader.add_search_path(temp_dir.path());
+        assert_eq!(loader.discover().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reject_tampered_bundle() {
+        let temp_dir = TempDir::new().unwrap();
+        let (_, checksum) = write_bundle(temp_dir.path(), "bundled", b"grammar");
+        // Any file changed after the checksum was taken, not only the binary
+        let (bundle, _) = write_bundle(temp_dir.path(), "bundled", b"tampered");
+
+        let mut loader = PluginLoader::new();
+        loader.set_cache_dir(temp_dir.path().join("cache"));
+        loader.trust_bundle("bundled", checksum.clone());
+        let err = loader.load_bundle(&bundle).await.err().unwrap();
+        assert!(matches!(
+            err,
+            PluginError::ChecksumMismatch { ref plugin, ref expected, .. } if plugin == "bundled" && *expected == checksum
+        ));
+        // Nothing is extracted from a rejected bundle
+        assert!(!temp_dir.path().join("cache").exists());
+
+        // Nor are bundles of plugins without a trusted checksum loaded
+        let mut loader = PluginLoader::new();
+        loader.set_cache_dir(temp_dir.path().join("cache"));
+        loader.trust_bundle("other", checksum);
+        let err = loader.load_bundle(&bundle).await.err().unwrap();
+        assert!(matches!(err, PluginError::UntrustedBundle { ref plugin } if plugin == "bundled"));
+        assert!(!temp_dir.path().join("cache").exists());
+    }
+
+    #[tokio::test]
+    async fn test_reject_bundle_escaping_cache() {
+        let temp_dir = TempDir::new().unwrap();
+        let outside = temp_dir.path().join("outside-0.2.0");
+        fs::create_dir_all(&outside).unwrap();
+        let (bundle, _) = write_bundle(temp_dir.path(), "../outside", b"grammar");
+
+        let mut loader = PluginLoader::new();
+        loader.set_cache_dir(temp_dir.path().join("cache"));
+        let err = loader.load_bundle(&bundle).await.err().unwrap();
+        assert!(matches!(err, PluginError::ManifestError(_)));
+        // Directories outside the cache are left alone
+        assert!(outside.exists());
+        assert!(!temp_dir.path().join("cache").exists());
+    }
 }
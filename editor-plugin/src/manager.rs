@@ -1,9 +1,39 @@
 //! Plugin manager implementation
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
-use crate::{Plugin, PluginMetadata, Result};
+use serde::de::DeserializeOwned;
+use tokio_util::sync::CancellationToken;
+use editor_core::event::{DocumentEvent, Event, EventHandler};
+use editor_core::{
+    Action, ActionRegistry, CompletionContext, CompletionItem, DiagnosticRegistry, StatusItem, StatusItems,
+};
+use crate::{
+    resolve_settings, DocumentEventKind, HostApi, Permission, Plugin, PluginCapability, PluginCommand, PluginError,
+    PluginMetadata, Result, API_VERSION,
+};
+
+/// A registered plugin, locked on its own so that calls into one plugin do
+/// not hold up the others
+type PluginHandle = Arc<RwLock<Box<dyn Plugin>>>;
+
+/// Plugins by name
+type Plugins = RwLock<HashMap<String, PluginHandle>>;
+
+/// Document event subscriptions by plugin name
+type Subscriptions = RwLock<HashMap<String, Subscription>>;
+
+/// Plugin states by plugin name
+type States = RwLock<HashMap<String, PluginState>>;
+
+/// Id of the status bar item showing a plugin's last failed command
+const COMMAND_ERROR_ITEM: &str = "command-error";
+
+/// Time a plugin has to offer completions before it is left out
+const COMPLETION_TIMEOUT: Duration = Duration::from_millis(300);
 
 /// Plugin event types
 #[derive(Debug, Clone)]
@@ -28,6 +58,53 @@ pub enum PluginEvent {
     },
 }
 
+impl PluginEvent {
+    /// Returns the event in the editor's shared event hierarchy, so editor
+    /// event handlers can follow plugins
+    ///
+    /// State changes have no counterpart there and give `None`.
+    pub fn to_event(&self) -> Option<editor_core::Event> {
+        let event = match self {
+            Self::Loaded(metadata) => editor_core::PluginEvent::Load { name: metadata.name.clone() },
+            Self::Unloaded(metadata) => editor_core::PluginEvent::Unload { name: metadata.name.clone() },
+            Self::Error { metadata, error } => editor_core::PluginEvent::Error {
+                name: metadata.name.clone(),
+                error: error.clone(),
+            },
+            Self::StateChanged { .. } => return None,
+        };
+        Some(editor_core::Event::Plugin(event))
+    }
+}
+
+/// The document events a plugin asked for when it was registered
+#[derive(Debug, Clone)]
+struct Subscription {
+    /// Kinds of events passed to the plugin
+    events: Vec<DocumentEventKind>,
+    /// Permissions deciding which files the plugin may hear about
+    permissions: Vec<Permission>,
+}
+
+impl Subscription {
+    /// Returns true if the plugin asked for `event` and may learn about the
+    /// file it names, if any
+    fn wants(&self, event: &DocumentEvent) -> bool {
+        if !self.events.contains(&DocumentEventKind::of(event)) {
+            return false;
+        }
+        let path = match event {
+            DocumentEvent::Saved { path } => Some(path.as_path()),
+            DocumentEvent::Opened { path, .. }
+            | DocumentEvent::Closed { path, .. }
+            | DocumentEvent::LanguageChanged { path, .. }
+            | DocumentEvent::LineEndingChanged { path, .. }
+            | DocumentEvent::SelectionChanged { path, .. } => path.as_deref(),
+        };
+        path.is_none_or(|path| self.permissions.iter().any(|permission| permission.covers_path(path)))
+    }
+}
+
 /// Plugin state
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PluginState {
@@ -41,43 +118,183 @@ pub enum PluginState {
     Error,
 }
 
+/// Response returned by a plugin command
+#[derive(Debug, Clone, PartialEq)]
+pub struct PluginResponse {
+    /// Value returned by the plugin
+    pub value: serde_json::Value,
+    /// Error and diagnostic messages reported by the plugin
+    pub diagnostics: Vec<String>,
+}
+
+impl PluginResponse {
+    /// Creates a response from a raw plugin value
+    ///
+    /// An `"error"` string and any `"diagnostics"` strings in the value are
+    /// collected into `diagnostics`.
+    pub fn from_value(value: serde_json::Value) -> Self {
+        let mut diagnostics = Vec::new();
+
+        if let Some(error) = value.get("error").and_then(|e| e.as_str()) {
+            diagnostics.push(error.to_string());
+        }
+
+        if let Some(messages) = value.get("diagnostics").and_then(|d| d.as_array()) {
+            diagnostics.extend(
+                messages.iter()
+                    .filter_map(|m| m.as_str())
+                    .map(String::from),
+            );
+        }
+
+        Self { value, diagnostics }
+    }
+
+    /// Returns the error message if the plugin returned an error object
+    pub fn error(&self) -> Option<&str> {
+        self.value.get("error").and_then(|e| e.as_str())
+    }
+
+    /// Returns true if the plugin returned an error object
+    pub fn is_error(&self) -> bool {
+        self.error().is_some()
+    }
+}
+
 /// Plugin manager
 pub struct PluginManager {
     /// Active plugins
-    plugins: Arc<RwLock<HashMap<String, Box<dyn Plugin>>>>,
+    plugins: Arc<Plugins>,
     /// Plugin states
-    states: Arc<RwLock<HashMap<String, PluginState>>>,
+    states: Arc<States>,
+    /// Document events each plugin subscribed to
+    subscriptions: Arc<Subscriptions>,
     /// Event subscribers
     subscribers: Arc<RwLock<Vec<tokio::sync::mpsc::Sender<PluginEvent>>>>,
+    /// Status bar items set by plugins
+    status_items: Arc<StatusItems>,
+    /// Diagnostic providers registered by plugins
+    diagnostics: Arc<DiagnosticRegistry>,
+    /// Actions running plugin commands
+    actions: Arc<ActionRegistry>,
+    /// Stored settings by plugin name
+    settings: HashMap<String, serde_json::Value>,
 }
 
 impl PluginManager {
     /// Creates a new plugin manager
     pub fn new() -> Self {
+        Self::with_status_items(Arc::new(StatusItems::new()))
+    }
+
+    /// Creates a plugin manager whose plugins update the given status bar items
+    ///
+    /// Pass `Editor::status_items` so plugin items show up in the editor.
+    pub fn with_status_items(status_items: Arc<StatusItems>) -> Self {
         Self {
             plugins: Arc::new(RwLock::new(HashMap::new())),
             states: Arc::new(RwLock::new(HashMap::new())),
+            subscriptions: Arc::new(RwLock::new(HashMap::new())),
             subscribers: Arc::new(RwLock::new(Vec::new())),
+            status_items,
+            diagnostics: Arc::new(DiagnosticRegistry::new()),
+            actions: Arc::new(ActionRegistry::new()),
+            settings: HashMap::new(),
         }
     }
 
+    /// Sets the registry plugin commands are added to as actions
+    ///
+    /// Pass `Editor::actions` so plugin commands show up in the command palette.
+    pub fn with_actions(mut self, actions: Arc<ActionRegistry>) -> Self {
+        self.actions = actions;
+        self
+    }
+
+    /// Sets the registry plugins add diagnostic providers to
+    ///
+    /// Pass `Editor::diagnostic_providers` so plugin diagnostics show up in
+    /// the editor.
+    pub fn with_diagnostics(mut self, diagnostics: Arc<DiagnosticRegistry>) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Sets the stored settings of each plugin, by plugin name
+    ///
+    /// Pass the plugin settings from the preferences; they are checked when
+    /// each plugin is registered.
+    pub fn with_settings(mut self, settings: HashMap<String, serde_json::Value>) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Returns the status bar items set by plugins
+    pub fn status_items(&self) -> &Arc<StatusItems> {
+        &self.status_items
+    }
+
+    /// Returns the registry plugin commands are added to
+    pub fn actions(&self) -> &Arc<ActionRegistry> {
+        &self.actions
+    }
+
+    /// Returns the registry plugins add diagnostic providers to
+    pub fn diagnostics(&self) -> &Arc<DiagnosticRegistry> {
+        &self.diagnostics
+    }
+
     /// Registers a plugin
-    pub async fn register_plugin(&self, plugin: Box<dyn Plugin>) -> Result<()> {
+    ///
+    /// Fails if the plugin was built against an incompatible major API version,
+    /// or if its stored settings do not match its schema. Otherwise the plugin
+    /// receives its `HostApi` handle, with its settings, and its commands are
+    /// added to the actions as "plugin.command", in the plugin's category.
+    /// Document events it subscribes to are passed to it from then on.
+    pub async fn register_plugin(&self, mut plugin: Box<dyn Plugin>) -> Result<()> {
         let metadata = plugin.metadata().clone();
         let name = metadata.name.clone();
 
-        self.plugins.write().await.insert(name.clone(), plugin);
+        let version = plugin.api_version();
+        if !version.is_compatible_with(API_VERSION) {
+            return Err(PluginError::IncompatibleApiVersion {
+                plugin: name,
+                version,
+                expected: API_VERSION,
+            });
+        }
+        let settings = resolve_settings(&name, &plugin.config_schema(), self.settings.get(&name))?;
+        let host = HostApi::new(name.clone(), self.status_items.clone(), self.diagnostics.clone())
+            .with_directory(plugin.directory().map(Path::to_path_buf))
+            .with_settings(settings);
+        plugin.attach_host(host);
+        for command in plugin.commands() {
+            self.actions.contribute(&name, self.command_action(&name, command));
+        }
+        let events = plugin.document_events();
+        if !events.is_empty() {
+            let subscription = Subscription { events, permissions: plugin.permissions() };
+            self.subscriptions.write().await.insert(name.clone(), subscription);
+        }
+
+        self.plugins.write().await.insert(name.clone(), Arc::new(RwLock::new(plugin)));
         self.states.write().await.insert(name.clone(), PluginState::Loaded);
 
         self.emit_event(PluginEvent::Loaded(metadata)).await;
         Ok(())
     }
 
-    /// Unregisters a plugin
+    /// Unregisters a plugin, removing its status bar items, diagnostic providers
+    /// and commands
     pub async fn unregister_plugin(&self, name: &str) -> Result<()> {
-        if let Some(plugin) = self.plugins.write().await.remove(name) {
-            let metadata = plugin.metadata().clone();
+        let removed = self.plugins.write().await.remove(name);
+        if let Some(plugin) = removed {
+            let metadata = plugin.read().await.metadata().clone();
             self.states.write().await.remove(name);
+            self.subscriptions.write().await.remove(name);
+            self.status_items.remove_plugin(name);
+            self.diagnostics.remove_plugin(name);
+            self.actions.remove_contributions(name);
             self.emit_event(PluginEvent::Unloaded(metadata)).await;
         }
         Ok(())
@@ -85,7 +302,8 @@ impl PluginManager {
 
     /// Initializes a plugin
     pub async fn initialize_plugin(&self, name: &str) -> Result<()> {
-        if let Some(plugin) = self.plugins.write().await.get_mut(name) {
+        if let Some(plugin) = self.plugin(name).await {
+            let mut plugin = plugin.write().await;
             plugin.initialize().await?;
             self.states.write().await.insert(name.to_string(), PluginState::Running);
             
@@ -99,7 +317,8 @@ impl PluginManager {
 
     /// Shuts down a plugin
     pub async fn shutdown_plugin(&self, name: &str) -> Result<()> {
-        if let Some(plugin) = self.plugins.write().await.get_mut(name) {
+        if let Some(plugin) = self.plugin(name).await {
+            let mut plugin = plugin.write().await;
             plugin.shutdown().await?;
             self.states.write().await.insert(name.to_string(), PluginState::Disabled);
             
@@ -112,12 +331,170 @@ impl PluginManager {
     }
 
     /// Executes a plugin command
-    pub async fn execute_command(&self, name: &str, command: &str, args: serde_json::Value) -> Result<serde_json::Value> {
-        if let Some(plugin) = self.plugins.read().await.get(name) {
-            plugin.execute(command, args).await
-        } else {
-            Err(crate::PluginError::ExecutionError(format!("Plugin {} not found", name)))
+    ///
+    /// If `token` is cancelled before the plugin finishes, the command is
+    /// aborted and `PluginError::Cancelled` is returned instead of its result.
+    pub async fn execute_command(
+        &self,
+        name: &str,
+        command: &str,
+        args: serde_json::Value,
+        token: &CancellationToken,
+    ) -> Result<serde_json::Value> {
+        run_command(&self.plugins, name, command, args, token).await
+    }
+
+    /// Passes a document event to the plugins subscribed to its kind
+    ///
+    /// Disabled plugins, and plugins whose permissions do not cover the file
+    /// the event names, are skipped.
+    pub async fn notify_document_event(&self, event: &DocumentEvent) {
+        forward_document_event(&self.plugins, &self.subscriptions, &self.states, event).await;
+    }
+
+    /// Returns the completions offered by plugins for `context`
+    ///
+    /// Only plugins declaring `PluginCapability::Completions` are asked, all
+    /// at once, and disabled ones are skipped. Plugins that do not answer
+    /// within `COMPLETION_TIMEOUT` are left out. Each item's `source` is the
+    /// plugin that offered it, and of items with the same label only the
+    /// first, in order of plugin name, is kept. Merge the result with a
+    /// language server's items using `editor_lsp::merge_completions`.
+    pub async fn provide_completions(&self, context: &CompletionContext) -> Vec<CompletionItem> {
+        let mut candidates: Vec<(String, PluginHandle)> = {
+            let plugins = self.plugins.read().await;
+            let states = self.states.read().await;
+            plugins
+                .iter()
+                .filter(|(name, _)| states.get(*name) != Some(&PluginState::Disabled))
+                .map(|(name, plugin)| (name.clone(), plugin.clone()))
+                .collect()
+        };
+        candidates.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        let answers = futures::future::join_all(candidates.iter().map(|(name, plugin)| async move {
+            let plugin = plugin.read().await;
+            if !plugin.capabilities().contains(&PluginCapability::Completions) {
+                return Vec::new();
+            }
+            match tokio::time::timeout(COMPLETION_TIMEOUT, plugin.provide_completions(context)).await {
+                Ok(items) => items,
+                Err(_) => {
+                    log::warn!("Plugin {} took too long to offer completions", name);
+                    Vec::new()
+                }
+            }
+        }))
+        .await;
+
+        let mut labels = HashSet::new();
+        let mut items = Vec::new();
+        for ((name, _), answer) in candidates.iter().zip(answers) {
+            for mut item in answer {
+                if labels.insert(item.label.clone()) {
+                    item.source.get_or_insert_with(|| name.clone());
+                    items.push(item);
+                }
+            }
+        }
+        items
+    }
+
+    /// Returns the handle of a registered plugin
+    async fn plugin(&self, name: &str) -> Option<PluginHandle> {
+        self.plugins.read().await.get(name).cloned()
+    }
+
+    /// Returns a handler passing the document events it receives to
+    /// subscribed plugins, e.g. for `Editor::add_event_handler`
+    pub fn document_event_handler(&self) -> Arc<dyn EventHandler> {
+        Arc::new(DocumentEventForwarder {
+            plugins: self.plugins.clone(),
+            subscriptions: self.subscriptions.clone(),
+            states: self.states.clone(),
+        })
+    }
+
+    /// Creates the action running a plugin command
+    ///
+    /// Invoking the action spawns the command on the current tokio runtime and
+    /// returns right away. If the command fails, the error is shown as a status
+    /// bar item of the plugin until the next command succeeds.
+    fn command_action(&self, plugin: &str, command: PluginCommand) -> Action {
+        let plugins = self.plugins.clone();
+        let status_items = self.status_items.clone();
+        let id = format!("{}.{}", plugin, command.name);
+        let title = command.title.clone();
+        let plugin = plugin.to_string();
+
+        Action::new(id, title, plugin.clone(), move |args| {
+            let runtime = tokio::runtime::Handle::try_current().map_err(|_| {
+                editor_core::Error::Action(format!("Cannot run {} outside of a tokio runtime", command.name))
+            })?;
+            let plugins = plugins.clone();
+            let status_items = status_items.clone();
+            let plugin = plugin.clone();
+            let command = command.clone();
+
+            runtime.spawn(async move {
+                let token = CancellationToken::new();
+                let error = match run_command(&plugins, &plugin, &command.name, args, &token).await {
+                    Ok(value) => PluginResponse::from_value(value).error().map(str::to_string),
+                    Err(e) => Some(e.to_string()),
+                };
+
+                match error {
+                    Some(message) => {
+                        log::warn!("Command {}.{} failed: {}", plugin, command.name, message);
+                        let item = StatusItem::new(format!("{}: {} failed", plugin, command.title))
+                            .with_tooltip(message);
+                        status_items.set(&plugin, COMMAND_ERROR_ITEM, item);
+                    }
+                    None => status_items.clear(&plugin, COMMAND_ERROR_ITEM),
+                }
+            });
+            Ok(serde_json::Value::Null)
+        })
+    }
+
+    /// Executes a plugin command and wraps the result in a `PluginResponse`
+    pub async fn execute(
+        &self,
+        name: &str,
+        command: &str,
+        args: serde_json::Value,
+        token: &CancellationToken,
+    ) -> Result<PluginResponse> {
+        let value = self.execute_command(name, command, args, token).await?;
+        Ok(PluginResponse::from_value(value))
+    }
+
+    /// Executes a plugin command and deserializes its result
+    ///
+    /// Returns `PluginError::CommandFailed` if the plugin returned an error object
+    /// and `PluginError::InvalidResponse` if the value does not match `T`.
+    pub async fn execute_typed<T: DeserializeOwned>(
+        &self,
+        name: &str,
+        command: &str,
+        args: serde_json::Value,
+        token: &CancellationToken,
+    ) -> Result<T> {
+        let response = self.execute(name, command, args, token).await?;
+
+        if let Some(message) = response.error() {
+            return Err(PluginError::CommandFailed {
+                plugin: name.to_string(),
+                command: command.to_string(),
+                message: message.to_string(),
+            });
         }
+
+        serde_json::from_value(response.value).map_err(|e| PluginError::InvalidResponse {
+            plugin: name.to_string(),
+            command: command.to_string(),
+            reason: e.to_string(),
+        })
     }
 
     /// Subscribes to plugin events
@@ -142,10 +519,12 @@ impl PluginManager {
 
     /// Returns all registered plugins
     pub async fn get_plugins(&self) -> Vec<PluginMetadata> {
-        self.plugins.read().await
-            .values()
-            .map(|p| p.metadata().clone())
-            .collect()
+        let plugins: Vec<PluginHandle> = self.plugins.read().await.values().cloned().collect();
+        let mut metadata = Vec::with_capacity(plugins.len());
+        for plugin in plugins {
+            metadata.push(plugin.read().await.metadata().clone());
+        }
+        metadata
     }
 }
 
@@ -155,13 +534,86 @@ impl Default for PluginManager {
     }
 }
 
+/// Forwards editor document events to subscribed plugins
+struct DocumentEventForwarder {
+    /// Plugins of the manager
+    plugins: Arc<Plugins>,
+    /// Document events each plugin subscribed to
+    subscriptions: Arc<Subscriptions>,
+    /// Plugin states, to skip disabled plugins
+    states: Arc<States>,
+}
+
+#[async_trait::async_trait]
+impl EventHandler for DocumentEventForwarder {
+    async fn handle(&self, event: Event) {
+        if let Event::Document(event) = event {
+            forward_document_event(&self.plugins, &self.subscriptions, &self.states, &event).await;
+        }
+    }
+}
+
+/// Passes a document event to the enabled plugins that want it
+async fn forward_document_event(plugins: &Plugins, subscriptions: &Subscriptions, states: &States, event: &DocumentEvent) {
+    let recipients: Vec<PluginHandle> = {
+        let subscriptions = subscriptions.read().await;
+        let states = states.read().await;
+        let plugins = plugins.read().await;
+        subscriptions
+            .iter()
+            .filter(|(name, subscription)| {
+                subscription.wants(event) && states.get(*name) != Some(&PluginState::Disabled)
+            })
+            .filter_map(|(name, _)| plugins.get(name).cloned())
+            .collect()
+    };
+    for plugin in recipients {
+        plugin.read().await.on_document_event(event);
+    }
+}
+
+/// Runs a command of a registered plugin, aborting if `token` is cancelled
+async fn run_command(
+    plugins: &Plugins,
+    name: &str,
+    command: &str,
+    args: serde_json::Value,
+    token: &CancellationToken,
+) -> Result<serde_json::Value> {
+    let Some(plugin) = plugins.read().await.get(name).cloned() else {
+        return Err(crate::PluginError::ExecutionError(format!("Plugin {} not found", name)));
+    };
+    let plugin = plugin.read().await;
+
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => Err(PluginError::Cancelled {
+            plugin: name.to_string(),
+            command: command.to_string(),
+        }),
+        result = plugin.execute(command, args) => result,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::PluginMetadata;
+    use crate::testing::MockPlugin;
+    use crate::{ApiVersion, PluginMetadata};
+    use editor_core::StatusItem;
+    use editor_core::LineEnding;
+    use editor_core::{Document, TrailingWhitespace};
+    use std::path::PathBuf;
+    use std::sync::Mutex;
+    use std::time::Duration;
 
     struct TestPlugin {
         metadata: PluginMetadata,
+        api_version: ApiVersion,
+        host: Option<HostApi>,
+        document_events: Vec<DocumentEventKind>,
+        permissions: Vec<Permission>,
+        received: Arc<Mutex<Vec<DocumentEvent>>>,
     }
 
     #[async_trait::async_trait]
@@ -170,6 +622,39 @@ mod tests {
             &self.metadata
         }
 
+        fn api_version(&self) -> ApiVersion {
+            self.api_version
+        }
+
+        fn attach_host(&mut self, host: HostApi) {
+            self.host = Some(host);
+        }
+
+        fn config_schema(&self) -> serde_json::Value {
+            serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "remote": { "type": "string", "default": "origin" }
+                }
+            })
+        }
+
+        fn commands(&self) -> Vec<PluginCommand> {
+            vec![PluginCommand::new("branch", "Show Branch"), PluginCommand::new("fail", "Break Things")]
+        }
+
+        fn document_events(&self) -> Vec<DocumentEventKind> {
+            self.document_events.clone()
+        }
+
+        fn permissions(&self) -> Vec<Permission> {
+            self.permissions.clone()
+        }
+
+        fn on_document_event(&self, event: &DocumentEvent) {
+            self.received.lock().unwrap().push(event.clone());
+        }
+
         async fn initialize(&mut self) -> Result<()> {
             Ok(())
         }
@@ -178,25 +663,85 @@ mod tests {
             Ok(())
         }
 
-        async fn execute(&self, _command: &str, _args: serde_json::Value) -> Result<serde_json::Value> {
-            Ok(serde_json::json!({"status": "ok"}))
+        async fn execute(&self, command: &str, _args: serde_json::Value) -> Result<serde_json::Value> {
+            match command {
+                "fail" => Ok(serde_json::json!({"error": "something went wrong"})),
+                "settings" => {
+                    let host = self.host.as_ref().expect("host attached on registration");
+                    Ok(host.settings().clone())
+                }
+                "branch" => {
+                    let host = self.host.as_ref().expect("host attached on registration");
+                    host.set_status_item("branch", StatusItem::new("Git: main ↑2").with_tooltip("2 commits ahead"));
+                    Ok(serde_json::json!({"status": "ok"}))
+                }
+                "lint" => {
+                    let host = self.host.as_ref().expect("host attached on registration");
+                    host.register_diagnostic_provider(None, Arc::new(TrailingWhitespace));
+                    Ok(serde_json::json!({"status": "ok"}))
+                }
+                "slow" => {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                    Ok(serde_json::json!({"text": "inserted"}))
+                }
+                _ => Ok(serde_json::json!({"status": "ok"})),
+            }
         }
     }
 
-    #[tokio::test]
-    async fn test_plugin_lifecycle() {
-        let manager = PluginManager::new();
+    #[derive(Debug, serde::Deserialize)]
+    struct StatusResponse {
+        status: String,
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct CountResponse {
+        #[allow(dead_code)]
+        count: usize,
+    }
 
-        let plugin = TestPlugin {
+    fn test_plugin() -> TestPlugin {
+        TestPlugin {
             metadata: PluginMetadata {
                 name: "test".to_string(),
                 version: "0.1.0".to_string(),
                 description: "Test plugin".to_string(),
             },
-        };
+            api_version: API_VERSION,
+            host: None,
+            document_events: Vec::new(),
+            permissions: Vec::new(),
+            received: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
 
-        manager.register_plugin(Box::new(plugin)).await.unwrap();
+    /// Returns a plugin subscribed to document events, and the events it receives
+    fn subscriber(
+        name: &str,
+        events: Vec<DocumentEventKind>,
+        allowed: &str,
+    ) -> (TestPlugin, Arc<Mutex<Vec<DocumentEvent>>>) {
+        let mut plugin = test_plugin();
+        plugin.metadata.name = name.to_string();
+        plugin.document_events = events;
+        plugin.permissions = vec![Permission::FileSystem { paths: vec![PathBuf::from(allowed)], read_only: true }];
+        let received = plugin.received.clone();
+        (plugin, received)
+    }
+
+    #[tokio::test]
+    async fn test_plugin_lifecycle() {
+        let manager = PluginManager::new();
+
+        let mut events = manager.subscribe().await;
+
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
         assert_eq!(manager.get_plugin_state("test").await, Some(PluginState::Loaded));
+        let event = events.recv().await.unwrap().to_event();
+        assert!(matches!(
+            event,
+            Some(editor_core::Event::Plugin(editor_core::PluginEvent::Load { ref name })) if name == "test"
+        ));
 
         manager.initialize_plugin("test").await.unwrap();
         assert_eq!(manager.get_plugin_state("test").await, Some(PluginState::Running));
@@ -207,4 +752,300 @@ mod tests {
         manager.unregister_plugin("test").await.unwrap();
         assert_eq!(manager.get_plugin_state("test").await, None);
     }
+
+    #[tokio::test]
+    async fn test_document_events_reach_subscribed_plugins() {
+        let manager = PluginManager::new();
+        let (saver, saved) = subscriber("saver", vec![DocumentEventKind::Saved], "/project");
+        let (opener, opened) = subscriber("opener", vec![DocumentEventKind::Opened], "/project");
+        let (outsider, outside) = subscriber("outsider", vec![DocumentEventKind::Saved], "/elsewhere");
+        for plugin in [saver, opener, outsider] {
+            manager.register_plugin(Box::new(plugin)).await.unwrap();
+        }
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.rs");
+        std::fs::write(&path, "fn main() {}\n").unwrap();
+        let mut editor = editor_core::Editor::new();
+        editor.add_event_handler(manager.document_event_handler());
+        editor.open_file(&path).unwrap();
+        let name = editor.active_document().unwrap().name().to_string();
+        editor.active_document_mut().unwrap().insert(0, "// saved\n").unwrap();
+        editor.save_document(&name).unwrap();
+        wait_until(|| !saved.lock().unwrap().is_empty()).await;
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "// saved\nfn main() {}\n");
+        assert!(matches!(saved.lock().unwrap()[..], [DocumentEvent::Saved { path: ref saved }] if *saved == path));
+        // Not subscribed to saves
+        assert!(opened.lock().unwrap().is_empty());
+        // Subscribed, but not allowed to see the file
+        assert!(outside.lock().unwrap().is_empty());
+
+        // Disabled plugins hear nothing
+        manager.shutdown_plugin("saver").await.unwrap();
+        manager.notify_document_event(&DocumentEvent::Saved { path }).await;
+        assert_eq!(saved.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_document_events_respect_permissions() {
+        let manager = PluginManager::new();
+        let kinds = vec![
+            DocumentEventKind::Closed,
+            DocumentEventKind::LanguageChanged,
+            DocumentEventKind::LineEndingChanged,
+            DocumentEventKind::SelectionChanged,
+        ];
+        let (insider, inside) = subscriber("insider", kinds.clone(), "/project");
+        let (outsider, outside) = subscriber("outsider", kinds, "/elsewhere");
+        for plugin in [insider, outsider] {
+            manager.register_plugin(Box::new(plugin)).await.unwrap();
+        }
+
+        let name = "main.rs".to_string();
+        let path = Some(PathBuf::from("/project/src/main.rs"));
+        let events = [
+            DocumentEvent::LanguageChanged { name: name.clone(), path: path.clone(), language: None },
+            DocumentEvent::LineEndingChanged { name: name.clone(), path: path.clone(), line_ending: LineEnding::Unix },
+            DocumentEvent::SelectionChanged { name: name.clone(), path: path.clone(), selections: Vec::new() },
+            DocumentEvent::Closed { name, path },
+        ];
+        for event in &events {
+            manager.notify_document_event(event).await;
+        }
+
+        assert_eq!(inside.lock().unwrap().len(), events.len());
+        assert!(outside.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_status_items_removed_on_unload() {
+        let status_items = Arc::new(StatusItems::new());
+        let manager = PluginManager::with_status_items(status_items.clone());
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+        status_items.set("other", "clock", StatusItem::new("12:00").with_priority(-1));
+
+        let token = CancellationToken::new();
+        manager.execute("test", "branch", serde_json::json!({}), &token).await.unwrap();
+        let texts: Vec<_> = status_items.items().into_iter().map(|item| item.text).collect();
+        assert_eq!(texts, vec!["Git: main ↑2", "12:00"]);
+        assert_eq!(status_items.items()[0].tooltip.as_deref(), Some("2 commits ahead"));
+
+        // Unloading the plugin only removes its own items
+        manager.unregister_plugin("test").await.unwrap();
+        let texts: Vec<_> = status_items.items().into_iter().map(|item| item.text).collect();
+        assert_eq!(texts, vec!["12:00"]);
+    }
+
+    #[tokio::test]
+    async fn test_diagnostic_providers_removed_on_unload() {
+        let diagnostics = Arc::new(DiagnosticRegistry::new());
+        let manager = PluginManager::new().with_diagnostics(diagnostics.clone());
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "trailing \n").unwrap();
+
+        let token = CancellationToken::new();
+        manager.execute("test", "lint", serde_json::json!({}), &token).await.unwrap();
+        diagnostics.apply(&mut doc);
+        assert_eq!(doc.markers().diagnostics().len(), 1);
+
+        manager.unregister_plugin("test").await.unwrap();
+        diagnostics.apply(&mut doc);
+        assert!(doc.markers().diagnostics().is_empty());
+    }
+
+    /// Lets spawned tasks run until `done` holds
+    async fn wait_until(done: impl Fn() -> bool) {
+        for _ in 0..100 {
+            if done() {
+                return;
+            }
+            tokio::task::yield_now().await;
+        }
+        panic!("condition not reached");
+    }
+
+    #[tokio::test]
+    async fn test_plugin_commands_in_palette() {
+        let actions = Arc::new(ActionRegistry::new());
+        let manager = PluginManager::new().with_actions(actions.clone());
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+        let status_items = manager.status_items().clone();
+
+        let matching: Vec<_> = actions.palette_matching("test: show").into_iter().map(|a| a.label()).collect();
+        assert_eq!(matching, vec!["test: Show Branch"]);
+
+        // Invoking the action runs the command through the manager
+        actions.invoke("test.branch", serde_json::json!({})).unwrap();
+        wait_until(|| !status_items.items().is_empty()).await;
+        assert_eq!(status_items.items()[0].text, "Git: main ↑2");
+
+        // Failures show up in the status bar
+        actions.invoke("test.fail", serde_json::json!({})).unwrap();
+        wait_until(|| status_items.items().len() == 2).await;
+        let failure = status_items.items().into_iter().find(|item| item.text == "test: Break Things failed").unwrap();
+        assert_eq!(failure.tooltip.as_deref(), Some("something went wrong"));
+
+        manager.unregister_plugin("test").await.unwrap();
+        assert!(actions.get("test.branch").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_incompatible_api_version() {
+        let manager = PluginManager::new();
+
+        let mut plugin = test_plugin();
+        plugin.api_version = ApiVersion::new(API_VERSION.major + 1, 0);
+        let err = manager.register_plugin(Box::new(plugin)).await.unwrap_err();
+        assert!(matches!(
+            err,
+            PluginError::IncompatibleApiVersion { ref plugin, expected, .. }
+                if plugin == "test" && expected == API_VERSION
+        ));
+        assert_eq!(manager.get_plugin_state("test").await, None);
+
+        // A newer minor version is still accepted
+        let mut plugin = test_plugin();
+        plugin.api_version = ApiVersion::new(API_VERSION.major, API_VERSION.minor + 1);
+        manager.register_plugin(Box::new(plugin)).await.unwrap();
+        assert_eq!(manager.get_plugin_state("test").await, Some(PluginState::Loaded));
+    }
+
+    #[tokio::test]
+    async fn test_plugin_settings_validated() {
+        let token = CancellationToken::new();
+        let manager = PluginManager::new();
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+        let settings = manager.execute("test", "settings", serde_json::json!({}), &token).await.unwrap();
+        assert_eq!(settings.value, serde_json::json!({"remote": "origin"}));
+
+        let stored = HashMap::from([("test".to_string(), serde_json::json!({"remote": 42}))]);
+        let manager = PluginManager::new().with_settings(stored);
+        let err = manager.register_plugin(Box::new(test_plugin())).await.unwrap_err();
+        assert!(matches!(err, PluginError::InvalidConfig { ref path, .. } if path == "/remote"));
+        assert_eq!(manager.get_plugin_state("test").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_execute_typed() {
+        let manager = PluginManager::new();
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+        let token = CancellationToken::new();
+
+        let response: StatusResponse = manager
+            .execute_typed("test", "status", serde_json::json!({}), &token)
+            .await
+            .unwrap();
+        assert_eq!(response.status, "ok");
+
+        // Value that does not match the requested type
+        let result = manager
+            .execute_typed::<CountResponse>("test", "status", serde_json::json!({}), &token)
+            .await;
+        assert!(matches!(result, Err(PluginError::InvalidResponse { .. })));
+
+        // Error object returned by the plugin
+        let response = manager.execute("test", "fail", serde_json::json!({}), &token).await.unwrap();
+        assert!(response.is_error());
+        assert_eq!(response.diagnostics, vec!["something went wrong".to_string()]);
+
+        let result = manager
+            .execute_typed::<StatusResponse>("test", "fail", serde_json::json!({}), &token)
+            .await;
+        match result {
+            Err(PluginError::CommandFailed { message, .. }) => {
+                assert_eq!(message, "something went wrong");
+            }
+            other => panic!("Expected CommandFailed, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancel_execute_command() {
+        let manager = PluginManager::new();
+        manager.register_plugin(Box::new(test_plugin())).await.unwrap();
+
+        let token = CancellationToken::new();
+        let cancel = token.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            cancel.cancel();
+        });
+
+        let result = manager.execute_command("test", "slow", serde_json::json!({}), &token).await;
+        assert!(matches!(result, Err(PluginError::Cancelled { .. })));
+
+        // The plugin keeps serving commands after a cancellation
+        let token = CancellationToken::new();
+        assert!(manager.execute_command("test", "echo", serde_json::json!({}), &token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_plugin_completions_merged_with_lsp() {
+        let manager = PluginManager::new();
+        let dictionary = MockPlugin::new("dictionary").with_completions(vec![
+            CompletionItem::new("printable").with_detail("dictionary word"),
+            CompletionItem::new("println"),
+        ]);
+        let requests = dictionary.completion_requests();
+        manager.register_plugin(Box::new(dictionary)).await.unwrap();
+        // Plugins without the capability are not asked
+        let silent = MockPlugin::new("silent")
+            .with_completions(vec![CompletionItem::new("print_hidden")])
+            .with_capabilities(Vec::new());
+        let silent_requests = silent.completion_requests();
+        manager.register_plugin(Box::new(silent)).await.unwrap();
+
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() { prin }").unwrap();
+        let context = CompletionContext::from_document(&doc, 16);
+        let items = manager.provide_completions(&context).await;
+        assert_eq!(requests.read()[0].prefix, "prin");
+        assert!(silent_requests.read().is_empty());
+        assert_eq!(items[0].source.as_deref(), Some("dictionary"));
+
+        let lsp = editor_lsp::CompletionResponse::Array(vec![
+            editor_lsp::CompletionItem { label: "println".to_string(), ..Default::default() },
+            editor_lsp::CompletionItem { label: "print".to_string(), ..Default::default() },
+        ]);
+        let merged = editor_lsp::filter_completions(editor_lsp::merge_completions(lsp, items), &context.prefix, 1);
+        let labels: Vec<&str> = merged.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["print", "printable", "println"]);
+        let printable = merged.iter().find(|item| item.label == "printable").unwrap();
+        assert_eq!(printable.detail.as_deref(), Some("dictionary word"));
+
+        // Disabled plugins are skipped
+        manager.shutdown_plugin("dictionary").await.unwrap();
+        assert!(manager.provide_completions(&context).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_slow_plugin_completions_left_out() {
+        let manager = Arc::new(PluginManager::new());
+        let slow = MockPlugin::new("slow")
+            .with_completions(vec![CompletionItem::new("sleepy")])
+            .with_completion_delay(Duration::from_secs(10));
+        manager.register_plugin(Box::new(slow)).await.unwrap();
+        let fast = MockPlugin::new("fast").with_completions(vec![CompletionItem::new("speedy")]);
+        manager.register_plugin(Box::new(fast)).await.unwrap();
+
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "s").unwrap();
+        let context = CompletionContext::from_document(&doc, 1);
+        let request = tokio::spawn({
+            let manager = manager.clone();
+            async move { manager.provide_completions(&context).await }
+        });
+
+        // Plugins can be registered while completions are pending
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let registered = manager.register_plugin(Box::new(MockPlugin::new("late")));
+        tokio::time::timeout(Duration::from_millis(100), registered).await.unwrap().unwrap();
+
+        let items = tokio::time::timeout(COMPLETION_TIMEOUT * 2, request).await.unwrap().unwrap();
+        let labels: Vec<&str> = items.iter().map(|item| item.label.as_str()).collect();
+        assert_eq!(labels, vec!["speedy"]);
+    }
 }
@@ -1,8 +1,12 @@
 //! Native plugin implementation
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use libloading::{Library, Symbol};
-use crate::{Plugin, PluginConfig, PluginMetadata, Result, PluginError};
+use editor_core::{CompletionContext, CompletionItem, DocumentEvent};
+use crate::{
+    completions_from_command, DocumentEventKind, Permission, Plugin, PluginCapability, PluginConfig, PluginMetadata,
+    Result, PluginError, Sandbox,
+};
 
 /// Native plugin
 #[allow(dead_code)]
@@ -11,6 +15,14 @@ pub struct NativePlugin {
     library: Library,
     /// Plugin metadata
     metadata: PluginMetadata,
+    /// Directory the plugin was loaded from
+    directory: PathBuf,
+    /// Document events the manifest subscribes to
+    events: Vec<DocumentEventKind>,
+    /// Permissions the manifest declares
+    permissions: Vec<Permission>,
+    /// Capabilities the manifest declares
+    capabilities: Vec<PluginCapability>,
     /// Plugin interface
     interface: Box<dyn PluginInterface>,
 }
@@ -23,19 +35,22 @@ pub trait PluginInterface: Send + Sync {
     fn shutdown(&mut self) -> Result<()>;
     /// Executes a command
     fn execute(&self, command: &str, args: serde_json::Value) -> Result<serde_json::Value>;
+    /// Reacts to a document event the manifest subscribes to
+    fn on_document_event(&self, _event: &DocumentEvent) {}
 }
 
 impl NativePlugin {
     /// Loads a native plugin from a path
+    ///
+    /// The sandbox cannot stop native code from using the network or running
+    /// processes, so a plugin whose manifest requests either is refused with
+    /// `PluginError::UntrustedNativePlugin` unless the sandbox configuration
+    /// trusts native plugins.
     pub async fn load(path: impl AsRef<Path>, config: PluginConfig) -> Result<Self> {
         let path = path.as_ref();
-        let library_path = if cfg!(target_os = "windows") {
-            path.join(&config.manifest.entry_point).with_extension("dll")
-        } else if cfg!(target_os = "macos") {
-            path.join(&config.manifest.entry_point).with_extension("dylib")
-        } else {
-            path.join(&config.manifest.entry_point).with_extension("so")
-        };
+        let sandbox = Sandbox::new(config.sandbox.clone());
+        sandbox.check_native_permissions(&config.manifest.name, &config.manifest.permissions)?;
+        let library_path = path.join(Self::library_file(&config.manifest.entry_point));
 
         unsafe {
             let library = Library::new(library_path)
@@ -56,10 +71,27 @@ impl NativePlugin {
                     version: config.manifest.version,
                     description: config.manifest.description,
                 },
+                directory: path.to_path_buf(),
+                events: config.manifest.events,
+                permissions: config.manifest.permissions,
+                capabilities: config.manifest.capabilities,
                 interface,
             })
         }
     }
+
+    /// Returns the file name of the shared library for an entry point on
+    /// this platform
+    pub(crate) fn library_file(entry_point: &str) -> PathBuf {
+        let extension = if cfg!(target_os = "windows") {
+            "dll"
+        } else if cfg!(target_os = "macos") {
+            "dylib"
+        } else {
+            "so"
+        };
+        Path::new(entry_point).with_extension(extension)
+    }
 }
 
 #[async_trait::async_trait]
@@ -68,6 +100,30 @@ impl Plugin for NativePlugin {
         &self.metadata
     }
 
+    fn directory(&self) -> Option<&Path> {
+        Some(&self.directory)
+    }
+
+    fn document_events(&self) -> Vec<DocumentEventKind> {
+        self.events.clone()
+    }
+
+    fn permissions(&self) -> Vec<Permission> {
+        self.permissions.clone()
+    }
+
+    fn capabilities(&self) -> Vec<PluginCapability> {
+        self.capabilities.clone()
+    }
+
+    async fn provide_completions(&self, context: &CompletionContext) -> Vec<CompletionItem> {
+        completions_from_command(self, context).await
+    }
+
+    fn on_document_event(&self, event: &DocumentEvent) {
+        self.interface.on_document_event(event)
+    }
+
     async fn initialize(&mut self) -> Result<()> {
         self.interface.initialize()
     }
@@ -6,7 +6,7 @@ use tokio::sync::RwLock;
 use serde::{Serialize, Deserialize};
 
 /// Plugin metadata
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct PluginMetadata {
     /// Plugin name
     pub name: String,
@@ -1,4 +1,13 @@
 //! Sandbox implementation for plugin isolation
+//!
+//! The checks only bind plugins that cannot reach the system except through
+//! the editor. WASM plugins have no network or process access besides the
+//! host imports, which call `Sandbox::check_network_access` and
+//! `Sandbox::check_command_execution` first. Native plugins run in the
+//! editor's process and can use `std::net` and `std::process` directly, so
+//! for them the checks are advisory; native plugins requesting network or
+//! process permissions are refused unless `SandboxConfig::trust_native` is
+//! set.
 
 use std::path::PathBuf;
 use std::collections::HashSet;
@@ -22,6 +31,9 @@ pub struct SandboxConfig {
     pub memory_limit: usize,
     /// CPU time limit in milliseconds
     pub cpu_limit: u64,
+    /// Load native plugins requesting network or process permissions, which
+    /// the sandbox cannot enforce
+    pub trust_native: bool,
 }
 
 impl Default for SandboxConfig {
@@ -33,6 +45,7 @@ impl Default for SandboxConfig {
             allowed_commands: HashSet::new(),
             memory_limit: 100 * 1024 * 1024, // 100MB
             cpu_limit: 1000, // 1 second
+            trust_native: false,
         }
     }
 }
@@ -99,9 +112,77 @@ impl SandboxConfig {
         self.cpu_limit = limit;
         self
     }
+
+    /// Sets whether native plugins requesting network or process permissions
+    /// are loaded
+    pub fn set_trust_native(&mut self, trust: bool) -> &mut Self {
+        self.trust_native = trust;
+        self
+    }
+
+    /// Returns the configuration with additional allowed file system paths
+    pub fn with_allowed_paths<I, P>(mut self, paths: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<PathBuf>,
+    {
+        self.allow_paths(paths);
+        self
+    }
+
+    /// Returns the configuration with additional allowed network hosts
+    pub fn with_allowed_hosts<I, S>(mut self, hosts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_hosts(hosts);
+        self
+    }
+
+    /// Returns the configuration with additional allowed network ports
+    pub fn with_allowed_ports<I>(mut self, ports: I) -> Self
+    where
+        I: IntoIterator<Item = u16>,
+    {
+        self.allow_ports(ports);
+        self
+    }
+
+    /// Returns the configuration with additional allowed system commands
+    pub fn with_allowed_commands<I, S>(mut self, commands: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.allow_commands(commands);
+        self
+    }
+
+    /// Returns the configuration with the given memory limit
+    pub fn with_max_memory(mut self, limit: usize) -> Self {
+        self.with_memory_limit(limit);
+        self
+    }
+
+    /// Returns the configuration with the given CPU time limit
+    pub fn with_max_cpu_time(mut self, limit: u64) -> Self {
+        self.with_cpu_limit(limit);
+        self
+    }
+
+    /// Returns the configuration trusting native plugins, or not
+    pub fn with_trust_native(mut self, trust: bool) -> Self {
+        self.set_trust_native(trust);
+        self
+    }
 }
 
 /// Sandbox for plugin isolation
+///
+/// Enforced for WASM plugins through the host imports; advisory for native
+/// plugins, see the module documentation.
+#[derive(Debug, Clone)]
 pub struct Sandbox {
     /// Sandbox configuration
     config: SandboxConfig,
@@ -188,6 +269,29 @@ impl Sandbox {
         Ok(())
     }
 
+    /// Checks that a native plugin may be loaded with the permissions it requests
+    ///
+    /// A native plugin can open connections and run processes without asking
+    /// the sandbox, so one requesting network or process permissions is only
+    /// loaded if the configuration trusts native plugins.
+    pub fn check_native_permissions(&self, plugin: &str, permissions: &[Permission]) -> Result<()> {
+        if self.config.trust_native {
+            return Ok(());
+        }
+        let unenforceable = permissions.iter().find_map(|permission| match permission {
+            Permission::Network { .. } => Some("network"),
+            Permission::Process { .. } => Some("process"),
+            Permission::FileSystem { .. } => None,
+        });
+        match unenforceable {
+            Some(permission) => Err(PluginError::UntrustedNativePlugin {
+                plugin: plugin.to_string(),
+                permission: permission.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
     /// Verifies permissions against sandbox configuration
     pub fn verify_permissions(&self, permissions: &[Permission]) -> Result<()> {
         for permission in permissions {
@@ -238,6 +342,40 @@ mod tests {
         assert_eq!(config.cpu_limit, 2000);
     }
 
+    #[test]
+    fn test_sandbox_configuration_chained() {
+        let manifest = crate::PluginManifest {
+            name: "sandboxed".to_string(),
+            version: "0.1.0".to_string(),
+            description: "Sandboxed plugin".to_string(),
+            author: "Test Author".to_string(),
+            license: "MIT".to_string(),
+            entry_point: "lib.rs".to_string(),
+            plugin_type: crate::PluginType::Native,
+            dependencies: vec![],
+            permissions: vec![],
+            events: vec![],
+            capabilities: vec![],
+        };
+        let config = crate::PluginConfig::new(manifest).with_sandbox(
+            SandboxConfig::new()
+                .with_allowed_paths(["/tmp"])
+                .with_allowed_hosts(["localhost"])
+                .with_allowed_ports([8000])
+                .with_allowed_commands(["git"])
+                .with_max_memory(64 * 1024 * 1024)
+                .with_max_cpu_time(500),
+        );
+
+        let sandbox = &config.sandbox;
+        assert!(sandbox.allowed_paths.contains(&PathBuf::from("/tmp")));
+        assert!(sandbox.allowed_hosts.contains("localhost"));
+        assert!(sandbox.allowed_ports.contains(&8000));
+        assert!(sandbox.allowed_commands.contains("git"));
+        assert_eq!(sandbox.memory_limit, 64 * 1024 * 1024);
+        assert_eq!(sandbox.cpu_limit, 500);
+    }
+
     #[test]
     fn test_sandbox_permission_verification() {
         let mut config = SandboxConfig::new();
@@ -257,4 +395,25 @@ mod tests {
         assert!(sandbox.check_network_access("example.com", 8000).is_err());
         assert!(sandbox.check_network_access("localhost", 8001).is_err());
     }
+
+    #[test]
+    fn test_native_plugins_need_trust_for_network() {
+        let network = Permission::Network { hosts: vec!["localhost".to_string()], ports: vec![8000] };
+        let process = Permission::Process { commands: vec!["git".to_string()] };
+        let files = Permission::FileSystem { paths: vec![PathBuf::from("/tmp")], read_only: true };
+
+        let sandbox = Sandbox::new(SandboxConfig::new());
+        assert!(sandbox.check_native_permissions("files", &[files.clone()]).is_ok());
+        assert!(matches!(
+            sandbox.check_native_permissions("fetch", &[files.clone(), network.clone()]),
+            Err(PluginError::UntrustedNativePlugin { permission, .. }) if permission == "network"
+        ));
+        assert!(matches!(
+            sandbox.check_native_permissions("git", &[process.clone()]),
+            Err(PluginError::UntrustedNativePlugin { permission, .. }) if permission == "process"
+        ));
+
+        let trusted = Sandbox::new(SandboxConfig::new().with_trust_native(true));
+        assert!(trusted.check_native_permissions("fetch", &[network, process, files]).is_ok());
+    }
 }
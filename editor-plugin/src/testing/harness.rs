@@ -4,9 +4,11 @@ use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use tokio::sync::{RwLock, mpsc};
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 
+use super::PluginEventKind;
 use crate::{
-    Plugin, PluginConfig, PluginEvent, PluginMetadata,
+    Plugin, PluginConfig, PluginError, PluginEvent, PluginMetadata, PluginResponse, PluginState,
     loader::PluginLoader, Result,
 };
 
@@ -42,8 +44,19 @@ impl TestHarness {
     /// Loads a plugin for testing
     pub async fn load_plugin(&self, path: impl AsRef<Path>) -> Result<()> {
         let plugin = self.loader.load(path).await?;
-        let name = plugin.metadata().name.clone();
-        self.plugins.write().await.insert(name, plugin);
+        self.add_plugin(plugin).await
+    }
+
+    /// Initializes a plugin built into the test, e.g. an example's plugin
+    /// type, and adds it under its name
+    ///
+    /// Like loading, this sends `Loaded` and then `StateChanged` to `Running`.
+    pub async fn add_plugin(&self, mut plugin: Box<dyn Plugin>) -> Result<()> {
+        let metadata = plugin.metadata().clone();
+        self.emit(PluginEvent::Loaded(metadata.clone()));
+        plugin.initialize().await?;
+        self.plugins.write().await.insert(metadata.name.clone(), plugin);
+        self.emit(PluginEvent::StateChanged { metadata, state: PluginState::Running });
         Ok(())
     }
 
@@ -76,11 +89,72 @@ impl TestHarness {
         }
     }
 
+    /// Executes a plugin command and deserializes its result
+    ///
+    /// A result with an `"error"` string fails with `CommandFailed` and sends
+    /// an `Error` event, as the plugin manager does; one that does not fit
+    /// `T` fails with `InvalidResponse`.
+    pub async fn execute_and_get<T: DeserializeOwned>(
+        &self,
+        plugin_name: &str,
+        command: &str,
+        args: serde_json::Value,
+    ) -> Result<T> {
+        let value = self.execute_command(plugin_name, command, args).await?;
+        let response = PluginResponse::from_value(value);
+        if let Some(message) = response.error() {
+            if let Some(plugin) = self.plugins.read().await.get(plugin_name) {
+                self.emit(PluginEvent::Error { metadata: plugin.metadata().clone(), error: message.to_string() });
+            }
+            return Err(PluginError::CommandFailed {
+                plugin: plugin_name.to_string(),
+                command: command.to_string(),
+                message: message.to_string(),
+            });
+        }
+        serde_json::from_value(response.value).map_err(|e| PluginError::InvalidResponse {
+            plugin: plugin_name.to_string(),
+            command: command.to_string(),
+            reason: e.to_string(),
+        })
+    }
+
     /// Waits for a specific event
     pub async fn wait_for_event(&mut self) -> Option<PluginEvent> {
         self.event_rx.recv().await
     }
 
+    /// Returns the events sent so far and not yet received, oldest first
+    pub fn drain_events(&mut self) -> Vec<PluginEvent> {
+        let mut events = Vec::new();
+        while let Ok(event) = self.event_rx.try_recv() {
+            events.push(event);
+        }
+        events
+    }
+
+    /// Drains the pending events and returns the first of `kind`
+    ///
+    /// # Panics
+    ///
+    /// Panics, listing the pending events, if none is of `kind`.
+    #[track_caller]
+    pub fn assert_event(&mut self, kind: PluginEventKind) -> PluginEvent {
+        let events = self.drain_events();
+        match events.iter().position(|event| PluginEventKind::of(event) == kind) {
+            Some(index) => events.into_iter().nth(index).expect("index of a pending event"),
+            None => panic!("expected a {:?} event, got {:?}", kind, events),
+        }
+    }
+
+    /// Sends an event without waiting; events beyond the channel capacity
+    /// are dropped, so tests that never drain them do not block
+    fn emit(&self, event: PluginEvent) {
+        if let Err(e) = self.event_tx.try_send(event) {
+            log::warn!("Dropped test harness event: {}", e);
+        }
+    }
+
     /// Sends a test event
     pub async fn send_event(&self, event: PluginEvent) -> Result<()> {
         self.event_tx.send(event).await.map_err(|e| {
@@ -1,9 +1,11 @@
 //! Mock implementations for testing
 
 use std::sync::Arc;
+use std::time::Duration;
 use async_trait::async_trait;
 use parking_lot::RwLock;
-use crate::{Plugin, PluginMetadata, Result, PluginEvent};
+use editor_core::{CompletionContext, CompletionItem};
+use crate::{Plugin, PluginCapability, PluginMetadata, Result, PluginEvent};
 
 /// Mock plugin for testing
 #[derive(Default)]
@@ -16,6 +18,14 @@ pub struct MockPlugin {
     shutdown_count: Arc<RwLock<usize>>,
     /// Execute call count and history
     execute_history: Arc<RwLock<Vec<(String, serde_json::Value)>>>,
+    /// Declared capabilities
+    capabilities: Vec<PluginCapability>,
+    /// Items returned by `provide_completions`
+    completions: Vec<CompletionItem>,
+    /// Contexts `provide_completions` was called with
+    completion_requests: Arc<RwLock<Vec<CompletionContext>>>,
+    /// Time `provide_completions` takes to answer
+    completion_delay: Duration,
 }
 
 impl MockPlugin {
@@ -30,9 +40,35 @@ impl MockPlugin {
             initialize_count: Arc::new(RwLock::new(0)),
             shutdown_count: Arc::new(RwLock::new(0)),
             execute_history: Arc::new(RwLock::new(Vec::new())),
+            capabilities: Vec::new(),
+            completions: Vec::new(),
+            completion_requests: Arc::new(RwLock::new(Vec::new())),
+            completion_delay: Duration::ZERO,
         }
     }
 
+    /// Offers `items` from `provide_completions`, declaring the completions
+    /// capability
+    pub fn with_completions(mut self, items: Vec<CompletionItem>) -> Self {
+        self.completions = items;
+        if !self.capabilities.contains(&PluginCapability::Completions) {
+            self.capabilities.push(PluginCapability::Completions);
+        }
+        self
+    }
+
+    /// Makes `provide_completions` wait before answering, like a slow plugin
+    pub fn with_completion_delay(mut self, delay: Duration) -> Self {
+        self.completion_delay = delay;
+        self
+    }
+
+    /// Replaces the declared capabilities
+    pub fn with_capabilities(mut self, capabilities: Vec<PluginCapability>) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
     /// Returns the number of times initialize was called
     pub fn initialize_count(&self) -> usize {
         *self.initialize_count.read()
@@ -47,6 +83,12 @@ impl MockPlugin {
     pub fn execute_history(&self) -> Vec<(String, serde_json::Value)> {
         self.execute_history.read().clone()
     }
+
+    /// Returns a handle to the contexts completions were requested for,
+    /// which stays valid once the plugin is moved into a manager
+    pub fn completion_requests(&self) -> Arc<RwLock<Vec<CompletionContext>>> {
+        self.completion_requests.clone()
+    }
 }
 
 #[async_trait]
@@ -55,6 +97,18 @@ impl Plugin for MockPlugin {
         &self.metadata
     }
 
+    fn capabilities(&self) -> Vec<PluginCapability> {
+        self.capabilities.clone()
+    }
+
+    async fn provide_completions(&self, context: &CompletionContext) -> Vec<CompletionItem> {
+        self.completion_requests.write().push(context.clone());
+        if !self.completion_delay.is_zero() {
+            tokio::time::sleep(self.completion_delay).await;
+        }
+        self.completions.clone()
+    }
+
     async fn initialize(&mut self) -> Result<()> {
         *self.initialize_count.write() += 1;
         Ok(())
@@ -94,11 +148,9 @@ impl MockEventHandler {
     pub fn events(&self) -> Vec<PluginEvent> {
         self.events.read().clone()
     }
-}
 
-#[async_trait]
-impl crate::event::EventHandler for MockEventHandler {
-    async fn handle_event(&self, event: PluginEvent) {
+    /// Records an event
+    pub async fn handle_event(&self, event: PluginEvent) {
         self.events.write().push(event);
     }
 }
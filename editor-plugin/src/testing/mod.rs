@@ -10,6 +10,31 @@ use std::path::PathBuf;
 use tokio::sync::mpsc;
 use crate::{Plugin, PluginEvent, Result};
 
+/// Kinds of `PluginEvent`, for asserting on events without matching their data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginEventKind {
+    /// `PluginEvent::Loaded`
+    Loaded,
+    /// `PluginEvent::Unloaded`
+    Unloaded,
+    /// `PluginEvent::StateChanged`
+    StateChanged,
+    /// `PluginEvent::Error`
+    Error,
+}
+
+impl PluginEventKind {
+    /// Returns the kind of an event
+    pub fn of(event: &PluginEvent) -> Self {
+        match event {
+            PluginEvent::Loaded(_) => Self::Loaded,
+            PluginEvent::Unloaded(_) => Self::Unloaded,
+            PluginEvent::StateChanged { .. } => Self::StateChanged,
+            PluginEvent::Error { .. } => Self::Error,
+        }
+    }
+}
+
 /// Test context for plugin testing
 pub struct TestContext {
     /// Temporary directory for test files
@@ -30,6 +55,22 @@ impl TestContext {
         })
     }
 
+    /// Creates a test context whose temporary directory holds the given
+    /// files, as pairs of a path relative to it and the file content
+    ///
+    /// Parent directories are created as needed.
+    pub fn with_files<'a>(files: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let context = Self::new()?;
+        for (name, content) in files {
+            let path = context.temp_dir.path().join(name);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, content)?;
+        }
+        Ok(context)
+    }
+
     /// Returns the path to the temporary directory
     pub fn temp_path(&self) -> PathBuf {
         self.temp_dir.path().to_path_buf()
@@ -88,4 +129,16 @@ mod tests {
         let content = tokio::fs::read_to_string(file_path).await.unwrap();
         assert_eq!(content, "Hello, World!");
     }
+
+    #[test]
+    fn test_context_with_files() {
+        let context = TestContext::with_files([
+            ("src/main.rs", "fn main() {}"),
+            ("README.md", "# Demo"),
+        ]).unwrap();
+
+        let read = |name: &str| std::fs::read_to_string(context.temp_path().join(name)).unwrap();
+        assert_eq!(read("src/main.rs"), "fn main() {}");
+        assert_eq!(read("README.md"), "# Demo");
+    }
 }
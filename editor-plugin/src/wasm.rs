@@ -1,9 +1,54 @@
 //! WebAssembly plugin implementation
+//!
+//! Plugins may import these host functions from the `env` module:
+//!
+//! - `host_log(level: i32, ptr: i32, len: i32)` writes the UTF-8 message at
+//!   `ptr..ptr + len` of the exported `memory` to the plugin's log sink. Levels
+//!   are 1 (error), 2 (warn), 3 (info), 4 (debug) and 5 (trace).
+//! - `host_now_millis() -> i64` returns the milliseconds since the Unix epoch.
+//!
+//! Plugins have no other way to reach the network or run processes than
+//! these imports, each checked against the plugin's sandbox:
+//!
+//! - `host_connect(ptr: i32, len: i32, port: i32) -> i32` opens a TCP
+//!   connection to the host named at `ptr..ptr + len` and returns a handle.
+//! - `host_send(handle: i32, ptr: i32, len: i32) -> i32` writes
+//!   `ptr..ptr + len` to a connection and returns the number of bytes sent.
+//! - `host_recv(handle: i32, ptr: i32, len: i32) -> i32` reads up to `len`
+//!   bytes from a connection to `ptr` and returns the number read, 0 once the
+//!   peer closed it.
+//! - `host_close(handle: i32) -> i32` closes a connection.
+//! - `host_spawn(ptr: i32, len: i32) -> i32` runs the command line at
+//!   `ptr..ptr + len`, split at whitespace, and returns its exit code. The
+//!   plugin waits until the command exits.
+//!
+//! They return `-1` if the sandbox denies the operation, `-2` if it failed
+//! and `-3` if the arguments are invalid, e.g. an unknown handle.
 
-use std::path::Path;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
 use std::sync::Mutex;
-use wasmer::{Store, Module, Instance, Value, MemoryAccessError, imports};
-use crate::{Plugin, PluginConfig, PluginMetadata, Result, PluginError};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wasmer::{
+    imports, AsStoreRef, Engine, Function, FunctionEnv, FunctionEnvMut, Instance, Memory, MemoryAccessError, Module,
+    Store, Value,
+};
+use editor_core::{CompletionContext, CompletionItem, DocumentEvent};
+use crate::{
+    completions_from_command, DocumentEventKind, Permission, Plugin, PluginCapability, PluginConfig, PluginLogSink,
+    PluginMetadata, Result, PluginError, Sandbox,
+};
+
+lazy_static::lazy_static! {
+    /// Engine shared by all plugins so cached modules can be instantiated in any store
+    static ref ENGINE: Engine = Store::default().engine().clone();
+    /// Compiled modules shared by all plugins
+    static ref MODULE_CACHE: ModuleCache = ModuleCache::new();
+}
 
 // Add error conversions for wasmer errors
 impl From<wasmer::ExportError> for PluginError {
@@ -36,12 +81,252 @@ impl From<serde_json::Error> for PluginError {
     }
 }
 
+/// Cache of compiled WebAssembly modules keyed by path and modification time
+struct ModuleCache {
+    /// Compiled modules with the modification time they were compiled from
+    modules: Mutex<HashMap<PathBuf, (SystemTime, Module)>>,
+    /// Number of modules compiled so far
+    compilations: AtomicUsize,
+}
+
+impl ModuleCache {
+    /// Creates an empty module cache
+    fn new() -> Self {
+        Self {
+            modules: Mutex::new(HashMap::new()),
+            compilations: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the compiled module for a path, compiling it if it is new or has changed
+    ///
+    /// Compilation runs on the blocking thread pool so it does not stall the runtime.
+    async fn get_or_compile(&self, path: &Path) -> Result<Module> {
+        let modified = tokio::fs::metadata(path).await?.modified()?;
+
+        if let Some((cached_at, module)) = self.modules.lock().unwrap().get(path) {
+            if *cached_at == modified {
+                return Ok(module.clone());
+            }
+        }
+
+        let wasm_bytes = tokio::fs::read(path).await?;
+        let module = tokio::task::spawn_blocking(move || Module::new(&*ENGINE, wasm_bytes))
+            .await
+            .map_err(|e| PluginError::LoadError(e.to_string()))?
+            .map_err(|e| PluginError::LoadError(e.to_string()))?;
+
+        self.compilations.fetch_add(1, Ordering::Relaxed);
+        self.modules.lock().unwrap()
+            .insert(path.to_path_buf(), (modified, module.clone()));
+
+        Ok(module)
+    }
+
+    /// Returns the number of modules compiled so far
+    fn compilations(&self) -> usize {
+        self.compilations.load(Ordering::Relaxed)
+    }
+}
+
+/// Returned by a host import when the sandbox denies the operation
+const HOST_DENIED: i32 = -1;
+/// Returned by a host import when the operation failed
+const HOST_FAILED: i32 = -2;
+/// Returned by a host import when its arguments are invalid
+const HOST_INVALID: i32 = -3;
+
+/// State available to host functions
+struct HostEnv {
+    /// Plugin memory, set once the instance is created
+    memory: Option<Memory>,
+    /// Sink receiving `host_log` messages
+    log_sink: PluginLogSink,
+    /// Checks the network and process imports
+    sandbox: Sandbox,
+    /// Connections opened by `host_connect`, by handle
+    connections: HashMap<i32, TcpStream>,
+    /// Handle of the next connection
+    next_handle: i32,
+}
+
+impl HostEnv {
+    /// Creates the state of a plugin's host functions
+    fn new(log_sink: PluginLogSink, sandbox: Sandbox) -> Self {
+        Self {
+            memory: None,
+            log_sink,
+            sandbox,
+            connections: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    /// Logs a warning to the plugin's log sink and returns `code`
+    fn warn(&self, message: &str, code: i32) -> i32 {
+        self.log_sink.log(log::Level::Warn, message);
+        code
+    }
+}
+
+/// Reads `ptr..ptr + len` of the plugin's memory
+fn read_guest(
+    memory: Option<&Memory>,
+    store: &impl AsStoreRef,
+    ptr: i32,
+    len: i32,
+) -> std::result::Result<Vec<u8>, String> {
+    let memory = memory.ok_or("plugin exports no memory")?;
+    let view = memory.view(store);
+    let (offset, len) = (ptr as u32 as u64, len.max(0) as u64);
+    if offset + len > view.data_size() {
+        return Err(format!("{}..{} is out of bounds", offset, offset + len));
+    }
+
+    let mut buffer = vec![0u8; len as usize];
+    view.read(offset, &mut buffer).map_err(|e| e.to_string())?;
+    Ok(buffer)
+}
+
+/// Reads the UTF-8 string at `ptr..ptr + len` of the plugin's memory
+fn read_guest_string(memory: Option<&Memory>, store: &impl AsStoreRef, ptr: i32, len: i32) -> Option<String> {
+    read_guest(memory, store, ptr, len).ok().and_then(|bytes| String::from_utf8(bytes).ok())
+}
+
+/// Implements the `host_log` import
+fn host_log(mut env: FunctionEnvMut<HostEnv>, level: i32, ptr: i32, len: i32) {
+    let (host, store) = env.data_and_store_mut();
+    if host.memory.is_none() {
+        return;
+    }
+    let buffer = match read_guest(host.memory.as_ref(), &store, ptr, len) {
+        Ok(buffer) => buffer,
+        Err(e) => {
+            log::warn!("Failed to read plugin log message: {}", e);
+            return;
+        }
+    };
+
+    let level = log::Level::iter()
+        .nth((level as usize).wrapping_sub(1))
+        .unwrap_or(log::Level::Info);
+    host.log_sink.log(level, &String::from_utf8_lossy(&buffer));
+}
+
+/// Implements the `host_connect` import
+fn host_connect(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32, port: i32) -> i32 {
+    let (host, store) = env.data_and_store_mut();
+    let (Some(name), Ok(port)) = (read_guest_string(host.memory.as_ref(), &store, ptr, len), u16::try_from(port)) else {
+        return HOST_INVALID;
+    };
+    if let Err(e) = host.sandbox.check_network_access(&name, port) {
+        return host.warn(&e.to_string(), HOST_DENIED);
+    }
+
+    match TcpStream::connect((name.as_str(), port)) {
+        Ok(stream) => {
+            let handle = host.next_handle;
+            host.next_handle += 1;
+            host.connections.insert(handle, stream);
+            handle
+        }
+        Err(e) => host.warn(&format!("Failed to connect to {}:{}: {}", name, port, e), HOST_FAILED),
+    }
+}
+
+/// Implements the `host_send` import
+fn host_send(mut env: FunctionEnvMut<HostEnv>, handle: i32, ptr: i32, len: i32) -> i32 {
+    let (host, store) = env.data_and_store_mut();
+    let Ok(data) = read_guest(host.memory.as_ref(), &store, ptr, len) else {
+        return HOST_INVALID;
+    };
+    let Some(stream) = host.connections.get_mut(&handle) else {
+        return HOST_INVALID;
+    };
+    match stream.write_all(&data) {
+        Ok(()) => data.len() as i32,
+        Err(e) => host.warn(&format!("Failed to send on connection {}: {}", handle, e), HOST_FAILED),
+    }
+}
+
+/// Implements the `host_recv` import
+fn host_recv(mut env: FunctionEnvMut<HostEnv>, handle: i32, ptr: i32, len: i32) -> i32 {
+    let (host, store) = env.data_and_store_mut();
+    let (Some(memory), Some(stream)) = (host.memory.as_ref(), host.connections.get_mut(&handle)) else {
+        return HOST_INVALID;
+    };
+    let view = memory.view(&store);
+    let (offset, len) = (ptr as u32 as u64, len.max(0) as u64);
+    if offset + len > view.data_size() {
+        return HOST_INVALID;
+    }
+
+    let mut buffer = vec![0u8; len as usize];
+    let read = match stream.read(&mut buffer) {
+        Ok(read) => read,
+        Err(e) => return host.warn(&format!("Failed to receive on connection {}: {}", handle, e), HOST_FAILED),
+    };
+    match view.write(offset, &buffer[..read]) {
+        Ok(()) => read as i32,
+        Err(_) => HOST_INVALID,
+    }
+}
+
+/// Implements the `host_close` import
+fn host_close(mut env: FunctionEnvMut<HostEnv>, handle: i32) -> i32 {
+    match env.data_mut().connections.remove(&handle) {
+        Some(_) => 0,
+        None => HOST_INVALID,
+    }
+}
+
+/// Implements the `host_spawn` import
+fn host_spawn(mut env: FunctionEnvMut<HostEnv>, ptr: i32, len: i32) -> i32 {
+    let (host, store) = env.data_and_store_mut();
+    let Some(command_line) = read_guest_string(host.memory.as_ref(), &store, ptr, len) else {
+        return HOST_INVALID;
+    };
+    let mut words = command_line.split_whitespace();
+    let Some(program) = words.next() else {
+        return HOST_INVALID;
+    };
+    if let Err(e) = host.sandbox.check_command_execution(program) {
+        return host.warn(&e.to_string(), HOST_DENIED);
+    }
+
+    let status = Command::new(program)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+    match status {
+        Ok(status) => status.code().unwrap_or(HOST_FAILED),
+        Err(e) => host.warn(&format!("Failed to run {}: {}", program, e), HOST_FAILED),
+    }
+}
+
+/// Implements the `host_now_millis` import
+fn host_now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as i64)
+}
+
 /// WebAssembly plugin
 pub struct WasmPlugin {
     /// Plugin instance
     instance: Instance,
     /// Plugin metadata
     metadata: PluginMetadata,
+    /// Directory the plugin was loaded from
+    directory: PathBuf,
+    /// Document events the manifest subscribes to
+    events: Vec<DocumentEventKind>,
+    /// Permissions the manifest declares
+    permissions: Vec<Permission>,
+    /// Capabilities the manifest declares
+    capabilities: Vec<PluginCapability>,
     /// WebAssembly store with interior mutability
     store: Mutex<Store>,
 }
@@ -146,25 +431,44 @@ impl WasmPlugin {
     }
 
     /// Loads a WebAssembly plugin from a path
+    ///
+    /// Compiled modules are cached by path and modification time, so reloading an
+    /// unchanged plugin skips compilation.
     pub async fn load(path: impl AsRef<Path>, config: PluginConfig) -> Result<Self> {
         let path = path.as_ref();
         let wasm_path = path.join(&config.manifest.entry_point).with_extension("wasm");
 
-        let wasm_bytes = std::fs::read(&wasm_path)?;
-
-        // Create a store
-        let mut store = Store::default();
+        // Compile the WebAssembly module, or reuse a cached compilation
+        let module = MODULE_CACHE.get_or_compile(&wasm_path).await?;
 
-        // Compile the WebAssembly module
-        let module = Module::new(&store, &wasm_bytes)
-            .map_err(|e| PluginError::LoadError(e.to_string()))?;
+        // Instantiate the module, which may run its start function
+        let log_sink = config.log_sink();
+        let sandbox = Sandbox::new(config.sandbox.clone());
+        let (store, instance) = tokio::task::spawn_blocking(move || {
+            let mut store = Store::new(ENGINE.clone());
+            let env = FunctionEnv::new(&mut store, HostEnv::new(log_sink, sandbox));
+            let import_object = imports! {
+                "env" => {
+                    "host_log" => Function::new_typed_with_env(&mut store, &env, host_log),
+                    "host_now_millis" => Function::new_typed(&mut store, host_now_millis),
+                    "host_connect" => Function::new_typed_with_env(&mut store, &env, host_connect),
+                    "host_send" => Function::new_typed_with_env(&mut store, &env, host_send),
+                    "host_recv" => Function::new_typed_with_env(&mut store, &env, host_recv),
+                    "host_close" => Function::new_typed_with_env(&mut store, &env, host_close),
+                    "host_spawn" => Function::new_typed_with_env(&mut store, &env, host_spawn),
+                },
+            };
+            let instance = Instance::new(&mut store, &module, &import_object)
+                .map_err(|e| PluginError::LoadError(e.to_string()))?;
 
-        // Create import object
-        let import_object = imports! {};
-
-        // Instantiate the module
-        let instance = Instance::new(&mut store, &module, &import_object)
-            .map_err(|e| PluginError::LoadError(e.to_string()))?;
+            // Plugins without memory cannot pass log messages
+            if let Ok(memory) = instance.exports.get_memory("memory") {
+                env.as_mut(&mut store).memory = Some(memory.clone());
+            }
+            Ok::<_, PluginError>((store, instance))
+        })
+        .await
+        .map_err(|e| PluginError::LoadError(e.to_string()))??;
 
         Ok(Self {
             instance,
@@ -173,6 +477,10 @@ impl WasmPlugin {
                 version: config.manifest.version,
                 description: config.manifest.description,
             },
+            directory: path.to_path_buf(),
+            events: config.manifest.events,
+            permissions: config.manifest.permissions,
+            capabilities: config.manifest.capabilities,
             store: Mutex::new(store),
         })
     }
@@ -192,6 +500,44 @@ impl Plugin for WasmPlugin {
         &self.metadata
     }
 
+    fn directory(&self) -> Option<&Path> {
+        Some(&self.directory)
+    }
+
+    fn document_events(&self) -> Vec<DocumentEventKind> {
+        self.events.clone()
+    }
+
+    fn permissions(&self) -> Vec<Permission> {
+        self.permissions.clone()
+    }
+
+    fn capabilities(&self) -> Vec<PluginCapability> {
+        self.capabilities.clone()
+    }
+
+    async fn provide_completions(&self, context: &CompletionContext) -> Vec<CompletionItem> {
+        completions_from_command(self, context).await
+    }
+
+    /// Passes the event as JSON to the module's `on_document_event` export,
+    /// if it has one
+    fn on_document_event(&self, event: &DocumentEvent) {
+        if self.instance.exports.get_function("on_document_event").is_err() {
+            return;
+        }
+        let result = serde_json::to_vec(event)
+            .map_err(PluginError::from)
+            .and_then(|bytes| {
+                let ptr = self.alloc(bytes.len())?;
+                self.write_memory(ptr, &bytes)?;
+                self.call_wasm_function("on_document_event", &[Value::I32(ptr as i32), Value::I32(bytes.len() as i32)])
+            });
+        if let Err(e) = result {
+            log::warn!("Plugin {} failed to handle a document event: {}", self.metadata.name, e);
+        }
+    }
+
     async fn initialize(&mut self) -> Result<()> {
         self.call_wasm_function("initialize", &[])?;
         Ok(())
@@ -235,9 +581,147 @@ impl Plugin for WasmPlugin {
 mod tests {
     use super::*;
 
-    // Note: This test requires a real WebAssembly plugin to work
-    // #[tokio::test]
-    // async fn test_wasm_plugin_loading() {
-    //     // Test implementation
-    // }
+    const MODULE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "initialize"))
+            (func (export "shutdown")))
+    "#;
+
+    fn manifest(name: &str) -> crate::PluginManifest {
+        crate::PluginManifest {
+            name: name.to_string(),
+            version: "0.1.0".to_string(),
+            description: "Test plugin".to_string(),
+            author: "Test Author".to_string(),
+            license: "MIT".to_string(),
+            entry_point: "plugin".to_string(),
+            plugin_type: crate::PluginType::Wasm,
+            dependencies: vec![],
+            permissions: vec![],
+            events: vec![],
+            capabilities: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_host_imports() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("plugin.wasm"), r#"
+            (module
+                (import "env" "host_log" (func $log (param i32 i32 i32)))
+                (import "env" "host_now_millis" (func $now (result i64)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "plugin ready")
+                (func (export "initialize")
+                    (call $log (i32.const 3) (i32.const 16) (i32.const 12)))
+                (func (export "now") (result i64)
+                    (call $now))
+                (func (export "shutdown")))
+        "#).unwrap();
+
+        let messages = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let sink = {
+            let messages = messages.clone();
+            PluginLogSink::new(move |level, message| {
+                messages.lock().unwrap().push((level, message.to_string()));
+            })
+        };
+        let config = PluginConfig::new(manifest("logger")).with_log_sink(sink);
+        let mut plugin = WasmPlugin::load(dir.path(), config).await.unwrap();
+        plugin.initialize().await.unwrap();
+        assert_eq!(
+            *messages.lock().unwrap(),
+            vec![(log::Level::Info, "plugin ready".to_string())]
+        );
+
+        let before = host_now_millis();
+        let now = plugin.call_wasm_function("now", &[]).unwrap()[0].unwrap_i64();
+        assert!(now >= before);
+    }
+
+    #[tokio::test]
+    async fn test_host_connect_checks_sandbox() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("plugin.wasm"), r#"
+            (module
+                (import "env" "host_connect" (func $connect (param i32 i32 i32) (result i32)))
+                (import "env" "host_close" (func $close (param i32) (result i32)))
+                (import "env" "host_spawn" (func $spawn (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "example.com")
+                (data (i32.const 32) "127.0.0.1")
+                (data (i32.const 48) "curl example.com")
+                (func (export "connect_remote") (param i32) (result i32)
+                    (call $connect (i32.const 16) (i32.const 11) (local.get 0)))
+                (func (export "connect_local") (param i32) (result i32)
+                    (call $connect (i32.const 32) (i32.const 9) (local.get 0)))
+                (func (export "close") (param i32) (result i32)
+                    (call $close (local.get 0)))
+                (func (export "spawn") (result i32)
+                    (call $spawn (i32.const 48) (i32.const 16)))
+                (func (export "initialize"))
+                (func (export "shutdown")))
+        "#).unwrap();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sandbox = crate::SandboxConfig::new()
+            .with_allowed_hosts(["127.0.0.1"])
+            .with_allowed_ports([80, port]);
+        let config = PluginConfig::new(manifest("network")).with_sandbox(sandbox);
+        let plugin = WasmPlugin::load(dir.path(), config).await.unwrap();
+        let call = |name: &str, args: &[Value]| plugin.call_wasm_function(name, args).unwrap()[0].unwrap_i32();
+
+        // Hosts and ports the sandbox does not allow are refused
+        assert_eq!(call("connect_remote", &[Value::I32(80)]), HOST_DENIED);
+        assert_eq!(call("connect_local", &[Value::I32(81)]), HOST_DENIED);
+        assert_eq!(call("connect_local", &[Value::I32(-1)]), HOST_INVALID);
+        assert_eq!(call("spawn", &[]), HOST_DENIED);
+
+        let handle = call("connect_local", &[Value::I32(port as i32)]);
+        assert_eq!(handle, 0);
+        listener.accept().unwrap();
+        assert_eq!(call("close", &[Value::I32(handle)]), 0);
+        assert_eq!(call("close", &[Value::I32(handle)]), HOST_INVALID);
+    }
+
+    #[tokio::test]
+    async fn test_module_cache_reuses_compilation() {
+        let dir = tempfile::tempdir().unwrap();
+        let wasm_path = dir.path().join("plugin.wasm");
+        std::fs::write(&wasm_path, MODULE).unwrap();
+
+        let cache = ModuleCache::new();
+        cache.get_or_compile(&wasm_path).await.unwrap();
+        cache.get_or_compile(&wasm_path).await.unwrap();
+        assert_eq!(cache.compilations(), 1);
+
+        // A modified file is compiled again
+        let later = SystemTime::now() + std::time::Duration::from_secs(60);
+        std::fs::File::options()
+            .write(true)
+            .open(&wasm_path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        cache.get_or_compile(&wasm_path).await.unwrap();
+        assert_eq!(cache.compilations(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_cached_module_instantiates_in_new_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let wasm_path = dir.path().join("plugin.wasm");
+        std::fs::write(&wasm_path, MODULE).unwrap();
+
+        let cache = ModuleCache::new();
+        for _ in 0..2 {
+            let module = cache.get_or_compile(&wasm_path).await.unwrap();
+            let mut store = Store::new(ENGINE.clone());
+            let instance = Instance::new(&mut store, &module, &imports! {}).unwrap();
+            assert!(instance.exports.get_function("initialize").is_ok());
+        }
+        assert_eq!(cache.compilations(), 1);
+    }
 }
@@ -0,0 +1,102 @@
+//! Comment continuation when starting a new line
+
+use crate::{Language, ScopeKind};
+
+/// Returns the text to insert at the start of a new line following `prev_line`
+///
+/// Continues a run of line comments with the same token (`// `, `/// `) and a
+/// `/* ... */` block with ` * `, keeping the previous line's indentation.
+/// Returns `None` when the new line should not continue a comment.
+pub fn comment_continuation(prev_line: &str, scope: ScopeKind, lang: &Language) -> Option<String> {
+    let comments = &lang.config().comments;
+    let trimmed = prev_line.trim_start();
+    let indent = &prev_line[..prev_line.len() - trimmed.len()];
+
+    match scope {
+        ScopeKind::LineComment => {
+            let token = comments.line.as_deref()?;
+            if !trimmed.starts_with(token) {
+                return None;
+            }
+
+            // Keep doc comment markers such as `///` and `//!`
+            let marker_len = trimmed[token.len()..]
+                .chars()
+                .take_while(|c| token.contains(*c) || *c == '!')
+                .map(char::len_utf8)
+                .sum::<usize>();
+            let marker = &trimmed[..token.len() + marker_len];
+
+            Some(format!("{}{} ", indent, marker))
+        }
+        ScopeKind::BlockComment => {
+            let start = comments.block_start.as_deref()?;
+            let end = comments.block_end.as_deref()?;
+
+            // Only C-style blocks use a leading `*` on each line
+            if !start.ends_with('*') || trimmed.ends_with(end) {
+                return None;
+            }
+
+            if trimmed.starts_with(start) {
+                Some(format!("{} * ", indent))
+            } else if trimmed.starts_with('*') {
+                Some(format!("{}* ", indent))
+            } else {
+                Some(indent.to_string())
+            }
+        }
+        ScopeKind::Code | ScopeKind::String => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{language, Parser};
+
+    /// Returns the continuation for a newline typed at the end of `line` within `source`
+    fn continuation_after(source: &str, line: usize) -> Option<String> {
+        language::register_default_languages().unwrap();
+        let rust_lang = language::get_language("rust").unwrap();
+
+        let mut parser = Parser::new();
+        parser.set_language(rust_lang.clone()).unwrap();
+
+        let prev_line = source.lines().nth(line).unwrap();
+        let line_end = source.lines()
+            .take(line + 1)
+            .map(|l| l.len() + 1)
+            .sum::<usize>() - 1;
+
+        let scope = parser.scope_at(source, line_end).unwrap();
+        comment_continuation(prev_line, scope, &rust_lang)
+    }
+
+    #[test]
+    fn test_continue_line_comments() {
+        let source = "fn main() {\n    // first\n    // second\n}\n";
+        assert_eq!(continuation_after(source, 2), Some("    // ".to_string()));
+
+        let source = "/// Docs\nfn main() {}\n";
+        assert_eq!(continuation_after(source, 0), Some("/// ".to_string()));
+    }
+
+    #[test]
+    fn test_continue_block_comment() {
+        let source = "/* start\n * middle\n */\nfn main() {}\n";
+        assert_eq!(continuation_after(source, 0), Some(" * ".to_string()));
+        assert_eq!(continuation_after(source, 1), Some(" * ".to_string()));
+        assert_eq!(continuation_after(source, 2), None);
+    }
+
+    #[test]
+    fn test_no_continuation_in_code() {
+        let source = "fn main() {\n    let x = 1;\n}\n";
+        assert_eq!(continuation_after(source, 0), None);
+        assert_eq!(continuation_after(source, 1), None);
+
+        let source = "let url = \"http://example.com\";\n";
+        assert_eq!(continuation_after(source, 0), None);
+    }
+}
@@ -0,0 +1,210 @@
+//! Rendering of highlighted text for export, as HTML or ANSI-colored text
+
+use std::fmt::Write;
+use crate::{Highlighter, HighlightEvent, Language, Style, Theme};
+
+/// ANSI sequence resetting all attributes
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Renders `text` highlighted in `language` as an HTML `<pre>` block
+///
+/// Each highlighted token is wrapped in a `<span>` with an inline style from
+/// the theme. The `<pre>` carries the theme's default style, so text between
+/// tokens is left bare. If highlighting fails the whole text is rendered in
+/// the default style.
+pub fn to_html(text: &str, language: &Language, theme: &Theme) -> String {
+    let mut html = String::new();
+    match style_attribute(&theme.default_style) {
+        Some(style) => write!(html, "<pre style=\"{}\">", style).unwrap(),
+        None => html.push_str("<pre>"),
+    }
+
+    for (segment, style) in segments(text, language, theme) {
+        match style.as_ref().and_then(style_attribute) {
+            Some(style) => {
+                write!(html, "<span style=\"{}\">", style).unwrap();
+                push_escaped(&mut html, segment);
+                html.push_str("</span>");
+            }
+            None => push_escaped(&mut html, segment),
+        }
+    }
+
+    html.push_str("</pre>");
+    html
+}
+
+/// Renders `text` highlighted in `language` with 24-bit ANSI color codes,
+/// for previews in a terminal
+///
+/// Every token starts with the codes of its style and ends with a reset;
+/// text between tokens uses the theme's default style. The output always
+/// ends with a reset, so it can be printed as is.
+pub fn to_ansi(text: &str, language: &Language, theme: &Theme) -> String {
+    let mut ansi = String::new();
+    for (segment, style) in segments(text, language, theme) {
+        match ansi_codes(style.as_ref().unwrap_or(&theme.default_style)) {
+            Some(codes) => write!(ansi, "\x1b[{}m{}{}", codes, segment, ANSI_RESET).unwrap(),
+            None => ansi.push_str(segment),
+        }
+    }
+    if !ansi.ends_with(ANSI_RESET) {
+        ansi.push_str(ANSI_RESET);
+    }
+    ansi
+}
+
+/// Splits `text` into highlighted tokens with their style and the gaps
+/// between them, which have none
+///
+/// If highlighting fails the whole text is one gap.
+fn segments<'a>(text: &'a str, language: &Language, theme: &Theme) -> Vec<(&'a str, Option<Style>)> {
+    let mut highlighter = Highlighter::new();
+    highlighter.set_theme(theme.clone());
+    let events = highlighter
+        .set_language(language.clone())
+        .and_then(|_| highlighter.highlight(text))
+        .unwrap_or_default();
+
+    let mut segments = Vec::new();
+    let mut position = 0;
+    for event in events {
+        let HighlightEvent::Source { start, end, style } = event else {
+            continue;
+        };
+        if start < position || end > text.len() {
+            continue;
+        }
+        if position < start {
+            segments.push((&text[position..start], None));
+        }
+        segments.push((&text[start..end], Some(style)));
+        position = end;
+    }
+    if position < text.len() {
+        segments.push((&text[position..], None));
+    }
+    segments
+}
+
+/// Returns the SGR parameters for a style, or `None` if it sets nothing
+fn ansi_codes(style: &Style) -> Option<String> {
+    let mut codes = Vec::new();
+    if style.bold {
+        codes.push("1".to_string());
+    }
+    if style.italic {
+        codes.push("3".to_string());
+    }
+    if style.underline {
+        codes.push("4".to_string());
+    }
+    if let Some(color) = style.foreground {
+        codes.push(format!("38;2;{};{};{}", color.r, color.g, color.b));
+    }
+    if let Some(color) = style.background {
+        codes.push(format!("48;2;{};{};{}", color.r, color.g, color.b));
+    }
+    (!codes.is_empty()).then(|| codes.join(";"))
+}
+
+/// Returns the inline CSS for a style, or `None` if it sets nothing
+fn style_attribute(style: &Style) -> Option<String> {
+    let mut properties = Vec::new();
+    if let Some(color) = style.foreground {
+        properties.push(format!("color:#{:02x}{:02x}{:02x}", color.r, color.g, color.b));
+    }
+    if let Some(color) = style.background {
+        properties.push(format!("background-color:#{:02x}{:02x}{:02x}", color.r, color.g, color.b));
+    }
+    if style.bold {
+        properties.push("font-weight:bold".to_string());
+    }
+    if style.italic {
+        properties.push("font-style:italic".to_string());
+    }
+    if style.underline {
+        properties.push("text-decoration:underline".to_string());
+    }
+    (!properties.is_empty()).then(|| properties.join(";"))
+}
+
+/// Appends text with the characters HTML treats specially escaped
+fn push_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '&' => html.push_str("&amp;"),
+            '"' => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use crate::Color;
+
+    /// Returns the text of an HTML fragment with its tags removed
+    fn strip_tags(html: &str) -> String {
+        let mut text = String::new();
+        let mut in_tag = false;
+        for c in html.chars() {
+            match c {
+                '<' => in_tag = true,
+                '>' if in_tag => in_tag = false,
+                c if !in_tag => text.push(c),
+                _ => {}
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn test_to_html() {
+        language::register_default_languages().unwrap();
+        let rust = language::get_language("rust").unwrap();
+        let mut theme = Theme::dark();
+        theme.default_style = Style::new().with_foreground(Color::new(0xab, 0xb2, 0xbf));
+
+        let source = "fn less(a: u8, b: u8) -> bool {\n    a < b && b > 0\n}\n";
+        let html = to_html(source, &rust, &theme);
+
+        assert!(html.starts_with("<pre style=\"color:#abb2bf\">"));
+        assert!(html.ends_with("</pre>"));
+        assert_eq!(html.matches("<span").count(), html.matches("</span>").count());
+        assert!(html.contains("<span style=\"color:#c678dd;font-weight:bold\">fn</span>"));
+
+        // Content is escaped, and unescaping it gives back the source
+        assert!(html.contains("&lt;") && html.contains("&amp;&amp;") && html.contains("&gt;"));
+        let text = strip_tags(&html);
+        assert!(!text.contains('<'));
+        let unescaped = text.replace("&lt;", "<").replace("&gt;", ">").replace("&amp;", "&");
+        assert_eq!(unescaped, source);
+    }
+
+    #[test]
+    fn test_to_ansi() {
+        language::register_default_languages().unwrap();
+        let rust = language::get_language("rust").unwrap();
+        let theme = Theme::dark();
+
+        let ansi = to_ansi("fn main() {}\n", &rust, &theme);
+        assert!(ansi.starts_with("\x1b[1;38;2;198;120;221mfn\x1b[0m "));
+        assert!(ansi.ends_with("\x1b[0m"));
+
+        // Stripping the escapes gives back the source
+        let mut text = String::new();
+        let mut rest = ansi.as_str();
+        while let Some(start) = rest.find('\x1b') {
+            text.push_str(&rest[..start]);
+            let end = rest[start..].find('m').unwrap();
+            rest = &rest[start + end + 1..];
+        }
+        text.push_str(rest);
+        assert_eq!(text, "fn main() {}\n");
+    }
+}
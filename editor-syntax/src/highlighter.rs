@@ -1,11 +1,20 @@
 //! Syntax highlighting implementation using tree-sitter
 
-use std::collections::HashMap;
-use tree_sitter::{Parser as TSParser, Query, QueryCursor};
+use std::cmp::Reverse;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+use parking_lot::Mutex;
+use editor_core::{SyntaxEdit, SyntaxPoint};
+use tree_sitter::{InputEdit, Parser as TSParser, Point, Query, QueryCursor, Tree};
 use crate::{Language, Theme, Style, Result, SyntaxError};
+use crate::language::get_language_for_document;
 
 /// Represents a highlighting event
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum HighlightEvent {
     /// Source text with style information
     Source {
@@ -18,47 +27,97 @@ pub enum HighlightEvent {
 }
 
 /// Syntax highlighter
+///
+/// The highlighter is `Send` and `Sync`. The tree-sitter parser is not `Sync`,
+/// so it is kept behind a mutex and shared with background highlight tasks.
 pub struct Highlighter {
     /// Tree-sitter parser
-    parser: TSParser,
+    parser: Arc<Mutex<TSParser>>,
     /// Current language
     language: Option<Language>,
     /// Current theme
     theme: Theme,
-    /// Highlight queries by language
-    queries: HashMap<String, Query>,
+    /// Highlight queries by language name
+    queries: HashMap<String, Arc<Query>>,
+    /// Tree from the last synchronous highlight, reused by `highlight_incremental`
+    tree: Option<Tree>,
+    /// Hash of the text `tree` was parsed from, so `highlight_range` can reuse it
+    tree_hash: Option<u64>,
+    /// Number of highlight queries compiled
+    #[cfg(test)]
+    query_compiles: usize,
 }
 
 impl Highlighter {
     /// Creates a new highlighter
     pub fn new() -> Self {
         Self {
-            parser: TSParser::new(),
+            parser: Arc::new(Mutex::new(TSParser::new())),
             language: None,
             theme: Theme::default(),
             queries: HashMap::new(),
+            tree: None,
+            tree_hash: None,
+            #[cfg(test)]
+            query_compiles: 0,
         }
     }
 
     /// Sets the current language
+    ///
+    /// The parser is only reconfigured when the language changes, and each
+    /// language's highlight query is compiled once and cached by name.
     pub fn set_language(&mut self, language: Language) -> Result<()> {
-        // Use the owned TSLanguage that the parser requires
-        let ts_lang = language.ts_language();
-        self.parser.set_language(ts_lang)
-            .map_err(|e| SyntaxError::ParserError(e.to_string()))?;
-        
+        let name = language.config().name.clone();
+        if self.active_language() == Some(name.as_str()) {
+            return Ok(());
+        }
+
         // Load highlight query if not already loaded
-        if !self.queries.contains_key(&language.config().name) {
+        if !self.queries.contains_key(&name) {
             let query_source = self.get_highlight_query(&language)?;
             let query = Query::new(language.ts_language(), &query_source)
                 .map_err(|e| SyntaxError::ParserError(e.to_string()))?;
-            self.queries.insert(language.config().name.clone(), query);
+            #[cfg(test)]
+            {
+                self.query_compiles += 1;
+            }
+            self.queries.insert(name, Arc::new(query));
         }
 
+        self.parser.lock().set_language(language.ts_language())
+            .map_err(|e| SyntaxError::ParserError(e.to_string()))?;
+
         self.language = Some(language);
+        self.tree = None;
+        self.tree_hash = None;
         Ok(())
     }
 
+    /// Applies a document's language setting, e.g. after `LanguageChanged`
+    ///
+    /// Clears the current language if the setting is `None` or unknown, and
+    /// returns whether a language is active afterwards.
+    pub fn apply_document_language(&mut self, language: Option<&str>) -> Result<bool> {
+        match language.and_then(get_language_for_document) {
+            Some(language) => {
+                self.set_language(language)?;
+                Ok(true)
+            }
+            None => {
+                self.language = None;
+                self.tree = None;
+                self.tree_hash = None;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Returns the name of the current language
+    pub fn active_language(&self) -> Option<&str> {
+        self.language.as_ref().map(|language| language.config().name.as_str())
+    }
+
     /// Sets the highlighting theme
     pub fn set_theme(&mut self, theme: Theme) {
         self.theme = theme;
@@ -66,75 +125,148 @@ impl Highlighter {
 
     /// Highlights the given text
     pub fn highlight(&mut self, text: &str) -> Result<Vec<HighlightEvent>> {
-        let language = self.language.as_ref()
-            .ok_or_else(|| SyntaxError::HighlightError("No language set".to_string()))?;
+        let query = self.active_query()?;
+        let tree = parse_text(&mut self.parser.lock(), text, None)?;
+        let events = highlight_tree(&tree, &query, &self.theme, text, 0..text.len());
+        self.store_tree(tree, text);
+        Ok(events)
+    }
 
-        // Parse the text
-        let tree = self.parser.parse(text, None)
-            .ok_or_else(|| SyntaxError::ParserError("Failed to parse text".to_string()))?;
-
-        let query = self.queries.get(&language.config().name)
-            .ok_or_else(|| SyntaxError::HighlightError("No highlight query found".to_string()))?;
-
-        let mut cursor = QueryCursor::new();
-        let matches = cursor.matches(query, tree.root_node(), text.as_bytes());
-
-        let mut events = Vec::new();
-        for match_ in matches {
-            for capture in match_.captures {
-                let node = capture.node;
-                let capture_name = &query.capture_names()[capture.index as usize];
-                
-                if let Some(style) = self.theme.get_style(capture_name) {
-                    events.push(HighlightEvent::Source {
-                        start: node.start_byte(),
-                        end: node.end_byte(),
-                        style: style.clone(),
-                    });
-                }
+    /// Highlights only the part of `text` in `range`, e.g. the visible lines
+    ///
+    /// The whole text is parsed, reusing the tree from the last highlight if
+    /// the text is unchanged, but only captures intersecting the range are
+    /// queried. Events are clamped to the range and match those of a full
+    /// highlight there.
+    pub fn highlight_range(&mut self, text: &str, range: Range<usize>) -> Result<Vec<HighlightEvent>> {
+        let query = self.active_query()?;
+        let hash = text_hash(text);
+        let tree = match self.tree.take() {
+            Some(tree) if self.tree_hash == Some(hash) => tree,
+            _ => parse_text(&mut self.parser.lock(), text, None)?,
+        };
+        let events = highlight_tree(&tree, &query, &self.theme, text, range);
+        self.tree = Some(tree);
+        self.tree_hash = Some(hash);
+        Ok(events)
+    }
+
+    /// Highlights `text` after `edits`, reusing the tree from the last highlight
+    ///
+    /// `edits` are the `SyntaxEdit`s of the document's buffer events since
+    /// the last call to `highlight` or `highlight_incremental`, in order.
+    /// Without a previous tree the text is parsed from scratch.
+    pub fn highlight_incremental(&mut self, text: &str, edits: &[SyntaxEdit]) -> Result<Vec<HighlightEvent>> {
+        let query = self.active_query()?;
+        if let Some(tree) = self.tree.as_mut() {
+            for edit in edits {
+                tree.edit(&input_edit(edit));
             }
         }
 
-        // Sort events by start position
-        events.sort_by_key(|event| match event {
-            HighlightEvent::Source { start, .. } => *start,
-            HighlightEvent::Error(_) => 0,
-        });
-
+        let tree = parse_text(&mut self.parser.lock(), text, self.tree.as_ref())?;
+        let events = highlight_tree(&tree, &query, &self.theme, text, 0..text.len());
+        self.store_tree(tree, text);
         Ok(events)
     }
 
+    /// Keeps the tree parsed from `text` for later highlights
+    fn store_tree(&mut self, tree: Tree, text: &str) {
+        self.tree = Some(tree);
+        self.tree_hash = Some(text_hash(text));
+    }
+
+    /// Highlights the given text on the blocking thread pool
+    ///
+    /// The returned future does not borrow the highlighter, so it can be spawned
+    /// as a task and its result sent back to the UI.
+    pub fn highlight_async(
+        &self,
+        text: impl Into<String>,
+    ) -> impl Future<Output = Result<Vec<HighlightEvent>>> + Send + 'static {
+        let text = text.into();
+        let range = 0..text.len();
+        self.highlight_range_async(text, range)
+    }
+
+    /// Highlights the part of `text` in `range` on the blocking thread pool
+    ///
+    /// Like `highlight_range`, but the text is always parsed from scratch.
+    pub fn highlight_range_async(
+        &self,
+        text: impl Into<String>,
+        range: Range<usize>,
+    ) -> impl Future<Output = Result<Vec<HighlightEvent>>> + Send + 'static {
+        let text = text.into();
+        let parser = Arc::clone(&self.parser);
+        let theme = self.theme.clone();
+        let query = self.active_query();
+
+        async move {
+            let query = query?;
+            tokio::task::spawn_blocking(move || {
+                let tree = parse_text(&mut parser.lock(), &text, None)?;
+                Ok(highlight_tree(&tree, &query, &theme, &text, range))
+            })
+            .await
+            .map_err(|e| SyntaxError::HighlightError(e.to_string()))?
+        }
+    }
+
+    /// Returns the highlight query for the current language
+    fn active_query(&self) -> Result<Arc<Query>> {
+        let name = self.active_language()
+            .ok_or_else(|| SyntaxError::HighlightError("No language set".to_string()))?;
+
+        self.queries.get(name)
+            .cloned()
+            .ok_or_else(|| SyntaxError::HighlightError("No highlight query found".to_string()))
+    }
+
     /// Returns the highlight query for a language
+    ///
+    /// Where captures overlap, later patterns take precedence over earlier
+    /// ones, so broad nodes such as macro invocations are listed first.
     fn get_highlight_query(&self, language: &Language) -> Result<String> {
         // In a real implementation, this would load language-specific queries
         // For now, return a basic query for demonstration
         match language.config().name.as_str() {
             "Rust" => Ok(r#"
+                (attribute_item) @attribute
+                (macro_invocation) @macro
                 (identifier) @variable
                 (type_identifier) @type
                 (string_literal) @string
                 (integer_literal) @number
                 (line_comment) @comment
                 (block_comment) @comment
-                (attribute_item) @attribute
-                (macro_invocation) @macro
-                (keyword) @keyword
+                [
+                    "fn" "let" "pub" "struct" "enum" "impl" "trait" "use" "mod" "const"
+                    "match" "if" "else" "for" "while" "loop" "return" "in" "as"
+                ] @keyword
+                (mutable_specifier) @keyword
             "#.to_string()),
             "Python" => Ok(r#"
+                (decorator) @attribute
                 (identifier) @variable
                 (string) @string
                 (integer) @number
                 (comment) @comment
-                (decorator) @attribute
-                (keyword) @keyword
+                [
+                    "def" "class" "return" "if" "elif" "else" "for" "while" "in"
+                    "import" "from" "as" "with" "pass"
+                ] @keyword
             "#.to_string()),
             "JavaScript" => Ok(r#"
+                (jsx_element) @jsx
                 (identifier) @variable
                 (string) @string
                 (number) @number
                 (comment) @comment
-                (jsx_element) @jsx
-                (keyword) @keyword
+                [
+                    "function" "const" "let" "var" "return" "if" "else" "for" "while"
+                    "class" "new" "import" "export" "from"
+                ] @keyword
             "#.to_string()),
             _ => Err(SyntaxError::UnsupportedLanguage(
                 language.config().name.clone()
@@ -143,6 +275,161 @@ impl Highlighter {
     }
 }
 
+/// Converts a document edit into a tree-sitter edit
+fn input_edit(edit: &SyntaxEdit) -> InputEdit {
+    let point = |point: SyntaxPoint| Point::new(point.row, point.column);
+    InputEdit {
+        start_byte: edit.start_byte,
+        old_end_byte: edit.old_end_byte,
+        new_end_byte: edit.new_end_byte,
+        start_position: point(edit.start_position),
+        old_end_position: point(edit.old_end_position),
+        new_end_position: point(edit.new_end_position),
+    }
+}
+
+/// Returns a hash identifying `text`
+fn text_hash(text: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    text.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Parses `text`, reusing the unchanged parts of `old_tree` if given
+fn parse_text(parser: &mut TSParser, text: &str, old_tree: Option<&Tree>) -> Result<Tree> {
+    parser.parse(text, old_tree)
+        .ok_or_else(|| SyntaxError::ParserError("Failed to parse text".to_string()))
+}
+
+/// Returns highlight events for the captures of `query` in `tree` within `range`
+///
+/// Text that failed to parse is still highlighted where the query captures
+/// it, and styled as an error elsewhere.
+fn highlight_tree(tree: &Tree, query: &Query, theme: &Theme, text: &str, range: Range<usize>) -> Vec<HighlightEvent> {
+    let mut cursor = QueryCursor::new();
+    cursor.set_byte_range(range.clone());
+    let matches = cursor.matches(query, tree.root_node(), text.as_bytes());
+
+    let mut spans = error_spans(tree, theme, &range);
+    for match_ in matches {
+        for capture in match_.captures {
+            let node = capture.node;
+            let capture_name = &query.capture_names()[capture.index as usize];
+
+            if let Some(style) = theme.get_style(capture_name) {
+                spans.push(CaptureSpan {
+                    start: node.start_byte(),
+                    end: node.end_byte(),
+                    priority: match_.pattern_index + 1,
+                    style: style.clone(),
+                });
+            }
+        }
+    }
+
+    // Captures of matches intersecting the range may extend beyond it
+    for span in &mut spans {
+        span.start = span.start.max(range.start);
+        span.end = span.end.min(range.end);
+    }
+    resolve_overlaps(spans)
+}
+
+/// Returns spans covering the ERROR nodes of `tree` that intersect `range`,
+/// below every capture
+///
+/// Invalid code, e.g. while typing, parses into ERROR nodes rather than
+/// failing. Their text uses the theme's "error" style if it has one and its
+/// default style otherwise, so it is not left unstyled.
+fn error_spans(tree: &Tree, theme: &Theme, range: &Range<usize>) -> Vec<CaptureSpan> {
+    let style = theme.get_style("error").unwrap_or(&theme.default_style);
+    let mut spans = Vec::new();
+    let mut nodes = vec![tree.root_node()];
+
+    while let Some(node) = nodes.pop() {
+        if node.is_error() {
+            spans.push(CaptureSpan {
+                start: node.start_byte(),
+                end: node.end_byte(),
+                priority: 0,
+                style: style.clone(),
+            });
+            continue;
+        }
+        let mut cursor = node.walk();
+        nodes.extend(node.children(&mut cursor).filter(|child| {
+            child.has_error() && child.start_byte() < range.end && child.end_byte() > range.start
+        }));
+    }
+
+    spans
+}
+
+/// A styled capture before overlaps are resolved
+#[derive(Debug, Clone)]
+struct CaptureSpan {
+    start: usize,
+    end: usize,
+    /// One more than the index of the query pattern that produced the capture,
+    /// 0 for the fallback style of ERROR nodes
+    priority: usize,
+    style: Style,
+}
+
+/// Resolves overlapping captures into non-overlapping events sorted by start
+///
+/// For every byte the capture from the latest query pattern wins; between
+/// captures of the same pattern the narrower one wins.
+fn resolve_overlaps(mut spans: Vec<CaptureSpan>) -> Vec<HighlightEvent> {
+    spans.retain(|span| span.start < span.end);
+    spans.sort_by_key(|span| span.start);
+
+    let mut boundaries: Vec<usize> = spans.iter().flat_map(|span| [span.start, span.end]).collect();
+    boundaries.sort_unstable();
+    boundaries.dedup();
+
+    // Active spans ordered so the winning one is on top
+    let mut active = BinaryHeap::new();
+    let mut next = 0;
+    let mut events: Vec<HighlightEvent> = Vec::new();
+    let mut last_winner = None;
+
+    for window in boundaries.windows(2) {
+        let (start, end) = (window[0], window[1]);
+
+        while next < spans.len() && spans[next].start == start {
+            let span = &spans[next];
+            active.push((span.priority, Reverse(span.end - span.start), Reverse(next), span.end));
+            next += 1;
+        }
+        while active.peek().is_some_and(|&(_, _, _, span_end)| span_end <= start) {
+            active.pop();
+        }
+
+        let Some(&(_, _, Reverse(winner), _)) = active.peek() else {
+            last_winner = None;
+            continue;
+        };
+
+        // Extend the previous event while the same capture keeps winning
+        if last_winner == Some(winner) {
+            if let Some(HighlightEvent::Source { end: event_end, .. }) = events.last_mut() {
+                *event_end = end;
+                continue;
+            }
+        }
+
+        events.push(HighlightEvent::Source {
+            start,
+            end,
+            style: spans[winner].style.clone(),
+        });
+        last_winner = Some(winner);
+    }
+
+    events
+}
+
 impl Default for Highlighter {
     fn default() -> Self {
         Self::new()
@@ -180,4 +467,238 @@ mod tests {
         let events = highlighter.highlight(source).unwrap();
         assert!(!events.is_empty());
     }
+
+    #[test]
+    fn test_set_language_compiles_query_once() {
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        let rust = language::get_language("rust").unwrap();
+        let python = language::get_language("python").unwrap();
+
+        highlighter.set_language(rust.clone()).unwrap();
+        highlighter.set_language(rust.clone()).unwrap();
+        assert_eq!(highlighter.query_compiles, 1);
+        assert_eq!(highlighter.active_language(), Some("Rust"));
+
+        // Switching back reuses the cached query
+        highlighter.set_language(python).unwrap();
+        highlighter.set_language(rust).unwrap();
+        assert_eq!(highlighter.query_compiles, 2);
+        assert_eq!(highlighter.active_language(), Some("Rust"));
+        assert!(!highlighter.highlight("fn main() {}").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_language_override() {
+        use editor_core::{Document, DocumentEvent, Event};
+
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        let mut doc = Document::new("notes.txt");
+        doc.insert(0, "fn main() { let x = 1; }").unwrap();
+        let mut events = doc.subscribe();
+
+        // The extension is not recognized
+        assert!(!highlighter.apply_document_language(doc.language()).unwrap());
+        assert!(highlighter.highlight(&doc.text()).is_err());
+
+        doc.set_language(Some("rust".to_string()));
+        let language = match events.try_recv().unwrap() {
+            Event::Document(DocumentEvent::LanguageChanged { language, .. }) => language,
+            other => panic!("unexpected event: {:?}", other),
+        };
+        assert!(highlighter.apply_document_language(language.as_deref()).unwrap());
+        assert_eq!(highlighter.active_language(), Some("Rust"));
+        assert!(!highlighter.highlight(&doc.text()).unwrap().is_empty());
+
+        // Extensions detected from the name still resolve
+        assert!(highlighter.apply_document_language(Some("py")).unwrap());
+        assert_eq!(highlighter.active_language(), Some("Python"));
+    }
+
+    /// Returns the style applied to each byte in `0..len`
+    fn style_per_byte(events: &[HighlightEvent], len: usize) -> Vec<Option<Style>> {
+        let mut styles = vec![None; len];
+        for event in events {
+            if let HighlightEvent::Source { start, end, style } = event {
+                for byte in &mut styles[*start..*end] {
+                    assert!(byte.is_none(), "events overlap");
+                    *byte = Some(style.clone());
+                }
+            }
+        }
+        styles
+    }
+
+    fn style(hex: &str) -> Style {
+        Style::new().with_foreground(crate::theme::Color::from_hex(hex).unwrap())
+    }
+
+    #[test]
+    fn test_resolve_overlaps() {
+        let (outer, inner, late) = (style("#111111"), style("#222222"), style("#333333"));
+        let span = |start, end, priority, style: &Style| CaptureSpan {
+            start,
+            end,
+            priority,
+            style: style.clone(),
+        };
+
+        // A narrow capture inside a broad one of the same pattern, plus a capture
+        // from a later pattern overlapping the end of the broad one
+        let events = resolve_overlaps(vec![
+            span(0, 10, 0, &outer),
+            span(2, 4, 0, &inner),
+            span(8, 12, 1, &late),
+        ]);
+
+        let mut expected = vec![Some(outer.clone()); 12];
+        expected[2..4].fill(Some(inner.clone()));
+        expected[8..12].fill(Some(late.clone()));
+        assert_eq!(style_per_byte(&events, 12), expected);
+
+        // The broad capture resumes after the narrow one as a separate event
+        assert_eq!(events.len(), 4);
+
+        // Query order decides between overlapping captures of different patterns
+        let events = resolve_overlaps(vec![span(0, 4, 1, &outer), span(0, 8, 0, &late)]);
+        let mut expected = vec![Some(late.clone()); 8];
+        expected[0..4].fill(Some(outer.clone()));
+        assert_eq!(style_per_byte(&events, 8), expected);
+    }
+
+    #[test]
+    fn test_string_inside_macro() {
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        highlighter.set_language(language::get_language("rust").unwrap()).unwrap();
+
+        let (macro_style, string_style) = (style("#AA0000"), style("#00AA00"));
+        let mut theme = Theme::new("Test", true);
+        theme.set_style("macro", macro_style.clone());
+        theme.set_style("string", string_style.clone());
+        highlighter.set_theme(theme);
+
+        let source = "fn main() { println!(\"hi\"); }";
+        let events = highlighter.highlight(source).unwrap();
+        let styles = style_per_byte(&events, source.len());
+
+        let macro_start = source.find("println").unwrap();
+        let string_start = source.find('"').unwrap();
+        assert_eq!(styles[macro_start], Some(macro_style.clone()));
+        assert_eq!(styles[string_start..string_start + 4], vec![Some(string_style); 4][..]);
+        assert_eq!(styles[string_start + 4], Some(macro_style));
+    }
+
+    #[test]
+    fn test_incomplete_code() {
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        highlighter.set_language(language::get_language("rust").unwrap()).unwrap();
+
+        let (keyword_style, error_style) = (style("#AA0000"), style("#444444"));
+        let mut theme = Theme::new("Test", true);
+        theme.set_style("keyword", keyword_style.clone());
+        theme.set_style("error", error_style.clone());
+        highlighter.set_theme(theme);
+
+        // A missing closing brace; `style_per_byte` checks the events do not overlap
+        let source = "fn main() {";
+        let events = highlighter.highlight(source).unwrap();
+        let styles = style_per_byte(&events, source.len());
+        assert_eq!(styles[0..2], vec![Some(keyword_style.clone()); 2][..]);
+
+        // Text inside an ERROR node is styled, captures within it still win
+        let source = "fn main() { let = 1; }";
+        let events = highlighter.highlight(source).unwrap();
+        let styles = style_per_byte(&events, source.len());
+        let let_start = source.find("let").unwrap();
+        assert_eq!(styles[let_start], Some(keyword_style));
+        assert_eq!(styles[source.find('=').unwrap()], Some(error_style));
+    }
+
+    #[test]
+    fn test_highlight_incremental() {
+        use editor_core::{BufferEvent, Document, Event};
+
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        highlighter.set_language(language::get_language("rust").unwrap()).unwrap();
+
+        let mut doc = Document::new("main.rs");
+        doc.insert(0, "fn main() {\n    let x = 1;\n}\n").unwrap();
+        highlighter.highlight(&doc.text()).unwrap();
+
+        let mut events = doc.subscribe();
+        doc.insert(27, "    let s = \"a\nb\";\n").unwrap();
+        doc.delete(3, 7).unwrap();
+        doc.insert(3, "run").unwrap();
+
+        let edits: Vec<_> = std::iter::from_fn(|| events.try_recv().ok())
+            .filter_map(|event| match event {
+                Event::Buffer(BufferEvent::Inserted { edit, .. } | BufferEvent::Deleted { edit, .. }) => Some(edit),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(edits.len(), 3);
+
+        let text = doc.text();
+        let incremental = highlighter.highlight_incremental(&text, &edits).unwrap();
+        assert_eq!(incremental, highlighter.highlight(&text).unwrap());
+    }
+
+    #[test]
+    fn test_highlight_range() {
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+        highlighter.set_language(language::get_language("rust").unwrap()).unwrap();
+
+        let source = "// header\nfn main() {\n    let s = \"one\ntwo\";\n    let = 1;\n}\n";
+        let full = style_per_byte(&highlighter.highlight(source).unwrap(), source.len());
+
+        // Ranges starting and ending inside captures and an ERROR node
+        let start = source.find("let").unwrap() + 1;
+        let end = source.find("two").unwrap() + 1;
+        for range in [start..end, end..source.len() - 3, 0..source.len()] {
+            let events = highlighter.highlight_range(source, range.clone()).unwrap();
+            assert!(events.iter().all(|event| match event {
+                HighlightEvent::Source { start, end, .. } => range.start <= *start && *end <= range.end,
+                HighlightEvent::Error(_) => false,
+            }));
+
+            let styles = style_per_byte(&events, source.len());
+            assert_eq!(styles[range.clone()], full[range.clone()]);
+            assert!(styles[..range.start].iter().chain(&styles[range.end..]).all(Option::is_none));
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_highlight_async() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Highlighter>();
+
+        language::register_default_languages().unwrap();
+        let mut highlighter = Highlighter::new();
+
+        let rust_lang = language::get_language("rust").unwrap();
+        highlighter.set_language(rust_lang).unwrap();
+
+        let source = "fn main() {\n    let x = \"text\"; // note\n}\n";
+        let expected = highlighter.highlight(source).unwrap();
+
+        // Runs as a spawned task on a worker thread, reporting back over a channel
+        let (tx, mut rx) = tokio::sync::mpsc::channel(1);
+        let task = highlighter.highlight_async(source);
+        tokio::spawn(async move {
+            tx.send(task.await).await.unwrap();
+        });
+
+        let events = rx.recv().await.unwrap().unwrap();
+        assert!(!events.is_empty());
+        assert_eq!(events, expected);
+
+        // Without a language there is nothing to highlight
+        let result = Highlighter::new().highlight_async(source).await;
+        assert!(matches!(result, Err(SyntaxError::HighlightError(_))));
+    }
 }
@@ -1,12 +1,14 @@
 //! Language configuration and management
 
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use parking_lot::RwLock;
 use serde::{Serialize, Deserialize};
 use tree_sitter::Language as TSLanguage;
+use editor_core::{FileAssociations, IndentRules};
 
-use crate::Result;
+use crate::{Result, SyntaxError};
 
 lazy_static::lazy_static! {
     static ref LANGUAGES: Arc<RwLock<HashMap<String, Language>>> = Arc::new(RwLock::new(HashMap::new()));
@@ -77,6 +79,12 @@ impl Language {
         &self.config
     }
 
+    /// Returns the indentation rules for `Document::reindent_range`
+    pub fn indent_rules(&self) -> IndentRules {
+        let rules = &self.config.indentation;
+        IndentRules::new(rules.increase_indent.clone(), rules.decrease_indent.clone())
+    }
+
     /// Returns the tree-sitter language
     pub fn ts_language(&self) -> TSLanguage {
         // Return a clone since tree-sitter expects an owned TSLanguage
@@ -194,6 +202,28 @@ pub fn register_default_languages() -> Result<()> {
     Ok(())
 }
 
+/// Registers a language at runtime, e.g. from a plugin shipping a grammar
+///
+/// The language id is its lowercased name and an existing language with the
+/// same id is replaced. Fails if the grammar was generated for a tree-sitter
+/// ABI this build cannot load.
+pub fn register_language(language: Language) -> Result<()> {
+    let version = language.ts_language.version();
+    if !(tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION).contains(&version) {
+        return Err(SyntaxError::UnsupportedLanguage(format!(
+            "{} grammar uses ABI version {}, expected {} to {}",
+            language.config.name,
+            version,
+            tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+            tree_sitter::LANGUAGE_VERSION,
+        )));
+    }
+
+    let id = language.config.name.to_lowercase();
+    LANGUAGES.write().insert(id, language);
+    Ok(())
+}
+
 /// Gets a language by name
 #[allow(dead_code)]
 pub fn get_language(name: &str) -> Option<Language> {
@@ -209,6 +239,34 @@ pub fn get_language_by_extension(ext: &str) -> Option<Language> {
         .map(|(_, lang)| lang.clone())
 }
 
+/// Gets the language of a file, checking user associations before its extension
+///
+/// Returns `None` if the associated language id is not registered.
+pub fn get_language_for_path(path: &Path, associations: &FileAssociations) -> Option<Language> {
+    match associations.language_for(path) {
+        Some(id) => get_language(id),
+        None => get_language_by_extension(&format!(".{}", path.extension()?.to_str()?)),
+    }
+}
+
+/// Gets the language of a document's language setting
+///
+/// The setting is either a language id, from an association or an override,
+/// or the file extension detected from the document's name.
+pub fn get_language_for_document(language: &str) -> Option<Language> {
+    get_language(language).or_else(|| get_language_by_extension(&format!(".{}", language)))
+}
+
+/// Returns the ids and display names of the registered languages, sorted by name
+pub fn registered_languages() -> Vec<(String, String)> {
+    let mut languages: Vec<_> = LANGUAGES.read()
+        .iter()
+        .map(|(id, lang)| (id.clone(), lang.config.name.clone()))
+        .collect();
+    languages.sort_by(|a, b| a.1.cmp(&b.1));
+    languages
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -220,6 +278,14 @@ mod tests {
         assert!(get_language("rust").is_some());
         assert!(get_language("python").is_some());
         assert!(get_language("javascript").is_some());
+
+        // Other tests may register more languages concurrently
+        let names: Vec<_> = registered_languages()
+            .into_iter()
+            .filter(|(id, _)| ["rust", "python", "javascript"].contains(&id.as_str()))
+            .map(|(_, name)| name)
+            .collect();
+        assert_eq!(names, vec!["JavaScript", "Python", "Rust"]);
     }
 
     #[test]
@@ -232,4 +298,46 @@ mod tests {
         let py_lang = get_language_by_extension(".py").unwrap();
         assert_eq!(py_lang.config().name, "Python");
     }
+
+    #[test]
+    fn test_register_language() {
+        let config = LanguageConfig {
+            name: "Dummy".to_string(),
+            extensions: vec![".dummy".to_string()],
+            comments: Comments {
+                line: Some("--".to_string()),
+                block_start: None,
+                block_end: None,
+            },
+            brackets: Brackets { pairs: vec![('(', ')')] },
+            indentation: IndentationRules {
+                increase_indent: vec!["(".to_string()],
+                decrease_indent: vec![")".to_string()],
+            },
+        };
+        register_language(Language::new(config, tree_sitter_rust::language())).unwrap();
+
+        let language = get_language_by_extension(".dummy").unwrap();
+        assert_eq!(language.config().name, "Dummy");
+        assert!(get_language("dummy").is_some());
+        let associations = FileAssociations::new();
+        assert!(get_language_for_path(Path::new("/repo/test.dummy"), &associations).is_some());
+    }
+
+    #[test]
+    fn test_language_for_path() {
+        register_default_languages().unwrap();
+        let associations = FileAssociations::new()
+            .with("*.pyw", "python")
+            .with("gulpfile.py", "javascript")
+            .with("Dockerfile", "dockerfile");
+
+        let language = |path: &str| {
+            get_language_for_path(Path::new(path), &associations).map(|lang| lang.config().name.clone())
+        };
+        assert_eq!(language("/repo/tool.pyw").as_deref(), Some("Python"));
+        assert_eq!(language("/repo/gulpfile.py").as_deref(), Some("JavaScript"));
+        assert_eq!(language("/repo/main.rs").as_deref(), Some("Rust"));
+        assert_eq!(language("/repo/Dockerfile"), None);
+    }
 }
@@ -2,15 +2,26 @@
 //!
 //! Provides syntax highlighting and code analysis using tree-sitter
 
+mod comment;
+mod export;
 mod highlighter;
 mod language;
+mod locals;
 mod parser;
+mod symbols;
 mod theme;
 
+pub use comment::comment_continuation;
+pub use export::{to_ansi, to_html};
 pub use highlighter::{Highlighter, HighlightEvent};
-pub use language::{Language, LanguageConfig, get_language_by_extension};
-pub use parser::Parser;
-pub use theme::{Theme, Style};
+pub use language::{
+    Brackets, Comments, IndentationRules, Language, LanguageConfig, get_language_by_extension,
+    get_language_for_document, get_language_for_path, register_language, registered_languages,
+};
+pub use locals::find_definition;
+pub use parser::{Parser, ScopeKind};
+pub use symbols::{document_symbols, spawn_index, IndexProgress, IndexTask, Symbol, SymbolIndex};
+pub use theme::{Color, Theme, Style};
 
 use thiserror::Error;
 
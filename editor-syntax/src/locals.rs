@@ -0,0 +1,255 @@
+//! Same-file definition lookup using tree-sitter scopes
+//!
+//! A best-effort fallback for "go to definition" when no language server is
+//! connected. Definitions and scopes come from a `locals.scm`-style query.
+
+use std::ops::Range;
+use tree_sitter::{Node, Parser as TSParser, Query, QueryCursor};
+use crate::Language;
+
+/// Returns the locals query for a language
+///
+/// `@scope` marks nodes that limit visibility. `@definition.var` bindings are
+/// only visible after they are declared, while `@definition.item` names are
+/// visible throughout their scope.
+pub(crate) fn locals_query(language: &Language) -> Option<&'static str> {
+    match language.config().name.as_str() {
+        "Rust" => Some(r#"
+            (source_file) @scope
+            (block) @scope
+            (function_item) @scope
+            (closure_expression) @scope
+            (for_expression) @scope
+            (match_arm) @scope
+
+            (let_declaration pattern: (identifier) @definition.var)
+            (let_declaration pattern: (mut_pattern (identifier) @definition.var))
+            (let_declaration pattern: (tuple_pattern (identifier) @definition.var))
+            (parameter pattern: (identifier) @definition.var)
+            (parameter pattern: (mut_pattern (identifier) @definition.var))
+            (closure_parameters (identifier) @definition.var)
+            (for_expression pattern: (identifier) @definition.var)
+
+            (function_item name: (identifier) @definition.item)
+            (struct_item name: (type_identifier) @definition.item)
+            (enum_item name: (type_identifier) @definition.item)
+            (trait_item name: (type_identifier) @definition.item)
+            (type_item name: (type_identifier) @definition.item)
+            (const_item name: (identifier) @definition.item)
+            (static_item name: (identifier) @definition.item)
+        "#),
+        "Python" => Some(r#"
+            (module) @scope
+            (function_definition) @scope
+            (class_definition) @scope
+
+            (assignment left: (identifier) @definition.var)
+            (for_statement left: (identifier) @definition.var)
+            (parameters (identifier) @definition.var)
+
+            (function_definition name: (identifier) @definition.item)
+            (class_definition name: (identifier) @definition.item)
+        "#),
+        "JavaScript" => Some(r#"
+            (program) @scope
+            (statement_block) @scope
+            (function_declaration) @scope
+            (arrow_function) @scope
+
+            (variable_declarator name: (identifier) @definition.var)
+            (formal_parameters (identifier) @definition.var)
+
+            (function_declaration name: (identifier) @definition.item)
+            (class_declaration name: (identifier) @definition.item)
+        "#),
+        _ => None,
+    }
+}
+
+/// Kinds of declarations whose bindings are only visible after them, so the
+/// initializer of `let x = x + 1` still sees the earlier `x`
+const DECLARATIONS: &[&str] = &["let_declaration", "assignment", "variable_declarator"];
+
+/// A definition found by the locals query
+struct Definition {
+    /// Byte range of the defined name
+    name: Range<usize>,
+    /// Byte range of the innermost scope containing the definition
+    scope: Range<usize>,
+    /// True if the name is visible before its definition
+    hoisted: bool,
+    /// Offset from which a binding is visible
+    visible_from: usize,
+}
+
+/// Finds the same-file definition of the identifier at `offset`
+///
+/// Returns the byte range of the defining name. The innermost enclosing scope
+/// wins, and within a scope the closest preceding binding wins, so shadowed
+/// `let`s resolve correctly. Returns `None` if there is no identifier at the
+/// offset, the language has no locals query or no definition is found.
+pub fn find_definition(text: &str, offset: usize, lang: &Language) -> Option<Range<usize>> {
+    let query = Query::new(lang.ts_language(), locals_query(lang)?)
+        .map_err(|e| log::warn!("Invalid locals query for {}: {}", lang.config().name, e))
+        .ok()?;
+
+    let mut parser = TSParser::new();
+    parser.set_language(lang.ts_language()).ok()?;
+    let tree = parser.parse(text, None)?;
+
+    let reference = identifier_at(tree.root_node(), offset)?;
+    let name = &text[reference.byte_range()];
+
+    let mut scopes = Vec::new();
+    let mut definitions = Vec::new();
+    let mut cursor = QueryCursor::new();
+    for query_match in cursor.matches(&query, tree.root_node(), text.as_bytes()) {
+        for capture in query_match.captures {
+            let node = capture.node;
+            match query.capture_names()[capture.index as usize].as_str() {
+                "scope" => scopes.push(node.byte_range()),
+                kind @ ("definition.var" | "definition.item") if &text[node.byte_range()] == name => {
+                    definitions.push((node, kind == "definition.item"));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let definitions = definitions.into_iter().map(|(node, hoisted)| {
+        // An item such as a function is visible from the scope around it, not
+        // the scope it creates
+        let defined = if hoisted { node.parent().unwrap_or(node) } else { node };
+        let scope = innermost_scope(&scopes, &defined.byte_range()).unwrap_or(0..text.len());
+        Definition {
+            name: node.byte_range(),
+            visible_from: visible_from(node, &scope),
+            scope,
+            hoisted,
+        }
+    });
+
+    let reference = reference.byte_range();
+    definitions
+        .filter(|def| def.scope.start <= reference.start && reference.end <= def.scope.end)
+        .filter(|def| def.hoisted || def.visible_from <= reference.start || def.name == reference)
+        // Innermost scope first, then the closest preceding binding
+        .max_by_key(|def| (def.scope.start, std::cmp::Reverse(def.scope.end), !def.hoisted, def.name.start))
+        .map(|def| def.name)
+}
+
+/// Returns the identifier node touching `offset`
+///
+/// An offset just past the end of an identifier also finds it, so a cursor
+/// placed after a name works.
+fn identifier_at(root: Node<'_>, offset: usize) -> Option<Node<'_>> {
+    let is_identifier = |node: &Node| node.kind().ends_with("identifier");
+
+    root.descendant_for_byte_range(offset, offset)
+        .filter(is_identifier)
+        .or_else(|| {
+            root.descendant_for_byte_range(offset.checked_sub(1)?, offset.checked_sub(1)?)
+                .filter(is_identifier)
+        })
+}
+
+/// Returns the offset from which the binding `node` in `scope` is visible:
+/// the end of its declaration, or the binding itself for parameters
+fn visible_from(node: Node<'_>, scope: &Range<usize>) -> usize {
+    let mut ancestor = node.parent();
+    while let Some(parent) = ancestor.filter(|parent| parent.byte_range() != *scope) {
+        if DECLARATIONS.contains(&parent.kind()) {
+            return parent.end_byte();
+        }
+        ancestor = parent.parent();
+    }
+    node.start_byte()
+}
+
+/// Returns the smallest scope strictly containing `range`
+fn innermost_scope(scopes: &[Range<usize>], range: &Range<usize>) -> Option<Range<usize>> {
+    scopes.iter()
+        .filter(|scope| scope.start <= range.start && range.end <= scope.end && *scope != range)
+        .min_by_key(|scope| scope.end - scope.start)
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+
+    fn rust() -> Language {
+        language::register_default_languages().unwrap();
+        language::get_language("rust").unwrap()
+    }
+
+    /// Returns the offset of the `n`th occurrence of `needle`
+    fn nth(text: &str, needle: &str, n: usize) -> usize {
+        text.match_indices(needle).nth(n).unwrap().0
+    }
+
+    #[test]
+    fn test_queries_compile() {
+        language::register_default_languages().unwrap();
+        for name in ["rust", "python", "javascript"] {
+            let lang = language::get_language(name).unwrap();
+            let query = locals_query(&lang).unwrap();
+            assert!(Query::new(lang.ts_language(), query).is_ok(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_local_variable() {
+        let source = "fn main() {\n    let count = 1;\n    let total = count + 2;\n}\n";
+        let reference = nth(source, "count", 1);
+
+        let def = find_definition(source, reference + 2, &rust()).unwrap();
+        assert_eq!(def.start, nth(source, "count", 0));
+        assert_eq!(&source[def], "count");
+
+        // Closure parameters are visible in the closure, inside the `let`
+        let source = "fn main() {\n    let add = |y| y + 1;\n}\n";
+        let def = find_definition(source, nth(source, "y", 1), &rust()).unwrap();
+        assert_eq!(def.start, nth(source, "y", 0));
+    }
+
+    #[test]
+    fn test_shadowing_and_scopes() {
+        let source = "\
+fn helper(x: i32) -> i32 { x }
+fn main() {
+    let x = 1;
+    {
+        let x = 2;
+        helper(x);
+    }
+    let mut x = x + 1;
+    x
+}
+";
+        let rust = rust();
+
+        // The inner block's binding shadows the outer one
+        let inner = find_definition(source, nth(source, "x", 4), &rust).unwrap();
+        assert_eq!(inner.start, nth(source, "x", 3));
+
+        // A binding's initializer still sees the earlier binding
+        let initializer = find_definition(source, nth(source, "x", 6), &rust).unwrap();
+        assert_eq!(initializer.start, nth(source, "x", 2));
+
+        // After the block, the latest binding in the function is used
+        let last = find_definition(source, nth(source, "x", 7), &rust).unwrap();
+        assert_eq!(last.start, nth(source, "x", 5));
+
+        // Parameters are scoped to their function
+        let param = find_definition(source, nth(source, "x", 1), &rust).unwrap();
+        assert_eq!(param.start, nth(source, "x", 0));
+
+        // Items are visible before their definition
+        let helper = find_definition(source, nth(source, "helper", 1), &rust).unwrap();
+        assert_eq!(helper.start, nth(source, "helper", 0));
+
+        assert_eq!(find_definition(source, nth(source, "i32", 0), &rust), None);
+    }
+}
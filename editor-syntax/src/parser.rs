@@ -1,8 +1,22 @@
 //! Code parsing functionality using tree-sitter
 
+use std::ops::Range;
 use tree_sitter::{Parser as TSParser, Tree, Node, TreeCursor};
 use crate::{Language, Result, SyntaxError};
 
+/// Kind of syntactic scope enclosing a position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    /// Regular code
+    Code,
+    /// Line comment, e.g. `// ...`
+    LineComment,
+    /// Block comment, e.g. `/* ... */`
+    BlockComment,
+    /// String or character literal
+    String,
+}
+
 /// Code parser using tree-sitter
 pub struct Parser {
     /// Tree-sitter parser
@@ -11,6 +25,8 @@ pub struct Parser {
     language: Option<Language>,
     /// Current syntax tree
     tree: Option<Tree>,
+    /// Selections that were expanded from, used by `shrink_selection`
+    selection_history: Vec<Range<usize>>,
 }
 
 impl Parser {
@@ -20,6 +36,7 @@ impl Parser {
             parser: TSParser::new(),
             language: None,
             tree: None,
+            selection_history: Vec::new(),
         }
     }
 
@@ -28,6 +45,7 @@ impl Parser {
         self.parser.set_language(language.ts_language())
             .map_err(|e| SyntaxError::ParserError(e.to_string()))?;
         self.language = Some(language);
+        self.selection_history.clear();
         Ok(())
     }
 
@@ -64,6 +82,98 @@ impl Parser {
     pub fn tree(&self) -> Option<&Tree> {
         self.tree.as_ref()
     }
+
+    /// Expands a selection to the byte span of the smallest syntax node strictly containing it
+    ///
+    /// Returns the range unchanged if it already covers the whole tree.
+    pub fn expand_selection(&mut self, text: &str, range: Range<usize>) -> Result<Range<usize>> {
+        let tree = self.parse(text, None)?;
+
+        let mut node = tree.root_node()
+            .descendant_for_byte_range(range.start, range.end)
+            .unwrap_or_else(|| tree.root_node());
+
+        while !strictly_contains(&node.byte_range(), &range) {
+            match node.parent() {
+                Some(parent) => node = parent,
+                None => return Ok(range),
+            }
+        }
+
+        self.selection_history.push(range);
+        Ok(node.byte_range())
+    }
+
+    /// Shrinks a selection back towards the range it was expanded from
+    ///
+    /// If the range was not produced by `expand_selection`, the first named child
+    /// node inside the range is selected instead.
+    pub fn shrink_selection(&mut self, text: &str, range: Range<usize>) -> Result<Range<usize>> {
+        if let Some(previous) = self.selection_history.pop() {
+            if strictly_contains(&range, &previous) {
+                return Ok(previous);
+            }
+            self.selection_history.clear();
+        }
+
+        let tree = self.parse(text, None)?;
+        let node = tree.root_node()
+            .descendant_for_byte_range(range.start, range.end)
+            .unwrap_or_else(|| tree.root_node());
+
+        let mut cursor = node.walk();
+        let child = node
+            .named_children(&mut cursor)
+            .find(|child| strictly_contains(&range, &child.byte_range()));
+
+        Ok(child.map(|child| child.byte_range()).unwrap_or(range))
+    }
+
+    /// Returns the kind of scope enclosing the given byte offset
+    ///
+    /// A line comment also encloses the offset just past its end, so the end of
+    /// a `// ...` line counts as inside the comment.
+    pub fn scope_at(&mut self, text: &str, offset: usize) -> Result<ScopeKind> {
+        let tree = self.parse(text, None)?;
+        let block_start = self.language.as_ref()
+            .and_then(|lang| lang.config().comments.block_start.clone());
+
+        // Include the preceding byte so a position just past a node still finds it
+        let mut node = tree.root_node()
+            .descendant_for_byte_range(offset.saturating_sub(1), offset);
+        while let Some(current) = node {
+            let range = current.byte_range();
+            let scope = match current.kind() {
+                "line_comment" => Some(ScopeKind::LineComment),
+                "block_comment" => Some(ScopeKind::BlockComment),
+                "comment" => {
+                    let is_block = block_start.as_deref()
+                        .is_some_and(|token| text[range.clone()].starts_with(token));
+                    Some(if is_block { ScopeKind::BlockComment } else { ScopeKind::LineComment })
+                }
+                "string" | "string_literal" | "raw_string_literal" | "char_literal"
+                | "template_string" => Some(ScopeKind::String),
+                _ => None,
+            };
+
+            if let Some(scope) = scope {
+                let inside = match scope {
+                    ScopeKind::LineComment => range.start < offset && offset <= range.end,
+                    _ => range.start < offset && offset < range.end,
+                };
+                return Ok(if inside { scope } else { ScopeKind::Code });
+            }
+
+            node = current.parent();
+        }
+
+        Ok(ScopeKind::Code)
+    }
+}
+
+/// Returns true if `outer` contains `inner` and is larger than it
+fn strictly_contains(outer: &Range<usize>, inner: &Range<usize>) -> bool {
+    outer.start <= inner.start && inner.end <= outer.end && outer != inner
 }
 
 impl Default for Parser {
@@ -166,4 +276,45 @@ mod tests {
         let errors: Vec<_> = parser.iter_errors(&tree).collect();
         assert!(!errors.is_empty());
     }
+
+    #[test]
+    fn test_selection_expansion() {
+        language::register_default_languages().unwrap();
+        let mut parser = Parser::new();
+
+        let rust_lang = language::get_language("rust").unwrap();
+        parser.set_language(rust_lang).unwrap();
+
+        let source = "fn main() { foo(1); }";
+        let ident = 12..15;
+        assert_eq!(&source[ident.clone()], "foo");
+
+        // Identifier grows to the call expression, then the statement
+        let call = parser.expand_selection(source, ident.clone()).unwrap();
+        assert_eq!(&source[call.clone()], "foo(1)");
+
+        let statement = parser.expand_selection(source, call.clone()).unwrap();
+        assert_eq!(&source[statement.clone()], "foo(1);");
+
+        // Shrinking retraces the expansion
+        assert_eq!(parser.shrink_selection(source, statement).unwrap(), call);
+        assert_eq!(parser.shrink_selection(source, call).unwrap(), ident);
+    }
+
+    #[test]
+    fn test_scope_at() {
+        language::register_default_languages().unwrap();
+        let mut parser = Parser::new();
+
+        let rust_lang = language::get_language("rust").unwrap();
+        parser.set_language(rust_lang).unwrap();
+
+        let source = "// note\nlet s = \"text\"; /* block */\n";
+        assert_eq!(parser.scope_at(source, 4).unwrap(), ScopeKind::LineComment);
+        assert_eq!(parser.scope_at(source, 7).unwrap(), ScopeKind::LineComment);
+        assert_eq!(parser.scope_at(source, 10).unwrap(), ScopeKind::Code);
+        assert_eq!(parser.scope_at(source, 18).unwrap(), ScopeKind::String);
+        assert_eq!(parser.scope_at(source, 28).unwrap(), ScopeKind::BlockComment);
+        assert_eq!(parser.scope_at(source, source.len() - 1).unwrap(), ScopeKind::Code);
+    }
 }
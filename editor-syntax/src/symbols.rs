@@ -0,0 +1,359 @@
+//! Workspace symbol index built from tree-sitter definitions
+//!
+//! Indexing parses every file of a registered language under a directory and
+//! keeps the items it defines, keyed by path and modification time. Updates
+//! skip files whose modification time is unchanged, so an index loaded from
+//! disk or interrupted by cancellation picks up where it left off.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::SystemTime;
+use editor_core::IgnoreRules;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tree_sitter::{Parser as TSParser, Query, QueryCursor};
+use crate::language::get_language_by_extension;
+use crate::locals::locals_query;
+use crate::{Language, Result, SyntaxError};
+
+/// An item defined in a file, such as a function or a type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Symbol {
+    /// Name of the item
+    pub name: String,
+    /// Kind of the defining node, e.g. `function_item`
+    pub kind: String,
+    /// Byte range of the name
+    pub range: Range<usize>,
+    /// Zero-based line of the name
+    pub line: usize,
+}
+
+/// Symbols of one file, with the modification time they were read at
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileSymbols {
+    /// Modification time of the file when it was parsed
+    modified: SystemTime,
+    /// Items defined in the file
+    symbols: Vec<Symbol>,
+}
+
+/// Outcome of an index update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IndexProgress {
+    /// Files parsed because they were new or changed
+    pub parsed: usize,
+    /// Files skipped because they were unchanged
+    pub unchanged: usize,
+    /// Files dropped because they no longer exist
+    pub removed: usize,
+    /// False if the update was cancelled before visiting every file
+    pub complete: bool,
+}
+
+/// Returns the items defined in `text`
+///
+/// Items are the `@definition.item` captures of the language's locals query;
+/// languages without one have no symbols.
+pub fn document_symbols(text: &str, language: &Language) -> Vec<Symbol> {
+    let Some(query) = locals_query(language).and_then(|source| Query::new(language.ts_language(), source).ok()) else {
+        return Vec::new();
+    };
+    let mut parser = TSParser::new();
+    if parser.set_language(language.ts_language()).is_err() {
+        return Vec::new();
+    }
+    symbols_with(&mut parser, &query, text)
+}
+
+/// Returns the items defined in `text`, with a parser and query for its language
+fn symbols_with(parser: &mut TSParser, query: &Query, text: &str) -> Vec<Symbol> {
+    let Some(tree) = parser.parse(text, None) else {
+        return Vec::new();
+    };
+    let mut cursor = QueryCursor::new();
+    let mut symbols = Vec::new();
+    for query_match in cursor.matches(query, tree.root_node(), text.as_bytes()) {
+        for capture in query_match.captures {
+            if query.capture_names()[capture.index as usize] != "definition.item" {
+                continue;
+            }
+            let node = capture.node;
+            symbols.push(Symbol {
+                name: text[node.byte_range()].to_string(),
+                kind: node.parent().map_or(node.kind(), |parent| parent.kind()).to_string(),
+                range: node.byte_range(),
+                line: node.start_position().row,
+            });
+        }
+    }
+    symbols
+}
+
+/// Symbols of the files under a workspace directory
+///
+/// The index is shared: searches may run while a background update adds
+/// files, since each file is stored as soon as it is parsed.
+#[derive(Debug, Default)]
+pub struct SymbolIndex {
+    /// Symbols by file path
+    files: RwLock<HashMap<PathBuf, FileSymbols>>,
+}
+
+impl SymbolIndex {
+    /// Creates an empty index
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads an index saved with `save`, or an empty one if the file is missing
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| SyntaxError::ParserError(format!("{}: {}", path.display(), e)))?;
+        let files = serde_json::from_str(&contents)
+            .map_err(|e| SyntaxError::ParserError(format!("{}: {}", path.display(), e)))?;
+        Ok(Self { files: RwLock::new(files) })
+    }
+
+    /// Saves the index, so the next update only parses files changed since
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        let contents = serde_json::to_string(&*self.files.read())
+            .map_err(|e| SyntaxError::ParserError(e.to_string()))?;
+        std::fs::write(path, contents)
+            .map_err(|e| SyntaxError::ParserError(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Returns the number of indexed files
+    pub fn file_count(&self) -> usize {
+        self.files.read().len()
+    }
+
+    /// Returns the symbols of a file
+    pub fn symbols(&self, path: &Path) -> Vec<Symbol> {
+        self.files.read().get(path).map(|file| file.symbols.clone()).unwrap_or_default()
+    }
+
+    /// Returns the symbols whose name contains `query`, ignoring case, sorted
+    /// by name and path
+    pub fn search(&self, query: &str) -> Vec<(PathBuf, Symbol)> {
+        let query = query.to_lowercase();
+        let mut found: Vec<_> = self.files.read()
+            .iter()
+            .flat_map(|(path, file)| file.symbols.iter().map(move |symbol| (path, symbol)))
+            .filter(|(_, symbol)| symbol.name.to_lowercase().contains(&query))
+            .map(|(path, symbol)| (path.clone(), symbol.clone()))
+            .collect();
+        found.sort_by(|a, b| (&a.1.name, &a.0).cmp(&(&b.1.name, &b.0)));
+        found
+    }
+
+    /// Brings the index up to date with the files under `root`
+    ///
+    /// Files of registered languages are parsed if they are new or their
+    /// modification time changed. Hidden files and directories are skipped,
+    /// as are those the workspace's `.gitignore` files ignore.
+    /// When `cancelled` is set the update stops after the current file, keeping
+    /// what it parsed so far; files that disappeared are only dropped by a
+    /// complete update.
+    pub fn update(&self, root: &Path, cancelled: &AtomicBool) -> IndexProgress {
+        let mut progress = IndexProgress::default();
+        let mut queries: HashMap<String, Option<(TSParser, Query)>> = HashMap::new();
+        let mut seen = HashSet::new();
+        let mut directories = vec![root.to_path_buf()];
+        let mut ignore = IgnoreRules::new(root, Vec::new());
+
+        while let Some(directory) = directories.pop() {
+            ignore.add_gitignore(&directory);
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                if cancelled.load(Ordering::Relaxed) {
+                    return progress;
+                }
+                let path = entry.path();
+                if entry.file_name().to_string_lossy().starts_with('.') || ignore.is_ignored(&path) {
+                    continue;
+                }
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                if metadata.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let Some(language) = path.extension()
+                    .and_then(|extension| get_language_by_extension(&format!(".{}", extension.to_string_lossy())))
+                else {
+                    continue;
+                };
+                let Ok(modified) = metadata.modified() else {
+                    continue;
+                };
+                seen.insert(path.clone());
+
+                let unchanged = self.files.read().get(&path).is_some_and(|file| file.modified == modified);
+                if unchanged {
+                    progress.unchanged += 1;
+                    continue;
+                }
+
+                let parser = queries.entry(language.config().name.clone()).or_insert_with(|| {
+                    let query = Query::new(language.ts_language(), locals_query(&language)?).ok()?;
+                    let mut parser = TSParser::new();
+                    parser.set_language(language.ts_language()).ok()?;
+                    Some((parser, query))
+                });
+                let symbols = match (parser, std::fs::read_to_string(&path)) {
+                    (Some((parser, query)), Ok(text)) => symbols_with(parser, query, &text),
+                    _ => Vec::new(),
+                };
+                self.files.write().insert(path, FileSymbols { modified, symbols });
+                progress.parsed += 1;
+
+                // Let foreground work run between files
+                std::thread::yield_now();
+            }
+        }
+
+        let mut files = self.files.write();
+        let before = files.len();
+        files.retain(|path, _| !path.starts_with(root) || seen.contains(path));
+        progress.removed = before - files.len();
+        progress.complete = true;
+        progress
+    }
+}
+
+/// A running background index update
+///
+/// Dropping the task cancels it; calling `spawn_index` again resumes from
+/// what it stored.
+#[derive(Debug)]
+pub struct IndexTask {
+    /// Set to stop the update
+    cancelled: Arc<AtomicBool>,
+    /// The blocking task running the update
+    handle: Option<tokio::task::JoinHandle<IndexProgress>>,
+}
+
+impl IndexTask {
+    /// Stops the update after the file being parsed
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Waits for the update to finish or stop after cancellation
+    pub async fn wait(mut self) -> Result<IndexProgress> {
+        let handle = self.handle.take().expect("index task is only awaited once");
+        handle.await.map_err(|e| SyntaxError::ParserError(e.to_string()))
+    }
+}
+
+impl Drop for IndexTask {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            self.cancel();
+        }
+    }
+}
+
+/// Updates `index` with the files under `root` on the blocking thread pool,
+/// so startup and the UI are not held up
+pub fn spawn_index(index: Arc<SymbolIndex>, root: impl Into<PathBuf>) -> IndexTask {
+    let root = root.into();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&cancelled);
+    let handle = tokio::task::spawn_blocking(move || index.update(&root, &flag));
+    IndexTask {
+        cancelled,
+        handle: Some(handle),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::language;
+    use std::time::Duration;
+
+    fn write(path: &Path, text: &str, modified: SystemTime) {
+        std::fs::write(path, text).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        language::register_default_languages().unwrap();
+        let rust = language::get_language("rust").unwrap();
+
+        let symbols = document_symbols("struct Point;\n\nfn main() {\n    let x = 1;\n}\n", &rust);
+        let names: Vec<_> = symbols.iter().map(|s| (s.name.as_str(), s.kind.as_str(), s.line)).collect();
+        assert_eq!(names, vec![("Point", "struct_item", 0), ("main", "function_item", 2)]);
+    }
+
+    #[test]
+    fn test_reindex_only_changed_files() {
+        language::register_default_languages().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let time = SystemTime::now() - Duration::from_secs(60);
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        write(&dir.path().join("src/a.rs"), "fn alpha() {}", time);
+        write(&dir.path().join("src/b.rs"), "fn beta() {}", time);
+        write(&dir.path().join("c.py"), "def gamma():\n    pass\n", time);
+        std::fs::write(dir.path().join("notes.txt"), "fn not_code() {}").unwrap();
+        // Ignored build output is left out
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        write(&dir.path().join("target/generated.rs"), "fn generated() {}", time);
+
+        let index = SymbolIndex::new();
+        let never = AtomicBool::new(false);
+        let progress = index.update(dir.path(), &never);
+        assert_eq!((progress.parsed, progress.unchanged, progress.complete), (3, 0, true));
+        assert_eq!(index.search("a").len(), 3);
+
+        // Touching one file re-parses only that file
+        write(&dir.path().join("src/b.rs"), "fn beta_two() {}", time + Duration::from_secs(30));
+        std::fs::remove_file(dir.path().join("c.py")).unwrap();
+        let progress = index.update(dir.path(), &never);
+        assert_eq!((progress.parsed, progress.unchanged, progress.removed), (1, 1, 1));
+        assert_eq!(index.search("beta")[0].1.name, "beta_two");
+
+        // A saved index resumes without parsing anything
+        let saved = dir.path().join(".symbols.json");
+        index.save(&saved).unwrap();
+        let loaded = SymbolIndex::load(&saved).unwrap();
+        let progress = loaded.update(dir.path(), &never);
+        assert_eq!((progress.parsed, progress.unchanged), (0, 2));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_and_resume() {
+        language::register_default_languages().unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let time = SystemTime::now() - Duration::from_secs(60);
+        for i in 0..4 {
+            write(&dir.path().join(format!("f{}.rs", i)), &format!("fn f{}() {{}}", i), time);
+        }
+
+        // Cancelled before it starts, nothing is parsed and nothing is dropped
+        let index = Arc::new(SymbolIndex::new());
+        let cancelled = AtomicBool::new(true);
+        assert!(!index.update(dir.path(), &cancelled).complete);
+        assert_eq!(index.file_count(), 0);
+
+        let progress = spawn_index(Arc::clone(&index), dir.path()).wait().await.unwrap();
+        assert_eq!((progress.parsed, progress.complete), (4, true));
+        let progress = spawn_index(Arc::clone(&index), dir.path()).wait().await.unwrap();
+        assert_eq!((progress.parsed, progress.unchanged), (0, 4));
+    }
+}
@@ -33,7 +33,7 @@ impl Color {
 }
 
 /// Text style for syntax highlighting
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Style {
     /// Foreground color
     pub foreground: Option<Color>,
@@ -1,13 +1,192 @@
 //! Main application window
 
 use eframe::egui;
-use editor_core::Editor;
-use crate::{UiError, theme::Theme};
+use editor_core::{Action, AutosaveScheduler, Clock, ContentKind, DiagnosticSeverity, Editor, Encoding, FileFinder, IdleCallbackId, IdleScheduler, LineEnding, ReloadPolicy, Selection, SystemClock};
+use crate::{UiError, highlight::HighlightScheduler, layout::{highlight_to_layout_job, ruler_offsets, split_long_lines, visible_line_range, visible_line_range_with_overscan, visible_slice, visible_tab_range, whitespace_glyphs, RenderWhitespace, WrapMode, LONG_LINE_THRESHOLD}, open::{open_file, OpenError}};
+use crate::cursor::{caret_in_galley, grapheme_chars, paint_cursor, CursorBlink, CursorSettings};
+use crate::theme::{FontSettings, Theme};
+use crate::problems::{collect_problems, severity_icon};
+use crate::diff_view::DiffView;
+use crate::session::SharedSession;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
-use editor_syntax::{Highlighter, HighlightEvent, get_language_by_extension};
+use editor_syntax::{
+    Highlighter, HighlightEvent, Language, Parser, comment_continuation, get_language_for_document,
+    registered_languages,
+};
 use rfd::FileDialog;
 use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use notify::Watcher;
+
+/// Id of the action starting a new untitled document
+pub const NEW_FILE: &str = "file.new";
+
+/// Id of the action picking a file to open
+pub const OPEN_FILE: &str = "file.open";
+
+/// Id of the action saving the current document
+pub const SAVE_FILE: &str = "file.save";
+
+/// Id of the action closing the current document
+pub const CLOSE_FILE: &str = "file.close";
+
+/// Id of the action toggling line wrapping of the editor view
+pub const TOGGLE_WORD_WRAP: &str = "view.toggleWordWrap";
+
+/// Id of the action toggling a bookmark on the cursor line
+pub const TOGGLE_BOOKMARK: &str = "bookmarks.toggle";
+
+/// Id of the action moving the cursor to the next bookmark
+pub const NEXT_BOOKMARK: &str = "bookmarks.next";
+
+/// Id of the action moving the cursor to the previous bookmark
+pub const PREV_BOOKMARK: &str = "bookmarks.prev";
+
+/// Id of the action copying the current document into a scratch document
+pub const DUPLICATE_TO_SCRATCH: &str = "file.duplicateToScratch";
+
+/// Id of the action opening a workspace file found by fuzzy matching its path
+pub const GO_TO_FILE: &str = "file.goToFile";
+
+/// Id of the action moving the cursor to the start of a line chosen by number
+pub const GO_TO_LINE: &str = "editor.goToLine";
+
+/// Id of the action moving the cursor to the next problem
+pub const NEXT_DIAGNOSTIC: &str = "diagnostics.next";
+
+/// Id of the action moving the cursor to the previous problem
+pub const PREV_DIAGNOSTIC: &str = "diagnostics.prev";
+
+/// Id of the action growing the selection to the enclosing syntax node
+pub const EXPAND_SELECTION: &str = "editor.expandSelection";
+
+/// Id of the action shrinking the selection back to the node it grew from
+pub const SHRINK_SELECTION: &str = "editor.shrinkSelection";
+
+/// Id of the action undoing the last edit of the current document
+pub const UNDO: &str = "edit.undo";
+
+/// Id of the action redoing the last undone edit of the current document
+pub const REDO: &str = "edit.redo";
+
+/// Id of the action showing the search panel
+pub const FIND: &str = "edit.find";
+
+/// Id of the action showing the command palette
+pub const COMMAND_PALETTE: &str = "view.commandPalette";
+
+/// Number of matching files listed by "Go to File"
+const MAX_FILE_FINDER_RESULTS: usize = 50;
+
+/// Time a message stays in the status bar
+const STATUS_MESSAGE_DURATION: Duration = Duration::from_secs(5);
+
+/// Width assumed for tabs that have not been laid out yet
+const ESTIMATED_TAB_WIDTH: f32 = 120.0;
+
+/// Keyboard shortcuts and the ids of the actions they invoke
+const SHORTCUTS: [(egui::KeyboardShortcut, &str); 9] = [
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::N), NEW_FILE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::O), OPEN_FILE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::S), SAVE_FILE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::W), CLOSE_FILE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::G), GO_TO_LINE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::F), FIND),
+    (egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::P), COMMAND_PALETTE),
+    (egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::F8), NEXT_DIAGNOSTIC),
+    (egui::KeyboardShortcut::new(egui::Modifiers::SHIFT, egui::Key::F8), PREV_DIAGNOSTIC),
+];
+
+/// Returns the keyboard shortcut bound to an action, if any
+fn shortcut_for(id: &str) -> Option<egui::KeyboardShortcut> {
+    SHORTCUTS.iter().find(|(_, action)| *action == id).map(|(shortcut, _)| *shortcut)
+}
+
+/// Returns the largest scroll offset of the editor view, in rows
+///
+/// With `scroll_past_end` the view can scroll until only the last row is
+/// visible, otherwise it stops once the last row reaches the bottom.
+fn max_scroll(total_rows: usize, viewport_rows: f32, scroll_past_end: bool) -> f32 {
+    let last_row = total_rows.saturating_sub(1) as f32;
+    if scroll_past_end {
+        last_row
+    } else {
+        (total_rows as f32 - viewport_rows).clamp(0.0, last_row)
+    }
+}
+
+/// Returns the scroll offset, in rows, that puts `row` in the middle of the
+/// viewport, as far as the view can scroll
+fn centered_scroll(row: usize, total_rows: usize, viewport_rows: f32, scroll_past_end: bool) -> f32 {
+    let max = max_scroll(total_rows, viewport_rows, scroll_past_end);
+    (row as f32 + 0.5 - viewport_rows / 2.0).clamp(0.0, max)
+}
+
+/// Returns the byte offset of the start of a line, numbered from 1 as shown
+/// in the gutter; numbers past the end go to the last line
+fn line_start(text: &str, line: usize) -> usize {
+    text.match_indices('\n')
+        .map(|(newline, _)| newline + 1)
+        .take(line.saturating_sub(1))
+        .last()
+        .unwrap_or(0)
+}
+
+/// Commands acting on the editor view, sent by palette actions
+///
+/// Actions run outside the frame, so they queue commands that the view
+/// handles on its next frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ViewCommand {
+    /// Start a new untitled document
+    NewFile,
+    /// Pick a file and open it
+    OpenFile,
+    /// Save the current document, asking where
+    SaveFile,
+    /// Close the current document
+    CloseFile,
+    /// Switch line wrapping on or off
+    ToggleWordWrap,
+    /// Toggle a bookmark on the cursor line
+    ToggleBookmark,
+    /// Move the cursor to the next bookmark
+    NextBookmark,
+    /// Move the cursor to the previous bookmark
+    PrevBookmark,
+    /// Copy the current document into a scratch document and show it
+    DuplicateToScratch,
+    /// Show the "Go to File" finder
+    GoToFile,
+    /// Ask for a line number and move the cursor there
+    GoToLine,
+    /// Move the cursor to the next problem
+    NextDiagnostic,
+    /// Move the cursor to the previous problem
+    PrevDiagnostic,
+    /// Grow the selection to the enclosing syntax node
+    ExpandSelection,
+    /// Shrink the selection back to the node it grew from
+    ShrinkSelection,
+    /// Undo the last edit of the current document
+    Undo,
+    /// Redo the last undone edit of the current document
+    Redo,
+    /// Show the search panel
+    Find,
+    /// Show the command palette
+    CommandPalette,
+}
+
+/// Byte range of a run of lines, and whether it is a single line longer than
+/// `LONG_LINE_THRESHOLD`
+type LineRun = (Range<usize>, bool);
 
 /// Main application state
 #[allow(dead_code)]
@@ -16,12 +195,64 @@ pub struct EditorApp {
     editor: Arc<RwLock<Editor>>,
     /// Current theme
     theme: Theme,
+    /// Editor font, which may change at runtime
+    font_settings: FontSettings,
+    /// Version of the font settings last applied to the context
+    applied_font_version: Option<u64>,
     /// UI state
     ui_state: UiState,
     /// Current document content
     current_document_content: String,
     /// Cursor position (line, column)
     cursor_position: (usize, usize),
+    /// Language of the current document
+    language: Option<Language>,
+    /// Syntax parser for the current document
+    parser: Parser,
+    /// Syntax highlighter for the current document
+    highlighter: Highlighter,
+    /// Decides when to re-highlight after edits
+    highlight_scheduler: HighlightScheduler,
+    /// Decides when edits are saved automatically, if auto-save is on
+    autosave: Option<AutosaveScheduler>,
+    /// Events of the last highlight
+    highlight_events: Vec<HighlightEvent>,
+    /// Byte range of the text last requested to be highlighted
+    highlight_range: Option<Range<usize>>,
+    /// Byte range of the text laid out near the viewport in the last frame
+    visible_range: Option<Range<usize>>,
+    /// Runs of lines split around lines longer than `LONG_LINE_THRESHOLD`,
+    /// with the text version and length they were found for; empty if there
+    /// are no such lines
+    long_lines: Option<((u64, usize), Vec<LineRun>)>,
+    /// Sends results from background highlight tasks, with the text version
+    /// they were made for
+    highlight_tx: Sender<(u64, Vec<HighlightEvent>)>,
+    /// Receives results from background highlight tasks
+    highlight_rx: Receiver<(u64, Vec<HighlightEvent>)>,
+    /// Receives commands queued by view actions
+    view_commands: Receiver<ViewCommand>,
+    /// Files of the workspace for "Go to File", listed when first shown
+    file_finder: Option<FileFinder>,
+    /// Watches the workspace listed by "Go to File" for files created,
+    /// renamed or deleted by other programs
+    workspace_watcher: Option<notify::RecommendedWatcher>,
+    /// Receives the paths changed in the watched workspace
+    workspace_events: Option<Receiver<PathBuf>>,
+    /// Runs deferred work once input and edits have stopped for a while
+    idle: IdleScheduler,
+    /// What happens to open files changed by other programs
+    reload_policy: ReloadPolicy,
+    /// Set when open files should be checked for changes on disk, e.g. once
+    /// the editor becomes idle
+    disk_check_due: Arc<AtomicBool>,
+    /// Blink phase of the editor cursor
+    cursor_blink: CursorBlink,
+    /// Time source for status message expiry and auto-save
+    clock: Arc<dyn Clock>,
+    /// Bookmarks, recently opened files and the workspace, kept across
+    /// sessions by the program running the window
+    session: SharedSession,
 }
 
 /// UI state
@@ -34,12 +265,81 @@ struct UiState {
     show_file_explorer: bool,
     /// Show search panel
     show_search: bool,
+    /// Show problems panel
+    show_problems: bool,
     /// Show settings panel
     show_settings: bool,
     /// Panel sizes
     panel_sizes: PanelSizes,
     /// Current file name
     file_name: String,
+    /// Large file awaiting confirmation, with its size in bytes
+    pending_large_file: Option<(PathBuf, u64)>,
+    /// Files to open once the editor is free, and whether large ones may be
+    /// opened
+    queued_opens: Vec<(PathBuf, bool)>,
+    /// Byte offset to move the editor cursor to on the next frame, centering
+    /// it in the view
+    pending_jump: Option<usize>,
+    /// Byte range to select in the editor on the next frame
+    pending_selection: Option<Range<usize>>,
+    /// Document and selection in the view last shared with the editor, in
+    /// byte offsets into the view's text
+    shared_selection: Option<(String, Selection)>,
+    /// Scroll offset, in rows, to move the editor view to on the next frame
+    pending_scroll: Option<f32>,
+    /// Whether the editor view can scroll until only the last row is visible
+    scroll_past_end: bool,
+    /// Index of the first tab shown when the tabs overflow
+    first_visible_tab: usize,
+    /// Width of each document's tab when last shown
+    tab_widths: HashMap<String, f32>,
+    /// Show the "Go to Line" prompt
+    show_go_to_line: bool,
+    /// Files changed on disk while they had unsaved edits, for the user to
+    /// reload or keep
+    reload_conflicts: Vec<PathBuf>,
+    /// Whether the window had focus in the last frame
+    window_focused: bool,
+    /// Line number typed into the "Go to Line" prompt
+    go_to_line_query: String,
+    /// Open comparison between two documents
+    diff_view: Option<DiffView>,
+    /// Line ending style of the current document
+    line_ending: LineEnding,
+    /// Document with mixed line endings, and the style offered to normalize
+    /// them to
+    line_ending_offer: Option<(String, LineEnding)>,
+    /// Files already offered line ending normalization
+    offered_normalization: HashSet<PathBuf>,
+    /// Whether the current document is text, and its encoding
+    content_kind: ContentKind,
+    /// Language id chosen with "Change Language Mode", overriding detection
+    language_override: Option<String>,
+    /// Filter text typed into the command palette
+    palette_query: String,
+    /// Show the "Go to File" finder
+    show_file_finder: bool,
+    /// Filter text typed into the "Go to File" finder
+    file_finder_query: String,
+    /// Message shown in the status bar, e.g. why a file failed to open, and
+    /// when it was shown
+    status_message: Option<(String, Instant)>,
+    /// Line wrapping of the editor view, starting from the preference
+    wrap_mode: WrapMode,
+    /// Wrapping "Toggle Word Wrap" turns back on, the last one used
+    wrap_when_toggled: WrapMode,
+    /// Scroll the editor cursor into view on the next frame, e.g. once lines
+    /// are wrapped differently
+    reveal_cursor: bool,
+    /// Which whitespace the editor view makes visible
+    render_whitespace: RenderWhitespace,
+    /// Columns the editor view draws vertical rulers at
+    rulers: Vec<u32>,
+    /// Least severe problems listed and visited by F8, all without one
+    problem_filter: Option<DiagnosticSeverity>,
+    /// Style and blinking of the editor cursor
+    cursor: CursorSettings,
 }
 
 /// Panel sizes
@@ -56,21 +356,189 @@ struct PanelSizes {
 
 impl EditorApp {
     /// Creates a new editor application
-    pub fn new(editor: Editor) -> Self {
-        Self {
+    pub fn new(
+        editor: Editor,
+        font_settings: FontSettings,
+        wrap_mode: WrapMode,
+        render_whitespace: RenderWhitespace,
+        rulers: Vec<u32>,
+        cursor: CursorSettings,
+    ) -> Self {
+        let ui_state = UiState {
+            wrap_mode,
+            wrap_when_toggled: wrap_mode,
+            render_whitespace,
+            rulers,
+            cursor,
+            ..Default::default()
+        };
+
+        let (highlight_tx, highlight_rx) = mpsc::channel();
+
+        // Files, wrapping, bookmarks and the cursor are view state, so these
+        // actions only queue a command for the view
+        let (view_command_tx, view_commands) = mpsc::channel();
+        let view_actions = [
+            (NEW_FILE, "New", "File", ViewCommand::NewFile),
+            (OPEN_FILE, "Open...", "File", ViewCommand::OpenFile),
+            (SAVE_FILE, "Save", "File", ViewCommand::SaveFile),
+            (CLOSE_FILE, "Close", "File", ViewCommand::CloseFile),
+            (TOGGLE_WORD_WRAP, "Toggle Word Wrap", "View", ViewCommand::ToggleWordWrap),
+            (TOGGLE_BOOKMARK, "Toggle Bookmark", "Go", ViewCommand::ToggleBookmark),
+            (NEXT_BOOKMARK, "Next Bookmark", "Go", ViewCommand::NextBookmark),
+            (PREV_BOOKMARK, "Previous Bookmark", "Go", ViewCommand::PrevBookmark),
+            (DUPLICATE_TO_SCRATCH, "Duplicate to Scratch Buffer", "File", ViewCommand::DuplicateToScratch),
+            (GO_TO_FILE, "Go to File", "Go", ViewCommand::GoToFile),
+            (GO_TO_LINE, "Go to Line...", "Go", ViewCommand::GoToLine),
+            (NEXT_DIAGNOSTIC, "Go to Next Problem", "Go", ViewCommand::NextDiagnostic),
+            (PREV_DIAGNOSTIC, "Go to Previous Problem", "Go", ViewCommand::PrevDiagnostic),
+            (EXPAND_SELECTION, "Expand Selection", "Edit", ViewCommand::ExpandSelection),
+            (SHRINK_SELECTION, "Shrink Selection", "Edit", ViewCommand::ShrinkSelection),
+            (UNDO, "Undo", "Edit", ViewCommand::Undo),
+            (REDO, "Redo", "Edit", ViewCommand::Redo),
+            (FIND, "Find", "Edit", ViewCommand::Find),
+            (COMMAND_PALETTE, "Command Palette...", "View", ViewCommand::CommandPalette),
+        ];
+        for (id, title, category, command) in view_actions {
+            let tx: Sender<ViewCommand> = view_command_tx.clone();
+            editor.actions().register(Action::new(id, title, category, move |_| {
+                let _ = tx.send(command);
+                Ok(serde_json::Value::Null)
+            }));
+        }
+
+        let mut app = Self {
             editor: Arc::new(RwLock::new(editor)),
             theme: Theme::default(),
-            ui_state: UiState::default(),
+            font_settings,
+            applied_font_version: None,
+            ui_state,
             current_document_content: String::new(),
             cursor_position: (0, 0),
+            language: None,
+            parser: Parser::new(),
+            highlighter: Highlighter::new(),
+            highlight_scheduler: HighlightScheduler::default(),
+            autosave: None,
+            highlight_events: Vec::new(),
+            highlight_range: None,
+            visible_range: None,
+            long_lines: None,
+            highlight_tx,
+            highlight_rx,
+            view_commands,
+            file_finder: None,
+            workspace_watcher: None,
+            workspace_events: None,
+            idle: IdleScheduler::default(),
+            reload_policy: ReloadPolicy::default(),
+            disk_check_due: Arc::new(AtomicBool::new(false)),
+            cursor_blink: CursorBlink::new(),
+            clock: Arc::new(SystemClock),
+            session: SharedSession::default(),
+        };
+
+        // Other programs may have changed open files while the user was away
+        let due = app.disk_check_due.clone();
+        app.on_idle(0, move || due.store(true, Ordering::Relaxed));
+        app
+    }
+
+    /// Registers work to run each time the editor becomes idle, e.g. indexing
+    ///
+    /// Higher priorities run first, one callback per frame.
+    pub fn on_idle(&mut self, priority: i32, callback: impl FnMut() + Send + 'static) -> IdleCallbackId {
+        self.idle.register(priority, callback)
+    }
+
+    /// Sets the quiet period after an edit before re-highlighting
+    pub fn set_highlight_debounce(&mut self, delay: Duration) {
+        self.highlight_scheduler.set_delay(delay);
+    }
+
+    /// Sets the theme, applied from the next frame
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    /// Sets whether the editor view can scroll until only the last row is
+    /// visible
+    pub fn set_scroll_past_end(&mut self, scroll_past_end: bool) {
+        self.ui_state.scroll_past_end = scroll_past_end;
+    }
+
+    /// Turns auto-save on with the time allowed between an edit and its
+    /// save, or off with `None`
+    pub fn set_autosave(&mut self, interval: Option<Duration>) {
+        self.autosave = interval.map(|interval| AutosaveScheduler::with_clock(interval, self.clock.clone()));
+    }
+
+    /// Sets the time source for status message expiry and auto-save, e.g. a
+    /// `MockClock` in tests
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+        if let Some(autosave) = &self.autosave {
+            self.set_autosave(Some(autosave.interval()));
         }
     }
+
+    /// Shares session data with the program running the window, which saves
+    /// it once the window closes
+    pub fn set_session(&mut self, session: SharedSession) {
+        self.session = session;
+    }
+
+    /// Sets what happens to open files changed by other programs
+    pub fn set_reload_policy(&mut self, policy: ReloadPolicy) {
+        self.reload_policy = policy;
+    }
+
+    /// Shows a message in the status bar for `STATUS_MESSAGE_DURATION`
+    fn show_status(&mut self, message: impl Into<String>) {
+        self.ui_state.status_message = Some((message.into(), self.clock.now()));
+    }
+
+    /// Returns the status bar message, unless it has expired, and the time
+    /// left until it does
+    fn current_status(&self) -> Option<(&str, Duration)> {
+        let (message, shown) = self.ui_state.status_message.as_ref()?;
+        let elapsed = self.clock.now().saturating_duration_since(*shown);
+        let remaining = STATUS_MESSAGE_DURATION.checked_sub(elapsed).filter(|remaining| !remaining.is_zero())?;
+        Some((message, remaining))
+    }
 }
 
 impl eframe::App for EditorApp {
+    fn on_close_event(&mut self) -> bool {
+        // Remember bookmarks where edits have moved them
+        if let Ok(mut editor) = self.editor.try_write() {
+            if let Err(e) = self.sync_document(&mut editor) {
+                log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+            }
+            self.store_bookmarks(&editor);
+        }
+        true
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Apply theme
+        // Apply theme, and the editor font whenever it changed
         self.theme.apply(ctx);
+        let font_version = self.font_settings.version();
+        if self.applied_font_version != Some(font_version) {
+            self.font_settings.get().apply(ctx);
+            self.applied_font_version = Some(font_version);
+        }
+
+        // Retry opening files while the editor was busy, or after the large
+        // file being confirmed
+        if self.ui_state.pending_large_file.is_none() {
+            for (path, allow_large) in std::mem::take(&mut self.ui_state.queued_opens) {
+                self.load_file(&path, allow_large);
+            }
+            if !self.ui_state.queued_opens.is_empty() {
+                ctx.request_repaint();
+            }
+        }
 
         // Show menu bar
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
@@ -80,6 +548,11 @@ impl eframe::App for EditorApp {
         // Show editor
         self.show_editor(ctx);
 
+        // Status bar
+        egui::TopBottomPanel::bottom("status_bar").show(ctx, |ui| {
+            self.show_status_bar(ui);
+        });
+
         // Bottom panel (output, search results)
         egui::TopBottomPanel::bottom("bottom_panel")
             .min_height(100.0)
@@ -98,34 +571,344 @@ impl eframe::App for EditorApp {
                 });
         }
 
+        // Command palette
+        if self.ui_state.show_command_palette {
+            self.show_command_palette(ctx);
+        }
+
+        // Go to File
+        self.apply_workspace_events();
+        if self.ui_state.show_file_finder {
+            self.show_file_finder(ctx);
+        }
+
+        // Go to Line
+        if self.ui_state.show_go_to_line {
+            self.show_go_to_line(ctx);
+        }
+
+        // Large file confirmation
+        if self.ui_state.pending_large_file.is_some() {
+            self.show_large_file_confirmation(ctx);
+        }
+
+        // Files changed on disk, when idle or back from another program
+        let focused = ctx.input(|i| i.focused);
+        if focused && !std::mem::replace(&mut self.ui_state.window_focused, focused) {
+            self.disk_check_due.store(true, Ordering::Relaxed);
+        }
+        if self.disk_check_due.swap(false, Ordering::Relaxed) {
+            self.check_disk_changes();
+        }
+        if !self.ui_state.reload_conflicts.is_empty() {
+            self.show_reload_conflict(ctx);
+        }
+
+        // Document comparison
+        if self.ui_state.diff_view.is_some() {
+            self.show_diff_view(ctx);
+        }
+
         // Handle keyboard shortcuts
         self.handle_shortcuts(ctx);
+
+        // Save edits once they have been unsaved for the auto-save interval
+        self.autosave_if_due(ctx);
+
+        // Run deferred work once input has stopped, and wake up when it is due
+        if ctx.input(|i| !i.events.is_empty()) {
+            self.idle.note_activity();
+            self.cursor_blink.note_activity();
+        }
+        if self.idle.poll() {
+            ctx.request_repaint();
+        } else if let Some(remaining) = self.idle.time_until_idle() {
+            ctx.request_repaint_after(remaining);
+        }
     }
 }
 
 impl EditorApp {
+    /// Opens a file, asking for confirmation first if it exceeds the large file threshold
+    fn request_open(&mut self, path: PathBuf) {
+        self.load_file(&path, false);
+    }
+
+    /// Opens a file in the editor and shows it, reporting failures in the status bar
+    fn load_file(&mut self, path: &Path, allow_large: bool) {
+        let Ok(mut editor) = self.editor.try_write() else {
+            // The editor is busy, e.g. saving in the background; retry next frame
+            self.ui_state.queued_opens.push((path.to_path_buf(), allow_large));
+            return;
+        };
+        if let Err(e) = open_file(&mut editor, path, allow_large) {
+            if let OpenError::TooLarge { size, .. } = e {
+                // Ask before opening, one file at a time
+                if self.ui_state.pending_large_file.is_some() {
+                    self.ui_state.queued_opens.push((path.to_path_buf(), false));
+                } else {
+                    self.ui_state.pending_large_file = Some((path.to_path_buf(), size));
+                }
+                return;
+            }
+            log::warn!("{}", e);
+            drop(editor);
+            self.show_status(e.to_string());
+            return;
+        }
+        let Some(doc) = editor.active_document_mut() else {
+            return;
+        };
+
+        // Restore bookmarks from earlier sessions, dropping those past the end
+        let bookmarks = doc.path().map(|path| self.session.bookmarks(path)).unwrap_or_default();
+        let restored = doc.restore_bookmarks(&bookmarks);
+        if restored < bookmarks.len() {
+            log::info!("Dropped {} bookmarks past the end of {}", bookmarks.len() - restored, path.display());
+        }
+
+        self.ui_state.content_kind = doc.content_kind();
+        self.ui_state.line_ending = *doc.line_ending();
+        self.ui_state.file_name = doc.name().to_string();
+        self.current_document_content = doc.text();
+        drop(editor);
+
+        self.session.note_opened(path);
+        if let Some(finder) = &mut self.file_finder {
+            finder.note_opened(path);
+        }
+
+        self.cursor_position = (0, 0);
+        self.ui_state.status_message = None;
+        self.ui_state.language_override = None;
+        self.reset_highlighter();
+        if let Some(autosave) = &mut self.autosave {
+            autosave.note_saved();
+        }
+
+        // Offer to normalize mixed line endings, once per file
+        if let Some(line_ending) = LineEnding::detect_mixed(&self.current_document_content) {
+            if self.ui_state.offered_normalization.insert(path.to_path_buf()) {
+                self.ui_state.line_ending_offer = Some((self.ui_state.file_name.clone(), line_ending));
+            }
+        }
+    }
+
+    /// Selects the document language and highlights on the next frame
+    ///
+    /// The language override wins over the file name's extension.
+    fn reset_highlighter(&mut self) {
+        self.highlighter = Highlighter::new();
+        self.parser = Parser::new();
+        let setting = self.ui_state.language_override.as_deref()
+            .or_else(|| self.ui_state.file_name.rsplit_once('.').map(|(_, extension)| extension));
+        self.language = setting.and_then(get_language_for_document)
+            .filter(|_| !self.ui_state.content_kind.is_binary());
+        if let Some(language) = &self.language {
+            let _ = self.highlighter.set_language(language.clone());
+            let _ = self.parser.set_language(language.clone());
+        }
+        self.highlight_events.clear();
+        self.highlight_range = None;
+        self.highlight_scheduler.request_immediate();
+    }
+
+    /// Continues a comment onto the line started by pressing Enter
+    ///
+    /// `cursor` is the character index just after the inserted newline. Returns the
+    /// new cursor index if a continuation was inserted.
+    fn continue_comment(&mut self, cursor: usize) -> Option<usize> {
+        let language = self.language.as_ref()?;
+        let text = &self.current_document_content;
+
+        let (newline, ch) = text.char_indices().nth(cursor.checked_sub(1)?)?;
+        if ch != '\n' {
+            return None;
+        }
+
+        let line_start = text[..newline].rfind('\n').map_or(0, |i| i + 1);
+        let scope = self.parser.scope_at(text, newline).ok()?;
+        let continuation = comment_continuation(&text[line_start..newline], scope, language)?;
+
+        self.current_document_content.insert_str(newline + 1, &continuation);
+        Some(cursor + continuation.chars().count())
+    }
+
+    /// Re-highlights the text around the viewport on a background task
+    ///
+    /// Results arrive on `highlight_rx`, tagged with the text version, and a
+    /// request still running is cancelled. Without a tokio runtime the document is
+    /// highlighted on the UI thread instead. The whole document is highlighted
+    /// until the viewport is known. Binary documents are not highlighted.
+    fn request_highlight(&mut self, ctx: &egui::Context) {
+        if self.ui_state.content_kind.is_binary() {
+            return;
+        }
+
+        let len = self.current_document_content.len();
+        let range = self.visible_range.clone()
+            .map_or(0..len, |range| range.start.min(len)..range.end.min(len));
+        self.highlight_range = Some(range.clone());
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            if let Ok(events) = self.highlighter.highlight_range(&self.current_document_content, range) {
+                self.apply_highlights(events);
+            }
+            return;
+        };
+
+        let task = self.highlighter.highlight_range_async(self.current_document_content.clone(), range);
+        let version = self.highlight_scheduler.version();
+        let tx = self.highlight_tx.clone();
+        let ctx = ctx.clone();
+        let handle = runtime.spawn(async move {
+            if let Ok(events) = task.await {
+                let _ = tx.send((version, events));
+                ctx.request_repaint();
+            }
+        });
+        self.highlight_scheduler.start_task(handle.abort_handle());
+    }
+
+    /// Keeps highlight events for laying out the text
+    fn apply_highlights(&mut self, events: Vec<HighlightEvent>) {
+        self.highlight_events = events;
+    }
+
+    fn show_large_file_confirmation(&mut self, ctx: &egui::Context) {
+        let Some((path, size)) = self.ui_state.pending_large_file.clone() else {
+            return;
+        };
+
+        let mut open = false;
+        let mut cancel = false;
+        egui::Window::new("Large File")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} is {:.1} MB. Opening it may use a lot of memory.",
+                    path.display(),
+                    size as f64 / (1024.0 * 1024.0)
+                ));
+                ui.horizontal(|ui| {
+                    open = ui.button("Open Anyway").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if open {
+            self.ui_state.pending_large_file = None;
+            self.load_file(&path, true);
+        } else if cancel {
+            self.ui_state.pending_large_file = None;
+        }
+    }
+
+    /// Applies the reload policy to open files changed on disk
+    ///
+    /// Edits in the view count as unsaved edits of the shown document.
+    /// Reloaded text replaces the view's; files with unsaved edits are left
+    /// for the user to reload or keep.
+    fn check_disk_changes(&mut self) {
+        let Ok(mut editor) = self.editor.try_write() else {
+            // The editor is busy, e.g. saving in the background; retry next frame
+            self.disk_check_due.store(true, Ordering::Relaxed);
+            return;
+        };
+        if let Err(e) = self.sync_document(&mut editor) {
+            log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+        }
+        let changes = editor.reload_changed_files(self.reload_policy);
+        drop(editor);
+
+        self.show_reloaded(&changes.reloaded);
+        for path in changes.conflicts {
+            if !self.ui_state.reload_conflicts.contains(&path) {
+                self.ui_state.reload_conflicts.push(path);
+            }
+        }
+    }
+
+    /// Shows the text of the shown document again if it was reloaded
+    fn show_reloaded(&mut self, reloaded: &[PathBuf]) {
+        let Some(first) = reloaded.first() else {
+            return;
+        };
+        let Ok(editor) = self.editor.try_read() else {
+            return;
+        };
+        let shown = editor.active_document()
+            .filter(|doc| doc.name() == self.ui_state.file_name)
+            .filter(|doc| doc.path().is_some_and(|path| reloaded.iter().any(|reloaded| reloaded == path)));
+        if let Some(doc) = shown {
+            self.current_document_content = doc.text();
+            self.ui_state.line_ending = *doc.line_ending();
+            self.ui_state.content_kind = doc.content_kind();
+            self.highlight_scheduler.request_immediate();
+        }
+        drop(editor);
+
+        let message = match reloaded.len() {
+            1 => format!("Reloaded {}, changed on disk", first.display()),
+            count => format!("Reloaded {} files changed on disk", count),
+        };
+        self.show_status(message);
+    }
+
+    /// Asks whether to reload a file changed on disk while it had unsaved
+    /// edits, or keep the edits
+    fn show_reload_conflict(&mut self, ctx: &egui::Context) {
+        let Some(path) = self.ui_state.reload_conflicts.first().cloned() else {
+            return;
+        };
+
+        let mut reload = false;
+        let mut keep = false;
+        egui::Window::new("File Changed on Disk")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} was changed by another program, and has unsaved edits.",
+                    path.display()
+                ));
+                ui.horizontal(|ui| {
+                    reload = ui.button("Reload").clicked();
+                    keep = ui.button("Keep My Edits").clicked();
+                });
+            });
+
+        if reload {
+            self.resolve_reload_conflict(&path, true);
+        } else if keep {
+            self.resolve_reload_conflict(&path, false);
+        }
+    }
+
+    /// Reloads a conflicting file, replacing its unsaved edits, or keeps the
+    /// edits
+    fn resolve_reload_conflict(&mut self, path: &Path, reload: bool) {
+        if reload {
+            let Ok(mut editor) = self.editor.try_write() else {
+                return;
+            };
+            if let Err(e) = editor.reload_file(path) {
+                log::warn!("Failed to reload {}: {}", path.display(), e);
+                drop(editor);
+                self.show_status(format!("Failed to reload {}: {}", path.display(), e));
+            } else {
+                drop(editor);
+                self.show_reloaded(&[path.to_path_buf()]);
+            }
+        }
+        self.ui_state.reload_conflicts.retain(|conflict| conflict != path);
+    }
+
     fn show_menu_bar(&mut self, ui: &mut egui::Ui) {
         egui::menu::bar(ui, |ui| {
             ui.menu_button("File", |ui| {
-                if ui.button("New").clicked() {
-                    self.current_document_content.clear();
-                    self.cursor_position = (0, 0);
-                    self.ui_state.file_name = "untitled".to_string();
-                }
-                if ui.button("Open...").clicked() {
-                    if let Some(path) = FileDialog::new().pick_file() {
-                        if let Ok(content) = fs::read_to_string(&path) {
-                            self.current_document_content = content;
-                            self.cursor_position = (0, 0);
-                            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                                self.ui_state.file_name = name.to_string();
-                            }
-                        }
-                    }
-                }
-                if ui.button("Save").clicked() {
-                    // TODO: Save current file
-                }
+                self.action_menu_items(ui, "File");
                 ui.separator();
                 if ui.button("Exit").clicked() {
                     // TODO: Exit application
@@ -133,12 +916,7 @@ impl EditorApp {
             });
 
             ui.menu_button("Edit", |ui| {
-                if ui.button("Undo").clicked() {
-                    // TODO: Undo
-                }
-                if ui.button("Redo").clicked() {
-                    // TODO: Redo
-                }
+                self.action_menu_items(ui, "Edit");
                 ui.separator();
                 if ui.button("Cut").clicked() {
                     // TODO: Cut
@@ -149,6 +927,13 @@ impl EditorApp {
                 if ui.button("Paste").clicked() {
                     // TODO: Paste
                 }
+                ui.separator();
+                ui.menu_button("Change Line Endings", |ui| {
+                    self.line_ending_menu(ui);
+                });
+                ui.menu_button("Change Language Mode", |ui| {
+                    self.language_menu(ui);
+                });
             });
 
             ui.menu_button("View", |ui| {
@@ -158,7 +943,166 @@ impl EditorApp {
                 if ui.checkbox(&mut self.ui_state.show_search, "Search").clicked() {
                     // Toggle search panel
                 }
+                self.action_menu_items(ui, "View");
+                ui.menu_button("Compare Active Document With", |ui| {
+                    let names = match self.editor.try_read() {
+                        Ok(editor) => {
+                            let active = editor.active_document().map(|doc| doc.name().to_string());
+                            editor.document_names()
+                                .into_iter()
+                                .filter(|name| Some(name) != active.as_ref())
+                                .collect()
+                        }
+                        Err(_) => Vec::new(),
+                    };
+                    for name in names {
+                        if ui.button(&name).clicked() {
+                            self.compare_with(&name);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+
+            ui.menu_button("Go", |ui| {
+                self.action_menu_items(ui, "Go");
+            });
+        });
+    }
+
+    /// Lists the available actions of a category as menu items, with their
+    /// shortcuts, and invokes the one clicked
+    fn action_menu_items(&mut self, ui: &mut egui::Ui, category: &str) {
+        let actions = match self.editor.try_read() {
+            Ok(editor) => editor.actions().menu(category),
+            Err(_) => return,
+        };
+        for action in actions {
+            let mut button = egui::Button::new(action.title.as_str());
+            if let Some(shortcut) = shortcut_for(&action.id) {
+                button = button.shortcut_text(ui.ctx().format_shortcut(&shortcut));
+            }
+            if ui.add(button).clicked() {
+                self.invoke_action(&action.id);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Invokes an action by id, logging failures
+    fn invoke_action(&self, id: &str) {
+        if let Ok(editor) = self.editor.try_read() {
+            if let Err(e) = editor.actions().invoke(id, serde_json::Value::Null) {
+                log::warn!("Failed to run {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Lists the line ending styles, converting the document when one is picked
+    fn line_ending_menu(&mut self, ui: &mut egui::Ui) {
+        for line_ending in LineEnding::ALL {
+            let selected = self.ui_state.line_ending == line_ending;
+            if ui.selectable_label(selected, line_ending.label()).clicked() {
+                self.change_line_ending(line_ending);
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Converts the current document to the given line ending style
+    ///
+    /// The text is replaced in one edit so it undoes as a single step.
+    fn change_line_ending(&mut self, line_ending: LineEnding) {
+        let normalized = line_ending.normalize(&self.current_document_content);
+        if normalized != self.current_document_content {
+            self.current_document_content = normalized;
+            self.highlight_scheduler.request_immediate();
+        }
+        self.ui_state.line_ending = line_ending;
+
+        // Keep the editor's copy in sync so subscribers see the change
+        if let Ok(mut editor) = self.editor.try_write() {
+            let is_current = editor.active_document()
+                .is_some_and(|doc| doc.name() == self.ui_state.file_name);
+            if is_current {
+                let _ = editor.change_line_ending(line_ending);
+            }
+        }
+    }
+
+    /// Lists the registered languages, plus automatic detection
+    fn language_menu(&mut self, ui: &mut egui::Ui) {
+        let automatic = self.ui_state.language_override.is_none();
+        if ui.selectable_label(automatic, "Auto Detect").clicked() {
+            self.change_language(None);
+            ui.close_menu();
+        }
+        ui.separator();
+        for (id, name) in registered_languages() {
+            let selected = self.ui_state.language_override.as_ref() == Some(&id);
+            if ui.selectable_label(selected, name).clicked() {
+                self.change_language(Some(id));
+                ui.close_menu();
+            }
+        }
+    }
+
+    /// Overrides the current document's language and re-highlights it
+    fn change_language(&mut self, language: Option<String>) {
+        self.ui_state.language_override = language.clone();
+        self.reset_highlighter();
+
+        // Keep the editor's copy in sync so subscribers see the change
+        if let Ok(mut editor) = self.editor.try_write() {
+            let is_current = editor.active_document()
+                .is_some_and(|doc| doc.name() == self.ui_state.file_name);
+            if is_current {
+                let _ = editor.change_language(language);
+            }
+        }
+    }
+
+    fn show_status_bar(&mut self, ui: &mut egui::Ui) {
+        // Plugin items, e.g. errors from plugin commands run from the palette
+        let items = match self.editor.try_read() {
+            Ok(editor) => editor.status_items().items(),
+            Err(_) => Vec::new(),
+        };
+        if let Some((message, remaining)) = self.current_status() {
+            ui.colored_label(ui.visuals().error_fg_color, message);
+            ui.ctx().request_repaint_after(remaining);
+        }
+        if let Some((name, line_ending)) = self.ui_state.line_ending_offer.clone() {
+            if name == self.ui_state.file_name {
+                ui.label(format!("{} has mixed line endings", name));
+                if ui.button(format!("Normalize to {}", line_ending.label())).clicked() {
+                    self.change_line_ending(line_ending);
+                    self.ui_state.line_ending_offer = None;
+                }
+                if ui.button("Dismiss").clicked() {
+                    self.ui_state.line_ending_offer = None;
+                }
+            }
+        }
+        for item in items {
+            let response = ui.label(&item.text);
+            if let Some(tooltip) = &item.tooltip {
+                response.on_hover_text(tooltip);
+            }
+        }
+
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            let language = self.language.as_ref()
+                .map_or_else(|| "Plain Text".to_string(), |language| language.config().name.clone());
+            ui.menu_button(language, |ui| {
+                self.language_menu(ui);
+            });
+            ui.separator();
+            ui.menu_button(self.ui_state.line_ending.label(), |ui| {
+                self.line_ending_menu(ui);
             });
+            ui.separator();
+            ui.label(self.ui_state.content_kind.label());
         });
     }
 
@@ -175,11 +1119,11 @@ impl EditorApp {
     fn show_bottom_panel(&mut self, ui: &mut egui::Ui) {
         ui.horizontal(|ui| {
             let _search_selected = ui.selectable_value(&mut self.ui_state.show_search, true, "Search").clicked();
-            let problems_selected = ui.selectable_label(true, "Problems").clicked();
+            let problems_selected = ui.selectable_label(self.ui_state.show_problems, "Problems").clicked();
             let output_selected = ui.selectable_label(true, "Output").clicked();
             
             if problems_selected {
-                // TODO: Handle problems panel selection
+                self.ui_state.show_problems = !self.ui_state.show_problems;
             }
             
             if output_selected {
@@ -192,65 +1136,994 @@ impl EditorApp {
         if self.ui_state.show_search {
             // TODO: Show search results
         }
+
+        if self.ui_state.show_problems {
+            self.show_problems(ui);
+        }
     }
 
-    fn show_editor(&mut self, ctx: &egui::Context) {
-        use egui::{TextStyle, text::TextFormat};
-    
-        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label(&self.ui_state.file_name);
+    /// Shows diagnostics from all open documents, grouped by document
+    fn show_problems(&mut self, ui: &mut egui::Ui) {
+        let mut groups = match self.editor.try_read() {
+            Ok(editor) => collect_problems(editor.documents()),
+            Err(_) => return,
+        };
+
+        // The filter also applies to F8 and Shift+F8
+        let filter = &mut self.ui_state.problem_filter;
+        egui::ComboBox::from_label("Show")
+            .selected_text(match filter {
+                None => "All",
+                Some(DiagnosticSeverity::Error) => "Errors",
+                Some(DiagnosticSeverity::Warning) => "Warnings and above",
+                Some(DiagnosticSeverity::Info) => "Info and above",
+                Some(DiagnosticSeverity::Hint) => "Hints and above",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(filter, None, "All");
+                ui.selectable_value(filter, Some(DiagnosticSeverity::Error), "Errors");
+                ui.selectable_value(filter, Some(DiagnosticSeverity::Warning), "Warnings and above");
+                ui.selectable_value(filter, Some(DiagnosticSeverity::Info), "Info and above");
             });
-        });
-    
-        egui::CentralPanel::default().show(ctx, |ui| {
-            let file_name = &self.ui_state.file_name;
-            let lang = file_name.split('.').last().and_then(get_language_by_extension);
-            let mut highlighter = Highlighter::new();
-            if let Some(language) = lang {
-                let _ = highlighter.set_language(language);
+        if let Some(min) = *filter {
+            for group in &mut groups {
+                group.problems.retain(|problem| problem.severity <= min);
             }
-    
-            let mut layouter = move |ui: &egui::Ui, text: &str, _wrap_width: f32| {
-                let mut layout_job = egui::text::LayoutJob::default();
-                if let Ok(events) = highlighter.highlight(text) {
-                    for event in events {
-                        if let HighlightEvent::Source { start, end, style } = event {
-                            let part = &text[start..end];
-                            let mut format = TextFormat::default();
-                            format.font_id = TextStyle::Monospace.resolve(ui.style());
-                            format.color = style.foreground
-                                .map(|fg| egui::Color32::from_rgb(fg.r, fg.g, fg.b))
-                                .unwrap_or_else(|| ui.visuals().text_color());
-                            layout_job.append(part, 0.0, format);
+            groups.retain(|group| !group.problems.is_empty());
+        }
+
+        if groups.is_empty() {
+            ui.label("No problems");
+            return;
+        }
+
+        let mut jump = None;
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            for group in &groups {
+                egui::CollapsingHeader::new(format!("{} ({})", group.document, group.problems.len()))
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for problem in &group.problems {
+                            let label = format!(
+                                "{} {} [{}] ({}:{})",
+                                severity_icon(problem.severity),
+                                problem.message,
+                                problem.source,
+                                problem.line + 1,
+                                problem.column + 1
+                            );
+                            if ui.selectable_label(false, label).clicked() {
+                                jump = Some((group.document.clone(), problem.position));
+                            }
                         }
-                    }
-                } else {
-                    let mut format = TextFormat::default();
-                    format.font_id = TextStyle::Monospace.resolve(ui.style());
-                    format.color = ui.visuals().text_color();
-                    layout_job.append(text, 0.0, format);
-                }
-                ui.fonts(|f| f.layout_job(layout_job))
-            };
-    
-            egui::TextEdit::multiline(&mut self.current_document_content)
-                .font(TextStyle::Monospace)
-                .desired_width(f32::INFINITY)
-                .desired_rows(30)
-                .layouter(&mut layouter)
-                .show(ui);
+                    });
+            }
         });
+
+        if let Some((document, position)) = jump {
+            self.jump_to(&document, position);
+        }
     }
-    
-    fn show_command_palette(&mut self, ctx: &egui::Context) {
-        egui::Window::new("Command Palette")
-            .collapsible(false)
-            .resizable(false)
-            .default_width(400.0)
-            .show(ctx, |_ui| {
-                // TODO: Show command palette
+
+    /// Opens a diff view comparing the active document with another open document
+    fn compare_with(&mut self, name: &str) {
+        let Ok(editor) = self.editor.try_read() else {
+            return;
+        };
+        let other = editor.documents().find(|doc| doc.name() == name);
+
+        if let (Some(active), Some(other)) = (editor.active_document(), other) {
+            self.ui_state.diff_view = Some(DiffView::new(active, other));
+        }
+    }
+
+    fn show_diff_view(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        if let Some(diff_view) = &self.ui_state.diff_view {
+            egui::Window::new("Compare Documents")
+                .open(&mut open)
+                .default_size([900.0, 600.0])
+                .show(ctx, |ui| {
+                    diff_view.show(ui);
+                });
+        }
+
+        if !open {
+            self.ui_state.diff_view = None;
+        }
+    }
+
+    /// Shows a document with the cursor at the given byte offset
+    fn jump_to(&mut self, document: &str, position: usize) {
+        if self.show_document(document) {
+            self.ui_state.pending_jump = Some(position);
+        }
+    }
+
+    /// Switches line wrapping of the editor view on or off, for this session
+    /// only, keeping the cursor in view
+    fn toggle_word_wrap(&mut self) {
+        if self.ui_state.wrap_mode != WrapMode::None {
+            self.ui_state.wrap_when_toggled = self.ui_state.wrap_mode;
+        }
+        self.ui_state.wrap_mode = self.ui_state.wrap_mode.toggled(self.ui_state.wrap_when_toggled);
+        self.ui_state.reveal_cursor = true;
+    }
+
+    /// Makes an open document the one shown, returning whether it is shown
+    fn show_document(&mut self, document: &str) -> bool {
+        if document == self.ui_state.file_name {
+            return true;
+        }
+        let Ok(mut editor) = self.editor.try_write() else {
+            return false;
+        };
+        if !editor.has_document(document) {
+            return false;
+        }
+        if let Err(e) = self.sync_document(&mut editor) {
+            log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+        }
+        if editor.set_active_document(document).is_err() {
+            return false;
+        }
+        if let Some(doc) = editor.active_document() {
+            self.current_document_content = doc.text();
+            self.ui_state.line_ending = *doc.line_ending();
+            self.ui_state.content_kind = doc.content_kind();
+        }
+        drop(editor);
+
+        self.ui_state.file_name = document.to_string();
+        self.reset_highlighter();
+        true
+    }
+
+    /// Shows a tab per open document, listing the tabs that do not fit
+    /// behind a "»" dropdown, and keeps the shown document's tab visible
+    fn show_tabs(&mut self, ui: &mut egui::Ui) {
+        let mut names = match self.editor.try_read() {
+            Ok(editor) => editor.document_names(),
+            Err(_) => return,
+        };
+        names.sort();
+        let active = names.iter().position(|name| *name == self.ui_state.file_name);
+        let widths: Vec<f32> = names.iter()
+            .map(|name| self.ui_state.tab_widths.get(name).copied().unwrap_or(ESTIMATED_TAB_WIDTH))
+            .collect();
+
+        let mut chosen = None;
+        ui.horizontal(|ui| {
+            let visible = visible_tab_range(&widths, ui.available_width(), self.ui_state.first_visible_tab, active);
+            self.ui_state.first_visible_tab = visible.start;
+
+            // Show the tabs that fit, measuring them for the next frame
+            for index in visible.clone() {
+                let response = ui.selectable_label(Some(index) == active, &names[index]);
+                let width = response.rect.width() + ui.spacing().item_spacing.x;
+                self.ui_state.tab_widths.insert(names[index].clone(), width);
+                if response.clicked() {
+                    chosen = Some(index);
+                }
+            }
+
+            if visible.len() < names.len() {
+                ui.menu_button("»", |ui| {
+                    for index in (0..names.len()).filter(|index| !visible.contains(index)) {
+                        if ui.button(&names[index]).clicked() {
+                            chosen = Some(index);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            }
+        });
+        self.ui_state.tab_widths.retain(|name, _| names.contains(name));
+
+        // The chosen tab is scrolled into view on the next frame, as the
+        // active one
+        if let Some(index) = chosen {
+            self.show_document(&names[index]);
+            ui.ctx().request_repaint();
+        }
+    }
+
+    fn show_editor(&mut self, ctx: &egui::Context) {
+        use egui::TextStyle;
+    
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| self.show_tabs(ui));
+
+        // Apply highlights finished by background tasks, unless the text has
+        // changed since they were requested
+        while let Ok((version, events)) = self.highlight_rx.try_recv() {
+            if self.highlight_scheduler.is_current(version) {
+                self.apply_highlights(events);
+            }
+        }
+
+        // Re-highlight on open and once edits have been quiet for the debounce period
+        let now = Instant::now();
+        if self.highlight_scheduler.poll(now) {
+            self.request_highlight(ctx);
+        } else if let Some(remaining) = self.highlight_scheduler.time_until_due(now) {
+            ctx.request_repaint_after(remaining);
+        }
+
+        // Refresh diagnostic markers once edits have settled
+        if let Ok(mut editor) = self.editor.try_write() {
+            editor.poll_diagnostics(now);
+        }
+    
+        // Toggle wrapping before layout; bookmark and problem navigation need
+        // the cursor
+        let mut cursor_commands = Vec::new();
+        while let Ok(command) = self.view_commands.try_recv() {
+            match command {
+                ViewCommand::NewFile => self.new_file(),
+                ViewCommand::OpenFile => self.pick_file_to_open(),
+                ViewCommand::SaveFile => self.save_file(),
+                ViewCommand::CloseFile => self.close_file(),
+                ViewCommand::ToggleWordWrap => self.toggle_word_wrap(),
+                ViewCommand::DuplicateToScratch => self.duplicate_to_scratch(),
+                ViewCommand::GoToFile => self.open_file_finder(),
+                ViewCommand::GoToLine => self.ui_state.show_go_to_line = true,
+                ViewCommand::ExpandSelection => self.step_selection(ctx, true),
+                ViewCommand::ShrinkSelection => self.step_selection(ctx, false),
+                ViewCommand::Undo => self.step_history(false),
+                ViewCommand::Redo => self.step_history(true),
+                ViewCommand::Find => self.ui_state.show_search = true,
+                ViewCommand::CommandPalette => self.ui_state.show_command_palette = true,
+                command => cursor_commands.push(command),
+            }
+        }
+
+        // Laying out a huge line freezes the UI, so such lines are shown
+        // read-only, laying out only the part in view. Finding them scans the
+        // text, so it is done once per version of the text.
+        let key = (self.highlight_scheduler.version(), self.current_document_content.len());
+        if self.long_lines.as_ref().is_none_or(|(found_for, _)| *found_for != key) {
+            let mut runs = split_long_lines(&self.current_document_content);
+            if !runs.iter().any(|(_, long)| *long) {
+                runs.clear();
+            }
+            self.long_lines = Some((key, runs));
+        }
+        let runs = self.long_lines.as_ref().map(|(_, runs)| runs.clone()).unwrap_or_default();
+        if !runs.is_empty() {
+            egui::CentralPanel::default().show(ctx, |ui| self.show_long_lines(ui, ctx, &runs, cursor_commands));
+            return;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let row_height = ui.text_style_height(&TextStyle::Monospace);
+            let viewport_rows = ui.available_height() / row_height;
+            let scroll_past_end = self.ui_state.scroll_past_end;
+            let mut scroll_area = egui::ScrollArea::vertical()
+                .id_source("editor_scroll")
+                .auto_shrink([false; 2]);
+            if let Some(row) = self.ui_state.pending_scroll.take() {
+                scroll_area = scroll_area.vertical_scroll_offset(row * row_height);
+            }
+            scroll_area.show(ui, |ui| self.show_text(ui, ctx, cursor_commands, viewport_rows, scroll_past_end));
+        });
+    }
+
+    /// Shows the editable text of the current document, inside the editor
+    /// view's scroll area
+    fn show_text(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        cursor_commands: Vec<ViewCommand>,
+        viewport_rows: f32,
+        scroll_past_end: bool,
+    ) {
+        use egui::TextStyle;
+
+        let events = &self.highlight_events;
+        let wrap_mode = self.ui_state.wrap_mode;
+        let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+            let mut layout_job = highlight_to_layout_job(text, events, ui.style(), ui.visuals());
+            let font_id = TextStyle::Monospace.resolve(ui.style());
+            let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+            layout_job.wrap.max_width = wrap_mode.max_width(wrap_width, char_width);
+            ui.fonts(|f| f.layout_job(layout_job))
+        };
+    
+        let text_edit_id = egui::Id::new("editor_text");
+        let jumped = self.ui_state.pending_jump.is_some();
+        if let Some(position) = self.ui_state.pending_jump.take() {
+            let text = &self.current_document_content;
+            let index = text.get(..position).unwrap_or(text).chars().count();
+            let mut state = egui::TextEdit::load_state(ui.ctx(), text_edit_id).unwrap_or_default();
+            let ccursor = egui::text::CCursor::new(index);
+            state.set_ccursor_range(Some(egui::text_edit::CCursorRange::one(ccursor)));
+            state.store(ui.ctx(), text_edit_id);
+            ui.memory_mut(|memory| memory.request_focus(text_edit_id));
+        }
+
+        if let Some(range) = self.ui_state.pending_selection.take() {
+            let text = &self.current_document_content;
+            let index = |offset: usize| text.get(..offset).unwrap_or(text).chars().count();
+            let mut state = egui::TextEdit::load_state(ui.ctx(), text_edit_id).unwrap_or_default();
+            let (start, end) = (egui::text::CCursor::new(index(range.start)), egui::text::CCursor::new(index(range.end)));
+            state.set_ccursor_range(Some(egui::text_edit::CCursorRange::two(start, end)));
+            state.store(ui.ctx(), text_edit_id);
+        }
+
+        let enter_pressed = ui.input(|i| i.key_pressed(egui::Key::Enter));
+        // The cursor is drawn below, in the configured style
+        ui.visuals_mut().text_cursor = egui::Stroke::NONE;
+        // Leave room on the left for the line numbers
+        let font_id = TextStyle::Monospace.resolve(ui.style());
+        let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+        let line_count = self.current_document_content.matches('\n').count() + 1;
+        let gutter_width = (line_count.to_string().len() + 1) as f32 * char_width;
+        let output = ui.horizontal_top(|ui| {
+            ui.add_space(gutter_width);
+            egui::TextEdit::multiline(&mut self.current_document_content)
+                .id(text_edit_id)
+                .font(TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .desired_rows(30)
+                .layouter(&mut layouter)
+                .show(ui)
+        }).inner;
+
+        // Leave room below the text, so the view scrolls as far as the
+        // preference allows
+        let total_rows = output.galley.rows.len();
+        let padding = max_scroll(total_rows, viewport_rows, scroll_past_end) + viewport_rows - total_rows as f32;
+        ui.add_space(padding.max(0.0) * ui.text_style_height(&TextStyle::Monospace));
+
+        // Keep the cursor in view once lines are wrapped differently
+        if std::mem::take(&mut self.ui_state.reveal_cursor) {
+            if let Some(range) = output.cursor_range {
+                let rect = output.galley.pos_from_cursor(&range.primary).translate(output.text_draw_pos.to_vec2());
+                ui.scroll_to_rect(rect, None);
+            }
+        }
+
+        // Center the cursor after go-to-line and other jumps
+        if let Some(range) = output.cursor_range.filter(|_| jumped) {
+            let row = output.galley.from_ccursor(range.primary.ccursor).rcursor.row;
+            self.ui_state.pending_scroll = Some(centered_scroll(row, total_rows, viewport_rows, scroll_past_end));
+            ctx.request_repaint();
+        }
+
+        if output.response.changed() && enter_pressed {
+            let cursor = output.cursor_range.map(|range| range.primary.ccursor.index);
+            if let Some(index) = cursor.and_then(|cursor| self.continue_comment(cursor)) {
+                let mut state = output.state.clone();
+                let ccursor = egui::text::CCursor::new(index);
+                state.set_ccursor_range(Some(egui::text_edit::CCursorRange::one(ccursor)));
+                state.store(ui.ctx(), output.response.id);
+            }
+        }
+
+        if let Some(range) = output.cursor_range {
+            self.share_selection(range.secondary.ccursor.index, range.primary.ccursor.index);
+        }
+
+        if output.response.changed() {
+            self.highlight_scheduler.note_edit(Instant::now());
+            ctx.request_repaint_after(self.highlight_scheduler.delay());
+            if let Some(autosave) = &mut self.autosave {
+                autosave.note_edit();
+            }
+        }
+
+        // Number the rows in view, and highlight only the text within a
+        // viewport of them, again once it scrolls past what was highlighted
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        let clip = ui.clip_rect();
+        let scroll_px = clip.top() - output.text_draw_pos.y;
+        let total_rows = output.galley.rows.len();
+        self.paint_line_numbers(ui, &output, visible_line_range(scroll_px, clip.height(), row_height, total_rows));
+        let rows = visible_line_range_with_overscan(scroll_px, clip.height(), row_height, total_rows, viewport_rows.ceil() as usize);
+        let visible_range = self.row_text_range(&output.galley, rows);
+        if self.ui_state.render_whitespace != RenderWhitespace::None {
+            self.paint_whitespace(ui, &output, &visible_range);
+        }
+        if !self.ui_state.rulers.is_empty() {
+            self.paint_rulers(ui, &output);
+        }
+        if output.response.has_focus() {
+            self.paint_cursor(ui, &output);
+        }
+        self.visible_range = Some(visible_range);
+        let covered = self.highlight_range.as_ref().zip(self.visible_range.as_ref())
+            .is_some_and(|(highlighted, visible)| highlighted.start <= visible.start && visible.end <= highlighted.end);
+        if !covered && !output.response.changed() {
+            self.request_highlight(ctx);
+        }
+
+        let cursor = output.cursor_range.map(|range| range.primary.ccursor.index);
+        for command in cursor_commands {
+            self.run_cursor_command(command, cursor.unwrap_or(0));
+            ctx.request_repaint();
+        }
+    }
+
+    /// Shows a document with very long lines, laying out only the part of
+    /// each long line in view
+    ///
+    /// Long lines are read-only and not highlighted. The lines between them
+    /// stay editable, each run of them in its own text box.
+    fn show_long_lines(
+        &mut self,
+        ui: &mut egui::Ui,
+        ctx: &egui::Context,
+        runs: &[LineRun],
+        cursor_commands: Vec<ViewCommand>,
+    ) {
+        use egui::TextStyle;
+
+        ui.label(egui::RichText::new(format!(
+            "Lines longer than {} characters are shown partially and are read-only",
+            LONG_LINE_THRESHOLD
+        )).weak());
+
+        let font_id = TextStyle::Monospace.resolve(ui.style());
+        let row_height = ui.text_style_height(&TextStyle::Monospace);
+        let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+        let wrap_mode = self.ui_state.wrap_mode;
+        let jump = self.ui_state.pending_jump.take();
+        let text = &self.current_document_content;
+        let all_events = &self.highlight_events;
+        let mut edit = None;
+        let mut focused = None;
+        let mut visible: Option<Range<usize>> = None;
+
+        egui::ScrollArea::both()
+            .id_source("editor_scroll")
+            .auto_shrink([false; 2])
+            .show_viewport(ui, |ui, viewport| {
+                ui.spacing_mut().item_spacing.y = 0.0;
+                for (index, (range, long)) in runs.iter().enumerate() {
+                    if *long {
+                        let line = &text[range.clone()];
+                        let size = egui::vec2(line.len() as f32 * char_width, row_height);
+                        let (rect, _) = ui.allocate_exact_size(size, egui::Sense::hover());
+                        if let Some(position) = jump.filter(|position| range.contains(position)) {
+                            let x = rect.left() + (position - range.start) as f32 * char_width;
+                            let caret = egui::Rect::from_min_size(egui::pos2(x, rect.top()), egui::vec2(char_width, row_height));
+                            ui.scroll_to_rect(caret, Some(egui::Align::Center));
+                        }
+                        if !ui.is_rect_visible(rect) {
+                            continue;
+                        }
+
+                        // Mark the row, so it is clear the line goes on
+                        let row = egui::Rect::from_x_y_ranges(ui.clip_rect().x_range(), rect.y_range());
+                        ui.painter().rect_filled(row, 0.0, ui.visuals().faint_bg_color);
+                        let slice = visible_slice(line, viewport.min.x, viewport.width(), char_width);
+                        let x = rect.left() + (viewport.min.x.max(0.0) / char_width).floor() * char_width;
+                        let color = ui.visuals().text_color();
+                        ui.painter().text(egui::pos2(x, rect.top()), egui::Align2::LEFT_TOP, &line[slice], font_id.clone(), color);
+                        continue;
+                    }
+
+                    // Highlight events of the run, moved to its offsets
+                    let events: Vec<HighlightEvent> = all_events.iter().filter_map(|event| match event {
+                        HighlightEvent::Source { start, end, style } if range.start <= *start && *end <= range.end => {
+                            Some(HighlightEvent::Source { start: start - range.start, end: end - range.start, style: style.clone() })
+                        }
+                        _ => None,
+                    }).collect();
+                    let mut layouter = |ui: &egui::Ui, text: &str, wrap_width: f32| {
+                        let mut layout_job = highlight_to_layout_job(text, &events, ui.style(), ui.visuals());
+                        layout_job.wrap.max_width = wrap_mode.max_width(wrap_width, char_width);
+                        ui.fonts(|f| f.layout_job(layout_job))
+                    };
+
+                    let id = egui::Id::new(("editor_segment", index));
+                    let jump = jump.filter(|position| range.contains(position) || *position == range.end);
+                    if let Some(position) = jump {
+                        let mut state = egui::TextEdit::load_state(ui.ctx(), id).unwrap_or_default();
+                        let ccursor = egui::text::CCursor::new(text[range.start..position].chars().count());
+                        state.set_ccursor_range(Some(egui::text_edit::CCursorRange::one(ccursor)));
+                        state.store(ui.ctx(), id);
+                        ui.memory_mut(|memory| memory.request_focus(id));
+                    }
+
+                    let mut segment = text[range.clone()].to_string();
+                    let output = egui::TextEdit::multiline(&mut segment)
+                        .id(id)
+                        .font(TextStyle::Monospace)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(1)
+                        .layouter(&mut layouter)
+                        .show(ui);
+                    if let Some(cursor) = output.cursor_range.filter(|_| jump.is_some()) {
+                        let caret = output.galley.pos_from_cursor(&cursor.primary).translate(output.text_draw_pos.to_vec2());
+                        ui.scroll_to_rect(caret, Some(egui::Align::Center));
+                    }
+                    if output.response.changed() {
+                        edit = Some((range.clone(), segment));
+                    }
+                    if output.response.has_focus() {
+                        focused = output.cursor_range.map(|cursor| (range.start, cursor.primary.ccursor.index));
+                    }
+                    if ui.is_rect_visible(output.response.rect) {
+                        visible = Some(visible.take().map_or(range.clone(), |visible| visible.start..range.end));
+                    }
+                }
+            });
+
+        if let Some((range, segment)) = edit {
+            self.current_document_content.replace_range(range, &segment);
+            self.highlight_scheduler.note_edit(Instant::now());
+            ctx.request_repaint_after(self.highlight_scheduler.delay());
+            if let Some(autosave) = &mut self.autosave {
+                autosave.note_edit();
+            }
+        } else if let Some(visible_range) = visible {
+            // Highlight the runs in view, again once others scroll in
+            let covered = self.highlight_range.as_ref()
+                .is_some_and(|highlighted| highlighted.start <= visible_range.start && visible_range.end <= highlighted.end);
+            self.visible_range = Some(visible_range);
+            if !covered {
+                self.request_highlight(ctx);
+            }
+        }
+
+        // Cursor commands get the cursor of the text box being edited, as an
+        // index into the whole text
+        let text = &self.current_document_content;
+        let cursor = focused.map(|(start, index)| text[..start].chars().count() + index);
+        for command in cursor_commands {
+            self.run_cursor_command(command, cursor.unwrap_or(0));
+            ctx.request_repaint();
+        }
+    }
+
+    /// Draws faint glyphs over the whitespace of the lines in `range`, as the
+    /// whitespace preference asks
+    fn paint_whitespace(&self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, range: &Range<usize>) {
+        let text = &self.current_document_content;
+        let char_offset = |index: usize| text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset);
+        let selection = output.cursor_range.map(|cursor| {
+            let (a, b) = (char_offset(cursor.primary.ccursor.index), char_offset(cursor.secondary.ccursor.index));
+            a.min(b)..a.max(b)
+        });
+
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let color = ui.visuals().weak_text_color().gamma_multiply(0.6);
+        let painter = ui.painter();
+
+        let mut line_start = text[..range.start].rfind('\n').map_or(0, |newline| newline + 1);
+        let mut line_chars = text[..line_start].chars().count();
+        for line in text[line_start..range.end.max(line_start)].split_inclusive('\n') {
+            let line_selection = selection.as_ref()
+                .filter(|selection| selection.start < line_start + line.len() && selection.end > line_start)
+                .map(|selection| selection.start.saturating_sub(line_start)..selection.end.saturating_sub(line_start));
+
+            for (offset, glyph) in whitespace_glyphs(line, self.ui_state.render_whitespace, line_selection) {
+                let index = line_chars + line[..offset].chars().count();
+                let cursor = output.galley.from_ccursor(egui::text::CCursor::new(index));
+                let rect = output.galley.pos_from_cursor(&cursor).translate(output.text_draw_pos.to_vec2());
+                painter.text(rect.left_center(), egui::Align2::LEFT_CENTER, glyph.symbol(), font_id.clone(), color);
+            }
+
+            line_start += line.len();
+            line_chars += line.chars().count();
+        }
+    }
+
+    /// Draws the cursor in the configured style, a distinct one for read-only
+    /// documents, and schedules the next blink
+    fn paint_cursor(&self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let Some(range) = output.cursor_range else {
+            return;
+        };
+        let read_only = self.editor.try_read().ok()
+            .and_then(|editor| editor.active_document().map(|doc| doc.is_read_only()))
+            .unwrap_or(false);
+        let settings = &self.ui_state.cursor;
+        if settings.blinks(read_only) {
+            ui.ctx().request_repaint_after(self.cursor_blink.time_until_toggle(settings.blink_rate));
+        }
+        if !settings.is_visible(&self.cursor_blink, read_only) {
+            return;
+        }
+
+        let text = &self.current_document_content;
+        let index = range.primary.ccursor.index;
+        let offset = text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset);
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+        let (caret, width) = caret_in_galley(&output.galley, index, grapheme_chars(text, offset), char_width);
+        let caret = caret.translate(output.text_draw_pos.to_vec2());
+        paint_cursor(ui.painter(), settings.shape(read_only), caret, width, ui.visuals().strong_text_color());
+    }
+
+    /// Draws faint vertical lines at the ruler columns, over the whole height
+    /// of the view
+    fn paint_rulers(&self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput) {
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+        let stroke = egui::Stroke::new(1.0, ui.visuals().weak_text_color().gamma_multiply(0.3));
+        let clip = ui.clip_rect();
+
+        for offset in ruler_offsets(&self.ui_state.rulers, char_width) {
+            let x = output.text_draw_pos.x + offset;
+            if x > clip.right() {
+                break;
+            }
+            ui.painter().vline(x, clip.y_range(), stroke);
+        }
+    }
+
+    /// Returns the byte range of the lines laid out in the galley rows
+    /// `rows`, including all of lines only partly in them
+    fn row_text_range(&self, galley: &egui::Galley, rows: Range<usize>) -> Range<usize> {
+        let chars_before = |row: usize| galley.rows[..row].iter().map(|row| row.char_count_including_newline()).sum::<usize>();
+        let text = &self.current_document_content;
+        let offset = |index: usize| text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset);
+        let start = offset(chars_before(rows.start));
+        let Some(last) = rows.end.checked_sub(1).filter(|_| !rows.is_empty()) else {
+            return start..start;
+        };
+        let end = offset(chars_before(last) + galley.rows[last].char_count_excluding_newline());
+
+        let start = text[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let end = text[end..].find('\n').map_or(text.len(), |newline| end + newline);
+        start..end
+    }
+
+    /// Draws the line numbers of the galley rows `rows` left of the text;
+    /// rows continuing a wrapped line get none
+    fn paint_line_numbers(&self, ui: &egui::Ui, output: &egui::text_edit::TextEditOutput, rows: Range<usize>) {
+        let galley = &output.galley;
+        let font_id = egui::TextStyle::Monospace.resolve(ui.style());
+        let char_width = ui.fonts(|f| f.glyph_width(&font_id, 'M'));
+        let color = ui.visuals().weak_text_color();
+        let right = output.response.rect.left() - char_width;
+
+        let mut line = galley.rows[..rows.start].iter().filter(|row| row.ends_with_newline).count();
+        let mut starts_line = rows.start == 0 || galley.rows[rows.start - 1].ends_with_newline;
+        for row in &galley.rows[rows] {
+            if starts_line {
+                let top = output.text_draw_pos.y + row.rect.top();
+                let number = (line + 1).to_string();
+                ui.painter().text(egui::pos2(right, top), egui::Align2::RIGHT_TOP, number, font_id.clone(), color);
+            }
+            if row.ends_with_newline {
+                line += 1;
+            }
+            starts_line = row.ends_with_newline;
+        }
+    }
+
+    /// Copies the text edited in the view into the editor's document it
+    /// shows, changing only the edited part so bookmarks and other markers
+    /// stay in place
+    fn sync_document(&self, editor: &mut Editor) -> editor_core::Result<()> {
+        let doc = editor.active_document_mut()
+            .filter(|doc| doc.name() == self.ui_state.file_name);
+        match doc {
+            Some(doc) => doc.set_text(&self.current_document_content).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+
+    /// Shares the view's selection, between character indices, with commands
+    /// and plugins, and gates actions that need a selection
+    ///
+    /// The editor is only updated when the selection changes, with offsets
+    /// mapped to its document's text, which may lack the latest edits.
+    fn share_selection(&mut self, anchor: usize, head: usize) {
+        let text = &self.current_document_content;
+        let byte_offset = |index: usize| text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset);
+        let selection = Selection::new(byte_offset(anchor), byte_offset(head));
+        let shared = (self.ui_state.file_name.clone(), selection);
+        if self.ui_state.shared_selection.as_ref() == Some(&shared) {
+            return;
+        }
+        let Ok(mut editor) = self.editor.try_write() else {
+            return;
+        };
+        editor.actions().set_context("editorHasSelection", !selection.is_empty());
+        let doc = editor.active_document_mut()
+            .filter(|doc| doc.name() == self.ui_state.file_name);
+        if let Some(doc) = doc {
+            doc.set_selections_in(text, vec![selection]);
+        }
+        self.ui_state.shared_selection = Some(shared);
+    }
+
+    /// Records the bookmarks of the open files in the session
+    fn store_bookmarks(&self, editor: &Editor) {
+        for doc in editor.persistent_documents() {
+            if let Some(path) = doc.path() {
+                self.session.set_bookmarks(path.to_path_buf(), doc.bookmarks());
+            }
+        }
+    }
+
+    /// Copies the shown document into a scratch document and shows the copy
+    ///
+    /// The copy includes edits made in the view but not yet in the editor.
+    fn duplicate_to_scratch(&mut self) {
+        let Ok(mut editor) = self.editor.try_write() else {
+            return;
+        };
+        if let Err(e) = self.sync_document(&mut editor) {
+            log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+        }
+        let name = match editor.duplicate_to_scratch(&self.ui_state.file_name) {
+            Ok(name) => name,
+            Err(e) => {
+                log::warn!("Failed to duplicate document: {}", e);
+                drop(editor);
+                self.show_status(e.to_string());
+                return;
+            }
+        };
+        if let Some(doc) = editor.active_document() {
+            self.current_document_content = doc.text();
+            self.ui_state.line_ending = *doc.line_ending();
+            self.ui_state.content_kind = doc.content_kind();
+        }
+        drop(editor);
+
+        self.ui_state.file_name = name;
+        self.reset_highlighter();
+    }
+
+    /// Grows the editor's selection to the enclosing syntax node, or with
+    /// `expand` false shrinks it back, selecting the result on the next frame
+    fn step_selection(&mut self, ctx: &egui::Context, expand: bool) {
+        let text = &self.current_document_content;
+        let Some(range) = egui::TextEdit::load_state(ctx, egui::Id::new("editor_text"))
+            .and_then(|state| state.ccursor_range())
+        else {
+            return;
+        };
+        let byte_offset = |index: usize| text.char_indices().nth(index).map_or(text.len(), |(offset, _)| offset);
+        let [start, end] = range.sorted().map(|ccursor| byte_offset(ccursor.index));
+        let result = if expand {
+            self.parser.expand_selection(text, start..end)
+        } else {
+            self.parser.shrink_selection(text, start..end)
+        };
+        match result {
+            Ok(selection) => self.ui_state.pending_selection = Some(selection),
+            // Without a language there is no syntax tree to follow
+            Err(e) => log::debug!("Cannot change the selection: {}", e),
+        }
+    }
+
+    /// Undoes, or with `redo` redoes, an edit of the current document
+    ///
+    /// Edits made in the view are copied into the editor's document first,
+    /// so they can be undone too.
+    fn step_history(&mut self, redo: bool) {
+        let Ok(mut editor) = self.editor.try_write() else {
+            return;
+        };
+        if let Err(e) = self.sync_document(&mut editor) {
+            log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+        }
+        let Some(doc) = editor.active_document_mut().filter(|doc| doc.name() == self.ui_state.file_name) else {
+            return;
+        };
+        let result = if redo { doc.redo() } else { doc.undo() };
+        match result {
+            Ok(true) => {
+                self.current_document_content = doc.text();
+                self.highlight_scheduler.request_immediate();
+            }
+            Ok(false) => {}
+            Err(e) => log::warn!("Failed to undo or redo in {}: {}", self.ui_state.file_name, e),
+        }
+    }
+
+    /// Toggles or moves to a bookmark, or moves to a problem, relative to the
+    /// cursor, given as a char index into the editor text
+    fn run_cursor_command(&mut self, command: ViewCommand, cursor: usize) {
+        let text = &self.current_document_content;
+        let offset = text.char_indices().nth(cursor).map_or(text.len(), |(i, _)| i);
+        let Ok(mut editor) = self.editor.try_write() else {
+            return;
+        };
+        // Bookmarks and problems are placed in the editor's copy of the text,
+        // so it needs the edits made in the view first
+        if let Err(e) = self.sync_document(&mut editor) {
+            log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+            return;
+        }
+        let Some(doc) = editor.active_document_mut() else {
+            return;
+        };
+
+        match command {
+            ViewCommand::ToggleBookmark => {
+                let line = text[..offset].matches('\n').count();
+                if let Err(e) = doc.toggle_bookmark(line, None) {
+                    log::warn!("Failed to toggle bookmark: {}", e);
+                }
+            }
+            ViewCommand::NextBookmark => self.ui_state.pending_jump = doc.next_bookmark(offset),
+            ViewCommand::PrevBookmark => self.ui_state.pending_jump = doc.prev_bookmark(offset),
+            ViewCommand::NextDiagnostic => {
+                self.ui_state.pending_jump = doc.next_diagnostic(offset, self.ui_state.problem_filter).map(|marker| marker.position);
+            }
+            ViewCommand::PrevDiagnostic => {
+                self.ui_state.pending_jump = doc.prev_diagnostic(offset, self.ui_state.problem_filter).map(|marker| marker.position);
+            }
+            _ => {}
+        }
+    }
+    
+    /// Shows "Go to File", listing the files under the session's workspace,
+    /// or the working directory without one, the first time
+    ///
+    /// The listing then follows changes to the workspace on disk.
+    fn open_file_finder(&mut self) {
+        if self.file_finder.is_none() {
+            let root = self.session.workspace_root()
+                .or_else(|| std::env::current_dir().ok())
+                .unwrap_or_default();
+            let mut finder = FileFinder::new(root);
+            if let Err(e) = finder.refresh() {
+                log::warn!("Failed to list workspace files: {}", e);
+                self.show_status(e.to_string());
+                return;
+            }
+            finder.set_recent(self.session.recent_files());
+            self.watch_workspace(finder.root());
+            self.file_finder = Some(finder);
+        }
+        self.ui_state.show_file_finder = true;
+    }
+
+    /// Starts watching the workspace for changes made by other programs
+    fn watch_workspace(&mut self, root: &Path) {
+        let (tx, rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            if let Ok(event) = event {
+                for path in event.paths {
+                    let _ = tx.send(path);
+                }
+            }
+        });
+        let watching = watcher.and_then(|mut watcher| {
+            watcher.watch(root, notify::RecursiveMode::Recursive).map(|_| watcher)
+        });
+        match watching {
+            Ok(watcher) => {
+                self.workspace_watcher = Some(watcher);
+                self.workspace_events = Some(rx);
+            }
+            // "Go to File" still works, but misses files added by other programs
+            Err(e) => log::warn!("Failed to watch {}: {}", root.display(), e),
+        }
+    }
+
+    /// Updates the "Go to File" listing with the changes seen in the workspace
+    fn apply_workspace_events(&mut self) {
+        let (Some(finder), Some(events)) = (&mut self.file_finder, &self.workspace_events) else {
+            return;
+        };
+        for path in events.try_iter() {
+            if let Err(e) = finder.note_changed(&path) {
+                log::warn!("Failed to update workspace files: {}", e);
+            }
+        }
+    }
+
+    /// Lists the workspace files matching the typed filter and opens the chosen one
+    fn show_file_finder(&mut self, ctx: &egui::Context) {
+        let Some(finder) = &self.file_finder else {
+            self.ui_state.show_file_finder = false;
+            return;
+        };
+        let files = finder.matching(&self.ui_state.file_finder_query);
+        let root = finder.root().to_path_buf();
+
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("Go to File")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.text_edit_singleline(&mut self.ui_state.file_finder_query).request_focus();
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for path in files.iter().take(MAX_FILE_FINDER_RESULTS) {
+                        if ui.selectable_label(false, path.display().to_string()).clicked() {
+                            chosen = Some(root.join(path));
+                        }
+                    }
+                });
+            });
+
+        if let Some(path) = chosen {
+            self.request_open(path);
+            open = false;
+        }
+
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.show_file_finder = false;
+            self.ui_state.file_finder_query.clear();
+        }
+    }
+
+    /// Asks for a line number and moves the cursor to the start of that line
+    fn show_go_to_line(&mut self, ctx: &egui::Context) {
+        let mut open = true;
+        let mut submitted = false;
+        egui::Window::new("Go to Line")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                let response = ui.text_edit_singleline(&mut self.ui_state.go_to_line_query);
+                response.request_focus();
+                submitted = ui.input(|i| i.key_pressed(egui::Key::Enter));
+            });
+
+        if submitted {
+            match self.ui_state.go_to_line_query.trim().parse::<usize>() {
+                Ok(line) => {
+                    self.ui_state.pending_jump = Some(line_start(&self.current_document_content, line));
+                    ctx.request_repaint();
+                }
+                Err(_) => {
+                    self.show_status(format!("Not a line number: {}", self.ui_state.go_to_line_query));
+                }
+            }
+            open = false;
+        }
+
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.show_go_to_line = false;
+            self.ui_state.go_to_line_query.clear();
+        }
+    }
+
+    /// Lists the available actions matching the typed filter and invokes the chosen one
+    fn show_command_palette(&mut self, ctx: &egui::Context) {
+        let actions = match self.editor.try_read() {
+            Ok(editor) => editor.actions().palette_matching(&self.ui_state.palette_query),
+            Err(_) => return,
+        };
+
+        let mut open = true;
+        let mut chosen = None;
+        egui::Window::new("Command Palette")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .default_width(400.0)
+            .show(ctx, |ui| {
+                ui.text_edit_singleline(&mut self.ui_state.palette_query).request_focus();
+                ui.separator();
+
+                egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+                    for action in &actions {
+                        if ui.selectable_label(false, action.label()).clicked() {
+                            chosen = Some(action.id.clone());
+                        }
+                    }
+                });
             });
+
+        if let Some(id) = chosen {
+            self.invoke_action(&id);
+            open = false;
+        }
+
+        if !open || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.ui_state.show_command_palette = false;
+            self.ui_state.palette_query.clear();
+        }
     }
 
     fn show_settings(&mut self, ctx: &egui::Context) {
@@ -264,61 +2137,148 @@ impl EditorApp {
             });
     }
 
+    /// Invokes the actions bound to the keyboard shortcuts pressed
     fn handle_shortcuts(&mut self, ctx: &egui::Context) {
-        ctx.input(|i| {
-            if i.key_pressed(egui::Key::P) && i.modifiers.command {
-                self.ui_state.show_command_palette = true;
-            }
-            // Ctrl+N: New file
-            if i.modifiers.command && i.key_pressed(egui::Key::N) {
-                self.current_document_content.clear();
-                self.cursor_position = (0, 0);
-                self.ui_state.file_name = "untitled".to_string();
-            }
-            // Ctrl+O: Open file
-            if i.modifiers.command && i.key_pressed(egui::Key::O) {
-                if let Some(path) = FileDialog::new().pick_file() {
-                    if let Ok(content) = fs::read_to_string(&path) {
-                        self.current_document_content = content;
-                        self.cursor_position = (0, 0);
-                        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                            self.ui_state.file_name = name.to_string();
-                        }
-                    }
-                }
+        for (shortcut, id) in SHORTCUTS {
+            if ctx.input_mut(|i| i.consume_shortcut(&shortcut)) {
+                self.invoke_action(id);
+                // View commands queued by the action run on the next frame
+                ctx.request_repaint();
             }
-            // Ctrl+S: Save file
-            if i.modifiers.command && i.key_pressed(egui::Key::S) {
-                let mut save_path = None;
-                if self.ui_state.file_name == "untitled" || self.ui_state.file_name.is_empty() {
-                    if let Some(path) = FileDialog::new().set_title("Save File").save_file() {
-                        save_path = Some(path);
-                    }
-                } else {
-                    if let Some(path) = FileDialog::new().set_file_name(&self.ui_state.file_name).save_file() {
-                        save_path = Some(path);
-                    }
-                }
-                if let Some(path) = save_path {
-                    if let Err(e) = fs::write(&path, &self.current_document_content) {
-                        eprintln!("Error saving file: {}", e);
-                    } else if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        self.ui_state.file_name = name.to_string();
-                    }
-                }
+        }
+    }
+
+    /// Starts a new untitled document
+    fn new_file(&mut self) {
+        self.current_document_content.clear();
+        self.ui_state.line_ending = LineEnding::default();
+        self.cursor_position = (0, 0);
+        self.ui_state.file_name = "untitled".to_string();
+        self.reset_highlighter();
+    }
+
+    /// Asks for a file and opens it
+    fn pick_file_to_open(&mut self) {
+        if let Some(path) = FileDialog::new().pick_file() {
+            self.request_open(path);
+        }
+    }
+
+    /// Asks where to save the current document and writes it there
+    fn save_file(&mut self) {
+        let dialog = if self.ui_state.file_name == "untitled" || self.ui_state.file_name.is_empty() {
+            FileDialog::new().set_title("Save File")
+        } else {
+            FileDialog::new().set_file_name(&self.ui_state.file_name)
+        };
+        let Some(path) = dialog.save_file() else {
+            return;
+        };
+        // The file keeps the encoding, and byte order mark, it was opened with
+        let encoding = match self.ui_state.content_kind {
+            ContentKind::Text { encoding } => encoding,
+            ContentKind::Binary => Encoding::Utf8,
+        };
+        if let Err(e) = fs::write(&path, encoding.encode(&self.current_document_content)) {
+            eprintln!("Error saving file: {}", e);
+            return;
+        }
+        if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+            self.ui_state.file_name = name.to_string();
+        }
+        if let Some(autosave) = &mut self.autosave {
+            autosave.note_saved();
+        }
+        // A new file should show up in "Go to File"
+        if let Some(finder) = &mut self.file_finder {
+            if let Err(e) = finder.note_changed(&path) {
+                log::warn!("Failed to update workspace files: {}", e);
             }
-            // Ctrl+W: Close file
-            if i.modifiers.command && i.key_pressed(egui::Key::W) {
-                self.current_document_content.clear();
-                self.cursor_position = (0, 0);
-                self.ui_state.file_name = "untitled".to_string();
+        }
+    }
+
+    /// Saves the current document if its edits have been unsaved for the
+    /// auto-save interval
+    ///
+    /// The changed part of the text is copied into the editor's document in
+    /// one edit, keeping bookmarks and other markers in place, and saved
+    /// through it. Untitled and scratch documents are left alone.
+    fn autosave_if_due(&mut self, ctx: &egui::Context) {
+        let Some(autosave) = &mut self.autosave else {
+            return;
+        };
+        if !autosave.is_due() {
+            if let Some(remaining) = autosave.time_until_due() {
+                ctx.request_repaint_after(remaining);
             }
-        });
+            return;
+        }
+        let Ok(mut editor) = self.editor.try_write() else {
+            // The editor is busy; retry next frame
+            ctx.request_repaint();
+            return;
+        };
+
+        let file_name = &self.ui_state.file_name;
+        let content = &self.current_document_content;
+        let doc = editor.active_document_mut()
+            .filter(|doc| doc.name() == file_name.as_str() && doc.path().is_some() && !doc.is_scratch());
+        let result = doc.map(|doc| doc.set_text(content).and_then(|_| doc.save()));
+        drop(editor);
+        // Failed saves are retried after the next edit rather than every frame
+        autosave.note_saved();
+        if let Some(Err(e)) = result {
+            log::warn!("Auto-save of {} failed: {}", self.ui_state.file_name, e);
+            self.show_status(format!("Auto-save failed: {}", e));
+        }
+    }
+
+    /// Closes the current document, remembering its bookmarks
+    fn close_file(&mut self) {
+        if let Ok(mut editor) = self.editor.try_write() {
+            if let Err(e) = self.sync_document(&mut editor) {
+                log::warn!("Failed to update {}: {}", self.ui_state.file_name, e);
+            }
+            self.store_bookmarks(&editor);
+            let _ = editor.close_document(&self.ui_state.file_name);
+        }
+        self.current_document_content.clear();
+        self.cursor_position = (0, 0);
+        self.ui_state.file_name = "untitled".to_string();
+        self.reset_highlighter();
     }
 }
 
 /// Runs the editor application
-pub fn run(editor: Editor) -> std::result::Result<(), UiError> {
+///
+/// The UI is drawn in `theme`. Fonts set on `font_settings` while running
+/// are applied on the next frame. `wrap_mode` is the initial line wrapping,
+/// which "Toggle Word Wrap" changes for the session only, `render_whitespace`
+/// which whitespace is shown and `rulers` the columns vertical rulers are
+/// drawn at, `cursor` the style and blinking of the cursor and
+/// `highlight_debounce` the quiet period after an edit before re-highlighting,
+/// `scroll_past_end` whether the view can scroll until only the last line is
+/// visible, and `autosave` the time allowed between an edit and its automatic
+/// save, if auto-save is on.
+/// Bookmarks are read from and stored in `session` as files are opened and
+/// closed. The `large_files` were too large to open without asking, and are
+/// opened once the user confirms.
+#[allow(clippy::too_many_arguments)]
+pub fn run(
+    editor: Editor,
+    theme: Theme,
+    font_settings: FontSettings,
+    wrap_mode: WrapMode,
+    render_whitespace: RenderWhitespace,
+    rulers: Vec<u32>,
+    cursor: CursorSettings,
+    highlight_debounce: Duration,
+    scroll_past_end: bool,
+    autosave: Option<Duration>,
+    reload_policy: ReloadPolicy,
+    session: SharedSession,
+    large_files: Vec<PathBuf>,
+) -> std::result::Result<(), UiError> {
     let options = eframe::NativeOptions {
         initial_window_size: Some(egui::vec2(1280.0, 720.0)),
         min_window_size: Some(egui::vec2(400.0, 300.0)),
@@ -328,8 +2288,425 @@ pub fn run(editor: Editor) -> std::result::Result<(), UiError> {
     eframe::run_native(
         "Rust Editor",
         options,
-        Box::new(|_cc| Box::new(EditorApp::new(editor))),
+        Box::new(move |_cc| {
+            let mut app = EditorApp::new(editor, font_settings, wrap_mode, render_whitespace, rulers, cursor);
+            app.set_theme(theme);
+            app.set_highlight_debounce(highlight_debounce);
+            app.set_scroll_past_end(scroll_past_end);
+            app.set_autosave(autosave);
+            app.set_reload_policy(reload_policy);
+            app.set_session(session);
+            for path in large_files {
+                app.request_open(path);
+            }
+            Box::new(app)
+        }),
     )?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::session::SessionData;
+    use editor_core::{Bookmark, MockClock};
+
+    #[test]
+    fn test_shortcuts_and_menus_use_actions() {
+        let app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        let editor = app.editor.try_read().unwrap();
+        let actions = editor.actions();
+
+        for (_, id) in SHORTCUTS {
+            assert!(actions.get(id).is_some(), "no action for shortcut {}", id);
+        }
+        let file: Vec<_> = actions.menu("File").into_iter().map(|action| action.id).collect();
+        assert!([NEW_FILE, OPEN_FILE, SAVE_FILE, CLOSE_FILE].iter().all(|id| file.iter().any(|f| f == id)));
+        assert_eq!(shortcut_for(SAVE_FILE).map(|shortcut| shortcut.key), Some(egui::Key::S));
+
+        // Invoking an action queues its command for the view
+        actions.invoke(TOGGLE_WORD_WRAP, serde_json::Value::Null).unwrap();
+        assert_eq!(app.view_commands.try_recv(), Ok(ViewCommand::ToggleWordWrap));
+    }
+
+    #[test]
+    fn test_autosave_writes_current_document() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "draft\n").unwrap();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.set_autosave(Some(Duration::ZERO));
+        app.load_file(&path, false);
+        let ctx = egui::Context::default();
+
+        // Nothing is saved until there is an edit
+        app.autosave_if_due(&ctx);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "draft\n");
+
+        app.current_document_content.push_str("more\n");
+        app.autosave.as_mut().unwrap().note_edit();
+        app.autosave_if_due(&ctx);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "draft\nmore\n");
+        assert_eq!(app.autosave.as_ref().unwrap().time_until_due(), None);
+    }
+
+    #[test]
+    fn test_row_text_range() {
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.current_document_content = "one\ntwo\nthree\nfour\n".to_string();
+        let ctx = egui::Context::default();
+        let mut galley = None;
+        let _ = ctx.run(egui::RawInput::default(), |ctx| {
+            let text = app.current_document_content.clone();
+            galley = Some(ctx.fonts(|f| f.layout_no_wrap(text, egui::FontId::monospace(14.0), egui::Color32::WHITE)));
+        });
+        let galley = galley.unwrap();
+
+        assert_eq!(app.row_text_range(&galley, 1..3), 4..13);
+        assert_eq!(app.row_text_range(&galley, 0..galley.rows.len()), 0..19);
+        assert_eq!(app.row_text_range(&galley, 2..2), 8..8);
+    }
+
+    #[test]
+    fn test_expand_and_shrink_selection() {
+        editor_syntax::init().unwrap();
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.ui_state.file_name = "main.rs".to_string();
+        app.current_document_content = "fn main() { foo(1); }".to_string();
+        app.reset_highlighter();
+        let ctx = egui::Context::default();
+        let select = |range: Range<usize>| {
+            let mut state = egui::text_edit::TextEditState::default();
+            let (start, end) = (egui::text::CCursor::new(range.start), egui::text::CCursor::new(range.end));
+            state.set_ccursor_range(Some(egui::text_edit::CCursorRange::two(start, end)));
+            state.store(&ctx, egui::Id::new("editor_text"));
+        };
+
+        select(12..15);
+        app.step_selection(&ctx, true);
+        let call = app.ui_state.pending_selection.take().unwrap();
+        assert_eq!(&app.current_document_content[call.clone()], "foo(1)");
+
+        select(call);
+        app.step_selection(&ctx, false);
+        assert_eq!(app.ui_state.pending_selection, Some(12..15));
+    }
+
+    #[test]
+    fn test_selection_shared_on_change() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one two\n").unwrap();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.load_file(&path, false);
+
+        // Text typed in the view, but not yet in the editor's document, is
+        // left out of the shared offsets
+        app.current_document_content.insert_str(0, "zero ");
+        app.share_selection(9, 12);
+        let selection = app.editor.try_read().unwrap().active_document().unwrap().primary_selection();
+        assert_eq!(selection, Selection::new(4, 7));
+
+        // An unchanged selection isn't shared again, even if the editor is busy
+        let busy = app.editor.clone();
+        let busy = busy.try_write().unwrap();
+        app.share_selection(9, 12);
+        drop(busy);
+        assert_eq!(app.ui_state.shared_selection, Some(("notes.txt".to_string(), Selection::new(9, 12))));
+    }
+
+    #[test]
+    fn test_bookmarks_follow_view_edits_and_persist() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        let session = SharedSession::default();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.set_session(session.clone());
+        app.load_file(&path, false);
+
+        // A line typed in the view, but not yet in the editor's document,
+        // moves the bookmarked line down
+        app.current_document_content.insert_str(0, "new\n");
+        app.run_cursor_command(ViewCommand::ToggleBookmark, 6);
+        app.close_file();
+        assert_eq!(session.bookmarks(&path), vec![Bookmark::new(2)]);
+
+        // Reopening restores it
+        app.load_file(&path, false);
+        let editor = app.editor.try_read().unwrap();
+        assert_eq!(editor.active_document().unwrap().bookmarks(), vec![Bookmark::new(2)]);
+    }
+
+    #[test]
+    fn test_file_finder_uses_session_workspace() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["main.rs", "menu.rs", "notes.txt"] {
+            fs::write(dir.path().join(name), "").unwrap();
+        }
+        let session = SharedSession::new(SessionData {
+            workspace_root: Some(dir.path().to_path_buf()),
+            recent_files: vec![dir.path().join("menu.rs")],
+            ..Default::default()
+        });
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.set_session(session.clone());
+
+        // Files opened before "Go to File" is shown still count as recent
+        app.load_file(&dir.path().join("notes.txt"), false);
+        assert_eq!(session.recent_files(), vec![dir.path().join("notes.txt"), dir.path().join("menu.rs")]);
+
+        app.open_file_finder();
+        let finder = app.file_finder.as_ref().unwrap();
+        assert_eq!(finder.root(), dir.path());
+        assert_eq!(finder.matching(""), vec![PathBuf::from("notes.txt"), PathBuf::from("menu.rs"), PathBuf::from("main.rs")]);
+
+        // Files created by other programs show up
+        fs::write(dir.path().join("model.rs"), "").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !app.file_finder.as_ref().unwrap().files().contains(&PathBuf::from("model.rs")) {
+            assert!(Instant::now() < deadline, "model.rs was not listed");
+            std::thread::sleep(Duration::from_millis(20));
+            app.apply_workspace_events();
+        }
+    }
+
+    #[test]
+    fn test_duplicate_to_scratch_copies_view_edits() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "saved\n").unwrap();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.load_file(&path, false);
+        app.current_document_content.push_str("typed\n");
+
+        app.duplicate_to_scratch();
+        assert_eq!(app.ui_state.file_name, "notes.txt (scratch 1)");
+        assert_eq!(app.current_document_content, "saved\ntyped\n");
+        let editor = app.editor.try_read().unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "saved\ntyped\n");
+    }
+
+    #[test]
+    fn test_mixed_line_endings_offered_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "a\nb\r\nc\n").unwrap();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.load_file(&path, false);
+        assert_eq!(app.ui_state.line_ending_offer, Some(("notes.txt".to_string(), LineEnding::Unix)));
+
+        // Accepting normalizes the view and the document
+        app.change_line_ending(LineEnding::Unix);
+        assert_eq!(app.current_document_content, "a\nb\nc\n");
+        assert_eq!(app.editor.try_read().unwrap().active_document().unwrap().text(), "a\nb\nc\n");
+
+        // Reopening does not offer again
+        app.ui_state.line_ending_offer = None;
+        app.close_file();
+        app.load_file(&path, false);
+        assert_eq!(app.ui_state.line_ending_offer, None);
+    }
+
+    #[test]
+    fn test_external_changes_reach_the_view() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        fs::write(&path, "one\n").unwrap();
+
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.load_file(&path, false);
+
+        // Files are checked once the editor becomes idle
+        app.idle.set_quiet_period(Duration::ZERO);
+        assert!(app.idle.poll());
+        assert!(app.disk_check_due.load(Ordering::Relaxed));
+
+        // A clean document is reloaded into the view
+        fs::write(&path, "one\ntwo\n").unwrap();
+        app.check_disk_changes();
+        assert_eq!(app.current_document_content, "one\ntwo\n");
+        assert!(app.ui_state.reload_conflicts.is_empty());
+
+        // Edits in the view make it a conflict, kept until resolved
+        app.current_document_content.push_str("three\n");
+        fs::write(&path, "zero\n").unwrap();
+        app.check_disk_changes();
+        assert_eq!(app.current_document_content, "one\ntwo\nthree\n");
+        assert_eq!(app.ui_state.reload_conflicts, vec![path.clone()]);
+
+        app.resolve_reload_conflict(&path, true);
+        assert_eq!(app.current_document_content, "zero\n");
+        assert!(app.ui_state.reload_conflicts.is_empty());
+        assert!(!app.editor.try_read().unwrap().active_document().unwrap().is_dirty());
+    }
+
+    #[test]
+    fn test_status_message_expires() {
+        let clock = Arc::new(MockClock::new());
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.set_clock(clock.clone());
+
+        app.show_status("Failed to open");
+        assert_eq!(app.current_status(), Some(("Failed to open", STATUS_MESSAGE_DURATION)));
+        clock.advance(STATUS_MESSAGE_DURATION - Duration::from_secs(1));
+        assert_eq!(app.current_status(), Some(("Failed to open", Duration::from_secs(1))));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(app.current_status(), None);
+    }
+
+    #[test]
+    fn test_toggle_word_wrap_restores_mode() {
+        let preference = WrapMode::Column(100);
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            preference,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+
+        app.toggle_word_wrap();
+        assert_eq!(app.ui_state.wrap_mode, WrapMode::None);
+        assert!(app.ui_state.reveal_cursor);
+        app.toggle_word_wrap();
+        assert_eq!(app.ui_state.wrap_mode, preference);
+
+        // Turning wrapping on when it started off wraps at the view
+        let mut app = EditorApp::new(
+            Editor::new(),
+            FontSettings::default(),
+            WrapMode::None,
+            RenderWhitespace::default(),
+            Vec::new(),
+            CursorSettings::default(),
+        );
+        app.toggle_word_wrap();
+        assert_eq!(app.ui_state.wrap_mode, WrapMode::View);
+    }
+
+    #[test]
+    fn test_max_scroll_past_end() {
+        // Only the last row remains visible
+        assert_eq!(max_scroll(100, 20.0, true), 99.0);
+        assert_eq!(max_scroll(5, 20.0, true), 4.0);
+        assert_eq!(max_scroll(0, 20.0, true), 0.0);
+    }
+
+    #[test]
+    fn test_max_scroll_clamped_to_end() {
+        // The last row stops at the bottom of the viewport
+        assert_eq!(max_scroll(100, 20.0, false), 80.0);
+        assert_eq!(max_scroll(100, 12.5, false), 87.5);
+        // Short documents do not scroll
+        assert_eq!(max_scroll(5, 20.0, false), 0.0);
+        assert_eq!(max_scroll(0, 20.0, false), 0.0);
+    }
+
+    #[test]
+    fn test_centered_scroll() {
+        assert_eq!(centered_scroll(50, 100, 20.0, false), 40.5);
+        // Rows near the start stay at the top
+        assert_eq!(centered_scroll(3, 100, 20.0, false), 0.0);
+        // Rows near the end are centered only when scrolling past the end
+        assert_eq!(centered_scroll(95, 100, 20.0, false), 80.0);
+        assert_eq!(centered_scroll(95, 100, 20.0, true), 85.5);
+    }
+
+    #[test]
+    fn test_go_to_line() {
+        let text = "one\ntwo\nthree\n";
+        assert_eq!(line_start(text, 1), 0);
+        assert_eq!(line_start(text, 2), 4);
+        assert_eq!(line_start(text, 3), 8);
+        // Line 0 and numbers past the end stay in the document
+        assert_eq!(line_start(text, 0), 0);
+        assert_eq!(line_start(text, 99), text.len());
+        assert_eq!(line_start("no newline", 5), 0);
+    }
+}
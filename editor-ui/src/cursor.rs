@@ -0,0 +1,254 @@
+//! Cursor appearance and blinking
+
+use eframe::egui;
+use editor_core::{Clock, SystemClock};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Default time the cursor stays shown, and then hidden, while blinking
+pub const DEFAULT_BLINK_RATE: Duration = Duration::from_millis(530);
+
+/// Width of the line cursor, and height of the underline cursor
+const CURSOR_THICKNESS: f32 = 2.0;
+
+/// Cursor style chosen in the preferences
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CursorStyle {
+    /// Thin vertical line before the caret
+    #[default]
+    Line,
+    /// Box covering the grapheme at the caret
+    Block,
+    /// Line under the grapheme at the caret
+    Underline,
+}
+
+/// Shape the cursor is drawn with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    /// Thin vertical line before the caret
+    Line,
+    /// Filled box covering the grapheme at the caret
+    Block,
+    /// Line under the grapheme at the caret
+    Underline,
+    /// Outline of the grapheme at the caret, for read-only documents
+    HollowBlock,
+}
+
+/// Cursor appearance preferences
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CursorSettings {
+    /// Style of the cursor in editable documents
+    pub style: CursorStyle,
+    /// Whether the cursor blinks
+    pub blink: bool,
+    /// Time the cursor stays shown, and then hidden, while blinking
+    pub blink_rate: Duration,
+}
+
+impl CursorSettings {
+    /// Returns the shape to draw the cursor with
+    ///
+    /// Read-only documents always get a hollow block, so it is clear typing
+    /// does nothing.
+    pub fn shape(&self, read_only: bool) -> CursorShape {
+        if read_only {
+            return CursorShape::HollowBlock;
+        }
+        match self.style {
+            CursorStyle::Line => CursorShape::Line,
+            CursorStyle::Block => CursorShape::Block,
+            CursorStyle::Underline => CursorShape::Underline,
+        }
+    }
+
+    /// Returns true if the cursor blinks; it stays shown in read-only documents
+    pub fn blinks(&self, read_only: bool) -> bool {
+        self.blink && !read_only && !self.blink_rate.is_zero()
+    }
+
+    /// Returns true if the cursor is shown in the current blink phase
+    pub fn is_visible(&self, blink: &CursorBlink, read_only: bool) -> bool {
+        !self.blinks(read_only) || blink.is_on(self.blink_rate)
+    }
+}
+
+impl Default for CursorSettings {
+    fn default() -> Self {
+        Self {
+            style: CursorStyle::default(),
+            blink: true,
+            blink_rate: DEFAULT_BLINK_RATE,
+        }
+    }
+}
+
+/// Tracks the blink phase of the cursor
+///
+/// The cursor is shown right after it moves or the user types, so it never
+/// disappears while in use, and then alternates between hidden and shown.
+#[derive(Debug, Clone)]
+pub struct CursorBlink {
+    /// Time source
+    clock: Arc<dyn Clock>,
+    /// Time the current blink cycle started
+    start: Instant,
+}
+
+impl CursorBlink {
+    /// Creates a blink phase using the system clock
+    pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates a blink phase using the given clock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
+        Self { start: clock.now(), clock }
+    }
+
+    /// Shows the cursor and restarts blinking, e.g. after input
+    pub fn note_activity(&mut self) {
+        self.start = self.clock.now();
+    }
+
+    /// Returns true if the cursor is shown when blinking at `rate`
+    pub fn is_on(&self, rate: Duration) -> bool {
+        if rate.is_zero() {
+            return true;
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        (elapsed.as_nanos() / rate.as_nanos()).is_multiple_of(2)
+    }
+
+    /// Returns the time until the cursor is next shown or hidden when
+    /// blinking at `rate`, to schedule a repaint
+    pub fn time_until_toggle(&self, rate: Duration) -> Duration {
+        if rate.is_zero() {
+            return Duration::MAX;
+        }
+        let elapsed = self.clock.now().saturating_duration_since(self.start);
+        let into_phase = elapsed.as_nanos() % rate.as_nanos();
+        rate - Duration::from_nanos(into_phase as u64)
+    }
+}
+
+impl Default for CursorBlink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the number of chars in the grapheme cluster starting at byte
+/// `offset` of `text`, or 0 at the end of a line or of the text
+pub fn grapheme_chars(text: &str, offset: usize) -> usize {
+    text.get(offset..)
+        .and_then(|rest| rest.graphemes(true).next())
+        .filter(|grapheme| !grapheme.starts_with(['\n', '\r']))
+        .map_or(0, |grapheme| grapheme.chars().count())
+}
+
+/// Returns the rectangle the cursor covers
+///
+/// `caret` is the zero-width rectangle of the caret, one row high, and
+/// `grapheme_width` the width of the grapheme after it.
+pub fn cursor_rect(shape: CursorShape, caret: egui::Rect, grapheme_width: f32) -> egui::Rect {
+    let covered = egui::Rect::from_min_size(caret.min, egui::vec2(grapheme_width, caret.height()));
+    match shape {
+        CursorShape::Line => egui::Rect::from_min_size(caret.min, egui::vec2(CURSOR_THICKNESS, caret.height())),
+        CursorShape::Block | CursorShape::HollowBlock => covered,
+        CursorShape::Underline => egui::Rect::from_min_max(
+            egui::pos2(covered.left(), covered.bottom() - CURSOR_THICKNESS),
+            covered.max,
+        ),
+    }
+}
+
+/// Draws the cursor
+///
+/// A block is translucent, so the grapheme under it stays readable.
+pub fn paint_cursor(painter: &egui::Painter, shape: CursorShape, caret: egui::Rect, grapheme_width: f32, color: egui::Color32) {
+    let rect = cursor_rect(shape, caret, grapheme_width);
+    match shape {
+        CursorShape::Block => painter.rect_filled(rect, 0.0, color.gamma_multiply(0.5)),
+        CursorShape::HollowBlock => painter.rect_stroke(rect, 0.0, egui::Stroke::new(1.0, color)),
+        CursorShape::Line | CursorShape::Underline => painter.rect_filled(rect, 0.0, color),
+    }
+}
+
+/// Returns the caret rectangle of char index `index` in `galley`, and the
+/// width of the grapheme of `grapheme_chars` chars after it
+///
+/// At the end of a line, or when the grapheme wraps to the next row,
+/// `fallback_width` is used.
+pub fn caret_in_galley(galley: &egui::Galley, index: usize, grapheme_chars: usize, fallback_width: f32) -> (egui::Rect, f32) {
+    let pos = |index: usize| galley.pos_from_cursor(&galley.from_ccursor(egui::text::CCursor::new(index)));
+    let caret = pos(index);
+    let next = pos(index + grapheme_chars);
+    let width = if grapheme_chars > 0 && next.top() == caret.top() && next.left() > caret.left() {
+        next.left() - caret.left()
+    } else {
+        fallback_width
+    };
+    (caret, width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::MockClock;
+
+    #[test]
+    fn test_blink_phase() {
+        let clock = Arc::new(MockClock::new());
+        let mut blink = CursorBlink::with_clock(clock.clone());
+        let rate = Duration::from_millis(500);
+
+        assert!(blink.is_on(rate));
+        assert_eq!(blink.time_until_toggle(rate), rate);
+        clock.advance(Duration::from_millis(499));
+        assert!(blink.is_on(rate));
+        clock.advance(Duration::from_millis(1));
+        assert!(!blink.is_on(rate));
+        clock.advance(Duration::from_millis(200));
+        assert_eq!(blink.time_until_toggle(rate), Duration::from_millis(300));
+        clock.advance(Duration::from_millis(300));
+        assert!(blink.is_on(rate));
+
+        // Activity shows the cursor and restarts the cycle
+        clock.advance(Duration::from_millis(600));
+        assert!(!blink.is_on(rate));
+        blink.note_activity();
+        assert!(blink.is_on(rate));
+        assert_eq!(blink.time_until_toggle(rate), rate);
+
+        // Without blinking, or in read-only documents, the cursor stays shown
+        let settings = CursorSettings { blink_rate: rate, ..CursorSettings::default() };
+        clock.advance(rate);
+        assert!(!settings.is_visible(&blink, false));
+        assert!(settings.is_visible(&blink, true));
+        assert!(CursorSettings { blink: false, ..settings }.is_visible(&blink, false));
+        assert!(CursorSettings { blink_rate: Duration::ZERO, ..settings }.is_visible(&blink, false));
+    }
+
+    #[test]
+    fn test_cursor_shape_selection() {
+        let settings = |style| CursorSettings { style, ..CursorSettings::default() };
+        assert_eq!(settings(CursorStyle::Line).shape(false), CursorShape::Line);
+        assert_eq!(settings(CursorStyle::Block).shape(false), CursorShape::Block);
+        assert_eq!(settings(CursorStyle::Underline).shape(false), CursorShape::Underline);
+        for style in [CursorStyle::Line, CursorStyle::Block, CursorStyle::Underline] {
+            assert_eq!(settings(style).shape(true), CursorShape::HollowBlock);
+        }
+
+        // A block covers the whole grapheme at the caret
+        assert_eq!(grapheme_chars("ae\u{301}b", 1), 2);
+        assert_eq!(grapheme_chars("a\r\nb", 1), 0);
+        assert_eq!(grapheme_chars("ab", 2), 0);
+        let caret = egui::Rect::from_min_size(egui::pos2(10.0, 20.0), egui::vec2(0.0, 16.0));
+        assert_eq!(cursor_rect(CursorShape::Block, caret, 8.0), egui::Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(18.0, 36.0)));
+        assert_eq!(cursor_rect(CursorShape::Underline, caret, 8.0), egui::Rect::from_min_max(egui::pos2(10.0, 34.0), egui::pos2(18.0, 36.0)));
+        assert_eq!(cursor_rect(CursorShape::Line, caret, 8.0).width(), CURSOR_THICKNESS);
+    }
+}
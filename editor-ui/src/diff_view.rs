@@ -0,0 +1,227 @@
+//! Read-only side-by-side comparison of two documents
+
+use eframe::egui;
+use editor_core::Document;
+use similar::{DiffTag, TextDiff};
+use std::ops::Range;
+
+/// Kind of change for a run of lines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    /// Lines are the same in both documents
+    Equal,
+    /// Lines only exist in the new document
+    Inserted,
+    /// Lines only exist in the old document
+    Deleted,
+    /// Lines were changed between the documents
+    Modified,
+}
+
+/// A run of lines in a line diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffOp {
+    /// Kind of change
+    pub kind: DiffKind,
+    /// Line range in the old document
+    pub old: Range<usize>,
+    /// Line range in the new document
+    pub new: Range<usize>,
+}
+
+/// Computes the line alignment between two texts
+pub fn compute_line_diff(old: &str, new: &str) -> Vec<DiffOp> {
+    TextDiff::from_lines(old, new)
+        .ops()
+        .iter()
+        .map(|op| DiffOp {
+            kind: match op.tag() {
+                DiffTag::Equal => DiffKind::Equal,
+                DiffTag::Insert => DiffKind::Inserted,
+                DiffTag::Delete => DiffKind::Deleted,
+                DiffTag::Replace => DiffKind::Modified,
+            },
+            old: op.old_range(),
+            new: op.new_range(),
+        })
+        .collect()
+}
+
+/// A displayed row pairing a line from each side
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DiffRow {
+    /// Kind of change
+    kind: DiffKind,
+    /// Line in the old document, if any
+    old: Option<usize>,
+    /// Line in the new document, if any
+    new: Option<usize>,
+}
+
+/// Expands diff operations into aligned rows, padding the shorter side
+fn align_rows(ops: &[DiffOp]) -> Vec<DiffRow> {
+    let mut rows = Vec::new();
+    for op in ops {
+        let len = op.old.len().max(op.new.len());
+        for i in 0..len {
+            let old = op.old.clone().nth(i);
+            let new = op.new.clone().nth(i);
+            let kind = match (op.kind, old, new) {
+                // Unpaired lines of a modified run are plain insertions or deletions
+                (DiffKind::Modified, None, Some(_)) => DiffKind::Inserted,
+                (DiffKind::Modified, Some(_), None) => DiffKind::Deleted,
+                (kind, _, _) => kind,
+            };
+            rows.push(DiffRow { kind, old, new });
+        }
+    }
+    rows
+}
+
+/// Side-by-side diff view of two document snapshots
+pub struct DiffView {
+    /// Old document name
+    old_name: String,
+    /// New document name
+    new_name: String,
+    /// Old document lines
+    old_lines: Vec<String>,
+    /// New document lines
+    new_lines: Vec<String>,
+    /// Diff operations
+    ops: Vec<DiffOp>,
+    /// Aligned rows
+    rows: Vec<DiffRow>,
+}
+
+impl DiffView {
+    /// Creates a diff view comparing two documents as they are now
+    pub fn new(old: &Document, new: &Document) -> Self {
+        let old_text = old.text();
+        let new_text = new.text();
+        let ops = compute_line_diff(&old_text, &new_text);
+        let rows = align_rows(&ops);
+
+        Self {
+            old_name: old.name().to_string(),
+            new_name: new.name().to_string(),
+            old_lines: old_text.lines().map(String::from).collect(),
+            new_lines: new_text.lines().map(String::from).collect(),
+            ops,
+            rows,
+        }
+    }
+
+    /// Returns the diff operations
+    pub fn ops(&self) -> &[DiffOp] {
+        &self.ops
+    }
+
+    /// Returns true if the documents have the same lines
+    pub fn is_identical(&self) -> bool {
+        self.ops.iter().all(|op| op.kind == DiffKind::Equal)
+    }
+
+    /// Shows the diff
+    ///
+    /// Both panes share one scroll area so they always scroll together.
+    pub fn show(&self, ui: &mut egui::Ui) {
+        ui.columns(2, |columns| {
+            columns[0].strong(&self.old_name);
+            columns[1].strong(&self.new_name);
+        });
+        ui.separator();
+
+        if self.is_identical() {
+            ui.label("Documents are identical");
+        }
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::both().show_rows(ui, row_height, self.rows.len(), |ui, visible| {
+            egui::Grid::new("diff_view_rows")
+                .num_columns(4)
+                .spacing([8.0, 0.0])
+                .start_row(visible.start)
+                .show(ui, |ui| {
+                    for row in &self.rows[visible] {
+                        let (old_color, new_color) = Self::row_colors(ui, row.kind);
+                        Self::show_line(ui, row.old, &self.old_lines, old_color);
+                        Self::show_line(ui, row.new, &self.new_lines, new_color);
+                        ui.end_row();
+                    }
+                });
+        });
+    }
+
+    /// Shows a line number and its text, or an empty cell for padding rows
+    fn show_line(ui: &mut egui::Ui, line: Option<usize>, lines: &[String], color: Option<egui::Color32>) {
+        match line {
+            Some(line) => {
+                ui.monospace(format!("{:>4}", line + 1));
+                let text = lines.get(line).map(String::as_str).unwrap_or_default();
+                let mut text = egui::RichText::new(text).monospace();
+                if let Some(color) = color {
+                    text = text.background_color(color);
+                }
+                ui.label(text);
+            }
+            None => {
+                ui.label("");
+                ui.label("");
+            }
+        }
+    }
+
+    /// Returns the background colors of the old and new panes for a row
+    fn row_colors(ui: &egui::Ui, kind: DiffKind) -> (Option<egui::Color32>, Option<egui::Color32>) {
+        let alpha = if ui.visuals().dark_mode { 60 } else { 40 };
+        let added = egui::Color32::from_rgba_unmultiplied(0, 200, 0, alpha);
+        let removed = egui::Color32::from_rgba_unmultiplied(220, 0, 0, alpha);
+        let changed = egui::Color32::from_rgba_unmultiplied(220, 180, 0, alpha);
+
+        match kind {
+            DiffKind::Equal => (None, None),
+            DiffKind::Inserted => (None, Some(added)),
+            DiffKind::Deleted => (Some(removed), None),
+            DiffKind::Modified => (Some(changed), Some(changed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inserted_lines() {
+        let ops = compute_line_diff("a\nb\n", "a\nx\ny\nb\n");
+        assert_eq!(ops, vec![
+            DiffOp { kind: DiffKind::Equal, old: 0..1, new: 0..1 },
+            DiffOp { kind: DiffKind::Inserted, old: 1..1, new: 1..3 },
+            DiffOp { kind: DiffKind::Equal, old: 1..2, new: 3..4 },
+        ]);
+    }
+
+    #[test]
+    fn test_deleted_lines() {
+        let ops = compute_line_diff("a\nb\nc\n", "a\nc\n");
+        assert_eq!(ops, vec![
+            DiffOp { kind: DiffKind::Equal, old: 0..1, new: 0..1 },
+            DiffOp { kind: DiffKind::Deleted, old: 1..2, new: 1..1 },
+            DiffOp { kind: DiffKind::Equal, old: 2..3, new: 1..2 },
+        ]);
+    }
+
+    #[test]
+    fn test_modified_lines() {
+        let ops = compute_line_diff("a\nb\nc\n", "a\nB\nB2\nc\n");
+        assert_eq!(ops[1], DiffOp { kind: DiffKind::Modified, old: 1..2, new: 1..3 });
+
+        // The extra new line of the modified run is padded on the old side
+        let rows = align_rows(&ops);
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[1], DiffRow { kind: DiffKind::Modified, old: Some(1), new: Some(1) });
+        assert_eq!(rows[2], DiffRow { kind: DiffKind::Inserted, old: None, new: Some(2) });
+        assert_eq!(rows[3], DiffRow { kind: DiffKind::Equal, old: Some(2), new: Some(3) });
+    }
+}
@@ -159,8 +159,7 @@ impl EditorView {
                                     }
                                 } else if line > 0 {
                                     // Move to end of previous line
-                                    let prev_line_len = doc.text().lines()
-                                        .nth(line - 1)
+                                    let prev_line_len = doc.line(line - 1)
                                         .map(|l| l.len())
                                         .unwrap_or(0);
                                     self.cursor = (line - 1, prev_line_len);
@@ -0,0 +1,213 @@
+//! Debounced syntax highlighting
+
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+
+/// Default quiet period after an edit before re-highlighting
+pub const DEFAULT_HIGHLIGHT_DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Coalesces rapid edits into a single highlight request
+///
+/// It also versions the text, so results of a background highlight that
+/// finish after further edits can be told apart and dropped.
+#[derive(Debug, Clone)]
+pub struct HighlightScheduler {
+    /// Quiet period required after the last edit
+    delay: Duration,
+    /// Time of the most recent edit not yet highlighted
+    last_edit: Option<Instant>,
+    /// Highlight on the next poll regardless of edits
+    immediate: bool,
+    /// Version of the text, changed by every edit and reset
+    version: u64,
+    /// Background highlight still running, if any
+    task: Option<AbortHandle>,
+}
+
+impl HighlightScheduler {
+    /// Creates a new scheduler with the given quiet period
+    ///
+    /// The first poll always requests a highlight.
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            last_edit: None,
+            immediate: true,
+            version: 0,
+            task: None,
+        }
+    }
+
+    /// Returns the quiet period
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Sets the quiet period
+    pub fn set_delay(&mut self, delay: Duration) {
+        self.delay = delay;
+    }
+
+    /// Requests a highlight on the next poll, e.g. after opening a file
+    pub fn request_immediate(&mut self) {
+        self.immediate = true;
+        self.last_edit = None;
+        self.version += 1;
+    }
+
+    /// Records an edit, restarting the quiet period
+    pub fn note_edit(&mut self, now: Instant) {
+        self.last_edit = Some(now);
+        self.version += 1;
+    }
+
+    /// Returns the version of the text, to tag a highlight request with
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Records the background task highlighting the current version,
+    /// cancelling the one it supersedes
+    pub fn start_task(&mut self, task: AbortHandle) {
+        if let Some(previous) = self.task.replace(task) {
+            previous.abort();
+        }
+    }
+
+    /// Returns true if a result highlighted for `version` still matches the
+    /// text and should be applied
+    pub fn is_current(&self, version: u64) -> bool {
+        version == self.version
+    }
+
+    /// Returns true if a highlight is waiting to run
+    pub fn is_pending(&self) -> bool {
+        self.immediate || self.last_edit.is_some()
+    }
+
+    /// Returns the time left until the pending highlight is due
+    pub fn time_until_due(&self, now: Instant) -> Option<Duration> {
+        if self.immediate {
+            return Some(Duration::ZERO);
+        }
+        self.last_edit
+            .map(|edit| self.delay.saturating_sub(now.saturating_duration_since(edit)))
+    }
+
+    /// Returns true if a highlight should run now, consuming the request
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if self.immediate {
+            self.immediate = false;
+            self.last_edit = None;
+            return true;
+        }
+
+        match self.last_edit {
+            Some(edit) if now.saturating_duration_since(edit) >= self.delay => {
+                self.last_edit = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Default for HighlightScheduler {
+    fn default() -> Self {
+        Self::new(DEFAULT_HIGHLIGHT_DEBOUNCE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rapid_edits_coalesce() {
+        let start = Instant::now();
+        let mut scheduler = HighlightScheduler::new(Duration::from_millis(50));
+
+        // Initial highlight on open
+        assert!(scheduler.poll(start));
+        assert!(!scheduler.poll(start));
+
+        // Ten edits 10ms apart, polling every frame in between
+        let mut requests = 0;
+        let mut now = start;
+        for _ in 0..10 {
+            now += Duration::from_millis(10);
+            scheduler.note_edit(now);
+            if scheduler.poll(now) {
+                requests += 1;
+            }
+        }
+        assert_eq!(requests, 0);
+        assert!(scheduler.is_pending());
+        assert_eq!(scheduler.time_until_due(now), Some(Duration::from_millis(50)));
+
+        // Quiet period elapses
+        now += Duration::from_millis(50);
+        if scheduler.poll(now) {
+            requests += 1;
+        }
+        if scheduler.poll(now + Duration::from_millis(100)) {
+            requests += 1;
+        }
+
+        assert_eq!(requests, 1);
+        assert!(!scheduler.is_pending());
+    }
+
+    #[tokio::test]
+    async fn test_only_latest_highlight_applied() {
+        let mut scheduler = HighlightScheduler::default();
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+
+        // A highlight that is still running when the text changes
+        let (_release, wait) = tokio::sync::oneshot::channel::<()>();
+        let stale = scheduler.version();
+        let sender = tx.clone();
+        let first = tokio::spawn(async move {
+            let _ = wait.await;
+            let _ = sender.send((stale, "old"));
+        });
+        scheduler.start_task(first.abort_handle());
+
+        scheduler.note_edit(Instant::now());
+        let version = scheduler.version();
+        let sender = tx.clone();
+        let second = tokio::spawn(async move {
+            let _ = sender.send((version, "new"));
+        });
+        scheduler.start_task(second.abort_handle());
+
+        // The superseded task is cancelled
+        assert!(first.await.unwrap_err().is_cancelled());
+        second.await.unwrap();
+
+        // A result for the old version that got through anyway is dropped
+        tx.send((stale, "old")).unwrap();
+        drop(tx);
+        let mut applied = Vec::new();
+        while let Some((version, events)) = rx.recv().await {
+            if scheduler.is_current(version) {
+                applied.push(events);
+            }
+        }
+        assert_eq!(applied, vec!["new"]);
+    }
+
+    #[test]
+    fn test_request_immediate() {
+        let now = Instant::now();
+        let mut scheduler = HighlightScheduler::default();
+        assert!(scheduler.poll(now));
+
+        scheduler.note_edit(now);
+        assert!(!scheduler.poll(now));
+
+        scheduler.request_immediate();
+        assert!(scheduler.poll(now));
+        assert!(!scheduler.poll(now + DEFAULT_HIGHLIGHT_DEBOUNCE));
+    }
+}
@@ -0,0 +1,530 @@
+//! Conversion of highlight results into egui text layouts
+
+use eframe::egui::{self, text::LayoutJob, Color32, Stroke, TextFormat, TextStyle};
+use editor_syntax::{HighlightEvent, Style};
+use std::ops::Range;
+
+/// Lines longer than this many bytes are laid out only around the viewport,
+/// and without syntax highlighting
+pub const LONG_LINE_THRESHOLD: usize = 10_000;
+
+/// Builds a monospace layout job for `text` colored by highlight events
+///
+/// Text between events uses the visuals' text color. Events are expected in
+/// order; they may be stale while edits are debounced, so the first one that
+/// no longer fits the text ends highlighting and the rest is plain. Lines
+/// longer than `LONG_LINE_THRESHOLD` are plain too. The job's text is `text`
+/// unchanged, tabs included, so byte offsets and cursor positions stay
+/// valid; egui lays tabs out as spaces.
+pub fn highlight_to_layout_job(
+    text: &str,
+    events: &[HighlightEvent],
+    style: &egui::Style,
+    visuals: &egui::Visuals,
+) -> LayoutJob {
+    let plain = TextFormat {
+        font_id: TextStyle::Monospace.resolve(style),
+        color: visuals.text_color(),
+        ..Default::default()
+    };
+    let mut job = LayoutJob::default();
+    let long_lines = long_line_ranges(text);
+
+    let mut offset = 0;
+    for event in events {
+        let HighlightEvent::Source { start, end, style } = event else {
+            continue;
+        };
+        let (start, end) = (*start, *end);
+        if start < offset
+            || end > text.len()
+            || !text.is_char_boundary(start)
+            || !text.is_char_boundary(end)
+        {
+            break;
+        }
+        if long_lines.iter().any(|line| start < line.end && end > line.start) {
+            continue;
+        }
+        if start > offset {
+            job.append(&text[offset..start], 0.0, plain.clone());
+        }
+        job.append(&text[start..end], 0.0, styled_format(&plain, style));
+        offset = end;
+    }
+    if offset < text.len() {
+        job.append(&text[offset..], 0.0, plain);
+    }
+
+    job
+}
+
+/// Returns the byte ranges of the lines longer than `LONG_LINE_THRESHOLD`
+fn long_line_ranges(text: &str) -> Vec<Range<usize>> {
+    if text.len() <= LONG_LINE_THRESHOLD {
+        return Vec::new();
+    }
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        if line.len() > LONG_LINE_THRESHOLD {
+            ranges.push(start..start + line.len());
+        }
+        start += line.len();
+    }
+    ranges
+}
+
+/// Returns true if a line of `text` is longer than `LONG_LINE_THRESHOLD`
+pub fn has_long_line(text: &str) -> bool {
+    text.len() > LONG_LINE_THRESHOLD && text.split('\n').any(|line| line.len() > LONG_LINE_THRESHOLD)
+}
+
+/// Splits `text` into runs of lines, in order, each either a single line
+/// longer than `LONG_LINE_THRESHOLD` or the lines between such lines
+///
+/// Returns the byte range of each run and whether it is a long line. Runs
+/// leave out the line break ending them, so each holds whole lines; an
+/// empty line between two long ones is an empty run.
+pub fn split_long_lines(text: &str) -> Vec<(Range<usize>, bool)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        if line.len() > LONG_LINE_THRESHOLD {
+            if offset > start {
+                runs.push((start..offset - 1, false));
+            }
+            let len = line.strip_suffix('\n').map_or(line.len(), str::len);
+            runs.push((offset..offset + len, true));
+            start = offset + line.len();
+        }
+        offset += line.len();
+    }
+    // The lines after the last long one, or an empty last line
+    if start < text.len() || text.is_empty() || text.ends_with('\n') {
+        runs.push((start..text.len(), false));
+    }
+    runs
+}
+
+/// Returns the byte range of the part of a line inside a viewport
+///
+/// The viewport starts `scroll_x` points into the line and is `view_width`
+/// points wide, with monospace characters `char_width` points wide. The
+/// range covers every character at least partly visible; the first one is
+/// at column `(scroll_x / char_width).floor()`.
+pub fn visible_slice(line: &str, scroll_x: f32, view_width: f32, char_width: f32) -> Range<usize> {
+    if char_width <= 0.0 {
+        return 0..line.len();
+    }
+    let first = (scroll_x.max(0.0) / char_width).floor() as usize;
+    let last = ((scroll_x.max(0.0) + view_width.max(0.0)) / char_width).ceil() as usize;
+
+    let mut boundaries = line.char_indices().map(|(offset, _)| offset).chain(std::iter::once(line.len()));
+    let start = boundaries.nth(first).unwrap_or(line.len());
+    let end = match last.checked_sub(first + 1) {
+        Some(skip) => boundaries.nth(skip).unwrap_or(line.len()),
+        None => start,
+    };
+    start..end
+}
+
+/// Lines laid out above and below the viewport by `visible_line_range`, so
+/// scrolling a little does not show unlaid-out rows
+pub const DEFAULT_OVERSCAN_LINES: usize = 2;
+
+/// Returns the range of lines inside a viewport, plus `DEFAULT_OVERSCAN_LINES`
+/// on either side
+///
+/// The viewport starts `scroll_px` points into the document and is
+/// `viewport_px` points high, with lines `line_height` points high. The text
+/// view and its gutter share this, so they agree on which lines to draw.
+pub fn visible_line_range(scroll_px: f32, viewport_px: f32, line_height: f32, total_lines: usize) -> Range<usize> {
+    visible_line_range_with_overscan(scroll_px, viewport_px, line_height, total_lines, DEFAULT_OVERSCAN_LINES)
+}
+
+/// Returns the range of lines inside a viewport, plus `overscan` lines on
+/// either side
+///
+/// Every line at least partly visible is included, and the range is clamped
+/// to `0..total_lines`. A viewport scrolled past the end gives an empty range
+/// at `total_lines`.
+pub fn visible_line_range_with_overscan(
+    scroll_px: f32,
+    viewport_px: f32,
+    line_height: f32,
+    total_lines: usize,
+    overscan: usize,
+) -> Range<usize> {
+    if line_height <= 0.0 {
+        return 0..total_lines;
+    }
+    let scroll_px = scroll_px.max(0.0);
+    let first = ((scroll_px / line_height).floor() as usize).min(total_lines);
+    let last = (((scroll_px + viewport_px.max(0.0)) / line_height).ceil() as usize).min(total_lines);
+    if first == total_lines {
+        return total_lines..total_lines;
+    }
+    first.saturating_sub(overscan)..last.saturating_add(overscan).min(total_lines)
+}
+
+/// Applies a syntax style on top of the plain text format
+fn styled_format(plain: &TextFormat, style: &Style) -> TextFormat {
+    let mut format = plain.clone();
+    if let Some(fg) = style.foreground {
+        format.color = Color32::from_rgb(fg.r, fg.g, fg.b);
+    }
+    if let Some(bg) = style.background {
+        format.background = Color32::from_rgb(bg.r, bg.g, bg.b);
+    }
+    format.italics = style.italic;
+    if style.underline {
+        format.underline = Stroke::new(1.0, format.color);
+    }
+    format
+}
+
+/// How long lines are wrapped in an editor view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WrapMode {
+    /// Lines extend past the view and scroll horizontally
+    None,
+    /// Lines wrap at the view width
+    #[default]
+    View,
+    /// Lines wrap at a column, or the view width if narrower
+    Column(u32),
+}
+
+impl WrapMode {
+    /// Returns the mode "Toggle Word Wrap" switches to, turning wrapping
+    /// back on as `wrapped`, the mode used before it was turned off
+    pub fn toggled(self, wrapped: WrapMode) -> Self {
+        match (self, wrapped) {
+            (WrapMode::None, WrapMode::None) => WrapMode::View,
+            (WrapMode::None, wrapped) => wrapped,
+            (WrapMode::View | WrapMode::Column(_), _) => WrapMode::None,
+        }
+    }
+
+    /// Returns the layout width for a view `view_width` points wide, with
+    /// monospace characters `char_width` points wide
+    pub fn max_width(self, view_width: f32, char_width: f32) -> f32 {
+        match self {
+            WrapMode::None => f32::INFINITY,
+            WrapMode::View => view_width,
+            WrapMode::Column(column) => view_width.min(column as f32 * char_width),
+        }
+    }
+}
+
+/// Which whitespace an editor view makes visible
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderWhitespace {
+    /// Whitespace is not shown
+    #[default]
+    None,
+    /// Spaces, tabs and line endings are shown everywhere
+    All,
+    /// Whitespace is shown inside the selection
+    Selection,
+    /// Spaces and tabs are shown at the end of lines
+    Trailing,
+}
+
+/// A glyph drawn over a whitespace character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhitespaceGlyph {
+    /// A space
+    Space,
+    /// A tab
+    Tab,
+    /// The end of a line
+    LineEnd,
+}
+
+impl WhitespaceGlyph {
+    /// Returns the character drawn for the glyph
+    pub fn symbol(self) -> char {
+        match self {
+            WhitespaceGlyph::Space => '·',
+            WhitespaceGlyph::Tab => '→',
+            WhitespaceGlyph::LineEnd => '¬',
+        }
+    }
+}
+
+/// Returns the whitespace glyphs to draw for a line, with their byte offsets
+/// in the line
+///
+/// `line` may end with its line ending, which gets a glyph at the ending's
+/// offset. `selection` is the selected byte range relative to the line start,
+/// if the selection touches the line.
+pub fn whitespace_glyphs(
+    line: &str,
+    mode: RenderWhitespace,
+    selection: Option<Range<usize>>,
+) -> Vec<(usize, WhitespaceGlyph)> {
+    let content = line.trim_end_matches(['\n', '\r']);
+    let has_ending = content.len() < line.len();
+    let shown = |offset: usize| match mode {
+        RenderWhitespace::None => false,
+        RenderWhitespace::All => true,
+        RenderWhitespace::Selection => selection.as_ref().is_some_and(|range| range.contains(&offset)),
+        RenderWhitespace::Trailing => offset >= content.trim_end_matches([' ', '\t']).len(),
+    };
+
+    let mut glyphs: Vec<_> = content
+        .char_indices()
+        .filter_map(|(offset, c)| match c {
+            ' ' => Some((offset, WhitespaceGlyph::Space)),
+            '\t' => Some((offset, WhitespaceGlyph::Tab)),
+            _ => None,
+        })
+        .filter(|&(offset, _)| shown(offset))
+        .collect();
+    if has_ending && mode != RenderWhitespace::Trailing && shown(content.len()) {
+        glyphs.push((content.len(), WhitespaceGlyph::LineEnd));
+    }
+    glyphs
+}
+
+/// Returns the x offsets from the start of the text of rulers at `columns`,
+/// with monospace characters `char_width` points wide
+///
+/// A ruler at column 80 sits after the 80th character. Offsets are sorted
+/// and duplicates dropped.
+pub fn ruler_offsets(columns: &[u32], char_width: f32) -> Vec<f32> {
+    let mut columns = columns.to_vec();
+    columns.sort_unstable();
+    columns.dedup();
+    columns.into_iter().map(|column| column as f32 * char_width).collect()
+}
+
+/// Width reserved for the "»" button listing the tabs that do not fit
+pub const OVERFLOW_BUTTON_WIDTH: f32 = 24.0;
+
+/// Returns the range of tabs shown in a tab strip of the given width
+///
+/// Tabs are shown from `first` onwards, shifted so that `active` is always
+/// visible. When not all tabs fit, room is left for the overflow button.
+/// At least one tab is shown even if it is wider than the strip.
+pub fn visible_tab_range(widths: &[f32], available: f32, first: usize, active: Option<usize>) -> Range<usize> {
+    let count = widths.len();
+    if count == 0 {
+        return 0..0;
+    }
+    if widths.iter().sum::<f32>() <= available {
+        return 0..count;
+    }
+
+    let available = available - OVERFLOW_BUTTON_WIDTH;
+    let fits_from = |first: usize| {
+        let mut end = first;
+        let mut used = 0.0;
+        while end < count && used + widths[end] <= available {
+            used += widths[end];
+            end += 1;
+        }
+        end.max(first + 1)
+    };
+
+    let mut first = first.min(count - 1);
+    if let Some(active) = active.filter(|&active| active < count) {
+        if active < first {
+            first = active;
+        } else if active >= fits_from(first) {
+            // Scroll right until the active tab is the last one shown
+            first = active;
+            let mut used = widths[active];
+            while first > 0 && used + widths[first - 1] <= available {
+                first -= 1;
+                used += widths[first];
+            }
+        }
+    }
+
+    // Fill any space left after the last tab with tabs before `first`
+    let mut end = fits_from(first);
+    if end == count {
+        let mut used: f32 = widths[first..end].iter().sum();
+        while first > 0 && used + widths[first - 1] <= available {
+            first -= 1;
+            used += widths[first];
+        }
+        end = fits_from(first);
+    }
+
+    first..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_syntax::Color;
+
+    fn source(start: usize, end: usize, hex: &str) -> HighlightEvent {
+        let color = Color::from_hex(hex).unwrap();
+        HighlightEvent::Source { start, end, style: Style::new().with_foreground(color) }
+    }
+
+    /// Returns the text and color of each section
+    fn sections(job: &LayoutJob) -> Vec<(&str, Color32)> {
+        job.sections.iter()
+            .map(|section| (&job.text[section.byte_range.clone()], section.format.color))
+            .collect()
+    }
+
+    #[test]
+    fn test_text_preserved_with_gaps() {
+        let text = "fn main() {\n\tlet é = 1;\n}";
+        let events = [source(0, 2, "#ff0000"), source(13, 16, "#0000ff")];
+        let (style, visuals) = (egui::Style::default(), egui::Visuals::dark());
+
+        let job = highlight_to_layout_job(text, &events, &style, &visuals);
+        assert_eq!(job.text, text);
+
+        let plain = visuals.text_color();
+        assert_eq!(sections(&job), vec![
+            ("fn", Color32::from_rgb(255, 0, 0)),
+            (" main() {\n\t", plain),
+            ("let", Color32::from_rgb(0, 0, 255)),
+            (" é = 1;\n}", plain),
+        ]);
+        assert!(job.sections.iter().all(|s| s.format.font_id == TextStyle::Monospace.resolve(&style)));
+    }
+
+    #[test]
+    fn test_whitespace_glyphs() {
+        use WhitespaceGlyph::{LineEnd, Space, Tab};
+        let line = "\tlet x = 1;  \r\n";
+
+        assert!(whitespace_glyphs(line, RenderWhitespace::None, Some(0..line.len())).is_empty());
+        assert_eq!(whitespace_glyphs(line, RenderWhitespace::All, None), vec![
+            (0, Tab), (4, Space), (6, Space), (8, Space), (11, Space), (12, Space), (13, LineEnd),
+        ]);
+        assert_eq!(whitespace_glyphs(line, RenderWhitespace::Trailing, None), vec![(11, Space), (12, Space)]);
+        assert_eq!(whitespace_glyphs("  \t\n", RenderWhitespace::Trailing, None), vec![(0, Space), (1, Space), (2, Tab)]);
+
+        // Only the selected part, including the line ending if it is selected
+        assert!(whitespace_glyphs(line, RenderWhitespace::Selection, None).is_empty());
+        assert_eq!(whitespace_glyphs(line, RenderWhitespace::Selection, Some(5..9)), vec![(6, Space), (8, Space)]);
+        assert_eq!(whitespace_glyphs(line, RenderWhitespace::Selection, Some(12..15)), vec![(12, Space), (13, LineEnd)]);
+
+        // The last line has no ending
+        assert_eq!(whitespace_glyphs("a b", RenderWhitespace::All, None), vec![(1, Space)]);
+    }
+
+    #[test]
+    fn test_visible_slice_of_long_line() {
+        let line = "x".repeat(5_000_000);
+        assert_eq!(visible_slice(&line, 800.0, 400.0, 8.0), 100..150);
+        // Partly visible characters at either edge are included
+        assert_eq!(visible_slice(&line, 804.0, 400.0, 8.0), 100..151);
+        assert_eq!(visible_slice(&line, 0.0, 400.0, 8.0), 0..50);
+        assert_eq!(visible_slice(&line, 39_999_800.0, 400.0, 8.0), 4_999_975..5_000_000);
+        assert_eq!(visible_slice(&line, 50_000_000.0, 400.0, 8.0), 5_000_000..5_000_000);
+
+        // Offsets stay on character boundaries
+        let line = "é".repeat(20_000);
+        assert_eq!(visible_slice(&line, 80.0, 40.0, 8.0), 20..30);
+
+        // Long lines are not highlighted
+        let text = format!("let a\n{}\n", "y".repeat(LONG_LINE_THRESHOLD + 1));
+        let events = [source(0, 3, "#00ff00"), source(6, 10, "#ff0000")];
+        let job = highlight_to_layout_job(&text, &events, &egui::Style::default(), &egui::Visuals::dark());
+        assert_eq!(job.text, text);
+        assert_eq!(job.sections.len(), 2);
+        assert!(has_long_line(&text));
+        assert!(!has_long_line("let a\n"));
+    }
+
+    #[test]
+    fn test_split_long_lines() {
+        let long = "x".repeat(LONG_LINE_THRESHOLD + 1);
+        let l = long.len();
+        let text = format!("a\nb\n{long}\n\n{long}\nc");
+        assert_eq!(split_long_lines(&text), vec![
+            (0..3, false),
+            (4..4 + l, true),
+            (5 + l..5 + l, false),
+            (6 + l..6 + 2 * l, true),
+            (7 + 2 * l..8 + 2 * l, false),
+        ]);
+
+        // Adjacent long lines, and an empty last line
+        let text = format!("{long}\n{long}\n");
+        assert_eq!(split_long_lines(&text), vec![(0..l, true), (l + 1..2 * l + 1, true), (2 * l + 2..2 * l + 2, false)]);
+        assert_eq!(split_long_lines(&long), vec![(0..l, true)]);
+
+        assert_eq!(split_long_lines("short\ntext"), vec![(0..10, false)]);
+        assert_eq!(split_long_lines(""), vec![(0..0, false)]);
+    }
+
+    #[test]
+    fn test_visible_line_range() {
+        // Typical scroll positions, with partly visible lines included
+        assert_eq!(visible_line_range_with_overscan(160.0, 320.0, 16.0, 1000, 0), 10..30);
+        assert_eq!(visible_line_range_with_overscan(168.0, 320.0, 16.0, 1000, 0), 10..31);
+        assert_eq!(visible_line_range(160.0, 320.0, 16.0, 1000), 8..32);
+
+        // Top and bottom edges
+        assert_eq!(visible_line_range(0.0, 320.0, 16.0, 1000), 0..22);
+        assert_eq!(visible_line_range(-40.0, 320.0, 16.0, 1000), 0..22);
+        assert_eq!(visible_line_range(15_840.0, 320.0, 16.0, 1000), 988..1000);
+        assert_eq!(visible_line_range(20_000.0, 320.0, 16.0, 1000), 1000..1000);
+        assert_eq!(visible_line_range(0.0, 320.0, 16.0, 5), 0..5);
+        assert_eq!(visible_line_range(0.0, 320.0, 16.0, 0), 0..0);
+
+        // Overscan is clamped to the document
+        assert_eq!(visible_line_range_with_overscan(160.0, 320.0, 16.0, 1000, 20), 0..50);
+        assert_eq!(visible_line_range_with_overscan(15_840.0, 320.0, 16.0, 1000, 20), 970..1000);
+        assert_eq!(visible_line_range_with_overscan(0.0, 320.0, 0.0, 1000, 2), 0..1000);
+    }
+
+    #[test]
+    fn test_ruler_offsets() {
+        assert_eq!(ruler_offsets(&[80], 8.0), vec![640.0]);
+        assert_eq!(ruler_offsets(&[100, 80, 100], 7.5), vec![600.0, 750.0]);
+        assert_eq!(ruler_offsets(&[0], 8.0), vec![0.0]);
+        assert!(ruler_offsets(&[], 8.0).is_empty());
+    }
+
+    #[test]
+    fn test_stale_events_render_plain() {
+        let text = "let x";
+        // The second event no longer fits the text after an edit
+        let events = [source(0, 3, "#00ff00"), source(4, 12, "#ff0000")];
+        let job = highlight_to_layout_job(text, &events, &egui::Style::default(), &egui::Visuals::light());
+
+        assert_eq!(job.text, text);
+        assert_eq!(sections(&job), vec![
+            ("let", Color32::from_rgb(0, 255, 0)),
+            (" x", egui::Visuals::light().text_color()),
+        ]);
+    }
+
+    #[test]
+    fn test_visible_tab_range() {
+        let widths = [100.0; 10];
+
+        // Everything fits
+        assert_eq!(visible_tab_range(&widths[..3], 400.0, 0, Some(0)), 0..3);
+
+        // Room for three tabs next to the overflow button
+        assert_eq!(visible_tab_range(&widths, 330.0, 0, Some(1)), 0..3);
+
+        // Activating a hidden tab on the right scrolls it into view as the last tab
+        assert_eq!(visible_tab_range(&widths, 330.0, 0, Some(6)), 4..7);
+
+        // Activating a hidden tab on the left scrolls back to it
+        assert_eq!(visible_tab_range(&widths, 330.0, 4, Some(2)), 2..5);
+
+        // Space after the last tab is filled with earlier tabs
+        assert_eq!(visible_tab_range(&widths, 330.0, 9, Some(9)), 7..10);
+
+        // A tab wider than the strip is still shown
+        assert_eq!(visible_tab_range(&[500.0, 100.0], 300.0, 0, Some(0)), 0..1);
+    }
+}
@@ -1,10 +1,36 @@
 //! GUI implementation for rust-editor
 
 mod app;
+mod cursor;
+mod diff_view;
+mod highlight;
+mod layout;
+mod open;
+mod problems;
+mod session;
 mod theme;
 
-pub use crate::app::run;
-pub use crate::theme::Theme;
+pub use crate::app::{
+    run, EditorApp, CLOSE_FILE, COMMAND_PALETTE, DUPLICATE_TO_SCRATCH, EXPAND_SELECTION, FIND, GO_TO_FILE, GO_TO_LINE,
+    NEW_FILE, NEXT_BOOKMARK, NEXT_DIAGNOSTIC, OPEN_FILE, PREV_BOOKMARK, PREV_DIAGNOSTIC, REDO, SAVE_FILE,
+    SHRINK_SELECTION, TOGGLE_BOOKMARK, TOGGLE_WORD_WRAP, UNDO,
+};
+pub use crate::cursor::{
+    caret_in_galley, cursor_rect, grapheme_chars, paint_cursor, CursorBlink, CursorSettings, CursorShape, CursorStyle,
+    DEFAULT_BLINK_RATE,
+};
+pub use crate::diff_view::{compute_line_diff, DiffKind, DiffOp, DiffView};
+pub use crate::highlight::{HighlightScheduler, DEFAULT_HIGHLIGHT_DEBOUNCE};
+pub use crate::layout::{
+    has_long_line, highlight_to_layout_job, ruler_offsets, split_long_lines, visible_line_range,
+    visible_line_range_with_overscan, visible_slice, visible_tab_range, whitespace_glyphs, RenderWhitespace,
+    WhitespaceGlyph, WrapMode,
+    DEFAULT_OVERSCAN_LINES, LONG_LINE_THRESHOLD, OVERFLOW_BUTTON_WIDTH,
+};
+pub use crate::open::{open_file, open_file_async, OpenError};
+pub use crate::problems::{collect_problems, Problem, ProblemGroup};
+pub use crate::session::{SessionData, SharedSession};
+pub use crate::theme::{EditorFont, FontSettings, Theme, DEFAULT_FONT_SIZE};
 
 use editor_core::Error as CoreError;
 use std::io;
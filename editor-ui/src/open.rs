@@ -0,0 +1,171 @@
+//! Opening files from the UI, with errors worth showing to the user
+
+use editor_core::{Editor, Error as CoreError};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Number of leading bytes checked to tell binary files from badly encoded text
+const SNIFF_LEN: u64 = 8 * 1024;
+
+/// Reason a file could not be opened
+#[derive(thiserror::Error, Debug)]
+pub enum OpenError {
+    #[error("{} does not exist", path.display())]
+    NotFound { path: PathBuf },
+
+    #[error("Permission denied reading {}", path.display())]
+    PermissionDenied { path: PathBuf },
+
+    #[error("{} is a binary file", path.display())]
+    Binary { path: PathBuf },
+
+    #[error("{} is not valid UTF-8 or UTF-16 text", path.display())]
+    Encoding { path: PathBuf },
+
+    #[error("{} is too large to open ({size} bytes)", path.display())]
+    TooLarge { path: PathBuf, size: u64 },
+
+    #[error("Could not open {}: {source}", path.display())]
+    Other {
+        path: PathBuf,
+        #[source]
+        source: CoreError,
+    },
+}
+
+impl OpenError {
+    /// Classifies an error returned while opening `path`
+    pub fn from_core(path: &Path, error: CoreError) -> Self {
+        let path = path.to_path_buf();
+        match error {
+            CoreError::Io(e) => match e.kind() {
+                io::ErrorKind::NotFound => OpenError::NotFound { path },
+                io::ErrorKind::PermissionDenied => OpenError::PermissionDenied { path },
+                io::ErrorKind::InvalidData => OpenError::Encoding { path },
+                _ => OpenError::Other { path, source: CoreError::Io(e) },
+            },
+            CoreError::LargeFile { size, .. } => OpenError::TooLarge { path, size },
+            source => OpenError::Other { path, source },
+        }
+    }
+
+    /// Returns the error for a file whose content was not recognized as text
+    ///
+    /// Files with NUL bytes are binary; anything else is text in an
+    /// unsupported encoding.
+    pub fn not_text(path: &Path) -> Self {
+        let path = path.to_path_buf();
+        let mut sample = Vec::new();
+        let has_nul = File::open(&path)
+            .and_then(|file| file.take(SNIFF_LEN).read_to_end(&mut sample))
+            .map(|_| sample.contains(&0))
+            .unwrap_or(true);
+
+        if has_nul {
+            OpenError::Binary { path }
+        } else {
+            OpenError::Encoding { path }
+        }
+    }
+
+    /// Returns the path that failed to open
+    pub fn path(&self) -> &Path {
+        match self {
+            OpenError::NotFound { path }
+            | OpenError::PermissionDenied { path }
+            | OpenError::Binary { path }
+            | OpenError::Encoding { path }
+            | OpenError::TooLarge { path, .. }
+            | OpenError::Other { path, .. } => path,
+        }
+    }
+}
+
+/// Opens a file as the editor's active document
+///
+/// Files above the editor's large file threshold are only opened if
+/// `allow_large` is set. Files that are not text are closed again and
+/// reported as `OpenError::Binary` or `OpenError::Encoding`.
+pub fn open_file(editor: &mut Editor, path: &Path, allow_large: bool) -> Result<(), OpenError> {
+    let result = if allow_large {
+        editor.open_file_large(path)
+    } else {
+        editor.open_file(path)
+    };
+    result.map_err(|e| OpenError::from_core(path, e))?;
+    keep_if_text(editor, path)
+}
+
+/// Opens a file as the editor's active document without blocking the runtime
+///
+/// Behaves like `open_file`.
+pub async fn open_file_async(editor: &mut Editor, path: &Path, allow_large: bool) -> Result<(), OpenError> {
+    let result = if allow_large {
+        editor.open_file_large_async(path).await
+    } else {
+        editor.open_file_async(path).await
+    };
+    result.map_err(|e| OpenError::from_core(path, e))?;
+    keep_if_text(editor, path)
+}
+
+/// Closes the just opened active document again if it is not text
+fn keep_if_text(editor: &mut Editor, path: &Path) -> Result<(), OpenError> {
+    let binary = editor.active_document()
+        .filter(|doc| doc.content_kind().is_binary())
+        .map(|doc| doc.name().to_string());
+    if let Some(name) = binary {
+        let _ = editor.close_document(&name);
+        return Err(OpenError::not_text(path));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_errors() {
+        let dir = TempDir::new().unwrap();
+        let mut editor = Editor::new();
+
+        let missing = dir.path().join("missing.txt");
+        let err = open_file(&mut editor, &missing, false).unwrap_err();
+        assert!(matches!(err, OpenError::NotFound { .. }));
+        assert_eq!(err.to_string(), format!("{} does not exist", missing.display()));
+
+        let binary = dir.path().join("image.png");
+        std::fs::write(&binary, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        assert!(matches!(open_file(&mut editor, &binary, false), Err(OpenError::Binary { .. })));
+        assert!(!editor.has_document("image.png"));
+
+        let latin1 = dir.path().join("latin1.txt");
+        std::fs::write(&latin1, b"caf\xe9\n").unwrap();
+        assert!(matches!(open_file(&mut editor, &latin1, false), Err(OpenError::Encoding { .. })));
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "hello\n").unwrap();
+        open_file(&mut editor, &text, false).unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_open_file_async() {
+        let dir = TempDir::new().unwrap();
+        let mut editor = Editor::new();
+
+        let binary = dir.path().join("image.png");
+        std::fs::write(&binary, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        let err = open_file_async(&mut editor, &binary, false).await.unwrap_err();
+        assert!(matches!(err, OpenError::Binary { .. }));
+        assert!(!editor.has_document("image.png"));
+
+        let text = dir.path().join("notes.txt");
+        std::fs::write(&text, "hello\n").unwrap();
+        open_file_async(&mut editor, &text, false).await.unwrap();
+        assert_eq!(editor.active_document().unwrap().text(), "hello\n");
+    }
+}
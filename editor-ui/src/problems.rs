@@ -0,0 +1,155 @@
+//! Problems panel model built from diagnostic markers
+
+use editor_core::{DiagnosticSeverity, Document, Marker, MarkerType};
+
+/// A diagnostic shown in the problems panel
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    /// Byte offset in the document
+    pub position: usize,
+    /// Zero-based line
+    pub line: usize,
+    /// Zero-based column (in characters)
+    pub column: usize,
+    /// Severity level
+    pub severity: DiagnosticSeverity,
+    /// Source of the diagnostic, e.g. a language server or linter
+    pub source: String,
+    /// Message to display
+    pub message: String,
+}
+
+/// Problems belonging to one document
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProblemGroup {
+    /// Document name
+    pub document: String,
+    /// Problems sorted by severity, then position
+    pub problems: Vec<Problem>,
+}
+
+/// Collects diagnostic markers from the given documents, grouped by document name
+///
+/// Groups are sorted by name and documents without diagnostics are skipped.
+pub fn collect_problems<'a>(documents: impl IntoIterator<Item = &'a Document>) -> Vec<ProblemGroup> {
+    let mut groups: Vec<ProblemGroup> = documents
+        .into_iter()
+        .filter_map(|doc| {
+            let text = doc.text();
+            let mut problems: Vec<Problem> = doc.markers()
+                .diagnostics()
+                .into_iter()
+                .filter_map(|marker| to_problem(marker, &text))
+                .collect();
+
+            if problems.is_empty() {
+                return None;
+            }
+
+            problems.sort_by_key(|p| (p.severity, p.position));
+            Some(ProblemGroup {
+                document: doc.name().to_string(),
+                problems,
+            })
+        })
+        .collect();
+
+    groups.sort_by(|a, b| a.document.cmp(&b.document));
+    groups
+}
+
+/// Returns the icon displayed for a severity
+pub fn severity_icon(severity: DiagnosticSeverity) -> &'static str {
+    match severity {
+        DiagnosticSeverity::Error => "❌",
+        DiagnosticSeverity::Warning => "⚠",
+        DiagnosticSeverity::Info => "ℹ",
+        DiagnosticSeverity::Hint => "💡",
+    }
+}
+
+/// Converts a diagnostic marker into a problem, resolving its line and column
+fn to_problem(marker: &Marker, text: &str) -> Option<Problem> {
+    let MarkerType::Diagnostic { severity, source } = &marker.marker_type else {
+        return None;
+    };
+
+    let message = marker.data.as_ref()
+        .and_then(|data| data.get("message"))
+        .and_then(|message| message.as_str())
+        .unwrap_or(&marker.name)
+        .to_string();
+
+    let position = marker.position.min(text.len());
+    let before = text.get(..position).unwrap_or(text);
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let column = before[line_start..].chars().count();
+
+    Some(Problem {
+        position,
+        line,
+        column,
+        severity: *severity,
+        source: source.clone(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(severity: DiagnosticSeverity) -> MarkerType {
+        MarkerType::Diagnostic {
+            severity,
+            source: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_collect_problems() {
+        let mut main = Document::new("main.rs");
+        main.insert(0, "fn main() {\n    let x = 1;\n}\n").unwrap();
+        main.markers_mut().set_with_data(
+            "unused",
+            20,
+            diagnostic(DiagnosticSeverity::Warning),
+            serde_json::json!({ "message": "unused variable `x`" }),
+        );
+        main.markers_mut().set_with_data(
+            "late-error",
+            26,
+            diagnostic(DiagnosticSeverity::Error),
+            serde_json::json!({ "message": "expected `;`" }),
+        );
+        main.markers_mut().set_with_type("early-error", 3, diagnostic(DiagnosticSeverity::Error));
+        main.markers_mut().set("bookmark", 0);
+
+        let mut lib = Document::new("lib.rs");
+        lib.insert(0, "pub fn lib() {}\n").unwrap();
+        lib.markers_mut().set_with_type("hint", 7, diagnostic(DiagnosticSeverity::Hint));
+
+        let clean = Document::new("clean.rs");
+
+        let groups = collect_problems([&main, &lib, &clean]);
+
+        // Grouped by document, sorted by name, clean documents skipped
+        let names: Vec<_> = groups.iter().map(|g| g.document.as_str()).collect();
+        assert_eq!(names, vec!["lib.rs", "main.rs"]);
+
+        // Sorted by severity, then position
+        let problems = &groups[1].problems;
+        let order: Vec<_> = problems.iter().map(|p| (p.severity, p.position)).collect();
+        assert_eq!(order, vec![
+            (DiagnosticSeverity::Error, 3),
+            (DiagnosticSeverity::Error, 26),
+            (DiagnosticSeverity::Warning, 20),
+        ]);
+
+        // Message comes from the marker data, falling back to the marker name
+        assert_eq!(problems[0].message, "early-error");
+        assert_eq!(problems[1].message, "expected `;`");
+        assert_eq!((problems[2].line, problems[2].column), (1, 8));
+    }
+}
@@ -0,0 +1,113 @@
+//! Session data shared between the editor window and the program running it
+
+use editor_core::{Bookmark, MAX_RECENT_FILES};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Session data the editor window reads and updates
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionData {
+    /// Bookmarked lines of files
+    pub bookmarks: HashMap<PathBuf, Vec<Bookmark>>,
+    /// Directory listed by "Go to File", if the session has a workspace
+    pub workspace_root: Option<PathBuf>,
+    /// Recently opened files, most recent first
+    pub recent_files: Vec<PathBuf>,
+}
+
+/// Handle to session data shared between the editor window and the program
+/// running it
+///
+/// The program fills it from its saved session before running the window
+/// and saves it back afterwards; the window keeps it up to date as files are
+/// opened and closed.
+#[derive(Debug, Clone, Default)]
+pub struct SharedSession {
+    /// Current session data
+    inner: Arc<Mutex<SessionData>>,
+}
+
+impl SharedSession {
+    /// Creates a handle holding the given data
+    pub fn new(data: SessionData) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    /// Returns a copy of the current data
+    pub fn get(&self) -> SessionData {
+        self.lock().clone()
+    }
+
+    /// Returns the bookmarks stored for a file
+    pub fn bookmarks(&self, path: &Path) -> Vec<Bookmark> {
+        self.lock().bookmarks.get(path).cloned().unwrap_or_default()
+    }
+
+    /// Stores the bookmarks of a file, forgetting the file if there are none
+    pub fn set_bookmarks(&self, path: PathBuf, bookmarks: Vec<Bookmark>) {
+        let mut data = self.lock();
+        if bookmarks.is_empty() {
+            data.bookmarks.remove(&path);
+        } else {
+            data.bookmarks.insert(path, bookmarks);
+        }
+    }
+
+    /// Returns the directory listed by "Go to File", if any
+    pub fn workspace_root(&self) -> Option<PathBuf> {
+        self.lock().workspace_root.clone()
+    }
+
+    /// Returns the recently opened files, most recent first
+    pub fn recent_files(&self) -> Vec<PathBuf> {
+        self.lock().recent_files.clone()
+    }
+
+    /// Moves a file to the front of the recently opened files
+    pub fn note_opened(&self, path: &Path) {
+        let mut data = self.lock();
+        data.recent_files.retain(|recent| recent != path);
+        data.recent_files.insert(0, path.to_path_buf());
+        data.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, SessionData> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_bookmarks() {
+        let session = SharedSession::default();
+        let shared = session.clone();
+        let path = PathBuf::from("notes.txt");
+
+        shared.set_bookmarks(path.clone(), vec![Bookmark::new(2)]);
+        assert_eq!(session.bookmarks(&path), vec![Bookmark::new(2)]);
+
+        // Files without bookmarks are forgotten
+        shared.set_bookmarks(path.clone(), Vec::new());
+        assert_eq!(session.get(), SessionData::default());
+    }
+
+    #[test]
+    fn test_recent_files() {
+        let session = SharedSession::default();
+        session.note_opened(Path::new("/src/a.rs"));
+        session.note_opened(Path::new("/src/b.rs"));
+        session.note_opened(Path::new("/src/a.rs"));
+        assert_eq!(session.recent_files(), vec![PathBuf::from("/src/a.rs"), PathBuf::from("/src/b.rs")]);
+
+        for i in 0..MAX_RECENT_FILES {
+            session.note_opened(&PathBuf::from(format!("/src/{}.rs", i)));
+        }
+        assert_eq!(session.recent_files().len(), MAX_RECENT_FILES);
+    }
+}
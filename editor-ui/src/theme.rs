@@ -1,7 +1,11 @@
 //! Theme settings for the editor UI
 
-use eframe::egui::{self, Visuals};
+use eframe::egui::{self, FontFamily, FontId, TextStyle, Visuals};
 use serde::{Serialize, Deserialize};
+use std::sync::{Arc, Mutex};
+
+/// Default editor font size in points
+pub const DEFAULT_FONT_SIZE: f32 = 14.0;
 
 /// Internal theme type used for system theme detection
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -223,3 +227,131 @@ impl Theme {
         }
     }
 }
+
+/// Font of the text editor
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditorFont {
+    /// Font family name, `EditorFont::MONOSPACE` for egui's built-in font
+    pub family: String,
+    /// Font size in points
+    pub size: f32,
+}
+
+impl EditorFont {
+    /// Family name of egui's built-in monospace font
+    pub const MONOSPACE: &'static str = "monospace";
+
+    /// Creates a font setting
+    pub fn new(family: impl Into<String>, size: f32) -> Self {
+        Self {
+            family: family.into(),
+            size,
+        }
+    }
+
+    /// Returns the family to use, falling back to monospace if the named
+    /// family is not among the `available` ones
+    pub fn resolve_family(&self, available: &[FontFamily]) -> FontFamily {
+        let named = FontFamily::Name(self.family.as_str().into());
+        if self.family != Self::MONOSPACE && available.contains(&named) {
+            named
+        } else {
+            FontFamily::Monospace
+        }
+    }
+
+    /// Sets the monospace text style, used by the editor view, to this font
+    ///
+    /// Sizes that are not positive fall back to `DEFAULT_FONT_SIZE`.
+    pub fn apply_to_style(&self, style: &mut egui::Style, available: &[FontFamily]) {
+        let size = if self.size.is_finite() && self.size > 0.0 { self.size } else { DEFAULT_FONT_SIZE };
+        style.text_styles.insert(TextStyle::Monospace, FontId::new(size, self.resolve_family(available)));
+    }
+
+    /// Applies the font to the egui context
+    pub fn apply(&self, ctx: &egui::Context) {
+        let available = ctx.fonts(|fonts| fonts.families());
+        if self.resolve_family(&available) == FontFamily::Monospace && self.family != Self::MONOSPACE {
+            log::warn!("Font '{}' is not available, using monospace", self.family);
+        }
+
+        let mut style = (*ctx.style()).clone();
+        self.apply_to_style(&mut style, &available);
+        ctx.set_style(style);
+    }
+}
+
+impl Default for EditorFont {
+    fn default() -> Self {
+        Self::new(Self::MONOSPACE, DEFAULT_FONT_SIZE)
+    }
+}
+
+/// Editor font shared between the UI and code changing it at runtime
+///
+/// Each change bumps a version, so the UI only re-applies the font after it
+/// changed.
+#[derive(Debug, Clone, Default)]
+pub struct FontSettings {
+    /// Current font and its version
+    inner: Arc<Mutex<(EditorFont, u64)>>,
+}
+
+impl FontSettings {
+    /// Creates settings holding the given font
+    pub fn new(font: EditorFont) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new((font, 0))),
+        }
+    }
+
+    /// Returns the current font
+    pub fn get(&self) -> EditorFont {
+        self.lock().0.clone()
+    }
+
+    /// Replaces the font
+    pub fn set(&self, font: EditorFont) {
+        let mut inner = self.lock();
+        inner.0 = font;
+        inner.1 += 1;
+    }
+
+    /// Returns the number of changes so far
+    pub fn version(&self) -> u64 {
+        self.lock().1
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (EditorFont, u64)> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_font_fallback() {
+        let available = vec![FontFamily::Monospace, FontFamily::Proportional, FontFamily::Name("Fira Code".into())];
+        let mut style = egui::Style::default();
+
+        EditorFont::new("Fira Code", 16.0).apply_to_style(&mut style, &available);
+        assert_eq!(style.text_styles[&TextStyle::Monospace], FontId::new(16.0, FontFamily::Name("Fira Code".into())));
+
+        // Unknown fonts and invalid sizes fall back
+        EditorFont::new("Comic Mono", -1.0).apply_to_style(&mut style, &available);
+        assert_eq!(style.text_styles[&TextStyle::Monospace], FontId::monospace(DEFAULT_FONT_SIZE));
+    }
+
+    #[test]
+    fn test_font_settings_versions() {
+        let settings = FontSettings::default();
+        assert_eq!(settings.get(), EditorFont::default());
+
+        let shared = settings.clone();
+        shared.set(EditorFont::new("Fira Code", 18.0));
+        assert_eq!(settings.version(), 1);
+        assert_eq!(settings.get().size, 18.0);
+    }
+}
@@ -2,6 +2,8 @@
 
 use crate::Widget;
 use eframe::egui;
+use editor_core::{Clock, StatusItem, SystemClock};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 /// Status message
@@ -44,11 +46,20 @@ pub struct StatusLine {
     git_branch: Option<String>,
     /// LSP status
     lsp_status: String,
+    /// Items contributed by plugins, in display order
+    plugin_items: Vec<StatusItem>,
+    /// Time source for message expiry
+    clock: Arc<dyn Clock>,
 }
 
 impl StatusLine {
-    /// Creates a new status line
+    /// Creates a new status line using the system clock
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates a new status line whose messages expire by the given clock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             messages: Vec::new(),
             mode: "NORMAL".to_string(),
@@ -57,6 +68,8 @@ impl StatusLine {
             line_ending: "LF".to_string(),
             git_branch: None,
             lsp_status: String::new(),
+            plugin_items: Vec::new(),
+            clock,
         }
     }
 
@@ -90,6 +103,11 @@ impl StatusLine {
         self.lsp_status = status.into();
     }
 
+    /// Sets the plugin items, as returned by `StatusItems::items`
+    pub fn set_plugin_items(&mut self, items: Vec<StatusItem>) {
+        self.plugin_items = items;
+    }
+
     /// Shows an info message
     pub fn info(&mut self, text: impl Into<String>, duration: Option<Duration>) {
         self.show_message(text.into(), MessageType::Info, duration);
@@ -110,16 +128,17 @@ impl StatusLine {
         self.messages.push(StatusMessage {
             text,
             message_type,
-            timestamp: Instant::now(),
+            timestamp: self.clock.now(),
             duration,
         });
     }
 
     /// Updates messages
     fn update_messages(&mut self) {
+        let now = self.clock.now();
         self.messages.retain(|msg| {
             if let Some(duration) = msg.duration {
-                msg.timestamp.elapsed() < duration
+                now.saturating_duration_since(msg.timestamp) < duration
             } else {
                 true
             }
@@ -165,6 +184,15 @@ impl Widget for StatusLine {
                 ui.separator();
             }
 
+            // Plugin items
+            for item in &self.plugin_items {
+                let response = ui.label(&item.text);
+                if let Some(tooltip) = &item.tooltip {
+                    response.on_hover_text(tooltip);
+                }
+                ui.separator();
+            }
+
             // Right side
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 ui.label(&self.encoding);
@@ -6,6 +6,7 @@ use editor_plugin::{Plugin, PluginMetadata, Result, Permission};
 use async_trait::async_trait;
 use serde_json::json;
 use std::path::Path;
+use std::sync::RwLock;
 use tokio::fs;
 use walkdir::WalkDir;
 
@@ -13,12 +14,12 @@ use walkdir::WalkDir;
 pub struct FileStatsPlugin {
     /// Plugin metadata
     metadata: PluginMetadata,
-    /// Current file statistics
-    stats: FileStats,
+    /// Statistics from the last analysis, updated from `execute`
+    stats: RwLock<FileStats>,
 }
 
 /// File statistics
-#[derive(Debug, Default, serde::Serialize)]
+#[derive(Debug, Default, Clone, serde::Serialize)]
 struct FileStats {
     /// Total number of files
     file_count: usize,
@@ -41,12 +42,12 @@ impl FileStatsPlugin {
                 version: "0.1.0".to_string(),
                 description: "File and directory statistics plugin".to_string(),
             },
-            stats: FileStats::default(),
+            stats: RwLock::new(FileStats::default()),
         }
     }
 
     /// Analyzes a directory
-    async fn analyze_directory(&mut self, path: &Path) -> Result<()> {
+    async fn analyze_directory(&self, path: &Path) -> Result<()> {
         let mut stats = FileStats::default();
 
         for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
@@ -71,20 +72,30 @@ impl FileStatsPlugin {
             }
         }
 
-        self.stats = stats;
+        // Only lock once the analysis is done so the lock is never held across an await
+        *self.stats.write().unwrap() = stats;
         Ok(())
     }
 
+    /// Returns the stored statistics along with their formatted representation
+    fn stats_response(&self) -> serde_json::Value {
+        let stats = self.stats.read().unwrap().clone();
+        json!({
+            "formatted": Self::format_stats(&stats),
+            "stats": stats,
+        })
+    }
+
     /// Formats the statistics as a human-readable string
-    fn format_stats(&self) -> String {
+    fn format_stats(stats: &FileStats) -> String {
         let mut output = String::new();
-        output.push_str(&format!("Files: {}\n", self.stats.file_count));
-        output.push_str(&format!("Directories: {}\n", self.stats.dir_count));
-        output.push_str(&format!("Total size: {} bytes\n", self.stats.total_size));
-        output.push_str(&format!("Lines of code: {}\n", self.stats.lines_of_code));
+        output.push_str(&format!("Files: {}\n", stats.file_count));
+        output.push_str(&format!("Directories: {}\n", stats.dir_count));
+        output.push_str(&format!("Total size: {} bytes\n", stats.total_size));
+        output.push_str(&format!("Lines of code: {}\n", stats.lines_of_code));
         
         output.push_str("\nFile types:\n");
-        for (ext, count) in &self.stats.file_types {
+        for (ext, count) in &stats.file_types {
             output.push_str(&format!("  .{}: {}\n", ext, count));
         }
 
@@ -113,22 +124,14 @@ impl Plugin for FileStatsPlugin {
             "analyze" => {
                 if let Some(path) = args.get("path").and_then(|v| v.as_str()) {
                     self.analyze_directory(Path::new(path)).await?;
-                    Ok(json!({
-                        "stats": self.stats,
-                        "formatted": self.format_stats()
-                    }))
+                    Ok(self.stats_response())
                 } else {
                     Ok(json!({
                         "error": "Path argument required"
                     }))
                 }
             }
-            "get_stats" => {
-                Ok(json!({
-                    "stats": self.stats,
-                    "formatted": self.format_stats()
-                }))
-            }
+            "get_stats" => Ok(self.stats_response()),
             _ => Ok(json!({
                 "error": format!("Unknown command: {}", command)
             }))
@@ -191,4 +194,30 @@ mod tests {
         // Shutdown plugin
         assert!(plugin.shutdown().await.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_get_stats_reflects_analysis() {
+        let temp_dir = TempDir::new().unwrap();
+        File::create(temp_dir.path().join("main.rs"))
+            .unwrap()
+            .write_all(b"fn main() {}\n")
+            .unwrap();
+
+        let plugin = FileStatsPlugin::new();
+
+        // Nothing analyzed yet
+        let before = plugin.execute("get_stats", json!({})).await.unwrap();
+        assert_eq!(before["stats"]["file_count"], 0);
+
+        plugin.execute(
+            "analyze",
+            json!({"path": temp_dir.path().to_str().unwrap()}),
+        ).await.unwrap();
+
+        // State stored by `analyze` is visible to later commands
+        let after = plugin.execute("get_stats", json!({})).await.unwrap();
+        assert_eq!(after["stats"]["file_count"], 1);
+        assert_eq!(after["stats"]["lines_of_code"], 1);
+        assert_eq!(after["stats"]["file_types"]["rs"], 1);
+    }
 }
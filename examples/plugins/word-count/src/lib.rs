@@ -72,6 +72,16 @@ pub extern "C" fn create_plugin() -> *mut dyn Plugin {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use editor_plugin::testing::{PluginEventKind, TestContext, TestHarness};
+    use editor_plugin::PluginError;
+    use serde::Deserialize;
+
+    /// Result of the `count` command
+    #[derive(Debug, Deserialize)]
+    struct CountResult {
+        count: usize,
+        message: String,
+    }
 
     #[tokio::test]
     async fn test_word_count() {
@@ -86,4 +96,34 @@ mod tests {
 
         assert_eq!(result["count"], 6);
     }
+
+    #[tokio::test]
+    async fn test_count_with_harness() {
+        let context = TestContext::with_files([
+            ("notes/todo.txt", "buy milk\nwalk the dog\n"),
+            ("notes/empty.txt", ""),
+        ]).unwrap();
+        let mut harness = TestHarness::new().unwrap();
+        harness.add_plugin(Box::new(WordCountPlugin::new())).await.unwrap();
+        harness.assert_event(PluginEventKind::Loaded);
+
+        let count = |name: &str| {
+            let text = std::fs::read_to_string(context.temp_path().join(name)).unwrap();
+            harness.execute_and_get::<CountResult>("word-count", "count", json!({ "text": text }))
+        };
+        let result = count("notes/todo.txt").await.unwrap();
+        assert_eq!(result.count, 5);
+        assert_eq!(result.message, "Word count: 5");
+        assert_eq!(count("notes/empty.txt").await.unwrap().count, 0);
+        assert!(harness.drain_events().is_empty());
+
+        // Unknown commands answer with an error, reported as an event
+        let error = harness.execute_and_get::<CountResult>("word-count", "sum", json!({})).await;
+        assert!(matches!(error, Err(PluginError::CommandFailed { .. })));
+        harness.assert_event(PluginEventKind::Error);
+
+        // Results of the wrong shape are rejected
+        let wrong = harness.execute_and_get::<Vec<String>>("word-count", "count", json!({ "text": "a b" })).await;
+        assert!(matches!(wrong, Err(PluginError::InvalidResponse { .. })));
+    }
 }
@@ -1,7 +1,10 @@
 //! History management for undo/redo functionality
 
 use super::operations::TextOperation;
+use editor_core::{Clock, SystemClock};
 use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Maximum number of history entries
 const MAX_HISTORY_SIZE: usize = 1000;
@@ -28,25 +31,50 @@ pub struct History {
     current_group: Option<u64>,
     /// Next group ID
     next_group_id: u64,
+    /// Time source for entry timestamps
+    clock: Arc<dyn Clock>,
+    /// Typing within this long of the previous insertion is undone with it
+    coalesce_window: Option<Duration>,
 }
 
 impl History {
-    /// Creates a new history
+    /// Creates a new history using the system clock
     pub fn new() -> Self {
+        Self::with_clock(Arc::new(SystemClock))
+    }
+
+    /// Creates a new history using the given clock
+    pub fn with_clock(clock: Arc<dyn Clock>) -> Self {
         Self {
             undo_stack: VecDeque::new(),
             redo_stack: VecDeque::new(),
             current_group: None,
             next_group_id: 1,
+            clock,
+            coalesce_window: None,
         }
     }
 
+    /// Sets how soon an insertion must follow the previous one to be undone
+    /// together with it, or disables coalescing with `None`
+    ///
+    /// Only insertions continuing right where the previous one ended are
+    /// coalesced, as when typing.
+    pub fn set_coalesce_window(&mut self, window: Option<Duration>) {
+        self.coalesce_window = window;
+    }
+
     /// Pushes an operation onto the history
     pub fn push(&mut self, operation: TextOperation) {
+        let timestamp = self.clock.system_time();
+        let group_id = match self.current_group {
+            Some(group) => Some(group),
+            None => self.coalesce_with_last(&operation, timestamp),
+        };
         let entry = HistoryEntry {
             operation,
-            timestamp: std::time::SystemTime::now(),
-            group_id: self.current_group,
+            timestamp,
+            group_id,
         };
 
         self.undo_stack.push_back(entry);
@@ -58,6 +86,38 @@ impl History {
         }
     }
 
+    /// Returns the group to put a typed insertion in if it continues the last
+    /// entry within the coalescing window
+    ///
+    /// The last entry is moved into a new group if it has none.
+    fn coalesce_with_last(&mut self, operation: &TextOperation, timestamp: std::time::SystemTime) -> Option<u64> {
+        let window = self.coalesce_window?;
+        let TextOperation::Insert { position, .. } = operation else {
+            return None;
+        };
+        let last = self.undo_stack.back()?;
+        let TextOperation::Insert { position: last_position, text: last_text } = &last.operation else {
+            return None;
+        };
+
+        let elapsed = timestamp.duration_since(last.timestamp).ok()?;
+        if elapsed > window || *position != last_position + last_text.len() {
+            return None;
+        }
+
+        match last.group_id {
+            Some(group) => Some(group),
+            None => {
+                let group = self.next_group_id;
+                self.next_group_id += 1;
+                if let Some(last) = self.undo_stack.back_mut() {
+                    last.group_id = Some(group);
+                }
+                Some(group)
+            }
+        }
+    }
+
     /// Starts a new operation group
     pub fn start_group(&mut self) {
         self.current_group = Some(self.next_group_id);
@@ -78,7 +138,7 @@ impl History {
             
             // Keep undoing operations in the same group
             while let Some(last) = self.undo_stack.back() {
-                if last.group_id != group_id {
+                if group_id.is_none() || last.group_id != group_id {
                     break;
                 }
                 if let Some(entry) = self.undo_stack.pop_back() {
@@ -90,7 +150,7 @@ impl History {
             for operation in operations.iter().rev() {
                 self.redo_stack.push_back(HistoryEntry {
                     operation: operation.clone(),
-                    timestamp: std::time::SystemTime::now(),
+                    timestamp: self.clock.system_time(),
                     group_id,
                 });
             }
@@ -111,7 +171,7 @@ impl History {
             
             // Keep redoing operations in the same group
             while let Some(last) = self.redo_stack.back() {
-                if last.group_id != group_id {
+                if group_id.is_none() || last.group_id != group_id {
                     break;
                 }
                 if let Some(entry) = self.redo_stack.pop_back() {
@@ -123,7 +183,7 @@ impl History {
             for operation in operations.iter().rev() {
                 self.undo_stack.push_back(HistoryEntry {
                     operation: operation.clone(),
-                    timestamp: std::time::SystemTime::now(),
+                    timestamp: self.clock.system_time(),
                     group_id,
                 });
             }
@@ -236,6 +296,53 @@ mod tests {
         assert_eq!(history.undo_count(), 3);
     }
 
+    fn insert(position: usize, text: &str) -> TextOperation {
+        TextOperation::Insert {
+            position,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_typing_coalesced_within_window() {
+        let clock = Arc::new(editor_core::MockClock::new());
+        let mut history = History::with_clock(clock.clone());
+        history.set_coalesce_window(Some(Duration::from_millis(500)));
+
+        history.push(insert(0, "a"));
+        clock.advance(Duration::from_millis(200));
+        history.push(insert(1, "b"));
+        clock.advance(Duration::from_millis(500));
+        history.push(insert(2, "c"));
+
+        // "abc" was typed without a long enough pause, so it is undone at once
+        history.undo().unwrap();
+        assert_eq!(history.undo_count(), 0);
+        assert_eq!(history.redo_count(), 3);
+    }
+
+    #[test]
+    fn test_typing_split_after_pause() {
+        let clock = Arc::new(editor_core::MockClock::new());
+        let mut history = History::with_clock(clock.clone());
+        history.set_coalesce_window(Some(Duration::from_millis(500)));
+
+        history.push(insert(0, "a"));
+        clock.advance(Duration::from_millis(200));
+        history.push(insert(1, "b"));
+        clock.advance(Duration::from_millis(501));
+        history.push(insert(2, "c"));
+        // Not where the previous insertion ended
+        history.push(insert(0, "d"));
+
+        history.undo().unwrap();
+        assert_eq!(history.undo_count(), 3);
+        history.undo().unwrap();
+        assert_eq!(history.undo_count(), 2);
+        history.undo().unwrap();
+        assert_eq!(history.undo_count(), 0);
+    }
+
     #[test]
     fn test_history_limit() {
         let mut history = History::new();
@@ -3,16 +3,20 @@
 mod rope;
 mod history;
 mod operations;
-mod markers;
 
-pub use rope::Buffer;
+pub use rope::{Buffer, RopeStats};
 pub use history::{History, HistoryEntry};
-pub use operations::{Operation, TextOperation};
-pub use markers::{Marker, MarkerSet};
+pub use operations::{CompoundBuilder, Operation, TextOperation};
+pub use editor_core::{Marker, MarkerSet};
 
+use editor_core::{ContentKind, MarkerType};
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use anyhow::Result;
+use anyhow::{Context, Result};
+
+/// Name of the marker holding the cursor position
+pub const CURSOR_MARKER: &str = "cursor";
 
 /// A text buffer with undo/redo support and markers
 pub struct TextBuffer {
@@ -39,6 +43,16 @@ pub enum LineEnding {
     Mac,
 }
 
+impl From<editor_core::LineEnding> for LineEnding {
+    fn from(line_ending: editor_core::LineEnding) -> Self {
+        match line_ending {
+            editor_core::LineEnding::Unix => LineEnding::Unix,
+            editor_core::LineEnding::Windows => LineEnding::Windows,
+            editor_core::LineEnding::Mac => LineEnding::Mac,
+        }
+    }
+}
+
 /// Indentation settings
 #[derive(Debug, Clone)]
 pub struct IndentationSettings {
@@ -69,6 +83,28 @@ impl TextBuffer {
         buffer
     }
 
+    /// Loads a file into a new buffer
+    ///
+    /// The text is kept as it is on disk, with its line ending style and
+    /// indentation detected from it, and the cursor marker at the start.
+    /// Fails if the file cannot be read or is not text.
+    pub async fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = tokio::fs::read(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let (kind, text) = ContentKind::decode(&bytes);
+        if kind.is_binary() {
+            anyhow::bail!("{} is not a text file", path.display());
+        }
+
+        let mut buffer = Self::from_text(&text);
+        buffer.line_ending = editor_core::LineEnding::detect(&text).into();
+        buffer.indentation = IndentationSettings::detect(&text);
+        buffer.markers.write().await.set_with_type(CURSOR_MARKER, 0, MarkerType::Cursor);
+        Ok(buffer)
+    }
+
     /// Returns the current text content
     pub async fn text(&self) -> String {
         self.buffer.read().await.text()
@@ -85,6 +121,8 @@ impl TextBuffer {
     }
 
     /// Inserts text at the specified position
+    ///
+    /// Markers at or after `position` move past the inserted text.
     pub async fn insert(&mut self, position: usize, text: &str) -> Result<()> {
         let operation = TextOperation::Insert {
             position,
@@ -94,8 +132,9 @@ impl TextBuffer {
         // Apply the operation
         {
             let mut buffer = self.buffer.write().await;
-            buffer.apply_operation(&operation)?;
+            operation.apply(&mut buffer)?;
         }
+        self.shift_markers(&operation).await;
 
         // Record in history
         self.history.write().await.push(operation);
@@ -104,10 +143,15 @@ impl TextBuffer {
     }
 
     /// Deletes text in the specified range
+    ///
+    /// Markers after the range move back with the text, and markers inside
+    /// it move to `start`.
     pub async fn delete(&mut self, start: usize, end: usize) -> Result<()> {
         let text = {
             let buffer = self.buffer.read().await;
-            buffer.slice(start..end).to_string()
+            buffer
+                .try_slice(start..end)
+                .ok_or_else(|| anyhow::anyhow!("Invalid range {}..{} for buffer of {} bytes", start, end, buffer.len()))?
         };
 
         let operation = TextOperation::Delete {
@@ -119,8 +163,36 @@ impl TextBuffer {
         // Apply the operation
         {
             let mut buffer = self.buffer.write().await;
-            buffer.apply_operation(&operation)?;
+            operation.apply(&mut buffer)?;
         }
+        self.shift_markers(&operation).await;
+
+        // Record in history
+        self.history.write().await.push(operation);
+
+        Ok(())
+    }
+
+    /// Replaces text in the specified range
+    ///
+    /// The old text is read and replaced under one write lock, so readers
+    /// never see the range deleted, and the change is a single undo step.
+    pub async fn replace(&mut self, start: usize, end: usize, new_text: &str) -> Result<()> {
+        let operation = {
+            let mut buffer = self.buffer.write().await;
+            let old_text = buffer
+                .try_slice(start..end)
+                .ok_or_else(|| anyhow::anyhow!("Invalid range {}..{} for buffer of {} bytes", start, end, buffer.len()))?;
+            let operation = TextOperation::Replace {
+                start,
+                end,
+                old_text,
+                new_text: new_text.to_string(),
+            };
+            operation.apply(&mut buffer)?;
+            operation
+        };
+        self.shift_markers(&operation).await;
 
         // Record in history
         self.history.write().await.push(operation);
@@ -131,8 +203,9 @@ impl TextBuffer {
     /// Undoes the last operation
     pub async fn undo(&mut self) -> Result<()> {
         if let Some(operation) = self.history.write().await.undo() {
-            let mut buffer = self.buffer.write().await;
-            buffer.apply_operation(&operation.invert())?;
+            let inverse = operation.invert();
+            inverse.apply(&mut *self.buffer.write().await)?;
+            self.shift_markers(&inverse).await;
         }
         Ok(())
     }
@@ -140,12 +213,17 @@ impl TextBuffer {
     /// Redoes the last undone operation
     pub async fn redo(&mut self) -> Result<()> {
         if let Some(operation) = self.history.write().await.redo() {
-            let mut buffer = self.buffer.write().await;
-            buffer.apply_operation(&operation)?;
+            operation.apply(&mut *self.buffer.write().await)?;
+            self.shift_markers(&operation).await;
         }
         Ok(())
     }
 
+    /// Moves the markers to follow an applied operation
+    async fn shift_markers(&self, operation: &TextOperation) {
+        shift_markers(&mut *self.markers.write().await, operation);
+    }
+
     /// Sets a marker at the specified position
     pub async fn set_marker(&mut self, name: &str, position: usize) {
         self.markers.write().await.set(name, position);
@@ -161,6 +239,16 @@ impl TextBuffer {
         self.markers.write().await.remove(name);
     }
 
+    /// Returns the line ending style
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    /// Returns the indentation settings
+    pub fn indentation(&self) -> &IndentationSettings {
+        &self.indentation
+    }
+
     /// Sets the line ending style
     pub fn set_line_ending(&mut self, line_ending: LineEnding) {
         self.line_ending = line_ending;
@@ -172,12 +260,61 @@ impl TextBuffer {
     }
 }
 
+/// Moves markers to follow an operation, one part at a time
+fn shift_markers(markers: &mut MarkerSet, operation: &TextOperation) {
+    match operation {
+        TextOperation::Insert { position, text } => markers.update_positions(*position, text.len() as isize),
+        TextOperation::Delete { start, end, .. } => markers.update_positions(*start, -((end - start) as isize)),
+        TextOperation::Replace { start, end, new_text, .. } => {
+            // Inserting after the old text first keeps markers at `start` in place
+            markers.update_positions(*end, new_text.len() as isize);
+            markers.update_positions(*start, -((end - start) as isize));
+        }
+        TextOperation::Compound { operations } => {
+            for operation in operations {
+                shift_markers(markers, operation);
+            }
+        }
+    }
+}
+
 impl Default for TextBuffer {
     fn default() -> Self {
         Self::new()
     }
 }
 
+impl IndentationSettings {
+    /// Guesses the settings a text was written with
+    ///
+    /// Tabs are used if more lines are indented with a tab than with a space.
+    /// The tab size is the smallest indent of the space-indented lines,
+    /// between 2 and 8, or the default if there is none.
+    pub fn detect(text: &str) -> Self {
+        let defaults = Self::default();
+        let mut tab_lines = 0;
+        let mut space_lines = 0;
+        let mut smallest: Option<usize> = None;
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            match line.as_bytes()[0] {
+                b'\t' => tab_lines += 1,
+                b' ' => {
+                    space_lines += 1;
+                    let width = line.len() - line.trim_start_matches(' ').len();
+                    smallest = Some(smallest.map_or(width, |smallest| smallest.min(width)));
+                }
+                _ => {}
+            }
+        }
+
+        Self {
+            use_spaces: tab_lines <= space_lines,
+            tab_size: smallest.map_or(defaults.tab_size, |width| width.clamp(2, 8) as u8),
+            ..defaults
+        }
+    }
+}
+
 impl Default for IndentationSettings {
     fn default() -> Self {
         Self {
@@ -215,6 +352,21 @@ mod tests {
         assert_eq!(buffer.text().await, "HelloWorld!");
     }
 
+    #[tokio::test]
+    async fn test_replace() {
+        let mut buffer = TextBuffer::from_text("Hello, World!");
+
+        buffer.replace(7, 12, "Rust").await.unwrap();
+        assert_eq!(buffer.text().await, "Hello, Rust!");
+        assert!(buffer.replace(7, 100, "x").await.is_err());
+
+        // One undo restores the replaced text
+        buffer.undo().await.unwrap();
+        assert_eq!(buffer.text().await, "Hello, World!");
+        buffer.redo().await.unwrap();
+        assert_eq!(buffer.text().await, "Hello, Rust!");
+    }
+
     #[tokio::test]
     async fn test_markers() {
         let mut buffer = TextBuffer::new();
@@ -230,4 +382,61 @@ mod tests {
         buffer.remove_marker("start").await;
         assert_eq!(buffer.get_marker("start").await, None);
     }
+
+    #[tokio::test]
+    async fn test_from_file_crlf() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("main.c");
+        std::fs::write(&path, "int main() {\r\n  if (1) {\r\n    return 0;\r\n  }\r\n}\r\n").unwrap();
+
+        let buffer = TextBuffer::from_file(&path).await.unwrap();
+        assert_eq!(buffer.text().await, "int main() {\r\n  if (1) {\r\n    return 0;\r\n  }\r\n}\r\n");
+        assert_eq!(buffer.line_ending(), LineEnding::Windows);
+        assert!(buffer.indentation().use_spaces);
+        assert_eq!(buffer.indentation().tab_size, 2);
+        assert_eq!(buffer.get_marker(CURSOR_MARKER).await, Some(0));
+
+        std::fs::write(&path, "fn main() {\n\tlet a = 1;\n}\n").unwrap();
+        let buffer = TextBuffer::from_file(&path).await.unwrap();
+        assert_eq!(buffer.line_ending(), LineEnding::Unix);
+        assert!(!buffer.indentation().use_spaces);
+
+        assert!(TextBuffer::from_file(dir.path().join("missing.c")).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_markers_follow_insert() {
+        let mut buffer = TextBuffer::from_text("Hello, World!");
+        buffer.set_marker("cursor", 7).await;
+        buffer.set_marker("start", 0).await;
+
+        buffer.insert(5, " there").await.unwrap();
+        assert_eq!(buffer.get_marker("cursor").await, Some(13));
+        assert_eq!(buffer.get_marker("start").await, Some(0));
+        assert_eq!(&buffer.text().await[13..], "World!");
+
+        // Inserting at a marker moves it past the text
+        buffer.insert(13, "big ").await.unwrap();
+        assert_eq!(buffer.get_marker("cursor").await, Some(17));
+
+        buffer.undo().await.unwrap();
+        assert_eq!(buffer.get_marker("cursor").await, Some(13));
+    }
+
+    #[tokio::test]
+    async fn test_markers_clamp_on_delete() {
+        let mut buffer = TextBuffer::from_text("Hello, World!");
+        buffer.set_marker("inside", 9).await;
+        buffer.set_marker("after", 12).await;
+
+        buffer.delete(5, 11).await.unwrap();
+        assert_eq!(buffer.text().await, "Hellod!");
+        assert_eq!(buffer.get_marker("inside").await, Some(5));
+        assert_eq!(buffer.get_marker("after").await, Some(6));
+
+        // Replacing keeps markers after the range next to the same text
+        buffer.replace(0, 5, "Y").await.unwrap();
+        assert_eq!(buffer.text().await, "Yd!");
+        assert_eq!(buffer.get_marker("after").await, Some(2));
+    }
 }
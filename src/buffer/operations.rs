@@ -1,6 +1,7 @@
 //! Text operations implementation
 
 use serde::{Serialize, Deserialize};
+use std::ops::Range;
 
 /// A text operation that can be performed on a buffer
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -74,19 +75,107 @@ impl TextOperation {
             (
                 TextOperation::Delete { start: s1, end: e1, .. },
                 TextOperation::Delete { start: s2, end: e2, .. }
-            ) => e1 == *s2 || s1 == *e2,
+            ) => e1 == s2 || s1 == e2,
 
             // Consecutive replacements at the same position
             (
                 TextOperation::Replace { start: s1, end: e1, .. },
                 TextOperation::Replace { start: s2, end: e2, .. }
-            ) => e1 == *s2,
+            ) => e1 == s2,
 
             _ => false,
         }
     }
 }
 
+/// Builds a compound operation from edits made against the original text
+///
+/// Edits may be added in any order, with offsets into the text as it was
+/// before any of them. `build` shifts each edit past the ones applied before
+/// it, so the result can be applied to the original text directly and its
+/// `invert` restores it. Edits that change nothing are dropped.
+#[derive(Debug, Clone)]
+pub struct CompoundBuilder<'a> {
+    /// Text the edits refer to
+    original: &'a str,
+    /// Edits as a range of the original text and its replacement
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl<'a> CompoundBuilder<'a> {
+    /// Creates a builder for edits against `original`
+    pub fn new(original: &'a str) -> Self {
+        Self {
+            original,
+            edits: Vec::new(),
+        }
+    }
+
+    /// Inserts text at a position of the original text
+    pub fn insert(self, position: usize, text: impl Into<String>) -> Self {
+        self.replace(position..position, text)
+    }
+
+    /// Deletes a range of the original text
+    pub fn delete(self, range: Range<usize>) -> Self {
+        self.replace(range, String::new())
+    }
+
+    /// Replaces a range of the original text
+    pub fn replace(mut self, range: Range<usize>, text: impl Into<String>) -> Self {
+        self.edits.push((range, text.into()));
+        self
+    }
+
+    /// Returns the compound operation
+    ///
+    /// Insertions at the same position keep the order they were added in.
+    /// Fails if a range is out of bounds, not on a character boundary or
+    /// overlaps another edit.
+    pub fn build(self) -> anyhow::Result<TextOperation> {
+        let mut edits = self.edits;
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+
+        let mut operations = Vec::new();
+        let mut previous_end = 0;
+        // Bytes added so far by the edits before the current one
+        let mut shift: isize = 0;
+        for (range, text) in edits {
+            let old_text = self.original.get(range.clone())
+                .ok_or_else(|| anyhow::anyhow!("Invalid edit range {:?}", range))?;
+            if range.start < previous_end {
+                anyhow::bail!("Edit at {:?} overlaps a previous edit", range);
+            }
+            previous_end = range.end;
+
+            if old_text == text {
+                continue;
+            }
+
+            let start = range.start.checked_add_signed(shift)
+                .ok_or_else(|| anyhow::anyhow!("Invalid edit range {:?}", range))?;
+            shift += text.len() as isize - old_text.len() as isize;
+
+            operations.push(match (old_text.is_empty(), text.is_empty()) {
+                (true, _) => TextOperation::Insert { position: start, text },
+                (false, true) => TextOperation::Delete {
+                    start,
+                    end: start + old_text.len(),
+                    text: old_text.to_string(),
+                },
+                (false, false) => TextOperation::Replace {
+                    start,
+                    end: start + old_text.len(),
+                    old_text: old_text.to_string(),
+                    new_text: text,
+                },
+            });
+        }
+
+        Ok(TextOperation::Compound { operations })
+    }
+}
+
 impl Operation for TextOperation {
     fn apply(&self, buffer: &mut super::rope::Buffer) -> anyhow::Result<()> {
         match self {
@@ -99,8 +188,7 @@ impl Operation for TextOperation {
                 Ok(())
             }
             TextOperation::Replace { start, end, new_text, .. } => {
-                buffer.delete(*start..*end);
-                buffer.insert(*start, new_text);
+                buffer.replace(*start..*end, new_text);
                 Ok(())
             }
             TextOperation::Compound { operations } => {
@@ -155,10 +243,15 @@ impl Operation for TextOperation {
             // Combine adjacent insertions
             (
                 TextOperation::Insert { position: pos1, text: text1 },
-                TextOperation::Insert { text: text2, .. }
+                TextOperation::Insert { position: pos2, text: text2 }
             ) => {
+                // The second text goes where it was inserted into the first
+                let offset = pos2 - pos1;
+                if !text1.is_char_boundary(offset) {
+                    return None;
+                }
                 let mut combined_text = text1.clone();
-                combined_text.push_str(text2);
+                combined_text.insert_str(offset, text2);
                 Some(TextOperation::Insert {
                     position: *pos1,
                     text: combined_text,
@@ -293,6 +386,47 @@ mod tests {
         assert_eq!(buffer.text(), "");
     }
 
+    #[test]
+    fn test_compound_builder() {
+        let original = "let a = 1;\nlet b = 2;\nlet c = 3;\n";
+        let mut buffer = Buffer::from_text(original);
+
+        // Edits are given out of order against the original offsets
+        let op = CompoundBuilder::new(original)
+            .replace(30..31, "three")
+            .insert(0, "// vars\n")
+            .delete(11..22)
+            .replace(4..5, "alpha")
+            .insert(11, "")
+            .build()
+            .unwrap();
+
+        op.apply(&mut buffer).unwrap();
+        assert_eq!(buffer.text(), "// vars\nlet alpha = 1;\nlet c = three;\n");
+
+        op.invert().apply(&mut buffer).unwrap();
+        assert_eq!(buffer.text(), original);
+
+        // The no-op insertion was dropped
+        match op {
+            TextOperation::Compound { operations } => assert_eq!(operations.len(), 4),
+            _ => panic!("Wrong operation type"),
+        }
+    }
+
+    #[test]
+    fn test_compound_builder_rejects_overlaps() {
+        let original = "hello world";
+        assert!(CompoundBuilder::new(original).delete(0..5).replace(3..7, "x").build().is_err());
+        assert!(CompoundBuilder::new(original).delete(5..20).build().is_err());
+
+        // Insertions at one position keep their order
+        let op = CompoundBuilder::new(original).insert(5, "1").insert(5, "2").build().unwrap();
+        let mut buffer = Buffer::from_text(original);
+        op.apply(&mut buffer).unwrap();
+        assert_eq!(buffer.text(), "hello12 world");
+    }
+
     #[test]
     fn test_operation_combining() {
         let op1 = TextOperation::Insert {
@@ -31,6 +31,21 @@ enum Node {
     },
 }
 
+/// Shape of a rope tree, for diagnostics and tree health checks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RopeStats {
+    /// Number of nodes, leaves included
+    pub node_count: usize,
+    /// Number of leaf nodes
+    pub leaf_count: usize,
+    /// Number of levels, 1 for a rope that is a single leaf
+    pub height: usize,
+    /// Length in bytes of the longest leaf
+    pub max_leaf_len: usize,
+    /// Total length in bytes
+    pub byte_len: usize,
+}
+
 /// Rope data structure for text storage
 #[derive(Clone)]
 pub struct Buffer {
@@ -74,6 +89,44 @@ impl Node {
         }
     }
 
+    /// Returns the largest char boundary at or before `offset`
+    fn floor_char_boundary(&self, offset: usize) -> usize {
+        match self {
+            Node::Leaf { text, .. } => {
+                let mut offset = min(offset, text.len());
+                while !text.is_char_boundary(offset) {
+                    offset -= 1;
+                }
+                offset
+            }
+            Node::Internal { left, right, .. } => {
+                let left_len = left.len();
+                if offset <= left_len {
+                    left.floor_char_boundary(offset)
+                } else {
+                    left_len + right.floor_char_boundary(offset - left_len)
+                }
+            }
+        }
+    }
+
+    /// Adds this subtree, found at `depth` (1 for the root), to `stats`
+    fn collect_stats(&self, depth: usize, stats: &mut RopeStats) {
+        stats.node_count += 1;
+        stats.height = max(stats.height, depth);
+        match self {
+            Node::Leaf { len, .. } => {
+                stats.leaf_count += 1;
+                stats.max_leaf_len = max(stats.max_leaf_len, *len);
+                stats.byte_len += len;
+            }
+            Node::Internal { left, right, .. } => {
+                left.collect_stats(depth + 1, stats);
+                right.collect_stats(depth + 1, stats);
+            }
+        }
+    }
+
     /// Splits the node at the given offset
     fn split(&self, offset: usize) -> (Node, Node) {
         match self {
@@ -94,6 +147,31 @@ impl Node {
         }
     }
 
+    /// Appends text to the rightmost leaf if it stays within `CHUNK_SIZE`,
+    /// returning false if there is no room
+    fn append_in_place(&mut self, appended: &str) -> bool {
+        let added_lines = appended.matches('\n').count();
+        match self {
+            Node::Leaf { text, len, lines } => {
+                if *len + appended.len() > CHUNK_SIZE {
+                    return false;
+                }
+                text.push_str(appended);
+                *len += appended.len();
+                *lines += added_lines;
+                true
+            }
+            Node::Internal { right, len, lines, .. } => {
+                if !right.append_in_place(appended) {
+                    return false;
+                }
+                *len += appended.len();
+                *lines += added_lines;
+                true
+            }
+        }
+    }
+
     /// Concatenates two nodes
     fn concat(left: Node, right: Node) -> Node {
         match (left, right) {
@@ -147,12 +225,30 @@ impl Buffer {
     }
 
     /// Returns a slice of the text
+    ///
+    /// The range is clamped to the buffer, an inverted range is empty and
+    /// offsets inside a character move back to its start, so this never
+    /// panics. Use `try_slice` to reject such ranges instead.
     pub fn slice(&self, range: Range<usize>) -> String {
-        let mut result = String::new();
-        self.slice_into(&self.root, range, &mut result);
+        let end = self.root.floor_char_boundary(range.end);
+        let start = self.root.floor_char_boundary(min(range.start, end));
+        let mut result = String::with_capacity(end - start);
+        self.slice_into(&self.root, start..end, &mut result);
         result
     }
 
+    /// Returns a slice of the text, or `None` if the range is inverted, ends
+    /// past the buffer or splits a character
+    pub fn try_slice(&self, range: Range<usize>) -> Option<String> {
+        let on_boundary = |offset| self.root.floor_char_boundary(offset) == offset;
+        if range.start > range.end || range.end > self.len() || !on_boundary(range.start) || !on_boundary(range.end) {
+            return None;
+        }
+        let mut result = String::with_capacity(range.len());
+        self.slice_into(&self.root, range, &mut result);
+        Some(result)
+    }
+
     /// Slices text into the given string
     fn slice_into(&self, node: &Node, range: Range<usize>, result: &mut String) {
         match node {
@@ -183,6 +279,12 @@ impl Buffer {
             return;
         }
 
+        // Appending, e.g. to a log or output panel, fills the last leaf
+        // without splitting and rebuilding the tree
+        if position == self.len() && self.root.append_in_place(text) {
+            return;
+        }
+
         let (left, right) = self.root.split(position);
         let middle = Node::leaf(text.to_string());
         self.root = Node::concat(Node::concat(left, middle), right);
@@ -199,6 +301,33 @@ impl Buffer {
         self.root = Node::concat(left, right);
     }
 
+    /// Replaces the text in the specified range
+    ///
+    /// The rope is split and joined once, rather than once for the deletion
+    /// and again for the insertion. Out of range positions are ignored, as
+    /// by `insert`.
+    pub fn replace(&mut self, range: Range<usize>, new_text: &str) {
+        if range.start > range.end || range.end > self.len() {
+            return;
+        }
+
+        let (left, temp) = self.root.split(range.start);
+        let (_, right) = temp.split(range.end - range.start);
+        let left = if new_text.is_empty() {
+            left
+        } else {
+            Node::concat(left, Node::leaf(new_text.to_string()))
+        };
+        self.root = Node::concat(left, right);
+    }
+
+    /// Returns the shape of the rope tree, computed in a single walk
+    pub fn stats(&self) -> RopeStats {
+        let mut stats = RopeStats::default();
+        self.root.collect_stats(1, &mut stats);
+        stats
+    }
+
     /// Returns an iterator over the lines
     pub fn lines_iter(&self) -> LinesIterator {
         LinesIterator {
@@ -264,7 +393,7 @@ mod tests {
 
         buffer.insert(5, ", World!");
         assert_eq!(buffer.text(), "Hello, World!");
-        assert_eq!(buffer.len(), 12);
+        assert_eq!(buffer.len(), 13);
 
         // Test delete
         buffer.delete(5..7);
@@ -275,6 +404,31 @@ mod tests {
         assert_eq!(buffer.slice(5..10), "World");
     }
 
+    #[test]
+    fn test_try_slice() {
+        let mut buffer = Buffer::from_text("héllo ");
+        buffer.insert(7, &"x".repeat(CHUNK_SIZE));
+        let len = buffer.len();
+
+        // Valid ranges, including ones spanning leaves
+        assert_eq!(buffer.try_slice(0..3).as_deref(), Some("hé"));
+        assert_eq!(buffer.try_slice(4..9).as_deref(), Some("lo xx"));
+        assert_eq!(buffer.try_slice(len..len).as_deref(), Some(""));
+
+        // Inverted ranges
+        let (start, end) = (5, 2);
+        assert_eq!(buffer.try_slice(start..end), None);
+        assert_eq!(buffer.slice(start..end), "");
+
+        // Past the end, or splitting a character
+        assert_eq!(buffer.try_slice(len - 1..len + 1), None);
+        assert_eq!(buffer.try_slice(len + 1..len + 2), None);
+        assert_eq!(buffer.try_slice(0..2), None);
+        assert_eq!(buffer.slice(len - 1..len + 1), "x");
+        assert_eq!(buffer.slice(len + 1..len + 2), "");
+        assert_eq!(buffer.slice(0..2), "h");
+    }
+
     #[test]
     fn test_line_operations() {
         let mut buffer = Buffer::new();
@@ -297,4 +451,55 @@ mod tests {
         buffer.delete(CHUNK_SIZE/2..CHUNK_SIZE*3/2);
         assert_eq!(buffer.len(), CHUNK_SIZE);
     }
+
+    #[test]
+    fn test_stats() {
+        let mut buffer = Buffer::from_text("hello");
+        assert_eq!(buffer.stats(), RopeStats {
+            node_count: 1,
+            leaf_count: 1,
+            height: 1,
+            max_leaf_len: 5,
+            byte_len: 5,
+        });
+
+        // Appending a full chunk splits off an empty leaf at the end and keeps
+        // the chunk in its own leaf: ((hello, chunk), "")
+        buffer.insert(5, &"x".repeat(CHUNK_SIZE));
+        assert_eq!(buffer.stats(), RopeStats {
+            node_count: 5,
+            leaf_count: 3,
+            height: 3,
+            max_leaf_len: CHUNK_SIZE,
+            byte_len: CHUNK_SIZE + 5,
+        });
+        assert_eq!(buffer.stats().byte_len, buffer.len());
+    }
+
+    #[test]
+    fn test_sequential_appends() {
+        let mut buffer = Buffer::new();
+        let mut expected = String::new();
+        for n in 0..5000 {
+            let line = format!("[{}] request handled\n", n);
+            buffer.insert(buffer.len(), &line);
+            expected.push_str(&line);
+        }
+        assert_eq!(buffer.text(), expected);
+        assert_eq!(buffer.len(), expected.len());
+        assert_eq!(buffer.lines(), 5000);
+
+        // Appends fill the last leaf instead of adding nodes for every line
+        let stats = buffer.stats();
+        assert_eq!(stats.byte_len, expected.len());
+        assert!(stats.max_leaf_len <= CHUNK_SIZE);
+        assert!(stats.leaf_count <= 2 * (expected.len() / CHUNK_SIZE + 1), "{:?}", stats);
+
+        // Appending to a single small leaf keeps it a single leaf
+        let mut buffer = Buffer::from_text("tail");
+        buffer.insert(4, " -f\n");
+        assert_eq!(buffer.text(), "tail -f\n");
+        assert_eq!(buffer.stats().node_count, 1);
+        assert_eq!(buffer.lines(), 1);
+    }
 }
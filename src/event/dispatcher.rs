@@ -1,6 +1,6 @@
 //! Event dispatcher implementation
 
-use super::{EditorEvent, EventHandler, HandlerId};
+use super::{Event, EventHandler, HandlerId};
 use std::collections::HashMap;
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -42,10 +42,10 @@ impl EventDispatcher {
     }
 
     /// Dispatches an event to all registered handlers
-    pub async fn dispatch(&self, event: EditorEvent) {
+    pub async fn dispatch(&self, event: Event) {
         let handlers = self.handlers.read().await;
         for handler in handlers.values() {
-            handler.handle_event(event.clone()).await;
+            handler.handle(event.clone()).await;
         }
     }
 }
@@ -59,7 +59,7 @@ impl Default for EventDispatcher {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::event::{BufferEvent, EditorEvent};
+    use crate::event::BufferEvent;
     use std::sync::atomic::{AtomicUsize, Ordering};
 
     struct TestHandler {
@@ -68,7 +68,7 @@ mod tests {
 
     #[async_trait::async_trait]
     impl EventHandler for TestHandler {
-        async fn handle_event(&self, _event: EditorEvent) {
+        async fn handle(&self, _event: Event) {
             self.counter.fetch_add(1, Ordering::SeqCst);
         }
     }
@@ -84,14 +84,14 @@ mod tests {
 
         let id = dispatcher.register(Box::new(handler)).await;
 
-        let event = EditorEvent::Buffer(BufferEvent::Modified { modified: true });
+        let event = Event::Buffer(BufferEvent::Modified { dirty: true });
         dispatcher.dispatch(event).await;
 
         assert_eq!(counter.load(Ordering::SeqCst), 1);
 
         dispatcher.unregister(id).await;
 
-        let event = EditorEvent::Buffer(BufferEvent::Modified { modified: false });
+        let event = Event::Buffer(BufferEvent::Modified { dirty: false });
         dispatcher.dispatch(event).await;
 
         assert_eq!(counter.load(Ordering::SeqCst), 1);
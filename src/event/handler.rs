@@ -1,24 +1,14 @@
 //! Event handler trait and implementations
 
-use super::EditorEvent;
+use super::{Event, EventHandler};
 use async_trait::async_trait;
 
-/// Handler ID type
-pub type HandlerId = u64;
-
-/// Event handler trait
-#[async_trait]
-pub trait EventHandler: Send + Sync {
-    /// Handles an event
-    async fn handle_event(&self, event: EditorEvent);
-}
-
 /// Event handler that logs events
 pub struct LoggingHandler;
 
 #[async_trait]
 impl EventHandler for LoggingHandler {
-    async fn handle_event(&self, event: EditorEvent) {
+    async fn handle(&self, event: Event) {
         log::info!("Event: {:?}", event);
     }
 }
@@ -28,14 +18,14 @@ pub struct FilteringHandler<H> {
     /// Inner handler
     inner: H,
     /// Filter function
-    filter: Box<dyn Fn(&EditorEvent) -> bool + Send + Sync>,
+    filter: Box<dyn Fn(&Event) -> bool + Send + Sync>,
 }
 
 impl<H: EventHandler> FilteringHandler<H> {
     /// Creates a new filtering handler
     pub fn new<F>(inner: H, filter: F) -> Self
     where
-        F: Fn(&EditorEvent) -> bool + Send + Sync + 'static,
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
     {
         Self {
             inner,
@@ -46,9 +36,9 @@ impl<H: EventHandler> FilteringHandler<H> {
 
 #[async_trait]
 impl<H: EventHandler> EventHandler for FilteringHandler<H> {
-    async fn handle_event(&self, event: EditorEvent) {
+    async fn handle(&self, event: Event) {
         if (self.filter)(&event) {
-            self.inner.handle_event(event).await;
+            self.inner.handle(event).await;
         }
     }
 }
@@ -56,7 +46,7 @@ impl<H: EventHandler> EventHandler for FilteringHandler<H> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::event::{BufferEvent, EditorEvent};
+    use crate::event::{BufferEvent, UiEvent};
     use std::sync::atomic::{AtomicUsize, Ordering};
     use std::sync::Arc;
 
@@ -66,7 +56,7 @@ mod tests {
 
     #[async_trait]
     impl EventHandler for CountingHandler {
-        async fn handle_event(&self, _event: EditorEvent) {
+        async fn handle(&self, _event: Event) {
             self.counter.fetch_add(1, Ordering::SeqCst);
         }
     }
@@ -79,18 +69,18 @@ mod tests {
         };
 
         let filtering_handler = FilteringHandler::new(counting_handler, |event| {
-            matches!(event, EditorEvent::Buffer(_))
+            matches!(event, Event::Buffer(_))
         });
 
         // Buffer event should be handled
         filtering_handler
-            .handle_event(EditorEvent::Buffer(BufferEvent::Modified { modified: true }))
+            .handle(Event::Buffer(BufferEvent::Modified { dirty: true }))
             .await;
         assert_eq!(counter.load(Ordering::SeqCst), 1);
 
         // UI event should be filtered out
         filtering_handler
-            .handle_event(EditorEvent::Ui(crate::event::UiEvent::ThemeChange {
+            .handle(Event::Ui(UiEvent::ThemeChange {
                 theme: "dark".to_string(),
             }))
             .await;
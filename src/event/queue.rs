@@ -1,14 +1,14 @@
 //! Event queue implementation
 
-use super::EditorEvent;
+use super::Event;
 use tokio::sync::mpsc;
 
 /// Queue for buffering events
 pub struct EventQueue {
     /// Sender for the event channel
-    sender: mpsc::Sender<EditorEvent>,
+    sender: mpsc::Sender<Event>,
     /// Receiver for the event channel
-    receiver: mpsc::Receiver<EditorEvent>,
+    receiver: mpsc::Receiver<Event>,
 }
 
 impl EventQueue {
@@ -19,17 +19,17 @@ impl EventQueue {
     }
 
     /// Sends an event to the queue
-    pub async fn send(&self, event: EditorEvent) -> Result<(), mpsc::error::SendError<EditorEvent>> {
+    pub async fn send(&self, event: Event) -> Result<(), mpsc::error::SendError<Event>> {
         self.sender.send(event).await
     }
 
     /// Receives an event from the queue
-    pub async fn receive(&mut self) -> Option<EditorEvent> {
+    pub async fn receive(&mut self) -> Option<Event> {
         self.receiver.recv().await
     }
 
     /// Returns a sender for the queue
-    pub fn sender(&self) -> mpsc::Sender<EditorEvent> {
+    pub fn sender(&self) -> mpsc::Sender<Event> {
         self.sender.clone()
     }
 }
@@ -50,13 +50,13 @@ mod tests {
         let mut queue = EventQueue::new(10);
         let sender = queue.sender();
 
-        let event = EditorEvent::Buffer(BufferEvent::Modified { modified: true });
+        let event = Event::Buffer(BufferEvent::Modified { dirty: true });
         sender.send(event.clone()).await.unwrap();
 
         let received = queue.receive().await.unwrap();
         match received {
-            EditorEvent::Buffer(BufferEvent::Modified { modified }) => {
-                assert!(modified);
+            Event::Buffer(BufferEvent::Modified { dirty }) => {
+                assert!(dirty);
             }
             _ => panic!("Wrong event type"),
         }
@@ -68,8 +68,8 @@ mod tests {
         let sender = queue.sender();
 
         let events = vec![
-            EditorEvent::Buffer(BufferEvent::Modified { modified: true }),
-            EditorEvent::Buffer(BufferEvent::Modified { modified: false }),
+            Event::Buffer(BufferEvent::Modified { dirty: true }),
+            Event::Buffer(BufferEvent::Modified { dirty: false }),
         ];
 
         for event in events.clone() {
@@ -80,7 +80,7 @@ mod tests {
             let received = queue.receive().await.unwrap();
             assert!(matches!(
                 received,
-                EditorEvent::Buffer(BufferEvent::Modified { .. })
+                Event::Buffer(BufferEvent::Modified { .. })
             ));
         }
     }
@@ -1,15 +1,7 @@
 //! Keybinding implementation
 
 use serde::{Serialize, Deserialize};
-use super::{KeyCode, KeyEvent};
-
-/// Key modifier flags
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum Modifier {
-    Ctrl,
-    Alt,
-    Shift,
-}
+use super::{KeyCode, KeyEvent, Modifier};
 
 /// Pattern for matching key events
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -3,7 +3,7 @@
 use std::collections::HashMap;
 use super::{Keybinding, KeyEvent, KeyCode, KeyPattern, Modifier};
 
-/// Manages keybindings and their associated commands
+/// Manages keybindings and the ids of the actions they invoke
 pub struct KeyMap {
     /// Registered keybindings
     bindings: HashMap<Keybinding, String>,
@@ -29,62 +29,79 @@ impl KeyMap {
         // File operations
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("s"),
+                KeyCode::Char('s'),
                 vec![Modifier::Ctrl],
             )]),
-            "save".to_string(),
+            editor_ui::SAVE_FILE.to_string(),
         );
 
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("o"),
+                KeyCode::Char('o'),
                 vec![Modifier::Ctrl],
             )]),
-            "open".to_string(),
+            editor_ui::OPEN_FILE.to_string(),
         );
 
         // Edit operations
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("z"),
+                KeyCode::Char('z'),
                 vec![Modifier::Ctrl],
             )]),
-            "undo".to_string(),
+            editor_ui::UNDO.to_string(),
         );
 
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("z"),
+                KeyCode::Char('z'),
                 vec![Modifier::Ctrl, Modifier::Shift],
             )]),
-            "redo".to_string(),
+            editor_ui::REDO.to_string(),
+        );
+
+        // Selection operations
+        self.add_binding(
+            Keybinding::new(vec![KeyPattern::new(
+                KeyCode::Right,
+                vec![Modifier::Alt, Modifier::Shift],
+            )]),
+            editor_ui::EXPAND_SELECTION.to_string(),
+        );
+
+        self.add_binding(
+            Keybinding::new(vec![KeyPattern::new(
+                KeyCode::Left,
+                vec![Modifier::Alt, Modifier::Shift],
+            )]),
+            editor_ui::SHRINK_SELECTION.to_string(),
         );
 
         // Search operations
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("f"),
+                KeyCode::Char('f'),
                 vec![Modifier::Ctrl],
             )]),
-            "find".to_string(),
+            editor_ui::FIND.to_string(),
         );
 
         // Navigation
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("g"),
+                KeyCode::Char('g'),
                 vec![Modifier::Ctrl],
             )]),
-            "goto_line".to_string(),
+            editor_ui::GO_TO_LINE.to_string(),
         );
 
         // UI operations
         self.add_binding(
             Keybinding::new(vec![KeyPattern::new(
-                KeyCode::Char("p"),
+                KeyCode::Char('p'),
                 vec![Modifier::Ctrl],
             )]),
-            "command_palette".to_string(),
+            editor_ui::COMMAND_PALETTE.to_string(),
         );
     }
 
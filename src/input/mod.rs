@@ -1,11 +1,13 @@
 //! Input handling module
+//!
+//! Key sequences are bound to action ids; the actions themselves live in
+//! the editor's `ActionRegistry`, shared with the menus and command palette.
 
 mod keybinding;
-mod commands;
 mod keymap;
 
-pub use keybinding::{Keybinding, KeyPattern, Modifier};
-pub use commands::{Command, CommandRegistry};
+pub use keybinding::{Keybinding, KeyPattern};
+pub use editor_core::{KeyCode, Modifier};
 pub use keymap::KeyMap;
 
 use std::collections::HashMap;
@@ -20,34 +22,10 @@ pub struct KeyEvent {
     pub modifiers: Vec<Modifier>,
 }
 
-/// Represents a key code
-#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum KeyCode {
-    Char(char),
-    Function(u8),
-    Left,
-    Right,
-    Up,
-    Down,
-    Home,
-    End,
-    PageUp,
-    PageDown,
-    Tab,
-    BackTab,
-    Delete,
-    Insert,
-    Backspace,
-    Enter,
-    Esc,
-}
-
 /// Input handler for the editor
 pub struct InputHandler {
     /// Keybinding registry
     keymap: KeyMap,
-    /// Command registry
-    commands: CommandRegistry,
     /// Current key sequence
     sequence: Vec<KeyEvent>,
 }
@@ -57,32 +35,24 @@ impl InputHandler {
     pub fn new() -> Self {
         Self {
             keymap: KeyMap::new(),
-            commands: CommandRegistry::new(),
             sequence: Vec::new(),
         }
     }
 
-    /// Registers a keybinding
-    pub fn register_keybinding(&mut self, binding: Keybinding, command: String) {
-        self.keymap.add_binding(binding, command);
-    }
-
-    /// Registers a command
-    pub fn register_command<F>(&mut self, name: &str, description: &str, handler: F)
-    where
-        F: Fn() -> anyhow::Result<()> + Send + Sync + "static,
-    {
-        self.commands.register(name, description, handler);
+    /// Binds a key sequence to an action id
+    pub fn register_keybinding(&mut self, binding: Keybinding, action: String) {
+        self.keymap.add_binding(binding, action);
     }
 
-    /// Handles a key event
-    pub fn handle_key(&mut self, event: KeyEvent) -> Option<Command> {
-        self.sequence.push(event.clone());
+    /// Handles a key event, returning the id of the action to invoke once
+    /// the sequence matches a binding
+    pub fn handle_key(&mut self, event: KeyEvent) -> Option<String> {
+        self.sequence.push(event);
 
         // Check if the current sequence matches any keybinding
-        if let Some(command) = self.keymap.get_command(&self.sequence) {
+        if let Some(action) = self.keymap.get_command(&self.sequence) {
             self.sequence.clear();
-            self.commands.get(&command)
+            Some(action)
         } else {
             // Check if the sequence could potentially match a binding
             if !self.keymap.is_prefix(&self.sequence) {
@@ -107,53 +77,12 @@ impl InputHandler {
         self.keymap.bindings()
     }
 
-    /// Returns all registered commands
-    pub fn commands(&self) -> HashMap<String, String> {
-        self.commands.list()
-    }
-
-    /// Creates a new input handler with default bindings and commands
+    /// Creates a new input handler with the default keybindings
     pub fn with_defaults() -> Self {
-        let mut handler = Self::new();
-        handler.register_default_commands();
-        handler
-    }
-
-    /// Registers default commands
-    fn register_default_commands(&mut self) {
-        // File operations
-        self.register_command("save", "Save current file", || {
-            // TODO: Implement save functionality
-            Ok(())
-        });
-
-        self.register_command("open", "Open file", || {
-            // TODO: Implement open functionality
-            Ok(())
-        });
-
-        // Edit operations
-        self.register_command("undo", "Undo last action", || {
-            // TODO: Implement undo functionality
-            Ok(())
-        });
-
-        self.register_command("redo", "Redo last action", || {
-            // TODO: Implement redo functionality
-            Ok(())
-        });
-
-        // Search operations
-        self.register_command("find", "Find text", || {
-            // TODO: Implement find functionality
-            Ok(())
-        });
-
-        // UI operations
-        self.register_command("command_palette", "Open command palette", || {
-            // TODO: Implement command palette functionality
-            Ok(())
-        });
+        Self {
+            keymap: KeyMap::with_defaults(),
+            sequence: Vec::new(),
+        }
     }
 }
 
@@ -171,37 +100,53 @@ mod tests {
     fn test_key_handling() {
         let mut handler = InputHandler::new();
         
-        // Register a test command
-        handler.register_command("test", "Test command", || Ok(()));
-        
         // Register a keybinding
         let binding = Keybinding::new(vec![
-            KeyPattern::new(KeyCode::Char("t"), vec![Modifier::Ctrl]),
+            KeyPattern::new(KeyCode::Char('t'), vec![Modifier::Ctrl]),
         ]);
         handler.register_keybinding(binding, "test".to_string());
 
         // Test key event handling
         let event = KeyEvent {
-            code: KeyCode::Char("t"),
+            code: KeyCode::Char('t'),
             modifiers: vec![Modifier::Ctrl],
         };
         
-        let command = handler.handle_key(event);
-        assert!(command.is_some());
+        let action = handler.handle_key(event);
+        assert_eq!(action.as_deref(), Some("test"));
         
         // Test sequence clearing
         assert!(handler.current_sequence().is_empty());
     }
 
     #[test]
-    fn test_default_commands() {
+    fn test_default_bindings() {
         let handler = InputHandler::with_defaults();
-        let commands = handler.commands();
+        let bindings = handler.keybindings();
         
-        // Check for some default commands
-        assert!(commands.contains_key("save"));
-        assert!(commands.contains_key("open"));
-        assert!(commands.contains_key("undo"));
-        assert!(commands.contains_key("redo"));
+        // File shortcuts invoke the editor's file actions
+        assert!(bindings.contains_key(editor_ui::SAVE_FILE));
+        assert!(bindings.contains_key(editor_ui::OPEN_FILE));
+        assert!(bindings.contains_key(editor_ui::UNDO));
+        assert!(bindings.contains_key(editor_ui::REDO));
+    }
+
+    #[test]
+    fn test_default_bindings_resolve_to_actions() {
+        let editor = editor_core::Editor::new();
+        let actions = editor.actions().clone();
+        // The view registers its actions with the editor it is given
+        let _app = editor_ui::EditorApp::new(
+            editor,
+            editor_ui::FontSettings::default(),
+            editor_ui::WrapMode::None,
+            editor_ui::RenderWhitespace::default(),
+            Vec::new(),
+            editor_ui::CursorSettings::default(),
+        );
+
+        for id in InputHandler::with_defaults().keybindings().keys() {
+            assert!(actions.get(id).is_some(), "no action registered for {}", id);
+        }
     }
 }
@@ -7,7 +7,8 @@ mod event;
 
 use anyhow::Result;
 use clap::Parser;
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 /// Command line arguments
 #[derive(Parser, Debug)]
@@ -21,7 +22,7 @@ struct Args {
     #[arg(short = 'R', long)]
     readonly: bool,
 
-    /// Configuration file
+    /// Configuration directory, overriding RUST_EDITOR_CONFIG_DIR
     #[arg(short, long)]
     config: Option<PathBuf>,
 
@@ -47,95 +48,280 @@ async fn main() -> Result<()> {
     log::info!("Starting Rust Editor...");
 
     // Initialize state
-    let state = state::EditorState::new()?;
+    let state = init_state(&args).await?;
 
-    // Load configuration
-    if let Some(config_path) = args.config {
-        log::info!("Loading configuration from: {}", config_path.display());
-        // TODO: Load custom configuration
-    }
-
-    // Set theme if specified
-    if let Some(theme_name) = args.theme {
-        log::info!("Setting theme: {}", theme_name);
-        let mut prefs = state.preferences().write().await;
-        // TODO: Load and set theme
-    }
-
-    // Create editor instance
+    // Create editor instance, using the font from the preferences
     let mut editor = Editor::new(state);
+    let (font, size, wrap_mode, render_whitespace, rulers, cursor, highlight_debounce, scroll_past_end, auto_save, auto_reload) = {
+        let prefs = editor.state().preferences();
+        let prefs = prefs.read().await;
+        (
+            prefs.editor.font_family.clone(),
+            prefs.editor.font_size,
+            editor_ui::WrapMode::from(&prefs.editor.word_wrap),
+            editor_ui::RenderWhitespace::from(prefs.editor.render_whitespace),
+            prefs.editor.rulers.clone(),
+            prefs.editor.cursor_settings(),
+            prefs.editor.highlight_debounce(),
+            prefs.editor.scroll_past_end,
+            prefs.editor.auto_save_interval(),
+            prefs.editor.auto_reload,
+        )
+    };
+    editor.handle_ui_event(event::UiEvent::FontChange { font, size }).await;
+    editor.apply_file_associations().await;
+    editor_lsp::init().await?;
+    editor.apply_trigger_characters().await;
 
-    // Open initial files
+    // Open initial files, or an untitled document if none were given
+    if args.files.is_empty() {
+        editor.core_mut().new_document("untitled-1")?;
+    }
+    // Large files are only opened once the user confirms in the UI
+    let mut large_files = Vec::new();
     for path in args.files {
-        if let Err(e) = editor.open_file(&path).await {
-            log::error!("Failed to open {}: {}", path.display(), e);
+        match editor.open_file(&path).await {
+            Err(e) if matches!(e.downcast_ref(), Some(editor_ui::OpenError::TooLarge { .. })) => {
+                large_files.push(path);
+            }
+            Err(e) => log::error!("Failed to open {}: {}", path.display(), e),
+            Ok(()) => {}
         }
     }
 
+    // Rank recently run commands first in the palette, and remember them
+    // when the window closes
+    let session = editor.state().session();
+    let paths = editor.state().paths().clone();
+    let actions = editor.core().actions().clone();
+    actions.set_recent(session.read().await.recent_commands.clone());
+
+    // Index workspace symbols in the background, resuming from the last run
+    editor_syntax::init()?;
+    let workspace = session.read().await.workspace.as_ref().map(|workspace| workspace.root.clone());
+    let indexing = workspace.map(|root| {
+        let index = editor_syntax::SymbolIndex::load(paths.symbol_index_file()).unwrap_or_else(|e| {
+            log::warn!("Failed to load symbol index: {}", e);
+            editor_syntax::SymbolIndex::new()
+        });
+        let index = std::sync::Arc::new(index);
+        (editor_syntax::spawn_index(index.clone(), root), index)
+    });
+
+    // Share bookmarks and recently opened files with the UI, which updates
+    // them as files are opened and closed, and the workspace "Go to File" lists
+    let shared_session = {
+        let session = session.read().await;
+        editor_ui::SharedSession::new(editor_ui::SessionData {
+            bookmarks: session.bookmarks.clone(),
+            workspace_root: session.workspace.as_ref().map(|workspace| workspace.root.clone()),
+            recent_files: session.recent_files.clone(),
+        })
+    };
+
     // Initialize UI
-    editor_ui::run(editor)?;
+    let fonts = editor.fonts().clone();
+    let theme = editor_ui::Theme::from(&editor.state().preferences().read().await.theme);
+    editor_ui::run(
+        editor.into_core(),
+        theme,
+        fonts,
+        wrap_mode,
+        render_whitespace,
+        rulers,
+        cursor,
+        highlight_debounce,
+        scroll_past_end,
+        auto_save,
+        auto_reload,
+        shared_session.clone(),
+        large_files,
+    )?;
+
+    let mut session = session.write().await;
+    session.recent_commands = actions.recent();
+    let shared = shared_session.get();
+    session.bookmarks = shared.bookmarks;
+    session.recent_files = shared.recent_files;
+    session.save_in(&paths)?;
+
+    if let Some((task, index)) = indexing {
+        task.cancel();
+        task.wait().await?;
+        index.save(paths.symbol_index_file())?;
+    }
 
     Ok(())
 }
 
+/// Loads the editor state as configured by the command line
+///
+/// Uses the configuration directory from `--config` if given, and switches
+/// to the `--theme` theme for this session.
+async fn init_state(args: &Args) -> Result<state::EditorState> {
+    if let Some(config_dir) = &args.config {
+        log::info!("Loading configuration from: {}", config_dir.display());
+    }
+    let state = state::EditorState::with_config_dir(args.config.as_deref())?;
+
+    if let Some(theme_name) = &args.theme {
+        log::info!("Setting theme: {}", theme_name);
+        state.apply_theme(theme_name).await?;
+    }
+    Ok(state)
+}
+
 /// Main editor struct
+///
+/// Opened documents live in the wrapped `editor_core::Editor`, which the UI
+/// takes over; this adds session tracking, events and input handling.
 pub struct Editor {
+    /// Documents and editing operations
+    core: editor_core::Editor,
     /// Editor state
     state: state::EditorState,
     /// Event dispatcher
     events: event::EventDispatcher,
     /// Input handler
     input: input::InputHandler,
+    /// Editor font shared with the UI
+    fonts: editor_ui::FontSettings,
+    /// Files already offered line ending normalization
+    offered_normalization: HashSet<PathBuf>,
 }
 
 impl Editor {
     /// Creates a new editor instance
     pub fn new(state: state::EditorState) -> Self {
         Self {
+            core: editor_core::Editor::new(),
             state,
             events: event::EventDispatcher::new(),
-            input: input::InputHandler::new(),
+            input: input::InputHandler::with_defaults(),
+            fonts: editor_ui::FontSettings::default(),
+            offered_normalization: HashSet::new(),
         }
     }
 
-    /// Opens a file
+    /// Opens a file and makes it the active document
+    ///
+    /// Files above the large file threshold are not opened; they fail with
+    /// `OpenError::TooLarge` so the UI can ask first. Failures are also
+    /// reported as a status message.
     pub async fn open_file(&mut self, path: &PathBuf) -> Result<()> {
         log::info!("Opening file: {}", path.display());
 
-        // Create new buffer
-        let mut buffer = buffer::TextBuffer::new();
+        if let Err(e) = editor_ui::open_file_async(&mut self.core, path, false).await {
+            self.events.dispatch(event::Event::Ui(event::UiEvent::StatusMessage {
+                text: e.to_string(),
+                duration: Some(10.0),
+                action: None,
+            })).await;
+            return Err(e.into());
+        }
+        let content = self.core.active_document()
+            .map(|doc| doc.text())
+            .unwrap_or_default();
+
+        // Warn about files indented with both tabs and spaces
+        if let editor_core::IndentReport::Mixed { lines } = editor_core::IndentReport::scan(&content) {
+            let shown: Vec<String> = lines.iter().take(5).map(|line| (line + 1).to_string()).collect();
+            let more = if lines.len() > shown.len() { ", ..." } else { "" };
+            self.events.dispatch(event::Event::Ui(event::UiEvent::StatusMessage {
+                text: format!(
+                    "{} mixes tabs and spaces for indentation (lines {}{})",
+                    path.display(),
+                    shown.join(", "),
+                    more
+                ),
+                duration: Some(5.0),
+                action: None,
+            })).await;
+        }
 
-        // Read file content
-        let content = tokio::fs::read_to_string(path).await?;
-        buffer.insert(0, &content).await?;
+        // Offer to normalize mixed line endings, once per file
+        if let Some(line_ending) = editor_core::LineEnding::detect_mixed(&content) {
+            if self.offered_normalization.insert(path.clone()) {
+                self.events.dispatch(event::Event::Ui(event::UiEvent::StatusMessage {
+                    text: format!("{} has mixed line endings", path.display()),
+                    duration: None,
+                    action: Some(event::StatusAction {
+                        title: format!("Normalize to {}", line_ending.label()),
+                        event: Box::new(event::UiEvent::NormalizeLineEndings {
+                            path: path.clone(),
+                            line_ending,
+                        }),
+                    }),
+                })).await;
+            }
+        }
 
-        // Create document
-        let mut session = self.state.session().write().await;
+        // Track the file in the session and restore its bookmarks and cursor
+        let session = self.state.session();
+        let mut session = session.write().await;
         session.add_file(path.clone());
-
-        // Update cursor position if file was previously open
-        if let Some(pos) = session.cursor_positions.get(path) {
-            buffer.set_marker("cursor", pos.line).await;
+        let bookmarks = session.bookmarks(path).to_vec();
+        if let Some(doc) = self.core.active_document_mut() {
+            let restored = doc.restore_bookmarks(&bookmarks);
+            if restored < bookmarks.len() {
+                log::info!("Dropped {} bookmarks past the end of {}", bookmarks.len() - restored, path.display());
+            }
+            if let Some(position) = session.cursor_positions.get(path) {
+                doc.set_selections(vec![position.to_selection(&content)]);
+            }
         }
 
         Ok(())
     }
 
+    /// Toggles a bookmark on a line of the active document
+    ///
+    /// Returns true if a bookmark was added.
+    pub async fn toggle_bookmark(&mut self, line: usize) -> Result<bool> {
+        let doc = self.core.active_document_mut()
+            .ok_or_else(|| anyhow::anyhow!("No document is open"))?;
+        let added = doc.toggle_bookmark(line, None)?;
+        self.store_bookmarks().await;
+        Ok(added)
+    }
+
+    /// Returns the start of the next bookmarked line after `offset` in the
+    /// active document
+    pub fn next_bookmark(&self, offset: usize) -> Option<usize> {
+        self.core.active_document()?.next_bookmark(offset)
+    }
+
+    /// Returns the start of the previous bookmarked line before `offset` in
+    /// the active document
+    pub fn prev_bookmark(&self, offset: usize) -> Option<usize> {
+        self.core.active_document()?.prev_bookmark(offset)
+    }
+
+    /// Records the bookmarks of the open files in the session
+    pub async fn store_bookmarks(&self) {
+        let session = self.state.session();
+        let mut session = session.write().await;
+        for doc in self.core.persistent_documents() {
+            if let Some(path) = doc.path() {
+                session.set_bookmarks(path.to_path_buf(), doc.bookmarks());
+            }
+        }
+    }
+
     /// Saves the current file
     pub async fn save_file(&mut self) -> Result<()> {
-        let session = self.state.session().read().await;
-        if let Some(path) = &session.active_file {
+        let active_file = self.state.session().read().await.active_file.clone();
+        if let Some(path) = &active_file {
             log::info!("Saving file: {}", path.display());
-            
-            // Get buffer content
-            // TODO: Get active buffer content
-            
-            // Save file
-            // TODO: Save file content
-            
+
+            let doc = self.core.documents_mut()
+                .find(|doc| doc.path() == Some(path.as_path()))
+                .ok_or_else(|| anyhow::anyhow!("{} is not open", path.display()))?;
+            doc.save()?;
+
             // Update state
-            self.events.dispatch(event::EditorEvent::Document(
-                event::DocumentEvent::Save {
+            self.events.dispatch(event::Event::Document(
+                event::DocumentEvent::Saved {
                     path: path.clone(),
                 }
             )).await;
@@ -143,17 +329,421 @@ impl Editor {
         Ok(())
     }
 
-    /// Handles input events
+    /// Handles input events, invoking the action bound to the key sequence
     pub async fn handle_input(&mut self, event: input::KeyEvent) -> Result<()> {
-        if let Some(command) = self.input.handle_key(event) {
-            // Execute command
-            command.execute().await?;
+        if let Some(id) = self.input.handle_key(event) {
+            self.core.actions().invoke(&id, serde_json::Value::Null)?;
+        }
+        Ok(())
+    }
+
+    /// Handles UI events
+    ///
+    /// Font changes are stored in the preferences and applied by the UI on
+    /// its next frame.
+    pub async fn handle_ui_event(&mut self, event: event::UiEvent) {
+        if let event::UiEvent::FontChange { font, size } = &event {
+            log::info!("Changing editor font: {} {}pt", font, size);
+            {
+                let prefs = self.state.preferences();
+                let mut prefs = prefs.write().await;
+                prefs.editor.font_family = font.clone();
+                prefs.editor.font_size = *size;
+            }
+            self.fonts.set(editor_ui::EditorFont::new(font.clone(), *size));
+        }
+        if let event::UiEvent::NormalizeLineEndings { path, line_ending } = &event {
+            if let Err(e) = self.normalize_line_endings(path, *line_ending) {
+                log::warn!("Failed to normalize line endings of {}: {}", path.display(), e);
+            }
+        }
+        if let event::UiEvent::ResolveReloadConflict { path, reload: true } = &event {
+            if let Err(e) = self.core.reload_file(path) {
+                log::warn!("Failed to reload {}: {}", path.display(), e);
+            }
         }
+        self.events.dispatch(event::Event::Ui(event)).await;
+    }
+
+    /// Converts the line endings of an open file, making it the active document
+    fn normalize_line_endings(&mut self, path: &Path, line_ending: editor_core::LineEnding) -> Result<()> {
+        let name = self.core.documents()
+            .find(|doc| doc.path() == Some(path))
+            .map(|doc| doc.name().to_string())
+            .ok_or_else(|| anyhow::anyhow!("{} is not open", path.display()))?;
+        self.core.set_active_document(&name)?;
+        self.core.change_line_ending(line_ending)?;
         Ok(())
     }
 
+    /// Applies the file associations from the preferences, re-detecting the
+    /// language of open documents
+    pub async fn apply_file_associations(&mut self) {
+        let associations = self.state.preferences().read().await.file_associations.clone();
+        self.core.set_file_associations(associations);
+    }
+
+    /// Applies the completion trigger characters configured per language,
+    /// replacing those of the language servers
+    pub async fn apply_trigger_characters(&self) {
+        let config = self.state.config();
+        let config = &config.read().await.language;
+        let mut languages = editor_lsp::registered_languages();
+        languages.extend(config.overrides.keys().cloned());
+        for language in languages {
+            if let Some(characters) = config.trigger_characters(&language) {
+                editor_lsp::set_trigger_characters(&language, characters);
+            }
+        }
+    }
+
+    /// Applies the auto-reload preference to open files changed on disk
+    ///
+    /// Files without unsaved edits are reloaded unless the policy is `Never`.
+    /// Files with unsaved edits are reloaded with `Always`; with `IfClean` a
+    /// `UiEvent::ReloadConflict` lets the user choose, answered with
+    /// `UiEvent::ResolveReloadConflict`. Returns the paths of reloaded files.
+    pub async fn handle_external_changes(&mut self) -> Vec<PathBuf> {
+        let policy = self.state.preferences().read().await.editor.auto_reload;
+        let changes = self.core.reload_changed_files(policy);
+        for path in changes.conflicts {
+            self.events.dispatch(event::Event::Ui(event::UiEvent::ReloadConflict { path })).await;
+        }
+        changes.reloaded
+    }
+
+    /// Returns the event dispatcher
+    pub fn events(&self) -> &event::EventDispatcher {
+        &self.events
+    }
+
     /// Returns the editor state
     pub fn state(&self) -> &state::EditorState {
         &self.state
     }
+
+    /// Returns the editor font shared with the UI
+    pub fn fonts(&self) -> &editor_ui::FontSettings {
+        &self.fonts
+    }
+
+    /// Returns the core editor holding the open documents
+    pub fn core(&self) -> &editor_core::Editor {
+        &self.core
+    }
+
+    /// Returns the core editor mutably, e.g. to edit the active document
+    pub fn core_mut(&mut self) -> &mut editor_core::Editor {
+        &mut self.core
+    }
+
+    /// Consumes the editor, returning the core editor for the UI
+    pub fn into_core(self) -> editor_core::Editor {
+        self.core
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parking_lot::Mutex;
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    /// Collects the text of status messages
+    struct StatusMessages(Arc<Mutex<Vec<String>>>);
+
+    #[async_trait::async_trait]
+    impl event::EventHandler for StatusMessages {
+        async fn handle(&self, event: event::Event) {
+            if let event::Event::Ui(event::UiEvent::StatusMessage { text, .. }) = event {
+                self.0.lock().push(text);
+            }
+        }
+    }
+
+    /// Collects the paths of reload conflicts
+    struct ReloadConflicts(Arc<Mutex<Vec<PathBuf>>>);
+
+    #[async_trait::async_trait]
+    impl event::EventHandler for ReloadConflicts {
+        async fn handle(&self, event: event::Event) {
+            if let event::Event::Ui(event::UiEvent::ReloadConflict { path }) = event {
+                self.0.lock().push(path);
+            }
+        }
+    }
+
+    /// Opens a file, edits it if `dirty`, and has another program change it
+    ///
+    /// Returns the text after applying `policy`, and the reported conflicts.
+    async fn external_change(policy: editor_core::ReloadPolicy, dirty: bool) -> (String, Vec<PathBuf>) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\n").unwrap();
+
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.state().preferences().write().await.editor.auto_reload = policy;
+        let conflicts = Arc::new(Mutex::new(Vec::new()));
+        editor.events().register(Box::new(ReloadConflicts(conflicts.clone()))).await;
+        editor.open_file(&path).await.unwrap();
+        if dirty {
+            editor.core_mut().active_document_mut().unwrap().insert(0, "edit\n").unwrap();
+        }
+
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        editor.handle_external_changes().await;
+        // Changes are handled once
+        assert!(editor.handle_external_changes().await.is_empty());
+
+        let text = editor.core().active_document().unwrap().text();
+        let conflicts = conflicts.lock().clone();
+        (text, conflicts)
+    }
+
+    #[tokio::test]
+    async fn test_reload_policies() {
+        use editor_core::ReloadPolicy::{Always, IfClean, Never};
+        let reloaded = "one\ntwo\n".to_string();
+        let kept = "edit\none\n".to_string();
+
+        assert_eq!(external_change(Never, false).await, ("one\n".to_string(), vec![]));
+        assert_eq!(external_change(Never, true).await, (kept.clone(), vec![]));
+        assert_eq!(external_change(IfClean, false).await, (reloaded.clone(), vec![]));
+        assert_eq!(external_change(Always, false).await, (reloaded.clone(), vec![]));
+        assert_eq!(external_change(Always, true).await, (reloaded, vec![]));
+
+        // Dirty files are left for the user to decide
+        let (text, conflicts) = external_change(IfClean, true).await;
+        assert_eq!(text, kept);
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0].ends_with("notes.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_reload_conflict() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\n").unwrap();
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.open_file(&path).await.unwrap();
+        editor.core_mut().active_document_mut().unwrap().insert(0, "edit\n").unwrap();
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        assert!(editor.handle_external_changes().await.is_empty());
+
+        editor.handle_ui_event(event::UiEvent::ResolveReloadConflict { path: path.clone(), reload: false }).await;
+        assert_eq!(editor.core().active_document().unwrap().text(), "edit\none\n");
+        editor.handle_ui_event(event::UiEvent::ResolveReloadConflict { path, reload: true }).await;
+        let doc = editor.core().active_document().unwrap();
+        assert_eq!(doc.text(), "one\ntwo\n");
+        assert!(!doc.is_dirty());
+    }
+
+    #[tokio::test]
+    async fn test_open_file_keeps_document() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "hello").unwrap();
+
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.open_file(&path).await.unwrap();
+        assert!(editor.state().session().read().await.open_files.contains(&path));
+
+        // The opened document is stored and can be edited and saved
+        let doc = editor.core_mut().active_document_mut().unwrap();
+        assert_eq!(doc.path(), Some(path.as_path()));
+        doc.insert(5, " world").unwrap();
+        assert_eq!(editor.core().active_document().unwrap().text(), "hello world");
+
+        editor.save_file().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_file_associations_from_preferences() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("app.conf");
+        std::fs::write(&path, "[server]\n").unwrap();
+
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.state().preferences().write().await.file_associations.insert("*.conf", "ini");
+        editor.apply_file_associations().await;
+        editor.open_file(&path).await.unwrap();
+        assert_eq!(editor.core().active_document().unwrap().language(), Some("ini"));
+    }
+
+    #[tokio::test]
+    async fn test_trigger_characters_from_config() {
+        let editor = Editor::new(state::EditorState::with_defaults());
+        {
+            let config = editor.state().config();
+            let mut config = config.write().await;
+            let mut settings = config.language.default.clone();
+            settings.trigger_characters = Some(vec!["@".to_string()]);
+            config.language.overrides.insert("cfg-test".to_string(), settings);
+        }
+        editor.apply_trigger_characters().await;
+        assert!(editor_lsp::should_trigger_completion("cfg-test", "user@"));
+        assert!(!editor_lsp::should_trigger_completion("cfg-test", "user."));
+    }
+
+    #[tokio::test]
+    async fn test_init_state_from_args() {
+        let dir = TempDir::new().unwrap();
+        let config = dir.path().to_str().unwrap();
+
+        let args = Args::try_parse_from(["rust-editor", "--config", config, "--theme", "Light+"]).unwrap();
+        let state = init_state(&args).await.unwrap();
+        assert_eq!(state.paths().config_dir(), dir.path());
+        assert_eq!(state.preferences().read().await.theme.name, "Light+");
+        assert_eq!(editor_ui::Theme::from(&state.preferences().read().await.theme), editor_ui::Theme::Light);
+
+        let args = Args::try_parse_from(["rust-editor", "--config", config, "--theme", "Missing"]).unwrap();
+        let err = init_state(&args).await.err().unwrap();
+        assert!(err.to_string().contains("Unknown theme 'Missing'"));
+    }
+
+    #[tokio::test]
+    async fn test_bookmarks_restored_from_session() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let config = dir.path().join("config");
+
+        let mut editor = Editor::new(state::EditorState::with_config_dir(Some(&config)).unwrap());
+        editor.open_file(&path).await.unwrap();
+        assert!(editor.toggle_bookmark(1).await.unwrap());
+        assert!(editor.toggle_bookmark(3).await.unwrap());
+        editor.state().save().await.unwrap();
+
+        // The file lost lines outside the editor, so the last bookmark is dropped
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let mut editor = Editor::new(state::EditorState::with_config_dir(Some(&config)).unwrap());
+        editor.open_file(&path).await.unwrap();
+        let doc = editor.core().active_document().unwrap();
+        assert_eq!(doc.bookmarks(), vec![editor_core::Bookmark::new(1)]);
+        assert_eq!(editor.next_bookmark(0), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_cursor_restored_from_session() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwö three\n").unwrap();
+
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.state().session().write().await.cursor_positions.insert(
+            path.clone(),
+            state::CursorPosition { line: 1, column: 4, selection_start: Some((1, 2)), selection_end: Some((1, 4)) },
+        );
+        editor.open_file(&path).await.unwrap();
+        let doc = editor.core().active_document().unwrap();
+        assert_eq!(doc.primary_selection(), editor_core::Selection::new(6, 9));
+
+        // Positions past the end of a line stay on it
+        editor.state().session().write().await.cursor_positions.insert(
+            path.clone(),
+            state::CursorPosition { line: 0, column: 80, selection_start: None, selection_end: None },
+        );
+        editor.open_file(&path).await.unwrap();
+        let doc = editor.core().active_document().unwrap();
+        assert_eq!(doc.primary_selection(), editor_core::Selection::cursor(3));
+    }
+
+    #[tokio::test]
+    async fn test_large_file_needs_confirmation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("big.log");
+        std::fs::write(&path, "x".repeat(64)).unwrap();
+
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        editor.core_mut().set_large_file_threshold(16);
+        let err = editor.open_file(&path).await.unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(editor_ui::OpenError::TooLarge { size: 64, .. })));
+        assert!(editor.core().active_document().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scratch_not_in_session() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("notes.txt");
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        let config = dir.path().join("config");
+
+        let mut editor = Editor::new(state::EditorState::with_config_dir(Some(&config)).unwrap());
+        editor.open_file(&path).await.unwrap();
+        let name = editor.core().active_document().unwrap().name().to_string();
+        let scratch = editor.core_mut().duplicate_to_scratch(&name).unwrap();
+        editor.toggle_bookmark(1).await.unwrap();
+        editor.state().save().await.unwrap();
+
+        // Only the file is restored; the scratch copy and its bookmark are
+        // not kept
+        let state = state::EditorState::with_config_dir(Some(&config)).unwrap();
+        let open_files = state.session().read().await.open_files.clone();
+        assert_eq!(open_files, vec![path.clone()]);
+        assert!(state.session().read().await.bookmarks(&path).is_empty());
+
+        let mut editor = Editor::new(state);
+        for file in &open_files {
+            editor.open_file(file).await.unwrap();
+        }
+        assert_eq!(editor.core().document_names(), vec![name]);
+        assert!(!editor.core().has_document(&scratch));
+    }
+
+    #[tokio::test]
+    async fn test_font_change_event() {
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        let fonts = editor.fonts().clone();
+        let version = fonts.version();
+
+        editor.handle_ui_event(event::UiEvent::FontChange {
+            font: "Fira Code".to_string(),
+            size: 18.0,
+        }).await;
+
+        // The UI sees the new font, and it is kept in the preferences
+        assert!(fonts.version() > version);
+        assert_eq!(fonts.get(), editor_ui::EditorFont::new("Fira Code", 18.0));
+        let prefs = editor.state().preferences();
+        let prefs = prefs.read().await;
+        assert_eq!(prefs.editor.font_family, "Fira Code");
+        assert_eq!(prefs.editor.font_size, 18.0);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_line_endings_offered() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("mixed.txt");
+        std::fs::write(&path, "a\r\nb\nc\n").unwrap();
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        editor.events().register(Box::new(StatusMessages(messages.clone()))).await;
+
+        // Offered once, however often the file is opened
+        editor.open_file(&path).await.unwrap();
+        editor.open_file(&path).await.unwrap();
+        assert_eq!(*messages.lock(), vec![format!("{} has mixed line endings", path.display())]);
+
+        editor.handle_ui_event(event::UiEvent::NormalizeLineEndings {
+            path: path.clone(),
+            line_ending: editor_core::LineEnding::Unix,
+        }).await;
+        assert_eq!(editor.core().active_document().unwrap().text(), "a\nb\nc\n");
+    }
+
+    #[tokio::test]
+    async fn test_open_missing_file() {
+        let dir = TempDir::new().unwrap();
+        let mut editor = Editor::new(state::EditorState::with_defaults());
+        let messages = Arc::new(Mutex::new(Vec::new()));
+        editor.events().register(Box::new(StatusMessages(messages.clone()))).await;
+
+        let missing = dir.path().join("missing.txt");
+        assert!(editor.open_file(&missing).await.is_err());
+        assert_eq!(*messages.lock(), vec![format!("{} does not exist", missing.display())]);
+        assert!(editor.core().active_document().is_none());
+        assert!(editor.state().session().read().await.open_files.is_empty());
+    }
 }
@@ -1,8 +1,8 @@
 //! Editor configuration
 
 use serde::{Serialize, Deserialize};
-use std::path::PathBuf;
 use anyhow::Result;
+use super::StatePaths;
 
 /// Editor configuration
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,25 +67,41 @@ pub struct LanguageSettings {
     pub formatter: Option<String>,
     /// LSP server command
     pub lsp_server: Option<String>,
+    /// Completion trigger characters override
+    #[serde(default)]
+    pub trigger_characters: Option<Vec<String>>,
+}
+
+impl LanguageConfig {
+    /// Returns the completion trigger characters for a language, if configured
+    pub fn trigger_characters(&self, language: &str) -> Option<&[String]> {
+        self.overrides
+            .get(language)
+            .and_then(|settings| settings.trigger_characters.as_deref())
+            .or(self.default.trigger_characters.as_deref())
+    }
 }
 
 /// Line ending style
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LineEnding {
-    /// Unix style (
-)
+    /// Unix style (`\n`)
     Unix,
-    /// Windows style (
-)
+    /// Windows style (`\r\n`)
     Windows,
-    /// Mac style ()
+    /// Mac style (`\r`)
     Mac,
 }
 
 impl Config {
-    /// Loads configuration from disk
+    /// Loads configuration from the default location
     pub fn load() -> Result<Self> {
-        let config_path = Self::config_path()?;
+        Self::load_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Loads configuration from the given state directories
+    pub fn load_in(paths: &StatePaths) -> Result<Self> {
+        let config_path = paths.config_file();
         if config_path.exists() {
             let contents = std::fs::read_to_string(config_path)?;
             Ok(toml::from_str(&contents)?)
@@ -94,9 +110,14 @@ impl Config {
         }
     }
 
-    /// Saves configuration to disk
+    /// Saves configuration to the default location
     pub fn save(&self) -> Result<()> {
-        let config_path = Self::config_path()?;
+        self.save_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Saves configuration to the given state directories
+    pub fn save_in(&self, paths: &StatePaths) -> Result<()> {
+        let config_path = paths.config_file();
         if let Some(parent) = config_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
@@ -104,15 +125,6 @@ impl Config {
         std::fs::write(config_path, contents)?;
         Ok(())
     }
-
-    /// Returns the configuration file path
-    fn config_path() -> Result<PathBuf> {
-        let mut path = dirs::config_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
-        path.push("rust-editor");
-        path.push("config.toml");
-        Ok(path)
-    }
 }
 
 impl Default for Config {
@@ -139,6 +151,7 @@ impl Default for Config {
                     use_spaces: None,
                     formatter: None,
                     lsp_server: None,
+                    trigger_characters: None,
                 },
                 overrides: std::collections::HashMap::new(),
             },
@@ -3,11 +3,14 @@
 mod config;
 mod session;
 mod preferences;
+mod paths;
 
 pub use config::{Config, EditorConfig};
-pub use session::{Session, SessionState};
-pub use preferences::{Preferences, Theme};
+pub use session::{CursorPosition, Session};
+pub use preferences::{PrefSection, Preferences, Theme};
+pub use paths::{StatePaths, CONFIG_DIR_ENV};
 
+use std::path::Path;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use anyhow::Result;
@@ -21,18 +24,52 @@ pub struct EditorState {
     session: Arc<RwLock<Session>>,
     /// User preferences
     preferences: Arc<RwLock<Preferences>>,
+    /// Where the state is loaded from and saved to
+    paths: StatePaths,
 }
 
 impl EditorState {
     /// Creates a new editor state
     pub fn new() -> Result<Self> {
+        Self::with_config_dir(None)
+    }
+
+    /// Creates a new editor state stored in `dir`, or in the directory
+    /// resolved by `StatePaths::resolve` when `None`
+    pub fn with_config_dir(dir: Option<&Path>) -> Result<Self> {
+        Self::load_in(StatePaths::resolve(dir)?)
+    }
+
+    /// Creates a new editor state stored in the given directories
+    pub fn load_in(paths: StatePaths) -> Result<Self> {
         Ok(Self {
-            config: Arc::new(RwLock::new(Config::load()?)),
-            session: Arc::new(RwLock::new(Session::new())),
-            preferences: Arc::new(RwLock::new(Preferences::load()?)),
+            config: Arc::new(RwLock::new(Config::load_in(&paths)?)),
+            session: Arc::new(RwLock::new(Session::load_in(&paths).unwrap_or_else(|e| {
+                log::warn!("Ignoring unreadable session: {}", e);
+                Session::new()
+            }))),
+            preferences: Arc::new(RwLock::new(Preferences::load_in(&paths)?)),
+            paths,
         })
     }
 
+    /// Creates a state with default configuration and preferences, without
+    /// reading them from disk
+    pub fn with_defaults() -> Self {
+        Self {
+            config: Arc::new(RwLock::new(Config::default())),
+            session: Arc::new(RwLock::new(Session::new())),
+            preferences: Arc::new(RwLock::new(Preferences::default())),
+            paths: StatePaths::resolve(None)
+                .unwrap_or_else(|_| StatePaths::in_dir(std::env::temp_dir().join("rust-editor"))),
+        }
+    }
+
+    /// Returns where the state is stored
+    pub fn paths(&self) -> &StatePaths {
+        &self.paths
+    }
+
     /// Returns the current configuration
     pub fn config(&self) -> Arc<RwLock<Config>> {
         self.config.clone()
@@ -50,9 +87,23 @@ impl EditorState {
 
     /// Saves the current state
     pub async fn save(&self) -> Result<()> {
-        self.config.read().await.save()?;
-        self.session.read().await.save()?;
-        self.preferences.read().await.save()?;
+        self.config.read().await.save_in(&self.paths)?;
+        self.session.read().await.save_in(&self.paths)?;
+        self.preferences.read().await.save_in(&self.paths)?;
+        Ok(())
+    }
+
+    /// Resets one section of the preferences to its default and saves them
+    pub async fn reset_preferences_section(&self, section: PrefSection) -> Result<()> {
+        let mut preferences = self.preferences.write().await;
+        preferences.reset_section(section);
+        preferences.save_in(&self.paths)
+    }
+
+    /// Switches to the named theme for this session, without saving it
+    pub async fn apply_theme(&self, name: &str) -> Result<()> {
+        let theme = Preferences::find_theme(name, &self.paths)?;
+        self.preferences.write().await.theme = theme;
         Ok(())
     }
 
@@ -64,3 +115,32 @@ impl EditorState {
         self.save().await
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn test_state_in_custom_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = EditorState::with_config_dir(Some(dir.path())).unwrap();
+        assert_eq!(state.paths().config_dir(), dir.path());
+
+        state.preferences().write().await.editor.auto_save = false;
+        state.config().write().await.editor.tab_size = 2;
+        state.session().write().await.add_file(PathBuf::from("/tmp/notes.txt"));
+        state.save().await.unwrap();
+
+        assert!(dir.path().join("preferences.toml").exists());
+        assert!(dir.path().join("config.toml").exists());
+        assert!(dir.path().join("session.json").exists());
+
+        let reloaded = EditorState::with_config_dir(Some(dir.path())).unwrap();
+        assert!(!reloaded.preferences().read().await.editor.auto_save);
+        assert_eq!(reloaded.config().read().await.editor.tab_size, 2);
+
+        let session = Session::load_in(reloaded.paths()).unwrap();
+        assert_eq!(session.active_file, Some(PathBuf::from("/tmp/notes.txt")));
+    }
+}
@@ -0,0 +1,118 @@
+//! Locations of the files holding the editor state
+
+use anyhow::Result;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Environment variable overriding the configuration directory
+pub const CONFIG_DIR_ENV: &str = "RUST_EDITOR_CONFIG_DIR";
+
+/// Name of the editor's directory inside the platform directories
+const APP_DIR: &str = "rust-editor";
+
+/// Directories the configuration, preferences and session are stored in
+///
+/// By default configuration lives in the platform config directory and the
+/// session in the platform cache directory. An override, from the CLI or
+/// `RUST_EDITOR_CONFIG_DIR`, holds all of them, which keeps portable installs
+/// self-contained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatePaths {
+    /// Directory of `config.toml` and `preferences.toml`
+    config_dir: PathBuf,
+    /// Directory of `session.json`
+    session_dir: PathBuf,
+}
+
+impl StatePaths {
+    /// Uses a single directory for all state files
+    pub fn in_dir(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        Self {
+            config_dir: dir.clone(),
+            session_dir: dir,
+        }
+    }
+
+    /// Resolves the directories, preferring an explicit directory, then
+    /// `RUST_EDITOR_CONFIG_DIR`, then the platform directories
+    pub fn resolve(explicit: Option<&Path>) -> Result<Self> {
+        Self::resolve_with(explicit, std::env::var_os(CONFIG_DIR_ENV))
+    }
+
+    /// Resolves the directories with the value of the environment override
+    /// given explicitly
+    fn resolve_with(explicit: Option<&Path>, env: Option<OsString>) -> Result<Self> {
+        if let Some(dir) = explicit {
+            return Ok(Self::in_dir(dir));
+        }
+        if let Some(dir) = env.filter(|dir| !dir.is_empty()) {
+            return Ok(Self::in_dir(dir));
+        }
+        Self::platform()
+    }
+
+    /// Returns the platform default directories
+    pub fn platform() -> Result<Self> {
+        let config_dir = dirs::config_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
+        let session_dir = dirs::cache_dir()
+            .ok_or_else(|| anyhow::anyhow!("Could not find cache directory"))?;
+        Ok(Self {
+            config_dir: config_dir.join(APP_DIR),
+            session_dir: session_dir.join(APP_DIR),
+        })
+    }
+
+    /// Returns the directory holding configuration and preferences
+    pub fn config_dir(&self) -> &Path {
+        &self.config_dir
+    }
+
+    /// Returns the configuration file path
+    pub fn config_file(&self) -> PathBuf {
+        self.config_dir.join("config.toml")
+    }
+
+    /// Returns the preferences file path
+    pub fn preferences_file(&self) -> PathBuf {
+        self.config_dir.join("preferences.toml")
+    }
+
+    /// Returns the directory of user themes
+    pub fn themes_dir(&self) -> PathBuf {
+        self.config_dir.join("themes")
+    }
+
+    /// Returns the session file path
+    pub fn session_file(&self) -> PathBuf {
+        self.session_dir.join("session.json")
+    }
+
+    /// Returns the workspace symbol index file path
+    pub fn symbol_index_file(&self) -> PathBuf {
+        self.session_dir.join("symbols.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_precedence() {
+        let cli = Path::new("/opt/editor/cli");
+        let env = Some(OsString::from("/opt/editor/env"));
+
+        let paths = StatePaths::resolve_with(Some(cli), env.clone()).unwrap();
+        assert_eq!(paths.preferences_file(), cli.join("preferences.toml"));
+        assert_eq!(paths.session_file(), cli.join("session.json"));
+
+        let paths = StatePaths::resolve_with(None, env).unwrap();
+        assert_eq!(paths.config_file(), Path::new("/opt/editor/env/config.toml"));
+
+        // An empty variable is treated as unset
+        let paths = StatePaths::resolve_with(None, Some(OsString::new()));
+        assert_eq!(paths.ok(), StatePaths::platform().ok());
+    }
+}
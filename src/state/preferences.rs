@@ -3,6 +3,8 @@
 use serde::{Serialize, Deserialize};
 use std::path::PathBuf;
 use anyhow::Result;
+use super::StatePaths;
+use editor_core::{FileAssociations, ReloadPolicy};
 
 /// User preferences
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +19,26 @@ pub struct Preferences {
     pub editor: EditorBehavior,
     /// Auto-completion settings
     pub completion: CompletionSettings,
+    /// File name and glob patterns mapped to language ids
+    #[serde(default)]
+    pub file_associations: FileAssociations,
+}
+
+/// A section of the preferences that can be reset on its own
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PrefSection {
+    /// Theme and colors
+    Theme,
+    /// Key bindings
+    KeyBindings,
+    /// Plugin settings
+    Plugins,
+    /// Editor behaviors
+    Editor,
+    /// Auto-completion settings
+    Completion,
+    /// File associations
+    FileAssociations,
 }
 
 /// Theme settings
@@ -132,12 +154,39 @@ pub struct EditorBehavior {
     pub ensure_final_newline: bool,
     /// Word wrap mode
     pub word_wrap: WrapMode,
+    /// Which whitespace is made visible
+    #[serde(default)]
+    pub render_whitespace: RenderWhitespace,
+    /// Columns vertical rulers are drawn at, e.g. 80 and 100
+    #[serde(default)]
+    pub rulers: Vec<u32>,
+    /// Shape of the cursor
+    #[serde(default)]
+    pub cursor_style: CursorStyle,
+    /// Whether the cursor blinks
+    #[serde(default = "default_cursor_blink")]
+    pub cursor_blink: bool,
+    /// Time the cursor stays shown, and then hidden, while blinking (ms)
+    #[serde(default = "default_cursor_blink_rate_ms")]
+    pub cursor_blink_rate_ms: u64,
     /// Scroll past end
     pub scroll_past_end: bool,
     /// Minimap enabled
     pub show_minimap: bool,
     /// Smart indent
     pub smart_indent: bool,
+    /// Quiet period after an edit before re-highlighting (ms)
+    #[serde(default = "default_highlight_debounce_ms")]
+    pub highlight_debounce_ms: u64,
+    /// Editor font family, "monospace" for the built-in font
+    #[serde(default = "default_font_family")]
+    pub font_family: String,
+    /// Editor font size in points
+    #[serde(default = "default_font_size")]
+    pub font_size: f32,
+    /// What happens to open files changed by other programs
+    #[serde(default)]
+    pub auto_reload: ReloadPolicy,
 }
 
 /// Word wrap mode
@@ -151,12 +200,126 @@ pub enum WrapMode {
     Column(u32),
 }
 
+/// Whitespace rendering mode
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RenderWhitespace {
+    /// Whitespace is not shown
+    #[default]
+    None,
+    /// All spaces, tabs and line endings are shown
+    All,
+    /// Whitespace in the selection is shown
+    Selection,
+    /// Whitespace at the end of lines is shown
+    Trailing,
+}
+
+impl From<RenderWhitespace> for editor_ui::RenderWhitespace {
+    fn from(mode: RenderWhitespace) -> Self {
+        match mode {
+            RenderWhitespace::None => editor_ui::RenderWhitespace::None,
+            RenderWhitespace::All => editor_ui::RenderWhitespace::All,
+            RenderWhitespace::Selection => editor_ui::RenderWhitespace::Selection,
+            RenderWhitespace::Trailing => editor_ui::RenderWhitespace::Trailing,
+        }
+    }
+}
+
+impl From<CursorStyle> for editor_ui::CursorStyle {
+    fn from(style: CursorStyle) -> Self {
+        match style {
+            CursorStyle::Line => editor_ui::CursorStyle::Line,
+            CursorStyle::Block => editor_ui::CursorStyle::Block,
+            CursorStyle::Underline => editor_ui::CursorStyle::Underline,
+        }
+    }
+}
+
+impl From<&Theme> for editor_ui::Theme {
+    fn from(theme: &Theme) -> Self {
+        if theme.is_dark {
+            editor_ui::Theme::Dark
+        } else {
+            editor_ui::Theme::Light
+        }
+    }
+}
+
+impl From<&WrapMode> for editor_ui::WrapMode {
+    fn from(mode: &WrapMode) -> Self {
+        match mode {
+            WrapMode::None => editor_ui::WrapMode::None,
+            WrapMode::View => editor_ui::WrapMode::View,
+            WrapMode::Column(column) => editor_ui::WrapMode::Column(*column),
+        }
+    }
+}
+
+/// Cursor style
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CursorStyle {
+    /// Thin vertical line
+    #[default]
+    Line,
+    /// Box covering the character at the cursor
+    Block,
+    /// Line under the character at the cursor
+    Underline,
+}
+
+/// Cursor blinking is on unless turned off
+fn default_cursor_blink() -> bool {
+    true
+}
+
+/// Default cursor blink rate (ms)
+fn default_cursor_blink_rate_ms() -> u64 {
+    editor_ui::DEFAULT_BLINK_RATE.as_millis() as u64
+}
+
+/// Default quiet period before re-highlighting (ms)
+fn default_highlight_debounce_ms() -> u64 {
+    editor_ui::DEFAULT_HIGHLIGHT_DEBOUNCE.as_millis() as u64
+}
+
+/// The built-in editor font
+fn default_font_family() -> String {
+    "monospace".to_string()
+}
+
+/// Default editor font size (points)
+fn default_font_size() -> f32 {
+    editor_ui::DEFAULT_FONT_SIZE
+}
+
+impl EditorBehavior {
+    /// Returns the cursor appearance for the editor view
+    pub fn cursor_settings(&self) -> editor_ui::CursorSettings {
+        editor_ui::CursorSettings {
+            style: self.cursor_style.into(),
+            blink: self.cursor_blink,
+            blink_rate: std::time::Duration::from_millis(self.cursor_blink_rate_ms),
+        }
+    }
+
+    /// Returns the quiet period after an edit before re-highlighting
+    pub fn highlight_debounce(&self) -> std::time::Duration {
+        std::time::Duration::from_millis(self.highlight_debounce_ms)
+    }
+
+    /// Returns the time allowed between an edit and its automatic save, or
+    /// `None` if auto-save is off
+    pub fn auto_save_interval(&self) -> Option<std::time::Duration> {
+        self.auto_save.then(|| std::time::Duration::from_secs(self.auto_save_interval.into()))
+    }
+}
+
 /// Auto-completion settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompletionSettings {
     /// Enable auto-completion
     pub enabled: bool,
-    /// Trigger on characters
+    /// Trigger on characters, unless the language config overrides them
     pub trigger_characters: Vec<char>,
     /// Minimum word length
     pub min_word_length: u32,
@@ -169,9 +332,14 @@ pub struct CompletionSettings {
 }
 
 impl Preferences {
-    /// Loads preferences from disk
+    /// Loads preferences from the default location
     pub fn load() -> Result<Self> {
-        let prefs_path = Self::preferences_path()?;
+        Self::load_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Loads preferences from the given state directories
+    pub fn load_in(paths: &StatePaths) -> Result<Self> {
+        let prefs_path = paths.preferences_file();
         if prefs_path.exists() {
             let contents = std::fs::read_to_string(prefs_path)?;
             Ok(toml::from_str(&contents)?)
@@ -180,9 +348,14 @@ impl Preferences {
         }
     }
 
-    /// Saves preferences to disk
+    /// Saves preferences to the default location
     pub fn save(&self) -> Result<()> {
-        let prefs_path = Self::preferences_path()?;
+        self.save_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Saves preferences to the given state directories
+    pub fn save_in(&self, paths: &StatePaths) -> Result<()> {
+        let prefs_path = paths.preferences_file();
         if let Some(parent) = prefs_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
@@ -191,13 +364,64 @@ impl Preferences {
         Ok(())
     }
 
-    /// Returns the preferences file path
-    fn preferences_path() -> Result<PathBuf> {
-        let mut path = dirs::config_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find config directory"))?;
-        path.push("rust-editor");
-        path.push("preferences.toml");
-        Ok(path)
+    /// Restores one section to its default, leaving the others intact
+    pub fn reset_section(&mut self, section: PrefSection) {
+        let defaults = Self::default();
+        match section {
+            PrefSection::Theme => self.theme = defaults.theme,
+            PrefSection::KeyBindings => self.keybindings = defaults.keybindings,
+            PrefSection::Plugins => self.plugins = defaults.plugins,
+            PrefSection::Editor => self.editor = defaults.editor,
+            PrefSection::Completion => self.completion = defaults.completion,
+            PrefSection::FileAssociations => self.file_associations = defaults.file_associations,
+        }
+    }
+
+    /// Returns the themes shipped with the editor
+    pub fn builtin_themes() -> Vec<Theme> {
+        vec![Self::dark_theme(), Self::light_theme()]
+    }
+
+    /// Finds a theme by name, case-insensitively
+    ///
+    /// Built-in themes are checked first, then the `.toml` files in the
+    /// themes directory, which match by theme name or file stem.
+    pub fn find_theme(name: &str, paths: &StatePaths) -> Result<Theme> {
+        let mut available: Vec<String> = Vec::new();
+        for theme in Self::builtin_themes() {
+            if theme.name.eq_ignore_ascii_case(name) {
+                return Ok(theme);
+            }
+            available.push(theme.name);
+        }
+
+        let themes_dir = paths.themes_dir();
+        if themes_dir.is_dir() {
+            let mut files: Vec<PathBuf> = std::fs::read_dir(&themes_dir)?
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+                .collect();
+            files.sort();
+
+            for path in files {
+                let contents = std::fs::read_to_string(&path)?;
+                let theme: Theme = toml::from_str(&contents)
+                    .map_err(|e| anyhow::anyhow!("Invalid theme file {}: {}", path.display(), e))?;
+                let stem_matches = path
+                    .file_stem()
+                    .is_some_and(|stem| stem.to_string_lossy().eq_ignore_ascii_case(name));
+                if stem_matches || theme.name.eq_ignore_ascii_case(name) {
+                    return Ok(theme);
+                }
+                available.push(theme.name);
+            }
+        }
+
+        Err(anyhow::anyhow!(
+            "Unknown theme '{}' (available: {})",
+            name,
+            available.join(", ")
+        ))
     }
 
     /// Returns the default dark theme
@@ -295,9 +519,18 @@ impl Default for Preferences {
                 trim_whitespace: true,
                 ensure_final_newline: true,
                 word_wrap: WrapMode::View,
+                render_whitespace: RenderWhitespace::None,
+                rulers: Vec::new(),
+                cursor_style: CursorStyle::Line,
+                cursor_blink: true,
+                cursor_blink_rate_ms: default_cursor_blink_rate_ms(),
                 scroll_past_end: true,
                 show_minimap: true,
                 smart_indent: true,
+                highlight_debounce_ms: default_highlight_debounce_ms(),
+                font_family: default_font_family(),
+                font_size: default_font_size(),
+                auto_reload: ReloadPolicy::IfClean,
             },
             completion: CompletionSettings {
                 enabled: true,
@@ -307,6 +540,7 @@ impl Default for Preferences {
                 parameter_hints: true,
                 show_documentation: true,
             },
+            file_associations: FileAssociations::new(),
         }
     }
 }
@@ -325,6 +559,35 @@ mod tests {
         assert_eq!(deserialized.theme.colors.background, prefs.theme.colors.background);
     }
 
+    #[test]
+    fn test_missing_editor_fields_use_defaults() {
+        let mut value = toml::Value::try_from(Preferences::default()).unwrap();
+        let editor = value["editor"].as_table_mut().unwrap();
+        editor.remove("highlight_debounce_ms");
+        editor.remove("font_family");
+        editor.remove("font_size");
+
+        let prefs: Preferences = value.try_into().unwrap();
+        assert_eq!(prefs.editor.highlight_debounce(), editor_ui::DEFAULT_HIGHLIGHT_DEBOUNCE);
+        assert_eq!(prefs.editor.font_family, "monospace");
+        assert_eq!(prefs.editor.font_size, editor_ui::DEFAULT_FONT_SIZE);
+    }
+
+    #[test]
+    fn test_file_associations_serialization() {
+        let mut prefs = Preferences::default();
+        prefs.file_associations.insert("Dockerfile", "dockerfile");
+        prefs.file_associations.insert("*.conf", "ini");
+
+        let serialized = toml::to_string_pretty(&prefs).unwrap();
+        let deserialized: Preferences = toml::from_str(&serialized).unwrap();
+        assert_eq!(deserialized.file_associations, prefs.file_associations);
+        assert_eq!(
+            deserialized.file_associations.language_for(std::path::Path::new("/srv/app.conf")),
+            Some("ini")
+        );
+    }
+
     #[test]
     fn test_theme_switching() {
         let mut prefs = Preferences::default();
@@ -333,4 +596,50 @@ mod tests {
         prefs.theme = Preferences::light_theme();
         assert!(!prefs.theme.is_dark);
     }
+
+    #[test]
+    fn test_find_theme() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = StatePaths::in_dir(dir.path());
+        assert_eq!(Preferences::find_theme("light+", &paths).unwrap().name, "Light+");
+
+        let mut theme = Preferences::light_theme();
+        theme.name = "Solarized".to_string();
+        std::fs::create_dir_all(paths.themes_dir()).unwrap();
+        std::fs::write(
+            paths.themes_dir().join("solarized.toml"),
+            toml::to_string_pretty(&theme).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Preferences::find_theme("Solarized", &paths).unwrap().name, "Solarized");
+
+        let err = Preferences::find_theme("Nope", &paths).unwrap_err();
+        assert!(err.to_string().contains("Unknown theme 'Nope'"));
+    }
+
+    #[test]
+    fn test_reset_section() {
+        let mut prefs = Preferences {
+            theme: Preferences::light_theme(),
+            ..Preferences::default()
+        };
+        prefs.keybindings.custom.insert("ctrl+shift+k".to_string(), "delete_line".to_string());
+        prefs.editor.auto_save = false;
+
+        prefs.reset_section(PrefSection::Theme);
+        assert!(prefs.theme.is_dark);
+        assert_eq!(
+            prefs.keybindings.custom.get("ctrl+shift+k").map(String::as_str),
+            Some("delete_line")
+        );
+        assert!(!prefs.editor.auto_save);
+
+        prefs.reset_section(PrefSection::KeyBindings);
+        assert!(prefs.keybindings.custom.is_empty());
+        assert!(!prefs.editor.auto_save);
+        assert_eq!(prefs.editor.auto_save_interval(), None);
+
+        prefs.reset_section(PrefSection::Editor);
+        assert_eq!(prefs.editor.auto_save_interval(), Some(std::time::Duration::from_secs(300)));
+    }
 }
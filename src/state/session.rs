@@ -3,6 +3,8 @@
 use serde::{Serialize, Deserialize};
 use std::path::PathBuf;
 use anyhow::Result;
+use editor_core::{Bookmark, LineIndex, Selection};
+use super::StatePaths;
 
 /// Editor session state
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -19,6 +21,15 @@ pub struct Session {
     pub scroll_positions: std::collections::HashMap<PathBuf, ScrollPosition>,
     /// Current workspace
     pub workspace: Option<WorkspaceState>,
+    /// Bookmarked lines of files
+    #[serde(default)]
+    pub bookmarks: std::collections::HashMap<PathBuf, Vec<Bookmark>>,
+    /// Ids of recently run commands, most recent first
+    #[serde(default)]
+    pub recent_commands: Vec<String>,
+    /// Recently opened files, most recent first
+    #[serde(default)]
+    pub recent_files: Vec<PathBuf>,
 }
 
 /// Window state
@@ -60,6 +71,32 @@ pub struct CursorPosition {
     pub selection_end: Option<(usize, usize)>,
 }
 
+impl CursorPosition {
+    /// Returns the selection this position describes in `text`
+    ///
+    /// Columns count characters. Lines and columns past the end of the text
+    /// or of their line are clamped to it.
+    pub fn to_selection(&self, text: &str) -> Selection {
+        let index = LineIndex::new(text);
+        let offset = |(line, column): (usize, usize)| {
+            let Some(range) = index.line_range(line) else {
+                return text.len();
+            };
+            let content = text[range.clone()].trim_end_matches(['\n', '\r']);
+            range.start + content.char_indices().nth(column).map_or(content.len(), |(offset, _)| offset)
+        };
+
+        let head = offset((self.line, self.column));
+        match (self.selection_start, self.selection_end) {
+            (Some(start), Some(end)) => {
+                let (start, end) = (offset(start), offset(end));
+                Selection::new(if start == head { end } else { start }, head)
+            }
+            _ => Selection::cursor(head),
+        }
+    }
+}
+
 /// Scroll position
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScrollPosition {
@@ -94,12 +131,20 @@ impl Session {
             cursor_positions: std::collections::HashMap::new(),
             scroll_positions: std::collections::HashMap::new(),
             workspace: None,
+            bookmarks: std::collections::HashMap::new(),
+            recent_commands: Vec::new(),
+            recent_files: Vec::new(),
         }
     }
 
-    /// Saves session state to disk
+    /// Saves session state to the default location
     pub fn save(&self) -> Result<()> {
-        let session_path = Self::session_path()?;
+        self.save_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Saves session state to the given state directories
+    pub fn save_in(&self, paths: &StatePaths) -> Result<()> {
+        let session_path = paths.session_file();
         if let Some(parent) = session_path.parent() {
             std::fs::create_dir_all(parent)?;
         }
@@ -108,9 +153,14 @@ impl Session {
         Ok(())
     }
 
-    /// Loads session state from disk
+    /// Loads session state from the default location
     pub fn load() -> Result<Self> {
-        let session_path = Self::session_path()?;
+        Self::load_in(&StatePaths::resolve(None)?)
+    }
+
+    /// Loads session state from the given state directories
+    pub fn load_in(paths: &StatePaths) -> Result<Self> {
+        let session_path = paths.session_file();
         if session_path.exists() {
             let contents = std::fs::read_to_string(session_path)?;
             Ok(serde_json::from_str(&contents)?)
@@ -119,15 +169,6 @@ impl Session {
         }
     }
 
-    /// Returns the session file path
-    fn session_path() -> Result<PathBuf> {
-        let mut path = dirs::cache_dir()
-            .ok_or_else(|| anyhow::anyhow!("Could not find cache directory"))?;
-        path.push("rust-editor");
-        path.push("session.json");
-        Ok(path)
-    }
-
     /// Adds a file to the session
     pub fn add_file(&mut self, path: PathBuf) {
         if !self.open_files.contains(&path) {
@@ -146,6 +187,20 @@ impl Session {
         }
     }
 
+    /// Stores the bookmarks of a file, forgetting the file if there are none
+    pub fn set_bookmarks(&mut self, path: PathBuf, bookmarks: Vec<Bookmark>) {
+        if bookmarks.is_empty() {
+            self.bookmarks.remove(&path);
+        } else {
+            self.bookmarks.insert(path, bookmarks);
+        }
+    }
+
+    /// Returns the bookmarks stored for a file
+    pub fn bookmarks(&self, path: &std::path::Path) -> &[Bookmark] {
+        self.bookmarks.get(path).map_or(&[], Vec::as_slice)
+    }
+
     /// Sets the workspace
     pub fn set_workspace(&mut self, root: PathBuf) {
         self.workspace = Some(WorkspaceState {
@@ -182,3 +237,31 @@ impl Default for WindowState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bookmarks_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let paths = StatePaths::in_dir(dir.path());
+        let file = PathBuf::from("/src/main.rs");
+
+        let mut session = Session::new();
+        session.set_bookmarks(file.clone(), vec![Bookmark::new(3), Bookmark::new(10).with_label("parser")]);
+        session.set_bookmarks(PathBuf::from("/src/empty.rs"), Vec::new());
+        session.save_in(&paths).unwrap();
+
+        let loaded = Session::load_in(&paths).unwrap();
+        assert_eq!(loaded.bookmarks(&file), session.bookmarks(&file));
+        assert_eq!(loaded.bookmarks.len(), 1);
+
+        // Sessions saved before bookmarks existed still load
+        let json = std::fs::read_to_string(paths.session_file()).unwrap();
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("bookmarks");
+        let old: Session = serde_json::from_value(value).unwrap();
+        assert!(old.bookmarks.is_empty());
+    }
+}
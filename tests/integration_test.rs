@@ -5,6 +5,7 @@ use editor_plugin::{Plugin, PluginManager};
 use editor_syntax::Highlighter;
 use editor_lsp::LspClient;
 use tokio;
+use tokio_util::sync::CancellationToken;
 
 mod common;
 
@@ -48,6 +49,7 @@ async fn test_plugin_integration() {
         "hello-world",
         "greet",
         serde_json::json!({"name": "Test"}),
+        &CancellationToken::new(),
     ).await.unwrap();
 
     assert_eq!(